@@ -0,0 +1,4 @@
+-- Secret scanning of writes, see `SecretScanConfig`
+ALTER TABLE CONFIG ADD COLUMN secret_scan_mode TEXT NOT NULL DEFAULT 'off';
+-- JSON encoded list of additional patterns
+ALTER TABLE CONFIG ADD COLUMN secret_scan_patterns TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,16 @@
+-- The config is stored as JSON under the `config` key of the storage view
+CREATE TABLE IF NOT EXISTS STORAGE_VIEW (
+    key TEXT PRIMARY KEY,
+    value BLOB NOT NULL
+);
+
+INSERT INTO STORAGE_VIEW (key, value)
+    SELECT 'config', CAST(json_object(
+        'max_versions', max_versions,
+        'secret_scan', json_object(
+            'mode', secret_scan_mode,
+            'patterns', json(secret_scan_patterns)
+        )
+    ) AS BLOB) FROM CONFIG;
+
+DROP TABLE CONFIG;
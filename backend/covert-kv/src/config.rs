@@ -15,12 +15,18 @@ pub async fn set_config(
     Extension(ctx): Extension<Arc<Context>>,
     Json(body): Json<SetConfigParams>,
 ) -> Result<Response, Error> {
+    let secret_scan = match body.secret_scan {
+        Some(secret_scan) => secret_scan,
+        None => ctx.repos.config.load().await?.secret_scan,
+    };
     let config = Configuration {
         max_versions: body.max_versions,
+        secret_scan,
     };
     ctx.repos.config.set(&config).await?;
     let resp = SetConfigResponse {
         max_versions: config.max_versions,
+        secret_scan: config.secret_scan,
     };
     Response::raw(resp).map_err(Into::into)
 }
@@ -31,6 +37,7 @@ pub async fn read_config(Extension(ctx): Extension<Arc<Context>>) -> Result<Resp
 
     let resp = ReadConfigResponse {
         max_versions: config.max_versions,
+        secret_scan: config.secret_scan,
     };
 
     Response::raw(resp).map_err(Into::into)
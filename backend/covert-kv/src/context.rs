@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use covert_storage::BackendStoragePool;
+use covert_storage::{BackendStoragePool, StorageView};
 
 use crate::store::{config::Repo as ConfigRepo, secrets::Repo as SecretsRepo};
 
@@ -16,10 +16,10 @@ pub struct Context {
 }
 
 impl Context {
-    pub fn new(storage: BackendStoragePool) -> Self {
+    pub fn new(storage: BackendStoragePool, view: StorageView) -> Self {
         Self {
             repos: Arc::new(Repos {
-                config: ConfigRepo::new(storage.clone()),
+                config: ConfigRepo::new(view),
                 secrets: SecretsRepo::new(storage),
             }),
         }
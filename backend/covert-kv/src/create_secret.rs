@@ -1,15 +1,19 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use chrono::Utc;
 
 use super::Context;
 use crate::{
-    domain::secret::Secret,
+    domain::{config::Configuration, secret::Secret},
     error::{Error, ErrorType},
+    scan::Scanner,
 };
 use covert_framework::extract::{Extension, Json, Path, Query};
 use covert_types::{
-    methods::kv::{CreateSecretParams, CreateSecretResponse, ReadSecretQuery, ReadSecretResponse},
+    methods::kv::{
+        CreateSecretParams, CreateSecretResponse, ReadSecretQuery, ReadSecretResponse,
+        SecretScanFinding, SecretScanMode,
+    },
     response::Response,
 };
 
@@ -19,6 +23,9 @@ pub async fn add_secret(
     Path(key): Path<String>,
     Json(body): Json<CreateSecretParams>,
 ) -> Result<Response, Error> {
+    let config = ctx.repos.config.load().await?;
+    let warnings = scan_secret(&config, &body.data)?;
+
     let version_metadata = ctx.repos.secrets.version_metadata(&key).await?;
 
     let value = serde_json::to_string(&body.data)?;
@@ -32,16 +39,45 @@ pub async fn add_secret(
     };
     ctx.repos.secrets.insert(&secret).await?;
 
-    let config = ctx.repos.config.load().await?;
+    written_secret_response(&ctx, &secret, config.max_versions, warnings).await
+}
+
+/// Scan the values of the secret according to the config of the mount. The
+/// findings are returned as warnings unless the mount blocks them.
+pub(crate) fn scan_secret(
+    config: &Configuration,
+    data: &HashMap<String, String>,
+) -> Result<Vec<SecretScanFinding>, Error> {
+    match config.secret_scan.mode {
+        SecretScanMode::Off => Ok(vec![]),
+        mode => {
+            let findings = Scanner::new(&config.secret_scan.patterns).scan(data);
+            if mode == SecretScanMode::Block && !findings.is_empty() {
+                return Err(ErrorType::SecretScanFindings(findings).into());
+            }
+            Ok(findings)
+        }
+    }
+}
+
+/// Prune the versions over the limit of the mount and describe the version
+/// that was just written.
+pub(crate) async fn written_secret_response(
+    ctx: &Context,
+    secret: &Secret,
+    max_versions: u32,
+    warnings: Vec<SecretScanFinding>,
+) -> Result<Response, Error> {
+    let key = &secret.key;
     ctx.repos
         .secrets
-        .prune_old_versions(&key, config.max_versions)
+        .prune_old_versions(key, max_versions)
         .await?;
 
     let version_metadata = ctx
         .repos
         .secrets
-        .version_metadata(&key)
+        .version_metadata(key)
         .await?
         .ok_or_else(|| {
             ErrorType::InternalError(anyhow::Error::msg(
@@ -56,6 +92,7 @@ pub async fn add_secret(
         destroyed: secret.destroyed,
         min_version: version_metadata.min_version,
         max_version: version_metadata.max_version,
+        warnings,
     };
     Response::raw(resp).map_err(Into::into)
 }
@@ -90,6 +127,7 @@ pub async fn read_secret(
             created_time: secret.created_time,
             deleted: secret.deleted,
             destroyed: secret.destroyed,
+            warnings: vec![],
         },
     };
 
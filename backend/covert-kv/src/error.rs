@@ -1,6 +1,10 @@
 use std::fmt::Display;
 
-use covert_types::error::{ApiError, StatusCode};
+use covert_storage::StorageViewError;
+use covert_types::{
+    error::{ApiError, ErrorCode, FieldError, StatusCode},
+    methods::kv::SecretScanFinding,
+};
 use thiserror::Error;
 use tracing_error::SpanTrace;
 
@@ -9,6 +13,8 @@ pub enum ErrorType {
     #[error("Internal error")]
     Storage(#[from] sqlx::Error),
     #[error("Internal error")]
+    StorageView(#[from] StorageViewError),
+    #[error("Internal error")]
     InternalError(anyhow::Error),
     #[error("Bad request")]
     BadRequest(#[from] serde_json::Error),
@@ -18,6 +24,12 @@ pub enum ErrorType {
     KeyVersionNotFound,
     #[error("Missing key versions to perform this operation on")]
     MissingKeyVersions,
+    #[error("The secret contains values that should not be stored")]
+    SecretScanFindings(Vec<SecretScanFinding>),
+    #[error("The latest version of the secret is deleted")]
+    LatestVersionDeleted,
+    #[error("The secret was written concurrently too many times to apply the patch")]
+    PatchConflict,
 }
 
 #[derive(Error, Debug)]
@@ -41,6 +53,15 @@ impl From<sqlx::Error> for Error {
     }
 }
 
+impl From<StorageViewError> for Error {
+    fn from(err: StorageViewError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Self {
@@ -61,17 +82,35 @@ impl From<ErrorType> for Error {
 
 impl From<Error> for ApiError {
     fn from(err: Error) -> Self {
-        let status_code = match err.variant {
-            ErrorType::Storage(_) | ErrorType::InternalError(_) => {
-                StatusCode::INTERNAL_SERVER_ERROR
+        let details = match &err.variant {
+            ErrorType::SecretScanFindings(findings) => findings
+                .iter()
+                .map(|finding| FieldError {
+                    field: finding.field.clone(),
+                    message: format!("Matches the `{}` detector", finding.detector),
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let (status_code, code) = match err.variant {
+            ErrorType::Storage(_) | ErrorType::StorageView(_) | ErrorType::InternalError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
             }
-            ErrorType::BadRequest(_) | ErrorType::MissingKeyVersions => StatusCode::BAD_REQUEST,
-            ErrorType::MetadataNotFound | ErrorType::KeyVersionNotFound => StatusCode::NOT_FOUND,
+            ErrorType::BadRequest(_)
+            | ErrorType::MissingKeyVersions
+            | ErrorType::SecretScanFindings(_) => (StatusCode::BAD_REQUEST, ErrorCode::BadRequest),
+            ErrorType::MetadataNotFound
+            | ErrorType::KeyVersionNotFound
+            | ErrorType::LatestVersionDeleted => (StatusCode::NOT_FOUND, ErrorCode::NotFound),
+            ErrorType::PatchConflict => (StatusCode::CONFLICT, ErrorCode::CasConflict),
         };
 
         ApiError {
             error: err.variant.into(),
+            code,
+            details,
             status_code,
+            retry_after: None,
             span_trace: Some(err.span_trace),
         }
     }
@@ -10,6 +10,9 @@ mod create_secret;
 mod domain;
 mod error;
 mod hard_delete_secret;
+mod list_secrets;
+mod patch_secret;
+mod scan;
 mod soft_delete_secret;
 mod store;
 
@@ -18,7 +21,7 @@ use std::sync::Arc;
 use context::Context;
 use covert_storage::{
     migrator::{migration_scripts, MigrationError},
-    BackendStoragePool,
+    BackendStoragePool, StorageView,
 };
 use rust_embed::RustEmbed;
 
@@ -26,6 +29,8 @@ use self::{
     config::{read_config, set_config},
     create_secret::{add_secret, read_secret},
     hard_delete_secret::hard_delete_secret,
+    list_secrets::list_secrets,
+    patch_secret::patch_secret,
     soft_delete_secret::{path_undelete_write, soft_delete_secret},
 };
 use covert_framework::{create, extract::Extension, read, Backend, Router};
@@ -41,37 +46,68 @@ struct Migrations;
 ///
 /// Returns an error if it fails to read the migration scripts.
 pub fn new_versioned_kv_backend(storage: BackendStoragePool) -> Result<Backend, MigrationError> {
-    let ctx = Context::new(storage);
+    let storage_view = StorageView::new(storage.clone());
+    let ctx = Context::new(storage, storage_view.clone());
 
     let router = Router::new()
         .route(
             "/config",
-            read(read_config).update(set_config).create(set_config),
+            read(read_config)
+                .update(set_config)
+                .create(set_config)
+                .help("Configure the max number of versions kept per secret."),
         )
         .route(
             "/data/*path",
-            read(read_secret).create(add_secret).update(add_secret),
+            read(read_secret)
+                .create(add_secret)
+                .update(add_secret)
+                .patch(patch_secret)
+                .help(
+                    "Read a version of the secret, write a new version or patch fields of the \
+                     latest version into a new version.",
+                ),
+        )
+        .route(
+            "/metadata",
+            read(list_secrets)
+                .list(list_secrets)
+                .help("List the keys of the mount."),
+        )
+        .route(
+            "/metadata/*path",
+            read(list_secrets)
+                .list(list_secrets)
+                .help("List the keys below the path."),
         )
         .route(
             "/delete/*path",
-            create(soft_delete_secret).update(soft_delete_secret),
+            create(soft_delete_secret)
+                .update(soft_delete_secret)
+                .help("Soft delete versions of the secret. They can be undeleted."),
         )
         .route(
             "/undelete/*path",
-            create(path_undelete_write).update(path_undelete_write),
+            create(path_undelete_write)
+                .update(path_undelete_write)
+                .help("Restore soft deleted versions of the secret."),
         )
         .route(
             "/destroy/*path",
-            create(hard_delete_secret).update(hard_delete_secret),
+            create(hard_delete_secret)
+                .update(hard_delete_secret)
+                .help("Permanently remove versions of the secret."),
         )
+        .leaseless()
         .layer(Extension(Arc::new(ctx)))
-        .build()
-        .into_service();
+        .layer(Extension(storage_view))
+        .build();
 
     let migrations = migration_scripts::<Migrations>()?;
 
     Ok(Backend {
-        handler: router,
+        paths: router.paths(),
+        handler: router.into_service(),
         category: BackendCategory::Logical,
         variant: BackendType::Kv,
         migrations,
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use covert_framework::extract::{Extension, Path};
+use covert_types::{methods::kv::ListSecretsResponse, response::Response};
+
+use crate::error::Error;
+
+use super::Context;
+
+/// List the keys directly below the prefix, like the entries of a directory.
+/// Keys with more segments are collapsed into their next segment followed by
+/// a `/`.
+#[tracing::instrument(skip_all)]
+pub async fn list_secrets(
+    Extension(ctx): Extension<Arc<Context>>,
+    prefix: Option<Path<String>>,
+) -> Result<Response, Error> {
+    let mut prefix = prefix.map(|Path(prefix)| prefix).unwrap_or_default();
+    if !prefix.is_empty() && !prefix.ends_with('/') {
+        prefix.push('/');
+    }
+
+    let mut keys: Vec<String> = ctx
+        .repos
+        .secrets
+        .list_keys(&prefix)
+        .await?
+        .iter()
+        .map(|key| {
+            let rest = &key[prefix.len()..];
+            match rest.find('/') {
+                Some(pos) => rest[..=pos].to_string(),
+                None => rest.to_string(),
+            }
+        })
+        .collect();
+    keys.dedup();
+
+    let resp = ListSecretsResponse { keys };
+    Response::raw(resp).map_err(Into::into)
+}
@@ -0,0 +1,78 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::Utc;
+
+use super::Context;
+use crate::{
+    create_secret::{scan_secret, written_secret_response},
+    domain::secret::Secret,
+    error::{Error, ErrorType},
+};
+use covert_framework::extract::{Extension, Json, Path};
+use covert_types::{methods::kv::PatchSecretParams, response::Response};
+
+/// Number of times the patch is applied to the latest version before giving
+/// up when other versions keep being written concurrently.
+const MAX_PATCH_ATTEMPTS: usize = 5;
+
+#[tracing::instrument(skip_all)]
+pub async fn patch_secret(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(key): Path<String>,
+    Json(body): Json<PatchSecretParams>,
+) -> Result<Response, Error> {
+    let config = ctx.repos.config.load().await?;
+
+    for _ in 0..MAX_PATCH_ATTEMPTS {
+        let version_metadata = ctx
+            .repos
+            .secrets
+            .version_metadata(&key)
+            .await?
+            .ok_or(ErrorType::MetadataNotFound)?;
+        let latest = ctx
+            .repos
+            .secrets
+            .get(&key, version_metadata.max_version)
+            .await?
+            .ok_or(ErrorType::MetadataNotFound)?;
+        if latest.deleted || latest.destroyed {
+            return Err(ErrorType::LatestVersionDeleted.into());
+        }
+
+        let mut data: HashMap<String, String> = latest
+            .value
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?
+            .unwrap_or_default();
+        for (field, value) in &body.data {
+            match value {
+                Some(value) => data.insert(field.clone(), value.clone()),
+                None => data.remove(field),
+            };
+        }
+        let warnings = scan_secret(&config, &data)?;
+
+        let secret = Secret {
+            key: key.clone(),
+            version: latest.version + 1,
+            value: Some(serde_json::to_string(&data)?),
+            created_time: Utc::now(),
+            deleted: false,
+            destroyed: false,
+        };
+        // Another version written since the latest version was read would be
+        // overwritten by the patch, so it is applied again on top of it.
+        if ctx
+            .repos
+            .secrets
+            .insert_next_version(&secret, latest.version)
+            .await?
+        {
+            return written_secret_response(&ctx, &secret, config.max_versions, warnings).await;
+        }
+    }
+
+    Err(ErrorType::PatchConflict.into())
+}
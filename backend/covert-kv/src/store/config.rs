@@ -1,27 +1,27 @@
-use covert_storage::BackendStoragePool;
+use covert_storage::StorageView;
 
-use crate::{domain::config::Configuration, error::Error};
+use crate::{
+    domain::config::Configuration,
+    error::{Error, ErrorType},
+};
 
-const CONFIGURATION_TABLE: &str = "CONFIG";
+/// Key of the config in the storage view of the mount.
+const CONFIG_KEY: &str = "config";
 
 #[derive(Debug)]
 pub struct Repo {
-    pool: BackendStoragePool,
+    view: StorageView,
 }
 
 impl Repo {
-    pub fn new(pool: BackendStoragePool) -> Self {
-        Self { pool }
+    pub fn new(view: StorageView) -> Self {
+        Self { view }
     }
 
     pub async fn load(&self) -> Result<Configuration, Error> {
-        let res = self
-            .pool
-            .query(&format!("SELECT * FROM {CONFIGURATION_TABLE}"))?
-            .fetch_optional()
-            .await?;
-        if let Some(config) = res {
-            Ok(config)
+        if let Some(config) = self.view.get(CONFIG_KEY).await? {
+            serde_json::from_slice(&config)
+                .map_err(|_| ErrorType::InternalError(anyhow::Error::msg("Invalid config")).into())
         } else {
             let config = Configuration::default();
             self.set(&config).await?;
@@ -30,22 +30,23 @@ impl Repo {
     }
 
     pub async fn set(&self, config: &Configuration) -> Result<(), Error> {
-        self.pool
-            .query(&format!(
-                "INSERT OR REPLACE INTO {CONFIGURATION_TABLE} (max_versions, lock) 
-                    VALUES ($1, 1)"
-            ))?
-            .bind(config.max_versions)
-            .execute()
-            .await
-            .map(|_| ())
-            .map_err(Into::into)
+        let config = serde_json::to_vec(config)?;
+        self.view.put(CONFIG_KEY, config).await.map_err(Into::into)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{domain::config::Configuration, store::secrets::tests::setup};
+    use std::sync::Arc;
+
+    use covert_storage::{
+        migrator::migration_scripts, BackendStoragePool, EncryptedPool, StorageView,
+    };
+    use covert_types::methods::kv::{SecretScanMode, SecretScanPattern};
+
+    use crate::{
+        context::Context, domain::config::Configuration, store::secrets::tests::setup, Migrations,
+    };
 
     #[sqlx::test]
     fn load_and_set_config() {
@@ -56,9 +57,59 @@ mod tests {
         assert_eq!(config, Configuration::default());
 
         config.max_versions += 1;
+        config.secret_scan.mode = SecretScanMode::Block;
+        config.secret_scan.patterns.push(SecretScanPattern {
+            name: "acme_key".to_string(),
+            prefix: "acme_".to_string(),
+        });
         repo.set(&config).await.unwrap();
 
         let loaded_config = repo.load().await.unwrap();
         assert_eq!(config, loaded_config);
     }
+
+    #[sqlx::test]
+    fn migrates_config_table_to_storage_view() {
+        let pool = Arc::new(EncryptedPool::new_tmp());
+        let storage = BackendStoragePool::new("foo_", pool);
+
+        // Write the config the way it was stored before the storage view
+        let mut migrations = migration_scripts::<Migrations>().unwrap();
+        let last = migrations.pop().unwrap();
+        for migration in migrations {
+            storage
+                .query(&migration.script)
+                .unwrap()
+                .execute()
+                .await
+                .unwrap();
+        }
+        storage
+            .query(
+                &r#"INSERT INTO CONFIG (max_versions, secret_scan_mode, secret_scan_patterns)
+                    VALUES (3, 'block', '[{"name":"acme_key","prefix":"acme_"}]')"#,
+            )
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        storage
+            .query(&last.script)
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+
+        let ctx = Context::new(storage.clone(), StorageView::new(storage));
+        let config = ctx.repos.config.load().await.unwrap();
+        assert_eq!(config.max_versions, 3);
+        assert_eq!(config.secret_scan.mode, SecretScanMode::Block);
+        assert_eq!(
+            config.secret_scan.patterns,
+            vec![SecretScanPattern {
+                name: "acme_key".to_string(),
+                prefix: "acme_".to_string(),
+            }]
+        );
+    }
 }
@@ -160,6 +160,34 @@ impl Repo {
             .map_err(Into::into)
     }
 
+    /// Insert the secret only if `previous_version` is still the latest
+    /// version of the key. Returns false if another version was written in
+    /// the meantime.
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_next_version(
+        &self,
+        secret: &Secret,
+        previous_version: u32,
+    ) -> Result<bool, Error> {
+        self.pool
+            .query(&format!(
+                "INSERT OR IGNORE INTO {SECRETS_TABLE} (key, version, value, created_time, deleted, destroyed)
+                    SELECT $1, $2, $3, $4, $5, $6
+                    WHERE (SELECT MAX(version) FROM {SECRETS_TABLE} WHERE key = $1) = $7"
+            ))?
+            .bind(&secret.key)
+            .bind(secret.version)
+            .bind(&secret.value)
+            .bind(secret.created_time)
+            .bind(secret.deleted)
+            .bind(secret.destroyed)
+            .bind(previous_version)
+            .execute()
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip_all)]
     pub async fn prune_old_versions(&self, key: &str, max_versions: u32) -> Result<(), Error> {
         self.pool
@@ -175,6 +203,22 @@ impl Repo {
             .map(|_| ())
             .map_err(Into::into)
     }
+
+    /// Keys starting with `prefix`, sorted.
+    #[tracing::instrument(skip_all)]
+    pub async fn list_keys(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.pool
+            .query(&format!(
+                "SELECT DISTINCT key FROM {SECRETS_TABLE} WHERE
+                    substr(key, 1, length($1)) = $1
+                ORDER BY key"
+            ))?
+            .bind(prefix)
+            .fetch_all::<(String,)>()
+            .await
+            .map(|keys| keys.into_iter().map(|(key,)| key).collect())
+            .map_err(Into::into)
+    }
 }
 
 #[cfg(test)]
@@ -182,7 +226,9 @@ pub mod tests {
     use std::{collections::HashMap, sync::Arc};
 
     use chrono::Utc;
-    use covert_storage::{migrator::migrate_backend, BackendStoragePool, EncryptedPool};
+    use covert_storage::{
+        migrator::migrate_backend, BackendStoragePool, EncryptedPool, StorageView,
+    };
 
     use crate::{context::Context, domain::secret::Secret, Migrations};
 
@@ -193,7 +239,7 @@ pub mod tests {
 
         migrate_backend::<Migrations>(&storage).await.unwrap();
 
-        Context::new(storage)
+        Context::new(storage.clone(), StorageView::new(storage))
     }
 
     #[sqlx::test]
@@ -11,10 +11,11 @@ pub async fn setup() -> Client {
     let (port_tx, port_rx) = oneshot::channel();
 
     let config = covert_system::Config {
-        port: 0,
+        port: Some(0),
         port_tx: Some(port_tx),
+        tls_disable: true,
         storage_path: ":memory:".into(),
-        replication: None,
+        ..Default::default()
     };
 
     tokio::spawn(async move {
@@ -36,6 +37,7 @@ pub async fn setup_unseal() -> Client {
         .initialize(&InitializeParams {
             shares: 1,
             threshold: 1,
+            stored_shares: None,
         })
         .await
         .unwrap()
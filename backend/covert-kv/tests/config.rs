@@ -1,14 +1,24 @@
-mod common;
-
 use std::collections::HashMap;
 
-use covert_sdk::kv::{CreateSecretParams, SetConfigParams};
+use covert_sdk::{
+    kv::{
+        CreateSecretParams, SecretScanConfig, SecretScanFinding, SecretScanMode, SecretScanPattern,
+        SetConfigParams,
+    },
+    mounts::BackendType,
+    Error, ErrorCode, ServerError,
+};
+use covert_system::testing::TestServer;
 
-use crate::common::{setup_unseal, MOUNT_PATH};
+const MOUNT_PATH: &str = "kv/";
 
 #[tokio::test]
 async fn max_versions() {
-    let sdk = setup_unseal().await;
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Kv)
+        .start()
+        .await;
+    let sdk = server.sdk();
 
     // Read default config
     let resp = sdk.kv.read_config(MOUNT_PATH).await.unwrap();
@@ -18,7 +28,13 @@ async fn max_versions() {
     let max_versions = 5;
     let resp = sdk
         .kv
-        .set_config(MOUNT_PATH, &SetConfigParams { max_versions })
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                max_versions,
+                secret_scan: None,
+            },
+        )
         .await
         .unwrap();
     assert_eq!(resp.max_versions, max_versions);
@@ -67,7 +83,119 @@ async fn max_versions() {
     // Try to read version 1
     let read_resp = sdk.kv.read(MOUNT_PATH, key, Some(1)).await;
     assert_eq!(
-        read_resp.unwrap_err(),
+        read_resp.unwrap_err().message(),
         "A key with that version was not found"
     );
 }
+
+#[tokio::test]
+async fn secret_scan() {
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Kv)
+        .start()
+        .await;
+    let sdk = server.sdk();
+
+    let key = "foo";
+    let data: HashMap<_, _> = [
+        (
+            "note".to_string(),
+            "my token is s.Zk1Vq8lWm0Tn3PzYb6Rx2AcD".to_string(),
+        ),
+        ("user".to_string(), "acme_admin".to_string()),
+    ]
+    .into_iter()
+    .collect();
+
+    // Off by default
+    let resp = sdk.kv.read_config(MOUNT_PATH).await.unwrap();
+    assert_eq!(resp.secret_scan.mode, SecretScanMode::Off);
+    let resp = sdk
+        .kv
+        .create(MOUNT_PATH, key, &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+    assert!(resp.warnings.is_empty());
+
+    // Warn
+    let secret_scan = SecretScanConfig {
+        mode: SecretScanMode::Warn,
+        patterns: vec![SecretScanPattern {
+            name: "acme".to_string(),
+            prefix: "acme_".to_string(),
+        }],
+    };
+    let resp = sdk
+        .kv
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                max_versions: 10,
+                secret_scan: Some(secret_scan.clone()),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.secret_scan, secret_scan);
+    let resp = sdk
+        .kv
+        .create(MOUNT_PATH, key, &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+    assert_eq!(resp.version, 2);
+    assert_eq!(
+        resp.warnings,
+        vec![
+            SecretScanFinding {
+                field: "note".to_string(),
+                detector: "covert_token".to_string()
+            },
+            SecretScanFinding {
+                field: "user".to_string(),
+                detector: "acme".to_string()
+            }
+        ]
+    );
+
+    // Updating only the max versions keeps the scan config
+    let resp = sdk
+        .kv
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                max_versions: 5,
+                secret_scan: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.secret_scan, secret_scan);
+
+    // Block
+    sdk.kv
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                max_versions: 5,
+                secret_scan: Some(SecretScanConfig {
+                    mode: SecretScanMode::Block,
+                    patterns: vec![],
+                }),
+            },
+        )
+        .await
+        .unwrap();
+    let err = sdk
+        .kv
+        .create(MOUNT_PATH, key, &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    let Error::Api(ServerError { details, .. }) = err else {
+        panic!("expected an API error");
+    };
+    assert_eq!(details.len(), 1);
+    assert_eq!(details[0].field, "note");
+    let read_resp = sdk.kv.read(MOUNT_PATH, key, None).await.unwrap();
+    assert_eq!(read_resp.metadata.version, 2);
+}
@@ -3,7 +3,7 @@ mod common;
 use std::collections::HashMap;
 
 use chrono::{Duration, Utc};
-use covert_sdk::kv::CreateSecretParams;
+use covert_sdk::{kv::CreateSecretParams, ErrorCode};
 
 use crate::common::{setup_unseal, MOUNT_PATH};
 
@@ -54,16 +54,14 @@ async fn create_and_read() {
     assert_eq!(read_resp.metadata.version, 1);
 
     // Read version that does not exist
-    let read_resp = sdk.kv.read(MOUNT_PATH, key, Some(2)).await;
-    assert_eq!(
-        read_resp.unwrap_err(),
-        "A key with that version was not found"
-    );
+    let err = sdk.kv.read(MOUNT_PATH, key, Some(2)).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+    assert_eq!(err.message(), "A key with that version was not found");
 
     // Read key that does not exist
     let read_resp = sdk.kv.read(MOUNT_PATH, "badkey", None).await;
     assert_eq!(
-        read_resp.unwrap_err(),
+        read_resp.unwrap_err().message(),
         "A key with that version was not found"
     );
 
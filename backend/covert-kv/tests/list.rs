@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use covert_sdk::{kv::CreateSecretParams, mounts::BackendType};
+use covert_system::testing::TestServer;
+
+const MOUNT_PATH: &str = "kv/";
+
+#[tokio::test]
+async fn list_keys() {
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Kv)
+        .start()
+        .await;
+    let sdk = server.sdk();
+
+    let data: HashMap<_, _> = [("foo".to_string(), "bar".to_string())]
+        .into_iter()
+        .collect();
+    for key in ["foo", "app/db", "app/api/token", "apps"] {
+        sdk.kv
+            .create(MOUNT_PATH, key, &CreateSecretParams { data: data.clone() })
+            .await
+            .unwrap();
+    }
+
+    let resp = sdk.kv.list(MOUNT_PATH, "").await.unwrap();
+    assert_eq!(resp.keys, ["app/", "apps", "foo"]);
+
+    let resp = sdk.kv.list(MOUNT_PATH, "app/").await.unwrap();
+    assert_eq!(resp.keys, ["api/", "db"]);
+
+    // The trailing slash is optional
+    let resp = sdk.kv.list(MOUNT_PATH, "app/api").await.unwrap();
+    assert_eq!(resp.keys, ["token"]);
+
+    let resp = sdk.kv.list(MOUNT_PATH, "missing/").await.unwrap();
+    assert!(resp.keys.is_empty());
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::collections::HashMap;
+
+use covert_sdk::{
+    kv::{CreateSecretParams, PatchSecretParams, SoftDeleteSecretParams},
+    ErrorCode,
+};
+
+use crate::common::{setup_unseal, MOUNT_PATH};
+
+fn patch(fields: &[(&str, Option<&str>)]) -> PatchSecretParams {
+    PatchSecretParams {
+        data: fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.map(ToString::to_string)))
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn patch_fields() {
+    let sdk = setup_unseal().await;
+    let key = "foo";
+
+    // Cannot patch a secret that does not exist
+    let err = sdk
+        .kv
+        .patch(MOUNT_PATH, key, &patch(&[("foo", Some("1"))]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    let data: HashMap<_, _> = [
+        ("foo".to_string(), "1".to_string()),
+        ("bar".to_string(), "2".to_string()),
+    ]
+    .into_iter()
+    .collect();
+    sdk.kv
+        .create(MOUNT_PATH, key, &CreateSecretParams { data })
+        .await
+        .unwrap();
+
+    let resp = sdk
+        .kv
+        .patch(
+            MOUNT_PATH,
+            key,
+            &patch(&[("foo", Some("3")), ("bar", None), ("baz", Some("4"))]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.version, 2);
+    assert_eq!(resp.max_version, 2);
+
+    let read_resp = sdk.kv.read(MOUNT_PATH, key, None).await.unwrap();
+    let expected: HashMap<_, _> = [
+        ("foo".to_string(), "3".to_string()),
+        ("baz".to_string(), "4".to_string()),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(read_resp.data, Some(expected));
+    assert_eq!(read_resp.metadata.version, 2);
+
+    // The previous version is unchanged
+    let read_resp = sdk.kv.read(MOUNT_PATH, key, Some(1)).await.unwrap();
+    assert_eq!(read_resp.data.unwrap().get("bar"), Some(&"2".to_string()));
+
+    // Cannot patch a deleted version
+    sdk.kv
+        .delete(
+            MOUNT_PATH,
+            key,
+            &SoftDeleteSecretParams { versions: vec![2] },
+        )
+        .await
+        .unwrap();
+    let err = sdk
+        .kv
+        .patch(MOUNT_PATH, key, &patch(&[("foo", Some("5"))]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
+
+#[tokio::test]
+async fn concurrent_patches_are_all_kept() {
+    let sdk = setup_unseal().await;
+    let key = "foo";
+
+    sdk.kv
+        .create(
+            MOUNT_PATH,
+            key,
+            &CreateSecretParams {
+                data: HashMap::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let (a, b, c) = tokio::join!(
+        sdk.kv.patch(MOUNT_PATH, key, &patch(&[("a", Some("1"))])),
+        sdk.kv.patch(MOUNT_PATH, key, &patch(&[("b", Some("2"))])),
+        sdk.kv.patch(MOUNT_PATH, key, &patch(&[("c", Some("3"))])),
+    );
+    a.unwrap();
+    b.unwrap();
+    c.unwrap();
+
+    let read_resp = sdk.kv.read(MOUNT_PATH, key, None).await.unwrap();
+    let data = read_resp.data.unwrap();
+    assert_eq!(data.len(), 3);
+    assert_eq!(read_resp.metadata.version, 4);
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use covert_sdk::{kv::CreateSecretParams, mounts::BackendType, ErrorCode};
+use covert_system::testing::TestServer;
+
+const MOUNT_PATH: &str = "kv/";
+
+#[tokio::test]
+async fn read_only_policy() {
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Kv)
+        .start()
+        .await;
+    let sdk = server.sdk();
+    server
+        .create_policy("reader", r#"path "kv/data/*" { capabilities = ["read"] }"#)
+        .await;
+
+    let data: HashMap<_, _> = [("foo".to_string(), "bar".to_string())]
+        .into_iter()
+        .collect();
+    sdk.kv
+        .create(
+            MOUNT_PATH,
+            "foo",
+            &CreateSecretParams { data: data.clone() },
+        )
+        .await
+        .unwrap();
+
+    let reader = server.client_with_policies(&["reader"]).await;
+    let resp = reader.kv.read(MOUNT_PATH, "foo", None).await.unwrap();
+    assert_eq!(resp.data, Some(data.clone()));
+
+    let err = reader
+        .kv
+        .create(MOUNT_PATH, "foo", &CreateSecretParams { data })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    // The token has no other policies
+    let token = server.create_token_with_policies(&[]).await;
+    let err = server
+        .client(&token)
+        .await
+        .kv
+        .read(MOUNT_PATH, "foo", None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
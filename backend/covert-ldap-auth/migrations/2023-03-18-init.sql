@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS CONFIG (
+    lock INTEGER PRIMARY KEY DEFAULT 1,
+    url TEXT NOT NULL,
+    bind_dn TEXT,
+    bind_password TEXT,
+    user_dn TEXT NOT NULL,
+    user_attr TEXT NOT NULL,
+    group_dn TEXT,
+    group_filter TEXT NOT NULL,
+    group_attr TEXT NOT NULL,
+
+    -- Used to ensure that maximum one config is ever inserted
+    CONSTRAINT CONFIG_LOCK CHECK (lock=1)
+);
+
+CREATE TABLE IF NOT EXISTS GROUP_MAPPINGS (
+    "name" TEXT PRIMARY KEY,
+    -- JSON list of policy names
+    policies TEXT NOT NULL
+);
@@ -0,0 +1,19 @@
+-- The config is stored as JSON under the `config` key of the storage view
+CREATE TABLE IF NOT EXISTS STORAGE_VIEW (
+    key TEXT PRIMARY KEY,
+    value BLOB NOT NULL
+);
+
+INSERT INTO STORAGE_VIEW (key, value)
+    SELECT 'config', CAST(json_object(
+        'url', url,
+        'bind_dn', bind_dn,
+        'bind_password', bind_password,
+        'user_dn', user_dn,
+        'user_attr', user_attr,
+        'group_dn', group_dn,
+        'group_filter', group_filter,
+        'group_attr', group_attr
+    ) AS BLOB) FROM CONFIG;
+
+DROP TABLE CONFIG;
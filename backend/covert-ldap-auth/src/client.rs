@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use ldap3::{
+    dn_escape, ldap_escape, parse_refs, Ldap, LdapConnAsync, LdapConnSettings, ResultEntry, Scope,
+    SearchEntry,
+};
+
+use crate::{
+    error::{Error, ErrorType},
+    Config,
+};
+
+/// Max time to wait for the connection to the LDAP server to be established.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+const USERNAME_PLACEHOLDER: &str = "{{username}}";
+
+const USER_DN_PLACEHOLDER: &str = "{{user_dn}}";
+
+fn user_search_filter(user_attr: &str, username: &str) -> String {
+    format!("({user_attr}={})", ldap_escape(username))
+}
+
+/// Substitutes the placeholders of the template in a single pass, so a
+/// placeholder in the username or DN is never substituted itself.
+fn group_search_filter(template: &str, username: &str, user_dn: &str) -> String {
+    let mut filter = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        filter.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(tail) = rest.strip_prefix(USERNAME_PLACEHOLDER) {
+            filter.push_str(&ldap_escape(username));
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix(USER_DN_PLACEHOLDER) {
+            filter.push_str(&ldap_escape(user_dn));
+            rest = tail;
+        } else {
+            filter.push_str("{{");
+            rest = &rest[2..];
+        }
+    }
+    filter.push_str(rest);
+    filter
+}
+
+/// Returns the entries from a search. Search references are skipped unless
+/// they are the only thing returned, in which case the referral is reported
+/// as an error as referrals are not followed.
+fn search_entries(results: Vec<ResultEntry>) -> Result<Vec<SearchEntry>, ErrorType> {
+    let (refs, entries): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .filter(|entry| !entry.is_intermediate())
+        .partition(ResultEntry::is_ref);
+
+    if entries.is_empty() && !refs.is_empty() {
+        let refs = refs.into_iter().flat_map(|r| parse_refs(r.0)).collect();
+        return Err(ErrorType::Referral { refs });
+    }
+
+    Ok(entries.into_iter().map(SearchEntry::construct).collect())
+}
+
+async fn connect(url: &str) -> Result<Ldap, Error> {
+    let settings = LdapConnSettings::new().set_conn_timeout(CONNECTION_TIMEOUT);
+    let (conn, ldap) = LdapConnAsync::with_settings(settings, url)
+        .await
+        .map_err(|err| ErrorType::Connection(err.to_string()))?;
+    ldap3::drive!(conn);
+    Ok(ldap)
+}
+
+/// Lookup the DN of the user. If no search credentials are configured the DN
+/// is built from the user attribute and the user base DN.
+async fn user_dn(ldap: &mut Ldap, config: &Config, username: &str) -> Result<String, Error> {
+    let Some(bind_dn) = &config.bind_dn else {
+        return Ok(format!(
+            "{}={},{}",
+            config.user_attr,
+            dn_escape(username),
+            config.user_dn
+        ));
+    };
+
+    ldap.simple_bind(bind_dn, config.bind_password.as_deref().unwrap_or_default())
+        .await?
+        .success()?;
+
+    let (results, _) = ldap
+        .search(
+            &config.user_dn,
+            Scope::Subtree,
+            &user_search_filter(&config.user_attr, username),
+            vec!["dn"],
+        )
+        .await?
+        .success()?;
+
+    let mut entries = search_entries(results)?;
+    if entries.len() != 1 {
+        // Unknown users fail like a wrong password, so the login cannot be
+        // used to find out which users exist
+        tracing::debug!(
+            matches = entries.len(),
+            "User search did not match one user"
+        );
+        return Err(ErrorType::InvalidCredentials.into());
+    }
+    Ok(entries.remove(0).dn)
+}
+
+/// Lookup the names of the groups the user is a member of.
+async fn user_groups(
+    ldap: &mut Ldap,
+    config: &Config,
+    group_dn: &str,
+    username: &str,
+    user_dn: &str,
+) -> Result<Vec<String>, Error> {
+    let (results, _) = ldap
+        .search(
+            group_dn,
+            Scope::Subtree,
+            &group_search_filter(&config.group_filter, username, user_dn),
+            vec![config.group_attr.as_str()],
+        )
+        .await?
+        .success()?;
+
+    let mut groups: Vec<String> = search_entries(results)?
+        .into_iter()
+        .filter_map(|mut entry| entry.attrs.remove(&config.group_attr))
+        .flatten()
+        .collect();
+    groups.sort();
+    groups.dedup();
+    Ok(groups)
+}
+
+/// Authenticates the user by binding as the user. Returns the groups the user
+/// is a member of if group membership resolution is configured.
+#[tracing::instrument(skip(config, password))]
+pub async fn authenticate(
+    config: &Config,
+    username: &str,
+    password: &str,
+) -> Result<Vec<String>, Error> {
+    // An empty password would result in an unauthenticated bind which most
+    // servers accept.
+    if password.is_empty() {
+        return Err(ErrorType::InvalidCredentials.into());
+    }
+
+    let mut ldap = connect(&config.url).await?;
+
+    let result = async {
+        let user_dn = user_dn(&mut ldap, config, username).await?;
+        ldap.simple_bind(&user_dn, password).await?.success()?;
+
+        match &config.group_dn {
+            Some(group_dn) => user_groups(&mut ldap, config, group_dn, username, &user_dn).await,
+            None => Ok(vec![]),
+        }
+    }
+    .await;
+
+    if let Err(error) = ldap.unbind().await {
+        tracing::debug!(?error, "Failed to unbind from the LDAP server");
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_user_filter() {
+        assert_eq!(user_search_filter("uid", "john"), "(uid=john)");
+        assert_eq!(
+            user_search_filter("uid", "*)(uid=*"),
+            "(uid=\\2a\\29\\28uid=\\2a)"
+        );
+    }
+
+    #[test]
+    fn renders_group_filter() {
+        assert_eq!(
+            group_search_filter(
+                "(|(member={{user_dn}})(memberUid={{username}}))",
+                "john*",
+                "uid=john*,ou=users,dc=example,dc=com"
+            ),
+            "(|(member=uid=john\\2a,ou=users,dc=example,dc=com)(memberUid=john\\2a))"
+        );
+    }
+
+    #[test]
+    fn does_not_substitute_placeholders_in_values() {
+        assert_eq!(
+            group_search_filter(
+                "(&(memberUid={{username}})(member={{user_dn}}))",
+                "{{user_dn}}",
+                "uid={{username}},ou=users,dc=example,dc=com"
+            ),
+            "(&(memberUid={{user_dn}})(member=uid={{username}},ou=users,dc=example,dc=com))"
+        );
+        assert_eq!(
+            group_search_filter("(cn={{other}})", "john", "uid=john"),
+            "(cn={{other}})"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt::Display;
+
+use covert_storage::StorageViewError;
+use covert_types::error::{ApiError, ErrorCode, StatusCode};
+use ldap3::LdapError;
+use thiserror::Error;
+use tracing_error::SpanTrace;
+
+/// LDAP result code returned when the server refers the client elsewhere.
+const LDAP_RC_REFERRAL: u32 = 10;
+
+/// LDAP result code returned on a failed bind.
+const LDAP_RC_INVALID_CREDENTIALS: u32 = 49;
+
+#[derive(Error, Debug)]
+pub enum ErrorType {
+    #[error("Internal error")]
+    Storage(#[from] sqlx::Error),
+    #[error("Internal error")]
+    StorageView(#[from] StorageViewError),
+    #[error("Bad request")]
+    BadRequest(#[from] serde_json::Error),
+    #[error("{0}")]
+    InvalidParams(String),
+    #[error("LDAP auth method has not been configured")]
+    MissingConfig,
+    #[error("Group `{name}` not found")]
+    GroupNotFound { name: String },
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+    #[error("Unable to connect to the LDAP server: {0}")]
+    Connection(String),
+    #[error("LDAP server returned a referral to `{}` and referrals are not followed", refs.join(", "))]
+    Referral { refs: Vec<String> },
+    #[error("LDAP server returned an error: {0}")]
+    Ldap(String),
+}
+
+impl From<LdapError> for ErrorType {
+    fn from(err: LdapError) -> Self {
+        match err {
+            LdapError::LdapResult { result } => match result.rc {
+                LDAP_RC_REFERRAL => ErrorType::Referral { refs: result.refs },
+                LDAP_RC_INVALID_CREDENTIALS => ErrorType::InvalidCredentials,
+                _ => ErrorType::Ldap(result.to_string()),
+            },
+            err => ErrorType::Connection(err.to_string()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub struct Error {
+    pub variant: ErrorType,
+    pub span_trace: SpanTrace,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n{}", self.variant, self.span_trace)
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<StorageViewError> for Error {
+    fn from(err: StorageViewError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<LdapError> for Error {
+    fn from(err: LdapError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<ErrorType> for Error {
+    fn from(err: ErrorType) -> Self {
+        Self {
+            variant: err,
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let (status_code, code) = match err.variant {
+            ErrorType::Storage(_) | ErrorType::StorageView(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+            ErrorType::BadRequest(_) | ErrorType::InvalidParams(_) | ErrorType::MissingConfig => {
+                (StatusCode::BAD_REQUEST, ErrorCode::BadRequest)
+            }
+            ErrorType::GroupNotFound { .. } => (StatusCode::NOT_FOUND, ErrorCode::NotFound),
+            ErrorType::InvalidCredentials => {
+                (StatusCode::UNAUTHORIZED, ErrorCode::PermissionDenied)
+            }
+            ErrorType::Connection(_) | ErrorType::Referral { .. } | ErrorType::Ldap(_) => {
+                (StatusCode::BAD_GATEWAY, ErrorCode::UpstreamError)
+            }
+        };
+
+        ApiError {
+            error: err.variant.into(),
+            code,
+            details: vec![],
+            status_code,
+            retry_after: None,
+            span_trace: Some(err.span_trace),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+#![forbid(unsafe_code)]
+#![forbid(clippy::unwrap_used)]
+#![deny(clippy::pedantic)]
+#![deny(clippy::get_unwrap)]
+#![allow(clippy::module_name_repetitions)]
+
+mod client;
+mod error;
+mod store;
+
+use std::{collections::HashMap, sync::Arc};
+
+use covert_framework::{
+    extract::{Extension, Json, Path},
+    lockout::{lockout_routes, with_lockout_migration, LoginLockout},
+    read, update_with_config, Backend, RouteConfig, Router,
+};
+use covert_storage::{
+    migrator::{migration_scripts, MigrationError},
+    BackendStoragePool, StorageView,
+};
+use covert_types::{
+    backend::{BackendCategory, BackendType},
+    error::ApiError,
+    methods::ldap::{
+        ConfigResponse, GroupResponse, ListGroupsResponse, LoginParams, RemoveGroupResponse,
+        SetConfigParams, SetGroupParams,
+    },
+    mount::MountConfig,
+    response::{AuthResponse, Response},
+};
+use error::{Error, ErrorType};
+use rust_embed::RustEmbed;
+use serde::{Deserialize, Serialize};
+use store::{config::ConfigRepo, group::GroupsRepo};
+
+pub struct Context {
+    config_repo: ConfigRepo,
+    groups_repo: GroupsRepo,
+    lockout: Arc<LoginLockout>,
+}
+
+#[derive(RustEmbed)]
+#[folder = "migrations/"]
+struct Migrations;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Config {
+    url: String,
+    bind_dn: Option<String>,
+    bind_password: Option<String>,
+    user_dn: String,
+    user_attr: String,
+    group_dn: Option<String>,
+    group_filter: String,
+    group_attr: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct GroupMapping {
+    name: String,
+    policies: Vec<String>,
+}
+
+/// Returns a new LDAP auth method.
+///
+/// # Errors
+///
+/// Returns an error if it fails to read the migration scripts.
+pub fn new_ldap_backend(pool: BackendStoragePool) -> Result<Backend, MigrationError> {
+    let storage_view = StorageView::new(pool.clone());
+    let lockout = Arc::new(LoginLockout::new(pool.clone()));
+    let ctx = Context {
+        config_repo: ConfigRepo::new(storage_view.clone()),
+        groups_repo: GroupsRepo::new(pool),
+        lockout: Arc::clone(&lockout),
+    };
+
+    let router = lockout_routes(Router::new())
+        .route(
+            "/config",
+            read(read_config).update(set_config).create(set_config),
+        )
+        .route(
+            "/login/*username",
+            update_with_config(login, RouteConfig::unauthenticated())
+                .create_with_config(login, RouteConfig::unauthenticated()),
+        )
+        .route("/groups", read(list_groups).list(list_groups))
+        .route(
+            "/groups/*name",
+            read(read_group)
+                .update(set_group)
+                .create(set_group)
+                .delete(remove_group),
+        )
+        .layer(Extension(Arc::new(ctx)))
+        .layer(Extension(lockout))
+        .layer(Extension(storage_view))
+        .build();
+
+    let migrations = with_lockout_migration(migration_scripts::<Migrations>()?)?;
+
+    Ok(Backend {
+        paths: router.paths(),
+        handler: router.into_service(),
+        category: BackendCategory::Credential,
+        variant: BackendType::Ldap,
+        migrations,
+    })
+}
+
+impl From<Config> for ConfigResponse {
+    fn from(config: Config) -> Self {
+        Self {
+            url: config.url,
+            bind_dn: config.bind_dn,
+            user_dn: config.user_dn,
+            user_attr: config.user_attr,
+            group_dn: config.group_dn,
+            group_filter: config.group_filter,
+            group_attr: config.group_attr,
+        }
+    }
+}
+
+impl From<GroupMapping> for GroupResponse {
+    fn from(group: GroupMapping) -> Self {
+        Self {
+            name: group.name,
+            policies: group.policies,
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn read_config(Extension(ctx): Extension<Arc<Context>>) -> Result<Response, Error> {
+    let config = ctx
+        .config_repo
+        .get()
+        .await?
+        .ok_or(ErrorType::MissingConfig)?;
+
+    Response::raw(ConfigResponse::from(config)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn set_config(
+    Json(params): Json<SetConfigParams>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    if params.bind_dn.is_some() != params.bind_password.is_some() {
+        return Err(ErrorType::InvalidParams(
+            "`bind_dn` and `bind_password` must be set together".to_string(),
+        )
+        .into());
+    }
+
+    let config = Config {
+        url: params.url,
+        bind_dn: params.bind_dn,
+        bind_password: params.bind_password,
+        user_dn: params.user_dn,
+        user_attr: params.user_attr,
+        group_dn: params.group_dn,
+        group_filter: params.group_filter,
+        group_attr: params.group_attr,
+    };
+    ctx.config_repo.set(&config).await?;
+
+    Response::raw(ConfigResponse::from(config)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all, fields(username = username))]
+async fn login(
+    Json(params): Json<LoginParams>,
+    Path(username): Path<String>,
+    Extension(config): Extension<MountConfig>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, ApiError> {
+    let ldap_config = ctx
+        .config_repo
+        .get()
+        .await?
+        .ok_or_else(|| Error::from(ErrorType::MissingConfig))?;
+
+    ctx.lockout.check(&username).await?;
+    let groups = match client::authenticate(&ldap_config, &username, &params.password).await {
+        Ok(groups) => {
+            ctx.lockout.record_success(&username).await?;
+            groups
+        }
+        Err(err) => {
+            if matches!(err.variant, ErrorType::InvalidCredentials) {
+                ctx.lockout.record_failure(&username).await?;
+            }
+            return Err(err.into());
+        }
+    };
+
+    let mut group_policies = vec![];
+    for group in &groups {
+        if let Some(mapping) = ctx.groups_repo.get(group).await? {
+            group_policies.extend(mapping.policies);
+        }
+    }
+    group_policies.sort();
+    group_policies.dedup();
+
+    let auth = AuthResponse {
+        metadata: HashMap::from([("username".to_string(), username.clone())]),
+        alias: username,
+        ttl: Some(config.default_lease_ttl),
+        policies: vec![],
+        group_policies,
+        create_entity: true,
+        renewable: params.renewable,
+    };
+    Ok(Response::Auth(auth))
+}
+
+#[tracing::instrument(skip_all)]
+async fn list_groups(Extension(ctx): Extension<Arc<Context>>) -> Result<Response, Error> {
+    let groups = ctx.groups_repo.list().await?;
+
+    let resp = ListGroupsResponse {
+        groups: groups.into_iter().map(Into::into).collect(),
+    };
+    Response::raw(resp).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all, fields(name = name))]
+async fn read_group(
+    Path(name): Path<String>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    let group = ctx
+        .groups_repo
+        .get(&name)
+        .await?
+        .ok_or(ErrorType::GroupNotFound { name })?;
+
+    Response::raw(GroupResponse::from(group)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all, fields(name = name))]
+async fn set_group(
+    Json(params): Json<SetGroupParams>,
+    Path(name): Path<String>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    let group = GroupMapping {
+        name,
+        policies: params.policies,
+    };
+    ctx.groups_repo.set(&group).await?;
+
+    Response::raw(GroupResponse::from(group)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all, fields(name = name))]
+async fn remove_group(
+    Path(name): Path<String>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    if !ctx.groups_repo.remove(&name).await? {
+        return Err(ErrorType::GroupNotFound { name }.into());
+    }
+
+    Response::raw(RemoveGroupResponse { name }).map_err(Into::into)
+}
@@ -0,0 +1,61 @@
+use covert_storage::StorageView;
+
+use crate::{error::Error, Config};
+
+/// Key of the config in the storage view of the mount.
+const CONFIG_KEY: &str = "config";
+
+#[derive(Debug)]
+pub struct ConfigRepo {
+    view: StorageView,
+}
+
+impl ConfigRepo {
+    pub fn new(view: StorageView) -> Self {
+        Self { view }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get(&self) -> Result<Option<Config>, Error> {
+        match self.view.get(CONFIG_KEY).await? {
+            Some(config) => Ok(Some(serde_json::from_slice(&config)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set(&self, config: &Config) -> Result<(), Error> {
+        let config = serde_json::to_vec(config)?;
+        self.view.put(CONFIG_KEY, config).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::group::tests::pool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn get_and_set() {
+        let store = ConfigRepo::new(StorageView::new(pool().await));
+        assert!(store.get().await.unwrap().is_none());
+
+        let mut config = Config {
+            url: "ldap://localhost:389".into(),
+            bind_dn: Some("cn=admin,dc=example,dc=com".into()),
+            bind_password: Some("admin".into()),
+            user_dn: "ou=users,dc=example,dc=com".into(),
+            user_attr: "uid".into(),
+            group_dn: None,
+            group_filter: "(member={{user_dn}})".into(),
+            group_attr: "cn".into(),
+        };
+        store.set(&config).await.unwrap();
+        assert_eq!(store.get().await.unwrap(), Some(config.clone()));
+
+        config.group_dn = Some("ou=groups,dc=example,dc=com".into());
+        store.set(&config).await.unwrap();
+        assert_eq!(store.get().await.unwrap(), Some(config));
+    }
+}
@@ -0,0 +1,128 @@
+use covert_storage::BackendStoragePool;
+
+use crate::{error::Error, GroupMapping};
+
+const GROUP_MAPPINGS_TABLE: &str = "GROUP_MAPPINGS";
+
+#[derive(Debug, sqlx::FromRow)]
+struct GroupMappingRaw {
+    name: String,
+    policies: String,
+}
+
+impl TryFrom<GroupMappingRaw> for GroupMapping {
+    type Error = Error;
+
+    fn try_from(raw: GroupMappingRaw) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: raw.name,
+            policies: serde_json::from_str(&raw.policies)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct GroupsRepo {
+    pool: BackendStoragePool,
+}
+
+impl GroupsRepo {
+    pub fn new(pool: BackendStoragePool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set(&self, group: &GroupMapping) -> Result<(), Error> {
+        self.pool
+            .query(&format!(
+                "INSERT OR REPLACE INTO {GROUP_MAPPINGS_TABLE} (name, policies)
+                    VALUES ($1, $2)"
+            ))?
+            .bind(&group.name)
+            .bind(serde_json::to_string(&group.policies)?)
+            .execute()
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get(&self, name: &str) -> Result<Option<GroupMapping>, Error> {
+        let group: Option<GroupMappingRaw> = self
+            .pool
+            .query(&format!(
+                "SELECT * FROM {GROUP_MAPPINGS_TABLE} WHERE name = ?"
+            ))?
+            .bind(name)
+            .fetch_optional()
+            .await?;
+        group.map(TryInto::try_into).transpose()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn list(&self) -> Result<Vec<GroupMapping>, Error> {
+        let groups: Vec<GroupMappingRaw> = self
+            .pool
+            .query(&format!(
+                "SELECT * FROM {GROUP_MAPPINGS_TABLE} ORDER BY name"
+            ))?
+            .fetch_all()
+            .await?;
+        groups.into_iter().map(TryInto::try_into).collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn remove(&self, name: &str) -> Result<bool, Error> {
+        self.pool
+            .query(&format!(
+                "DELETE FROM {GROUP_MAPPINGS_TABLE} WHERE name = ?"
+            ))?
+            .bind(name)
+            .execute()
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use covert_storage::{migrator::migrate_backend, EncryptedPool};
+
+    use crate::Migrations;
+
+    use super::*;
+
+    pub async fn pool() -> BackendStoragePool {
+        let pool = Arc::new(EncryptedPool::new_tmp());
+
+        let storage = BackendStoragePool::new("foo_", pool);
+
+        migrate_backend::<Migrations>(&storage).await.unwrap();
+
+        storage
+    }
+
+    #[sqlx::test]
+    async fn crud() {
+        let store = GroupsRepo::new(pool().await);
+
+        let mut group = GroupMapping {
+            name: "admins".into(),
+            policies: vec!["admin".into()],
+        };
+        store.set(&group).await.unwrap();
+        assert_eq!(store.get(&group.name).await.unwrap(), Some(group.clone()));
+        assert_eq!(store.get("devs").await.unwrap(), None);
+
+        // Setting an existing group replaces the policies
+        group.policies.push("audit".into());
+        store.set(&group).await.unwrap();
+        assert_eq!(store.list().await.unwrap(), vec![group.clone()]);
+
+        assert!(store.remove(&group.name).await.unwrap());
+        assert!(store.list().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use covert_sdk::ldap::{GroupResponse, LoginParams, SetConfigParams, SetGroupParams};
+
+use crate::common::{setup_unseal, MOUNT_PATH};
+
+#[tokio::test]
+async fn config_and_groups() {
+    let sdk = setup_unseal().await;
+
+    // Not configured to start with
+    assert!(sdk.ldap.read_config(MOUNT_PATH).await.is_err());
+
+    let params = SetConfigParams {
+        url: "ldap://127.0.0.1:1".to_string(),
+        bind_dn: Some("cn=admin,dc=example,dc=com".to_string()),
+        bind_password: Some("admin".to_string()),
+        user_dn: "ou=users,dc=example,dc=com".to_string(),
+        user_attr: "uid".to_string(),
+        group_dn: Some("ou=groups,dc=example,dc=com".to_string()),
+        group_filter: "(member={{user_dn}})".to_string(),
+        group_attr: "cn".to_string(),
+    };
+    sdk.ldap.set_config(MOUNT_PATH, &params).await.unwrap();
+    let config = sdk.ldap.read_config(MOUNT_PATH).await.unwrap();
+    assert_eq!(config.url, params.url);
+    assert_eq!(config.bind_dn, params.bind_dn);
+    assert_eq!(config.group_dn, params.group_dn);
+
+    // Bind DN without password is rejected
+    assert!(sdk
+        .ldap
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                bind_password: None,
+                ..params.clone()
+            },
+        )
+        .await
+        .is_err());
+
+    let group = sdk
+        .ldap
+        .set_group(
+            MOUNT_PATH,
+            "admins",
+            &SetGroupParams {
+                policies: vec!["admin".to_string()],
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        group,
+        GroupResponse {
+            name: "admins".to_string(),
+            policies: vec!["admin".to_string()],
+        }
+    );
+    assert_eq!(
+        sdk.ldap.read_group(MOUNT_PATH, "admins").await.unwrap(),
+        group
+    );
+    assert_eq!(
+        sdk.ldap.list_groups(MOUNT_PATH).await.unwrap().groups,
+        vec![group]
+    );
+
+    sdk.ldap.remove_group(MOUNT_PATH, "admins").await.unwrap();
+    assert!(sdk.ldap.read_group(MOUNT_PATH, "admins").await.is_err());
+    assert!(sdk
+        .ldap
+        .list_groups(MOUNT_PATH)
+        .await
+        .unwrap()
+        .groups
+        .is_empty());
+}
+
+#[tokio::test]
+async fn login_with_unreachable_server() {
+    let sdk = setup_unseal().await;
+
+    let login = LoginParams {
+        password: "pass".to_string(),
+        renewable: true,
+    };
+
+    // Login before the backend is configured
+    let err = sdk
+        .ldap
+        .login(MOUNT_PATH, "john", &login)
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("not been configured"), "{err}");
+
+    sdk.ldap
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                url: "ldap://127.0.0.1:1".to_string(),
+                bind_dn: None,
+                bind_password: None,
+                user_dn: "ou=users,dc=example,dc=com".to_string(),
+                user_attr: "uid".to_string(),
+                group_dn: None,
+                group_filter: "(member={{user_dn}})".to_string(),
+                group_attr: "cn".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let err = sdk
+        .ldap
+        .login(MOUNT_PATH, "john", &login)
+        .await
+        .unwrap_err();
+    assert!(
+        err.message()
+            .contains("Unable to connect to the LDAP server"),
+        "{err}"
+    );
+
+    // Empty password is never sent to the server
+    let err = sdk
+        .ldap
+        .login(
+            MOUNT_PATH,
+            "john",
+            &LoginParams {
+                password: String::new(),
+                renewable: true,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("Invalid credentials"), "{err}");
+}
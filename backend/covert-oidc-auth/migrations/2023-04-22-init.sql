@@ -0,0 +1,7 @@
+-- Logins started at `auth_url` waiting for the callback from the provider
+CREATE TABLE IF NOT EXISTS AUTH_STATES (
+    "state" TEXT PRIMARY KEY,
+    nonce TEXT NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    error::{Error, ErrorType},
+    AuthState, Config,
+};
+
+/// Max time to wait for a response from the provider.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+
+/// Endpoints of the provider from its discovery document.
+#[derive(Debug, Deserialize)]
+pub struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+fn http_client() -> Result<Client, Error> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(Into::into)
+}
+
+/// Fetches the discovery document of the issuer. The issuer in the document
+/// must be the configured one, as ID tokens are validated against it.
+pub async fn discover(issuer: &str) -> Result<ProviderMetadata, Error> {
+    let url = format!("{}{DISCOVERY_PATH}", issuer.trim_end_matches('/'));
+    let metadata: ProviderMetadata = http_client()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    if metadata.issuer != issuer {
+        return Err(ErrorType::Provider(format!(
+            "discovery document is for issuer `{}` instead of `{issuer}`",
+            metadata.issuer
+        ))
+        .into());
+    }
+    Ok(metadata)
+}
+
+/// URL of the authorization endpoint the user is sent to for login.
+pub fn auth_url(
+    metadata: &ProviderMetadata,
+    config: &Config,
+    state: &AuthState,
+) -> Result<String, Error> {
+    let mut url = Url::parse(&metadata.authorization_endpoint)
+        .map_err(|err| ErrorType::Provider(format!("invalid authorization endpoint: {err}")))?;
+
+    let scope = std::iter::once("openid")
+        .chain(
+            config
+                .scopes
+                .iter()
+                .map(String::as_str)
+                .filter(|scope| *scope != "openid"),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &state.redirect_uri)
+        .append_pair("scope", &scope)
+        .append_pair("state", &state.state)
+        .append_pair("nonce", &state.nonce);
+    Ok(url.into())
+}
+
+/// Exchanges the authorization code for an ID token at the token endpoint.
+pub async fn exchange_code(
+    metadata: &ProviderMetadata,
+    config: &Config,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<String, Error> {
+    let resp = http_client()?
+        .post(&metadata.token_endpoint)
+        .basic_auth(&config.client_id, Some(&config.client_secret))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+        ])
+        .send()
+        .await?;
+
+    // Unknown, expired and already used codes are rejected with a 400
+    if resp.status() == reqwest::StatusCode::BAD_REQUEST {
+        return Err(ErrorType::InvalidCode.into());
+    }
+    let resp: TokenResponse = resp.error_for_status()?.json().await?;
+    Ok(resp.id_token)
+}
+
+/// Verifies the signature of the ID token with the keys of the provider and
+/// returns its claims. The token must be issued by the provider for this
+/// client and login, and must not have expired.
+pub async fn verify_id_token(
+    metadata: &ProviderMetadata,
+    config: &Config,
+    id_token: &str,
+    nonce: &str,
+) -> Result<Map<String, Value>, Error> {
+    let header = decode_header(id_token)?;
+    // Keys shared with the provider are never used, the token must be signed
+    // with one of the published keys
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(ErrorType::InvalidToken(format!(
+            "unsupported signing algorithm `{:?}`",
+            header.alg
+        ))
+        .into());
+    }
+
+    let jwks: JwkSet = http_client()?
+        .get(&metadata.jwks_uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| ErrorType::InvalidToken("signing key not found".to_string()))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&metadata.issuer]);
+    validation.set_audience(&[&config.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+    let claims =
+        decode::<Map<String, Value>>(id_token, &DecodingKey::from_jwk(jwk)?, &validation)?.claims;
+
+    if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+        return Err(ErrorType::InvalidToken("nonce does not match the login".to_string()).into());
+    }
+    Ok(claims)
+}
+
+/// Value of the claim identifying the user.
+pub fn user_claim(claims: &Map<String, Value>, name: &str) -> Result<String, Error> {
+    match claims.get(name) {
+        Some(Value::String(value)) if !value.is_empty() => Ok(value.clone()),
+        Some(Value::Number(value)) => Ok(value.to_string()),
+        _ => Err(ErrorType::InvalidToken(format!("missing `{name}` claim")).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn auth_url_has_login_parameters() {
+        let metadata = ProviderMetadata {
+            issuer: "https://accounts.example.com".into(),
+            authorization_endpoint: "https://accounts.example.com/authorize?prompt=login".into(),
+            token_endpoint: "https://accounts.example.com/token".into(),
+            jwks_uri: "https://accounts.example.com/jwks".into(),
+        };
+        let config = Config {
+            issuer: metadata.issuer.clone(),
+            client_id: "covert".into(),
+            client_secret: "secret".into(),
+            allowed_redirect_uris: vec!["https://covert.example.com/ui/".into()],
+            scopes: vec!["openid".into(), "email".into()],
+            user_claim: "email".into(),
+        };
+        let state = AuthState {
+            state: "abc".into(),
+            nonce: "def".into(),
+            redirect_uri: "https://covert.example.com/ui/".into(),
+            expires_at: Utc::now(),
+        };
+
+        let url = Url::parse(&auth_url(&metadata, &config, &state).unwrap()).unwrap();
+        let query = url.query_pairs().into_owned().collect::<Vec<_>>();
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(param("prompt"), Some("login"));
+        assert_eq!(param("response_type"), Some("code"));
+        assert_eq!(param("client_id"), Some("covert"));
+        assert_eq!(
+            param("redirect_uri"),
+            Some("https://covert.example.com/ui/")
+        );
+        assert_eq!(param("scope"), Some("openid email"));
+        assert_eq!(param("state"), Some("abc"));
+        assert_eq!(param("nonce"), Some("def"));
+    }
+
+    #[test]
+    fn user_claims() {
+        let claims = json!({ "sub": "1234", "email": "", "uid": 42 });
+        let claims = claims.as_object().unwrap();
+
+        assert_eq!(user_claim(claims, "sub").unwrap(), "1234");
+        assert_eq!(user_claim(claims, "uid").unwrap(), "42");
+        assert!(user_claim(claims, "email").is_err());
+        assert!(user_claim(claims, "name").is_err());
+    }
+}
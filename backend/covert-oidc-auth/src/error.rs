@@ -0,0 +1,132 @@
+use std::fmt::Display;
+
+use covert_storage::StorageViewError;
+use covert_types::error::{ApiError, ErrorCode, StatusCode};
+use thiserror::Error;
+use tracing_error::SpanTrace;
+
+#[derive(Error, Debug)]
+pub enum ErrorType {
+    #[error("Internal error")]
+    Storage(#[from] sqlx::Error),
+    #[error("Internal error")]
+    StorageView(#[from] StorageViewError),
+    #[error("Bad request")]
+    BadRequest(#[from] serde_json::Error),
+    #[error("{0}")]
+    InvalidParams(String),
+    #[error("OIDC auth method has not been configured")]
+    MissingConfig,
+    #[error("Unknown or expired login state")]
+    InvalidState,
+    #[error("Invalid authorization code")]
+    InvalidCode,
+    #[error("Invalid ID token: {0}")]
+    InvalidToken(String),
+    #[error("OIDC provider returned an error: {0}")]
+    Provider(String),
+}
+
+impl From<reqwest::Error> for ErrorType {
+    fn from(err: reqwest::Error) -> Self {
+        ErrorType::Provider(err.to_string())
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for ErrorType {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        ErrorType::InvalidToken(err.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+pub struct Error {
+    pub variant: ErrorType,
+    pub span_trace: SpanTrace,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n{}", self.variant, self.span_trace)
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<StorageViewError> for Error {
+    fn from(err: StorageViewError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<ErrorType> for Error {
+    fn from(err: ErrorType) -> Self {
+        Self {
+            variant: err,
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let (status_code, code) = match err.variant {
+            ErrorType::Storage(_) | ErrorType::StorageView(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+            ErrorType::BadRequest(_) | ErrorType::InvalidParams(_) | ErrorType::MissingConfig => {
+                (StatusCode::BAD_REQUEST, ErrorCode::BadRequest)
+            }
+            ErrorType::InvalidState | ErrorType::InvalidCode | ErrorType::InvalidToken(_) => {
+                (StatusCode::UNAUTHORIZED, ErrorCode::PermissionDenied)
+            }
+            ErrorType::Provider(_) => (StatusCode::BAD_GATEWAY, ErrorCode::UpstreamError),
+        };
+
+        ApiError {
+            error: err.variant.into(),
+            code,
+            details: vec![],
+            status_code,
+            retry_after: None,
+            span_trace: Some(err.span_trace),
+        }
+    }
+}
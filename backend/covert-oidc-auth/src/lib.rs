@@ -0,0 +1,239 @@
+#![forbid(unsafe_code)]
+#![forbid(clippy::unwrap_used)]
+#![deny(clippy::pedantic)]
+#![deny(clippy::get_unwrap)]
+#![allow(clippy::module_name_repetitions)]
+
+mod client;
+mod error;
+mod store;
+
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use covert_framework::{
+    extract::{Extension, Json},
+    read, update_with_config, Backend, RouteConfig, Router,
+};
+use covert_storage::{
+    migrator::{migration_scripts, MigrationError},
+    BackendStoragePool, StorageView,
+};
+use covert_types::{
+    backend::{BackendCategory, BackendType},
+    methods::oidc::{
+        AuthUrlParams, AuthUrlResponse, CallbackParams, ConfigResponse, SetConfigParams,
+    },
+    mount::MountConfig,
+    response::{AuthResponse, Response},
+};
+use error::{Error, ErrorType};
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::Url;
+use rust_embed::RustEmbed;
+use serde::{Deserialize, Serialize};
+use store::{config::ConfigRepo, state::StatesRepo};
+
+/// Seconds the user has to log in with the provider before the login expires.
+const STATE_TTL_SECS: i64 = 600;
+
+/// Length of the random state and nonce of a login.
+const STATE_LENGTH: usize = 32;
+
+pub struct Context {
+    config_repo: ConfigRepo,
+    states_repo: StatesRepo,
+}
+
+#[derive(RustEmbed)]
+#[folder = "migrations/"]
+struct Migrations;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Config {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    allowed_redirect_uris: Vec<String>,
+    scopes: Vec<String>,
+    user_claim: String,
+}
+
+/// Login started at `auth_url` and completed at `callback`.
+#[derive(Debug, sqlx::FromRow, PartialEq, Eq, Clone)]
+pub struct AuthState {
+    state: String,
+    nonce: String,
+    redirect_uri: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Returns a new OIDC auth method.
+///
+/// # Errors
+///
+/// Returns an error if it fails to read the migration scripts.
+pub fn new_oidc_backend(pool: BackendStoragePool) -> Result<Backend, MigrationError> {
+    let storage_view = StorageView::new(pool.clone());
+    let ctx = Context {
+        config_repo: ConfigRepo::new(storage_view.clone()),
+        states_repo: StatesRepo::new(pool),
+    };
+
+    let router = Router::new()
+        .route(
+            "/config",
+            read(read_config).update(set_config).create(set_config),
+        )
+        .route(
+            "/auth_url",
+            update_with_config(auth_url, RouteConfig::unauthenticated())
+                .create_with_config(auth_url, RouteConfig::unauthenticated()),
+        )
+        .route(
+            "/callback",
+            update_with_config(callback, RouteConfig::unauthenticated())
+                .create_with_config(callback, RouteConfig::unauthenticated()),
+        )
+        .layer(Extension(Arc::new(ctx)))
+        .layer(Extension(storage_view))
+        .build();
+
+    let migrations = migration_scripts::<Migrations>()?;
+
+    Ok(Backend {
+        paths: router.paths(),
+        handler: router.into_service(),
+        category: BackendCategory::Credential,
+        variant: BackendType::Oidc,
+        migrations,
+    })
+}
+
+impl From<Config> for ConfigResponse {
+    fn from(config: Config) -> Self {
+        Self {
+            issuer: config.issuer,
+            client_id: config.client_id,
+            allowed_redirect_uris: config.allowed_redirect_uris,
+            scopes: config.scopes,
+            user_claim: config.user_claim,
+        }
+    }
+}
+
+fn random_string() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(STATE_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+#[tracing::instrument(skip_all)]
+async fn read_config(Extension(ctx): Extension<Arc<Context>>) -> Result<Response, Error> {
+    let config = ctx
+        .config_repo
+        .get()
+        .await?
+        .ok_or(ErrorType::MissingConfig)?;
+
+    Response::raw(ConfigResponse::from(config)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn set_config(
+    Json(params): Json<SetConfigParams>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    for url in std::iter::once(&params.issuer).chain(&params.allowed_redirect_uris) {
+        if let Err(err) = Url::parse(url) {
+            return Err(ErrorType::InvalidParams(format!("Invalid URL `{url}`: {err}")).into());
+        }
+    }
+    if params.user_claim.is_empty() {
+        return Err(ErrorType::InvalidParams("`user_claim` must not be empty".to_string()).into());
+    }
+
+    let config = Config {
+        issuer: params.issuer,
+        client_id: params.client_id,
+        client_secret: params.client_secret,
+        allowed_redirect_uris: params.allowed_redirect_uris,
+        scopes: params.scopes,
+        user_claim: params.user_claim,
+    };
+    ctx.config_repo.set(&config).await?;
+
+    Response::raw(ConfigResponse::from(config)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn auth_url(
+    Json(params): Json<AuthUrlParams>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    let config = ctx
+        .config_repo
+        .get()
+        .await?
+        .ok_or(ErrorType::MissingConfig)?;
+    if !config.allowed_redirect_uris.contains(&params.redirect_uri) {
+        return Err(ErrorType::InvalidParams(format!(
+            "Redirect URI `{}` is not allowed",
+            params.redirect_uri
+        ))
+        .into());
+    }
+
+    let metadata = client::discover(&config.issuer).await?;
+    let state = AuthState {
+        state: random_string(),
+        nonce: random_string(),
+        redirect_uri: params.redirect_uri,
+        expires_at: Utc::now() + Duration::seconds(STATE_TTL_SECS),
+    };
+    let auth_url = client::auth_url(&metadata, &config, &state)?;
+    ctx.states_repo.create(&state).await?;
+
+    Response::raw(AuthUrlResponse {
+        auth_url,
+        state: state.state,
+    })
+    .map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn callback(
+    Json(params): Json<CallbackParams>,
+    Extension(config): Extension<MountConfig>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    let oidc_config = ctx
+        .config_repo
+        .get()
+        .await?
+        .ok_or(ErrorType::MissingConfig)?;
+    let state = ctx
+        .states_repo
+        .take(&params.state, Utc::now())
+        .await?
+        .ok_or(ErrorType::InvalidState)?;
+
+    let metadata = client::discover(&oidc_config.issuer).await?;
+    let id_token =
+        client::exchange_code(&metadata, &oidc_config, &params.code, &state.redirect_uri).await?;
+    let claims = client::verify_id_token(&metadata, &oidc_config, &id_token, &state.nonce).await?;
+    let alias = client::user_claim(&claims, &oidc_config.user_claim)?;
+
+    let auth = AuthResponse {
+        metadata: HashMap::from([(oidc_config.user_claim, alias.clone())]),
+        alias,
+        ttl: Some(config.default_lease_ttl),
+        policies: vec![],
+        group_policies: vec![],
+        create_entity: true,
+        renewable: params.renewable,
+    };
+    Ok(Response::Auth(auth))
+}
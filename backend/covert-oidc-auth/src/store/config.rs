@@ -0,0 +1,59 @@
+use covert_storage::StorageView;
+
+use crate::{error::Error, Config};
+
+/// Key of the config in the storage view of the mount.
+const CONFIG_KEY: &str = "config";
+
+#[derive(Debug)]
+pub struct ConfigRepo {
+    view: StorageView,
+}
+
+impl ConfigRepo {
+    pub fn new(view: StorageView) -> Self {
+        Self { view }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get(&self) -> Result<Option<Config>, Error> {
+        match self.view.get(CONFIG_KEY).await? {
+            Some(config) => Ok(Some(serde_json::from_slice(&config)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set(&self, config: &Config) -> Result<(), Error> {
+        let config = serde_json::to_vec(config)?;
+        self.view.put(CONFIG_KEY, config).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::state::tests::pool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn get_and_set() {
+        let store = ConfigRepo::new(StorageView::new(pool().await));
+        assert!(store.get().await.unwrap().is_none());
+
+        let mut config = Config {
+            issuer: "https://accounts.example.com".into(),
+            client_id: "covert".into(),
+            client_secret: "secret".into(),
+            allowed_redirect_uris: vec!["https://covert.example.com/ui/".into()],
+            scopes: vec!["email".into()],
+            user_claim: "email".into(),
+        };
+        store.set(&config).await.unwrap();
+        assert_eq!(store.get().await.unwrap(), Some(config.clone()));
+
+        config.scopes = vec![];
+        store.set(&config).await.unwrap();
+        assert_eq!(store.get().await.unwrap(), Some(config));
+    }
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use covert_storage::BackendStoragePool;
+
+use crate::{error::Error, AuthState};
+
+const AUTH_STATES_TABLE: &str = "AUTH_STATES";
+
+#[derive(Debug)]
+pub struct StatesRepo {
+    pool: BackendStoragePool,
+}
+
+impl StatesRepo {
+    pub fn new(pool: BackendStoragePool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores a started login and removes the logins that were never
+    /// completed.
+    #[tracing::instrument(skip_all)]
+    pub async fn create(&self, state: &AuthState) -> Result<(), Error> {
+        self.pool
+            .query(&format!(
+                "DELETE FROM {AUTH_STATES_TABLE} WHERE expires_at <= $1"
+            ))?
+            .bind(Utc::now())
+            .execute()
+            .await?;
+
+        self.pool
+            .query(&format!(
+                "INSERT INTO {AUTH_STATES_TABLE} (state, nonce, redirect_uri, expires_at)
+                    VALUES ($1, $2, $3, $4)"
+            ))?
+            .bind(&state.state)
+            .bind(&state.nonce)
+            .bind(&state.redirect_uri)
+            .bind(state.expires_at)
+            .execute()
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    /// Removes a started login and returns it if it has not expired. A state
+    /// can only be taken once so a callback cannot be replayed.
+    #[tracing::instrument(skip_all)]
+    pub async fn take(&self, state: &str, now: DateTime<Utc>) -> Result<Option<AuthState>, Error> {
+        let auth_state: Option<AuthState> = self
+            .pool
+            .query(&format!(
+                "SELECT * FROM {AUTH_STATES_TABLE} WHERE state = $1"
+            ))?
+            .bind(state)
+            .fetch_optional()
+            .await?;
+        let removed = self
+            .pool
+            .query(&format!("DELETE FROM {AUTH_STATES_TABLE} WHERE state = $1"))?
+            .bind(state)
+            .execute()
+            .await?
+            .rows_affected()
+            == 1;
+
+        Ok(auth_state.filter(|auth_state| removed && auth_state.expires_at > now))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use chrono::Duration;
+    use covert_storage::{migrator::migrate_backend, EncryptedPool};
+
+    use crate::Migrations;
+
+    use super::*;
+
+    pub async fn pool() -> BackendStoragePool {
+        let pool = Arc::new(EncryptedPool::new_tmp());
+
+        let storage = BackendStoragePool::new("foo_", pool);
+
+        migrate_backend::<Migrations>(&storage).await.unwrap();
+
+        storage
+    }
+
+    fn auth_state(state: &str, expires_at: DateTime<Utc>) -> AuthState {
+        AuthState {
+            state: state.into(),
+            nonce: "nonce".into(),
+            redirect_uri: "https://covert.example.com/ui/".into(),
+            expires_at,
+        }
+    }
+
+    #[sqlx::test]
+    async fn states_are_taken_once() {
+        let store = StatesRepo::new(pool().await);
+        let state = auth_state("state", Utc::now() + Duration::minutes(5));
+        store.create(&state).await.unwrap();
+
+        assert_eq!(store.take("state", Utc::now()).await.unwrap(), Some(state));
+        assert_eq!(store.take("state", Utc::now()).await.unwrap(), None);
+        assert_eq!(store.take("unknown", Utc::now()).await.unwrap(), None);
+    }
+
+    #[sqlx::test]
+    async fn expired_states_are_rejected() {
+        let store = StatesRepo::new(pool().await);
+        let now = Utc::now();
+        store
+            .create(&auth_state("state", now + Duration::minutes(5)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .take("state", now + Duration::minutes(10))
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use covert_sdk::{
+    mounts::BackendType,
+    oidc::{AuthUrlParams, CallbackParams, SetConfigParams},
+};
+use covert_system::testing::TestServer;
+
+const MOUNT_PATH: &str = "auth/oidc/";
+
+fn config_params() -> SetConfigParams {
+    SetConfigParams {
+        issuer: "http://127.0.0.1:1".to_string(),
+        client_id: "covert".to_string(),
+        client_secret: "secret".to_string(),
+        allowed_redirect_uris: vec!["http://localhost/ui/".to_string()],
+        scopes: vec!["email".to_string()],
+        user_claim: "email".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn config() {
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Oidc)
+        .start()
+        .await;
+    let sdk = server.sdk();
+
+    // Not configured to start with
+    assert!(sdk.oidc.read_config(MOUNT_PATH).await.is_err());
+
+    let params = config_params();
+    sdk.oidc.set_config(MOUNT_PATH, &params).await.unwrap();
+    let config = sdk.oidc.read_config(MOUNT_PATH).await.unwrap();
+    assert_eq!(config.issuer, params.issuer);
+    assert_eq!(config.client_id, params.client_id);
+    assert_eq!(config.allowed_redirect_uris, params.allowed_redirect_uris);
+    assert_eq!(config.scopes, params.scopes);
+    assert_eq!(config.user_claim, params.user_claim);
+
+    // Redirect URIs must be URLs
+    assert!(sdk
+        .oidc
+        .set_config(
+            MOUNT_PATH,
+            &SetConfigParams {
+                allowed_redirect_uris: vec!["/ui/".to_string()],
+                ..params
+            },
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn login_flow_errors() {
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Oidc)
+        .start()
+        .await;
+    let sdk = server.sdk();
+
+    let auth_url = AuthUrlParams {
+        redirect_uri: "http://localhost/ui/".to_string(),
+    };
+
+    // Login before the backend is configured
+    let err = sdk.oidc.auth_url(MOUNT_PATH, &auth_url).await.unwrap_err();
+    assert!(err.message().contains("not been configured"), "{err}");
+
+    sdk.oidc
+        .set_config(MOUNT_PATH, &config_params())
+        .await
+        .unwrap();
+
+    // Only the configured redirect URIs can be used
+    let err = sdk
+        .oidc
+        .auth_url(
+            MOUNT_PATH,
+            &AuthUrlParams {
+                redirect_uri: "http://attacker.example.com/".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("is not allowed"), "{err}");
+
+    // The provider is unreachable
+    let err = sdk.oidc.auth_url(MOUNT_PATH, &auth_url).await.unwrap_err();
+    assert!(err.message().contains("OIDC provider"), "{err}");
+
+    // Callbacks are only accepted for logins started at `auth_url`
+    let err = sdk
+        .oidc
+        .callback(
+            MOUNT_PATH,
+            &CallbackParams {
+                state: "unknown".to_string(),
+                code: "code".to_string(),
+                renewable: true,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.message().contains("Unknown or expired login state"),
+        "{err}"
+    );
+}
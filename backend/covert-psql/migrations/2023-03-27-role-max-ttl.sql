@@ -0,0 +1,2 @@
+-- Max TTL in milliseconds of the credentials generated for the role
+ALTER TABLE ROLES ADD COLUMN max_ttl INTEGER;
@@ -0,0 +1,14 @@
+-- The connection config is stored as JSON under the `config/connection` key
+-- of the storage view
+CREATE TABLE IF NOT EXISTS STORAGE_VIEW (
+    key TEXT PRIMARY KEY,
+    value BLOB NOT NULL
+);
+
+INSERT INTO STORAGE_VIEW (key, value)
+    SELECT 'config/connection', CAST(json_object(
+        'connection_url', connection_url,
+        'max_open_connections', max_open_connections
+    ) AS BLOB) FROM CONNECTION;
+
+DROP TABLE CONNECTION;
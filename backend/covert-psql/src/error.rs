@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
-use covert_types::error::{ApiError, StatusCode};
+use covert_storage::StorageViewError;
+use covert_types::error::{ApiError, ErrorCode, StatusCode};
 use thiserror::Error;
 use tracing_error::SpanTrace;
 
@@ -8,6 +9,8 @@ use tracing_error::SpanTrace;
 pub enum ErrorType {
     #[error("Internal error")]
     Storage(#[from] sqlx::Error),
+    #[error("Internal error")]
+    StorageView(#[from] StorageViewError),
     #[error("Bad request")]
     BadRequest(#[from] serde_json::Error),
     #[error("Invalid connection string")]
@@ -41,6 +44,15 @@ impl From<sqlx::Error> for Error {
     }
 }
 
+impl From<StorageViewError> for Error {
+    fn from(err: StorageViewError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Self {
@@ -61,20 +73,23 @@ impl From<ErrorType> for Error {
 
 impl From<Error> for ApiError {
     fn from(err: Error) -> Self {
-        let status_code = match err.variant {
-            ErrorType::Storage(_) | ErrorType::InternalError(_) => {
-                StatusCode::INTERNAL_SERVER_ERROR
+        let (status_code, code) = match err.variant {
+            ErrorType::Storage(_) | ErrorType::StorageView(_) | ErrorType::InternalError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
             }
             ErrorType::BadRequest(_) | ErrorType::InvalidConnectionString => {
-                StatusCode::BAD_REQUEST
+                (StatusCode::BAD_REQUEST, ErrorCode::BadRequest)
             }
-            ErrorType::RoleNotFound { .. } => StatusCode::NOT_FOUND,
-            ErrorType::MissingConnection => StatusCode::FORBIDDEN,
+            ErrorType::RoleNotFound { .. } => (StatusCode::NOT_FOUND, ErrorCode::NotFound),
+            ErrorType::MissingConnection => (StatusCode::FORBIDDEN, ErrorCode::PreconditionFailed),
         };
 
         ApiError {
             error: err.variant.into(),
+            code,
+            details: vec![],
             status_code,
+            retry_after: None,
             span_trace: Some(err.span_trace),
         }
     }
@@ -8,6 +8,7 @@ mod error;
 mod path_config_connection;
 mod path_role_create;
 mod path_roles;
+mod path_rotate_root;
 mod secret_creds;
 mod store;
 
@@ -15,7 +16,7 @@ use std::sync::Arc;
 
 use covert_storage::{
     migrator::{migration_scripts, MigrationError},
-    BackendStoragePool,
+    BackendStoragePool, StorageView,
 };
 use error::{Error, ErrorType};
 use rust_embed::RustEmbed;
@@ -35,6 +36,7 @@ use self::{
     path_config_connection::{path_connection_read, path_connection_write},
     path_role_create::generate_role_credentials,
     path_roles::path_role_create,
+    path_rotate_root::path_rotate_root,
     secret_creds::secret_creds_revoke,
 };
 
@@ -55,9 +57,10 @@ pub struct Context {
 /// Returns an error if it fails to read the migration scripts.
 #[tracing::instrument(skip_all)]
 pub async fn new_psql_backend(storage: BackendStoragePool) -> Result<Backend, MigrationError> {
+    let storage_view = StorageView::new(storage.clone());
     let ctx = Arc::new(Context {
         db: RwLock::default(),
-        connection_repo: ConnectionStore::new(storage.clone()),
+        connection_repo: ConnectionStore::new(storage_view.clone()),
         role_repo: RoleStore::new(storage),
     });
 
@@ -73,7 +76,11 @@ pub async fn new_psql_backend(storage: BackendStoragePool) -> Result<Backend, Mi
                 .update(path_connection_write)
                 .create(path_connection_write),
         )
-        .route("/creds/:name", update(generate_role_credentials))
+        .route("/rotate-root", update(path_rotate_root))
+        .route(
+            "/creds/:name",
+            update(generate_role_credentials).requires_lease(),
+        )
         .route(
             "/roles/:name",
             update(path_role_create).create(path_role_create),
@@ -83,13 +90,14 @@ pub async fn new_psql_backend(storage: BackendStoragePool) -> Result<Backend, Mi
             revoke(secret_creds_revoke).renew(secret_creds_renew),
         )
         .layer(Extension(ctx))
-        .build()
-        .into_service();
+        .layer(Extension(storage_view))
+        .build();
 
     let migrations = migration_scripts::<Migrations>()?;
 
     Ok(Backend {
-        handler: router,
+        paths: router.paths(),
+        handler: router.into_service(),
         category: BackendCategory::Logical,
         variant: BackendType::Postgres,
         migrations,
@@ -121,10 +129,16 @@ impl Context {
             Ok(res) => Ok(res),
             Err(lock) => {
                 drop(lock);
-                match self.connection_repo.get().await? {
-                    Some(_) => self.handle_missing_pool_for_configured_connection().await,
-                    None => Err(ErrorType::MissingConnection.into()),
+                if self.connection_repo.get().await?.is_none() {
+                    return Err(ErrorType::MissingConnection.into());
+                }
+                // The pool is not set up on startup if the connection config
+                // was only moved to the storage view by a migration after it
+                if self.set_pool().await.is_err() {
+                    return self.handle_missing_pool_for_configured_connection().await;
                 }
+                RwLockReadGuard::try_map(self.db.read().await, Option::as_ref)
+                    .map_err(|_| ErrorType::MissingConnection.into())
             }
         }
     }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
@@ -18,7 +18,7 @@ use covert_types::{
     mount::MountConfig,
     psql::RoleCredentials,
     response::{LeaseRenewRevokeEndpoint, LeaseResponse, Response},
-    ttl::calculate_ttl,
+    ttl::{compute_ttl, TtlLimits},
 };
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -51,7 +51,11 @@ pub async fn generate_role_credentials(
 
     let now = Utc::now();
     let issued_at = now;
-    let ttl = calculate_ttl(now, issued_at, &config, params.ttl)
+    let limits = TtlLimits {
+        role_max: role.max_ttl(),
+        ..Default::default()
+    };
+    let ttl = compute_ttl(now, issued_at, &config, params.ttl, &limits)
         .map_err(|_| ErrorType::InternalError(anyhow::Error::msg("Unable to calculate TTL")))?;
 
     let expiration = now + ttl;
@@ -82,6 +86,8 @@ pub async fn generate_role_credentials(
             ttl: Some(ttl.to_std().map_err(|_| {
                 ErrorType::InternalError(anyhow::Error::msg("Unable to create TTL"))
             })?),
+            max_ttl: role.max_ttl(),
+            tags: BTreeMap::from([("role".to_string(), name.clone())]),
         };
     Ok(Response::Lease(lease))
 }
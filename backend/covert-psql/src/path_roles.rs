@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::error::Error;
 
@@ -14,6 +14,16 @@ use covert_types::{
 pub struct RoleEntry {
     pub sql: String,
     pub revocation_sql: String,
+    /// Max TTL in milliseconds of the generated credentials
+    pub max_ttl: Option<i64>,
+}
+
+impl RoleEntry {
+    #[must_use]
+    pub fn max_ttl(&self) -> Option<Duration> {
+        self.max_ttl
+            .map(|millis| Duration::from_millis(u64::try_from(millis).unwrap_or(0)))
+    }
 }
 
 #[tracing::instrument(skip_all, fields(role_name = name, role = ?body))]
@@ -28,10 +38,14 @@ pub async fn path_role_create(
     let role = RoleEntry {
         sql: body.sql,
         revocation_sql: body.revocation_sql,
+        max_ttl: body
+            .max_ttl
+            .map(|max_ttl| i64::try_from(max_ttl.as_millis()).unwrap_or(i64::MAX)),
     };
     b.role_repo.create(&name, &role).await?;
 
     let resp = CreateRoleResponse {
+        max_ttl: role.max_ttl(),
         sql: role.sql,
         revocation_sql: role.revocation_sql,
     };
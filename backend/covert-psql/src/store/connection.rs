@@ -1,52 +1,40 @@
-use covert_storage::BackendStoragePool;
+use covert_storage::StorageView;
 use covert_types::psql::ConnectionConfig;
 
 use crate::error::Error;
 
-pub const CONNECTION_TABLE: &str = "CONNECTION";
+/// Key of the connection config in the storage view of the mount.
+const CONNECTION_KEY: &str = "config/connection";
 
 pub struct ConnectionStore {
-    pool: BackendStoragePool,
+    view: StorageView,
 }
 
 impl ConnectionStore {
-    pub fn new(pool: BackendStoragePool) -> Self {
-        Self { pool }
+    pub fn new(view: StorageView) -> Self {
+        Self { view }
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn set(&self, conn: &ConnectionConfig) -> Result<(), Error> {
-        self.pool
-            .query(
-                &format!("INSERT OR REPLACE INTO {CONNECTION_TABLE} (lock, connection_url, max_open_connections) 
-                    VALUES (?, ?, ?)"),
-            )?
-            .bind(1)
-            .bind(&conn.connection_url)
-            .bind(conn.max_open_connections)
-            .execute()
+        let conn = serde_json::to_vec(conn)?;
+        self.view
+            .put(CONNECTION_KEY, conn)
             .await
-            .map(|_| ())
             .map_err(Into::into)
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn get(&self) -> Result<Option<ConnectionConfig>, Error> {
-        self.pool
-            .query(&format!("SELECT * FROM {CONNECTION_TABLE}"))?
-            .fetch_optional()
-            .await
-            .map_err(Into::into)
+        match self.view.get(CONNECTION_KEY).await? {
+            Some(conn) => Ok(Some(serde_json::from_slice(&conn)?)),
+            None => Ok(None),
+        }
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn remove(&self) -> Result<bool, Error> {
-        self.pool
-            .query(&format!("DELETE FROM {CONNECTION_TABLE}"))?
-            .execute()
-            .await
-            .map(|res| res.rows_affected() == 1)
-            .map_err(Into::into)
+        self.view.delete(CONNECTION_KEY).await.map_err(Into::into)
     }
 }
 
@@ -54,7 +42,9 @@ impl ConnectionStore {
 pub mod tests {
     use std::sync::Arc;
 
-    use covert_storage::{migrator::migrate_backend, BackendStoragePool, EncryptedPool};
+    use covert_storage::{
+        migrator::migrate_backend, BackendStoragePool, EncryptedPool, StorageView,
+    };
     use covert_types::psql::ConnectionConfig;
 
     use crate::{store::connection::ConnectionStore, Migrations};
@@ -72,7 +62,7 @@ pub mod tests {
     #[sqlx::test]
     async fn crud() {
         let pool = setup_context().await;
-        let store = ConnectionStore::new(pool);
+        let store = ConnectionStore::new(StorageView::new(pool));
 
         assert!(store.get().await.unwrap().is_none());
 
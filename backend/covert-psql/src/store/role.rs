@@ -17,12 +17,13 @@ impl RoleStore {
     pub async fn create(&self, name: &str, role: &RoleEntry) -> Result<bool, Error> {
         self.pool
             .query(&format!(
-                "INSERT INTO {ROLES_TABLE} (name, sql, revocation_sql) 
-                    VALUES (?, ?, ?)"
+                "INSERT INTO {ROLES_TABLE} (name, sql, revocation_sql, max_ttl) 
+                    VALUES (?, ?, ?, ?)"
             ))?
             .bind(name)
             .bind(&role.sql)
             .bind(&role.revocation_sql)
+            .bind(role.max_ttl)
             .execute()
             .await
             .map(|res| res.rows_affected() == 1)
@@ -33,7 +34,7 @@ impl RoleStore {
     pub async fn get(&self, name: &str) -> Result<Option<RoleEntry>, Error> {
         self.pool
             .query(&format!(
-                "SELECT sql, revocation_sql FROM {ROLES_TABLE} WHERE name = ?"
+                "SELECT sql, revocation_sql, max_ttl FROM {ROLES_TABLE} WHERE name = ?"
             ))?
             .bind(name)
             .fetch_optional()
@@ -61,6 +62,7 @@ mod tests {
         let role = RoleEntry {
             sql: "SELECT ..".into(),
             revocation_sql: "UPDATE ..".into(),
+            max_ttl: Some(60_000),
         };
         assert!(store.create(role_name, &role).await.is_ok());
         assert_eq!(store.get(role_name).await.unwrap(), Some(role.clone()));
@@ -37,6 +37,7 @@ async fn create_role(sdk: &Client) -> String {
             &CreateRoleParams {
                 sql: role_sql.to_string(),
                 revocation_sql: role_revocation_sql.to_string(),
+                max_ttl: None,
             },
         )
         .await
@@ -119,6 +120,7 @@ async fn restore_connnection_after_seal() {
         .initialize(&InitializeParams {
             shares: 1,
             threshold: 1,
+            stored_shares: None,
         })
         .await
         .unwrap()
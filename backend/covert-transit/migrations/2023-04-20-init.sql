@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS KEYS (
+    name TEXT PRIMARY KEY,
+    -- AES-256-GCM key
+    key BLOB NOT NULL,
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    created_time TIMESTAMP NOT NULL
+);
@@ -0,0 +1,120 @@
+//! Encryption with the keys of the engine. A ciphertext is
+//! [`CIPHERTEXT_PREFIX`] followed by the base64 encoded nonce and the
+//! AES-256-GCM ciphertext. The context of the plaintext is authenticated as
+//! associated data but not part of the ciphertext.
+
+use aes_gcm::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, Aes256Gcm, KeyInit, Nonce,
+};
+use covert_types::methods::transit::CIPHERTEXT_PREFIX;
+
+use crate::error::ErrorType;
+
+/// Length in bytes of the nonce prepended to the ciphertext.
+const NONCE_LEN: usize = 12;
+
+/// A new random AES-256-GCM key.
+pub fn generate_key() -> Vec<u8> {
+    Aes256Gcm::generate_key(&mut OsRng).to_vec()
+}
+
+pub fn encrypt(key: &[u8], plaintext: &[u8], context: &[u8]) -> Result<String, ErrorType> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| ErrorType::Encryption)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: context,
+            },
+        )
+        .map_err(|_| ErrorType::Encryption)?;
+
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    Ok(format!("{CIPHERTEXT_PREFIX}{}", base64::encode(data)))
+}
+
+pub fn decrypt(key: &[u8], ciphertext: &str, context: &[u8]) -> Result<Vec<u8>, ErrorType> {
+    let data = ciphertext
+        .strip_prefix(CIPHERTEXT_PREFIX)
+        .and_then(|data| base64::decode(data).ok())
+        .filter(|data| data.len() > NONCE_LEN)
+        .ok_or(ErrorType::InvalidCiphertext)?;
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| ErrorType::Encryption)?;
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: context,
+            },
+        )
+        .map_err(|_| ErrorType::InvalidCiphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let key = generate_key();
+        let ciphertext = encrypt(&key, b"hunter2", b"").unwrap();
+        assert!(ciphertext.starts_with(CIPHERTEXT_PREFIX));
+        assert!(!ciphertext.contains("hunter2"));
+        assert_eq!(decrypt(&key, &ciphertext, b"").unwrap(), b"hunter2");
+
+        // Every encryption uses a new nonce
+        assert_ne!(encrypt(&key, b"hunter2", b"").unwrap(), ciphertext);
+
+        let ciphertext = encrypt(&key, b"hunter2", b"db/password").unwrap();
+        assert_eq!(
+            decrypt(&key, &ciphertext, b"db/password").unwrap(),
+            b"hunter2"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_ciphertexts() {
+        let key = generate_key();
+        let ciphertext = encrypt(&key, b"hunter2", b"db/password").unwrap();
+
+        // Another key
+        assert!(matches!(
+            decrypt(&generate_key(), &ciphertext, b"db/password"),
+            Err(ErrorType::InvalidCiphertext)
+        ));
+        // Another context
+        for context in [&b""[..], b"db/username"] {
+            assert!(matches!(
+                decrypt(&key, &ciphertext, context),
+                Err(ErrorType::InvalidCiphertext)
+            ));
+        }
+        // Modified ciphertext
+        let mut data = base64::decode(&ciphertext[CIPHERTEXT_PREFIX.len()..]).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        let modified = format!("{CIPHERTEXT_PREFIX}{}", base64::encode(data));
+        assert!(matches!(
+            decrypt(&key, &modified, b"db/password"),
+            Err(ErrorType::InvalidCiphertext)
+        ));
+        // Not a ciphertext of the engine
+        for ciphertext in [
+            "hunter2",
+            "covert:v1:",
+            "covert:v1:not base64",
+            "covert:v1:AAAA",
+        ] {
+            assert!(matches!(
+                decrypt(&key, ciphertext, b""),
+                Err(ErrorType::InvalidCiphertext)
+            ));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::Display;
+
+use covert_types::error::{ApiError, ErrorCode, StatusCode};
+use thiserror::Error;
+use tracing_error::SpanTrace;
+
+#[derive(Error, Debug)]
+pub enum ErrorType {
+    #[error("Internal error")]
+    Storage(#[from] sqlx::Error),
+    #[error("Bad request")]
+    BadRequest(#[from] serde_json::Error),
+    #[error("Key `{name}` was not found")]
+    KeyNotFound { name: String },
+    #[error("Key `{name}` already exists")]
+    KeyExists { name: String },
+    #[error("Key `{name}` is disabled")]
+    KeyDisabled { name: String },
+    #[error("Plaintext must be base64 encoded")]
+    InvalidPlaintext,
+    #[error("Context must be base64 encoded")]
+    InvalidContext,
+    #[error("Invalid ciphertext")]
+    InvalidCiphertext,
+    #[error("Failed to encrypt")]
+    Encryption,
+}
+
+#[derive(Error, Debug)]
+pub struct Error {
+    pub variant: ErrorType,
+    pub span_trace: SpanTrace,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n{}", self.variant, self.span_trace)
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<ErrorType> for Error {
+    fn from(err: ErrorType) -> Self {
+        Self {
+            variant: err,
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let (status_code, code) = match err.variant {
+            ErrorType::Storage(_) | ErrorType::Encryption => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+            ErrorType::BadRequest(_)
+            | ErrorType::InvalidPlaintext
+            | ErrorType::InvalidContext
+            | ErrorType::InvalidCiphertext
+            | ErrorType::KeyDisabled { .. } => (StatusCode::BAD_REQUEST, ErrorCode::BadRequest),
+            ErrorType::KeyNotFound { .. } => (StatusCode::NOT_FOUND, ErrorCode::NotFound),
+            ErrorType::KeyExists { .. } => (StatusCode::CONFLICT, ErrorCode::Conflict),
+        };
+
+        ApiError {
+            error: err.variant.into(),
+            code,
+            details: vec![],
+            status_code,
+            retry_after: None,
+            span_trace: Some(err.span_trace),
+        }
+    }
+}
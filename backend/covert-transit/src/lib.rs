@@ -0,0 +1,212 @@
+#![forbid(unsafe_code)]
+#![forbid(clippy::unwrap_used)]
+#![deny(clippy::pedantic)]
+#![deny(clippy::get_unwrap)]
+#![allow(clippy::module_name_repetitions)]
+
+mod cipher;
+mod error;
+mod store;
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use covert_framework::{
+    extract::{Extension, Json, Path},
+    read, update, Backend, Router,
+};
+use covert_storage::{
+    migrator::{migration_scripts, MigrationError},
+    BackendStoragePool, StorageView,
+};
+use covert_types::{
+    backend::{BackendCategory, BackendType},
+    methods::transit::{
+        DecryptParams, DecryptResponse, EncryptParams, EncryptResponse, KeyResponse,
+        UpdateKeyParams,
+    },
+    response::Response,
+};
+use error::{Error, ErrorType};
+use rust_embed::RustEmbed;
+use store::keys::{Key, KeysRepo};
+
+pub struct Context {
+    keys_repo: KeysRepo,
+}
+
+#[derive(RustEmbed)]
+#[folder = "migrations/"]
+struct Migrations;
+
+/// Returns a new transit secret engine. It encrypts and decrypts data with
+/// named keys that never leave the engine.
+///
+/// # Errors
+///
+/// Returns an error if it fails to read the migration scripts.
+pub fn new_transit_backend(pool: BackendStoragePool) -> Result<Backend, MigrationError> {
+    let storage_view = StorageView::new(pool.clone());
+    let ctx = Context {
+        keys_repo: KeysRepo::new(pool),
+    };
+
+    let router = Router::new()
+        .route(
+            "/keys/*name",
+            read(read_key)
+                .create(create_key)
+                .update(update_key)
+                .delete(delete_key)
+                .help("Create a new random key, read it, disable or enable it, or remove it."),
+        )
+        .route(
+            "/encrypt/*name",
+            update(encrypt)
+                .create(encrypt)
+                .help("Encrypt base64 encoded plaintext with the key and an optional context."),
+        )
+        .route(
+            "/decrypt/*name",
+            update(decrypt).create(decrypt).help(
+                "Decrypt a ciphertext of the key and its context into base64 encoded plaintext.",
+            ),
+        )
+        .leaseless()
+        .layer(Extension(Arc::new(ctx)))
+        .layer(Extension(storage_view))
+        .build();
+
+    let migrations = migration_scripts::<Migrations>()?;
+
+    Ok(Backend {
+        paths: router.paths(),
+        handler: router.into_service(),
+        category: BackendCategory::Logical,
+        variant: BackendType::Transit,
+        migrations,
+    })
+}
+
+impl From<Key> for KeyResponse {
+    fn from(key: Key) -> Self {
+        Self {
+            name: key.name,
+            disabled: key.disabled,
+            created_time: key.created_time,
+        }
+    }
+}
+
+/// Decoded context of the plaintext, empty if there is none.
+fn decode_context(context: Option<&str>) -> Result<Vec<u8>, Error> {
+    context.map_or_else(
+        || Ok(Vec::new()),
+        |context| base64::decode(context).map_err(|_| ErrorType::InvalidContext.into()),
+    )
+}
+
+/// The key, if it exists and is not disabled.
+async fn usable_key(ctx: &Context, name: String) -> Result<Key, Error> {
+    let key = ctx
+        .keys_repo
+        .get(&name)
+        .await?
+        .ok_or_else(|| ErrorType::KeyNotFound { name: name.clone() })?;
+    if key.disabled {
+        return Err(ErrorType::KeyDisabled { name }.into());
+    }
+    Ok(key)
+}
+
+#[tracing::instrument(skip_all)]
+async fn create_key(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let key = Key {
+        name,
+        key: cipher::generate_key(),
+        disabled: false,
+        created_time: Utc::now(),
+    };
+    if !ctx.keys_repo.create(&key).await? {
+        return Err(ErrorType::KeyExists { name: key.name }.into());
+    }
+
+    Response::raw(KeyResponse::from(key)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn read_key(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let key = ctx
+        .keys_repo
+        .get(&name)
+        .await?
+        .ok_or(ErrorType::KeyNotFound { name })?;
+
+    Response::raw(KeyResponse::from(key)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn update_key(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(name): Path<String>,
+    Json(body): Json<UpdateKeyParams>,
+) -> Result<Response, Error> {
+    if !ctx.keys_repo.set_disabled(&name, body.disabled).await? {
+        return Err(ErrorType::KeyNotFound { name }.into());
+    }
+    let key = ctx
+        .keys_repo
+        .get(&name)
+        .await?
+        .ok_or(ErrorType::KeyNotFound { name })?;
+
+    Response::raw(KeyResponse::from(key)).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_key(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    if !ctx.keys_repo.remove(&name).await? {
+        return Err(ErrorType::KeyNotFound { name }.into());
+    }
+
+    Ok(Response::ok())
+}
+
+#[tracing::instrument(skip_all)]
+async fn encrypt(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(name): Path<String>,
+    Json(body): Json<EncryptParams>,
+) -> Result<Response, Error> {
+    let key = usable_key(&ctx, name).await?;
+    let plaintext = base64::decode(&body.plaintext).map_err(|_| ErrorType::InvalidPlaintext)?;
+    let context = decode_context(body.context.as_deref())?;
+    let ciphertext = cipher::encrypt(&key.key, &plaintext, &context)?;
+
+    Response::raw(EncryptResponse { ciphertext }).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn decrypt(
+    Extension(ctx): Extension<Arc<Context>>,
+    Path(name): Path<String>,
+    Json(body): Json<DecryptParams>,
+) -> Result<Response, Error> {
+    let key = usable_key(&ctx, name).await?;
+    let context = decode_context(body.context.as_deref())?;
+    let plaintext = cipher::decrypt(&key.key, &body.ciphertext, &context)?;
+
+    Response::raw(DecryptResponse {
+        plaintext: base64::encode(plaintext),
+    })
+    .map_err(Into::into)
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use covert_storage::BackendStoragePool;
+
+use crate::error::Error;
+
+const KEYS_TABLE: &str = "KEYS";
+
+/// Named AES-256-GCM key of the engine.
+#[derive(Debug, sqlx::FromRow, PartialEq, Eq, Clone)]
+pub struct Key {
+    pub name: String,
+    pub key: Vec<u8>,
+    pub disabled: bool,
+    pub created_time: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct KeysRepo {
+    pool: BackendStoragePool,
+}
+
+impl KeysRepo {
+    pub fn new(pool: BackendStoragePool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores a new key. Returns `false` if a key with the same name exists.
+    #[tracing::instrument(skip_all)]
+    pub async fn create(&self, key: &Key) -> Result<bool, Error> {
+        self.pool
+            .query(&format!(
+                "INSERT OR IGNORE INTO {KEYS_TABLE} (name, key, disabled, created_time)
+                    VALUES ($1, $2, $3, $4)"
+            ))?
+            .bind(&key.name)
+            .bind(&key.key)
+            .bind(key.disabled)
+            .bind(key.created_time)
+            .execute()
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get(&self, name: &str) -> Result<Option<Key>, Error> {
+        self.pool
+            .query(&format!("SELECT * FROM {KEYS_TABLE} WHERE name = $1"))?
+            .bind(name)
+            .fetch_optional()
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Returns `false` if the key does not exist.
+    #[tracing::instrument(skip_all)]
+    pub async fn set_disabled(&self, name: &str, disabled: bool) -> Result<bool, Error> {
+        self.pool
+            .query(&format!(
+                "UPDATE {KEYS_TABLE} SET disabled = $1 WHERE name = $2"
+            ))?
+            .bind(disabled)
+            .bind(name)
+            .execute()
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+
+    /// Returns `false` if the key does not exist.
+    #[tracing::instrument(skip_all)]
+    pub async fn remove(&self, name: &str) -> Result<bool, Error> {
+        self.pool
+            .query(&format!("DELETE FROM {KEYS_TABLE} WHERE name = $1"))?
+            .bind(name)
+            .execute()
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use covert_storage::{migrator::migrate_backend, EncryptedPool};
+
+    use crate::Migrations;
+
+    use super::*;
+
+    async fn pool() -> BackendStoragePool {
+        let pool = Arc::new(EncryptedPool::new_tmp());
+
+        let storage = BackendStoragePool::new("foo_", pool);
+
+        migrate_backend::<Migrations>(&storage).await.unwrap();
+
+        storage
+    }
+
+    fn key(name: &str) -> Key {
+        Key {
+            name: name.into(),
+            key: vec![7; 32],
+            disabled: false,
+            created_time: Utc::now(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn crud() {
+        let store = KeysRepo::new(pool().await);
+        let app = key("app");
+
+        assert!(store.create(&app).await.unwrap());
+        assert_eq!(store.get("app").await.unwrap(), Some(app.clone()));
+        // The key of an existing name is never replaced
+        assert!(!store.create(&key("app")).await.unwrap());
+        assert_eq!(store.get("app").await.unwrap(), Some(app.clone()));
+
+        assert!(store.set_disabled("app", true).await.unwrap());
+        assert!(store.get("app").await.unwrap().unwrap().disabled);
+        assert!(!store.set_disabled("unknown", true).await.unwrap());
+
+        assert!(store.remove("app").await.unwrap());
+        assert!(!store.remove("app").await.unwrap());
+        assert_eq!(store.get("app").await.unwrap(), None);
+    }
+}
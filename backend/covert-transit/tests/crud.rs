@@ -0,0 +1,200 @@
+use covert_sdk::{
+    mounts::BackendType,
+    transit::{DecryptParams, EncryptParams, UpdateKeyParams},
+};
+use covert_system::testing::TestServer;
+
+const MOUNT_PATH: &str = "transit/";
+
+async fn start() -> TestServer {
+    TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Transit)
+        .start()
+        .await
+}
+
+#[tokio::test]
+async fn key_lifecycle() {
+    let server = start().await;
+    let sdk = server.sdk();
+
+    // Missing to start with
+    assert!(sdk.transit.read_key(MOUNT_PATH, "app").await.is_err());
+
+    let key = sdk.transit.create_key(MOUNT_PATH, "app").await.unwrap();
+    assert_eq!(key.name, "app");
+    assert!(!key.disabled);
+    assert_eq!(
+        sdk.transit
+            .read_key(MOUNT_PATH, "app")
+            .await
+            .unwrap()
+            .created_time,
+        key.created_time
+    );
+
+    // Keys are never replaced
+    let err = sdk.transit.create_key(MOUNT_PATH, "app").await.unwrap_err();
+    assert!(err.message().contains("already exists"), "{err}");
+
+    let key = sdk
+        .transit
+        .update_key(MOUNT_PATH, "app", &UpdateKeyParams { disabled: true })
+        .await
+        .unwrap();
+    assert!(key.disabled);
+
+    sdk.transit.delete_key(MOUNT_PATH, "app").await.unwrap();
+    assert!(sdk.transit.read_key(MOUNT_PATH, "app").await.is_err());
+    assert!(sdk.transit.delete_key(MOUNT_PATH, "app").await.is_err());
+}
+
+#[tokio::test]
+async fn encrypt_and_decrypt() {
+    let server = start().await;
+    let sdk = server.sdk();
+    sdk.transit.create_key(MOUNT_PATH, "app").await.unwrap();
+
+    let plaintext = base64::encode("hunter2");
+    let resp = sdk
+        .transit
+        .encrypt(
+            MOUNT_PATH,
+            "app",
+            &EncryptParams {
+                plaintext: plaintext.clone(),
+                context: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert!(resp.ciphertext.starts_with("covert:v1:"));
+
+    let decrypted = sdk
+        .transit
+        .decrypt(
+            MOUNT_PATH,
+            "app",
+            &DecryptParams {
+                ciphertext: resp.ciphertext.clone(),
+                context: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(decrypted.plaintext, plaintext);
+
+    // Ciphertexts of another key are rejected
+    sdk.transit.create_key(MOUNT_PATH, "other").await.unwrap();
+    assert!(sdk
+        .transit
+        .decrypt(
+            MOUNT_PATH,
+            "other",
+            &DecryptParams {
+                ciphertext: resp.ciphertext.clone(),
+                context: None,
+            },
+        )
+        .await
+        .is_err());
+
+    // Disabled keys can be used for neither
+    sdk.transit
+        .update_key(MOUNT_PATH, "app", &UpdateKeyParams { disabled: true })
+        .await
+        .unwrap();
+    let err = sdk
+        .transit
+        .encrypt(
+            MOUNT_PATH,
+            "app",
+            &EncryptParams {
+                plaintext,
+                context: None,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("disabled"), "{err}");
+    let err = sdk
+        .transit
+        .decrypt(
+            MOUNT_PATH,
+            "app",
+            &DecryptParams {
+                ciphertext: resp.ciphertext,
+                context: None,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("disabled"), "{err}");
+}
+
+#[tokio::test]
+async fn ciphertexts_are_bound_to_their_context() {
+    let server = start().await;
+    let sdk = server.sdk();
+    sdk.transit.create_key(MOUNT_PATH, "app").await.unwrap();
+
+    let plaintext = base64::encode("hunter2");
+    let context = Some(base64::encode("kv/:app:password"));
+    let resp = sdk
+        .transit
+        .encrypt(
+            MOUNT_PATH,
+            "app",
+            &EncryptParams {
+                plaintext: plaintext.clone(),
+                context: context.clone(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let decrypted = sdk
+        .transit
+        .decrypt(
+            MOUNT_PATH,
+            "app",
+            &DecryptParams {
+                ciphertext: resp.ciphertext.clone(),
+                context,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(decrypted.plaintext, plaintext);
+
+    // Neither another context nor a missing one decrypts the ciphertext
+    for context in [Some(base64::encode("kv/:app:username")), None] {
+        assert!(sdk
+            .transit
+            .decrypt(
+                MOUNT_PATH,
+                "app",
+                &DecryptParams {
+                    ciphertext: resp.ciphertext.clone(),
+                    context,
+                },
+            )
+            .await
+            .is_err());
+    }
+
+    // Contexts must be base64 encoded
+    let err = sdk
+        .transit
+        .encrypt(
+            MOUNT_PATH,
+            "app",
+            &EncryptParams {
+                plaintext,
+                context: Some("not base64!".to_string()),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("base64"), "{err}");
+}
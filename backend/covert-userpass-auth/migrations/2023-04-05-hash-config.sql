@@ -0,0 +1,9 @@
+-- Parameters used to hash new passwords. Defaults are used until configured.
+CREATE TABLE IF NOT EXISTS HASH_CONFIG (
+    lock INTEGER PRIMARY KEY DEFAULT 1,
+    -- JSON encoded algorithm and parameters
+    config TEXT NOT NULL,
+
+    -- Used to ensure that maximum one config is ever inserted
+    CONSTRAINT CONFIG_LOCK CHECK (lock=1)
+);
@@ -0,0 +1,11 @@
+-- The hash config is stored as JSON under the `config/hash` key of the
+-- storage view
+CREATE TABLE IF NOT EXISTS STORAGE_VIEW (
+    key TEXT PRIMARY KEY,
+    value BLOB NOT NULL
+);
+
+INSERT INTO STORAGE_VIEW (key, value)
+    SELECT 'config/hash', CAST(config AS BLOB) FROM HASH_CONFIG;
+
+DROP TABLE HASH_CONFIG;
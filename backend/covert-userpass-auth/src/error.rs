@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
-use covert_types::error::{ApiError, StatusCode};
+use covert_storage::StorageViewError;
+use covert_types::error::{ApiError, ErrorCode, StatusCode};
 use thiserror::Error;
 use tracing_error::SpanTrace;
 
@@ -8,6 +9,8 @@ use tracing_error::SpanTrace;
 pub enum ErrorType {
     #[error("Internal error")]
     Storage(#[from] sqlx::Error),
+    #[error("Internal error")]
+    StorageView(#[from] StorageViewError),
     #[error("Bad request")]
     BadRequest(#[from] serde_json::Error),
     #[error("User with username: `{username}` not found")]
@@ -16,6 +19,8 @@ pub enum ErrorType {
     IncorrectPassword,
     #[error("Unsupported password")]
     UnsupportedPassword,
+    #[error("Invalid password hash config: {0}")]
+    InvalidHashConfig(String),
 }
 
 #[derive(Error, Debug)]
@@ -39,6 +44,15 @@ impl From<sqlx::Error> for Error {
     }
 }
 
+impl From<StorageViewError> for Error {
+    fn from(err: StorageViewError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Self {
@@ -59,16 +73,25 @@ impl From<ErrorType> for Error {
 
 impl From<Error> for ApiError {
     fn from(err: Error) -> Self {
-        let status_code = match err.variant {
-            ErrorType::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            ErrorType::BadRequest(_) | ErrorType::UnsupportedPassword => StatusCode::BAD_REQUEST,
-            ErrorType::UserNotFound { .. } => StatusCode::NOT_FOUND,
-            ErrorType::IncorrectPassword { .. } => StatusCode::UNAUTHORIZED,
+        let (status_code, code) = match err.variant {
+            ErrorType::Storage(_) | ErrorType::StorageView(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+            ErrorType::BadRequest(_)
+            | ErrorType::UnsupportedPassword
+            | ErrorType::InvalidHashConfig(_) => (StatusCode::BAD_REQUEST, ErrorCode::BadRequest),
+            ErrorType::UserNotFound { .. } => (StatusCode::NOT_FOUND, ErrorCode::NotFound),
+            ErrorType::IncorrectPassword { .. } => {
+                (StatusCode::UNAUTHORIZED, ErrorCode::PermissionDenied)
+            }
         };
 
         ApiError {
             error: err.variant.into(),
+            code,
+            details: vec![],
             status_code,
+            retry_after: None,
             span_trace: Some(err.span_trace),
         }
     }
@@ -0,0 +1,362 @@
+use argon2::Argon2;
+use covert_types::methods::userpass::PasswordHashConfig;
+use password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use scrypt::Scrypt;
+
+use crate::error::{Error, ErrorType};
+
+// Parameters below the floor are rejected as too weak. The ceilings keep a
+// single login from exhausting the memory or CPU of the server.
+const MIN_ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+const MIN_ARGON2_ITERATIONS: u32 = 2;
+const MAX_ARGON2_ITERATIONS: u32 = 16;
+const MAX_ARGON2_PARALLELISM: u32 = 16;
+const MIN_SCRYPT_LOG_N: u8 = 14;
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MIN_SCRYPT_R: u32 = 8;
+const MAX_SCRYPT_P: u32 = 16;
+const MIN_BCRYPT_COST: u32 = 10;
+const MAX_BCRYPT_COST: u32 = 16;
+const MAX_HASH_MEMORY: u64 = 1 << 30;
+
+/// Check that the parameters are within the safety floor and ceiling.
+pub fn validate(config: &PasswordHashConfig) -> Result<(), Error> {
+    let invalid = |msg: String| Err(ErrorType::InvalidHashConfig(msg).into());
+    match *config {
+        PasswordHashConfig::Argon2 {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => {
+            if memory_kib < MIN_ARGON2_MEMORY_KIB {
+                return invalid(format!(
+                    "argon2 `memory_kib` must be at least {MIN_ARGON2_MEMORY_KIB}"
+                ));
+            }
+            if !(MIN_ARGON2_ITERATIONS..=MAX_ARGON2_ITERATIONS).contains(&iterations) {
+                return invalid(format!(
+                    "argon2 `iterations` must be between {MIN_ARGON2_ITERATIONS} and {MAX_ARGON2_ITERATIONS}"
+                ));
+            }
+            if !(1..=MAX_ARGON2_PARALLELISM).contains(&parallelism) {
+                return invalid(format!(
+                    "argon2 `parallelism` must be between 1 and {MAX_ARGON2_PARALLELISM}"
+                ));
+            }
+            if u64::from(memory_kib) << 10 > MAX_HASH_MEMORY {
+                return invalid(format!(
+                    "argon2 parameters must use at most {} MiB of memory",
+                    MAX_HASH_MEMORY >> 20
+                ));
+            }
+            Ok(())
+        }
+        PasswordHashConfig::Scrypt { log_n, r, p } => {
+            if !(MIN_SCRYPT_LOG_N..=MAX_SCRYPT_LOG_N).contains(&log_n) {
+                return invalid(format!(
+                    "scrypt `log_n` must be between {MIN_SCRYPT_LOG_N} and {MAX_SCRYPT_LOG_N}"
+                ));
+            }
+            if r < MIN_SCRYPT_R {
+                return invalid(format!("scrypt `r` must be at least {MIN_SCRYPT_R}"));
+            }
+            if !(1..=MAX_SCRYPT_P).contains(&p) {
+                return invalid(format!("scrypt `p` must be between 1 and {MAX_SCRYPT_P}"));
+            }
+            if scrypt_memory(log_n, r, p) > MAX_HASH_MEMORY {
+                return invalid(format!(
+                    "scrypt parameters must use at most {} MiB of memory",
+                    MAX_HASH_MEMORY >> 20
+                ));
+            }
+            Ok(())
+        }
+        PasswordHashConfig::Bcrypt { cost } => {
+            if (MIN_BCRYPT_COST..=MAX_BCRYPT_COST).contains(&cost) {
+                Ok(())
+            } else {
+                invalid(format!(
+                    "bcrypt `cost` must be between {MIN_BCRYPT_COST} and {MAX_BCRYPT_COST}"
+                ))
+            }
+        }
+    }
+}
+
+/// Memory in bytes used by scrypt when the `p` lanes are computed in
+/// parallel.
+fn scrypt_memory(log_n: u8, r: u32, p: u32) -> u64 {
+    128 * u64::from(r) * ((1u64 << log_n) + 2) * u64::from(p)
+}
+
+fn argon2(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Argon2<'static>, Error> {
+    let params = argon2::Params::new(memory_kib, iterations, parallelism, None)
+        .map_err(|err| ErrorType::InvalidHashConfig(err.to_string()))?;
+    Ok(Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        params,
+    ))
+}
+
+/// Hash the password in the PHC string format for Argon2id and scrypt, or the
+/// modular crypt format for bcrypt.
+pub fn hash_password(password: &str, config: &PasswordHashConfig) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = match *config {
+        PasswordHashConfig::Argon2 {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => argon2(memory_kib, iterations, parallelism)?.hash_password(password.as_bytes(), &salt),
+        PasswordHashConfig::Scrypt { log_n, r, p } => {
+            let params = scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN)
+                .map_err(|err| ErrorType::InvalidHashConfig(err.to_string()))?;
+            Scrypt.hash_password_customized(password.as_bytes(), None, None, params, &salt)
+        }
+        PasswordHashConfig::Bcrypt { cost } => {
+            return bcrypt::hash(password, cost).map_err(|_| ErrorType::UnsupportedPassword.into());
+        }
+    };
+    hash.map(|hash| hash.to_string())
+        .map_err(|_| ErrorType::UnsupportedPassword.into())
+}
+
+/// Parameters of a stored hash.
+enum StoredParams {
+    Argon2 {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+    Bcrypt {
+        cost: u32,
+    },
+}
+
+impl StoredParams {
+    fn parse(hash: &str) -> Option<Self> {
+        if hash.starts_with("$2") {
+            // `$2b$<cost>$<salt and hash>`
+            let cost = hash.split('$').nth(2)?.parse().ok()?;
+            return Some(Self::Bcrypt { cost });
+        }
+
+        let hash = PasswordHash::new(hash).ok()?;
+        if hash.algorithm == argon2::ARGON2ID_IDENT {
+            let params = argon2::Params::try_from(&hash).ok()?;
+            Some(Self::Argon2 {
+                memory_kib: params.m_cost(),
+                iterations: params.t_cost(),
+                parallelism: params.p_cost(),
+            })
+        } else if hash.algorithm == scrypt::ALG_ID {
+            let params = scrypt::Params::try_from(&hash).ok()?;
+            Some(Self::Scrypt {
+                log_n: params.log_n(),
+                r: params.r(),
+                p: params.p(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Verify the password against a hash created with any supported algorithm
+/// and parameters.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$2") {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+    let Ok(hash) = PasswordHash::new(hash) else {
+        return false;
+    };
+    // The parameters are read from the hash, not from the hashers
+    hash.verify_password(&[&Argon2::default(), &Scrypt], password)
+        .is_ok()
+}
+
+/// Whether the hash should be replaced by a hash with the configured
+/// parameters. Hashes using another algorithm or weaker parameters than the
+/// configuration are rehashed.
+pub fn needs_rehash(hash: &str, config: &PasswordHashConfig) -> bool {
+    match (StoredParams::parse(hash), *config) {
+        (
+            Some(StoredParams::Argon2 {
+                memory_kib,
+                iterations,
+                parallelism,
+            }),
+            PasswordHashConfig::Argon2 {
+                memory_kib: config_memory_kib,
+                iterations: config_iterations,
+                parallelism: config_parallelism,
+            },
+        ) => {
+            memory_kib < config_memory_kib
+                || iterations < config_iterations
+                || parallelism < config_parallelism
+        }
+        (
+            Some(StoredParams::Scrypt { log_n, r, p }),
+            PasswordHashConfig::Scrypt {
+                log_n: config_log_n,
+                r: config_r,
+                p: config_p,
+            },
+        ) => log_n < config_log_n || r < config_r || p < config_p,
+        (Some(StoredParams::Bcrypt { cost }), PasswordHashConfig::Bcrypt { cost: config_cost }) => {
+            cost < config_cost
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARGON2: PasswordHashConfig = PasswordHashConfig::Argon2 {
+        memory_kib: MIN_ARGON2_MEMORY_KIB,
+        iterations: 2,
+        parallelism: 1,
+    };
+
+    const SCRYPT: PasswordHashConfig = PasswordHashConfig::Scrypt {
+        log_n: 14,
+        r: 8,
+        p: 1,
+    };
+
+    #[test]
+    fn hash_and_verify() {
+        for config in [ARGON2, SCRYPT, PasswordHashConfig::Bcrypt { cost: 10 }] {
+            let hash = hash_password("password", &config).unwrap();
+            assert!(verify_password("password", &hash));
+            assert!(!verify_password("wrong", &hash));
+            assert!(!needs_rehash(&hash, &config));
+        }
+        let hash = hash_password("password", &ARGON2).unwrap();
+        assert!(
+            hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"),
+            "{hash}"
+        );
+        let hash = hash_password("password", &SCRYPT).unwrap();
+        assert!(hash.starts_with("$scrypt$ln=14,r=8,p=1$"), "{hash}");
+        // Salted
+        assert_ne!(hash, hash_password("password", &SCRYPT).unwrap());
+        assert!(!verify_password("password", "not a hash"));
+    }
+
+    #[test]
+    fn verify_existing_scrypt_hashes() {
+        // Written before the hashes were created by the `scrypt` crate
+        let hash = "$scrypt$ln=14,r=8,p=1$AAECAwQFBgcICQoLDA0ODw$6iMJXpgeItuXSS3ial5ceU6o+LQA0aKIA8ORmTlhNMU";
+        assert!(verify_password("password", hash));
+        assert!(!verify_password("wrong", hash));
+        assert!(!needs_rehash(hash, &SCRYPT));
+    }
+
+    #[test]
+    fn rehash_weaker_hashes() {
+        let legacy = bcrypt::hash("password", 8).unwrap();
+        assert!(verify_password("password", &legacy));
+        assert!(needs_rehash(&legacy, &SCRYPT));
+        assert!(needs_rehash(
+            &legacy,
+            &PasswordHashConfig::Bcrypt { cost: 10 }
+        ));
+
+        let hash = hash_password("password", &SCRYPT).unwrap();
+        assert!(needs_rehash(
+            &hash,
+            &PasswordHashConfig::Scrypt {
+                log_n: 15,
+                r: 8,
+                p: 1
+            }
+        ));
+        assert!(needs_rehash(
+            &hash,
+            &PasswordHashConfig::Bcrypt { cost: 10 }
+        ));
+        assert!(needs_rehash(&hash, &ARGON2));
+
+        let hash = hash_password("password", &ARGON2).unwrap();
+        assert!(needs_rehash(
+            &hash,
+            &PasswordHashConfig::Argon2 {
+                memory_kib: MIN_ARGON2_MEMORY_KIB,
+                iterations: 3,
+                parallelism: 1,
+            }
+        ));
+        assert!(needs_rehash(&hash, &SCRYPT));
+    }
+
+    #[test]
+    fn reject_weak_parameters() {
+        assert!(validate(&PasswordHashConfig::default()).is_ok());
+        assert!(validate(&ARGON2).is_ok());
+        assert!(validate(&SCRYPT).is_ok());
+        for config in [
+            PasswordHashConfig::Argon2 {
+                memory_kib: 4096,
+                iterations: 2,
+                parallelism: 1,
+            },
+            PasswordHashConfig::Argon2 {
+                memory_kib: MIN_ARGON2_MEMORY_KIB,
+                iterations: 1,
+                parallelism: 1,
+            },
+            PasswordHashConfig::Argon2 {
+                memory_kib: MIN_ARGON2_MEMORY_KIB,
+                iterations: 2,
+                parallelism: 0,
+            },
+            PasswordHashConfig::Argon2 {
+                memory_kib: 2 * 1024 * 1024,
+                iterations: 2,
+                parallelism: 1,
+            },
+            PasswordHashConfig::Scrypt {
+                log_n: 10,
+                r: 8,
+                p: 1,
+            },
+            PasswordHashConfig::Scrypt {
+                log_n: 14,
+                r: 4,
+                p: 1,
+            },
+            PasswordHashConfig::Scrypt {
+                log_n: 14,
+                r: 8,
+                p: 0,
+            },
+            PasswordHashConfig::Scrypt {
+                log_n: 20,
+                r: 16,
+                p: 1,
+            },
+            PasswordHashConfig::Bcrypt { cost: 8 },
+            PasswordHashConfig::Bcrypt { cost: 31 },
+        ] {
+            assert!(validate(&config).is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn scrypt_memory_ceiling_counts_parallelism() {
+        let config = |p| PasswordHashConfig::Scrypt { log_n: 17, r: 8, p };
+        assert!(validate(&config(1)).is_ok());
+        assert!(validate(&config(16)).is_err());
+    }
+}
@@ -5,39 +5,41 @@
 #![allow(clippy::module_name_repetitions)]
 
 mod error;
+mod hash;
 mod store;
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use bcrypt::{hash, verify};
 use covert_framework::{
     create, delete,
     extract::{Extension, Json, Path},
-    update, update_with_config, Backend, RouteConfig, Router,
+    lockout::{lockout_routes, with_lockout_migration, LoginLockout},
+    read, update, update_with_config, Backend, RouteConfig, Router,
 };
 use covert_storage::{
     migrator::{migration_scripts, MigrationError},
-    BackendStoragePool,
+    BackendStoragePool, StorageView,
 };
 use covert_types::{
     backend::{BackendCategory, BackendType},
+    error::ApiError,
     methods::userpass::{
-        CreateUserParams, CreateUserResponse, ListUsersResponse, LoginParams, RemoveUserResponse,
-        UpdateUserPasswordParams, UpdateUserPasswordResponse, UserListItem,
+        CreateUserParams, CreateUserResponse, ListUsersResponse, LoginParams, PasswordHashConfig,
+        RemoveUserResponse, UpdateUserPasswordParams, UpdateUserPasswordResponse, UserListItem,
     },
     response::Response,
 };
 use covert_types::{mount::MountConfig, response::AuthResponse};
 use error::{Error, ErrorType};
+use hash::{hash_password, needs_rehash, verify_password};
 use rust_embed::RustEmbed;
 use serde::{Deserialize, Serialize};
-use store::user::UsersRepo;
-
-// TODO: maybe increase this
-const DEFAULT_COST: u32 = 8;
+use store::{config::HashConfigRepo, user::UsersRepo};
 
 pub struct Context {
     users_repo: UsersRepo,
+    hash_config_repo: HashConfigRepo,
+    lockout: Arc<LoginLockout>,
 }
 
 #[derive(RustEmbed)]
@@ -56,27 +58,39 @@ pub struct User {
 ///
 /// Returns an error if it fails to read the migration scripts.
 pub fn new_userpass_backend(pool: BackendStoragePool) -> Result<Backend, MigrationError> {
+    let storage_view = StorageView::new(pool.clone());
+    let lockout = Arc::new(LoginLockout::new(pool.clone()));
     let ctx = Context {
         users_repo: UsersRepo::new(pool),
+        hash_config_repo: HashConfigRepo::new(storage_view.clone()),
+        lockout: Arc::clone(&lockout),
     };
 
-    let router = Router::new()
+    let router = lockout_routes(Router::new())
         .route(
             "/login",
             update_with_config(login, RouteConfig::unauthenticated())
                 .create_with_config(login, RouteConfig::unauthenticated()),
         )
+        .route(
+            "/config/hash",
+            read(read_hash_config)
+                .update(update_hash_config)
+                .create(update_hash_config),
+        )
         .route("/users", create(create_user).read(list_users))
         .route("/users/:username", delete(remove_user))
         .route("/users/:username/password", update(update_user_password))
         .layer(Extension(Arc::new(ctx)))
-        .build()
-        .into_service();
+        .layer(Extension(lockout))
+        .layer(Extension(storage_view))
+        .build();
 
-    let migrations = migration_scripts::<Migrations>()?;
+    let migrations = with_lockout_migration(migration_scripts::<Migrations>()?)?;
 
     Ok(Backend {
-        handler: router,
+        paths: router.paths(),
+        handler: router.into_service(),
         category: BackendCategory::Credential,
         variant: BackendType::Userpass,
         migrations,
@@ -97,7 +111,7 @@ async fn user_by_username_and_password(
             username: username.to_string(),
         })?;
 
-    if !matches!(verify(password, &user.password), Ok(true)) {
+    if !verify_password(password, &user.password) {
         return Err(ErrorType::IncorrectPassword.into());
     }
 
@@ -109,23 +123,81 @@ async fn login(
     Json(params): Json<LoginParams>,
     Extension(config): Extension<MountConfig>,
     Extension(ctx): Extension<Arc<Context>>,
-) -> Result<Response, Error> {
-    let _user = user_by_username_and_password(&ctx, &params.username, &params.password).await?;
+) -> Result<Response, ApiError> {
+    ctx.lockout.check(&params.username).await?;
+    match user_by_username_and_password(&ctx, &params.username, &params.password).await {
+        Ok(user) => {
+            ctx.lockout.record_success(&params.username).await?;
+            rehash_if_needed(&ctx, &user, &params.password).await;
+        }
+        Err(err) => {
+            if matches!(
+                err.variant,
+                ErrorType::IncorrectPassword | ErrorType::UserNotFound { .. }
+            ) {
+                ctx.lockout.record_failure(&params.username).await?;
+            }
+            return Err(err.into());
+        }
+    }
 
     let auth = AuthResponse {
+        metadata: HashMap::from([("username".to_string(), params.username.clone())]),
         alias: params.username,
         ttl: Some(config.default_lease_ttl),
+        policies: vec![],
+        group_policies: vec![],
+        create_entity: false,
+        renewable: params.renewable,
     };
     Ok(Response::Auth(auth))
 }
 
+/// Replace the stored hash if it was created with weaker parameters than the
+/// current config. The password is only available in plaintext on login, so
+/// this is the only chance to upgrade the hash. Failures are logged and do not
+/// fail the login.
+#[tracing::instrument(skip_all, fields(username = user.username))]
+async fn rehash_if_needed(ctx: &Context, user: &User, password: &str) {
+    let res = async {
+        let hash_config = ctx.hash_config_repo.get().await?;
+        if needs_rehash(&user.password, &hash_config) {
+            let password = hash_password(password, &hash_config)?;
+            ctx.users_repo
+                .update_password(&user.username, &password)
+                .await?;
+        }
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(error) = res {
+        tracing::warn!(?error, "Failed to rehash password");
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn read_hash_config(Extension(ctx): Extension<Arc<Context>>) -> Result<Response, Error> {
+    let config = ctx.hash_config_repo.get().await?;
+    Response::raw(config).map_err(Into::into)
+}
+
+#[tracing::instrument(skip_all)]
+async fn update_hash_config(
+    Json(config): Json<PasswordHashConfig>,
+    Extension(ctx): Extension<Arc<Context>>,
+) -> Result<Response, Error> {
+    hash::validate(&config)?;
+    ctx.hash_config_repo.set(&config).await?;
+    Response::raw(config).map_err(Into::into)
+}
+
 #[tracing::instrument(skip_all, fields(username = params.username))]
 async fn create_user(
     Json(params): Json<CreateUserParams>,
     Extension(ctx): Extension<Arc<Context>>,
 ) -> Result<Response, Error> {
-    let password =
-        hash(&params.password, DEFAULT_COST).map_err(|_| ErrorType::UnsupportedPassword)?;
+    let hash_config = ctx.hash_config_repo.get().await?;
+    let password = hash_password(&params.password, &hash_config)?;
     let user = User {
         username: params.username,
         password,
@@ -160,8 +232,8 @@ async fn update_user_password(
     Extension(ctx): Extension<Arc<Context>>,
 ) -> Result<Response, Error> {
     let _user = user_by_username_and_password(&ctx, &username, &params.password).await?;
-    let new_password =
-        hash(&params.new_password, DEFAULT_COST).map_err(|_| ErrorType::UnsupportedPassword)?;
+    let hash_config = ctx.hash_config_repo.get().await?;
+    let new_password = hash_password(&params.new_password, &hash_config)?;
     ctx.users_repo
         .update_password(&username, &new_password)
         .await?;
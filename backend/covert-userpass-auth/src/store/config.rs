@@ -0,0 +1,53 @@
+use covert_storage::StorageView;
+use covert_types::methods::userpass::PasswordHashConfig;
+
+use crate::error::Error;
+
+/// Key of the hash config in the storage view of the mount.
+const HASH_CONFIG_KEY: &str = "config/hash";
+
+#[derive(Debug)]
+pub struct HashConfigRepo {
+    view: StorageView,
+}
+
+impl HashConfigRepo {
+    pub fn new(view: StorageView) -> Self {
+        Self { view }
+    }
+
+    /// The configured parameters, or the defaults if not configured.
+    #[tracing::instrument(skip_all)]
+    pub async fn get(&self) -> Result<PasswordHashConfig, Error> {
+        match self.view.get(HASH_CONFIG_KEY).await? {
+            Some(config) => serde_json::from_slice(&config).map_err(Into::into),
+            None => Ok(PasswordHashConfig::default()),
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn set(&self, config: &PasswordHashConfig) -> Result<(), Error> {
+        let config = serde_json::to_vec(config)?;
+        self.view
+            .put(HASH_CONFIG_KEY, config)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::user::tests::pool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn get_and_set() {
+        let repo = HashConfigRepo::new(StorageView::new(pool().await));
+        assert_eq!(repo.get().await.unwrap(), PasswordHashConfig::default());
+
+        let config = PasswordHashConfig::Bcrypt { cost: 12 };
+        repo.set(&config).await.unwrap();
+        assert_eq!(repo.get().await.unwrap(), config);
+    }
+}
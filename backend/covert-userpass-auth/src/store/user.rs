@@ -77,7 +77,7 @@ impl UsersRepo {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::sync::Arc;
 
     use covert_storage::{migrator::migrate_backend, EncryptedPool};
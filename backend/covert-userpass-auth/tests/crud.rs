@@ -69,6 +69,7 @@ async fn crud() {
             &LoginParams {
                 username: username.to_string(),
                 password: password.to_string(),
+                renewable: true,
             },
         )
         .await;
@@ -109,6 +110,7 @@ async fn crud() {
             &LoginParams {
                 username: username.to_string(),
                 password: password.to_string(),
+                renewable: true,
             },
         )
         .await;
@@ -122,10 +124,11 @@ async fn crud() {
             &LoginParams {
                 username: username.to_string(),
                 password: new_password.to_string(),
+                renewable: true,
             },
         )
         .await;
-    assert!(resp.is_ok());
+    let auth = resp.unwrap();
 
     let resp = sdk.userpass.remove(MOUNT_PATH, username).await.unwrap();
     assert_eq!(resp.username, username);
@@ -141,8 +144,15 @@ async fn crud() {
             &LoginParams {
                 username: username.to_string(),
                 password: new_password.to_string(),
+                renewable: true,
             },
         )
         .await;
     assert!(resp.is_err());
+
+    // The token issued on login carries the alias metadata
+    sdk.set_token(Some(auth.token.to_string())).await;
+    let resp = sdk.token.lookup_self().await.unwrap();
+    assert_eq!(resp.entity_name, entity_name);
+    assert_eq!(resp.metadata["username"], username);
 }
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use covert_sdk::{
+    entity::{AttachEntityAliasParams, CreateEntityParams, EntityAlias},
+    mounts::BackendType,
+    userpass::{CreateUserParams, LoginParams},
+};
+use covert_system::testing::TestServer;
+
+const MOUNT_PATH: &str = "auth/userpass/";
+
+#[tokio::test]
+async fn login_token_expires() {
+    let server = TestServer::builder()
+        .mount(MOUNT_PATH, BackendType::Userpass)
+        .start()
+        .await;
+    let sdk = server.sdk();
+
+    let username = "foo";
+    let password = "foo_pass";
+    sdk.userpass
+        .create(
+            MOUNT_PATH,
+            &CreateUserParams {
+                username: username.to_string(),
+                password: password.to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: username.to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: username.to_string(),
+            aliases: vec![EntityAlias {
+                name: username.to_string(),
+                mount_path: MOUNT_PATH.to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let auth = sdk
+        .userpass
+        .login(
+            MOUNT_PATH,
+            &LoginParams {
+                username: username.to_string(),
+                password: password.to_string(),
+                renewable: false,
+            },
+        )
+        .await
+        .unwrap();
+    server.assert_leases(MOUNT_PATH, 1).await;
+    let client = server.client(&auth.token.to_string()).await;
+
+    // Valid until the TTL of the mount has passed
+    server
+        .advance_time(auth.lease_duration - Duration::from_secs(60))
+        .await;
+    assert!(client.token.lookup_self().await.is_ok());
+
+    server.advance_time(Duration::from_secs(60)).await;
+    server.assert_lease_revoked(&auth.lease_id).await;
+    server.assert_leases(MOUNT_PATH, 0).await;
+    assert!(client.token.lookup_self().await.is_err());
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use covert_sdk::{
+    entity::{AttachEntityAliasParams, CreateEntityParams, EntityAlias},
+    userpass::{CreateUserParams, LoginParams, PasswordHashConfig, UpdateUserPasswordParams},
+    ErrorCode,
+};
+
+use crate::common::{setup_unseal, MOUNT_PATH};
+
+#[tokio::test]
+async fn configure_password_hashing() {
+    let sdk = setup_unseal().await;
+
+    assert_eq!(
+        sdk.userpass.read_hash_config(MOUNT_PATH).await.unwrap(),
+        PasswordHashConfig::default()
+    );
+
+    let weak = PasswordHashConfig::Bcrypt { cost: 4 };
+    let err = sdk
+        .userpass
+        .set_hash_config(MOUNT_PATH, &weak)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    let bcrypt = PasswordHashConfig::Bcrypt { cost: 10 };
+    sdk.userpass
+        .set_hash_config(MOUNT_PATH, &bcrypt)
+        .await
+        .unwrap();
+    assert_eq!(
+        sdk.userpass.read_hash_config(MOUNT_PATH).await.unwrap(),
+        bcrypt
+    );
+
+    sdk.userpass
+        .create(
+            MOUNT_PATH,
+            &CreateUserParams {
+                username: "foo".to_string(),
+                password: "bar".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: "foo".to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: "foo".to_string(),
+            aliases: vec![EntityAlias {
+                name: "foo".to_string(),
+                mount_path: MOUNT_PATH.to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    // Passwords hashed with the previous config keep working and are rehashed
+    // on login
+    let scrypt = PasswordHashConfig::Scrypt {
+        log_n: 14,
+        r: 8,
+        p: 1,
+    };
+    sdk.userpass
+        .set_hash_config(MOUNT_PATH, &scrypt)
+        .await
+        .unwrap();
+    let login = LoginParams {
+        username: "foo".to_string(),
+        password: "bar".to_string(),
+        renewable: true,
+    };
+    for _ in 0..2 {
+        assert!(sdk.userpass.login(MOUNT_PATH, &login).await.is_ok());
+    }
+
+    sdk.userpass
+        .update_password(
+            MOUNT_PATH,
+            "foo",
+            &UpdateUserPasswordParams {
+                password: "bar".to_string(),
+                new_password: "baz".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    let login = LoginParams {
+        password: "baz".to_string(),
+        ..login
+    };
+    assert!(sdk.userpass.login(MOUNT_PATH, &login).await.is_ok());
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::time::Duration;
+
+use covert_sdk::{
+    entity::{AttachEntityAliasParams, CreateEntityParams, EntityAlias},
+    lockout::LockoutConfig,
+    userpass::{CreateUserParams, LoginParams},
+    ErrorCode,
+};
+
+use crate::common::{setup_unseal, MOUNT_PATH};
+
+#[tokio::test]
+async fn lockout_after_failed_logins() {
+    let sdk = setup_unseal().await;
+
+    // Enabled by default
+    assert_eq!(
+        sdk.lockout.read_config(MOUNT_PATH).await.unwrap(),
+        LockoutConfig::default()
+    );
+
+    let config = LockoutConfig {
+        attempts: 2,
+        window: Duration::from_secs(60),
+        lockout_duration: Duration::from_secs(60),
+    };
+    sdk.lockout.set_config(MOUNT_PATH, &config).await.unwrap();
+    assert_eq!(sdk.lockout.read_config(MOUNT_PATH).await.unwrap(), config);
+
+    sdk.userpass
+        .create(
+            MOUNT_PATH,
+            &CreateUserParams {
+                username: "foo".to_string(),
+                password: "bar".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: "foo".to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: "foo".to_string(),
+            aliases: vec![EntityAlias {
+                name: "foo".to_string(),
+                mount_path: MOUNT_PATH.to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let wrong_login = LoginParams {
+        username: "foo".to_string(),
+        password: "wrong".to_string(),
+        renewable: true,
+    };
+    let login = LoginParams {
+        username: "foo".to_string(),
+        password: "bar".to_string(),
+        renewable: true,
+    };
+
+    for _ in 0..config.attempts {
+        let err = sdk
+            .userpass
+            .login(MOUNT_PATH, &wrong_login)
+            .await
+            .unwrap_err();
+        assert!(err.message().contains("Incorrect password"), "{err}");
+    }
+
+    // Locked even with the correct password
+    let err = sdk.userpass.login(MOUNT_PATH, &login).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::LockedOut));
+    assert!(
+        err.message().contains("Too many failed login attempts"),
+        "{err}"
+    );
+
+    let locked = sdk.lockout.locked(MOUNT_PATH).await.unwrap();
+    assert_eq!(locked.aliases.len(), 1);
+    assert_eq!(locked.aliases[0].alias, "foo");
+    assert_eq!(locked.aliases[0].failed_attempts, config.attempts);
+
+    // Operator unlocks the user
+    sdk.lockout.unlock(MOUNT_PATH, "foo").await.unwrap();
+    assert!(sdk
+        .lockout
+        .locked(MOUNT_PATH)
+        .await
+        .unwrap()
+        .aliases
+        .is_empty());
+    assert!(sdk.userpass.login(MOUNT_PATH, &login).await.is_ok());
+
+    // A successful login resets the counter
+    sdk.userpass
+        .login(MOUNT_PATH, &wrong_login)
+        .await
+        .unwrap_err();
+    sdk.userpass.login(MOUNT_PATH, &login).await.unwrap();
+    sdk.userpass
+        .login(MOUNT_PATH, &wrong_login)
+        .await
+        .unwrap_err();
+    assert!(sdk.userpass.login(MOUNT_PATH, &login).await.is_ok());
+}
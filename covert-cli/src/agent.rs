@@ -0,0 +1,283 @@
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use clap::Args;
+use covert_sdk::{
+    ldap,
+    token::{AuthResponse, RenewTokenSelfParams},
+    userpass, Client, Error, ErrorCode,
+};
+use tempfile::NamedTempFile;
+use tokio::{
+    sync::watch,
+    time::{sleep_until, Instant},
+};
+
+use crate::{login::LoginMethod, template::Templates};
+
+/// First wait before retrying a failed login or renewal, doubled on every
+/// failure.
+pub const MIN_BACKOFF: Duration = Duration::from_secs(1);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Args, Debug)]
+pub struct Agent {
+    #[arg(
+        long,
+        value_enum,
+        default_value = "userpass",
+        help = "auth method to log in with"
+    )]
+    method: LoginMethod,
+    #[arg(short, long)]
+    username: String,
+    #[arg(
+        long,
+        help = "file with the password, read again on every login so it can be rotated"
+    )]
+    password_file: PathBuf,
+    #[arg(long, help = "path of the auth method, `auth/<method>/` by default")]
+    path: Option<String>,
+    #[arg(long, help = "file the token is written to, with mode 0600")]
+    sink: PathBuf,
+    #[arg(
+        long,
+        help = "revoke the token and remove the sink on SIGTERM or SIGINT"
+    )]
+    revoke_on_exit: bool,
+    #[arg(
+        long,
+        help = "TOML file with a `[[template]]` block for every file to render with secrets"
+    )]
+    templates: Option<PathBuf>,
+}
+
+impl Agent {
+    pub async fn handle(self, sdk: &Client) {
+        // Never use a token of the environment, the tokens of the agent are
+        // not children of it
+        sdk.set_token(None).await;
+        let templates = self.templates.as_deref().map(|path| {
+            Templates::load(path).unwrap_or_else(|e| {
+                eprintln!("Error: {e:#}");
+                process::exit(1);
+            })
+        });
+        let (logged_in, ready) = watch::channel(false);
+        let render = async move {
+            match &templates {
+                Some(templates) => templates.render(sdk, ready).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            () = self.run(sdk, &logged_in) => (),
+            () = render => (),
+            () = covert_system::shutdown_signal() => self.shutdown(sdk).await,
+        }
+    }
+
+    /// Log in and keep the token valid, until interrupted.
+    async fn run(&self, sdk: &Client, logged_in: &watch::Sender<bool>) {
+        loop {
+            let auth = self.login(sdk).await;
+            sdk.set_token(Some(auth.token.to_string())).await;
+            eprintln!(
+                "Logged in, the token expires in {}",
+                humantime::format_duration(auth.lease_duration)
+            );
+            if let Err(e) = write_file(&self.sink, auth.token.to_string().as_bytes(), 0o600) {
+                // The token is useless to the application without the sink
+                eprintln!("Error: {e:#}");
+                process::exit(1);
+            }
+            logged_in.send_replace(true);
+            self.keep_renewed(sdk, &auth).await;
+        }
+    }
+
+    /// Log in, retrying with backoff until it succeeds, e.g. while the server
+    /// is restarting or sealed.
+    async fn login(&self, sdk: &Client) -> AuthResponse {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match self.try_login(sdk).await {
+                Ok(auth) => return auth,
+                Err(e) => {
+                    eprintln!(
+                        "Login failed: {e:#}, retrying in {}",
+                        humantime::format_duration(backoff)
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    async fn try_login(&self, sdk: &Client) -> anyhow::Result<AuthResponse> {
+        let password = fs::read_to_string(&self.password_file)
+            .with_context(|| format!("failed to read `{}`", self.password_file.display()))?;
+        let password = password.trim_end_matches(['\r', '\n']).to_string();
+        let username = self.username.clone();
+        // The new token must not be a child of the expired one
+        sdk.set_token(None).await;
+        let resp = match self.method {
+            LoginMethod::Userpass => {
+                let path = self.path.as_deref().unwrap_or("auth/userpass/");
+                let params = userpass::LoginParams {
+                    username,
+                    password,
+                    renewable: true,
+                };
+                sdk.userpass.login(path, &params).await
+            }
+            LoginMethod::Ldap => {
+                let path = self.path.as_deref().unwrap_or("auth/ldap/");
+                let params = ldap::LoginParams {
+                    password,
+                    renewable: true,
+                };
+                sdk.ldap.login(path, &username, &params).await
+            }
+        };
+        match resp {
+            Ok(auth) => Ok(auth),
+            Err(Error::MfaRequired(_)) => {
+                eprintln!("Error: the auth method requires MFA, which the agent cannot complete");
+                process::exit(1);
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Renew the token when two thirds of its TTL have passed. Returns once
+    /// the token cannot be renewed anymore and a new one has to be issued,
+    /// i.e. when it is not renewable, reached its max TTL, expired or was
+    /// revoked.
+    async fn keep_renewed(&self, sdk: &Client, auth: &AuthResponse) {
+        let increment = auth.lease_duration;
+        let mut expires_at = Instant::now() + auth.lease_duration;
+        sleep_until(renew_at(Instant::now(), expires_at)).await;
+        if !auth.renewable {
+            eprintln!("The token is not renewable, logging in again");
+            return;
+        }
+
+        let mut renewable = true;
+        let mut backoff = MIN_BACKOFF;
+
+        while renewable {
+            let params = RenewTokenSelfParams {
+                increment: Some(increment),
+            };
+            match sdk.token.renew_self(&params).await {
+                Ok(resp) => {
+                    backoff = MIN_BACKOFF;
+                    let Some(ttl) = remaining(&resp.lease.expire_time) else {
+                        eprintln!(
+                            "Error: the token was renewed until `{}`, which could not be read",
+                            resp.lease.expire_time
+                        );
+                        return;
+                    };
+                    expires_at = Instant::now() + ttl;
+                    // The TTL is capped by the max TTL of the token
+                    renewable = resp.lease.renewable && ttl + MIN_BACKOFF >= increment;
+                    eprintln!(
+                        "Renewed the token, it expires in {}",
+                        humantime::format_duration(round(ttl))
+                    );
+                    sleep_until(renew_at(Instant::now(), expires_at)).await;
+                }
+                // Revoked, expired or the lease is gone
+                Err(e)
+                    if matches!(
+                        e.code(),
+                        Some(ErrorCode::PermissionDenied | ErrorCode::NotFound)
+                    ) =>
+                {
+                    eprintln!("The token cannot be renewed anymore: {e}");
+                    return;
+                }
+                Err(e) => {
+                    if Instant::now() + backoff >= expires_at {
+                        eprintln!("Renewal failed: {e}, the token expired");
+                        return;
+                    }
+                    eprintln!(
+                        "Renewal failed: {e}, retrying in {}",
+                        humantime::format_duration(backoff)
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+        eprintln!("The token reached its max TTL, logging in again");
+    }
+
+    async fn shutdown(&self, sdk: &Client) {
+        if !self.revoke_on_exit {
+            return;
+        }
+        match sdk.token.revoke_self().await {
+            Ok(_) => eprintln!("Revoked the token"),
+            // Not logged in yet or already expired
+            Err(e) if e.code() == Some(ErrorCode::PermissionDenied) => (),
+            Err(e) => eprintln!("Error: failed to revoke the token: {e}"),
+        }
+        if let Err(e) = fs::remove_file(&self.sink) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Error: failed to remove `{}`: {e}", self.sink.display());
+            }
+        }
+    }
+}
+
+/// Replace the file with a file with the mode holding the contents. The file
+/// is replaced at once so applications never read a partially written file.
+pub fn write_file(path: &Path, contents: &[u8], mode: u32) -> anyhow::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut file = NamedTempFile::new_in(dir)
+        .with_context(|| format!("failed to create a file in `{}`", dir.display()))?;
+    file.as_file()
+        .set_permissions(fs::Permissions::from_mode(mode))
+        .context("failed to set the mode of the file")?;
+    file.write_all(contents)
+        .context("failed to write the file")?;
+    file.persist(path)
+        .with_context(|| format!("failed to write `{}`", path.display()))?;
+    Ok(())
+}
+
+/// When two thirds of the time until the token expires have passed.
+fn renew_at(now: Instant, expires_at: Instant) -> Instant {
+    now + expires_at.saturating_duration_since(now) * 2 / 3
+}
+
+/// Time until the expire time of a lease returned by the server, which is in
+/// UTC.
+fn remaining(expire_time: &str) -> Option<Duration> {
+    let expire_time = expire_time.replace("+00:00", "Z");
+    let expires_at = humantime::parse_rfc3339_weak(&expire_time).ok()?;
+    Some(
+        expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    )
+}
+
+fn round(duration: Duration) -> Duration {
+    Duration::from_secs(duration.as_secs())
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use clap::{Args, Subcommand};
+use covert_sdk::{
+    audit::{AuditTailEvent, AuditTailParams},
+    Client,
+};
+use serde_json::Value;
+
+use crate::output::{self, Format};
+
+/// Wait before following the device again after the stream was interrupted.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Args, Debug)]
+pub struct Audit {
+    #[clap(subcommand)]
+    subcommand: AuditSubcommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuditSubcommands {
+    #[command(
+        about = "follow the entries written by an audit device",
+        long_about = "Print the entries written by an audit device as they are written. The \
+                      entries are the ones written by the device, sensitive values are hashed \
+                      and can be compared with `sys/audit-hash`. The stream is resumed when the \
+                      connection is lost, entries that could not be sent are reported."
+    )]
+    Tail {
+        #[arg(help = "path of the audit device, the first enabled device when unset")]
+        device: Option<String>,
+        #[arg(
+            long,
+            help = "only print requests to the path, a trailing `*` matches any path with the \
+                    prefix, e.g. `secret/*`"
+        )]
+        path: Option<String>,
+        #[arg(long, help = "only print requests of the entity")]
+        entity: Option<String>,
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 10,
+            help = "number of entries written before to start with, before filtering"
+        )]
+        lines: u64,
+    },
+}
+
+impl Audit {
+    pub async fn handle(self, sdk: &Client) {
+        match self.subcommand {
+            AuditSubcommands::Tail {
+                device,
+                path,
+                entity,
+                lines,
+            } => {
+                let device = match device {
+                    Some(device) => device,
+                    None => match sdk.audit.list().await {
+                        Ok(resp) => match resp.devices.into_iter().next() {
+                            Some(device) => device.path,
+                            None => {
+                                println!("Error: no audit device is enabled");
+                                return;
+                            }
+                        },
+                        Err(e) => {
+                            println!("Error: {e}");
+                            return;
+                        }
+                    },
+                };
+                let filter = TailFilter { path, entity };
+                tail(sdk, &device, &filter, lines).await;
+            }
+        }
+    }
+}
+
+struct TailFilter {
+    path: Option<String>,
+    entity: Option<String>,
+}
+
+impl TailFilter {
+    fn matches(&self, entry: &Value) -> bool {
+        let path_matches = self.path.as_deref().is_none_or(|pattern| {
+            let path = entry["request"]["path"].as_str().unwrap_or_default();
+            match pattern.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == pattern,
+            }
+        });
+        let entity_matches = self
+            .entity
+            .as_deref()
+            .is_none_or(|entity| entry["auth"]["entity"] == entity);
+        path_matches && entity_matches
+    }
+}
+
+/// Print the entries of the device until the stream is ended by the server.
+/// Interrupted streams are resumed after the last entry received.
+async fn tail(sdk: &Client, device: &str, filter: &TailFilter, lines: u64) {
+    let mut params = AuditTailParams {
+        last: Some(lines),
+        ..AuditTailParams::default()
+    };
+    let mut connected = false;
+    loop {
+        let mut stream = match sdk.audit.tail(device, &params).await {
+            Ok(stream) => stream,
+            // Only retried once the device could be followed, e.g. not when
+            // the token is not allowed to
+            Err(
+                e @ (covert_sdk::Error::Transport(_) | covert_sdk::Error::RetriesExhausted { .. }),
+            ) if connected => {
+                eprintln!("Connection lost: {e}, reconnecting");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+            Err(e) => {
+                println!("Error: {e}");
+                return;
+            }
+        };
+        connected = true;
+
+        loop {
+            match stream.next().await {
+                Ok(Some(AuditTailEvent::Start { epoch })) => params.epoch = Some(epoch),
+                Ok(Some(AuditTailEvent::Entry { seq, entry })) => {
+                    params.since = Some(seq);
+                    if filter.matches(&entry) {
+                        print_entry(&entry);
+                    }
+                }
+                Ok(Some(AuditTailEvent::Gap { missed })) => match missed {
+                    Some(missed) => eprintln!("-- {missed} entries missed --"),
+                    None => eprintln!("-- the device was reopened, entries may be missed --"),
+                },
+                Ok(None) => {
+                    eprintln!("The audit device `{device}` is no longer enabled");
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Connection lost: {e}, reconnecting");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Print the entry on one line, formatted for humans when the output is a
+/// table and as JSON otherwise.
+fn print_entry(entry: &Value) {
+    if output::format() != Format::Table {
+        println!("{entry}");
+        return;
+    }
+    let text = |value: &Value| value.as_str().unwrap_or("-").to_string();
+    let request = &entry["request"];
+    let mut line = format!(
+        "{time} {entry_type:<8} {operation:<6} {namespace}/{path} entity={entity} policy={policy}",
+        time = text(&entry["time"]),
+        entry_type = text(&entry["type"]),
+        operation = text(&request["operation"]),
+        namespace = text(&request["namespace"]),
+        path = text(&request["path"]),
+        entity = text(&entry["auth"]["entity"]),
+        policy = text(&entry["auth"]["policy_result"]),
+    );
+    if let Some(error) = entry.get("error") {
+        line.push_str(&format!(
+            " error={}: {}",
+            text(&error["code"]),
+            text(&error["message"])
+        ));
+    }
+    println!("{line}");
+}
@@ -1,8 +1,8 @@
-use std::{str::FromStr, time::Duration};
+use std::str::FromStr;
 
 use clap::Subcommand;
 use covert_sdk::{
-    mounts::{BackendType, CreateMountParams, MountConfig, UpdateMountParams},
+    mounts::{BackendType, CreateMountParams, ListingVisibility, TuneMountParams},
     Client,
 };
 
@@ -36,6 +36,13 @@ pub enum AuthSubcommand {
         default_lease_ttl: Option<humantime::Duration>,
         #[arg(long, help = "the default TTL for token issed by this auth method")]
         max_lease_ttl: Option<humantime::Duration>,
+        #[arg(long, help = "what the auth method is used for, empty to remove it")]
+        description: Option<String>,
+        #[arg(
+            long,
+            help = "who sees the mount in the listing: default, hidden or unauth"
+        )]
+        listing_visibility: Option<ListingVisibility>,
     },
     #[command(about = "list auth methods")]
     List,
@@ -66,16 +73,18 @@ impl Auth {
                 path,
                 default_lease_ttl,
                 max_lease_ttl,
+                description,
+                listing_visibility,
             } => {
-                let mut config = MountConfig::default();
-                if let Some(ttl) = default_lease_ttl {
-                    config.default_lease_ttl = Duration::from_millis(ttl.as_millis() as u64);
-                }
-                if let Some(ttl) = max_lease_ttl {
-                    config.max_lease_ttl = Duration::from_millis(ttl.as_millis() as u64);
-                }
-
-                let resp = sdk.mount.update(&path, &UpdateMountParams { config }).await;
+                // Only the given settings are changed
+                let params = TuneMountParams {
+                    default_lease_ttl: default_lease_ttl.map(Into::into),
+                    max_lease_ttl: max_lease_ttl.map(Into::into),
+                    description,
+                    listing_visibility,
+                    ..Default::default()
+                };
+                let resp = sdk.mount.tune(&path, &params).await;
                 handle_resp(resp);
             }
             AuthSubcommand::List => {
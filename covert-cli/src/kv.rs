@@ -1,10 +1,16 @@
-use std::error::Error;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+};
 
+use anyhow::{bail, Context};
 use clap::{Args, Subcommand};
 use covert_sdk::{
     kv::{
-        CreateSecretParams, HardDeleteSecretParams, RecoverSecretParams, SetConfigParams,
-        SoftDeleteSecretParams,
+        CreateSecretParams, HardDeleteSecretParams, PatchSecretParams, RecoverSecretParams,
+        SetConfigParams, SoftDeleteSecretParams,
     },
     Client,
 };
@@ -18,7 +24,7 @@ pub struct Kv {
 }
 
 /// Parse a single key-value pair
-fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
+pub(crate) fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
 where
     T: std::str::FromStr,
     T::Err: Error + Send + Sync + 'static,
@@ -31,52 +37,134 @@ where
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+/// Path of a secret, `<mount>/<key>`.
+#[derive(Args, Debug)]
+pub struct SecretPath {
+    #[arg(help = "path of the secret, `<mount>/<key>`")]
+    path: String,
+    #[arg(
+        short,
+        long,
+        help = "path where the KV backend is mounted, the whole path is the key if set"
+    )]
+    mount: Option<String>,
+}
+
+impl SecretPath {
+    /// Split the path into the mount and the key. The mount is the first
+    /// segment of the path unless it is given explicitly, e.g. for mounts
+    /// with more than one segment.
+    fn split(&self) -> (String, String) {
+        if let Some(mount) = &self.mount {
+            return (mount.clone(), self.path.trim_start_matches('/').to_string());
+        }
+        let path = self.path.trim_start_matches('/');
+        match path.split_once('/') {
+            Some((mount, key)) => (format!("{mount}/"), key.to_string()),
+            None => (format!("{path}/"), String::new()),
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum KvSubcommand {
-    #[command(about = "add new secret")]
+    #[command(
+        about = "add new secret version",
+        long_about = "Add a new version of the secret. Values can be read from a file with \
+            `key=@<file>` or from stdin with `key=-`."
+    )]
     Put {
-        #[arg(help = "key to add secret to")]
-        key: String,
-        #[arg(short, long, value_parser = parse_key_val::<String, String>)]
+        #[command(flatten)]
+        path: SecretPath,
+        #[arg(
+            required = true,
+            value_parser = parse_key_val::<String, String>,
+            help = "data of the secret, `key=value`, `key=@file` or `key=-`"
+        )]
         data: Vec<(String, String)>,
-        #[arg(short, long)]
-        path: String,
+        #[arg(long, help = "store the values base64 encoded, e.g. for binary files")]
+        base64: bool,
+    },
+    #[command(
+        about = "update fields of the latest secret version",
+        long_about = "Merge the fields into the latest version of the secret to add a new \
+            version, the other fields are kept. Values are given like for `put`."
+    )]
+    Patch {
+        #[command(flatten)]
+        path: SecretPath,
+        #[arg(
+            value_parser = parse_key_val::<String, String>,
+            help = "fields to set, `key=value`, `key=@file` or `key=-`"
+        )]
+        data: Vec<(String, String)>,
+        #[arg(long, help = "fields to remove")]
+        remove: Vec<String>,
+        #[arg(long, help = "store the values base64 encoded, e.g. for binary files")]
+        base64: bool,
     },
     #[command(about = "retrieve secret")]
     Get {
-        #[arg(help = "key to retrieve")]
-        key: String,
-        #[arg(short, long)]
-        path: String,
+        #[command(flatten)]
+        path: SecretPath,
+        #[arg(short, long, help = "only print the raw value of the field")]
+        field: Option<String>,
         #[arg(short, long)]
         version: Option<u32>,
+        #[arg(
+            long,
+            requires = "field",
+            help = "decode the base64 encoded value of the field"
+        )]
+        base64: bool,
+    },
+    #[command(
+        about = "list keys",
+        long_about = "List the keys directly below the prefix. Keys ending with `/` have more \
+            keys below them."
+    )]
+    List {
+        #[command(flatten)]
+        path: SecretPath,
     },
     #[command(about = "soft-delete secret, can be recovered with the \"recover\" subcommand")]
     Delete {
-        #[arg(help = "key to delete")]
-        key: String,
-        #[arg(short, long, use_value_delimiter = true, value_delimiter = ',')]
+        #[command(flatten)]
+        path: SecretPath,
+        #[arg(
+            short,
+            long,
+            use_value_delimiter = true,
+            value_delimiter = ',',
+            help = "versions to delete, the latest version by default"
+        )]
         versions: Vec<u32>,
-        #[arg(short, long)]
-        path: String,
     },
     #[command(about = "hard-delete secret, cannot be recovered")]
     HardDelete {
-        #[arg(help = "key to delete")]
-        key: String,
-        #[arg(short, long, use_value_delimiter = true, value_delimiter = ',')]
+        #[command(flatten)]
+        path: SecretPath,
+        #[arg(
+            short,
+            long,
+            required = true,
+            use_value_delimiter = true,
+            value_delimiter = ','
+        )]
         versions: Vec<u32>,
-        #[arg(short, long)]
-        path: String,
     },
     #[command(about = "recover soft-deleted secret")]
     Recover {
-        #[arg(help = "key to recover")]
-        key: String,
-        #[arg(short, long, use_value_delimiter = true, value_delimiter = ',')]
+        #[command(flatten)]
+        path: SecretPath,
+        #[arg(
+            short,
+            long,
+            required = true,
+            use_value_delimiter = true,
+            value_delimiter = ','
+        )]
         versions: Vec<u32>,
-        #[arg(short, long)]
-        path: String,
     },
     #[command(about = "update config for the kv backend")]
     SetConfig {
@@ -95,38 +183,92 @@ pub enum KvSubcommand {
 impl Kv {
     pub async fn handle(self, sdk: &Client) {
         match self.subcommand {
-            KvSubcommand::Put { key, data, path } => {
+            KvSubcommand::Put { path, data, base64 } => {
+                let (mount, key) = path.split();
+                let data = match read_values(data, base64) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        println!("Error: {e:#}");
+                        return;
+                    }
+                };
                 let resp = sdk
                     .kv
-                    .create(
-                        &path,
-                        &key,
-                        &CreateSecretParams {
-                            data: data.into_iter().collect(),
-                        },
-                    )
+                    .create(&mount, &key, &CreateSecretParams { data })
                     .await;
                 handle_resp(resp);
             }
-            KvSubcommand::Get { key, path, version } => {
-                let resp = sdk.kv.read(&path, &key, version).await;
+            KvSubcommand::Patch {
+                path,
+                data,
+                remove,
+                base64,
+            } => {
+                let (mount, key) = path.split();
+                if data.is_empty() && remove.is_empty() {
+                    println!("Error: no fields to set or remove");
+                    return;
+                }
+                let mut data: HashMap<_, _> = match read_values(data, base64) {
+                    Ok(data) => data.into_iter().map(|(k, v)| (k, Some(v))).collect(),
+                    Err(e) => {
+                        println!("Error: {e:#}");
+                        return;
+                    }
+                };
+                data.extend(remove.into_iter().map(|field| (field, None)));
+                let resp = sdk
+                    .kv
+                    .patch(&mount, &key, &PatchSecretParams { data })
+                    .await;
                 handle_resp(resp);
             }
-            KvSubcommand::Recover {
-                key,
+            KvSubcommand::Get {
                 path,
-                versions,
+                field,
+                version,
+                base64,
             } => {
+                let (mount, key) = path.split();
+                let resp = sdk.kv.read(&mount, &key, version).await;
+                let Some(field) = field else {
+                    handle_resp(resp);
+                    return;
+                };
+                let data = match resp {
+                    Ok(resp) => resp.data.unwrap_or_default(),
+                    Err(e) => {
+                        handle_resp::<()>(Err(e));
+                        return;
+                    }
+                };
+                if let Err(e) = print_field(&data, &field, base64) {
+                    println!("Error: {e:#}");
+                }
+            }
+            KvSubcommand::List { path } => {
+                let (mount, prefix) = path.split();
+                let resp = sdk.kv.list(&mount, &prefix).await;
+                handle_resp(resp);
+            }
+            KvSubcommand::Recover { path, versions } => {
+                let (mount, key) = path.split();
                 let resp = sdk
                     .kv
-                    .recover(&path, &key, &RecoverSecretParams { versions })
+                    .recover(&mount, &key, &RecoverSecretParams { versions })
                     .await;
                 handle_resp(resp);
             }
             KvSubcommand::SetConfig { max_versions, path } => {
                 let resp = sdk
                     .kv
-                    .set_config(&path, &SetConfigParams { max_versions })
+                    .set_config(
+                        &path,
+                        &SetConfigParams {
+                            max_versions,
+                            secret_scan: None,
+                        },
+                    )
                     .await;
                 handle_resp(resp);
             }
@@ -134,28 +276,86 @@ impl Kv {
                 let resp = sdk.kv.read_config(&path).await;
                 handle_resp(resp);
             }
-            KvSubcommand::Delete {
-                key,
-                versions,
-                path,
-            } => {
+            KvSubcommand::Delete { path, versions } => {
+                let (mount, key) = path.split();
+                let versions = if versions.is_empty() {
+                    match sdk.kv.read(&mount, &key, None).await {
+                        Ok(resp) => vec![resp.metadata.version],
+                        Err(e) => {
+                            handle_resp::<()>(Err(e));
+                            return;
+                        }
+                    }
+                } else {
+                    versions
+                };
                 let resp = sdk
                     .kv
-                    .delete(&path, &key, &SoftDeleteSecretParams { versions })
+                    .delete(&mount, &key, &SoftDeleteSecretParams { versions })
                     .await;
                 handle_resp(resp);
             }
-            KvSubcommand::HardDelete {
-                key,
-                versions,
-                path,
-            } => {
+            KvSubcommand::HardDelete { path, versions } => {
+                let (mount, key) = path.split();
                 let resp = sdk
                     .kv
-                    .hard_delete(&path, &key, &HardDeleteSecretParams { versions })
+                    .hard_delete(&mount, &key, &HardDeleteSecretParams { versions })
                     .await;
                 handle_resp(resp);
             }
         }
     }
 }
+
+/// Resolve the `@file` and `-` values and base64 encode them if requested.
+fn read_values(
+    data: Vec<(String, String)>,
+    base64: bool,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut stdin_used = false;
+    let mut values = HashMap::new();
+    for (key, value) in data {
+        let bytes = if value == "-" {
+            if stdin_used {
+                bail!("only one value can be read from stdin");
+            }
+            stdin_used = true;
+            let mut bytes = vec![];
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .context("failed to read stdin")?;
+            bytes
+        } else if let Some(file) = value.strip_prefix('@') {
+            fs::read(file).with_context(|| format!("failed to read `{file}`"))?
+        } else {
+            value.into_bytes()
+        };
+        let value = if base64 {
+            base64::encode(bytes)
+        } else {
+            String::from_utf8(bytes).with_context(|| {
+                format!("the value of `{key}` is not UTF-8, use --base64 to store it")
+            })?
+        };
+        values.insert(key, value);
+    }
+    Ok(values)
+}
+
+/// Print the value of the field as is, without quotes or a trailing newline
+/// for decoded values, so it can be piped to other commands.
+fn print_field(data: &HashMap<String, String>, field: &str, base64: bool) -> anyhow::Result<()> {
+    let Some(value) = data.get(field) else {
+        bail!("the secret has no field `{field}`");
+    };
+    let mut stdout = io::stdout().lock();
+    if base64 {
+        let bytes = base64::decode(value)
+            .with_context(|| format!("the value of `{field}` is not base64 encoded"))?;
+        stdout.write_all(&bytes)?;
+    } else {
+        writeln!(stdout, "{value}")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
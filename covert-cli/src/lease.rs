@@ -1,9 +1,22 @@
-use std::time::Duration;
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, IsTerminal, Write},
+    time::Duration,
+};
 
+use anyhow::{bail, Context};
 use clap::{Args, Subcommand};
-use covert_sdk::Client;
+use covert_sdk::{lease::LeaseEntry, Client};
+use serde::Serialize;
 
-use crate::handle_resp;
+use crate::{
+    handle_resp,
+    kv::parse_key_val,
+    output::{self, Format},
+};
+
+/// Revoking more leases than this by prefix asks for a confirmation.
+const CONFIRM_REVOKE_THRESHOLD: usize = 10;
 
 #[derive(Args, Debug)]
 pub struct Leases {
@@ -13,46 +26,233 @@ pub struct Leases {
 
 #[derive(Subcommand, Debug)]
 pub enum LeasesSubcommand {
-    #[command(about = "revoke lease")]
-    Revoke { lease_id: String },
+    #[command(
+        about = "revoke a lease, or all leases under a prefix",
+        long_about = "Revoke a lease, or with `--prefix` all leases under the mount path prefix. \
+            Revoking more than 10 leases by prefix asks for a confirmation first, unless `--yes` \
+            is given."
+    )]
+    Revoke {
+        #[arg(help = "id of the lease, or the mount path prefix with `--prefix`")]
+        lease_id: String,
+        #[arg(long, help = "revoke all leases under the mount path prefix")]
+        prefix: bool,
+        #[arg(
+            long,
+            requires = "prefix",
+            help = "remove the leases even if the backend fails to revoke them"
+        )]
+        force: bool,
+        #[arg(short, long, help = "do not ask for a confirmation")]
+        yes: bool,
+    },
     #[command(about = "renew lease")]
     Renew {
         lease_id: String,
-        #[arg(long)]
-        ttl: Option<humantime::Duration>,
+        #[arg(long, alias = "ttl", help = "how long the lease should live from now")]
+        increment: Option<humantime::Duration>,
     },
     #[command(about = "lookup lease")]
     Lookup { lease_id: String },
     #[command(about = "revoke leases by mount path prefix")]
-    RevokeMount { prefix: String },
+    RevokeMount {
+        prefix: String,
+        #[arg(long, value_parser = parse_key_val::<String, String>, help = "only revoke leases with the tag")]
+        tag: Vec<(String, String)>,
+    },
+    #[command(
+        about = "remove leases by mount path prefix even if the backend fails to revoke them",
+        long_about = "Remove leases by mount path prefix even if the backend fails to revoke them. \
+            Only use this when the backend is permanently gone: credentials the backend failed \
+            to revoke may still be valid in the external system and will no longer be tracked."
+    )]
+    RevokeForce { prefix: String },
     #[command(about = "list leases by mount path prefix")]
-    ListMount { prefix: String },
+    ListMount {
+        prefix: String,
+        #[arg(long, value_parser = parse_key_val::<String, String>, help = "only list leases with the tag")]
+        tag: Vec<(String, String)>,
+    },
+    #[command(about = "count leases, optionally by mount path prefix")]
+    Count {
+        prefix: Option<String>,
+        #[arg(long, value_parser = parse_key_val::<String, String>, help = "only count leases with the tag")]
+        tag: Vec<(String, String)>,
+    },
+    #[command(about = "revoke leases by tag across all mounts")]
+    RevokeTag {
+        #[arg(long, required = true, value_parser = parse_key_val::<String, String>)]
+        tag: Vec<(String, String)>,
+    },
 }
 
 impl Leases {
     pub async fn handle(self, sdk: &Client) {
         match self.subcommand {
-            LeasesSubcommand::Revoke { lease_id } => {
+            LeasesSubcommand::Revoke {
+                lease_id,
+                prefix: false,
+                ..
+            } => {
                 let resp = sdk.lease.revoke(&lease_id).await;
-                handle_resp(resp);
+                handle_lease(resp, |resp| &resp.lease);
             }
-            LeasesSubcommand::Renew { lease_id, ttl } => {
-                let ttl = ttl.map(|ttl| Duration::from_millis(ttl.as_millis() as u64));
-                let resp = sdk.lease.renew(&lease_id, ttl).await;
-                handle_resp(resp);
+            LeasesSubcommand::Revoke {
+                lease_id: prefix,
+                prefix: true,
+                force,
+                yes,
+            } => {
+                if !yes {
+                    match confirm_revoke(sdk, &prefix).await {
+                        Ok(true) => (),
+                        Ok(false) => {
+                            println!("Nothing was revoked");
+                            return;
+                        }
+                        Err(e) => {
+                            println!("Error: {e:#}");
+                            return;
+                        }
+                    }
+                }
+                if force {
+                    revoke_force(sdk, &prefix).await;
+                } else {
+                    let resp = sdk.lease.revoke_by_mount(&prefix).await;
+                    handle_leases(resp, |resp| &resp.leases);
+                }
+            }
+            LeasesSubcommand::Renew {
+                lease_id,
+                increment,
+            } => {
+                let increment =
+                    increment.map(|increment| Duration::from_millis(increment.as_millis() as u64));
+                let resp = sdk.lease.renew(&lease_id, increment).await;
+                handle_lease(resp, |resp| &resp.lease);
             }
             LeasesSubcommand::Lookup { lease_id } => {
                 let resp = sdk.lease.lookup(&lease_id).await;
+                handle_lease(resp, |resp| &resp.lease);
+            }
+            LeasesSubcommand::ListMount { prefix, tag } => {
+                let tags = BTreeMap::from_iter(tag);
+                let resp = sdk.lease.list_by_mount_with_tags(&prefix, &tags).await;
+                handle_leases(resp, |resp| &resp.leases);
+            }
+            LeasesSubcommand::RevokeMount { prefix, tag } => {
+                let tags = BTreeMap::from_iter(tag);
+                let resp = sdk.lease.revoke_by_tags(Some(&prefix), &tags).await;
                 handle_resp(resp);
             }
-            LeasesSubcommand::ListMount { prefix } => {
-                let resp = sdk.lease.list_by_mount(&prefix).await;
+            LeasesSubcommand::Count { prefix, tag } => {
+                let tags = BTreeMap::from_iter(tag);
+                let resp = sdk.lease.count(prefix.as_deref(), &tags).await;
                 handle_resp(resp);
             }
-            LeasesSubcommand::RevokeMount { prefix } => {
-                let resp = sdk.lease.revoke_by_mount(&prefix).await;
+            LeasesSubcommand::RevokeTag { tag } => {
+                let tags = BTreeMap::from_iter(tag);
+                let resp = sdk.lease.revoke_by_tags(None, &tags).await;
                 handle_resp(resp);
             }
+            LeasesSubcommand::RevokeForce { prefix } => revoke_force(sdk, &prefix).await,
+        }
+    }
+}
+
+async fn revoke_force(sdk: &Client, prefix: &str) {
+    let resp = sdk.lease.force_revoke_by_mount(prefix).await;
+    if let Ok(resp) = &resp {
+        if !resp.backend_failures.is_empty() {
+            eprintln!(
+                "WARNING: {} leases were removed without being revoked by the backend, their credentials may still be valid",
+                resp.backend_failures.len()
+            );
+        }
+    }
+    handle_resp(resp);
+}
+
+/// Ask whether to go on when many leases would be revoked by the prefix.
+async fn confirm_revoke(sdk: &Client, prefix: &str) -> anyhow::Result<bool> {
+    let count = sdk
+        .lease
+        .list_by_mount(prefix)
+        .await
+        .context("failed to look up the leases under the prefix")?
+        .leases
+        .len();
+    if count <= CONFIRM_REVOKE_THRESHOLD {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        bail!("{count} leases would be revoked, use --yes to revoke them without a confirmation");
+    }
+    print!("Revoke {count} leases under `{prefix}`? [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("failed to read stdin")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// The fields of a lease shown in tables, in the order they are shown.
+#[derive(Serialize)]
+struct LeaseRow<'a> {
+    id: &'a str,
+    path: &'a str,
+    issued: &'a str,
+    expires: &'a str,
+    last_renewal: &'a str,
+    renewable: bool,
+}
+
+impl<'a> From<&'a LeaseEntry> for LeaseRow<'a> {
+    fn from(lease: &'a LeaseEntry) -> Self {
+        Self {
+            id: &lease.id,
+            path: &lease.issued_mount_path,
+            issued: &lease.issue_time,
+            expires: &lease.expire_time,
+            last_renewal: &lease.last_renewal_time,
+            renewable: lease.renewable,
+        }
+    }
+}
+
+/// Print the lease of the response as a table, or the whole response in the
+/// other formats.
+fn handle_lease<T: Serialize>(
+    resp: Result<T, covert_sdk::Error>,
+    lease: impl Fn(&T) -> &LeaseEntry,
+) {
+    match resp {
+        Ok(resp) if output::format() == Format::Table => {
+            handle_resp(Ok(LeaseRow::from(lease(&resp))));
+        }
+        resp => handle_resp(resp),
+    }
+}
+
+/// Print the leases of the response as a table, or the whole response in the
+/// other formats.
+fn handle_leases<T: Serialize>(
+    resp: Result<T, covert_sdk::Error>,
+    leases: impl Fn(&T) -> &Vec<LeaseEntry>,
+) {
+    match resp {
+        Ok(resp) if output::format() == Format::Table => {
+            let leases = leases(&resp);
+            if leases.is_empty() {
+                println!("No leases");
+                return;
+            }
+            let rows = leases.iter().map(LeaseRow::from).collect::<Vec<_>>();
+            handle_resp(Ok(rows));
         }
+        resp => handle_resp(resp),
     }
 }
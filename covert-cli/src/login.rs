@@ -0,0 +1,129 @@
+use std::io::{self, BufRead, Write};
+
+use clap::{Args, ValueEnum};
+use covert_sdk::{ldap, mfa::ValidateMfaParams, userpass, Client, Error, ErrorCode};
+use serde::Serialize;
+
+use crate::{handle_resp, token_helper::TokenHelper};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LoginMethod {
+    Userpass,
+    Ldap,
+}
+
+#[derive(Args, Debug)]
+pub struct Login {
+    #[arg(
+        long,
+        value_enum,
+        default_value = "userpass",
+        help = "auth method to log in with"
+    )]
+    method: LoginMethod,
+    #[arg(short, long)]
+    username: String,
+    #[arg(long, help = "path of the auth method, `auth/<method>/` by default")]
+    path: Option<String>,
+    #[arg(long, help = "issue a token that cannot be renewed")]
+    non_renewable: bool,
+}
+
+#[derive(Serialize)]
+struct LoginOutput {
+    lease_id: String,
+    ttl: String,
+}
+
+impl Login {
+    pub async fn handle(self, sdk: &Client, helper: &TokenHelper) {
+        let password = match rpassword::prompt_password("Password: ") {
+            Ok(password) => password,
+            Err(e) => {
+                println!("Error: failed to read the password: {e}");
+                return;
+            }
+        };
+        // The new token must not be a child of the current one
+        sdk.set_token(None).await;
+
+        let renewable = !self.non_renewable;
+        let resp = match self.method {
+            LoginMethod::Userpass => {
+                let path = self.path.unwrap_or_else(|| "auth/userpass/".to_string());
+                let params = userpass::LoginParams {
+                    username: self.username,
+                    password,
+                    renewable,
+                };
+                sdk.userpass.login(&path, &params).await
+            }
+            LoginMethod::Ldap => {
+                let path = self.path.unwrap_or_else(|| "auth/ldap/".to_string());
+                let params = ldap::LoginParams {
+                    password,
+                    renewable,
+                };
+                sdk.ldap.login(&path, &self.username, &params).await
+            }
+        };
+        let resp = match resp {
+            Err(Error::MfaRequired(requirement)) => {
+                let code = match read_line("TOTP code: ") {
+                    Ok(code) => code,
+                    Err(e) => {
+                        println!("Error: failed to read the TOTP code: {e}");
+                        return;
+                    }
+                };
+                let params = ValidateMfaParams {
+                    request_id: requirement.request_id,
+                    code,
+                };
+                sdk.mfa.validate(&params).await
+            }
+            resp => resp,
+        };
+
+        let auth = match resp {
+            Ok(auth) => auth,
+            Err(e) => {
+                handle_resp::<()>(Err(e));
+                return;
+            }
+        };
+        if let Err(e) = helper.store(&auth.token.to_string()) {
+            println!("Error: failed to store the token: {e:#}");
+            return;
+        }
+        handle_resp::<LoginOutput>(Ok(LoginOutput {
+            lease_id: auth.lease_id,
+            ttl: humantime::format_duration(auth.lease_duration).to_string(),
+        }));
+    }
+}
+
+fn read_line(prompt: &str) -> io::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// Revoke the token in use and erase the stored token. The stored token is
+/// kept if the revocation fails for any other reason than the token already
+/// being invalid.
+pub async fn handle_logout(sdk: &Client, helper: &TokenHelper) {
+    match sdk.token.revoke_self().await {
+        Ok(_) => (),
+        Err(e) if e.code() == Some(ErrorCode::PermissionDenied) => (),
+        Err(e) => {
+            handle_resp::<()>(Err(e));
+            return;
+        }
+    }
+    if let Err(e) = helper.erase() {
+        println!("Error: failed to erase the stored token: {e:#}");
+    }
+}
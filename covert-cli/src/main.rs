@@ -1,24 +1,36 @@
 //! Covert command-line interface
 
+mod agent;
+mod audit;
 mod auth;
 mod entity;
 mod kv;
 mod lease;
+mod login;
 mod namespace;
 mod operator;
+mod output;
 mod policy;
 mod psql;
 mod secrets;
 mod server;
 mod status;
+mod template;
+mod token;
+mod token_helper;
 mod userpass;
 
+use std::path::PathBuf;
+
+use agent::Agent;
+use audit::Audit;
 use auth::Auth;
 use clap::{arg, command, Parser, Subcommand};
 use covert_sdk::Client;
 use entity::Entity;
 use kv::Kv;
 use lease::Leases;
+use login::{handle_logout, Login};
 use namespace::Namespace;
 use operator::Operator;
 use policy::Policy;
@@ -26,7 +38,9 @@ use psql::Psql;
 use secrets::Secrets;
 use serde::Serialize;
 use server::Server;
-use status::handle_status;
+use status::Status;
+use token::Token;
+use token_helper::TokenHelper;
 use userpass::Userpass;
 
 #[derive(Parser, Debug)]
@@ -35,17 +49,47 @@ struct Cli {
     #[arg(long, env = "COVERT_ADDR", default_value = "http://127.0.0.1:8080/v1")]
     covert_addr: String,
 
+    /// Token of the requests, the token stored by `covert login` when unset
     #[arg(long, env = "COVERT_TOKEN")]
     covert_token: Option<String>,
 
+    /// Command storing the token of `covert login`, e.g. in the OS keychain,
+    /// instead of `~/.covert/token`. It is called with `get`, `store` or
+    /// `erase`
+    #[arg(long, env = "COVERT_TOKEN_HELPER")]
+    covert_token_helper: Option<PathBuf>,
+
+    /// PEM encoded CA certificate used to verify the server certificate
+    #[arg(long, env = "COVERT_CACERT")]
+    covert_cacert: Option<String>,
+
+    /// Connect to the server over this unix socket instead of TCP
+    #[arg(long, env = "COVERT_SOCKET")]
+    covert_socket: Option<String>,
+
+    /// Format of the output, a table when stdout is a terminal and JSON
+    /// otherwise by default
+    #[arg(long, global = true, value_enum, env = "COVERT_FORMAT")]
+    format: Option<output::Format>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    #[command(about = "check status")]
-    Status,
+    #[command(
+        about = "check status",
+        long_about = "Print whether the server is initialized and unsealed, its version and its \
+                      role in an HA cluster. The exit code is 0 when the server is unsealed, 1 on \
+                      errors, 2 when it is sealed and 3 when it is not initialized, so scripts can \
+                      branch without parsing the output."
+    )]
+    Status(Status),
+    #[command(about = "log in and store the token for the other commands")]
+    Login(Login),
+    #[command(about = "revoke the token in use and erase the stored token")]
+    Logout,
     #[command(about = "useful subcommands for operators, typically used to initialize and unseal")]
     Operator(Operator),
     #[command(about = "manage entities")]
@@ -66,38 +110,74 @@ enum Commands {
     Userpass(Userpass),
     #[command(about = "manage leases")]
     Lease(Leases),
+    #[command(about = "manage tokens")]
+    Token(Token),
     #[command(alias = "ns", about = "manage namespaces")]
     Namespace(Namespace),
+    #[command(about = "follow audit devices")]
+    Audit(Audit),
+    #[command(
+        about = "keep a token valid for an application",
+        long_about = "Log in with the auth method and write the token to the sink file for an \
+                      application to read. The token is renewed before it expires and a new \
+                      token is issued once it cannot be renewed anymore. Failed logins and \
+                      renewals, e.g. while the server restarts or is sealed, are retried with \
+                      backoff. With `--templates` the agent also renders files with secrets, \
+                      rendered again when the secrets change."
+    )]
+    Agent(Agent),
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    output::init(cli.format);
 
-    let sdk = Client::new(cli.covert_addr.clone());
-    sdk.set_token(cli.covert_token).await;
+    let mut builder = Client::builder(cli.covert_addr.clone());
+    if let Some(path) = cli.covert_cacert.as_ref() {
+        let pem = std::fs::read(path).expect("failed to read CA certificate");
+        builder = builder
+            .root_certificate_pem(&pem)
+            .expect("failed to read CA certificate");
+    }
+    if let Some(path) = cli.covert_socket.as_ref() {
+        builder = builder.unix_socket(path);
+    }
+    let sdk = builder.build().expect("failed to create client");
+    let helper = TokenHelper::new(cli.covert_token_helper).expect("failed to set up token helper");
+    let token = match cli.covert_token {
+        Some(token) => Some(token),
+        None => helper.get().expect("failed to read the stored token"),
+    };
+    sdk.set_token(token).await;
 
     match cli.command {
         Commands::Entity(entity) => entity.handle(&sdk).await,
         Commands::Policy(policy) => policy.handle(&sdk).await,
         Commands::Server(server) => server.handle().await,
         Commands::Operator(operator) => operator.handle(&sdk).await,
-        Commands::Status => handle_status(&sdk).await,
+        Commands::Status(status) => status.handle(&sdk).await,
+        Commands::Login(login) => login.handle(&sdk, &helper).await,
+        Commands::Logout => handle_logout(&sdk, &helper).await,
         Commands::Auth(auth) => auth.handle(&sdk).await,
         Commands::Secrets(secret) => secret.handle(&sdk).await,
         Commands::Kv(kv) => kv.handle(&sdk).await,
         Commands::Psql(psql) => psql.handle(&sdk).await,
         Commands::Userpass(userpass) => userpass.handle(&sdk).await,
         Commands::Lease(lease) => lease.handle(&sdk).await,
+        Commands::Token(token) => token.handle(&sdk).await,
         Commands::Namespace(ns) => ns.handle(&sdk).await,
+        Commands::Audit(audit) => audit.handle(&sdk).await,
+        Commands::Agent(agent) => agent.handle(&sdk).await,
     }
 }
 
-pub(crate) fn handle_resp<T: Serialize>(resp: Result<T, String>) {
+pub(crate) fn handle_resp<T: Serialize>(resp: Result<T, covert_sdk::Error>) {
     match resp {
         Ok(resp) => {
-            let resp = serde_json::to_string_pretty(&resp).unwrap();
-            println!("{resp}");
+            if let Err(e) = output::print(&resp) {
+                println!("Error: failed to print the response: {e:#}");
+            }
         }
         Err(e) => {
             println!("Error: {e}");
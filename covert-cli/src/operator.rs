@@ -1,10 +1,16 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
 use clap::{Args, Subcommand};
 use covert_sdk::{
-    operator::{InitializeParams, UnsealParams},
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
     Client,
 };
 
-use crate::handle_resp;
+use crate::{
+    handle_resp,
+    output::{self, Format},
+};
 
 #[derive(Args, Debug)]
 pub struct Operator {
@@ -14,45 +20,185 @@ pub struct Operator {
 
 #[derive(Subcommand, Debug)]
 pub enum OperatorSubcommands {
-    #[command(about = "unseal the Covert server")]
+    #[command(
+        about = "unseal the Covert server",
+        long_about = "Submit a key share to unseal the Covert server. The share is prompted for \
+                      without echo unless given with `--unseal-keys`. The server is unsealed \
+                      once the threshold of shares is provided, by one or more operators."
+    )]
     Unseal {
-        #[arg(long, use_value_delimiter = true, value_delimiter = ',')]
+        #[arg(
+            long,
+            use_value_delimiter = true,
+            value_delimiter = ',',
+            help = "key shares to submit instead of prompting, e.g. for automation"
+        )]
         unseal_keys: Vec<String>,
     },
     #[command(about = "seal the Covert server")]
     Seal,
-    #[command(about = "initialize the Covert server")]
+    #[command(
+        about = "initialize the Covert server",
+        long_about = "Initialize the Covert server and print the key shares. The shares are only \
+                      shown once, the root token is printed once the server is unsealed with \
+                      them."
+    )]
     Init {
-        #[arg(long)]
-        shares: u8,
-        #[arg(long)]
-        threshold: u8,
+        #[arg(
+            long,
+            alias = "shares",
+            help = "number of key shares to split the key into"
+        )]
+        key_shares: u8,
+        #[arg(
+            long,
+            alias = "threshold",
+            help = "number of key shares required to unseal"
+        )]
+        key_threshold: u8,
+        #[arg(
+            long,
+            help = "number of key shares kept by the seal, must equal the shares"
+        )]
+        stored_shares: Option<u8>,
+    },
+    #[command(
+        about = "export a redacted support bundle",
+        long_about = "Export a gzip compressed tarball with the sanitized config, host info, seal \
+                      status, mounts, audit device summaries and diagnose results to attach to \
+                      bug reports. Secrets and tokens are redacted by the server."
+    )]
+    SupportBundle {
+        #[arg(short, long, default_value = "covert-support-bundle.tar.gz")]
+        output: PathBuf,
     },
 }
 
 impl Operator {
     pub async fn handle(self, sdk: &Client) {
         match self.subcommand {
-            OperatorSubcommands::Init { shares, threshold } => {
+            OperatorSubcommands::Init {
+                key_shares,
+                key_threshold,
+                stored_shares,
+            } => {
                 let resp = sdk
                     .operator
-                    .initialize(&InitializeParams { shares, threshold })
+                    .initialize(&InitializeParams {
+                        shares: key_shares,
+                        threshold: key_threshold,
+                        stored_shares,
+                    })
                     .await;
-                handle_resp(resp);
+                handle_init(resp, key_threshold);
             }
             OperatorSubcommands::Unseal { unseal_keys } => {
-                let resp = sdk
-                    .operator
-                    .unseal(&UnsealParams {
-                        shares: unseal_keys,
-                    })
-                    .await;
-                handle_resp(resp);
+                let shares = if unseal_keys.is_empty() {
+                    match prompt_secret("Unseal key share: ") {
+                        Ok(share) => vec![share],
+                        Err(e) => {
+                            println!("Error: {e:#}");
+                            return;
+                        }
+                    }
+                } else {
+                    unseal_keys
+                };
+                let resp = sdk.operator.unseal(&UnsealParams { shares }).await;
+                handle_unseal(resp);
             }
             OperatorSubcommands::Seal => {
                 let resp = sdk.operator.seal().await;
                 handle_resp(resp);
             }
+            OperatorSubcommands::SupportBundle { output } => {
+                match sdk.operator.support_bundle().await {
+                    Ok(bundle) => match std::fs::write(&output, bundle) {
+                        Ok(()) => println!("Support bundle written to {}", output.display()),
+                        Err(e) => println!("Error: {e}"),
+                    },
+                    Err(e) => println!("Error: {e}"),
+                }
+            }
+        }
+    }
+}
+
+/// Print the key shares. The warning goes to stderr so the output can still
+/// be parsed when it is JSON or YAML.
+fn handle_init(resp: Result<InitializeResponse, covert_sdk::Error>, threshold: u8) {
+    let shares = match &resp {
+        Ok(InitializeResponse::NewKeyShares(keys)) => keys.shares.clone(),
+        Ok(InitializeResponse::Unsealed(_)) => {
+            warn_root_token();
+            handle_resp(resp);
+            return;
         }
+        _ => {
+            handle_resp(resp);
+            return;
+        }
+    };
+    eprintln!(
+        "WARNING: the key shares are only shown once. Store them in separate safe places, the \
+         server cannot be unsealed without {threshold} of them and they cannot be recovered."
+    );
+    if output::format() != Format::Table {
+        handle_resp(resp);
+        return;
+    }
+    for (i, share) in shares.iter().enumerate() {
+        println!("Key share {}: {share}", i + 1);
+    }
+    println!();
+    println!(
+        "Covert is initialized with {} key shares and a threshold of {threshold}. Unseal it with \
+         `covert operator unseal` to get the root token.",
+        shares.len()
+    );
+}
+
+/// Print the progress of the unseal, or the root token once it is complete.
+fn handle_unseal(resp: Result<UnsealResponse, covert_sdk::Error>) {
+    if matches!(resp, Ok(UnsealResponse::Complete { .. })) {
+        warn_root_token();
+    }
+    if output::format() != Format::Table {
+        handle_resp(resp);
+        return;
+    }
+    match resp {
+        Ok(UnsealResponse::InProgress {
+            threshold,
+            key_shares_total,
+            key_shares_provided,
+        }) => {
+            println!(
+                "{key_shares_provided}/{threshold} shares provided, the key has \
+                 {key_shares_total} shares"
+            );
+        }
+        Ok(UnsealResponse::Complete { root_token }) => {
+            println!("Unseal complete");
+            println!("Root token: {root_token}");
+        }
+        Err(e) => handle_resp::<()>(Err(e)),
+    }
+}
+
+fn warn_root_token() {
+    eprintln!(
+        "WARNING: the root token is only shown once and can do anything. Set up other ways to \
+         authenticate and revoke it as soon as possible."
+    );
+}
+
+/// Prompt for a secret on the terminal without echo.
+fn prompt_secret(prompt: &str) -> anyhow::Result<String> {
+    let secret = rpassword::prompt_password(prompt).context("failed to read from the terminal")?;
+    let secret = secret.trim();
+    if secret.is_empty() {
+        bail!("no value was entered");
     }
+    Ok(secret.to_string())
 }
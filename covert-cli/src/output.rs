@@ -0,0 +1,241 @@
+//! Rendering of the results of the commands in the format picked with
+//! `--format`.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, IsTerminal, Write},
+    sync::OnceLock,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Max width of a table column that is not the last one. Longer values are
+/// cut, the other formats show them in full.
+const MAX_COLUMN_WIDTH: usize = 64;
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Aligned columns for humans
+    Table,
+    /// The response of the API
+    Json,
+    Yaml,
+}
+
+impl Format {
+    fn renderer(self) -> &'static dyn Render {
+        match self {
+            Self::Table => &TableRenderer,
+            Self::Json => &JsonRenderer,
+            Self::Yaml => &YamlRenderer,
+        }
+    }
+}
+
+/// Set the format of the output. Defaults to a table when stdout is a
+/// terminal and to JSON otherwise, e.g. when piped to another command.
+pub fn init(format: Option<Format>) {
+    let format = format.unwrap_or_else(|| {
+        if io::stdout().is_terminal() {
+            Format::Table
+        } else {
+            Format::Json
+        }
+    });
+    // Only set once, in main
+    let _ = FORMAT.set(format);
+}
+
+/// The format of the output.
+pub fn format() -> Format {
+    FORMAT.get().copied().unwrap_or(Format::Json)
+}
+
+/// Print the value to stdout in the format of the output.
+pub fn print<T: Serialize>(value: &T) -> anyhow::Result<()> {
+    let value = serde_json::to_value(value)?;
+    let format = format();
+    let mut stdout = io::stdout().lock();
+    format.renderer().render(&value, &mut stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Renders the result of a command.
+pub trait Render {
+    fn render(&self, value: &Value, out: &mut dyn Write) -> anyhow::Result<()>;
+}
+
+pub struct JsonRenderer;
+
+impl Render for JsonRenderer {
+    fn render(&self, value: &Value, out: &mut dyn Write) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, value)?;
+        writeln!(out)?;
+        Ok(())
+    }
+}
+
+pub struct YamlRenderer;
+
+impl Render for YamlRenderer {
+    fn render(&self, value: &Value, out: &mut dyn Write) -> anyhow::Result<()> {
+        serde_yaml::to_writer(out, value)?;
+        Ok(())
+    }
+}
+
+/// Renders objects as `key value` rows, with the keys of nested values joined
+/// by dots, e.g. `metadata.version`. Lists of objects, on their own or as the
+/// only field of the response, are rendered with a column per key instead.
+pub struct TableRenderer;
+
+impl Render for TableRenderer {
+    fn render(&self, value: &Value, out: &mut dyn Write) -> anyhow::Result<()> {
+        let list = match value {
+            Value::Array(items) => Some(items),
+            Value::Object(fields) if fields.len() == 1 => match fields.values().next() {
+                Some(Value::Array(items)) => Some(items),
+                _ => None,
+            },
+            _ => None,
+        };
+        match (list, value) {
+            (Some(items), _) if !items.is_empty() && items.iter().all(Value::is_object) => {
+                write_list(items, out)?;
+            }
+            (_, Value::Object(_) | Value::Array(_)) => {
+                let mut rows = vec![];
+                flatten("", value, &mut rows);
+                let rows = rows.into_iter().map(|(key, value)| vec![key, value]);
+                write_table(&["Key".to_string(), "Value".to_string()], rows, out)?;
+            }
+            (_, value) => writeln!(out, "{}", scalar(value))?,
+        }
+        Ok(())
+    }
+}
+
+/// Render a list of objects with a column per flattened key.
+fn write_list(items: &[Value], out: &mut dyn Write) -> io::Result<()> {
+    let rows: Vec<Vec<(String, String)>> = items
+        .iter()
+        .map(|item| {
+            let mut row = vec![];
+            flatten("", item, &mut row);
+            row
+        })
+        .collect();
+    // Keys in the order they first appear, items can miss some of them
+    let mut seen = BTreeSet::new();
+    let header: Vec<String> = rows
+        .iter()
+        .flatten()
+        .filter(|(key, _)| seen.insert(key.clone()))
+        .map(|(key, _)| key.clone())
+        .collect();
+    let rows = rows.into_iter().map(|row| {
+        header
+            .iter()
+            .map(|key| {
+                row.iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    });
+    write_table(&header, rows, out)
+}
+
+/// Collect the scalar values with their dotted keys. Lists of scalars are
+/// kept on one row.
+fn flatten(prefix: &str, value: &Value, rows: &mut Vec<(String, String)>) {
+    let key = |child: &str| {
+        if prefix.is_empty() {
+            child.to_string()
+        } else {
+            format!("{prefix}.{child}")
+        }
+    };
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (field, value) in fields {
+                flatten(&key(field), value, rows);
+            }
+        }
+        Value::Array(items) if items.iter().any(|i| i.is_object() || i.is_array()) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(&key(&i.to_string()), item, rows);
+            }
+        }
+        value => rows.push((prefix.to_string(), scalar(value))),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(scalar).collect::<Vec<_>>().join(", "),
+        Value::Object(_) => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Write the rows with aligned columns. The cells of the last column are
+/// written in full, with their lines aligned, the other cells are cut at
+/// [`MAX_COLUMN_WIDTH`].
+fn write_table(
+    header: &[String],
+    rows: impl Iterator<Item = Vec<String>>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let last = header.len().saturating_sub(1);
+    let rows: Vec<Vec<String>> = std::iter::once(header.to_vec())
+        .chain(rows)
+        .map(|row| {
+            row.into_iter()
+                .enumerate()
+                .map(|(i, cell)| if i == last { cell } else { cut(&cell) })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = (0..last)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let indent = widths.iter().map(|width| width + 2).sum();
+
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            if i == last {
+                line.push_str(&cell.replace('\n', &format!("\n{:indent$}", "")));
+            } else {
+                line.push_str(&format!("{cell:<width$}  ", width = widths[i]));
+            }
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Keep the first line of the cell, at most [`MAX_COLUMN_WIDTH`] characters.
+fn cut(cell: &str) -> String {
+    let line = cell.lines().next().unwrap_or_default();
+    if line.len() == cell.len() && line.chars().count() <= MAX_COLUMN_WIDTH {
+        return line.to_string();
+    }
+    let mut cut: String = line.chars().take(MAX_COLUMN_WIDTH - 1).collect();
+    cut.push('…');
+    cut
+}
@@ -1,7 +1,19 @@
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+use anyhow::Context;
 use clap::{Args, Subcommand};
-use covert_sdk::{policy::CreatePolicyParams, Client};
+use covert_sdk::{
+    policy::{CreatePolicyParams, PathPolicy, PolicyParseError},
+    Client,
+};
 
-use crate::handle_resp;
+use crate::{
+    handle_resp,
+    output::{self, Format},
+};
 
 #[derive(Args, Debug)]
 pub struct Policy {
@@ -11,16 +23,27 @@ pub struct Policy {
 
 #[derive(Subcommand, Debug)]
 pub enum PolicySubcommands {
-    #[command(about = "add new policy")]
-    Add {
-        #[arg(short, long, help = "name of policy to create")]
+    #[command(
+        about = "validate and upload a policy",
+        long_about = "Validate the policy document locally and upload it. Errors are reported \
+                      with the line they were found at and nothing is uploaded."
+    )]
+    Write {
+        #[arg(help = "name of the policy")]
+        name: String,
+        #[arg(help = "file with the policy document, `-` to read it from stdin")]
+        file: String,
+        #[arg(long, help = "only validate the policy, e.g. in CI pipelines")]
+        check: bool,
+    },
+    #[command(about = "read policy")]
+    Read {
+        #[arg(help = "name of the policy")]
         name: String,
-        #[arg(long)]
-        policy: String,
     },
-    #[command(about = "remove policy")]
-    Remove {
-        #[arg(help = "name of policy to remove")]
+    #[command(alias = "remove", about = "delete policy")]
+    Delete {
+        #[arg(help = "name of the policy")]
         name: String,
     },
     #[command(about = "list policies")]
@@ -30,14 +53,37 @@ pub enum PolicySubcommands {
 impl Policy {
     pub async fn handle(self, sdk: &Client) {
         match self.subcommand {
-            PolicySubcommands::Add { name, policy } => {
+            PolicySubcommands::Write { name, file, check } => {
+                let policy = match read_document(&file) {
+                    Ok(policy) => policy,
+                    Err(e) => {
+                        println!("Error: {e:#}");
+                        return;
+                    }
+                };
+                if let Err(e) = PathPolicy::parse(&policy) {
+                    println!("{}", diagnostic(&file, &policy, &e));
+                    std::process::exit(1);
+                }
+                if check {
+                    println!("The policy is valid");
+                    return;
+                }
                 let resp = sdk
                     .policy
                     .create(&CreatePolicyParams { name, policy })
                     .await;
                 handle_resp(resp);
             }
-            PolicySubcommands::Remove { name } => {
+            PolicySubcommands::Read { name } => {
+                let resp = sdk.policy.read(&name).await;
+                match resp {
+                    // The document can be edited and written again
+                    Ok(resp) if output::format() == Format::Table => print!("{}", resp.document),
+                    resp => handle_resp(resp),
+                }
+            }
+            PolicySubcommands::Delete { name } => {
                 let resp = sdk.policy.remove(&name).await;
                 handle_resp(resp);
             }
@@ -48,3 +94,44 @@ impl Policy {
         }
     }
 }
+
+fn read_document(file: &str) -> anyhow::Result<String> {
+    if file == "-" {
+        let mut policy = String::new();
+        io::stdin()
+            .read_to_string(&mut policy)
+            .context("failed to read stdin")?;
+        Ok(policy)
+    } else {
+        fs::read_to_string(file).with_context(|| format!("failed to read `{file}`"))
+    }
+}
+
+/// The error with the line of the document it was found at, e.g.
+///
+/// ```text
+/// Error: unknown capability `reed`
+///  --> policy.hcl:2:29
+///   |
+/// 2 |     capabilities = ["read", "reed"]
+///   |                             ^
+/// ```
+fn diagnostic(file: &str, policy: &str, error: &PolicyParseError) -> String {
+    let file = if file == "-" { "<stdin>" } else { file };
+    let line = policy.lines().nth(error.line - 1).unwrap_or_default();
+    let number = error.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Keep tabs so the marker lines up with the line above
+    let indent: String = line
+        .chars()
+        .take(error.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!(
+        "Error: {message}\n{gutter}--> {file}:{line_number}:{column}\n{gutter} |\n{number} | \
+         {line}\n{gutter} | {indent}^",
+        message = error.message,
+        line_number = error.line,
+        column = error.column,
+    )
+}
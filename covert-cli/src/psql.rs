@@ -47,6 +47,8 @@ pub enum PsqlSubcommand {
         sql: String,
         #[arg(long)]
         revocation_sql: String,
+        #[arg(long, help = "max time to live for credentials of the role")]
+        max_ttl: Option<humantime::Duration>,
     },
 }
 
@@ -84,6 +86,7 @@ impl Psql {
                 path,
                 sql,
                 revocation_sql,
+                max_ttl,
             } => {
                 let resp = sdk
                     .psql
@@ -93,6 +96,7 @@ impl Psql {
                         &CreateRoleParams {
                             sql,
                             revocation_sql,
+                            max_ttl: max_ttl.map(Into::into),
                         },
                     )
                     .await;
@@ -1,8 +1,8 @@
-use std::{str::FromStr, time::Duration};
+use std::str::FromStr;
 
 use clap::{Args, Subcommand};
 use covert_sdk::{
-    mounts::{BackendType, CreateMountParams, MountConfig, UpdateMountParams},
+    mounts::{BackendType, CreateMountParams, ListingVisibility, MountConfig, TuneMountParams},
     Client,
 };
 
@@ -22,6 +22,11 @@ pub enum SecretsSubcommand {
         name: String,
         #[arg(short, long)]
         path: Option<String>,
+        #[arg(
+            long,
+            help = "name of the registered plugin to mount, for `plugin` engines"
+        )]
+        plugin: Option<String>,
     },
     #[command(about = "disable secret engine")]
     Disable {
@@ -42,6 +47,13 @@ pub enum SecretsSubcommand {
             help = "the default TTL for secrets issed by this secrets engine"
         )]
         max_lease_ttl: Option<humantime::Duration>,
+        #[arg(long, help = "what the secrets engine is used for, empty to remove it")]
+        description: Option<String>,
+        #[arg(
+            long,
+            help = "who sees the mount in the listing: default, hidden or unauth"
+        )]
+        listing_visibility: Option<ListingVisibility>,
     },
     #[command(about = "list secret engines")]
     List,
@@ -50,14 +62,19 @@ pub enum SecretsSubcommand {
 impl Secrets {
     pub async fn handle(self, sdk: &Client) {
         match self.subcommand {
-            SecretsSubcommand::Enable { name, path } => {
-                let path = path.unwrap_or_else(|| format!("{}/", name.to_lowercase()));
+            SecretsSubcommand::Enable { name, path, plugin } => {
+                let path = path.unwrap_or_else(|| {
+                    format!("{}/", plugin.as_deref().unwrap_or(&name).to_lowercase())
+                });
                 let resp = sdk
                     .mount
                     .create(
                         &path,
                         &CreateMountParams {
-                            config: Default::default(),
+                            config: MountConfig {
+                                plugin,
+                                ..Default::default()
+                            },
                             variant: BackendType::from_str(&name).expect("invalid backend"),
                         },
                     )
@@ -72,16 +89,18 @@ impl Secrets {
                 path,
                 default_lease_ttl,
                 max_lease_ttl,
+                description,
+                listing_visibility,
             } => {
-                let mut config = MountConfig::default();
-                if let Some(ttl) = default_lease_ttl {
-                    config.default_lease_ttl = Duration::from_millis(ttl.as_millis() as u64);
-                }
-                if let Some(ttl) = max_lease_ttl {
-                    config.max_lease_ttl = Duration::from_millis(ttl.as_millis() as u64);
-                }
-
-                let resp = sdk.mount.update(&path, &UpdateMountParams { config }).await;
+                // Only the given settings are changed
+                let params = TuneMountParams {
+                    default_lease_ttl: default_lease_ttl.map(Into::into),
+                    max_lease_ttl: max_lease_ttl.map(Into::into),
+                    description,
+                    listing_visibility,
+                    ..Default::default()
+                };
+                let resp = sdk.mount.tune(&path, &params).await;
                 handle_resp(resp);
             }
             SecretsSubcommand::List => {
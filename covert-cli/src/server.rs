@@ -1,31 +1,271 @@
+use std::{path::PathBuf, sync::Arc};
+
 use clap::Args;
-use covert_system::Config;
+use covert_system::{
+    Config, ConfigReloader, ListenerAddress, ListenerConfig, LogFormat, Reloader, ShutdownTimedOut,
+    TlsConfig, TlsVersion,
+};
 use tracing::info;
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{prelude::*, EnvFilter};
+use tracing_subscriber::{
+    fmt::{
+        self,
+        format::{Format, Json, JsonFields},
+    },
+    prelude::*,
+    reload, EnvFilter,
+};
+
+/// Exit code when the server failed.
+const EXIT_ERROR: i32 = 1;
+/// Exit code when in-flight work did not finish within the shutdown timeout.
+const EXIT_SHUTDOWN_TIMED_OUT: i32 = 2;
+/// Exit code when the graceful shutdown was interrupted by a second signal.
+const EXIT_FORCED: i32 = 130;
 
 #[derive(Args, Debug)]
 pub struct Server {
-    #[arg(short, long)]
-    config: String,
+    #[arg(short, long, help = "path to the TOML config file")]
+    config: PathBuf,
+    #[arg(
+        long,
+        help = "validate the config and print the effective config without starting the server"
+    )]
+    test_config: bool,
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+}
+
+/// Flags overriding the values of the config file.
+#[derive(Args, Debug, Clone)]
+struct ConfigOverrides {
+    #[arg(long, help = "TCP port to listen on")]
+    port: Option<u16>,
+    #[arg(long, help = "directory of the storage")]
+    storage_path: Option<String>,
+    #[arg(
+        long,
+        help = "address to listen on, replaces the listeners of the config file"
+    )]
+    listener: Vec<ListenerAddress>,
+    #[arg(long, help = "PEM encoded certificate chain of the server")]
+    tls_cert_file: Option<PathBuf>,
+    #[arg(long, help = "PEM encoded private key of the server certificate")]
+    tls_key_file: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["tls_cert_file", "tls_key_file"],
+        help = "serve plain HTTP"
+    )]
+    tls_disable: bool,
+    #[arg(long, help = "format of the log output, text or json")]
+    log_format: Option<LogFormat>,
+    #[arg(long, help = "max TTL of any lease")]
+    max_lease_ttl: Option<humantime::Duration>,
+    #[arg(long, help = "time in-flight work is given to finish on shutdown")]
+    shutdown_timeout: Option<humantime::Duration>,
+    #[arg(long, help = "time a request is given to be handled")]
+    request_timeout: Option<humantime::Duration>,
+}
+
+impl ConfigOverrides {
+    fn apply(self, config: &mut Config) -> Result<(), String> {
+        if let Some(port) = self.port {
+            config.port = Some(port);
+        }
+        if let Some(storage_path) = self.storage_path {
+            config.storage_path = storage_path;
+        }
+        if !self.listener.is_empty() {
+            config.listeners = self
+                .listener
+                .into_iter()
+                .map(|address| ListenerConfig {
+                    address,
+                    mode: None,
+                    owner: None,
+                    group: None,
+                })
+                .collect();
+        }
+        match (self.tls_cert_file, self.tls_key_file, config.tls.as_mut()) {
+            (None, None, _) => (),
+            (cert_file, key_file, Some(tls)) => {
+                if let Some(cert_file) = cert_file {
+                    tls.cert_file = cert_file;
+                }
+                if let Some(key_file) = key_file {
+                    tls.key_file = key_file;
+                }
+            }
+            (Some(cert_file), Some(key_file), None) => {
+                config.tls = Some(TlsConfig {
+                    cert_file,
+                    key_file,
+                    min_version: TlsVersion::default(),
+                    request_client_cert: false,
+                    require_client_cert: false,
+                    client_ca_file: None,
+                    cipher_suites: vec![],
+                });
+                config.tls_disable = false;
+            }
+            (_, _, None) => {
+                return Err(
+                    "--tls-cert-file and --tls-key-file are both required when the config file has no [tls] table"
+                        .to_string(),
+                );
+            }
+        }
+        if self.tls_disable {
+            config.tls = None;
+            config.tls_disable = true;
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        if let Some(ttl) = self.max_lease_ttl {
+            config.max_lease_ttl = Some(ttl.into());
+        }
+        if let Some(timeout) = self.shutdown_timeout {
+            config.shutdown_timeout = timeout.into();
+        }
+        if let Some(timeout) = self.request_timeout {
+            config.request_timeout = timeout.into();
+        }
+        Ok(())
+    }
+}
+
+/// Read the config file, expand the environment variables in it and apply the
+/// `COVERT_` variables and then the flags on top of it.
+fn load_config(path: &PathBuf, overrides: ConfigOverrides) -> Result<Config, String> {
+    let config_file = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read config file `{}`: {err}", path.display()))?;
+    let mut config = Config::from_toml_with_env(&config_file, |name| std::env::var(name).ok())
+        .map_err(|err| format!("{err:#}"))?;
+    overrides.apply(&mut config)?;
+    Ok(config)
+}
+
+type ApplyLogging = Box<dyn Fn(&Config) -> Result<(), String> + Send + Sync>;
+
+/// Reads the config file again and applies the log level and format of it
+/// on SIGHUP.
+struct CliReloader {
+    path: PathBuf,
+    overrides: ConfigOverrides,
+    apply_logging: ApplyLogging,
+}
+
+impl ConfigReloader for CliReloader {
+    fn load(&self) -> anyhow::Result<Config> {
+        load_config(&self.path, self.overrides.clone()).map_err(anyhow::Error::msg)
+    }
+
+    fn apply_logging(&self, config: &Config) -> anyhow::Result<()> {
+        (self.apply_logging)(config).map_err(anyhow::Error::msg)
+    }
+}
+
+fn env_filter(config: &Config) -> Result<EnvFilter, String> {
+    match &config.log_level {
+        Some(level) => {
+            EnvFilter::try_new(level).map_err(|err| format!("Invalid log level `{level}`: {err}"))
+        }
+        None => {
+            Ok(EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("hyper=off,debug")))
+        }
+    }
+}
+
+/// Install the tracing subscriber. Returns a function applying the log level
+/// and format of a reloaded config.
+fn init_logging(
+    config: &Config,
+) -> Result<impl Fn(&Config) -> Result<(), String> + Send + Sync, String> {
+    let (text_layer, json_layer) = fmt_layers(config.log_format);
+    let (filter, filter_handle) = reload::Layer::new(env_filter(config)?);
+    let (text_layer, text_handle) = reload::Layer::new(text_layer);
+    let (json_layer, json_handle) = reload::Layer::new(json_layer);
+
+    let subscriber = tracing_subscriber::Registry::default()
+        .with(ErrorLayer::default())
+        .with(filter)
+        .with(text_layer)
+        .with(json_layer);
+
+    // set the subscriber as the default for the application
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("failed to setup tracing subscriber");
+
+    Ok(move |config: &Config| {
+        let filter = env_filter(config)?;
+        let (text_layer, json_layer) = fmt_layers(config.log_format);
+        filter_handle
+            .reload(filter)
+            .and_then(|()| text_handle.reload(text_layer))
+            .and_then(|()| json_handle.reload(json_layer))
+            .map_err(|err| format!("Failed to apply logging config: {err}"))
+    })
+}
+
+/// Output layers of the log format, only one of them is enabled.
+#[allow(clippy::type_complexity)]
+fn fmt_layers<T, J>(
+    format: LogFormat,
+) -> (
+    Option<fmt::Layer<T>>,
+    Option<fmt::Layer<J, JsonFields, Format<Json>>>,
+) {
+    match format {
+        LogFormat::Text => (Some(fmt::Layer::default()), None),
+        LogFormat::Json => (None, Some(fmt::Layer::default().json())),
+    }
 }
 
 impl Server {
     pub async fn handle(self) {
-        let env_filter =
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("hyper=off,debug"));
-
-        let subscriber = tracing_subscriber::Registry::default()
-            .with(ErrorLayer::default())
-            .with(env_filter)
-            .with(tracing_subscriber::fmt::Layer::default());
+        let mut config = match load_config(&self.config, self.overrides.clone()) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(EXIT_ERROR);
+            }
+        };
 
-        // set the subscriber as the default for the application
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("failed to setup tracing subscriber");
+        if self.test_config {
+            if let Err(err) = config
+                .validate()
+                .map_err(|err| format!("{err:#}"))
+                .and_then(|()| env_filter(&config).map(|_| ()))
+            {
+                eprintln!("Invalid config: {err}");
+                std::process::exit(EXIT_ERROR);
+            }
+            match config.to_redacted_toml() {
+                Ok(config) => print!("{config}"),
+                Err(err) => {
+                    eprintln!("Failed to print config: {err}");
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+            return;
+        }
 
-        let config_file = std::fs::read_to_string(&self.config).expect("failed to read config");
-        let mut config: Config = toml::from_str(&config_file).expect("failed to parse config file");
+        let apply_logging = match init_logging(&config) {
+            Ok(apply_logging) => apply_logging,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(EXIT_ERROR);
+            }
+        };
+        config.reloader = Some(Reloader(Arc::new(CliReloader {
+            path: self.config,
+            overrides: self.overrides,
+            apply_logging: Box::new(apply_logging),
+        })));
 
         let tmpdir_storage_path = tempfile::tempdir().unwrap();
         config.storage_path = if config.storage_path.is_empty() {
@@ -35,8 +275,26 @@ impl Server {
             config.storage_path.clone()
         };
 
-        covert_system::start(config, covert_system::shutdown_signal())
-            .await
-            .unwrap()
+        let shutdown_signal = async {
+            covert_system::shutdown_signal().await;
+            // A second interrupt skips the graceful shutdown
+            tokio::spawn(async {
+                covert_system::shutdown_signal().await;
+                eprintln!("Received second shutdown signal, exiting immediately");
+                std::process::exit(EXIT_FORCED);
+            });
+        };
+
+        match covert_system::start(config, shutdown_signal).await {
+            Ok(()) => (),
+            Err(err) if err.is::<ShutdownTimedOut>() => {
+                eprintln!("{err}");
+                std::process::exit(EXIT_SHUTDOWN_TIMED_OUT);
+            }
+            Err(err) => {
+                eprintln!("Server error: {err:#}");
+                std::process::exit(EXIT_ERROR);
+            }
+        }
     }
 }
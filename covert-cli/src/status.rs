@@ -1,8 +1,162 @@
-use covert_sdk::Client;
+use std::{
+    io::{self, IsTerminal},
+    process,
+    time::Duration,
+};
 
-use crate::handle_resp;
+use clap::{Args, ValueEnum};
+use covert_sdk::{
+    status::{StatusResponse, StorageState},
+    Client,
+};
+use tokio::time::{sleep, Instant};
 
-pub async fn handle_status(sdk: &Client) {
-    let resp = sdk.status.status().await;
-    handle_resp(resp);
+use crate::{
+    handle_resp,
+    output::{self, Format},
+};
+
+/// Wait between two checks of the status with `--retry`.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Args, Debug)]
+pub struct Status {
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        conflicts_with = "retry",
+        help = "print the status again every SECONDS until interrupted"
+    )]
+    watch: Option<u64>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STATE",
+        num_args = 0..=1,
+        default_missing_value = "unsealed",
+        help = "wait until the server reaches the state, unsealed by default"
+    )]
+    retry: Option<DesiredState>,
+    #[arg(
+        long,
+        requires = "retry",
+        help = "stop waiting after this long, e.g. `2m`, and exit with the code of the last status"
+    )]
+    timeout: Option<humantime::Duration>,
+}
+
+/// State waited for with `--retry`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DesiredState {
+    /// The server answers, whatever its state
+    Reachable,
+    /// The server is initialized, sealed or not
+    Initialized,
+    Sealed,
+    Unsealed,
+}
+
+impl DesiredState {
+    fn reached_by(self, state: StorageState) -> bool {
+        match self {
+            Self::Reachable => true,
+            Self::Initialized => state != StorageState::Uninitialized,
+            Self::Sealed => state == StorageState::Sealed,
+            Self::Unsealed => state == StorageState::Unsealed,
+        }
+    }
+}
+
+impl Status {
+    pub async fn handle(self, sdk: &Client) {
+        if let Some(seconds) = self.watch {
+            watch(sdk, Duration::from_secs(seconds.max(1))).await;
+            return;
+        }
+        let resp = match self.retry {
+            Some(desired) => {
+                let deadline = self.timeout.map(|timeout| Instant::now() + *timeout);
+                wait_for(sdk, desired, deadline).await
+            }
+            None => sdk.status.status().await,
+        };
+        let code = exit_code(&resp);
+        print_status(resp);
+        process::exit(code);
+    }
+}
+
+/// Check the status until the server reaches the desired state or the
+/// deadline passes. Errors, e.g. while the server is still starting, are
+/// retried.
+async fn wait_for(
+    sdk: &Client,
+    desired: DesiredState,
+    deadline: Option<Instant>,
+) -> Result<StatusResponse, covert_sdk::Error> {
+    loop {
+        let resp = sdk.status.status().await;
+        if matches!(&resp, Ok(status) if desired.reached_by(status.state)) {
+            return resp;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() + RETRY_INTERVAL > deadline) {
+            return resp;
+        }
+        sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// Print the status every interval, redrawing the screen when the output is
+/// a table on a terminal. Only returns when interrupted.
+async fn watch(sdk: &Client, interval: Duration) {
+    let redraw = output::format() == Format::Table && io::stdout().is_terminal();
+    loop {
+        if redraw {
+            // Clear the screen and move the cursor to the top left
+            print!("\x1b[2J\x1b[H");
+        }
+        print_status(sdk.status.status().await);
+        sleep(interval).await;
+    }
+}
+
+/// 0 when unsealed, 1 on errors, 2 when sealed and 3 when not initialized.
+fn exit_code(resp: &Result<StatusResponse, covert_sdk::Error>) -> i32 {
+    match resp {
+        Ok(status) => match status.state {
+            StorageState::Unsealed => 0,
+            StorageState::Sealed => 2,
+            StorageState::Uninitialized => 3,
+        },
+        Err(_) => 1,
+    }
+}
+
+fn print_status(resp: Result<StatusResponse, covert_sdk::Error>) {
+    let status = match resp {
+        Ok(status) if output::format() == Format::Table => status,
+        resp => {
+            handle_resp(resp);
+            return;
+        }
+    };
+    println!(
+        "Initialized   {}",
+        status.state != StorageState::Uninitialized
+    );
+    println!("Sealed        {}", status.state != StorageState::Unsealed);
+    println!("Version       {}", status.version);
+    if let Some(ha) = status.ha {
+        let mode = match (ha.active, ha.performance_standby) {
+            (true, _) => "active",
+            (false, true) => "performance standby",
+            (false, false) => "standby",
+        };
+        println!("HA mode       {mode}");
+        println!("Node id       {}", ha.node_id);
+        println!(
+            "Active node   {}",
+            ha.active_address.as_deref().unwrap_or_default()
+        );
+    }
 }
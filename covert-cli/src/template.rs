@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use covert_sdk::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    process::Command,
+    sync::watch,
+    time::{sleep_until, Instant},
+};
+
+use crate::agent::{write_file, MAX_BACKOFF, MIN_BACKOFF};
+
+/// How often secrets without a lease are read again.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_PERMS: u32 = 0o600;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TemplatesFile {
+    poll_interval: Option<String>,
+    #[serde(default, rename = "template")]
+    templates: Vec<TemplateBlock>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TemplateBlock {
+    source: Option<PathBuf>,
+    contents: Option<String>,
+    destination: PathBuf,
+    perms: Option<String>,
+    command: Option<String>,
+}
+
+/// Files rendered with secrets by the agent, read from a TOML file with a
+/// `[[template]]` block per file.
+pub struct Templates {
+    poll_interval: Duration,
+    files: Vec<FileTemplate>,
+}
+
+struct FileTemplate {
+    template: Template,
+    destination: PathBuf,
+    perms: u32,
+    command: Option<String>,
+}
+
+/// The secrets a file was rendered with and when it is rendered again.
+struct RenderState {
+    secrets: HashMap<Source, Fetched>,
+    /// Contents of the file as last written, to only write changes.
+    contents: Option<String>,
+    next_at: Instant,
+    backoff: Duration,
+}
+
+struct Fetched {
+    value: Value,
+    refresh_at: Instant,
+}
+
+impl Templates {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = fs::read_to_string(path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let file: TemplatesFile = toml::from_str(&file)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        let poll_interval = match file.poll_interval {
+            Some(interval) => humantime::parse_duration(&interval)
+                .with_context(|| format!("invalid poll interval `{interval}`"))?,
+            None => DEFAULT_POLL_INTERVAL,
+        };
+        if poll_interval.is_zero() {
+            bail!("the poll interval must be positive");
+        }
+        if file.templates.is_empty() {
+            bail!("`{}` has no `[[template]]` block", path.display());
+        }
+        let files = file
+            .templates
+            .into_iter()
+            .map(FileTemplate::new)
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            poll_interval,
+            files,
+        })
+    }
+
+    /// Render the files once logged in and again when their secrets change.
+    pub async fn render(&self, sdk: &Client, mut logged_in: watch::Receiver<bool>) {
+        // The secrets cannot be read without a token
+        while !*logged_in.borrow() {
+            if logged_in.changed().await.is_err() {
+                return;
+            }
+        }
+        let mut states = self
+            .files
+            .iter()
+            .map(|file| RenderState {
+                secrets: HashMap::new(),
+                contents: fs::read_to_string(&file.destination).ok(),
+                next_at: Instant::now(),
+                backoff: MIN_BACKOFF,
+            })
+            .collect::<Vec<_>>();
+        loop {
+            for (file, state) in self.files.iter().zip(&mut states) {
+                if state.next_at <= Instant::now() {
+                    file.update(sdk, state, self.poll_interval).await;
+                }
+            }
+            let next_at = states
+                .iter()
+                .map(|state| state.next_at)
+                .min()
+                .unwrap_or_else(|| Instant::now() + self.poll_interval);
+            sleep_until(next_at).await;
+        }
+    }
+}
+
+impl FileTemplate {
+    fn new(block: TemplateBlock) -> anyhow::Result<Self> {
+        let destination = block.destination;
+        let context = || format!("invalid template for `{}`", destination.display());
+        let text = match (block.source, block.contents) {
+            (Some(source), None) => fs::read_to_string(&source)
+                .with_context(|| format!("failed to read `{}`", source.display()))?,
+            (None, Some(contents)) => contents,
+            _ => bail!("{}: set either `source` or `contents`", context()),
+        };
+        let template = Template::parse(&text)
+            .map_err(anyhow::Error::msg)
+            .with_context(context)?;
+        let perms = match block.perms {
+            Some(perms) => u32::from_str_radix(&perms, 8)
+                .ok()
+                .filter(|perms| *perms <= 0o777)
+                .ok_or_else(|| anyhow!("invalid perms `{perms}`, expected e.g. `0640`"))
+                .with_context(context)?,
+            None => DEFAULT_PERMS,
+        };
+        Ok(Self {
+            template,
+            destination,
+            perms,
+            command: block.command,
+        })
+    }
+
+    /// Render the file, retrying with backoff if it fails. The file is left
+    /// as it is until it can be rendered again.
+    async fn update(&self, sdk: &Client, state: &mut RenderState, poll_interval: Duration) {
+        match self.try_update(sdk, state, poll_interval).await {
+            Ok(next_at) => {
+                state.next_at = next_at;
+                state.backoff = MIN_BACKOFF;
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to render `{}`: {e:#}, retrying in {}",
+                    self.destination.display(),
+                    humantime::format_duration(state.backoff)
+                );
+                state.next_at = Instant::now() + state.backoff;
+                state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    /// Fetch the secrets that are due and write the file if its contents
+    /// changed. Returns when the next secret is due.
+    async fn try_update(
+        &self,
+        sdk: &Client,
+        state: &mut RenderState,
+        poll_interval: Duration,
+    ) -> anyhow::Result<Instant> {
+        for source in self.template.sources() {
+            let due = state
+                .secrets
+                .get(source)
+                .is_none_or(|fetched| fetched.refresh_at <= Instant::now());
+            if due {
+                let fetched = source
+                    .fetch(sdk, poll_interval)
+                    .await
+                    .with_context(|| format!("failed to fetch `{source}`"))?;
+                state.secrets.insert(source.clone(), fetched);
+            }
+        }
+
+        let contents = self.template.render(&state.secrets)?;
+        if state.contents.as_deref() != Some(contents.as_str()) {
+            write_file(&self.destination, contents.as_bytes(), self.perms)?;
+            eprintln!("Rendered `{}`", self.destination.display());
+            state.contents = Some(contents);
+            if let Some(command) = &self.command {
+                run_command(command).await;
+            }
+        }
+
+        Ok(state
+            .secrets
+            .values()
+            .map(|fetched| fetched.refresh_at)
+            .min()
+            .unwrap_or_else(|| Instant::now() + poll_interval))
+    }
+}
+
+async fn run_command(command: &str) {
+    match Command::new("sh").arg("-c").arg(command).status().await {
+        Ok(status) if status.success() => (),
+        Ok(status) => eprintln!("Error: `{command}` exited with {status}"),
+        Err(e) => eprintln!("Error: failed to run `{command}`: {e}"),
+    }
+}
+
+/// Where the value of a template action comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Source {
+    /// `{{ secret "<path>" "<field>" }}` reads the path, e.g. a KV secret.
+    Read(String),
+    /// `{{ creds "<path>" "<field>" }}` sends an update request to the path,
+    /// e.g. to issue dynamic database credentials.
+    Issue(String),
+}
+
+impl Source {
+    async fn fetch(&self, sdk: &Client, poll_interval: Duration) -> anyhow::Result<Fetched> {
+        let value = match self {
+            Source::Read(path) => sdk.logical.read(path).await?,
+            Source::Issue(path) => sdk.logical.write(path, &json!({})).await?,
+        };
+        // Secrets with a lease are fetched again when two thirds of the lease
+        // have passed, so the file is updated before they expire
+        let lease_duration = value
+            .get("lease_duration")
+            .and_then(Value::as_str)
+            .and_then(|duration| humantime::parse_duration(duration).ok());
+        let refresh_in = lease_duration.map_or(poll_interval, |duration| duration * 2 / 3);
+        Ok(Fetched {
+            value,
+            refresh_at: Instant::now() + refresh_in.max(MIN_BACKOFF),
+        })
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Read(path) | Source::Issue(path) => write!(f, "{path}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Secret { source: Source, field: String },
+}
+
+/// Text with `{{ secret "<path>" "<field>" }}` and
+/// `{{ creds "<path>" "<field>" }}` actions replaced by a field of the data
+/// at the path.
+#[derive(Debug)]
+struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let action = &rest[start + 2..];
+            let end = action.find("}}").ok_or("unclosed `{{`")?;
+            parts.push(parse_action(action[..end].trim())?);
+            rest = &action[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The sources of the actions, each once.
+    fn sources(&self) -> Vec<&Source> {
+        let mut sources = vec![];
+        for part in &self.parts {
+            if let Part::Secret { source, .. } = part {
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+        sources
+    }
+
+    fn render(&self, secrets: &HashMap<Source, Fetched>) -> anyhow::Result<String> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Secret { source, field } => {
+                    let value = secrets
+                        .get(source)
+                        .and_then(|fetched| lookup(&fetched.value, field))
+                        .ok_or_else(|| anyhow!("`{source}` has no field `{field}`"))?;
+                    match value {
+                        Value::String(value) => rendered.push_str(value),
+                        value => rendered.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+/// The field of the data of a response, or of the response itself.
+fn lookup<'a>(value: &'a Value, field: &str) -> Option<&'a Value> {
+    value
+        .get("data")
+        .and_then(|data| data.get(field))
+        .or_else(|| value.get(field))
+        .filter(|value| !value.is_null())
+}
+
+fn parse_action(action: &str) -> Result<Part, String> {
+    let (function, args) = action
+        .split_once(char::is_whitespace)
+        .unwrap_or((action, ""));
+    let source: fn(String) -> Source = match function {
+        "secret" => Source::Read,
+        "creds" => Source::Issue,
+        _ => {
+            return Err(format!(
+                "unknown function `{function}`, expected `secret` or `creds`"
+            ))
+        }
+    };
+    let [path, field]: [String; 2] = parse_args(args)?
+        .try_into()
+        .map_err(|_| format!("`{function}` takes a path and a field"))?;
+    Ok(Part::Secret {
+        source: source(path),
+        field,
+    })
+}
+
+/// Double quoted strings separated by whitespace, with `\"` and `\\`
+/// escapes.
+fn parse_args(args: &str) -> Result<Vec<String>, String> {
+    let mut parsed = vec![];
+    let mut chars = args.chars();
+    loop {
+        match chars.next() {
+            None => return Ok(parsed),
+            Some(c) if c.is_whitespace() => continue,
+            Some('"') => (),
+            Some(c) => return Err(format!("expected a quoted string, found `{c}`")),
+        }
+        let mut arg = String::new();
+        loop {
+            match chars.next() {
+                None => return Err("unterminated string".into()),
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some(c @ ('"' | '\\')) => arg.push(c),
+                    _ => return Err("invalid escape in string".into()),
+                },
+                Some(c) => arg.push(c),
+            }
+        }
+        parsed.push(arg);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal, Read},
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Subcommand};
+use covert_sdk::{
+    token::{CreateTokenParams, RenewTokenSelfParams, TokenTargetParams},
+    Client,
+};
+use serde::Serialize;
+
+use crate::{
+    handle_resp,
+    kv::parse_key_val,
+    output::{self, Format},
+};
+
+#[derive(Args, Debug)]
+pub struct Token {
+    #[clap(subcommand)]
+    subcommand: TokenSubcommand,
+}
+
+/// Tokens are never taken as arguments so they do not end up in the shell
+/// history, they are read from stdin with `-` instead.
+#[derive(Subcommand, Debug)]
+pub enum TokenSubcommand {
+    #[command(
+        about = "create a token",
+        long_about = "Create a token for an entity as a child of the token in use, and print the \
+            token and its accessor. Requires `sudo` on `sys/token/create`."
+    )]
+    Create {
+        #[arg(long, help = "entity the token is issued to")]
+        entity: String,
+        #[arg(long = "policy", help = "policies granted besides the entity policies")]
+        policies: Vec<String>,
+        #[arg(long, default_value = "1h")]
+        ttl: humantime::Duration,
+        #[arg(long, help = "do not revoke the token together with the token in use")]
+        orphan: bool,
+        #[arg(long, help = "allow the token to be renewed")]
+        renewable: bool,
+        #[arg(long, help = "number of requests the token can be used for")]
+        num_uses: Option<u32>,
+        #[arg(long, value_parser = parse_key_val::<String, String>)]
+        metadata: Vec<(String, String)>,
+    },
+    #[command(
+        about = "look up a token",
+        long_about = "Look up the token in use, e.g. the token stored by `covert login`, another \
+            token read from stdin with `-`, or a token by its accessor."
+    )]
+    Lookup {
+        #[arg(
+            default_value = "self",
+            conflicts_with = "accessor",
+            help = "`self` for the token in use or `-` to read the token from stdin"
+        )]
+        token: String,
+        #[arg(long)]
+        accessor: Option<String>,
+    },
+    #[command(about = "renew the token in use")]
+    Renew {
+        #[arg(long, help = "how long the token should live from now")]
+        increment: Option<humantime::Duration>,
+    },
+    #[command(
+        about = "revoke a token and its child tokens",
+        long_about = "Revoke the token in use with `--self`, another token read from stdin with \
+            `-`, or a token by its accessor, together with its child tokens and leases."
+    )]
+    Revoke {
+        #[arg(
+            required_unless_present_any = ["accessor", "self_"],
+            conflicts_with_all = ["accessor", "self_"],
+            help = "`-` to read the token from stdin"
+        )]
+        token: Option<String>,
+        #[arg(long, conflicts_with = "self_")]
+        accessor: Option<String>,
+        #[arg(long = "self", help = "revoke the token in use")]
+        self_: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct CreateOutput {
+    token: String,
+    accessor: String,
+    lease_id: String,
+    ttl: String,
+}
+
+impl Token {
+    pub async fn handle(self, sdk: &Client) {
+        match self.subcommand {
+            TokenSubcommand::Create {
+                entity,
+                policies,
+                ttl,
+                orphan,
+                renewable,
+                num_uses,
+                metadata,
+            } => {
+                let resp = sdk
+                    .token
+                    .create(&CreateTokenParams {
+                        entity_name: entity,
+                        policies,
+                        ttl: ttl.into(),
+                        not_before: None,
+                        num_uses,
+                        renewable,
+                        metadata: HashMap::from_iter(metadata),
+                        orphan,
+                    })
+                    .await;
+                let resp = resp.map(|auth| CreateOutput {
+                    token: auth.token.to_string(),
+                    accessor: auth.accessor,
+                    lease_id: auth.lease_id,
+                    ttl: humantime::format_duration(auth.lease_duration).to_string(),
+                });
+                handle_resp(resp);
+            }
+            TokenSubcommand::Lookup { token, accessor } => {
+                let params = match (token.as_str(), accessor) {
+                    (_, Some(accessor)) => TokenTargetParams {
+                        token: None,
+                        accessor: Some(accessor),
+                    },
+                    ("self", None) => {
+                        let resp = sdk.token.lookup_self().await;
+                        handle_resp(resp);
+                        return;
+                    }
+                    (token, None) => match read_token(token) {
+                        Ok(token) => TokenTargetParams {
+                            token: Some(token),
+                            accessor: None,
+                        },
+                        Err(e) => {
+                            println!("Error: {e:#}");
+                            return;
+                        }
+                    },
+                };
+                let resp = sdk.token.lookup(&params).await;
+                handle_resp(resp);
+            }
+            TokenSubcommand::Renew { increment } => {
+                let increment = increment.map(Duration::from);
+                let resp = sdk
+                    .token
+                    .renew_self(&RenewTokenSelfParams { increment })
+                    .await;
+                handle_resp(resp);
+            }
+            TokenSubcommand::Revoke {
+                token,
+                accessor,
+                self_,
+            } => {
+                if self_ {
+                    let resp = sdk.token.revoke_self().await;
+                    handle_resp(resp);
+                    return;
+                }
+                let params = match (token, accessor) {
+                    (Some(token), _) => match read_token(&token) {
+                        Ok(token) => TokenTargetParams {
+                            token: Some(token),
+                            accessor: None,
+                        },
+                        Err(e) => {
+                            println!("Error: {e:#}");
+                            return;
+                        }
+                    },
+                    (None, accessor) => TokenTargetParams {
+                        token: None,
+                        accessor,
+                    },
+                };
+                let resp = sdk.token.revoke(&params).await;
+                if resp.is_ok() && output::format() == Format::Table {
+                    println!("Token revoked");
+                    return;
+                }
+                handle_resp(resp);
+            }
+        }
+    }
+}
+
+/// Read the token from stdin, prompting for it without echo on a terminal.
+fn read_token(arg: &str) -> anyhow::Result<String> {
+    if arg != "-" {
+        bail!("pass the token on stdin with `-` so it is not stored in the shell history");
+    }
+    let token = if io::stdin().is_terminal() {
+        rpassword::prompt_password("Token: ").context("failed to read from the terminal")?
+    } else {
+        let mut token = String::new();
+        io::stdin()
+            .read_to_string(&mut token)
+            .context("failed to read stdin")?;
+        token
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        bail!("no token was entered");
+    }
+    Ok(token.to_string())
+}
@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context};
+
+/// Stores the token of `covert login` between commands.
+#[derive(Debug)]
+pub enum TokenHelper {
+    /// A file only readable by the user, `~/.covert/token` by default.
+    File(PathBuf),
+    /// An external command, e.g. to keep the token in the OS keychain. It is
+    /// called with `get`, `store` or `erase` as its argument. `get` prints the
+    /// token on stdout, `store` reads it from stdin.
+    External(PathBuf),
+}
+
+impl TokenHelper {
+    /// The external helper if one is set, the token file otherwise.
+    pub fn new(external: Option<PathBuf>) -> anyhow::Result<Self> {
+        if let Some(path) = external {
+            return Ok(Self::External(path));
+        }
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .context("failed to find the home directory for the token file")?;
+        Ok(Self::File(
+            PathBuf::from(home).join(".covert").join("token"),
+        ))
+    }
+
+    /// The stored token, if any.
+    pub fn get(&self) -> anyhow::Result<Option<String>> {
+        let token = match self {
+            Self::File(path) => match fs::read_to_string(path) {
+                Ok(token) => token,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => {
+                    return Err(err).with_context(|| format!("failed to read `{}`", path.display()))
+                }
+            },
+            Self::External(path) => {
+                let output = Command::new(path)
+                    .arg("get")
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .with_context(|| format!("failed to run `{}`", path.display()))?;
+                if !output.status.success() {
+                    bail!("`{} get` failed with {}", path.display(), output.status);
+                }
+                String::from_utf8(output.stdout).context("the token helper returned no UTF-8")?
+            }
+        };
+        let token = token.trim();
+        Ok((!token.is_empty()).then(|| token.to_string()))
+    }
+
+    /// Store the token, replacing the stored one.
+    pub fn store(&self, token: &str) -> anyhow::Result<()> {
+        match self {
+            Self::File(path) => {
+                write_private(path, token)
+                    .with_context(|| format!("failed to write `{}`", path.display()))?;
+            }
+            Self::External(path) => {
+                let mut child = Command::new(path)
+                    .arg("store")
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("failed to run `{}`", path.display()))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(token.as_bytes())?;
+                }
+                let status = child.wait()?;
+                if !status.success() {
+                    bail!("`{} store` failed with {status}", path.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the stored token.
+    pub fn erase(&self) -> anyhow::Result<()> {
+        match self {
+            Self::File(path) => match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                    Err(err).with_context(|| format!("failed to remove `{}`", path.display()))
+                }
+                _ => Ok(()),
+            },
+            Self::External(path) => {
+                let status = Command::new(path)
+                    .arg("erase")
+                    .status()
+                    .with_context(|| format!("failed to run `{}`", path.display()))?;
+                if !status.success() {
+                    bail!("`{} erase` failed with {status}", path.display());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Write the file with mode 0600, in a directory with mode 0700.
+fn write_private(path: &std::path::Path, contents: &str) -> io::Result<()> {
+    let mut dir = fs::DirBuilder::new();
+    dir.recursive(true);
+    let mut file = fs::OpenOptions::new();
+    file.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+        dir.mode(0o700);
+        file.mode(0o600);
+        // The mode is only applied to new files
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    if let Some(parent) = path.parent() {
+        dir.create(parent)?;
+    }
+    file.open(path)?.write_all(contents.as_bytes())
+}
@@ -43,6 +43,8 @@ pub enum UserpassSubcommand {
         password: String,
         #[arg(long)]
         path: String,
+        #[arg(long, help = "issue a token that cannot be renewed")]
+        non_renewable: bool,
     },
     #[command(about = "update password for user")]
     UpdatePassword {
@@ -79,10 +81,18 @@ impl Userpass {
                 path,
                 username,
                 password,
+                non_renewable,
             } => {
                 let resp = sdk
                     .userpass
-                    .login(&path, &LoginParams { username, password })
+                    .login(
+                        &path,
+                        &LoginParams {
+                            username,
+                            password,
+                            renewable: !non_renewable,
+                        },
+                    )
                     .await;
                 handle_resp(resp);
             }
@@ -0,0 +1,17 @@
+-- Tables used by `covert_framework::lockout`
+CREATE TABLE IF NOT EXISTS AUTH_LOCKOUT_CONFIG (
+    lock INTEGER PRIMARY KEY DEFAULT 1,
+    attempts INTEGER NOT NULL,
+    window_secs INTEGER NOT NULL,
+    lockout_secs INTEGER NOT NULL,
+
+    -- Used to ensure that maximum one config is ever inserted
+    CONSTRAINT CONFIG_LOCK CHECK (lock=1)
+);
+
+CREATE TABLE IF NOT EXISTS FAILED_LOGINS (
+    alias TEXT PRIMARY KEY,
+    attempts INTEGER NOT NULL,
+    window_start TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP
+);
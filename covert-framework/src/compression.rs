@@ -0,0 +1,171 @@
+use hyper::{body::HttpBody, header::CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use tower_http::compression::{predicate::Predicate, CompressionLayer};
+
+/// Responses smaller than this are not worth compressing.
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// Content types that are already compressed or must reach the client as
+/// they are produced.
+const SKIPPED_CONTENT_TYPES: &[&str] = &["image/", "application/grpc", "text/event-stream"];
+
+/// Response compression settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CompressionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Min size in bytes of a response body to compress it.
+    #[serde(default = "default_min_size")]
+    pub min_size: u64,
+}
+
+fn default_min_size() -> u64 {
+    DEFAULT_MIN_SIZE
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+}
+
+/// Decides which responses are compressed.
+///
+/// Only bodies with a size known up front are compressed, streamed responses
+/// are passed through untouched so they reach the client as they are
+/// produced.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionPredicate {
+    config: CompressionConfig,
+}
+
+impl Predicate for CompressionPredicate {
+    fn should_compress<B>(&self, response: &hyper::Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        if !self.config.enabled {
+            return false;
+        }
+        let skipped_content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| {
+                SKIPPED_CONTENT_TYPES
+                    .iter()
+                    .any(|skipped| content_type.starts_with(skipped))
+            });
+        let large_enough = response
+            .body()
+            .size_hint()
+            .exact()
+            .is_some_and(|size| size >= self.config.min_size);
+
+        !skipped_content_type && large_enough
+    }
+}
+
+/// Compress responses with gzip or deflate depending on the `Accept-Encoding`
+/// header of the request.
+///
+/// The layer must wrap every layer that inspects response bodies, e.g. for
+/// auditing, so they see the uncompressed body.
+#[must_use]
+pub fn compression_layer(config: CompressionConfig) -> CompressionLayer<CompressionPredicate> {
+    CompressionLayer::new()
+        .gzip(true)
+        .deflate(true)
+        .compress_when(CompressionPredicate { config })
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING},
+        Body, Request, Response,
+    };
+    use tower::{service_fn, Layer, ServiceExt};
+
+    use super::*;
+
+    async fn content_encoding(
+        config: CompressionConfig,
+        accept_encoding: &str,
+        resp: fn() -> Response<Body>,
+    ) -> Option<String> {
+        let svc = compression_layer(config).layer(service_fn(move |_: Request<Body>| async move {
+            Ok::<_, hyper::Error>(resp())
+        }));
+        let req = Request::builder()
+            .header(ACCEPT_ENCODING, accept_encoding)
+            .body(Body::empty())
+            .unwrap();
+        let resp = svc.oneshot(req).await.unwrap();
+        resp.headers()
+            .get(CONTENT_ENCODING)
+            .map(|encoding| encoding.to_str().unwrap().to_string())
+    }
+
+    fn large_json() -> Response<Body> {
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(vec![b'a'; 2048]))
+            .unwrap()
+    }
+
+    fn small_json() -> Response<Body> {
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(vec![b'a'; 16]))
+            .unwrap()
+    }
+
+    fn large_stream() -> Response<Body> {
+        let (mut tx, body) = Body::channel();
+        tokio::spawn(async move {
+            let _ = tx.send_data(vec![b'a'; 2048].into()).await;
+        });
+        Response::builder()
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(body)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn compresses_large_responses_when_enabled() {
+        let enabled = CompressionConfig {
+            enabled: true,
+            ..CompressionConfig::default()
+        };
+
+        assert_eq!(
+            content_encoding(enabled, "gzip", large_json)
+                .await
+                .as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(
+            content_encoding(enabled, "deflate", large_json)
+                .await
+                .as_deref(),
+            Some("deflate")
+        );
+        assert_eq!(
+            content_encoding(enabled, "identity", large_json).await,
+            None
+        );
+        assert_eq!(content_encoding(enabled, "gzip", small_json).await, None);
+        assert_eq!(content_encoding(enabled, "gzip", large_stream).await, None);
+
+        // Off by default
+        assert_eq!(
+            content_encoding(CompressionConfig::default(), "gzip", large_json).await,
+            None
+        );
+    }
+}
@@ -1,12 +1,11 @@
-use std::{
-    ops::Deref,
-    task::{Context, Poll},
-};
+use std::ops::Deref;
 
-use covert_types::{error::ApiError, request::Request};
-use tower::Service;
+use covert_types::{error::ApiError, request::Request, response::Response};
+use tower::{Layer, Service};
 
-use super::FromRequest;
+use crate::middleware::{Middleware, MiddlewareLayer, MiddlewareService, Next};
+
+use super::{FromRequest, OptionalFromRequest};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Extension<T>(pub T);
@@ -19,43 +18,28 @@ impl<T> Deref for Extension<T> {
     }
 }
 
-impl<S, T> tower::Layer<S> for Extension<T>
+impl<S, T> Layer<S> for Extension<T>
 where
+    S: Service<Request, Response = Response, Error = ApiError> + Clone + Send + 'static,
+    S::Future: Send + 'static,
     T: Clone + Send + Sync + 'static,
 {
-    type Service = AddExtension<S, T>;
+    type Service = MiddlewareService<Self>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        AddExtension {
-            inner,
-            value: self.0.clone(),
-        }
+        MiddlewareLayer::new(self.clone()).layer(inner)
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct AddExtension<S, T> {
-    pub(crate) inner: S,
-    pub(crate) value: T,
-}
-
-impl<S, T> Service<Request> for AddExtension<S, T>
+/// Inserts the value into the request extensions before calling the handler.
+#[async_trait::async_trait]
+impl<T> Middleware for Extension<T>
 where
-    S: Service<Request>,
     T: Clone + Send + Sync + 'static,
 {
-    type Response = S::Response;
-    type Error = S::Error;
-    type Future = S::Future;
-
-    #[inline]
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx)
-    }
-
-    fn call(&mut self, mut req: Request) -> Self::Future {
-        req.extensions.insert(self.value.clone());
-        self.inner.call(req)
+    async fn call(&self, mut req: Request, next: Next) -> Result<Response, ApiError> {
+        req.extensions.insert(self.0.clone());
+        next.run(req).await
     }
 }
 
@@ -71,3 +55,12 @@ where
             .map(|ext| Extension(ext.clone()))
     }
 }
+
+impl<T> OptionalFromRequest for Extension<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn from_request(req: &mut Request) -> Result<Option<Self>, ApiError> {
+        Ok(req.extensions.get::<T>().map(|ext| Extension(ext.clone())))
+    }
+}
@@ -4,7 +4,7 @@ use covert_types::error::ApiError;
 use serde::de::DeserializeOwned;
 use tracing::debug;
 
-use super::{FromRequest, Request};
+use super::{FromRequest, OptionalFromRequest, Request};
 
 #[derive(Debug)]
 pub struct Json<T>(pub T);
@@ -28,3 +28,13 @@ impl<T: DeserializeOwned> FromRequest for Json<T> {
         })
     }
 }
+
+/// A request without a body has no JSON value.
+impl<T: DeserializeOwned> OptionalFromRequest for Json<T> {
+    fn from_request(req: &mut Request) -> Result<Option<Self>, ApiError> {
+        if req.data.is_empty() {
+            return Ok(None);
+        }
+        <Self as FromRequest>::from_request(req).map(Some)
+    }
+}
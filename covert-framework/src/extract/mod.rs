@@ -17,3 +17,70 @@ pub trait FromRequest: Sized {
     /// Returns error if the extraction from the [`Request`] was unsuccessful.
     fn from_request(req: &mut Request) -> Result<Self, ApiError>;
 }
+
+/// Extractors that can be missing from a request, e.g. a request without a
+/// body or an extension that is only set for authenticated requests.
+pub trait OptionalFromRequest: Sized {
+    /// Perform the extraction, `None` if the value is missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the value is present but invalid.
+    fn from_request(req: &mut Request) -> Result<Option<Self>, ApiError>;
+}
+
+impl<T: OptionalFromRequest> FromRequest for Option<T> {
+    fn from_request(req: &mut Request) -> Result<Self, ApiError> {
+        T::from_request(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use covert_types::{error::ErrorCode, request::Operation};
+    use hyper::http::Extensions;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn request(data: &str, params: &[(&str, &str)]) -> Request {
+        Request {
+            id: Uuid::default(),
+            operation: Operation::Create,
+            path: "/foo".to_string(),
+            namespace: vec![],
+            data: data.as_bytes().to_vec().into(),
+            extensions: Extensions::default(),
+            token: None,
+            params: params
+                .iter()
+                .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+                .collect(),
+            query_string: String::default(),
+            headers: HashMap::default(),
+        }
+    }
+
+    #[test]
+    fn missing_values_are_none() {
+        let mut req = request("", &[]);
+        let body = <Option<Json<Value>>>::from_request(&mut req).unwrap();
+        assert!(body.is_none());
+        let path = <Option<Path<String>>>::from_request(&mut req).unwrap();
+        assert!(path.is_none());
+        let ext = <Option<Extension<u32>>>::from_request(&mut req).unwrap();
+        assert!(ext.is_none());
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let mut req = request("{", &[("version", "abc")]);
+        let err = <Option<Json<Value>>>::from_request(&mut req).unwrap_err();
+        assert_eq!(err.code, ErrorCode::BadRequest);
+        let err = <Option<Path<u32>>>::from_request(&mut req).unwrap_err();
+        assert_eq!(err.code, ErrorCode::BadRequest);
+    }
+}
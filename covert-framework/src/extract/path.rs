@@ -1,9 +1,21 @@
-use std::{ops::Deref, str::FromStr};
+use std::{fmt::Display, ops::Deref, str::FromStr};
 
-use covert_types::error::ApiError;
+use covert_types::error::{ApiError, ErrorCode};
+use percent_encoding::percent_decode_str;
+use serde::{
+    de::{self, value::StringDeserializer, DeserializeOwned, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+};
 
-use super::{FromRequest, Request};
+use super::{FromRequest, OptionalFromRequest, Request};
 
+/// Extract the captured path parameters into any type implementing
+/// [`serde::Deserialize`].
+///
+/// Routes can capture single segments with `{name}` (or `:name`) and the rest
+/// of the path with a trailing `*name`. The captures are percent-decoded and
+/// can be extracted into a single value when the route has one capture, into
+/// a tuple by position or into a struct by name.
 #[derive(Debug)]
 pub struct Path<T>(pub T);
 
@@ -16,61 +28,368 @@ impl<T> Deref for Path<T> {
     }
 }
 
-trait FromPathParameters: Sized {
-    fn from_path_params(params: &[String]) -> Result<Self, ApiError>;
+impl<T: DeserializeOwned> FromRequest for Path<T> {
+    #[tracing::instrument(level = "debug", name = "path_extractor", skip_all)]
+    fn from_request(req: &mut Request) -> Result<Self, ApiError> {
+        from_path_params(&req.params).map(Path).map_err(|err| {
+            ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg(format!("Invalid path: {err}")),
+            )
+        })
+    }
 }
 
-impl FromPathParameters for String {
-    fn from_path_params(params: &[String]) -> Result<Self, ApiError> {
-        params
-            .get(0)
-            .ok_or_else(ApiError::bad_request)
-            .map(Clone::clone)
+/// Routes without captures have no path parameters.
+impl<T: DeserializeOwned> OptionalFromRequest for Path<T> {
+    fn from_request(req: &mut Request) -> Result<Option<Self>, ApiError> {
+        if req.params.is_empty() {
+            return Ok(None);
+        }
+        <Self as FromRequest>::from_request(req).map(Some)
     }
 }
 
-impl<T1, T2> FromPathParameters for (T1, T2)
-where
-    T1: FromStr,
-    T2: FromStr,
-{
-    fn from_path_params(params: &[String]) -> Result<Self, ApiError> {
-        let param = params.get(0).ok_or_else(ApiError::bad_request)?;
-        let t1 = T1::from_str(param).map_err(|_| ApiError::bad_request())?;
+fn from_path_params<T: DeserializeOwned>(params: &[(String, String)]) -> Result<T, Error> {
+    let params = params
+        .iter()
+        .map(|(key, value)| {
+            percent_decode_str(value)
+                .decode_utf8()
+                .map(|value| ValueDeserializer {
+                    key: key.clone(),
+                    value: value.into_owned(),
+                })
+                .map_err(|_| Error(format!("path parameter `{key}`: invalid UTF-8")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    T::deserialize(PathDeserializer { params })
+}
 
-        let param = params.get(1).ok_or_else(ApiError::bad_request)?;
-        let t2 = T2::from_str(param).map_err(|_| ApiError::bad_request())?;
+#[derive(Debug)]
+struct Error(String);
 
-        Ok((t1, t2))
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
-impl<T1, T2, T3> FromPathParameters for (T1, T2, T3)
-where
-    T1: FromStr,
-    T2: FromStr,
-    T3: FromStr,
-{
-    fn from_path_params(params: &[String]) -> Result<Self, ApiError> {
-        let param = params.get(0).ok_or_else(ApiError::bad_request)?;
-        let t1 = T1::from_str(param).map_err(|_| ApiError::bad_request())?;
+impl std::error::Error for Error {}
 
-        let param = params.get(1).ok_or_else(ApiError::bad_request)?;
-        let t2 = T2::from_str(param).map_err(|_| ApiError::bad_request())?;
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
 
-        let param = params.get(2).ok_or_else(ApiError::bad_request)?;
-        let t3 = T3::from_str(param).map_err(|_| ApiError::bad_request())?;
+struct PathDeserializer {
+    params: Vec<ValueDeserializer>,
+}
 
-        Ok((t1, t2, t3))
+impl PathDeserializer {
+    fn single(self) -> Result<ValueDeserializer, Error> {
+        let len = self.params.len();
+        let mut params = self.params.into_iter();
+        match (params.next(), len) {
+            (Some(param), 1) => Ok(param),
+            _ => Err(Error(format!(
+                "expected a single path parameter, found {len}"
+            ))),
+        }
     }
 }
 
-impl<T> FromRequest for Path<T>
-where
-    T: FromPathParameters,
-{
-    #[tracing::instrument(level = "debug", name = "path_extractor", skip_all)]
-    fn from_request(req: &mut Request) -> Result<Self, ApiError> {
-        T::from_path_params(&req.params).map(Path)
+macro_rules! deserialize_single {
+    ($($method:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for PathDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.params.len() == 1 {
+            return self.single()?.deserialize_any(visitor);
+        }
+        self.deserialize_map(visitor)
+    }
+
+    deserialize_single! {
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_identifier,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(de::value::SeqDeserializer::new(self.params.into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.params.len() != len {
+            return Err(Error(format!(
+                "expected {len} path parameters, found {}",
+                self.params.len()
+            )));
+        }
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let entries = self
+            .params
+            .into_iter()
+            .map(|param| (param.key.clone(), param));
+        visitor.visit_map(de::value::MapDeserializer::new(entries))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        unit unit_struct ignored_any
+    }
+}
+
+/// Deserializer for the value of a single path parameter. Errors are
+/// prefixed with the name of the parameter.
+struct ValueDeserializer {
+    key: String,
+    value: String,
+}
+
+impl ValueDeserializer {
+    fn error(&self, msg: impl Display) -> Error {
+        Error(format!("path parameter `{}`: {msg}", self.key))
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.value
+            .parse()
+            .map_err(|err| self.error(format!("invalid value `{}`: {err}", self.value)))
+    }
+
+    fn prefix_error(&self, err: Error) -> Error {
+        if err.0.starts_with("path parameter") {
+            err
+        } else {
+            self.error(err)
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let value = self.parse()?;
+                visitor.$visit(value).map_err(|err| self.prefix_error(err))
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let value = self.value.clone();
+        visitor
+            .visit_string(value)
+            .map_err(|err| self.prefix_error(err))
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value: StringDeserializer<Error> = self.value.clone().into_deserializer();
+        visitor
+            .visit_enum(value)
+            .map_err(|err| self.prefix_error(err))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(self.error("nested values are not supported"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct identifier
+        ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn params(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct VersionPath {
+        key: String,
+        version: u32,
+    }
+
+    #[test]
+    fn deserialize_path() {
+        let key: String = from_path_params(&params(&[("key", "foo/bar%20baz")])).unwrap();
+        assert_eq!(key, "foo/bar baz");
+
+        let version: u32 = from_path_params(&params(&[("version", "3")])).unwrap();
+        assert_eq!(version, 3);
+
+        let path: VersionPath =
+            from_path_params(&params(&[("key", "foo"), ("version", "3")])).unwrap();
+        assert_eq!(
+            path,
+            VersionPath {
+                key: "foo".to_string(),
+                version: 3,
+            }
+        );
+
+        let (key, version): (String, u32) =
+            from_path_params(&params(&[("key", "foo"), ("version", "3")])).unwrap();
+        assert_eq!(key, "foo");
+        assert_eq!(version, 3);
+    }
+
+    #[test]
+    fn errors_name_the_parameter() {
+        let err = from_path_params::<VersionPath>(&params(&[("key", "foo"), ("version", "abc")]))
+            .unwrap_err();
+        assert!(err.to_string().contains("`version`"), "{err}");
+
+        let err = from_path_params::<VersionPath>(&params(&[("key", "foo")])).unwrap_err();
+        assert!(err.to_string().contains("`version`"), "{err}");
+
+        let err =
+            from_path_params::<(String, u32, u32)>(&params(&[("key", "foo"), ("version", "3")]))
+                .unwrap_err();
+        assert!(err.to_string().contains("expected 3"), "{err}");
+
+        assert!(from_path_params::<String>(&params(&[])).is_err());
+        assert!(from_path_params::<String>(&params(&[("key", "%FF")])).is_err());
     }
 }
@@ -1,10 +1,17 @@
-use std::ops::Deref;
+use std::{fmt::Display, ops::Deref, str::FromStr};
 
-use covert_types::error::ApiError;
-use serde::de::DeserializeOwned;
+use covert_types::error::{ApiError, ErrorCode};
+use serde::{
+    de::{self, value::StrDeserializer, DeserializeOwned, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+};
 
-use super::{FromRequest, Request};
+use super::{FromRequest, OptionalFromRequest, Request};
 
+/// Extract the query string into any type implementing [`serde::Deserialize`].
+///
+/// Repeated keys (`?id=1&id=2` or `?id[]=1&id[]=2`) can be deserialized into
+/// a sequence and booleans accept `true`, `false`, `1` and `0`.
 #[derive(Debug)]
 pub struct Query<T>(pub T);
 
@@ -20,8 +27,309 @@ impl<T> Deref for Query<T> {
 impl<T: DeserializeOwned> FromRequest for Query<T> {
     #[tracing::instrument(level = "debug", name = "query_string_extractor", skip_all)]
     fn from_request(req: &mut Request) -> Result<Self, ApiError> {
-        serde_qs::from_str(&req.query_string)
-            .map(Query)
-            .map_err(|_| ApiError::bad_request())
+        from_query_str(&req.query_string).map(Query).map_err(|err| {
+            ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg(format!("Invalid query string: {err}")),
+            )
+        })
+    }
+}
+
+/// Requests without a query string have no query.
+impl<T: DeserializeOwned> OptionalFromRequest for Query<T> {
+    fn from_request(req: &mut Request) -> Result<Option<Self>, ApiError> {
+        if req.query_string.is_empty() {
+            return Ok(None);
+        }
+        <Self as FromRequest>::from_request(req).map(Some)
+    }
+}
+
+fn from_query_str<T: DeserializeOwned>(query: &str) -> Result<T, Error> {
+    let mut params: Vec<(String, Vec<String>)> = vec![];
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        let key = key.strip_suffix("[]").unwrap_or(&key);
+        match params.iter_mut().find(|(k, _)| k == key) {
+            Some((_, values)) => values.push(value.into_owned()),
+            None => params.push((key.to_string(), vec![value.into_owned()])),
+        }
+    }
+    T::deserialize(QueryDeserializer { params })
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+struct QueryDeserializer {
+    params: Vec<(String, Vec<String>)>,
+}
+
+impl<'de> de::Deserializer<'de> for QueryDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(QueryMapAccess {
+            params: self.params.into_iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct QueryMapAccess {
+    params: std::vec::IntoIter<(String, Vec<String>)>,
+    value: Option<ValueDeserializer>,
+}
+
+impl<'de> de::MapAccess<'de> for QueryMapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, values)) = self.params.next() else {
+            return Ok(None);
+        };
+        let key_de: StrDeserializer<'_, Error> = key.as_str().into_deserializer();
+        let key_value = seed.deserialize(key_de)?;
+        self.value = Some(ValueDeserializer { key, values });
+        Ok(Some(key_value))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error("value is missing".to_string()))?;
+        seed.deserialize(value)
+    }
+}
+
+/// Deserializer for the values of a single query parameter. Errors are
+/// prefixed with the name of the parameter.
+struct ValueDeserializer {
+    key: String,
+    values: Vec<String>,
+}
+
+impl ValueDeserializer {
+    fn single(&self) -> Result<&str, Error> {
+        match &self.values[..] {
+            [value] => Ok(value),
+            _ => Err(self.error("expected a single value")),
+        }
+    }
+
+    fn error(&self, msg: impl Display) -> Error {
+        Error(format!("query parameter `{}`: {msg}", self.key))
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.single()?;
+        value
+            .parse()
+            .map_err(|err| self.error(format!("invalid value `{value}`: {err}")))
+    }
+
+    fn prefix_error(&self, err: Error) -> Error {
+        if err.0.starts_with("query parameter") {
+            err
+        } else {
+            self.error(err)
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let value = self.parse()?;
+                visitor.$visit(value).map_err(|err| self.prefix_error(err))
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.values.len() > 1 {
+            return self.deserialize_seq(visitor);
+        }
+        let value = self.single()?.to_string();
+        visitor
+            .visit_string(value)
+            .map_err(|err| self.prefix_error(err))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let value = match self.single()? {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            value => {
+                return Err(self.error(format!(
+                    "invalid value `{value}`, expected one of `true`, `false`, `1` or `0`"
+                )))
+            }
+        };
+        visitor.visit_bool(value)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let key = self.key.clone();
+        let values = self.values.into_iter().map(move |value| ValueDeserializer {
+            key: key.clone(),
+            values: vec![value],
+        });
+        visitor.visit_seq(de::value::SeqDeserializer::new(values))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value: StrDeserializer<'_, Error> = self.single()?.into_deserializer();
+        visitor
+            .visit_enum(value)
+            .map_err(|err| self.prefix_error(err))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(self.error("nested values are not supported"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple tuple_struct identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Params {
+        version: Option<u32>,
+        #[serde(default)]
+        id: Vec<String>,
+        #[serde(default)]
+        deleted: bool,
+    }
+
+    #[test]
+    fn deserialize_query() {
+        let params: Params = from_query_str("version=2&id=a&id=b%20c&deleted=1").unwrap();
+        assert_eq!(
+            params,
+            Params {
+                version: Some(2),
+                id: vec!["a".to_string(), "b c".to_string()],
+                deleted: true,
+            }
+        );
+
+        let params: Params = from_query_str("id[]=a&deleted=false").unwrap();
+        assert_eq!(
+            params,
+            Params {
+                version: None,
+                id: vec!["a".to_string()],
+                deleted: false,
+            }
+        );
+
+        let params: Params = from_query_str("").unwrap();
+        assert_eq!(params.version, None);
+    }
+
+    #[test]
+    fn errors_name_the_parameter() {
+        let err = from_query_str::<Params>("version=abc").unwrap_err();
+        assert!(err.to_string().contains("`version`"), "{err}");
+
+        let err = from_query_str::<Params>("deleted=yes").unwrap_err();
+        assert!(err.to_string().contains("`deleted`"), "{err}");
+
+        let err = from_query_str::<Params>("version=1&version=2").unwrap_err();
+        assert!(err.to_string().contains("`version`"), "{err}");
     }
 }
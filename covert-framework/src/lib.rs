@@ -4,9 +4,14 @@
 #![deny(clippy::get_unwrap)]
 #![allow(clippy::module_name_repetitions)]
 
+pub mod compression;
 pub mod extract;
 mod handler;
+pub mod lockout;
 mod method_router;
+pub mod middleware;
+mod path_matcher;
+pub mod request_log;
 mod router;
 mod sync_service;
 
@@ -19,11 +24,12 @@ use covert_storage::{
 use tower::ServiceExt;
 
 pub use method_router::*;
+pub use path_matcher::{normalize_path, RouteError};
 pub use router::Router;
 pub use sync_service::SyncService;
 
 use covert_types::{
-    backend::{BackendCategory, BackendType},
+    backend::{BackendCategory, BackendType, RouteHelp},
     error::ApiError,
     request::Request,
     response::Response,
@@ -35,6 +41,8 @@ pub struct Backend {
     pub category: BackendCategory,
     pub variant: BackendType,
     pub migrations: Vec<MigrationScript>,
+    /// Routes served by the backend.
+    pub paths: Vec<RouteHelp>,
 }
 
 impl Backend {
@@ -58,7 +66,9 @@ impl Backend {
         self.variant
     }
 
-    /// Run migration for backend.
+    /// Run the pending migrations for the backend. The already applied
+    /// migrations are verified against the migrations of the backend unless
+    /// `verify_checksums` is `false`.
     ///
     /// # Errors
     ///
@@ -69,7 +79,15 @@ impl Backend {
         pool: Arc<EncryptedPool>,
         mount_id: &str,
         prefix: &str,
+        verify_checksums: bool,
     ) -> Result<(), MigrationError> {
-        migrate(pool.as_ref(), &self.migrations, mount_id, prefix).await
+        migrate(
+            pool.as_ref(),
+            &self.migrations,
+            mount_id,
+            prefix,
+            verify_checksums,
+        )
+        .await
     }
 }
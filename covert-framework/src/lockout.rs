@@ -0,0 +1,414 @@
+//! Lockout of aliases after repeated failed logins for auth backends.
+//!
+//! The state is kept in the backend storage so it is scoped to the mount and
+//! shared by every node using the storage. Backends using the lockout must
+//! add its tables to their migrations with [`with_lockout_migration`] and
+//! register the operator routes with [`lockout_routes`].
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use covert_storage::{
+    migrator::{migration_script, sort_migrations, MigrationError, MigrationScript},
+    BackendStoragePool,
+};
+use covert_types::{
+    error::{ApiError, ErrorCode},
+    methods::lockout::{
+        ListLockedAliasesResponse, LockedAliasItem, LockoutConfig, UnlockAliasResponse,
+    },
+    response::Response,
+};
+
+use crate::{
+    extract::{Extension, Json, Path},
+    read, Router,
+};
+
+const CONFIG_TABLE: &str = "AUTH_LOCKOUT_CONFIG";
+
+const FAILED_LOGINS_TABLE: &str = "FAILED_LOGINS";
+
+const LOCKOUT_MIGRATION: &str = "2023-03-20-auth-lockout.sql";
+
+/// Add the migration creating the lockout tables to the migrations of a
+/// backend.
+///
+/// # Errors
+///
+/// Returns error if any of the migrations have the same version.
+pub fn with_lockout_migration(
+    mut migrations: Vec<MigrationScript>,
+) -> Result<Vec<MigrationScript>, MigrationError> {
+    migrations.push(migration_script(
+        LOCKOUT_MIGRATION,
+        include_str!("../migrations/2023-03-20-auth-lockout.sql").to_string(),
+    )?);
+    sort_migrations(&mut migrations)?;
+    Ok(migrations)
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct LockoutConfigRaw {
+    attempts: i64,
+    window_secs: i64,
+    lockout_secs: i64,
+}
+
+impl From<LockoutConfigRaw> for LockoutConfig {
+    fn from(raw: LockoutConfigRaw) -> Self {
+        Self {
+            attempts: u32::try_from(raw.attempts).unwrap_or_default(),
+            window: std::time::Duration::from_secs(u64::try_from(raw.window_secs).unwrap_or(0)),
+            lockout_duration: std::time::Duration::from_secs(
+                u64::try_from(raw.lockout_secs).unwrap_or(0),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct FailedLogins {
+    alias: String,
+    attempts: i64,
+    window_start: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+fn storage_error(error: &sqlx::Error) -> ApiError {
+    tracing::error!(?error, "Auth lockout storage error");
+    ApiError::internal_error()
+}
+
+fn to_duration(duration: std::time::Duration) -> Duration {
+    Duration::from_std(duration).unwrap_or_else(|_| Duration::max_value())
+}
+
+fn locked_error(alias: &str) -> ApiError {
+    ApiError::new(
+        ErrorCode::LockedOut,
+        anyhow::Error::msg(format!(
+            "Too many failed login attempts for `{alias}`, try again later"
+        )),
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct LoginLockout {
+    pool: BackendStoragePool,
+}
+
+impl LoginLockout {
+    #[must_use]
+    pub fn new(pool: BackendStoragePool) -> Self {
+        Self { pool }
+    }
+
+    /// Returns the lockout config for the mount.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the config cannot be read from the storage.
+    pub async fn config(&self) -> Result<LockoutConfig, ApiError> {
+        let config: Option<LockoutConfigRaw> = self
+            .pool
+            .query(&format!("SELECT * FROM {CONFIG_TABLE}"))
+            .map_err(|err| storage_error(&err))?
+            .fetch_optional()
+            .await
+            .map_err(|err| storage_error(&err))?;
+        Ok(config.map(Into::into).unwrap_or_default())
+    }
+
+    /// Update the lockout config for the mount.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the config cannot be stored.
+    pub async fn set_config(&self, config: &LockoutConfig) -> Result<(), ApiError> {
+        self.pool
+            .query(&format!(
+                "INSERT OR REPLACE INTO {CONFIG_TABLE} (attempts, window_secs, lockout_secs, lock)
+                    VALUES ($1, $2, $3, 1)"
+            ))
+            .map_err(|err| storage_error(&err))?
+            .bind(i64::from(config.attempts))
+            .bind(i64::try_from(config.window.as_secs()).unwrap_or(i64::MAX))
+            .bind(i64::try_from(config.lockout_duration.as_secs()).unwrap_or(i64::MAX))
+            .execute()
+            .await
+            .map(|_| ())
+            .map_err(|err| storage_error(&err))
+    }
+
+    async fn failed_logins(&self, alias: &str) -> Result<Option<FailedLogins>, ApiError> {
+        self.pool
+            .query(&format!(
+                "SELECT * FROM {FAILED_LOGINS_TABLE} WHERE alias = ?"
+            ))
+            .map_err(|err| storage_error(&err))?
+            .bind(alias)
+            .fetch_optional()
+            .await
+            .map_err(|err| storage_error(&err))
+    }
+
+    /// Check that the alias is allowed to attempt a login. This should be
+    /// called before the credentials are verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the alias is locked.
+    pub async fn check(&self, alias: &str) -> Result<(), ApiError> {
+        let locked = self
+            .failed_logins(alias)
+            .await?
+            .and_then(|failed| failed.locked_until)
+            .is_some_and(|locked_until| locked_until > Utc::now());
+        if locked {
+            return Err(locked_error(alias));
+        }
+        Ok(())
+    }
+
+    /// Record a failed login for the alias and lock it if it has reached the
+    /// max number of failed logins within the window.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the failed login cannot be stored.
+    pub async fn record_failure(&self, alias: &str) -> Result<(), ApiError> {
+        let config = self.config().await?;
+        if config.attempts == 0 {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        self.remove_expired(&config, now).await?;
+        let (attempts, window_start) = match self.failed_logins(alias).await? {
+            // Start over if the window or a previous lockout has expired
+            Some(failed)
+                if failed.window_start + to_duration(config.window) > now
+                    && failed.locked_until.is_none() =>
+            {
+                (failed.attempts + 1, failed.window_start)
+            }
+            _ => (1, now),
+        };
+        let locked_until = (attempts >= i64::from(config.attempts))
+            .then(|| now + to_duration(config.lockout_duration));
+
+        self.pool
+            .query(&format!(
+                "INSERT OR REPLACE INTO {FAILED_LOGINS_TABLE} (alias, attempts, window_start, locked_until)
+                    VALUES ($1, $2, $3, $4)"
+            ))
+            .map_err(|err| storage_error(&err))?
+            .bind(alias)
+            .bind(attempts)
+            .bind(window_start)
+            .bind(locked_until)
+            .execute()
+            .await
+            .map(|_| ())
+            .map_err(|err| storage_error(&err))
+    }
+
+    /// Remove the failed logins whose window and lockout have expired, as
+    /// they no longer affect any login. This also keeps failed logins for
+    /// aliases that don't exist from piling up.
+    async fn remove_expired(
+        &self,
+        config: &LockoutConfig,
+        now: DateTime<Utc>,
+    ) -> Result<(), ApiError> {
+        self.pool
+            .query(&format!(
+                "DELETE FROM {FAILED_LOGINS_TABLE} WHERE
+                    (locked_until IS NULL AND window_start <= $1) OR locked_until <= $2"
+            ))
+            .map_err(|err| storage_error(&err))?
+            .bind(now - to_duration(config.window))
+            .bind(now)
+            .execute()
+            .await
+            .map(|_| ())
+            .map_err(|err| storage_error(&err))
+    }
+
+    /// Reset the failed logins for the alias after a successful login.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the failed logins cannot be removed.
+    pub async fn record_success(&self, alias: &str) -> Result<(), ApiError> {
+        self.unlock(alias).await.map(|_| ())
+    }
+
+    /// List the aliases that are currently locked.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the locked aliases cannot be read from the storage.
+    pub async fn locked(&self) -> Result<Vec<LockedAliasItem>, ApiError> {
+        let locked: Vec<FailedLogins> = self
+            .pool
+            .query(&format!(
+                "SELECT * FROM {FAILED_LOGINS_TABLE} WHERE locked_until > ? ORDER BY alias"
+            ))
+            .map_err(|err| storage_error(&err))?
+            .bind(Utc::now())
+            .fetch_all()
+            .await
+            .map_err(|err| storage_error(&err))?;
+
+        Ok(locked
+            .into_iter()
+            .filter_map(|failed| {
+                failed.locked_until.map(|locked_until| LockedAliasItem {
+                    alias: failed.alias,
+                    failed_attempts: u32::try_from(failed.attempts).unwrap_or(u32::MAX),
+                    locked_until,
+                })
+            })
+            .collect())
+    }
+
+    /// Remove the failed logins for the alias. Returns true if there was
+    /// anything to remove.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the failed logins cannot be removed.
+    pub async fn unlock(&self, alias: &str) -> Result<bool, ApiError> {
+        self.pool
+            .query(&format!(
+                "DELETE FROM {FAILED_LOGINS_TABLE} WHERE alias = ?"
+            ))
+            .map_err(|err| storage_error(&err))?
+            .bind(alias)
+            .execute()
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(|err| storage_error(&err))
+    }
+}
+
+/// Add the `/config/auth-lockout`, `/locked-users` and `/unlock/*alias` routes
+/// to the router. The [`LoginLockout`] must be added as an [`Extension`].
+#[must_use]
+pub fn lockout_routes(router: Router) -> Router {
+    router
+        .route(
+            "/config/auth-lockout",
+            read(handle_read_config)
+                .update(handle_set_config)
+                .create(handle_set_config),
+        )
+        .route("/locked-users", read(handle_list_locked))
+        .route(
+            "/unlock/*alias",
+            crate::update(handle_unlock).create(handle_unlock),
+        )
+}
+
+async fn handle_read_config(
+    Extension(lockout): Extension<Arc<LoginLockout>>,
+) -> Result<Response, ApiError> {
+    let config = lockout.config().await?;
+    Response::raw(config).map_err(|_| ApiError::internal_error())
+}
+
+async fn handle_set_config(
+    Json(config): Json<LockoutConfig>,
+    Extension(lockout): Extension<Arc<LoginLockout>>,
+) -> Result<Response, ApiError> {
+    lockout.set_config(&config).await?;
+    Response::raw(config).map_err(|_| ApiError::internal_error())
+}
+
+async fn handle_list_locked(
+    Extension(lockout): Extension<Arc<LoginLockout>>,
+) -> Result<Response, ApiError> {
+    let aliases = lockout.locked().await?;
+    Response::raw(ListLockedAliasesResponse { aliases }).map_err(|_| ApiError::internal_error())
+}
+
+async fn handle_unlock(
+    Path(alias): Path<String>,
+    Extension(lockout): Extension<Arc<LoginLockout>>,
+) -> Result<Response, ApiError> {
+    if !lockout.unlock(&alias).await? {
+        return Err(ApiError::not_found());
+    }
+    Response::raw(UnlockAliasResponse { alias }).map_err(|_| ApiError::internal_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use covert_storage::EncryptedPool;
+
+    use super::*;
+
+    async fn lockout() -> LoginLockout {
+        let pool = BackendStoragePool::new("foo_", Arc::new(EncryptedPool::new_tmp()));
+        for migration in with_lockout_migration(vec![]).unwrap() {
+            pool.query(&migration.script)
+                .unwrap()
+                .execute()
+                .await
+                .unwrap();
+        }
+        LoginLockout::new(pool)
+    }
+
+    async fn failed_aliases(lockout: &LoginLockout) -> Vec<String> {
+        let failed: Vec<FailedLogins> = lockout
+            .pool
+            .query(&format!(
+                "SELECT * FROM {FAILED_LOGINS_TABLE} ORDER BY alias"
+            ))
+            .unwrap()
+            .fetch_all()
+            .await
+            .unwrap();
+        failed.into_iter().map(|failed| failed.alias).collect()
+    }
+
+    #[tokio::test]
+    async fn expired_failed_logins_are_removed() {
+        let lockout = lockout().await;
+        lockout
+            .set_config(&LockoutConfig {
+                attempts: 2,
+                window: std::time::Duration::from_secs(60),
+                lockout_duration: std::time::Duration::from_secs(60),
+            })
+            .await
+            .unwrap();
+
+        lockout.record_failure("locked").await.unwrap();
+        lockout.record_failure("locked").await.unwrap();
+        lockout.record_failure("unknown").await.unwrap();
+        assert_eq!(failed_aliases(&lockout).await, vec!["locked", "unknown"]);
+        assert!(lockout.check("locked").await.is_err());
+
+        // Move the failed logins and the lockout out of the window
+        let past = Utc::now() - Duration::minutes(5);
+        lockout
+            .pool
+            .query(&format!(
+                "UPDATE {FAILED_LOGINS_TABLE} SET window_start = $1,
+                    locked_until = CASE WHEN locked_until IS NULL THEN NULL ELSE $1 END"
+            ))
+            .unwrap()
+            .bind(past)
+            .execute()
+            .await
+            .unwrap();
+
+        lockout.record_failure("other").await.unwrap();
+        assert_eq!(failed_aliases(&lockout).await, vec!["other"]);
+        assert!(lockout.check("locked").await.is_ok());
+    }
+}
@@ -1,7 +1,7 @@
 use std::{collections::HashMap, future::Future, pin::Pin, task::Poll};
 
 use covert_types::auth::AuthPolicy;
-use covert_types::error::ApiError;
+use covert_types::error::{ApiError, ErrorCode};
 use covert_types::request::{Operation, Request};
 use covert_types::response::Response;
 use tower::{util::BoxCloneService, Service};
@@ -31,6 +31,29 @@ impl RouteConfig {
             ..Default::default()
         }
     }
+
+    /// Require the `sudo` capability, in addition to the capability for the
+    /// operation, for a single handler of a route.
+    #[must_use]
+    pub fn sudo() -> Self {
+        Self {
+            policy: AuthPolicy::Sudo,
+            ..Default::default()
+        }
+    }
+}
+
+impl RouteConfig {
+    /// Whether a caller with the given policy may call the route.
+    fn allows(&self, policy: &AuthPolicy) -> bool {
+        match self.policy {
+            AuthPolicy::Sudo => *policy == AuthPolicy::Sudo,
+            AuthPolicy::Authenticated => {
+                matches!(policy, AuthPolicy::Authenticated | AuthPolicy::Sudo)
+            }
+            AuthPolicy::Unauthenticated => true,
+        }
+    }
 }
 
 impl Default for RouteConfig {
@@ -74,11 +97,7 @@ impl Service<Request> for Route {
         let Some(policy) = req.extensions.get::<AuthPolicy>() else {
             return Box::pin(async { Err(ApiError::unauthorized()) });
         };
-        let auth = match self.config.policy {
-            AuthPolicy::Authenticated => *policy == AuthPolicy::Authenticated,
-            AuthPolicy::Unauthenticated => true,
-        };
-        if !auth {
+        if !self.config.allows(policy) {
             return Box::pin(async { Err(ApiError::unauthorized()) });
         }
 
@@ -86,9 +105,44 @@ impl Service<Request> for Route {
     }
 }
 
+/// Declares whether the responses of a route are backed by a lease.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LeaseMode {
+    /// The handlers decide by returning a lease or not.
+    #[default]
+    Optional,
+    /// The route never produces a lease. A lease returned by a handler is
+    /// reduced to its data so it is never registered with the lease manager.
+    Leaseless,
+    /// Every successful response must be a lease, e.g. for dynamic secrets.
+    Required,
+}
+
+impl LeaseMode {
+    /// Check the response of a handler against the declared mode.
+    fn apply(self, resp: Response) -> Result<Response, ApiError> {
+        match (self, resp) {
+            (LeaseMode::Leaseless, Response::Lease(lease)) => Ok(Response::Raw(lease.data)),
+            (LeaseMode::Leaseless, Response::Auth(_)) => Err(ApiError::new(
+                ErrorCode::Internal,
+                anyhow::Error::msg("Leaseless route returned an auth response"),
+            )),
+            (LeaseMode::Required, resp @ (Response::Lease(_) | Response::Auth(_)))
+            | (LeaseMode::Optional | LeaseMode::Leaseless, resp) => Ok(resp),
+            (LeaseMode::Required, _) => Err(ApiError::new(
+                ErrorCode::Internal,
+                anyhow::Error::msg("Route requires a lease but the handler returned none"),
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MethodRouter {
     routes: HashMap<Operation, Route>,
+    lease_mode: LeaseMode,
+    binary: bool,
+    help: Option<String>,
 }
 
 impl Default for MethodRouter {
@@ -148,7 +202,9 @@ macro_rules! top_level_handlers {
 top_level_handlers!(Create, create, create_with_config);
 top_level_handlers!(Read, read, read_with_config);
 top_level_handlers!(Update, update, update_with_config);
+top_level_handlers!(Patch, patch, patch_with_config);
 top_level_handlers!(Delete, delete, delete_with_config);
+top_level_handlers!(List, list, list_with_config);
 top_level_handlers!(Revoke, revoke, revoke_with_config);
 top_level_handlers!(Renew, renew, renew_with_config);
 
@@ -157,13 +213,18 @@ impl MethodRouter {
     pub fn new() -> Self {
         Self {
             routes: HashMap::default(),
+            lease_mode: LeaseMode::default(),
+            binary: false,
+            help: None,
         }
     }
 
     chained_handlers!(Create, create, create_with_config);
     chained_handlers!(Read, read, read_with_config);
     chained_handlers!(Update, update, update_with_config);
+    chained_handlers!(Patch, patch, patch_with_config);
     chained_handlers!(Delete, delete, delete_with_config);
+    chained_handlers!(List, list, list_with_config);
     chained_handlers!(Revoke, revoke, revoke_with_config);
     chained_handlers!(Renew, renew, renew_with_config);
 
@@ -178,6 +239,71 @@ impl MethodRouter {
         self
     }
 
+    /// Require the `sudo` capability, in addition to the capability for the
+    /// operation, for all handlers of the route.
+    #[must_use]
+    pub fn sudo(mut self) -> Self {
+        for route in self.routes.values_mut() {
+            route.config.policy = AuthPolicy::Sudo;
+        }
+        self
+    }
+
+    /// The handlers of the route never produce leases. See
+    /// [`LeaseMode::Leaseless`].
+    #[must_use]
+    pub fn leaseless(mut self) -> Self {
+        self.lease_mode = LeaseMode::Leaseless;
+        self
+    }
+
+    /// The handlers of the route must produce a lease. See
+    /// [`LeaseMode::Required`].
+    #[must_use]
+    pub fn requires_lease(mut self) -> Self {
+        self.lease_mode = LeaseMode::Required;
+        self
+    }
+
+    pub(crate) fn lease_mode(&self) -> LeaseMode {
+        self.lease_mode
+    }
+
+    /// The handlers of the route return binary or streamed bodies instead of
+    /// JSON. Such responses cannot be wrapped, so requests asking for a
+    /// wrapped response are rejected before the handler runs.
+    #[must_use]
+    pub fn binary(mut self) -> Self {
+        self.binary = true;
+        self
+    }
+
+    /// Describe the route. The text is returned to clients that request help
+    /// for the route.
+    #[must_use]
+    pub fn help(mut self, text: &str) -> Self {
+        self.help = Some(text.to_string());
+        self
+    }
+
+    pub(crate) fn help_text(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Operations that have a handler.
+    pub(crate) fn operations(&self) -> Vec<Operation> {
+        let mut operations = self.routes.keys().copied().collect::<Vec<_>>();
+        operations.sort();
+        operations
+    }
+
+    /// Whether a caller with the given policy may call any of the handlers.
+    pub(crate) fn allows(&self, policy: &AuthPolicy) -> bool {
+        self.routes
+            .values()
+            .any(|route| route.config.allows(policy))
+    }
+
     #[must_use]
     pub fn layer<L>(self, layer: L) -> Self
     where
@@ -198,7 +324,12 @@ impl MethodRouter {
             })
             .collect();
 
-        Self { routes }
+        Self {
+            routes,
+            lease_mode: self.lease_mode,
+            binary: self.binary,
+            help: self.help,
+        }
     }
 }
 
@@ -215,11 +346,25 @@ impl Service<Request> for MethodRouter {
 
     fn call(&mut self, req: Request) -> Self::Future {
         let route = self.routes.get(&req.operation).map(Clone::clone);
+        let supported = if route.is_none() {
+            let mut supported = self.routes.keys().copied().collect::<Vec<_>>();
+            supported.sort_by_key(ToString::to_string);
+            supported
+        } else {
+            vec![]
+        };
+
+        let wrapped = self.binary && matches!(req.wrap_ttl(), Ok(Some(_)));
 
+        let lease_mode = self.lease_mode;
         Box::pin(async move {
             match route {
-                Some(route) => route.oneshot(req).await,
-                None => Err(ApiError::not_found()),
+                Some(_) if wrapped => Err(ApiError::new(
+                    ErrorCode::BadRequest,
+                    anyhow::Error::msg("Only JSON responses can be wrapped"),
+                )),
+                Some(route) => lease_mode.apply(route.oneshot(req).await?),
+                None => Err(ApiError::method_not_allowed(req.operation, &supported)),
             }
         })
     }
@@ -0,0 +1,194 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use covert_types::{error::ApiError, request::Request, response::Response};
+use futures::future::BoxFuture;
+use tower::{util::BoxCloneService, Layer, Service, ServiceExt};
+
+/// Per-request behaviour wrapped around the route handlers.
+///
+/// A middleware receives the typed [`Request`] and the rest of the handler
+/// chain as [`Next`]. It can modify the request before calling [`Next::run`],
+/// inspect or replace the [`Response`] afterwards, or short-circuit the chain
+/// by returning without calling the handler at all.
+///
+/// Middleware registered on a [`Router`](crate::Router) runs in registration
+/// order: the first one registered sees the request first and the response
+/// last.
+#[async_trait::async_trait]
+pub trait Middleware: Send + Sync + 'static {
+    async fn call(&self, req: Request, next: Next) -> Result<Response, ApiError>;
+}
+
+/// The remaining middleware and the route handler.
+pub struct Next {
+    inner: BoxCloneService<Request, Response, ApiError>,
+}
+
+impl Next {
+    /// Pass the request to the rest of the chain.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if any of the remaining middleware or the handler fails.
+    pub async fn run(self, req: Request) -> Result<Response, ApiError> {
+        self.inner.oneshot(req).await
+    }
+}
+
+/// [`Layer`] that wraps a service with a [`Middleware`].
+pub struct MiddlewareLayer<M> {
+    middleware: Arc<M>,
+}
+
+impl<M> MiddlewareLayer<M> {
+    pub fn new(middleware: M) -> Self {
+        Self {
+            middleware: Arc::new(middleware),
+        }
+    }
+}
+
+impl<M> Clone for MiddlewareLayer<M> {
+    fn clone(&self) -> Self {
+        Self {
+            middleware: Arc::clone(&self.middleware),
+        }
+    }
+}
+
+impl<S, M> Layer<S> for MiddlewareLayer<M>
+where
+    S: Service<Request, Response = Response, Error = ApiError> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    M: Middleware,
+{
+    type Service = MiddlewareService<M>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MiddlewareService {
+            inner: BoxCloneService::new(inner),
+            middleware: Arc::clone(&self.middleware),
+        }
+    }
+}
+
+pub struct MiddlewareService<M> {
+    inner: BoxCloneService<Request, Response, ApiError>,
+    middleware: Arc<M>,
+}
+
+impl<M> Clone for MiddlewareService<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            middleware: Arc::clone(&self.middleware),
+        }
+    }
+}
+
+impl<M: Middleware> Service<Request> for MiddlewareService<M> {
+    type Response = Response;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Response, ApiError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let middleware = Arc::clone(&self.middleware);
+        let next = Next {
+            inner: self.inner.clone(),
+        };
+        Box::pin(async move { middleware.call(req, next).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use covert_types::{auth::AuthPolicy, request::Operation, state::StorageState};
+    use hyper::http::Extensions;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use crate::{extract::Extension, read, Router};
+
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    struct Record {
+        name: &'static str,
+        log: Log,
+    }
+
+    #[async_trait::async_trait]
+    impl Middleware for Record {
+        async fn call(&self, req: Request, next: Next) -> Result<Response, ApiError> {
+            self.log.lock().unwrap().push(format!("{} pre", self.name));
+            let resp = next.run(req).await;
+            self.log.lock().unwrap().push(format!("{} post", self.name));
+            resp
+        }
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn handler(Extension(log): Extension<Log>) -> Result<Response, ApiError> {
+        log.lock().unwrap().push("handler".to_string());
+        Ok(Response::Raw(Value::Null))
+    }
+
+    fn request() -> Request {
+        let mut extensions = Extensions::default();
+        extensions.insert(StorageState::Unsealed);
+        extensions.insert(AuthPolicy::Authenticated);
+        Request {
+            id: Uuid::default(),
+            operation: Operation::Read,
+            path: "/foo".to_string(),
+            namespace: vec![],
+            data: Vec::default().into(),
+            extensions,
+            token: None,
+            params: vec![],
+            query_string: String::default(),
+            headers: HashMap::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_in_registration_order() {
+        let log = Log::default();
+        let router = Router::new()
+            .route("/foo", read(handler))
+            .middleware(Record {
+                name: "first",
+                log: Arc::clone(&log),
+            })
+            .layer(Extension(Arc::clone(&log)))
+            .middleware(Record {
+                name: "second",
+                log: Arc::clone(&log),
+            })
+            .build();
+
+        router.oneshot(request()).await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "first pre",
+                "second pre",
+                "handler",
+                "second post",
+                "first post"
+            ]
+        );
+    }
+}
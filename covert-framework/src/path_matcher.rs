@@ -0,0 +1,400 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use covert_types::error::{ApiError, ErrorCode};
+
+/// Error returned when a route cannot be registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// Both routes match exactly the same paths.
+    Conflict { route: String, existing: String },
+    /// The route pattern is malformed.
+    InvalidPattern { route: String, reason: &'static str },
+}
+
+impl Display for RouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RouteError::Conflict { route, existing } => {
+                write!(
+                    f,
+                    "Route `{route}` conflicts with existing route `{existing}`"
+                )
+            }
+            RouteError::InvalidPattern { route, reason } => {
+                write!(f, "Invalid route `{route}`: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Normalize a request path before it is matched against the routes.
+///
+/// Duplicate slashes are collapsed, `.` segments are removed and `..`
+/// segments remove the preceding segment. A single trailing slash is kept as
+/// it is meaningful for wildcard captures, e.g. to list a directory. The
+/// returned path has no leading slash.
+///
+/// # Errors
+///
+/// Returns a bad request error if a `..` segment would move above the root of
+/// the path.
+pub fn normalize_path(path: &str) -> Result<String, ApiError> {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(ApiError::new(
+                        ErrorCode::BadRequest,
+                        anyhow::Error::msg(format!(
+                            "Invalid path `{path}`: traverses above the root"
+                        )),
+                    ));
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let mut normalized = segments.join("/");
+    if path.ends_with('/') && !normalized.is_empty() {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    Param(String),
+    CatchAll(String),
+}
+
+impl Segment {
+    /// Specificity of the segment, higher is more specific.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 2,
+            Segment::Param(_) => 1,
+            Segment::CatchAll(_) => 0,
+        }
+    }
+
+    /// Two segments have the same shape if they match exactly the same values.
+    fn same_shape(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Segment::Static(a), Segment::Static(b)) => a == b,
+            (a, b) => a.rank() == b.rank(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn parse(raw: &str) -> Result<Self, RouteError> {
+        let invalid = |reason| RouteError::InvalidPattern {
+            route: raw.to_string(),
+            reason,
+        };
+
+        let parts = raw.trim_start_matches('/').split('/').collect::<Vec<_>>();
+        let mut segments: Vec<Segment> = Vec::with_capacity(parts.len());
+        for part in parts {
+            let after_wildcard = segments
+                .iter()
+                .any(|segment| matches!(segment, Segment::CatchAll(_)));
+            let segment = if let Some(name) = part.strip_prefix('*') {
+                if after_wildcard {
+                    return Err(invalid("a route can only have one wildcard"));
+                }
+                Segment::CatchAll(name.to_string())
+            } else if after_wildcard {
+                if part.is_empty() || part.starts_with(':') || part.starts_with('{') {
+                    return Err(invalid("only static segments can follow a wildcard"));
+                }
+                Segment::Static(part.to_string())
+            } else if let Some(name) = part.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                Segment::Param(name.to_string())
+            } else {
+                Segment::Static(part.to_string())
+            };
+            match &segment {
+                Segment::Param(name) | Segment::CatchAll(name) if name.is_empty() => {
+                    return Err(invalid("parameters must be named"));
+                }
+                _ => (),
+            }
+            segments.push(segment);
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            segments,
+        })
+    }
+
+    fn same_shape(&self, other: &Self) -> bool {
+        self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&other.segments)
+                .all(|(a, b)| a.same_shape(b))
+    }
+
+    /// Match the path against the pattern and return the captured parameters.
+    fn matches(&self, path: &[&str]) -> Option<Vec<(String, String)>> {
+        let mut params = vec![];
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Static(s) => {
+                    if path.get(i) != Some(&s.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => match path.get(i) {
+                    Some(value) if !value.is_empty() => {
+                        params.push((name.clone(), (*value).to_string()));
+                    }
+                    _ => return None,
+                },
+                Segment::CatchAll(name) => {
+                    // The static segments after the wildcard match the end of
+                    // the path
+                    let suffix = &self.segments[i + 1..];
+                    let end = path.len().checked_sub(suffix.len())?;
+                    let matches_suffix = path.get(end..)?.iter().zip(suffix).all(
+                        |(value, segment)| matches!(segment, Segment::Static(s) if s == value),
+                    );
+                    let rest = path.get(i..end).map(|rest| rest.join("/"))?;
+                    if !matches_suffix || rest.is_empty() {
+                        return None;
+                    }
+                    params.push((name.clone(), rest));
+                    return Some(params);
+                }
+            }
+        }
+
+        (path.len() == self.segments.len()).then_some(params)
+    }
+
+    /// Compare the specificity of two patterns matching the same path. At the
+    /// first segment where they differ a static segment wins over a parameter
+    /// which wins over a wildcard. Of two wildcards the one followed by more
+    /// static segments wins.
+    fn specificity(&self, other: &Self) -> Ordering {
+        self.segments
+            .iter()
+            .zip(&other.segments)
+            .map(|(a, b)| a.rank().cmp(&b.rank()))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| self.segments.len().cmp(&other.segments.len()))
+    }
+}
+
+/// Match paths against a set of route patterns.
+///
+/// Patterns are `/` separated segments where a segment is either static, a
+/// named parameter (`:name` or `{name}`) matching a single segment or a
+/// wildcard (`*name`) matching one or more segments. Only static segments can
+/// follow the wildcard, e.g. `/mounts/*path/tune`, and they must match the end
+/// of the path. The most specific route matching a path is selected
+/// regardless of the order the routes were inserted in.
+#[derive(Debug, Clone)]
+pub(crate) struct PathMatcher<T> {
+    routes: Vec<(Pattern, T)>,
+}
+
+impl<T> Default for PathMatcher<T> {
+    fn default() -> Self {
+        Self { routes: Vec::new() }
+    }
+}
+
+impl<T> PathMatcher<T> {
+    pub fn insert(&mut self, route: &str, value: T) -> Result<(), RouteError> {
+        let pattern = Pattern::parse(route)?;
+        if let Some((existing, _)) = self.routes.iter().find(|(p, _)| p.same_shape(&pattern)) {
+            return Err(RouteError::Conflict {
+                route: route.to_string(),
+                existing: existing.raw.clone(),
+            });
+        }
+        self.routes.push((pattern, value));
+        Ok(())
+    }
+
+    /// Find the most specific route matching the path.
+    pub fn at(&self, path: &str) -> Option<(&T, Vec<(String, String)>)> {
+        let path = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+        self.routes
+            .iter()
+            .filter_map(|(pattern, value)| {
+                pattern
+                    .matches(&path)
+                    .map(|params| (pattern, value, params))
+            })
+            .max_by(|(a, ..), (b, ..)| a.specificity(b))
+            .map(|(_, value, params)| (value, params))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(routes: &[&'static str]) -> PathMatcher<&'static str> {
+        let mut matcher = PathMatcher::default();
+        for route in routes {
+            matcher.insert(route, *route).unwrap();
+        }
+        matcher
+    }
+
+    fn at(matcher: &PathMatcher<&'static str>, path: &str) -> Option<&'static str> {
+        matcher.at(path).map(|(route, _)| *route)
+    }
+
+    #[test]
+    fn static_wins_over_params_and_wildcards() {
+        let routes = ["/keys/*name", "/keys/:name", "/keys/rotate"];
+        // Registration order must not matter
+        for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            let matcher = matcher(&order.map(|i| routes[i]));
+            assert_eq!(at(&matcher, "/keys/rotate"), Some("/keys/rotate"));
+            assert_eq!(at(&matcher, "/keys/foo"), Some("/keys/:name"));
+            assert_eq!(at(&matcher, "/keys/foo/bar"), Some("/keys/*name"));
+            assert_eq!(at(&matcher, "/keys/rotate/bar"), Some("/keys/*name"));
+            assert_eq!(at(&matcher, "/keys/"), None);
+            assert_eq!(at(&matcher, "/keys"), None);
+        }
+    }
+
+    #[test]
+    fn earlier_segments_decide_precedence() {
+        let matcher = matcher(&["/users/:name/password", "/users/admin/*rest"]);
+        assert_eq!(
+            at(&matcher, "/users/admin/password"),
+            Some("/users/admin/*rest")
+        );
+        assert_eq!(
+            at(&matcher, "/users/foo/password"),
+            Some("/users/:name/password")
+        );
+    }
+
+    #[test]
+    fn static_segments_after_wildcard() {
+        let routes = ["/mounts/*path", "/mounts/*path/tune", "/mounts/*path/paths"];
+        for order in [[0, 1, 2], [2, 1, 0]] {
+            let matcher = matcher(&order.map(|i| routes[i]));
+            assert_eq!(
+                matcher.at("/mounts/apps/kv/tune").unwrap(),
+                (
+                    &"/mounts/*path/tune",
+                    vec![("path".to_string(), "apps/kv".to_string())]
+                )
+            );
+            assert_eq!(
+                at(&matcher, "/mounts/kv/paths"),
+                Some("/mounts/*path/paths")
+            );
+            // A trailing slash ends a wildcard capture
+            assert_eq!(
+                matcher.at("/mounts/kv/tune/").unwrap(),
+                (
+                    &"/mounts/*path",
+                    vec![("path".to_string(), "kv/tune/".to_string())]
+                )
+            );
+            assert_eq!(
+                at(&matcher, "/mounts/kv/tune/tune"),
+                Some("/mounts/*path/tune")
+            );
+            assert_eq!(at(&matcher, "/mounts/kv/tunes"), Some("/mounts/*path"));
+            // The wildcard captures at least one segment
+            assert_eq!(at(&matcher, "/mounts/tune"), Some("/mounts/*path"));
+        }
+    }
+
+    #[test]
+    fn captures_params() {
+        let matcher = matcher(&["/data/{key}/versions/:version", "/leases/*lease_id"]);
+        assert_eq!(
+            matcher.at("/data/foo/versions/2").unwrap().1,
+            vec![
+                ("key".to_string(), "foo".to_string()),
+                ("version".to_string(), "2".to_string())
+            ]
+        );
+        assert_eq!(
+            matcher.at("leases/kv/creds/abc").unwrap().1,
+            vec![("lease_id".to_string(), "kv/creds/abc".to_string())]
+        );
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        for (path, normalized) in [
+            ("", ""),
+            ("/", ""),
+            ("/mounts/secret", "mounts/secret"),
+            ("mounts/secret/", "mounts/secret/"),
+            ("//mounts///secret//", "mounts/secret/"),
+            ("/mounts/./secret", "mounts/secret"),
+            ("/mounts/foo/../secret", "mounts/secret"),
+            ("/mounts/secret/..", "mounts"),
+            ("/mounts/..", ""),
+            ("/mounts/%2e%2e/secret", "mounts/%2e%2e/secret"),
+        ] {
+            assert_eq!(normalize_path(path).unwrap(), normalized, "{path}");
+        }
+
+        for path in ["..", "/../sys", "/mounts/../../sys", "mounts/../.."] {
+            assert!(normalize_path(path).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn rejects_conflicting_routes() {
+        let mut matcher = matcher(&["/keys/:name", "/keys/*rest", "/config"]);
+        for (route, existing) in [
+            ("/keys/:id", "/keys/:name"),
+            ("/keys/{id}", "/keys/:name"),
+            ("/keys/*other", "/keys/*rest"),
+            ("/config", "/config"),
+        ] {
+            assert_eq!(
+                matcher.insert(route, route),
+                Err(RouteError::Conflict {
+                    route: route.to_string(),
+                    existing: existing.to_string(),
+                })
+            );
+        }
+        for route in ["/keys/*rest/:name", "/keys/*rest/*other", "/keys/*rest/"] {
+            assert!(
+                matches!(
+                    matcher.insert(route, ""),
+                    Err(RouteError::InvalidPattern { .. })
+                ),
+                "{route}"
+            );
+        }
+        assert!(matches!(
+            matcher.insert("/keys/:", ""),
+            Err(RouteError::InvalidPattern { .. })
+        ));
+    }
+}
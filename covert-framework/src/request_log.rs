@@ -0,0 +1,251 @@
+use std::{
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use covert_types::{error::ApiError, request::Request};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tower::{Layer, Service};
+
+/// Request logging settings.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RequestLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Fraction of the successful requests that are logged, between 0 and 1.
+    /// Failed requests are always logged.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Level of the logs of successful requests.
+    #[serde(default)]
+    pub level: LogLevel,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_rate: default_sample_rate(),
+            level: LogLevel::default(),
+        }
+    }
+}
+
+impl RequestLogConfig {
+    /// Check that the sample rate is a fraction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sample rate is not between 0 and 1.
+    pub fn validate(&self) -> Result<(), String> {
+        if (0.0..=1.0).contains(&self.sample_rate) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Request log sample rate must be between 0 and 1, got {}",
+                self.sample_rate
+            ))
+        }
+    }
+
+    /// Whether the successful request should be logged. The decision is made
+    /// from the random request id, so it is stable for a request without
+    /// needing a random number generator on the hot path.
+    fn sampled(&self, req: &Request) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        // Upper half of the id as a fraction in [0, 1). The fixed variant bits
+        // of a v4 UUID are at the top of the lower half, the fixed version
+        // bits only touch insignificant bits of the fraction.
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = (req.id.as_u128() >> 64) as u64 as f64 / (u64::MAX as f64 + 1.0);
+        fraction < self.sample_rate
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+/// Logs one line with the operation, namespace, status and latency of a
+/// sampled fraction of the requests, and of every failed request.
+///
+/// The server handles every request inside a `request` span carrying the
+/// request id, method, path, token accessor and entity, which are included
+/// in the line by the log formatter.
+#[derive(Debug, Clone)]
+pub struct RequestLogService<S> {
+    inner: S,
+    config: RequestLogConfig,
+}
+
+impl<S, R> Service<Request> for RequestLogService<S>
+where
+    S: Service<Request, Response = R, Error = ApiError>,
+    S::Future: Send + 'static,
+{
+    type Response = R;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        if !self.config.enabled {
+            return Box::pin(self.inner.call(req));
+        }
+
+        let sampled = self.config.sampled(&req);
+        let level = self.config.level;
+        let operation = format!("{:?}", req.operation).to_lowercase();
+        let namespace = req.namespace.join("/");
+        let start = Instant::now();
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let resp = fut.await;
+            #[allow(clippy::cast_possible_truncation)]
+            let latency_ms = start.elapsed().as_millis() as u64;
+            match &resp {
+                Ok(_) if sampled => log(level, &operation, &namespace, latency_ms),
+                Ok(_) => (),
+                Err(error) if error.status_code.is_server_error() => tracing::error!(
+                    operation,
+                    namespace,
+                    status = error.status_code.as_u16(),
+                    latency_ms,
+                    error = %error.error,
+                    "request failed"
+                ),
+                Err(error) => tracing::warn!(
+                    operation,
+                    namespace,
+                    status = error.status_code.as_u16(),
+                    latency_ms,
+                    error = %error.error,
+                    "request failed"
+                ),
+            }
+            resp
+        })
+    }
+}
+
+fn log(level: LogLevel, operation: &str, namespace: &str, latency_ms: u64) {
+    // Successful responses are always sent with status 200
+    let status = 200;
+    macro_rules! log {
+        ($level:ident) => {
+            tracing::$level!(
+                operation,
+                namespace,
+                status,
+                latency_ms,
+                "request completed"
+            )
+        };
+    }
+    match level {
+        LogLevel::Trace => log!(trace),
+        LogLevel::Debug => log!(debug),
+        LogLevel::Info => log!(info),
+        LogLevel::Warn => log!(warn),
+        LogLevel::Error => log!(error),
+    }
+}
+
+/// [`Layer`] that logs requests with [`RequestLogService`].
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLogLayer {
+    config: RequestLogConfig,
+}
+
+impl RequestLogLayer {
+    #[must_use]
+    pub fn new(config: RequestLogConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Layer<S> for RequestLogLayer {
+    type Service = RequestLogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLogService {
+            inner,
+            config: self.config,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use covert_types::request::Operation;
+    use hyper::http::Extensions;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn request(id: u128) -> Request {
+        Request {
+            id: Uuid::from_u128(id),
+            operation: Operation::Read,
+            path: "/foo".to_string(),
+            namespace: vec![],
+            data: Vec::default().into(),
+            extensions: Extensions::default(),
+            token: None,
+            params: vec![],
+            query_string: String::default(),
+            headers: HashMap::default(),
+        }
+    }
+
+    #[test]
+    fn samples_by_request_id() {
+        let config = |sample_rate| RequestLogConfig {
+            enabled: true,
+            sample_rate,
+            level: LogLevel::Info,
+        };
+        let low = request(1);
+        let high = request(u128::MAX);
+        let middle = request(1 << 127);
+
+        assert!(config(1.0).sampled(&high));
+        assert!(!config(0.0).sampled(&low));
+        assert!(config(0.5).sampled(&low));
+        assert!(!config(0.5).sampled(&middle));
+        assert!(config(0.6).sampled(&middle));
+        assert!(!config(0.99).sampled(&high));
+    }
+
+    #[test]
+    fn sample_rate_must_be_fraction() {
+        let mut config = RequestLogConfig::default();
+        assert!(config.validate().is_ok());
+        config.sample_rate = 1.5;
+        assert!(config.validate().is_err());
+        config.sample_rate = -0.1;
+        assert!(config.validate().is_err());
+    }
+}
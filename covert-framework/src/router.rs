@@ -1,20 +1,41 @@
-use std::{future::Future, marker::PhantomData, pin::Pin};
+use std::{future::Future, marker::PhantomData, pin::Pin, sync::Arc};
 
-use covert_types::{error::ApiError, request::Request, response::Response};
+use covert_types::{
+    auth::AuthPolicy,
+    backend::RouteHelp,
+    error::ApiError,
+    request::{Operation, Request},
+    response::Response,
+};
 use tower::{Layer, Service, ServiceExt};
 
 use super::{
-    method_router::{MethodRouter, Route},
+    method_router::{LeaseMode, MethodRouter, Route},
+    middleware::{Middleware, MiddlewareLayer},
+    path_matcher::{normalize_path, PathMatcher, RouteError},
     SyncService,
 };
 
 pub struct Building;
 pub struct Ready;
 
-/// Wrapper around `matchit::Router`
+type BoxLayer = Arc<dyn Fn(MethodRouter) -> MethodRouter + Send + Sync>;
+
+/// Router dispatching requests to the most specific matching route.
+///
+/// Routes are matched segment by segment, at the first segment where two
+/// matching routes differ a static segment is preferred over a parameter
+/// (`:name` or `{name}`) which is preferred over a wildcard (`*name`). A
+/// wildcard can be followed by static segments, e.g. `/mounts/*path/tune`,
+/// which is preferred over the same wildcard without them. The registration
+/// order of the routes does not matter.
 pub struct Router<Stage = Building> {
-    routes: Vec<(&'static str, MethodRouter)>,
-    router: matchit::Router<MethodRouter>,
+    routes: Vec<(String, MethodRouter)>,
+    nested: Vec<(String, Router)>,
+    sudo_paths: Vec<String>,
+    leaseless: bool,
+    layers: Vec<BoxLayer>,
+    router: PathMatcher<(String, MethodRouter)>,
     _marker: PhantomData<Stage>,
 }
 
@@ -29,49 +50,195 @@ impl Router {
     pub fn new() -> Self {
         Self {
             routes: Vec::default(),
-            router: matchit::Router::default(),
+            nested: Vec::default(),
+            sudo_paths: Vec::default(),
+            leaseless: false,
+            layers: Vec::default(),
+            router: PathMatcher::default(),
             _marker: PhantomData,
         }
     }
 
     #[must_use]
-    pub fn route(mut self, path: &'static str, route: MethodRouter) -> Self {
-        self.routes.push((path, route));
+    pub fn route(mut self, path: &str, route: MethodRouter) -> Self {
+        self.routes.push((path.to_string(), route));
+        self
+    }
+
+    /// Mount all routes of `router` under `prefix`.
+    ///
+    /// The layers, sudo paths and lease declarations of the nested router
+    /// only apply to its own routes, the layers of this router wrap them. The
+    /// routes are checked for conflicts with all other routes when the router
+    /// is built.
+    #[must_use]
+    pub fn nest(mut self, prefix: &str, router: Router) -> Self {
+        self.nested
+            .push((prefix.trim_end_matches('/').to_string(), router));
+        self
+    }
+
+    /// Add all routes of `router` to this router. Same as nesting it without
+    /// a prefix.
+    #[must_use]
+    pub fn merge(self, router: Router) -> Self {
+        self.nest("", router)
+    }
+
+    /// Mark routes as privileged. The policy of the caller must grant the
+    /// `sudo` capability on the path, in addition to the capability for the
+    /// operation, for all handlers of the routes. The paths must be route
+    /// patterns registered with [`Router::route`].
+    #[must_use]
+    pub fn sudo_paths(mut self, paths: &[&str]) -> Self {
+        self.sudo_paths
+            .extend(paths.iter().map(ToString::to_string));
         self
     }
 
+    /// Declare all routes of the backend leaseless, unless a route declares
+    /// its own [`LeaseMode`].
+    #[must_use]
+    pub fn leaseless(mut self) -> Self {
+        self.leaseless = true;
+        self
+    }
+
+    /// Wrap all routes with a [`Layer`]. Layers run in registration order,
+    /// the first one registered sees the request first.
     #[must_use]
     pub fn layer<L>(mut self, layer: L) -> Self
     where
-        L: Layer<Route>,
+        L: Layer<Route> + Send + Sync + 'static,
         L::Service:
             Service<Request, Error = ApiError, Response = Response> + Clone + Send + 'static,
         <L::Service as Service<Request>>::Future: Send + 'static,
     {
-        self.routes = self
-            .routes
-            .into_iter()
-            .map(|(path, route)| (path, route.layer(&layer)))
-            .collect();
+        self.layers
+            .push(Arc::new(move |route: MethodRouter| route.layer(&layer)));
         self
     }
 
-    pub fn build(mut self) -> Router<Ready> {
-        for (path, route) in self.routes.clone() {
-            self.router
-                .insert(path, route)
-                .expect("No path should overlap");
+    /// Wrap all routes with a [`Middleware`].
+    #[must_use]
+    pub fn middleware<M: Middleware>(self, middleware: M) -> Self {
+        self.layer(MiddlewareLayer::new(middleware))
+    }
+
+    /// Build the router.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a route is malformed or conflicts with another route, see
+    /// [`Router::try_build`].
+    #[must_use]
+    pub fn build(self) -> Router<Ready> {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Build the router.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a route is malformed, if two routes match exactly
+    /// the same paths or if a sudo path is not a registered route.
+    pub fn try_build(self) -> Result<Router<Ready>, RouteError> {
+        let layers = self.layers.clone();
+        let sudo_paths = self.sudo_paths.clone();
+        let leaseless = self.leaseless;
+        let routes = self.flatten()?;
+        let mut matcher = PathMatcher::default();
+        for (path, route) in routes.clone() {
+            matcher.insert(&path, (path.clone(), route))?;
         }
-        Router::<Ready> {
-            routes: self.routes,
-            router: self.router,
+        Ok(Router::<Ready> {
+            routes,
+            nested: Vec::default(),
+            sudo_paths,
+            leaseless,
+            layers,
+            router: matcher,
             _marker: PhantomData,
+        })
+    }
+
+    /// Collect the routes of this router and all nested routers with the
+    /// route configs and layers applied.
+    fn flatten(self) -> Result<Vec<(String, MethodRouter)>, RouteError> {
+        let mut routes = self.routes;
+        for (prefix, router) in self.nested {
+            for (path, route) in router.flatten()? {
+                let path = match path.as_str() {
+                    "" | "/" if !prefix.is_empty() => prefix.clone(),
+                    _ => format!("{prefix}{path}"),
+                };
+                routes.push((path, route));
+            }
+        }
+
+        if let Some(path) = self
+            .sudo_paths
+            .iter()
+            .find(|path| !routes.iter().any(|(route, _)| route == *path))
+        {
+            return Err(RouteError::InvalidPattern {
+                route: path.clone(),
+                reason: "sudo path is not a registered route",
+            });
         }
+
+        // The last registered layer is applied first so that it ends up
+        // closest to the handler.
+        Ok(routes
+            .into_iter()
+            .map(|(path, route)| {
+                let route = if self.sudo_paths.contains(&path) {
+                    route.sudo()
+                } else {
+                    route
+                };
+                let route = if self.leaseless && route.lease_mode() == LeaseMode::Optional {
+                    route.leaseless()
+                } else {
+                    route
+                };
+                let route = self
+                    .layers
+                    .iter()
+                    .rev()
+                    .fold(route, |route, layer| layer(route));
+                (path, route)
+            })
+            .collect())
     }
 }
 
+/// Whether the query string asks for the description of the route instead of
+/// calling it.
+fn wants_help(query: &str) -> bool {
+    form_urlencoded::parse(query.as_bytes())
+        .any(|(key, value)| key == "help" && matches!(&*value, "1" | "true"))
+}
+
 impl Router<Ready> {
+    /// Describe all routes of the router, sorted by path.
+    #[must_use]
+    pub fn paths(&self) -> Vec<RouteHelp> {
+        let mut paths = self
+            .routes
+            .iter()
+            .map(|(path, route)| RouteHelp {
+                path: path.clone(),
+                operations: route.operations(),
+                help: route.help_text().map(ToString::to_string),
+            })
+            .collect::<Vec<_>>();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        paths
+    }
+
     // TODO: rename to `into_make_service`?
+    #[must_use]
     pub fn into_service(self) -> SyncService<Request, Response> {
         SyncService::new(self)
     }
@@ -81,6 +248,10 @@ impl Clone for Router<Ready> {
     fn clone(&self) -> Self {
         Self {
             routes: self.routes.clone(),
+            nested: Vec::default(),
+            sudo_paths: self.sudo_paths.clone(),
+            leaseless: self.leaseless,
+            layers: self.layers.clone(),
             router: self.router.clone(),
             _marker: PhantomData,
         }
@@ -103,20 +274,442 @@ impl Service<Request> for Router<Ready> {
     }
 
     fn call(&mut self, mut req: Request) -> Self::Future {
-        let prefixed_path = if req.path.starts_with('/') {
-            req.path.clone()
-        } else {
-            format!("/{}", req.path)
+        let path = match normalize_path(&req.path) {
+            Ok(path) => path,
+            Err(err) => return Box::pin(async { Err(err) }),
         };
-        let Ok(matched_router) =  self.router.at(&prefixed_path) else {
+        let prefixed_path = format!("/{path}");
+        // A trailing slash is only significant for wildcard captures, other
+        // routes match with or without it.
+        let matched = self.router.at(&prefixed_path).or_else(|| {
+            prefixed_path
+                .strip_suffix('/')
+                .and_then(|path| self.router.at(path))
+        });
+        let Some(((pattern, matched_router), params)) = matched else {
             return Box::pin(async { Err(ApiError::not_found()) });
         };
-        req.params = matched_router
-            .params
-            .iter()
-            .map(|(_key, val)| val.to_string())
-            .collect();
-        let matched_router = matched_router.value.clone();
+
+        if matches!(req.operation, Operation::Read | Operation::List)
+            && wants_help(&req.query_string)
+        {
+            // Only describe routes the caller is allowed to call
+            let allowed = req
+                .extensions
+                .get::<AuthPolicy>()
+                .is_some_and(|policy| matched_router.allows(policy));
+            if !allowed {
+                return Box::pin(async { Err(ApiError::unauthorized()) });
+            }
+            let help = RouteHelp {
+                path: pattern.clone(),
+                operations: matched_router.operations(),
+                help: matched_router.help_text().map(ToString::to_string),
+            };
+            return Box::pin(
+                async move { Response::raw(help).map_err(|_| ApiError::internal_error()) },
+            );
+        }
+
+        req.path = path;
+        req.params = params;
+        let matched_router = matched_router.clone();
         Box::pin(async move { matched_router.oneshot(req).await })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use crate::middleware::Next;
+    use covert_types::{
+        auth::AuthPolicy,
+        error::StatusCode,
+        request::WRAP_TTL_HEADER,
+        response::{LeaseRenewRevokeEndpoint, LeaseResponse},
+        state::StorageState,
+    };
+    use hyper::http::Extensions;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use crate::{
+        extract::{Extension, Path},
+        read, read_with_config, update, RouteConfig,
+    };
+
+    use super::*;
+
+    #[allow(clippy::unused_async)]
+    async fn counter(Extension(calls): Extension<Arc<AtomicU64>>) -> Result<Response, ApiError> {
+        let calls = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(Response::Raw(Value::from(calls)))
+    }
+
+    fn request(operation: Operation, path: &str) -> Request {
+        let mut extensions = Extensions::default();
+        extensions.insert(StorageState::Unsealed);
+        extensions.insert(AuthPolicy::Authenticated);
+        Request {
+            id: Uuid::default(),
+            operation,
+            path: path.to_string(),
+            namespace: vec![],
+            data: Vec::default().into(),
+            extensions,
+            token: None,
+            params: vec![],
+            query_string: String::default(),
+            headers: HashMap::default(),
+        }
+    }
+
+    async fn call(router: &Router<Ready>, operation: Operation, path: &str) -> Value {
+        match router.clone().oneshot(request(operation, path)).await {
+            Ok(Response::Raw(value)) => value,
+            resp => panic!("unexpected response: {resp:?}"),
+        }
+    }
+
+    #[test]
+    fn conflicting_routes_are_rejected() {
+        let err = Router::new()
+            .route("/users/:name", read(counter))
+            .route("/users/{username}", update(counter))
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Route `/users/{username}` conflicts with existing route `/users/:name`"
+        );
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn echo(Path(path): Path<String>) -> Result<Response, ApiError> {
+        Ok(Response::Raw(Value::from(path)))
+    }
+
+    #[tokio::test]
+    async fn paths_are_normalized_before_matching() {
+        let calls = Arc::new(AtomicU64::default());
+        let router = Router::new()
+            .route("/config", read(counter))
+            .route("/metadata/*path", read(echo))
+            .layer(Extension(Arc::clone(&calls)))
+            .build();
+
+        for path in [
+            "/config",
+            "config",
+            "/config/",
+            "//config",
+            "/./config",
+            "/a/../config",
+        ] {
+            assert!(
+                call(&router, Operation::Read, path).await.is_u64(),
+                "{path}"
+            );
+        }
+        // Only a single trailing slash is ignored
+        assert!(router
+            .clone()
+            .oneshot(request(Operation::Read, "/config/foo"))
+            .await
+            .is_err());
+
+        // The trailing slash is kept for wildcard captures
+        for (path, captured) in [
+            ("/metadata/foo", "foo"),
+            ("/metadata/foo/", "foo/"),
+            ("/metadata//foo//bar/", "foo/bar/"),
+            ("/metadata/foo/./bar", "foo/bar"),
+            ("/metadata/foo/../bar", "bar"),
+        ] {
+            assert_eq!(
+                call(&router, Operation::Read, path).await,
+                Value::from(captured),
+                "{path}"
+            );
+        }
+
+        for path in ["/..", "/metadata/../../config", "../config"] {
+            let err = router
+                .clone()
+                .oneshot(request(Operation::Read, path))
+                .await
+                .unwrap_err();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn binary_routes_are_not_wrapped() {
+        let calls = Arc::new(AtomicU64::default());
+        let router = Router::new()
+            .route("/json", read(counter))
+            .route("/bundle", read(counter).binary())
+            .layer(Extension(Arc::clone(&calls)))
+            .build();
+
+        let wrapped = |path| {
+            let mut req = request(Operation::Read, path);
+            req.headers
+                .insert(WRAP_TTL_HEADER.to_lowercase(), "5m".to_string());
+            req
+        };
+        let err = router
+            .clone()
+            .oneshot(wrapped("/bundle"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // Wrapping is left to the server for the other routes
+        assert!(router.clone().oneshot(wrapped("/json")).await.is_ok());
+        assert_eq!(call(&router, Operation::Read, "/bundle").await, 2);
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn lease() -> Result<Response, ApiError> {
+        let endpoint = || LeaseRenewRevokeEndpoint {
+            path: "/creds".to_string(),
+            data: Value::Null,
+        };
+        Ok(Response::Lease(LeaseResponse {
+            revoke: endpoint(),
+            renew: endpoint(),
+            data: Value::from("secret"),
+            ttl: None,
+            max_ttl: None,
+            tags: BTreeMap::new(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn lease_modes_are_enforced() {
+        let calls = Arc::new(AtomicU64::default());
+        let router = Router::new()
+            .route("/creds", read(lease))
+            .route("/stateless", read(lease).leaseless())
+            .route("/dynamic", read(counter).requires_lease())
+            .layer(Extension(Arc::clone(&calls)))
+            .build();
+
+        let resp = router
+            .clone()
+            .oneshot(request(Operation::Read, "/creds"))
+            .await;
+        assert!(matches!(resp, Ok(Response::Lease(_))));
+
+        // Leases from leaseless routes are reduced to their data
+        assert_eq!(
+            call(&router, Operation::Read, "/stateless").await,
+            Value::from("secret")
+        );
+
+        let resp = router
+            .clone()
+            .oneshot(request(Operation::Read, "/dynamic"))
+            .await;
+        assert!(resp.is_err());
+
+        // The whole backend can be declared leaseless
+        let router = Router::new()
+            .route("/creds", read(lease))
+            .route("/dynamic", read(lease).requires_lease())
+            .leaseless()
+            .build();
+        assert_eq!(
+            call(&router, Operation::Read, "/creds").await,
+            Value::from("secret")
+        );
+        let resp = router
+            .clone()
+            .oneshot(request(Operation::Read, "/dynamic"))
+            .await;
+        assert!(matches!(resp, Ok(Response::Lease(_))));
+    }
+
+    struct Tag(&'static str);
+
+    #[async_trait::async_trait]
+    impl Middleware for Tag {
+        async fn call(&self, req: Request, next: Next) -> Result<Response, ApiError> {
+            match next.run(req).await? {
+                Response::Raw(value) => {
+                    let value = value
+                        .as_str()
+                        .map_or_else(|| value.to_string(), str::to_string);
+                    Ok(Response::Raw(Value::from(format!("{}({value})", self.0))))
+                }
+                resp => Ok(resp),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn nested_routers() {
+        let calls = Arc::new(AtomicU64::default());
+        let users = Router::new()
+            .route("/", read(counter))
+            .route("/:name", update(counter))
+            .sudo_paths(&["/:name"])
+            .middleware(Tag("users"));
+        let config = Router::new().route("/config", read(counter));
+        let router = Router::new()
+            .route("/status", read(counter))
+            .nest("/users/", users)
+            .merge(config)
+            .middleware(Tag("root"))
+            .layer(Extension(Arc::clone(&calls)))
+            .build();
+
+        // Layers of a nested router only wrap its own routes
+        assert_eq!(
+            call(&router, Operation::Read, "/users").await,
+            Value::from("root(users(1))")
+        );
+        assert_eq!(
+            call(&router, Operation::Read, "/status").await,
+            Value::from("root(2)")
+        );
+        assert_eq!(
+            call(&router, Operation::Read, "/config").await,
+            Value::from("root(3)")
+        );
+
+        // Route configs of the nested router are kept
+        let resp = router
+            .clone()
+            .oneshot(request(Operation::Update, "/users/foo"))
+            .await;
+        assert!(resp.is_err());
+        let mut req = request(Operation::Update, "/users/foo");
+        req.extensions.insert(AuthPolicy::Sudo);
+        assert!(router.clone().oneshot(req).await.is_ok());
+
+        // Conflicts are detected across the nested routers
+        let err = Router::new()
+            .route("/users/:id", read(counter))
+            .nest("/users", Router::new().route("/:name", read(counter)))
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            RouteError::Conflict {
+                route: "/users/:name".to_string(),
+                existing: "/users/:id".to_string(),
+            }
+        );
+        assert!(Router::new()
+            .route("/config", read(counter))
+            .merge(Router::new().route("/config", update(counter)))
+            .try_build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn sudo_paths_require_sudo() {
+        let calls = Arc::new(AtomicU64::default());
+        let router = Router::new()
+            .route("/seal", update(counter))
+            .route("/status", read(counter))
+            .sudo_paths(&["/seal"])
+            .layer(Extension(Arc::clone(&calls)))
+            .build();
+
+        let resp = router
+            .clone()
+            .oneshot(request(Operation::Update, "/seal"))
+            .await;
+        assert!(resp.is_err());
+
+        let mut req = request(Operation::Update, "/seal");
+        req.extensions.insert(AuthPolicy::Sudo);
+        assert!(router.clone().oneshot(req).await.is_ok());
+
+        // Sudo also grants access to the routes that do not require it
+        let mut req = request(Operation::Read, "/status");
+        req.extensions.insert(AuthPolicy::Sudo);
+        assert!(router.clone().oneshot(req).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let err = Router::new()
+            .route("/seal", update(counter))
+            .sudo_paths(&["/unseal"])
+            .try_build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, RouteError::InvalidPattern { .. }));
+    }
+
+    #[tokio::test]
+    async fn routes_describe_themselves() {
+        let calls = Arc::new(AtomicU64::default());
+        let router = Router::new()
+            .route(
+                "/data/*path",
+                read(counter)
+                    .update(counter)
+                    .help("Read or write a secret."),
+            )
+            .route("/config", update(counter))
+            .route(
+                "/health",
+                read_with_config(counter, RouteConfig::unauthenticated()),
+            )
+            .layer(Extension(Arc::clone(&calls)))
+            .build();
+
+        let mut req = request(Operation::Read, "/data/foo/bar");
+        req.query_string = "help=1".to_string();
+        let Ok(Response::Raw(value)) = router.clone().oneshot(req).await else {
+            panic!("expected help response");
+        };
+        assert_eq!(
+            serde_json::from_value::<RouteHelp>(value).unwrap(),
+            RouteHelp {
+                path: "/data/*path".to_string(),
+                operations: vec![Operation::Read, Operation::Update],
+                help: Some("Read or write a secret.".to_string()),
+            }
+        );
+        // The handler is not called
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // Help is only given for routes the caller is allowed to call
+        let mut req = request(Operation::Read, "/config");
+        req.query_string = "help=true".to_string();
+        req.extensions.insert(AuthPolicy::Unauthenticated);
+        assert!(router.clone().oneshot(req).await.is_err());
+        let mut req = request(Operation::Read, "/health");
+        req.query_string = "help=1".to_string();
+        req.extensions.insert(AuthPolicy::Unauthenticated);
+        assert!(router.clone().oneshot(req).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        assert_eq!(
+            router
+                .paths()
+                .into_iter()
+                .map(|route| (route.path, route.operations))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/config".to_string(), vec![Operation::Update]),
+                (
+                    "/data/*path".to_string(),
+                    vec![Operation::Read, Operation::Update]
+                ),
+                ("/health".to_string(), vec![Operation::Read]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    println!("cargo:rerun-if-changed=proto/plugin.proto");
+    tonic_build::compile_protos("proto/plugin.proto")?;
+    Ok(())
+}
@@ -0,0 +1,372 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use covert_types::{
+    backend::RouteHelp,
+    error::ApiError,
+    mount::MountConfig,
+    request::{Operation, Request},
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use tokio::{
+    net::UnixStream,
+    process::{Child, Command},
+    sync::Mutex,
+    task::JoinHandle,
+    time::{sleep, timeout, Instant, MissedTickBehavior},
+};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    convert::{request_to_proto, response_from_proto, routes_from_proto},
+    proto::{self, plugin_client::PluginClient as GrpcClient},
+    PluginError, RequestContext, PLUGIN_SOCKET_ENV,
+};
+
+/// Time the plugin is given to listen on its socket after it is started.
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time between two health checks of the plugin.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time the plugin is given to answer a health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time the plugin is given to tear down before it is killed.
+const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound on the wait between two failed restarts.
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How to start a plugin process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginCommand {
+    /// Name the plugin is registered with.
+    pub name: String,
+    /// Path of the plugin binary.
+    pub path: PathBuf,
+    pub args: Vec<String>,
+    /// Hex encoded SHA-256 checksum of the binary. The plugin is only started
+    /// if the binary matches it.
+    pub sha256: String,
+}
+
+/// Client of the plugin process serving a mount. The process is health
+/// checked in the background and restarted if it exits or stops answering.
+pub struct PluginClient {
+    inner: Arc<Inner>,
+    routes: Vec<RouteHelp>,
+    supervisor: JoinHandle<()>,
+}
+
+struct Inner {
+    command: PluginCommand,
+    setup: proto::SetupRequest,
+    process: Mutex<Option<PluginProcess>>,
+}
+
+struct PluginProcess {
+    child: Child,
+    client: GrpcClient<Channel>,
+    socket: PathBuf,
+}
+
+impl Drop for PluginProcess {
+    fn drop(&mut self) {
+        // The process itself is killed on drop
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+impl PluginClient {
+    /// Start the plugin process for the mount.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the binary does not match the checksum or the
+    /// plugin fails to start and set up the mount.
+    pub async fn start(
+        command: PluginCommand,
+        mount_id: Uuid,
+        namespace_id: &str,
+        config: &MountConfig,
+    ) -> Result<Self, PluginError> {
+        let setup = proto::SetupRequest {
+            mount_id: mount_id.to_string(),
+            namespace_id: namespace_id.to_string(),
+            config: serde_json::to_vec(config).unwrap_or_default(),
+        };
+        let (process, routes) = PluginProcess::spawn(&command, &setup).await?;
+        info!(plugin = command.name, %mount_id, "Started plugin");
+
+        let inner = Arc::new(Inner {
+            command,
+            setup,
+            process: Mutex::new(Some(process)),
+        });
+        let supervisor = tokio::spawn(supervise(Arc::downgrade(&inner)));
+
+        Ok(Self {
+            inner,
+            routes,
+            supervisor,
+        })
+    }
+
+    /// Routes served by the plugin.
+    #[must_use]
+    pub fn routes(&self) -> &[RouteHelp] {
+        &self.routes
+    }
+
+    /// Send the request to the plugin. Revocations are sent to the revoke hook
+    /// of the plugin.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the backend of the plugin, or an error if the
+    /// plugin is not running or the call fails.
+    pub async fn handle_request(
+        &self,
+        req: Request,
+        context: RequestContext,
+    ) -> Result<Response, ApiError> {
+        let operation = req.operation;
+        let mut client = self.inner.client().await?;
+        let req = request_to_proto(req, context);
+        let resp = if operation == Operation::Revoke {
+            client.revoke(req).await
+        } else {
+            client.handle_request(req).await
+        }
+        .map_err(PluginError::from)?;
+
+        response_from_proto(resp.into_inner())
+    }
+
+    /// Tear down the mount and stop the plugin process.
+    pub async fn teardown(&self) {
+        self.supervisor.abort();
+        let Some(mut process) = self.inner.process.lock().await.take() else {
+            return;
+        };
+        match timeout(
+            TEARDOWN_TIMEOUT,
+            process.client.teardown(proto::TeardownRequest {}),
+        )
+        .await
+        {
+            Ok(Ok(_)) => (),
+            Ok(Err(error)) => warn!(plugin = self.inner.command.name, ?error, "Teardown failed"),
+            Err(_) => warn!(plugin = self.inner.command.name, "Teardown timed out"),
+        }
+        let _ = process.child.kill().await;
+    }
+}
+
+impl Drop for PluginClient {
+    fn drop(&mut self) {
+        self.supervisor.abort();
+    }
+}
+
+impl Inner {
+    async fn client(&self) -> Result<GrpcClient<Channel>, PluginError> {
+        self.process
+            .lock()
+            .await
+            .as_ref()
+            .map(|process| process.client.clone())
+            .ok_or_else(|| PluginError::Unavailable {
+                name: self.command.name.clone(),
+            })
+    }
+
+    /// Returns true if the process is running and answers health checks.
+    async fn is_healthy(&self) -> bool {
+        let mut client = {
+            let mut process = self.process.lock().await;
+            match process.as_mut() {
+                Some(process) if matches!(process.child.try_wait(), Ok(None)) => {
+                    process.client.clone()
+                }
+                _ => return false,
+            }
+        };
+        matches!(
+            timeout(HEALTH_CHECK_TIMEOUT, client.health(proto::HealthRequest {})).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Replace the process with a new one. Requests wait for the new process.
+    async fn restart(&self) -> Result<(), PluginError> {
+        let mut process = self.process.lock().await;
+        if let Some(mut old) = process.take() {
+            let _ = old.child.kill().await;
+        }
+        let (new, _) = PluginProcess::spawn(&self.command, &self.setup).await?;
+        *process = Some(new);
+        Ok(())
+    }
+}
+
+/// Health check the plugin until the client is dropped and restart it when it
+/// is unhealthy. Failed restarts are retried with an exponential backoff.
+async fn supervise(inner: Weak<Inner>) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut backoff = HEALTH_CHECK_INTERVAL;
+    loop {
+        interval.tick().await;
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        if inner.is_healthy().await {
+            backoff = HEALTH_CHECK_INTERVAL;
+            continue;
+        }
+
+        let name = &inner.command.name;
+        warn!(plugin = name, "Plugin is unhealthy, restarting it");
+        match inner.restart().await {
+            Ok(()) => info!(plugin = name, "Restarted plugin"),
+            Err(error) => {
+                error!(plugin = name, ?error, "Failed to restart plugin");
+                drop(inner);
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+            }
+        }
+    }
+}
+
+impl PluginProcess {
+    /// Start the plugin and set up the mount. The process is killed if it
+    /// fails to set up.
+    async fn spawn(
+        command: &PluginCommand,
+        setup: &proto::SetupRequest,
+    ) -> Result<(Self, Vec<RouteHelp>), PluginError> {
+        verify_checksum(&command.path, &command.sha256).await?;
+
+        let start_error = |error: String| PluginError::Start {
+            name: command.name.clone(),
+            error,
+        };
+        let socket =
+            std::env::temp_dir().join(format!("covert-plugin-{}.sock", Uuid::new_v4().to_simple()));
+        let mut child = Command::new(&command.path)
+            .args(&command.args)
+            .env(PLUGIN_SOCKET_ENV, &socket)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| start_error(err.to_string()))?;
+
+        let client = match connect(&socket, &mut child).await {
+            Ok(client) => client,
+            Err(error) => {
+                let _ = child.kill().await;
+                let _ = std::fs::remove_file(&socket);
+                return Err(start_error(error));
+            }
+        };
+        let mut process = Self {
+            child,
+            client,
+            socket,
+        };
+        let routes = process
+            .client
+            .setup(setup.clone())
+            .await?
+            .into_inner()
+            .routes;
+
+        Ok((process, routes_from_proto(routes)))
+    }
+}
+
+/// Connect to the socket of the plugin once it is listening.
+async fn connect(socket: &Path, child: &mut Child) -> Result<GrpcClient<Channel>, String> {
+    // The URI is ignored, every connection is made to the socket
+    let endpoint = Endpoint::from_static("http://[::]:50051");
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(format!("the plugin exited with {status}"));
+        }
+        let socket = socket.to_path_buf();
+        let connector = tower::service_fn(move |_: Uri| UnixStream::connect(socket.clone()));
+        match endpoint.connect_with_connector(connector).await {
+            Ok(channel) => return Ok(GrpcClient::new(channel)),
+            Err(_) if Instant::now() < deadline => sleep(Duration::from_millis(50)).await,
+            Err(err) => {
+                return Err(format!(
+                    "the plugin did not listen on its socket within {START_TIMEOUT:?}. Error: {err}"
+                ))
+            }
+        }
+    }
+}
+
+/// Check that the binary matches the hex encoded SHA-256 checksum.
+async fn verify_checksum(path: &Path, expected: &str) -> Result<(), PluginError> {
+    let binary = tokio::fs::read(path)
+        .await
+        .map_err(|error| PluginError::ReadBinary {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    let actual = hex::encode(Sha256::digest(&binary));
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(PluginError::ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            actual,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn checksum() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"#!/bin/sh\n").unwrap();
+        let sha256 = hex::encode(Sha256::digest(b"#!/bin/sh\n"));
+
+        assert!(verify_checksum(file.path(), &sha256).await.is_ok());
+        assert!(verify_checksum(file.path(), &sha256.to_uppercase())
+            .await
+            .is_ok());
+
+        file.write_all(b"echo tampered\n").unwrap();
+        assert!(matches!(
+            verify_checksum(file.path(), &sha256).await,
+            Err(PluginError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_binary() {
+        assert!(matches!(
+            verify_checksum(Path::new("/does/not/exist"), "").await,
+            Err(PluginError::ReadBinary { .. })
+        ));
+    }
+}
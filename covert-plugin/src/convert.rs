@@ -0,0 +1,375 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use covert_types::{
+    backend::RouteHelp,
+    error::{ApiError, ErrorCode},
+    request::{Operation, Request, TOKEN_HEADER},
+    response::{LeaseRenewRevokeEndpoint, LeaseResponse, Response},
+};
+use futures::TryStreamExt;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{proto, MountInfo, PluginError, RequestContext};
+
+impl From<RequestContext> for proto::RequestContext {
+    fn from(context: RequestContext) -> Self {
+        Self {
+            entity_name: context.entity_name,
+            policies: context.policies,
+        }
+    }
+}
+
+impl From<proto::RequestContext> for RequestContext {
+    fn from(context: proto::RequestContext) -> Self {
+        Self {
+            entity_name: context.entity_name,
+            policies: context.policies,
+        }
+    }
+}
+
+/// Returns true for the headers that can carry the token of the request.
+fn is_token_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case(TOKEN_HEADER) || name.eq_ignore_ascii_case("authorization")
+}
+
+/// The request sent to the plugin. The token is left out, the plugin only
+/// learns who made the request from the context.
+pub(crate) fn request_to_proto(req: Request, context: RequestContext) -> proto::Request {
+    proto::Request {
+        id: req.id.to_string(),
+        operation: req.operation.to_string(),
+        path: req.path,
+        data: req.data.to_vec(),
+        query_string: req.query_string,
+        headers: req
+            .headers
+            .into_iter()
+            .filter(|(name, _)| !is_token_header(name))
+            .collect(),
+        namespace: req.namespace,
+        context: Some(context.into()),
+    }
+}
+
+/// The request handled by the backend of the plugin.
+pub(crate) fn request_from_proto(
+    req: proto::Request,
+    mount: MountInfo,
+) -> Result<Request, ApiError> {
+    let operation = Operation::from_str(&req.operation)?;
+    let mut extensions = http::Extensions::new();
+    extensions.insert(RequestContext::from(req.context.unwrap_or_default()));
+    extensions.insert(mount);
+
+    Ok(Request {
+        id: Uuid::parse_str(&req.id).unwrap_or_else(|_| Uuid::new_v4()),
+        operation,
+        path: req.path,
+        namespace: req.namespace,
+        data: req.data.into(),
+        query_string: req.query_string,
+        extensions,
+        params: vec![],
+        token: None,
+        headers: req.headers,
+    })
+}
+
+fn json_from_proto(data: &[u8]) -> Result<Value, PluginError> {
+    if data.is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_slice(data).map_err(|err| PluginError::InvalidResponse(err.to_string()))
+}
+
+fn json_to_proto(data: &Value) -> Vec<u8> {
+    serde_json::to_vec(data).unwrap_or_default()
+}
+
+fn endpoint_from_proto(
+    endpoint: Option<proto::Endpoint>,
+) -> Result<LeaseRenewRevokeEndpoint, PluginError> {
+    let endpoint = endpoint.ok_or_else(|| {
+        PluginError::InvalidResponse("lease is missing the renew or revoke endpoint".into())
+    })?;
+    Ok(LeaseRenewRevokeEndpoint {
+        path: endpoint.path,
+        data: json_from_proto(&endpoint.data)?,
+    })
+}
+
+fn endpoint_to_proto(endpoint: &LeaseRenewRevokeEndpoint) -> proto::Endpoint {
+    proto::Endpoint {
+        path: endpoint.path.clone(),
+        data: json_to_proto(&endpoint.data),
+    }
+}
+
+fn error_from_proto(error: proto::Error) -> ApiError {
+    let code = serde_json::from_value(Value::String(error.code)).unwrap_or(ErrorCode::Unknown);
+    error.details.into_iter().fold(
+        ApiError::new(code, anyhow::Error::msg(error.message)),
+        |api_error, detail| api_error.with_detail(detail.field, detail.message),
+    )
+}
+
+fn error_to_proto(error: &ApiError) -> proto::Error {
+    let code = serde_json::to_value(error.code)
+        .ok()
+        .and_then(|code| code.as_str().map(ToString::to_string))
+        .unwrap_or_default();
+    proto::Error {
+        code,
+        message: error.error.to_string(),
+        details: error
+            .details
+            .iter()
+            .map(|detail| proto::FieldError {
+                field: detail.field.clone(),
+                message: detail.message.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// The response of the plugin, errors returned by its backend are returned as
+/// they are.
+pub(crate) fn response_from_proto(resp: proto::Response) -> Result<Response, ApiError> {
+    let result = resp
+        .result
+        .ok_or_else(|| PluginError::InvalidResponse("response is empty".into()))?;
+    let resp = match result {
+        proto::response::Result::Raw(raw) => Response::Raw(json_from_proto(&raw.data)?),
+        proto::response::Result::Lease(lease) => Response::Lease(LeaseResponse {
+            data: json_from_proto(&lease.data)?,
+            ttl: lease.ttl_secs.map(Duration::from_secs),
+            max_ttl: lease.max_ttl_secs.map(Duration::from_secs),
+            revoke: endpoint_from_proto(lease.revoke)?,
+            renew: endpoint_from_proto(lease.renew)?,
+            tags: lease.tags.into_iter().collect(),
+        }),
+        proto::response::Result::Bytes(bytes) => Response::bytes(bytes.content_type, bytes.body),
+        proto::response::Result::Error(error) => return Err(error_from_proto(error)),
+    };
+    Ok(resp)
+}
+
+/// The response sent to the server. Streamed bodies are buffered as the
+/// protocol has no streaming responses.
+pub(crate) async fn response_to_proto(res: Result<Response, ApiError>) -> proto::Response {
+    let result = match res {
+        Ok(Response::Raw(data)) => proto::response::Result::Raw(proto::RawResponse {
+            data: json_to_proto(&data),
+        }),
+        Ok(Response::Lease(lease)) => proto::response::Result::Lease(proto::LeaseResponse {
+            data: json_to_proto(&lease.data),
+            ttl_secs: lease.ttl.map(|ttl| ttl.as_secs()),
+            max_ttl_secs: lease.max_ttl.map(|ttl| ttl.as_secs()),
+            revoke: Some(endpoint_to_proto(&lease.revoke)),
+            renew: Some(endpoint_to_proto(&lease.renew)),
+            tags: lease.tags.into_iter().collect::<HashMap<_, _>>(),
+        }),
+        Ok(Response::Bytes(bytes)) => proto::response::Result::Bytes(proto::BytesResponse {
+            content_type: bytes.content_type,
+            body: bytes.body.to_vec(),
+        }),
+        Ok(Response::Stream(stream)) => {
+            match stream
+                .body
+                .try_fold(Vec::new(), |mut body, chunk| async move {
+                    body.extend_from_slice(&chunk);
+                    Ok(body)
+                })
+                .await
+            {
+                Ok(body) => proto::response::Result::Bytes(proto::BytesResponse {
+                    content_type: stream.content_type,
+                    body,
+                }),
+                Err(err) => proto::response::Result::Error(error_to_proto(&ApiError::new(
+                    ErrorCode::Internal,
+                    err.into(),
+                ))),
+            }
+        }
+        Ok(Response::Auth(_)) => proto::response::Result::Error(error_to_proto(&ApiError::new(
+            ErrorCode::Internal,
+            anyhow::Error::msg("Plugins cannot authenticate users"),
+        ))),
+        Err(error) => proto::response::Result::Error(error_to_proto(&error)),
+    };
+    proto::Response {
+        result: Some(result),
+    }
+}
+
+/// Routes of the plugin, operations unknown to the server are left out.
+pub(crate) fn routes_from_proto(routes: Vec<proto::Route>) -> Vec<RouteHelp> {
+    routes
+        .into_iter()
+        .map(|route| RouteHelp {
+            path: route.path,
+            operations: route
+                .operations
+                .iter()
+                .filter_map(|operation| Operation::from_str(operation).ok())
+                .collect(),
+            help: route.help,
+        })
+        .collect()
+}
+
+pub(crate) fn route_to_proto(route: &RouteHelp) -> proto::Route {
+    proto::Route {
+        path: route.path.clone(),
+        operations: route.operations.iter().map(ToString::to_string).collect(),
+        help: route.help.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bytes::Bytes;
+    use covert_types::{error::StatusCode, mount::MountConfig};
+    use serde_json::json;
+
+    use super::*;
+
+    fn mount() -> MountInfo {
+        MountInfo {
+            mount_id: Uuid::new_v4().to_string(),
+            namespace_id: Uuid::new_v4().to_string(),
+            config: MountConfig::default(),
+        }
+    }
+
+    #[test]
+    fn request_round_trip_without_token() {
+        let mut headers = HashMap::new();
+        headers.insert("x-covert-token".to_string(), "hvs.secret".to_string());
+        headers.insert("authorization".to_string(), "Bearer hvs.secret".to_string());
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        let id = Uuid::new_v4();
+        let req = Request {
+            id,
+            operation: Operation::Update,
+            path: "creds/foo".into(),
+            namespace: vec!["root".into(), "team-a".into()],
+            data: Bytes::from_static(b"{\"ttl\":60}"),
+            query_string: "version=2".into(),
+            extensions: http::Extensions::new(),
+            params: vec![],
+            token: Some("hvs.secret".into()),
+            headers,
+        };
+        let context = RequestContext {
+            entity_name: Some("alice".into()),
+            policies: vec!["default".into(), "dev".into()],
+        };
+
+        let sent = request_to_proto(req, context.clone());
+        assert_eq!(sent.headers.len(), 1);
+        assert!(!format!("{sent:?}").contains("hvs.secret"));
+
+        let mount = mount();
+        let received = request_from_proto(sent, mount.clone()).unwrap();
+        assert_eq!(received.id, id);
+        assert_eq!(received.operation, Operation::Update);
+        assert_eq!(received.path, "creds/foo");
+        assert_eq!(received.namespace, vec!["root", "team-a"]);
+        assert_eq!(received.data, Bytes::from_static(b"{\"ttl\":60}"));
+        assert_eq!(received.query_string, "version=2");
+        assert_eq!(received.token, None);
+        assert_eq!(received.extensions.get::<RequestContext>(), Some(&context));
+        assert_eq!(received.extensions.get::<MountInfo>(), Some(&mount));
+    }
+
+    #[test]
+    fn invalid_operation() {
+        let req = proto::Request {
+            operation: "sing".into(),
+            ..Default::default()
+        };
+        assert!(request_from_proto(req, mount()).is_err());
+    }
+
+    #[tokio::test]
+    async fn lease_round_trip() {
+        let mut tags = BTreeMap::new();
+        tags.insert("role".to_string(), "reader".to_string());
+        let resp = Response::Lease(LeaseResponse {
+            revoke: LeaseRenewRevokeEndpoint {
+                path: "creds".into(),
+                data: json!({ "username": "foo" }),
+            },
+            renew: LeaseRenewRevokeEndpoint {
+                path: "creds/renew".into(),
+                data: json!({ "username": "foo" }),
+            },
+            data: json!({ "username": "foo", "password": "bar" }),
+            ttl: Some(Duration::from_secs(60)),
+            max_ttl: None,
+            tags: tags.clone(),
+        });
+
+        let resp = response_from_proto(response_to_proto(Ok(resp)).await).unwrap();
+        let Response::Lease(lease) = resp else {
+            panic!("expected a lease response");
+        };
+        assert_eq!(lease.data, json!({ "username": "foo", "password": "bar" }));
+        assert_eq!(lease.ttl, Some(Duration::from_secs(60)));
+        assert_eq!(lease.max_ttl, None);
+        assert_eq!(lease.revoke.path, "creds");
+        assert_eq!(lease.revoke.data, json!({ "username": "foo" }));
+        assert_eq!(lease.renew.path, "creds/renew");
+        assert_eq!(lease.tags, tags);
+    }
+
+    #[tokio::test]
+    async fn raw_round_trip() {
+        let resp = response_from_proto(response_to_proto(Ok(Response::ok())).await).unwrap();
+        assert!(matches!(resp, Response::Raw(Value::Null)));
+
+        let resp = Response::raw(json!({ "foo": "bar" })).unwrap();
+        let resp = response_from_proto(response_to_proto(Ok(resp)).await).unwrap();
+        assert!(matches!(resp, Response::Raw(data) if data == json!({ "foo": "bar" })));
+    }
+
+    #[tokio::test]
+    async fn error_round_trip() {
+        let error = ApiError::new(ErrorCode::NotFound, anyhow::Error::msg("Role not found"))
+            .with_detail("name", "no role with that name");
+
+        let error = response_from_proto(response_to_proto(Err(error)).await).unwrap_err();
+        assert_eq!(error.code, ErrorCode::NotFound);
+        assert_eq!(error.status_code, StatusCode::NOT_FOUND);
+        assert_eq!(error.error.to_string(), "Role not found");
+        assert_eq!(error.details[0].field, "name");
+    }
+
+    #[tokio::test]
+    async fn auth_responses_are_refused() {
+        let resp = Response::Auth(covert_types::response::AuthResponse {
+            alias: "foo".into(),
+            ttl: None,
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            create_entity: false,
+            renewable: true,
+        });
+        let error = response_from_proto(response_to_proto(Ok(resp)).await).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Internal);
+    }
+
+    #[test]
+    fn empty_response_is_invalid() {
+        let error = response_from_proto(proto::Response { result: None }).unwrap_err();
+        assert_eq!(error.code, ErrorCode::UpstreamError);
+    }
+}
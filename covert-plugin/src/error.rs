@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use covert_types::error::{ApiError, ErrorCode};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("Failed to read the binary `{path}` of the plugin")]
+    ReadBinary {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+    #[error("The checksum of the plugin binary `{path}` is `{actual}`, expected `{expected}`")]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    #[error("Failed to start the plugin `{name}`. Error: {error}")]
+    Start { name: String, error: String },
+    #[error("The plugin `{name}` is not running")]
+    Unavailable { name: String },
+    #[error("The call to the plugin failed. Error: {0}")]
+    Call(#[from] tonic::Status),
+    #[error("Invalid response from the plugin. Error: {0}")]
+    InvalidResponse(String),
+}
+
+impl From<PluginError> for ApiError {
+    fn from(err: PluginError) -> Self {
+        ApiError::new(ErrorCode::UpstreamError, err.into())
+    }
+}
@@ -0,0 +1,51 @@
+#![forbid(unsafe_code)]
+#![forbid(clippy::unwrap_used)]
+#![deny(clippy::pedantic)]
+#![deny(clippy::get_unwrap)]
+#![allow(clippy::module_name_repetitions)]
+
+//! Backends served by external plugin processes.
+//!
+//! The server starts one plugin process per mount and talks to it over gRPC
+//! on a unix socket, see `proto/plugin.proto`. Plugins are written with
+//! `covert-framework` like any other backend and served with [`serve`].
+
+mod client;
+mod convert;
+mod error;
+mod server;
+
+pub mod proto {
+    #![allow(clippy::pedantic)]
+    tonic::include_proto!("covert.plugin.v1");
+}
+
+pub use client::{PluginClient, PluginCommand};
+pub use error::PluginError;
+pub use server::serve;
+
+use covert_types::mount::MountConfig;
+
+/// Environment variable with the path of the unix socket the plugin process
+/// has to listen on.
+pub const PLUGIN_SOCKET_ENV: &str = "COVERT_PLUGIN_SOCKET";
+
+/// Who made the request to the plugin. Attached to the request extensions of
+/// every request handled by a plugin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    /// Entity of the token of the request, unset for requests made by the
+    /// server itself, e.g. to revoke a lease.
+    pub entity_name: Option<String>,
+    /// Names of the policies of the token.
+    pub policies: Vec<String>,
+}
+
+/// The mount served by the plugin. Attached to the request extensions of every
+/// request handled by a plugin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub mount_id: String,
+    pub namespace_id: String,
+    pub config: MountConfig,
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use covert_framework::Backend;
+use covert_types::{
+    mount::MountConfig,
+    request::{Operation, Request},
+};
+use tokio::{
+    net::UnixListener,
+    sync::{Notify, RwLock},
+};
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::{transport::Server, Status};
+
+use crate::{
+    convert::{request_from_proto, response_to_proto, route_to_proto},
+    proto::{
+        self,
+        plugin_server::{Plugin, PluginServer},
+    },
+    MountInfo, PLUGIN_SOCKET_ENV,
+};
+
+/// Serve the backend to the Covert server that started the process, on the
+/// socket given in [`PLUGIN_SOCKET_ENV`]. Returns once the mount is torn down.
+///
+/// # Errors
+///
+/// Returns an error if the process was not started by the Covert server or
+/// the socket cannot be served.
+pub async fn serve(backend: Backend) -> anyhow::Result<()> {
+    let socket = std::env::var_os(PLUGIN_SOCKET_ENV).ok_or_else(|| {
+        anyhow::Error::msg(format!(
+            "`{PLUGIN_SOCKET_ENV}` is not set, plugins are started by the Covert server"
+        ))
+    })?;
+    let listener = UnixListener::bind(&socket)?;
+
+    let torn_down = Arc::new(Notify::new());
+    let service = PluginService {
+        backend: Arc::new(backend),
+        mount: Arc::default(),
+        torn_down: Arc::clone(&torn_down),
+    };
+    Server::builder()
+        .add_service(PluginServer::new(service))
+        .serve_with_incoming_shutdown(UnixListenerStream::new(listener), async move {
+            torn_down.notified().await;
+        })
+        .await?;
+
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+struct PluginService {
+    backend: Arc<Backend>,
+    mount: Arc<RwLock<Option<MountInfo>>>,
+    torn_down: Arc<Notify>,
+}
+
+impl PluginService {
+    async fn request(&self, req: proto::Request) -> Result<Request, Status> {
+        let mount = self
+            .mount
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| Status::failed_precondition("The mount is not set up"))?;
+        request_from_proto(req, mount).map_err(|err| Status::invalid_argument(err.to_string()))
+    }
+}
+
+#[tonic::async_trait]
+impl Plugin for PluginService {
+    async fn setup(
+        &self,
+        req: tonic::Request<proto::SetupRequest>,
+    ) -> Result<tonic::Response<proto::SetupResponse>, Status> {
+        let req = req.into_inner();
+        let config = serde_json::from_slice::<MountConfig>(&req.config)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        *self.mount.write().await = Some(MountInfo {
+            mount_id: req.mount_id,
+            namespace_id: req.namespace_id,
+            config,
+        });
+
+        Ok(tonic::Response::new(proto::SetupResponse {
+            routes: self.backend.paths.iter().map(route_to_proto).collect(),
+        }))
+    }
+
+    async fn teardown(
+        &self,
+        _req: tonic::Request<proto::TeardownRequest>,
+    ) -> Result<tonic::Response<proto::TeardownResponse>, Status> {
+        // The server stops once the response is sent
+        self.torn_down.notify_one();
+        Ok(tonic::Response::new(proto::TeardownResponse {}))
+    }
+
+    async fn handle_request(
+        &self,
+        req: tonic::Request<proto::Request>,
+    ) -> Result<tonic::Response<proto::Response>, Status> {
+        let req = self.request(req.into_inner()).await?;
+        let resp = response_to_proto(self.backend.handle_request(req).await).await;
+        Ok(tonic::Response::new(resp))
+    }
+
+    async fn revoke(
+        &self,
+        req: tonic::Request<proto::Request>,
+    ) -> Result<tonic::Response<proto::Response>, Status> {
+        let mut req = self.request(req.into_inner()).await?;
+        req.operation = Operation::Revoke;
+        let resp = response_to_proto(self.backend.handle_request(req).await).await;
+        Ok(tonic::Response::new(resp))
+    }
+
+    async fn health(
+        &self,
+        _req: tonic::Request<proto::HealthRequest>,
+    ) -> Result<tonic::Response<proto::HealthResponse>, Status> {
+        Ok(tonic::Response::new(proto::HealthResponse {}))
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::system::{
+    AuditDevice, AuditDeviceConfig, AuditDeviceError, AuditFilterRule, AuditFormat,
+    AuditHashParams, AuditHashResponse, AuditTailEvent, AuditTailParams,
+    DisableAuditDeviceResponse, EnableAuditDeviceParams, FileAuditConfig, ListAuditDevicesResponse,
+    ReadAuditDeviceResponse, SocketAuditConfig, SocketType,
+};
+
+use crate::{base::BaseClient, error::Error};
+
+/// Stream of the entries written by an audit device, returned by
+/// [`Client::tail`].
+pub struct AuditTail {
+    resp: reqwest::Response,
+    buf: Vec<u8>,
+}
+
+impl AuditTail {
+    /// The next event of the stream, `None` once the server ended it, e.g.
+    /// because the device was disabled. Interrupted streams can be resumed
+    /// with the epoch and the sequence number of the last entry received.
+    pub async fn next(&mut self) -> Result<Option<AuditTailEvent>, Error> {
+        loop {
+            if let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=end).collect();
+                return serde_json::from_slice(&line)
+                    .map(Some)
+                    .map_err(|e| Error::Transport(format!("{e:#?}")));
+            }
+            match self
+                .resp
+                .chunk()
+                .await
+                .map_err(|e| Error::Transport(format!("{e:#?}")))?
+            {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    pub async fn enable(
+        &self,
+        path: &str,
+        params: &EnableAuditDeviceParams,
+    ) -> Result<AuditDevice, Error> {
+        self.client.post(format!("/sys/audit/{path}"), params).await
+    }
+
+    pub async fn list(&self) -> Result<ListAuditDevicesResponse, Error> {
+        self.client.get("/sys/audit".into()).await
+    }
+
+    pub async fn read(&self, path: &str) -> Result<ReadAuditDeviceResponse, Error> {
+        self.client.get(format!("/sys/audit/{path}")).await
+    }
+
+    pub async fn disable(&self, path: &str) -> Result<DisableAuditDeviceResponse, Error> {
+        self.client.delete(format!("/sys/audit/{path}")).await
+    }
+
+    /// Follow the entries written by the device, see [`AuditTailParams`] to
+    /// start with recent entries or to resume an interrupted stream.
+    pub async fn tail(&self, path: &str, params: &AuditTailParams) -> Result<AuditTail, Error> {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        if let Some(epoch) = params.epoch {
+            query.append_pair("epoch", &epoch.to_string());
+        }
+        if let Some(since) = params.since {
+            query.append_pair("since", &since.to_string());
+        }
+        if let Some(last) = params.last {
+            query.append_pair("last", &last.to_string());
+        }
+        let resp = self
+            .client
+            .get_stream(format!("/sys/audit-tail/{path}?{}", query.finish()))
+            .await?;
+        Ok(AuditTail { resp, buf: vec![] })
+    }
+
+    pub async fn hash(
+        &self,
+        path: &str,
+        params: &AuditHashParams,
+    ) -> Result<AuditHashResponse, Error> {
+        self.client
+            .post(format!("/sys/audit-hash/{path}"), params)
+            .await
+    }
+}
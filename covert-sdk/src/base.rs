@@ -1,28 +1,64 @@
-use reqwest::RequestBuilder;
+use std::{sync::Arc, time::Duration};
+
+use covert_types::{
+    error::{ErrorCode, FieldError},
+    methods::{AuthResponse, MfaRequirementResponse},
+    request::{TokenHeader, IDEMPOTENCY_KEY_HEADER, TOKEN_HEADER, WRAP_TTL_HEADER},
+};
+use reqwest::{Method, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
+use crate::{
+    error::{Error, ServerError},
+    retry::{Retry, RetryPolicy},
+    transport::Transport,
+};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response<T> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    /// Version of the secret returned with a CAS conflict.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_version: Option<u64>,
 }
 
 pub(crate) struct BaseClient {
     api_url: String,
+    http: reqwest::Client,
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
     token: RwLock<Option<String>>,
+    token_header: TokenHeader,
     namespace: RwLock<Option<String>>,
 }
 
 impl BaseClient {
-    pub fn new(api_url: impl ToString) -> Self {
+    pub fn new(
+        api_url: impl ToString,
+        http: reqwest::Client,
+        transport: Arc<dyn Transport>,
+        retry: RetryPolicy,
+        token_header: TokenHeader,
+    ) -> Self {
         let namespace = std::env::var("COVERT_NAMESPACE").ok();
 
         Self {
             api_url: api_url.to_string(),
+            http,
+            transport,
+            retry,
             token: RwLock::new(None),
+            token_header,
             namespace: RwLock::new(namespace),
         }
     }
@@ -37,13 +73,13 @@ impl BaseClient {
         *ns_l = namespace;
     }
 
-    pub async fn send<T: for<'de> serde::de::Deserialize<'de>>(
-        &self,
-        mut rb: RequestBuilder,
-    ) -> Result<T, String> {
+    async fn with_headers(&self, mut rb: RequestBuilder) -> RequestBuilder {
         let token_l = self.token.read().await;
         if let Some(token) = token_l.as_ref() {
-            rb = rb.header("X-Covert-Token", token);
+            rb = match self.token_header {
+                TokenHeader::Covert => rb.header(TOKEN_HEADER, token),
+                TokenHeader::Authorization => rb.bearer_auth(token),
+            };
         }
         drop(token_l);
 
@@ -53,58 +89,325 @@ impl BaseClient {
         }
         drop(ns_l);
 
-        rb.send()
-            .await
-            .map_err(|e| format!("{e:#?}"))?
-            .json::<Response<T>>()
+        rb
+    }
+
+    /// Send the request, retrying it according to the [`RetryPolicy`] if
+    /// `retry` allows it. Returns the response with the number of attempts.
+    async fn execute(
+        &self,
+        rb: RequestBuilder,
+        retry: Retry,
+    ) -> Result<(reqwest::Response, u32), Error> {
+        let mut req = self
+            .with_headers(rb)
             .await
-            .map_err(|e| format!("{e:#?}"))
-            .and_then(|res| {
-                if let Some(data) = res.data {
-                    Ok(data)
-                } else if let Some(err) = res.error {
-                    Err(err)
-                } else {
-                    Err("Unexpected emtpy response from server".into())
+            .build()
+            .map_err(|e| Error::Transport(format!("{e:#?}")))?;
+        let retry = self.retry.applies_to(retry);
+
+        let mut attempt = 1;
+        loop {
+            // Requests with a streamed body cannot be cloned and are sent once
+            let next = if retry { req.try_clone() } else { None };
+            let outcome = self.transport.send(req).await;
+            match next.zip(self.retry.delay(attempt, &outcome)) {
+                Some((next, delay)) => {
+                    tokio::time::sleep(delay).await;
+                    req = next;
+                    attempt += 1;
                 }
-            })
+                None => {
+                    return outcome
+                        .map(|resp| (resp, attempt))
+                        .map_err(|e| Error::after_attempts(attempt, Error::Transport(e.message)))
+                }
+            }
+        }
+    }
+
+    async fn send<T: for<'de> serde::de::Deserialize<'de>>(
+        &self,
+        rb: RequestBuilder,
+        retry: Retry,
+    ) -> Result<T, Error> {
+        let (resp, attempts) = self.execute(rb, retry).await?;
+        parse_response(resp)
+            .await
+            .map_err(|error| Error::after_attempts(attempts, error))
+    }
+
+    /// Read a binary response. Errors are still returned as JSON by the
+    /// server.
+    pub async fn get_bytes(&self, path: String) -> Result<Vec<u8>, Error> {
+        let (resp, attempts) = self
+            .execute(
+                self.http.get(format!("{}{}", self.api_url, path)),
+                Retry::Idempotent,
+            )
+            .await?;
+        if !resp.status().is_success() {
+            return parse_response::<()>(resp)
+                .await
+                .map(|()| vec![])
+                .map_err(|error| Error::after_attempts(attempts, error));
+        }
+        resp.bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| Error::Transport(format!("{e:#?}")))
+    }
+
+    /// Send a GET request whose body is read as it is streamed by the
+    /// server. Errors are still returned as JSON by the server.
+    pub async fn get_stream(&self, path: String) -> Result<reqwest::Response, Error> {
+        let (resp, attempts) = self
+            .execute(
+                self.http.get(format!("{}{}", self.api_url, path)),
+                Retry::Idempotent,
+            )
+            .await?;
+        if resp.status().is_success() {
+            return Ok(resp);
+        }
+        let error = match parse_response::<()>(resp).await {
+            Err(error) => error,
+            Ok(()) => Error::Transport("Unexpected response from server".into()),
+        };
+        Err(Error::after_attempts(attempts, error))
     }
 
     pub async fn get<T: for<'de> serde::de::Deserialize<'de>>(
         &self,
         path: String,
-    ) -> Result<T, String> {
-        let client = reqwest::Client::new();
-        let request_builder = client.get(format!("{}{}", self.api_url, path));
-        self.send(request_builder).await
+    ) -> Result<T, Error> {
+        let request_builder = self.http.get(format!("{}{}", self.api_url, path));
+        self.send(request_builder, Retry::Idempotent).await
     }
 
     pub async fn delete<T: for<'de> serde::de::Deserialize<'de>>(
         &self,
         path: String,
-    ) -> Result<T, String> {
-        let client = reqwest::Client::new();
-        let request_builder = client.delete(format!("{}{}", self.api_url, path));
-        self.send(request_builder).await
+    ) -> Result<T, Error> {
+        let request_builder = self.http.delete(format!("{}{}", self.api_url, path));
+        self.send(request_builder, Retry::Never).await
     }
 
     pub async fn put<T: Serialize, U: for<'de> serde::de::Deserialize<'de>>(
         &self,
         path: String,
         body: &T,
-    ) -> Result<U, String> {
-        let client = reqwest::Client::new();
-        let request_builder = client.put(format!("{}{}", self.api_url, path)).json(body);
-        self.send(request_builder).await
+    ) -> Result<U, Error> {
+        let request_builder = self
+            .http
+            .put(format!("{}{}", self.api_url, path))
+            .json(body);
+        self.send(request_builder, Retry::Never).await
+    }
+
+    pub async fn patch<T: Serialize, U: for<'de> serde::de::Deserialize<'de>>(
+        &self,
+        path: String,
+        body: &T,
+    ) -> Result<U, Error> {
+        let request_builder = self
+            .http
+            .patch(format!("{}{}", self.api_url, path))
+            .json(body);
+        self.send(request_builder, Retry::Never).await
+    }
+
+    /// Send a `PUT` request renewing a lease or token, which is retried if
+    /// [`RetryPolicy::retry_renewals`] is set.
+    pub async fn renew<T: Serialize, U: for<'de> serde::de::Deserialize<'de>>(
+        &self,
+        path: String,
+        body: &T,
+    ) -> Result<U, Error> {
+        let request_builder = self
+            .http
+            .put(format!("{}{}", self.api_url, path))
+            .json(body);
+        self.send(request_builder, Retry::Renewal).await
+    }
+
+    /// Send a login request. A login that has to be completed with a second
+    /// factor is returned as [`Error::MfaRequired`].
+    pub async fn login<T: Serialize>(&self, path: String, body: &T) -> Result<AuthResponse, Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum LoginResponse {
+            Auth(AuthResponse),
+            Mfa(MfaRequirementResponse),
+        }
+
+        match self.put(path, body).await? {
+            LoginResponse::Auth(auth) => Ok(auth),
+            LoginResponse::Mfa(mfa) => Err(Error::MfaRequired(mfa.mfa_requirement)),
+        }
     }
 
     pub async fn post<T: Serialize, U: for<'de> serde::de::Deserialize<'de>>(
         &self,
         path: String,
         body: &T,
-    ) -> Result<U, String> {
-        let client = reqwest::Client::new();
-        let request_builder = client.post(format!("{}{}", self.api_url, path)).json(body);
-        self.send(request_builder).await
+    ) -> Result<U, Error> {
+        let request_builder = self
+            .http
+            .post(format!("{}{}", self.api_url, path))
+            .json(body);
+        self.send(request_builder, Retry::Never).await
+    }
+
+    /// Send a `POST` request that can be safely retried. Retries with the same
+    /// `key` return the result of the first request instead of creating a
+    /// duplicate.
+    pub async fn post_idempotent<T: Serialize, U: for<'de> serde::de::Deserialize<'de>>(
+        &self,
+        path: String,
+        body: &T,
+        key: &str,
+    ) -> Result<U, Error> {
+        let request_builder = self
+            .http
+            .post(format!("{}{}", self.api_url, path))
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .json(body);
+        self.send(request_builder, Retry::Idempotent).await
+    }
+
+    /// Send a request whose response is wrapped in a wrapping token that is
+    /// valid for `ttl`.
+    pub async fn wrapped<T: Serialize, U: for<'de> serde::de::Deserialize<'de>>(
+        &self,
+        method: Method,
+        path: String,
+        body: Option<&T>,
+        ttl: Duration,
+    ) -> Result<U, Error> {
+        let mut request_builder = self
+            .http
+            .request(method, format!("{}{}", self.api_url, path))
+            .header(WRAP_TTL_HEADER, ttl.as_secs());
+        if let Some(body) = body {
+            request_builder = request_builder.json(body);
+        }
+        self.send(request_builder, Retry::Never).await
+    }
+}
+
+async fn parse_response<T: for<'de> serde::de::Deserialize<'de>>(
+    resp: reqwest::Response,
+) -> Result<T, Error> {
+    let status = resp.status();
+    let path = resp.url().path().to_string();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| Error::Transport(format!("{e:#?}")))?;
+    let res = match serde_json::from_str::<Response<T>>(&body) {
+        Ok(res) => res,
+        // Errors returned by a proxy in front of the server are not JSON
+        Err(_) if !status.is_success() => {
+            let error = ServerError {
+                status,
+                // covert-types is on another version of `http` than reqwest
+                code: covert_types::error::StatusCode::from_u16(status.as_u16())
+                    .map_or(ErrorCode::Unknown, ErrorCode::from),
+                message: status.to_string(),
+                details: vec![],
+                retry_after: None,
+                body,
+            };
+            return Err(Error::from_server(&path, error, None));
+        }
+        Err(e) => return Err(Error::Transport(format!("{e:#?}"))),
+    };
+
+    if let Some(data) = res.data {
+        Ok(data)
+    } else if let Some(message) = res.error {
+        let error = ServerError {
+            status,
+            code: res.code.unwrap_or(ErrorCode::Unknown),
+            message,
+            details: res.details,
+            retry_after: res.retry_after_secs.map(Duration::from_secs),
+            body,
+        };
+        Err(Error::from_server(&path, error, res.current_version))
+    } else {
+        Err(Error::Transport(
+            "Unexpected emtpy response from server".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    async fn parse(status: u16, body: &'static str) -> Result<String, Error> {
+        let resp = http::Response::builder().status(status).body(body).unwrap();
+        parse_response(reqwest::Response::from(resp)).await
+    }
+
+    #[tokio::test]
+    async fn typed_errors() {
+        assert_eq!(parse(200, r#"{"data":"foo"}"#).await.unwrap(), "foo");
+
+        let err = parse(403, r#"{"error":"Sealed","code":"sealed_state"}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Sealed(_)), "{err:?}");
+        assert_eq!(err.status(), Some(StatusCode::FORBIDDEN));
+
+        let err = parse(403, r#"{"error":"Denied","code":"permission_denied"}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied(_)), "{err:?}");
+        assert!(!err.is_retryable());
+
+        let err = parse(404, r#"{"error":"Not found","code":"not_found"}"#)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NotFound { .. }), "{err:?}");
+        assert_eq!(err.message(), "Not found");
+
+        let body = r#"{"error":"Conflict","code":"cas_conflict","current_version":3}"#;
+        let err = parse(409, body).await.unwrap_err();
+        let Error::CasConflict {
+            current_version,
+            error,
+        } = err
+        else {
+            panic!("expected a CAS conflict");
+        };
+        assert_eq!(current_version, Some(3));
+        assert_eq!(error.body, body);
+
+        let err = parse(
+            429,
+            r#"{"error":"Slow down","code":"rate_limited","retry_after_secs":2}"#,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.server_error().unwrap().retry_after,
+            Some(Duration::from_secs(2))
+        );
+        assert!(err.is_retryable());
+
+        // Codes not known to the SDK and errors of proxies are still returned
+        let err = parse(418, r#"{"error":"Teapot","code":"teapot"}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Unknown));
+        let err = parse(502, "<html>Bad Gateway</html>").await.unwrap_err();
+        assert!(matches!(err, Error::Api(_)), "{err:?}");
+        assert_eq!(err.code(), Some(ErrorCode::UpstreamError));
+        assert_eq!(err.server_error().unwrap().body, "<html>Bad Gateway</html>");
     }
 }
@@ -4,11 +4,13 @@ pub use covert_types::entity::EntityAlias;
 use covert_types::methods::system::ListEntitiesResponse;
 pub use covert_types::methods::system::{
     AttachEntityAliasParams, AttachEntityAliasResponse, AttachEntityPolicyParams,
-    AttachEntityPolicyResponse, CreateEntityParams, CreateEntityResponse, RemoveEntityAliasParams,
+    AttachEntityPolicyResponse, ChildTokens, CreateEntityParams, CreateEntityResponse,
+    DeleteEntityParams, DeleteEntityResponse, ImportEntitiesParams, ImportEntitiesResponse,
+    ImportEntity, ImportEntityResult, ImportEntityStatus, RemoveEntityAliasParams,
     RemoveEntityAliasResponse, RemoveEntityPolicyParams, RemoveEntityPolicyResponse,
 };
 
-use crate::base::BaseClient;
+use crate::{base::BaseClient, error::Error};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -19,17 +21,33 @@ impl Client {
         Self { client }
     }
 
-    pub async fn create(
+    pub async fn create(&self, params: &CreateEntityParams) -> Result<CreateEntityResponse, Error> {
+        self.client.post("/sys/entity".into(), params).await
+    }
+
+    /// Create an entity with an idempotency key so the request can be retried
+    /// without creating the entity twice.
+    pub async fn create_idempotent(
         &self,
         params: &CreateEntityParams,
-    ) -> Result<CreateEntityResponse, String> {
-        self.client.post("/sys/entity".into(), params).await
+        key: &str,
+    ) -> Result<CreateEntityResponse, Error> {
+        self.client
+            .post_idempotent("/sys/entity".into(), params, key)
+            .await
+    }
+
+    pub async fn import(
+        &self,
+        params: &ImportEntitiesParams,
+    ) -> Result<ImportEntitiesResponse, Error> {
+        self.client.post("/sys/entity/import".into(), params).await
     }
 
     pub async fn attach_policies(
         &self,
         params: &AttachEntityPolicyParams,
-    ) -> Result<AttachEntityPolicyResponse, String> {
+    ) -> Result<AttachEntityPolicyResponse, Error> {
         self.client.put("/sys/entity/policy".into(), params).await
     }
 
@@ -37,7 +55,7 @@ impl Client {
         &self,
         name: &str,
         params: &RemoveEntityPolicyParams,
-    ) -> Result<RemoveEntityPolicyResponse, String> {
+    ) -> Result<RemoveEntityPolicyResponse, Error> {
         self.client
             .put(format!("/sys/entity/policy/{name}"), params)
             .await
@@ -46,7 +64,7 @@ impl Client {
     pub async fn attach_alias(
         &self,
         params: &AttachEntityAliasParams,
-    ) -> Result<AttachEntityAliasResponse, String> {
+    ) -> Result<AttachEntityAliasResponse, Error> {
         self.client.put("/sys/entity/alias".into(), params).await
     }
 
@@ -54,13 +72,29 @@ impl Client {
         &self,
         name: &str,
         params: &RemoveEntityAliasParams,
-    ) -> Result<RemoveEntityAliasResponse, String> {
+    ) -> Result<RemoveEntityAliasResponse, Error> {
         self.client
             .put(format!("/sys/entity/alias/{name}"), params)
             .await
     }
 
-    pub async fn list(&self) -> Result<ListEntitiesResponse, String> {
+    /// Delete the entity and revoke its tokens. [`DeleteEntityParams`] decides
+    /// whether the tokens issued with them to other entities are revoked too.
+    pub async fn delete(
+        &self,
+        name: &str,
+        params: &DeleteEntityParams,
+    ) -> Result<DeleteEntityResponse, Error> {
+        let child_tokens = match params.child_tokens {
+            ChildTokens::Revoke => "revoke",
+            ChildTokens::Orphan => "orphan",
+        };
+        self.client
+            .delete(format!("/sys/entity/{name}?child_tokens={child_tokens}"))
+            .await
+    }
+
+    pub async fn list(&self) -> Result<ListEntitiesResponse, Error> {
         self.client.get("/sys/entity".into()).await
     }
 }
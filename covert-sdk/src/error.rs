@@ -0,0 +1,175 @@
+use std::{fmt::Display, time::Duration};
+
+pub use covert_types::{
+    error::{ErrorCode, FieldError},
+    methods::MfaRequirement,
+};
+pub use reqwest::StatusCode;
+
+/// Error returned by the SDK. Errors of the server are mapped to a variant
+/// by their [`ErrorCode`], codes without a variant of their own are returned
+/// as [`Error::Api`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The server is sealed, or the operation is not allowed in its current
+    /// state.
+    Sealed(ServerError),
+    /// The token is missing or is not allowed to use the path.
+    PermissionDenied(ServerError),
+    /// Nothing was found at the path.
+    NotFound {
+        /// URL path of the request.
+        path: String,
+        error: ServerError,
+    },
+    /// A check-and-set write was refused since the secret was written in the
+    /// meantime.
+    CasConflict {
+        /// Version of the secret, if returned by the server.
+        current_version: Option<u64>,
+        error: ServerError,
+    },
+    /// The server responded with any other error.
+    Api(ServerError),
+    /// The request could not be sent or the response could not be read.
+    Transport(String),
+    /// The login was accepted but has to be completed with a second factor
+    /// through [`crate::mfa::Client::validate`].
+    MfaRequired(MfaRequirement),
+    /// The request was retried according to the [`crate::RetryPolicy`] and
+    /// the last attempt failed with `error`.
+    RetriesExhausted { attempts: u32, error: Box<Error> },
+    /// A listing had more pages than [`crate::pagination::list_all`] was
+    /// allowed to fetch.
+    PageLimitExceeded { max_pages: usize },
+}
+
+/// Error response of the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    pub status: StatusCode,
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: Vec<FieldError>,
+    /// How long to wait before retrying, e.g. when rate limited.
+    pub retry_after: Option<Duration>,
+    /// Body of the response, to read fields not known to this version of the
+    /// SDK.
+    pub body: String,
+}
+
+impl Error {
+    /// Map the error response of the server to its variant.
+    pub(crate) fn from_server(
+        path: &str,
+        error: ServerError,
+        current_version: Option<u64>,
+    ) -> Self {
+        match error.code {
+            ErrorCode::SealedState => Error::Sealed(error),
+            ErrorCode::PermissionDenied => Error::PermissionDenied(error),
+            ErrorCode::NotFound => Error::NotFound {
+                path: path.to_string(),
+                error,
+            },
+            ErrorCode::CasConflict => Error::CasConflict {
+                current_version,
+                error,
+            },
+            _ => Error::Api(error),
+        }
+    }
+
+    pub(crate) fn after_attempts(attempts: u32, error: Error) -> Self {
+        if attempts > 1 {
+            Error::RetriesExhausted {
+                attempts,
+                error: Box::new(error),
+            }
+        } else {
+            error
+        }
+    }
+
+    /// The error response of the server.
+    #[must_use]
+    pub fn server_error(&self) -> Option<&ServerError> {
+        match self {
+            Error::Sealed(error)
+            | Error::PermissionDenied(error)
+            | Error::NotFound { error, .. }
+            | Error::CasConflict { error, .. }
+            | Error::Api(error) => Some(error),
+            Error::Transport(_) | Error::MfaRequired(_) | Error::PageLimitExceeded { .. } => None,
+            Error::RetriesExhausted { error, .. } => error.server_error(),
+        }
+    }
+
+    /// The error code returned by the server.
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.server_error().map(|error| error.code)
+    }
+
+    /// The HTTP status code returned by the server.
+    #[must_use]
+    pub fn status(&self) -> Option<StatusCode> {
+        self.server_error().map(|error| error.status)
+    }
+
+    #[must_use]
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Sealed(error)
+            | Error::PermissionDenied(error)
+            | Error::NotFound { error, .. }
+            | Error::CasConflict { error, .. }
+            | Error::Api(error) => &error.message,
+            Error::Transport(message) => message,
+            Error::MfaRequired(_) => "The login requires MFA",
+            Error::PageLimitExceeded { .. } => "The listing has more pages than allowed",
+            Error::RetriesExhausted { error, .. } => error.message(),
+        }
+    }
+
+    /// Whether sending the request again later could succeed, e.g. when the
+    /// connection failed, the server was rate limited or failed to reach an
+    /// upstream system.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Transport(_) => true,
+            Error::RetriesExhausted { error, .. } => error.is_retryable(),
+            _ => self.server_error().is_some_and(|error| {
+                matches!(
+                    error.code,
+                    ErrorCode::Timeout
+                        | ErrorCode::RateLimited
+                        | ErrorCode::ServerBusy
+                        | ErrorCode::UpstreamError
+                ) || matches!(
+                    error.status,
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                )
+            }),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::RetriesExhausted { attempts, error } => {
+                write!(f, "{error} (after {attempts} attempts)")
+            }
+            Error::PageLimitExceeded { max_pages } => {
+                write!(f, "The listing has more than {max_pages} pages")
+            }
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::system::{
+    IdentitySnapshot, ImportEntity, ImportIdentityMode, ImportIdentityParams,
+    ImportIdentityResponse,
+};
+
+use crate::{base::BaseClient, error::Error};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    /// Export an encrypted snapshot of the policies and entities of the
+    /// namespace. It can only be imported by a server unsealed with the same
+    /// master key.
+    pub async fn export(&self) -> Result<IdentitySnapshot, Error> {
+        self.client.get("/sys/identity/export".into()).await
+    }
+
+    pub async fn import(
+        &self,
+        params: &ImportIdentityParams,
+    ) -> Result<ImportIdentityResponse, Error> {
+        self.client
+            .post("/sys/identity/import".into(), params)
+            .await
+    }
+}
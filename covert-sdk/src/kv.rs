@@ -2,12 +2,13 @@ use std::sync::Arc;
 
 use covert_types::methods::kv::CreateSecretResponse;
 pub use covert_types::methods::kv::{
-    CreateSecretParams, HardDeleteSecretParams, HardDeleteSecretResponse, ReadConfigResponse,
-    ReadSecretResponse, RecoverSecretParams, RecoverSecretResponse, SetConfigParams,
-    SetConfigResponse, SoftDeleteSecretParams, SoftDeleteSecretResponse,
+    CreateSecretParams, HardDeleteSecretParams, HardDeleteSecretResponse, ListSecretsResponse,
+    PatchSecretParams, ReadConfigResponse, ReadSecretResponse, RecoverSecretParams,
+    RecoverSecretResponse, SecretScanConfig, SecretScanFinding, SecretScanMode, SecretScanPattern,
+    SetConfigParams, SetConfigResponse, SoftDeleteSecretParams, SoftDeleteSecretResponse,
 };
 
-use crate::{base::BaseClient, utils::get_mount_path};
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
 
 pub struct Client {
     config: Arc<BaseClient>,
@@ -23,17 +24,29 @@ impl Client {
         mount: &str,
         key: &str,
         params: &CreateSecretParams,
-    ) -> Result<CreateSecretResponse, String> {
+    ) -> Result<CreateSecretResponse, Error> {
         let path = get_mount_path(mount, &format!("data/{key}"));
         self.config.post(path, params).await
     }
 
+    /// Merge the fields into the latest version of the secret to create a new
+    /// version. Fields set to `None` are removed.
+    pub async fn patch(
+        &self,
+        mount: &str,
+        key: &str,
+        params: &PatchSecretParams,
+    ) -> Result<CreateSecretResponse, Error> {
+        let path = get_mount_path(mount, &format!("data/{key}"));
+        self.config.patch(path, params).await
+    }
+
     pub async fn read(
         &self,
         mount: &str,
         key: &str,
         version: Option<u32>,
-    ) -> Result<ReadSecretResponse, String> {
+    ) -> Result<ReadSecretResponse, Error> {
         let mut path = get_mount_path(mount, &format!("data/{key}"));
         if let Some(version) = version {
             path = format!("{path}?version={version}");
@@ -41,16 +54,23 @@ impl Client {
         self.config.get(path).await
     }
 
+    /// List the keys directly below `prefix`. Keys ending with `/` have more
+    /// keys below them.
+    pub async fn list(&self, mount: &str, prefix: &str) -> Result<ListSecretsResponse, Error> {
+        let path = get_mount_path(mount, &format!("metadata/{prefix}"));
+        self.config.get(path).await
+    }
+
     pub async fn set_config(
         &self,
         mount: &str,
         params: &SetConfigParams,
-    ) -> Result<SetConfigResponse, String> {
+    ) -> Result<SetConfigResponse, Error> {
         let path = get_mount_path(mount, "config");
         self.config.post(path, params).await
     }
 
-    pub async fn read_config(&self, mount: &str) -> Result<ReadConfigResponse, String> {
+    pub async fn read_config(&self, mount: &str) -> Result<ReadConfigResponse, Error> {
         let path = get_mount_path(mount, "config");
         self.config.get(path).await
     }
@@ -60,7 +80,7 @@ impl Client {
         mount: &str,
         key: &str,
         params: &SoftDeleteSecretParams,
-    ) -> Result<SoftDeleteSecretResponse, String> {
+    ) -> Result<SoftDeleteSecretResponse, Error> {
         let path = get_mount_path(mount, &format!("delete/{key}"));
         self.config.post(path, params).await
     }
@@ -70,7 +90,7 @@ impl Client {
         mount: &str,
         key: &str,
         params: &RecoverSecretParams,
-    ) -> Result<RecoverSecretResponse, String> {
+    ) -> Result<RecoverSecretResponse, Error> {
         let path = get_mount_path(mount, &format!("undelete/{key}"));
         self.config.post(path, params).await
     }
@@ -80,7 +100,7 @@ impl Client {
         mount: &str,
         key: &str,
         params: &HardDeleteSecretParams,
-    ) -> Result<HardDeleteSecretResponse, String> {
+    ) -> Result<HardDeleteSecretResponse, Error> {
         let path = get_mount_path(mount, &format!("destroy/{key}"));
         self.config.post(path, params).await
     }
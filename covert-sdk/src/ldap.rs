@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::{
+    ldap::{
+        ConfigResponse, GroupResponse, ListGroupsResponse, LoginParams, RemoveGroupResponse,
+        SetConfigParams, SetGroupParams,
+    },
+    AuthResponse,
+};
+
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    pub async fn set_config(
+        &self,
+        mount: &str,
+        params: &SetConfigParams,
+    ) -> Result<ConfigResponse, Error> {
+        let path = get_mount_path(mount, "config");
+        self.client.put(path, params).await
+    }
+
+    pub async fn read_config(&self, mount: &str) -> Result<ConfigResponse, Error> {
+        let path = get_mount_path(mount, "config");
+        self.client.get(path).await
+    }
+
+    pub async fn login(
+        &self,
+        mount: &str,
+        username: &str,
+        params: &LoginParams,
+    ) -> Result<AuthResponse, Error> {
+        let path = get_mount_path(mount, &format!("login/{username}"));
+        self.client.login(path, params).await
+    }
+
+    pub async fn set_group(
+        &self,
+        mount: &str,
+        name: &str,
+        params: &SetGroupParams,
+    ) -> Result<GroupResponse, Error> {
+        let path = get_mount_path(mount, &format!("groups/{name}"));
+        self.client.put(path, params).await
+    }
+
+    pub async fn read_group(&self, mount: &str, name: &str) -> Result<GroupResponse, Error> {
+        let path = get_mount_path(mount, &format!("groups/{name}"));
+        self.client.get(path).await
+    }
+
+    pub async fn list_groups(&self, mount: &str) -> Result<ListGroupsResponse, Error> {
+        let path = get_mount_path(mount, "groups");
+        self.client.get(path).await
+    }
+
+    pub async fn remove_group(
+        &self,
+        mount: &str,
+        name: &str,
+    ) -> Result<RemoveGroupResponse, Error> {
+        let path = get_mount_path(mount, &format!("groups/{name}"));
+        self.client.delete(path).await
+    }
+}
@@ -1,12 +1,36 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
 
 use covert_types::methods::system::RenewLeaseParams;
 pub use covert_types::methods::system::{
-    ListLeasesResponse, LookupLeaseResponse, RenewLeaseResponse, RevokedLeaseResponse,
-    RevokedLeasesResponse,
+    CountLeasesResponse, LeaseEntry, ListLeasesResponse, LookupLeaseResponse, RenewLeaseResponse,
+    RevokedLeaseResponse, RevokedLeasesResponse,
 };
+use futures::Stream;
 
-use crate::base::BaseClient;
+use crate::{
+    base::BaseClient,
+    error::Error,
+    pagination::{self, PageParams, Paginated},
+};
+
+/// Query string selecting the leases that have all the tags.
+fn tag_filter(tags: &BTreeMap<String, String>) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let mut query = form_urlencoded::Serializer::new(String::from("?"));
+    for (key, value) in tags {
+        query.append_pair("tag", &format!("{key}={value}"));
+    }
+    query.finish()
+}
+
+fn page(limit: u32, cursor: Option<String>) -> PageParams {
+    PageParams {
+        limit: Some(limit),
+        cursor,
+    }
+}
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -20,37 +44,151 @@ impl Client {
     pub async fn renew(
         &self,
         lease_id: &str,
-        ttl: Option<Duration>,
-    ) -> Result<RenewLeaseResponse, String> {
+        increment: Option<Duration>,
+    ) -> Result<RenewLeaseResponse, Error> {
         self.client
-            .put(
+            .renew(
                 format!("/sys/leases/renew/{lease_id}"),
-                &RenewLeaseParams { ttl },
+                &RenewLeaseParams { increment },
             )
             .await
     }
 
-    pub async fn revoke(&self, lease_id: &str) -> Result<RevokedLeaseResponse, String> {
+    pub async fn revoke(&self, lease_id: &str) -> Result<RevokedLeaseResponse, Error> {
         self.client
             .put(format!("/sys/leases/revoke/{lease_id}"), &())
             .await
     }
 
-    pub async fn lookup(&self, lease_id: &str) -> Result<LookupLeaseResponse, String> {
+    pub async fn lookup(&self, lease_id: &str) -> Result<LookupLeaseResponse, Error> {
         self.client
             .get(format!("/sys/leases/lookup/{lease_id}"))
             .await
     }
 
-    pub async fn revoke_by_mount(&self, prefix: &str) -> Result<RevokedLeasesResponse, String> {
+    pub async fn revoke_by_mount(&self, prefix: &str) -> Result<RevokedLeasesResponse, Error> {
         self.client
             .put(format!("/sys/leases/revoke-mount/{prefix}"), &())
             .await
     }
 
-    pub async fn list_by_mount(&self, prefix: &str) -> Result<ListLeasesResponse, String> {
+    /// Remove the leases under the prefix even if the backend fails to
+    /// revoke them. The credentials of the leases in
+    /// [`RevokedLeasesResponse::backend_failures`] may still be valid.
+    pub async fn force_revoke_by_mount(
+        &self,
+        prefix: &str,
+    ) -> Result<RevokedLeasesResponse, Error> {
+        self.client
+            .put(format!("/sys/leases/revoke-force/{prefix}"), &())
+            .await
+    }
+
+    pub async fn list_by_mount(&self, prefix: &str) -> Result<ListLeasesResponse, Error> {
         self.client
             .get(format!("/sys/leases/lookup-mount/{prefix}"))
             .await
     }
+
+    /// List the leases under the prefix that have all the given tags.
+    pub async fn list_by_mount_with_tags(
+        &self,
+        prefix: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<ListLeasesResponse, Error> {
+        self.client
+            .get(format!(
+                "/sys/leases/lookup-mount/{prefix}{}",
+                tag_filter(tags)
+            ))
+            .await
+    }
+
+    /// List a page of the leases under the prefix that have all the given
+    /// tags. Pages are ordered by lease id.
+    pub async fn list_page_by_mount(
+        &self,
+        prefix: &str,
+        tags: &BTreeMap<String, String>,
+        page: &PageParams,
+    ) -> Result<Paginated<LeaseEntry>, Error> {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in tags {
+            query.append_pair("tag", &format!("{key}={value}"));
+        }
+        page.append_to(&mut query);
+        let resp: ListLeasesResponse = self
+            .client
+            .get(format!(
+                "/sys/leases/lookup-mount/{prefix}?{}",
+                query.finish()
+            ))
+            .await?;
+        Ok(Paginated {
+            items: resp.leases,
+            next_cursor: resp.next_cursor,
+        })
+    }
+
+    /// List all the leases under the prefix page by page, following at most
+    /// [`pagination::DEFAULT_MAX_PAGES`] pages.
+    pub async fn list_all_by_mount(
+        &self,
+        prefix: &str,
+        tags: &BTreeMap<String, String>,
+        page_size: u32,
+    ) -> Result<Vec<LeaseEntry>, Error> {
+        pagination::list_all(
+            |cursor| async move {
+                self.list_page_by_mount(prefix, tags, &page(page_size, cursor))
+                    .await
+            },
+            pagination::DEFAULT_MAX_PAGES,
+        )
+        .await
+    }
+
+    /// Stream the leases under the prefix, fetching `page_size` of them at
+    /// once.
+    pub fn stream_by_mount<'a>(
+        &'a self,
+        prefix: &'a str,
+        tags: &'a BTreeMap<String, String>,
+        page_size: u32,
+    ) -> impl Stream<Item = Result<LeaseEntry, Error>> + 'a {
+        pagination::stream(move |cursor| async move {
+            self.list_page_by_mount(prefix, tags, &page(page_size, cursor))
+                .await
+        })
+    }
+
+    /// Count the leases that have all the given tags, either under the mount
+    /// prefix or across all mounts.
+    pub async fn count(
+        &self,
+        prefix: Option<&str>,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<CountLeasesResponse, Error> {
+        let path = match prefix {
+            Some(prefix) => format!("/sys/leases/count-mount/{prefix}"),
+            None => "/sys/leases/count".to_string(),
+        };
+        self.client.get(format!("{path}{}", tag_filter(tags))).await
+    }
+
+    /// Revoke the leases that have all the given tags, either under the
+    /// mount prefix or across all mounts.
+    pub async fn revoke_by_tags(
+        &self,
+        prefix: Option<&str>,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<RevokedLeasesResponse, Error> {
+        let path = match prefix {
+            Some(prefix) => format!("/sys/leases/revoke-mount/{prefix}"),
+            None => "/sys/leases/revoke-tag".to_string(),
+        };
+        self.client
+            .put(format!("{path}{}", tag_filter(tags)), &())
+            .await
+    }
 }
@@ -1,60 +1,151 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use base::BaseClient;
+pub use covert_types::request::TokenHeader;
+pub use error::{Error, ErrorCode, ServerError};
+pub use retry::RetryPolicy;
+use transport::{InProcess, Transport};
 
+pub mod audit;
 pub(crate) mod base;
 pub mod entity;
+pub mod error;
+pub mod identity;
 pub mod kv;
+pub mod ldap;
 pub mod lease;
+pub mod lockout;
+pub mod logical;
+pub mod mfa;
 pub mod mounts;
 pub mod namespace;
+pub mod oidc;
 pub mod operator;
+pub mod pagination;
 pub mod policy;
 pub mod psql;
+pub mod quota;
+pub(crate) mod retry;
+pub mod static_secret;
 pub mod status;
+pub mod token;
+pub mod transit;
+pub(crate) mod transport;
 pub mod userpass;
 pub(crate) mod utils;
+pub mod wrapping;
 
 pub struct Client {
+    pub audit: crate::audit::Client,
     pub entity: crate::entity::Client,
+    pub identity: crate::identity::Client,
     pub policy: crate::policy::Client,
     pub operator: crate::operator::Client,
     pub status: crate::status::Client,
     pub mount: crate::mounts::Client,
     pub kv: crate::kv::Client,
+    pub ldap: crate::ldap::Client,
     pub psql: crate::psql::Client,
+    pub quota: crate::quota::Client,
+    pub static_secret: crate::static_secret::Client,
     pub userpass: crate::userpass::Client,
     pub lease: crate::lease::Client,
+    pub lockout: crate::lockout::Client,
+    pub logical: crate::logical::Client,
+    pub mfa: crate::mfa::Client,
     pub namespace: crate::namespace::Client,
+    pub oidc: crate::oidc::Client,
+    pub token: crate::token::Client,
+    pub transit: crate::transit::Client,
+    pub wrapping: crate::wrapping::Client,
     base: Arc<BaseClient>,
 }
 
 impl Client {
     pub fn new(api_url: impl ToString) -> Self {
-        let base_client = Arc::new(BaseClient::new(api_url));
+        let http = reqwest::Client::new();
+        Self::with_transport(
+            api_url,
+            http.clone(),
+            Arc::new(http),
+            RetryPolicy::default(),
+            TokenHeader::default(),
+        )
+    }
+
+    #[must_use]
+    pub fn builder(api_url: impl ToString) -> ClientBuilder {
+        ClientBuilder {
+            api_url: api_url.to_string(),
+            root_certificates: vec![],
+            unix_socket: None,
+            service: None,
+            token_header: TokenHeader::default(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    fn with_transport(
+        api_url: impl ToString,
+        http: reqwest::Client,
+        transport: Arc<dyn Transport>,
+        retry_policy: RetryPolicy,
+        token_header: TokenHeader,
+    ) -> Self {
+        let base_client = Arc::new(BaseClient::new(
+            api_url,
+            http,
+            transport,
+            retry_policy,
+            token_header,
+        ));
 
+        let audit = crate::audit::Client::new(Arc::clone(&base_client));
         let entity = crate::entity::Client::new(Arc::clone(&base_client));
+        let identity = crate::identity::Client::new(Arc::clone(&base_client));
         let policy = crate::policy::Client::new(Arc::clone(&base_client));
         let operator = crate::operator::Client::new(Arc::clone(&base_client));
         let status = crate::status::Client::new(Arc::clone(&base_client));
         let mounts = crate::mounts::Client::new(Arc::clone(&base_client));
         let kv = crate::kv::Client::new(Arc::clone(&base_client));
+        let ldap = crate::ldap::Client::new(Arc::clone(&base_client));
         let psql = crate::psql::Client::new(Arc::clone(&base_client));
+        let quota = crate::quota::Client::new(Arc::clone(&base_client));
+        let static_secret = crate::static_secret::Client::new(Arc::clone(&base_client));
         let userpass = crate::userpass::Client::new(Arc::clone(&base_client));
         let lease = crate::lease::Client::new(Arc::clone(&base_client));
+        let lockout = crate::lockout::Client::new(Arc::clone(&base_client));
+        let logical = crate::logical::Client::new(Arc::clone(&base_client));
+        let mfa = crate::mfa::Client::new(Arc::clone(&base_client));
         let namespace = crate::namespace::Client::new(Arc::clone(&base_client));
+        let oidc = crate::oidc::Client::new(Arc::clone(&base_client));
+        let token = crate::token::Client::new(Arc::clone(&base_client));
+        let transit = crate::transit::Client::new(Arc::clone(&base_client));
+        let wrapping = crate::wrapping::Client::new(Arc::clone(&base_client));
 
         Self {
+            audit,
             entity,
+            identity,
             policy,
             operator,
             status,
             mount: mounts,
             kv,
+            ldap,
             psql,
+            quota,
+            static_secret,
             userpass,
             lease,
+            lockout,
+            logical,
+            mfa,
             namespace,
+            oidc,
+            token,
+            transit,
+            wrapping,
             base: base_client,
         }
     }
@@ -67,3 +158,109 @@ impl Client {
         self.base.set_namespace(namespace).await
     }
 }
+
+pub struct ClientBuilder {
+    api_url: String,
+    root_certificates: Vec<reqwest::Certificate>,
+    unix_socket: Option<PathBuf>,
+    service: Option<Arc<dyn Transport>>,
+    token_header: TokenHeader,
+    retry_policy: RetryPolicy,
+}
+
+impl ClientBuilder {
+    /// Trust the PEM encoded CA certificate when connecting to the server
+    /// over TLS, in addition to the system trust store.
+    pub fn root_certificate_pem(mut self, pem: &[u8]) -> Result<Self, Error> {
+        let cert = reqwest::Certificate::from_pem(pem)
+            .map_err(|e| Error::Transport(format!("Invalid root certificate: {e}")))?;
+        self.root_certificates.push(cert);
+        Ok(self)
+    }
+
+    /// Connect to the server over the unix socket at `path` instead of TCP.
+    /// The host of the API URL is ignored, e.g. `http://localhost/v1` can be
+    /// used as the API URL.
+    #[must_use]
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Send the requests to `service` in the same process instead of over
+    /// the network, e.g. to test against a server without binding a port.
+    /// The root certificates and unix socket are ignored.
+    #[must_use]
+    pub fn service<S>(mut self, service: S) -> Self
+    where
+        S: tower::Service<http::Request<bytes::Bytes>, Response = http::Response<bytes::Bytes>>
+            + Clone
+            + Send
+            + 'static,
+        S::Error: std::fmt::Display,
+        S::Future: Send,
+    {
+        self.service = Some(Arc::new(InProcess::new(service)));
+        self
+    }
+
+    /// Header to send the token in, `X-Covert-Token` by default. Use
+    /// [`TokenHeader::Authorization`] to send it as a bearer token, e.g. when
+    /// a gateway in front of the server only forwards `Authorization`.
+    #[must_use]
+    pub fn token_header(mut self, header: TokenHeader) -> Self {
+        self.token_header = header;
+        self
+    }
+
+    /// How failed requests are retried, see [`RetryPolicy`]. Use
+    /// [`RetryPolicy::never`] to send every request once.
+    #[must_use]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Build a client sending its requests through `transport`.
+    #[cfg(test)]
+    pub(crate) fn build_with_transport(self, transport: Arc<dyn Transport>) -> Client {
+        Client::with_transport(
+            self.api_url,
+            reqwest::Client::new(),
+            transport,
+            self.retry_policy,
+            self.token_header,
+        )
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        if let Some(service) = self.service {
+            return Ok(Client::with_transport(
+                self.api_url,
+                reqwest::Client::new(),
+                service,
+                self.retry_policy,
+                self.token_header,
+            ));
+        }
+        let mut builder = self
+            .root_certificates
+            .into_iter()
+            .fold(reqwest::Client::builder(), |builder, cert| {
+                builder.add_root_certificate(cert)
+            });
+        if let Some(path) = self.unix_socket {
+            builder = builder.unix_socket(path);
+        }
+        let http = builder
+            .build()
+            .map_err(|e| Error::Transport(format!("{e:#?}")))?;
+        Ok(Client::with_transport(
+            self.api_url,
+            http.clone(),
+            Arc::new(http),
+            self.retry_policy,
+            self.token_header,
+        ))
+    }
+}
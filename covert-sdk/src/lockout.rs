@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::lockout::{
+    ListLockedAliasesResponse, LockedAliasItem, LockoutConfig, UnlockAliasResponse,
+};
+
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    pub async fn read_config(&self, mount: &str) -> Result<LockoutConfig, Error> {
+        let path = get_mount_path(mount, "config/auth-lockout");
+        self.client.get(path).await
+    }
+
+    pub async fn set_config(
+        &self,
+        mount: &str,
+        config: &LockoutConfig,
+    ) -> Result<LockoutConfig, Error> {
+        let path = get_mount_path(mount, "config/auth-lockout");
+        self.client.put(path, config).await
+    }
+
+    pub async fn locked(&self, mount: &str) -> Result<ListLockedAliasesResponse, Error> {
+        let path = get_mount_path(mount, "locked-users");
+        self.client.get(path).await
+    }
+
+    pub async fn unlock(&self, mount: &str, alias: &str) -> Result<UnlockAliasResponse, Error> {
+        let path = get_mount_path(mount, &format!("unlock/{alias}"));
+        self.client.put(path, &()).await
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::{base::BaseClient, error::Error};
+
+/// Requests to any path, for callers that don't know the backend mounted at
+/// the path, e.g. to render secrets into templates.
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    pub async fn read(&self, path: &str) -> Result<Value, Error> {
+        self.client.get(normalize(path)).await
+    }
+
+    /// Send an update request, e.g. to issue dynamic credentials.
+    pub async fn write(&self, path: &str, data: &Value) -> Result<Value, Error> {
+        self.client.put(normalize(path), data).await
+    }
+}
+
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_start_matches('/'))
+}
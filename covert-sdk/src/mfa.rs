@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::{
+    system::{GenerateTotpKeyParams, GenerateTotpKeyResponse, TotpKeyResponse, ValidateMfaParams},
+    AuthResponse, MfaMethod, MfaRequirement,
+};
+
+use crate::{base::BaseClient, error::Error};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    /// Complete a login that returned [`Error::MfaRequired`].
+    pub async fn validate(&self, params: &ValidateMfaParams) -> Result<AuthResponse, Error> {
+        self.client
+            .put("/sys/mfa/validate".to_string(), params)
+            .await
+    }
+
+    /// Generate a new TOTP key for the entity, replacing its current key.
+    pub async fn generate_totp_key(
+        &self,
+        entity: &str,
+        params: &GenerateTotpKeyParams,
+    ) -> Result<GenerateTotpKeyResponse, Error> {
+        self.client
+            .post(format!("/sys/mfa/totp/{entity}"), params)
+            .await
+    }
+
+    pub async fn read_totp_key(&self, entity: &str) -> Result<TotpKeyResponse, Error> {
+        self.client.get(format!("/sys/mfa/totp/{entity}")).await
+    }
+
+    pub async fn remove_totp_key(&self, entity: &str) -> Result<TotpKeyResponse, Error> {
+        self.client.delete(format!("/sys/mfa/totp/{entity}")).await
+    }
+}
@@ -1,13 +1,14 @@
 use std::sync::Arc;
 
-pub use covert_types::backend::{BackendCategory, BackendType};
+pub use covert_types::backend::{BackendCategory, BackendType, RouteHelp};
 pub use covert_types::methods::system::{
-    CreateMountParams, CreateMountResponse, DisableMountResponse, MountsListResponse,
-    UpdateMountParams, UpdateMountResponse,
+    AppliedMigration, CreateMountParams, CreateMountResponse, DisableMountResponse,
+    MountMigrationsResponse, MountPathsResponse, MountResponse, MountTuneResponse, MountTuning,
+    MountsListResponse, PendingMigration, TuneMountParams, UpdateMountParams, UpdateMountResponse,
 };
-pub use covert_types::mount::MountConfig;
+pub use covert_types::mount::{ListingVisibility, MountConfig, TransitKey};
 
-use crate::base::BaseClient;
+use crate::{base::BaseClient, error::Error};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -22,7 +23,7 @@ impl Client {
         &self,
         path: &str,
         params: &CreateMountParams,
-    ) -> Result<CreateMountResponse, String> {
+    ) -> Result<CreateMountResponse, Error> {
         self.client
             .post(format!("/sys/mounts/{path}"), params)
             .await
@@ -32,15 +33,56 @@ impl Client {
         &self,
         path: &str,
         params: &UpdateMountParams,
-    ) -> Result<UpdateMountResponse, String> {
+    ) -> Result<UpdateMountResponse, Error> {
         self.client.put(format!("/sys/mounts/{path}"), params).await
     }
 
-    pub async fn list(&self) -> Result<MountsListResponse, String> {
+    pub async fn list(&self) -> Result<MountsListResponse, Error> {
         self.client.get("/sys/mounts".into()).await
     }
 
-    pub async fn remove(&self, path: &str) -> Result<DisableMountResponse, String> {
+    pub async fn get(&self, path: &str) -> Result<MountResponse, Error> {
+        self.client.get(format!("/sys/mounts/{path}")).await
+    }
+
+    pub async fn migrations(&self, path: &str) -> Result<MountMigrationsResponse, Error> {
+        self.client
+            .get(format!(
+                "/sys/mounts/{}/migrations",
+                path.trim_end_matches('/')
+            ))
+            .await
+    }
+
+    /// Describe the routes served by the mount.
+    pub async fn paths(&self, path: &str) -> Result<MountPathsResponse, Error> {
+        self.client
+            .get(format!("/sys/mounts/{}/paths", path.trim_end_matches('/')))
+            .await
+    }
+
+    /// Read the tuning of the mount, with the limits of the server applied.
+    pub async fn tuning(&self, path: &str) -> Result<MountTuneResponse, Error> {
+        self.client
+            .get(format!("/sys/mounts/{}/tune", path.trim_end_matches('/')))
+            .await
+    }
+
+    /// Change some settings of the mount and keep the others.
+    pub async fn tune(
+        &self,
+        path: &str,
+        params: &TuneMountParams,
+    ) -> Result<MountTuneResponse, Error> {
+        self.client
+            .put(
+                format!("/sys/mounts/{}/tune", path.trim_end_matches('/')),
+                params,
+            )
+            .await
+    }
+
+    pub async fn remove(&self, path: &str) -> Result<DisableMountResponse, Error> {
         self.client.delete(format!("/sys/mounts/{path}")).await
     }
 }
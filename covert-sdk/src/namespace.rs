@@ -4,7 +4,7 @@ pub use covert_types::methods::system::{
     CreateNamespaceParams, CreateNamespaceResponse, DeleteNamespaceResponse, ListNamespaceResponse,
 };
 
-use crate::base::BaseClient;
+use crate::{base::BaseClient, error::Error};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -18,15 +18,15 @@ impl Client {
     pub async fn create(
         &self,
         params: &CreateNamespaceParams,
-    ) -> Result<CreateNamespaceResponse, String> {
+    ) -> Result<CreateNamespaceResponse, Error> {
         self.client.post("/sys/namespaces".into(), params).await
     }
 
-    pub async fn delete(&self, name: &str) -> Result<DeleteNamespaceResponse, String> {
+    pub async fn delete(&self, name: &str) -> Result<DeleteNamespaceResponse, Error> {
         self.client.delete(format!("/sys/namespaces/{name}")).await
     }
 
-    pub async fn list(&self) -> Result<ListNamespaceResponse, String> {
+    pub async fn list(&self) -> Result<ListNamespaceResponse, Error> {
         self.client.get("/sys/namespaces".into()).await
     }
 }
@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::{
+    oidc::{AuthUrlParams, AuthUrlResponse, CallbackParams, ConfigResponse, SetConfigParams},
+    AuthResponse,
+};
+
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    pub async fn set_config(
+        &self,
+        mount: &str,
+        params: &SetConfigParams,
+    ) -> Result<ConfigResponse, Error> {
+        let path = get_mount_path(mount, "config");
+        self.client.put(path, params).await
+    }
+
+    pub async fn read_config(&self, mount: &str) -> Result<ConfigResponse, Error> {
+        let path = get_mount_path(mount, "config");
+        self.client.get(path).await
+    }
+
+    /// Starts a login. The user logs in with the provider at the returned URL
+    /// and is sent back to the redirect URI with the code and state for the
+    /// [`callback`](Self::callback).
+    pub async fn auth_url(
+        &self,
+        mount: &str,
+        params: &AuthUrlParams,
+    ) -> Result<AuthUrlResponse, Error> {
+        let path = get_mount_path(mount, "auth_url");
+        self.client.put(path, params).await
+    }
+
+    pub async fn callback(
+        &self,
+        mount: &str,
+        params: &CallbackParams,
+    ) -> Result<AuthResponse, Error> {
+        let path = get_mount_path(mount, "callback");
+        self.client.login(path, params).await
+    }
+}
@@ -4,7 +4,7 @@ pub use covert_types::methods::system::{
     InitializeParams, InitializeResponse, SealResponse, UnsealParams, UnsealResponse,
 };
 
-use crate::base::BaseClient;
+use crate::{base::BaseClient, error::Error};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -15,18 +15,20 @@ impl Client {
         Self { client }
     }
 
-    pub async fn initialize(
-        &self,
-        params: &InitializeParams,
-    ) -> Result<InitializeResponse, String> {
+    pub async fn initialize(&self, params: &InitializeParams) -> Result<InitializeResponse, Error> {
         self.client.post("/sys/init".into(), params).await
     }
 
-    pub async fn unseal(&self, params: &UnsealParams) -> Result<UnsealResponse, String> {
+    pub async fn unseal(&self, params: &UnsealParams) -> Result<UnsealResponse, Error> {
         self.client.post("/sys/unseal".into(), params).await
     }
 
-    pub async fn seal(&self) -> Result<SealResponse, String> {
+    pub async fn seal(&self) -> Result<SealResponse, Error> {
         self.client.post("/sys/seal".into(), &()).await
     }
+
+    /// Export a redacted support bundle as a gzip compressed tarball.
+    pub async fn support_bundle(&self) -> Result<Vec<u8>, Error> {
+        self.client.get_bytes("/sys/support-bundle".into()).await
+    }
 }
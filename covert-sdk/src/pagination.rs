@@ -0,0 +1,153 @@
+use std::future::Future;
+
+use futures::{stream, Stream, StreamExt};
+
+use crate::error::Error;
+
+/// Max number of pages [`list_all`] follows by default before giving up, so
+/// a listing that keeps growing can't loop forever.
+pub const DEFAULT_MAX_PAGES: usize = 1000;
+
+/// A page of a listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, unset on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Which page of a listing to fetch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageParams {
+    /// Max number of items in the page, the server caps it.
+    pub limit: Option<u32>,
+    /// The `next_cursor` of the previous page, unset for the first page.
+    pub cursor: Option<String>,
+}
+
+impl PageParams {
+    /// Add the parameters to the query string.
+    pub(crate) fn append_to(&self, query: &mut form_urlencoded::Serializer<'_, String>) {
+        if let Some(limit) = self.limit {
+            query.append_pair("limit", &limit.to_string());
+        }
+        if let Some(cursor) = &self.cursor {
+            query.append_pair("cursor", cursor);
+        }
+    }
+}
+
+/// Fetch every page of a listing by following the cursors, at most
+/// `max_pages` of them. `fetch` gets the cursor of the page to fetch.
+///
+/// Items created or removed while the pages are fetched may or may not be
+/// returned, depending on the page they fall in.
+pub async fn list_all<T, F, Fut>(mut fetch: F, max_pages: usize) -> Result<Vec<T>, Error>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Paginated<T>, Error>>,
+{
+    let mut items = vec![];
+    let mut cursor = None;
+    for _ in 0..max_pages {
+        let page = fetch(cursor.take()).await?;
+        items.extend(page.items);
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok(items),
+        }
+    }
+    Err(Error::PageLimitExceeded { max_pages })
+}
+
+/// Stream the items of a listing, fetching the next page once the items of
+/// the previous one are consumed. Dropping the stream stops fetching pages.
+/// The stream ends after the first error.
+pub fn stream<T, F, Fut>(fetch: F) -> impl Stream<Item = Result<T, Error>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Paginated<T>, Error>>,
+{
+    enum Next {
+        Page(Option<String>),
+        Done,
+    }
+
+    stream::unfold((fetch, Next::Page(None)), |(mut fetch, next)| async move {
+        let Next::Page(cursor) = next else {
+            return None;
+        };
+        match fetch(cursor).await {
+            Ok(page) => {
+                let next = page
+                    .next_cursor
+                    .map_or(Next::Done, |cursor| Next::Page(Some(cursor)));
+                let items = page.items.into_iter().map(Ok).collect::<Vec<_>>();
+                Some((stream::iter(items), (fetch, next)))
+            }
+            Err(error) => Some((stream::iter(vec![Err(error)]), (fetch, Next::Done))),
+        }
+    })
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    /// Pages of the numbers below `len`, the cursor is the next number.
+    async fn numbers(cursor: Option<String>, len: u32) -> Result<Paginated<u32>, Error> {
+        let start = cursor.map_or(0, |cursor| cursor.parse().unwrap());
+        let end = (start + 2).min(len);
+        Ok(Paginated {
+            items: (start..end).collect(),
+            next_cursor: (end < len).then(|| end.to_string()),
+        })
+    }
+
+    #[test]
+    fn page_query() {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        PageParams::default().append_to(&mut query);
+        assert_eq!(query.finish(), "");
+
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        PageParams {
+            limit: Some(10),
+            cursor: Some("a b".into()),
+        }
+        .append_to(&mut query);
+        assert_eq!(query.finish(), "limit=10&cursor=a+b");
+    }
+
+    #[tokio::test]
+    async fn follow_cursors() {
+        let items = list_all(|cursor| numbers(cursor, 5), 10).await.unwrap();
+        assert_eq!(items, vec![0, 1, 2, 3, 4]);
+
+        let err = list_all(|cursor| numbers(cursor, 5), 2).await.unwrap_err();
+        assert_eq!(err, Error::PageLimitExceeded { max_pages: 2 });
+
+        let items: Vec<u32> = stream(|cursor| numbers(cursor, 5))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn stop_early() {
+        let mut pages = 0;
+        let items: Vec<_> = stream(|cursor| {
+            pages += 1;
+            numbers(cursor, 100)
+        })
+        .take(3)
+        .collect()
+        .await;
+        assert_eq!(items.len(), 3);
+        assert_eq!(pages, 2);
+    }
+}
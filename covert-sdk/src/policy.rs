@@ -1,10 +1,14 @@
 use std::sync::Arc;
 
-pub use covert_types::methods::system::{
-    CreatePolicyParams, CreatePolicyResponse, ListPolicyResponse, RemovePolicyResponse,
+pub use covert_types::{
+    methods::system::{
+        CreatePolicyParams, CreatePolicyResponse, FormatPolicyParams, FormatPolicyResponse,
+        ListPolicyResponse, ReadPolicyResponse, RemovePolicyResponse,
+    },
+    policy::{PathPolicy, PolicyParseError},
 };
 
-use crate::base::BaseClient;
+use crate::{base::BaseClient, error::Error};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -15,18 +19,23 @@ impl Client {
         Self { client }
     }
 
-    pub async fn create(
-        &self,
-        params: &CreatePolicyParams,
-    ) -> Result<CreatePolicyResponse, String> {
+    pub async fn create(&self, params: &CreatePolicyParams) -> Result<CreatePolicyResponse, Error> {
         self.client.post("/sys/policies".into(), params).await
     }
 
-    pub async fn list(&self) -> Result<ListPolicyResponse, String> {
+    pub async fn format(&self, params: &FormatPolicyParams) -> Result<FormatPolicyResponse, Error> {
+        self.client.put("/sys/policies/format".into(), params).await
+    }
+
+    pub async fn list(&self) -> Result<ListPolicyResponse, Error> {
         self.client.get("/sys/policies".into()).await
     }
 
-    pub async fn remove(&self, name: &str) -> Result<RemovePolicyResponse, String> {
+    pub async fn read(&self, name: &str) -> Result<ReadPolicyResponse, Error> {
+        self.client.get(format!("/sys/policies/{name}")).await
+    }
+
+    pub async fn remove(&self, name: &str) -> Result<RemovePolicyResponse, Error> {
         self.client.delete(format!("/sys/policies/{name}")).await
     }
 }
@@ -3,10 +3,10 @@ use std::{sync::Arc, time::Duration};
 use covert_types::methods::psql::CreateRoleCredsParams;
 pub use covert_types::methods::psql::{
     CreateRoleCredsResponse, CreateRoleParams, CreateRoleResponse, ReadConnectionResponse,
-    SetConnectionParams, SetConnectionResponse,
+    RotateRootResponse, SetConnectionParams, SetConnectionResponse,
 };
 
-use crate::{base::BaseClient, utils::get_mount_path};
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -21,22 +21,29 @@ impl Client {
         &self,
         mount: &str,
         params: &SetConnectionParams,
-    ) -> Result<SetConnectionResponse, String> {
+    ) -> Result<SetConnectionResponse, Error> {
         let path = get_mount_path(mount, "config/connection");
         self.client.post(path, params).await
     }
 
-    pub async fn read_connection(&self, mount: &str) -> Result<ReadConnectionResponse, String> {
+    pub async fn read_connection(&self, mount: &str) -> Result<ReadConnectionResponse, Error> {
         let path = get_mount_path(mount, "config/connection");
         self.client.get(path).await
     }
 
+    /// Replace the password of the user the engine connects with by a
+    /// password only known to the engine.
+    pub async fn rotate_root(&self, mount: &str) -> Result<RotateRootResponse, Error> {
+        let path = get_mount_path(mount, "rotate-root");
+        self.client.put(path, &()).await
+    }
+
     pub async fn create_credentials(
         &self,
         mount: &str,
         name: &str,
         ttl: Option<Duration>,
-    ) -> Result<CreateRoleCredsResponse, String> {
+    ) -> Result<CreateRoleCredsResponse, Error> {
         let path = get_mount_path(mount, &format!("creds/{name}"));
         self.client.put(path, &CreateRoleCredsParams { ttl }).await
     }
@@ -46,7 +53,7 @@ impl Client {
         mount: &str,
         name: &str,
         params: &CreateRoleParams,
-    ) -> Result<CreateRoleResponse, String> {
+    ) -> Result<CreateRoleResponse, Error> {
         let path = get_mount_path(mount, &format!("roles/{name}"));
         self.client.post(path, params).await
     }
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::system::{
+    DeleteRateLimitQuotaResponse, EntityQuotaUsage, ListRateLimitQuotasResponse, QuotaScope,
+    RateLimitQuota, RateLimitQuotaParams, RateLimitQuotaUsage, ReadRateLimitQuotaResponse,
+};
+
+use crate::{base::BaseClient, error::Error};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    /// Create a rate limit quota or replace the configuration of an existing
+    /// one.
+    pub async fn set_rate_limit(
+        &self,
+        name: &str,
+        params: &RateLimitQuotaParams,
+    ) -> Result<RateLimitQuota, Error> {
+        self.client
+            .post(format!("/sys/quotas/rate-limit/{name}"), params)
+            .await
+    }
+
+    pub async fn list_rate_limits(&self) -> Result<ListRateLimitQuotasResponse, Error> {
+        self.client.get("/sys/quotas/rate-limit".into()).await
+    }
+
+    pub async fn read_rate_limit(&self, name: &str) -> Result<ReadRateLimitQuotaResponse, Error> {
+        self.client
+            .get(format!("/sys/quotas/rate-limit/{name}"))
+            .await
+    }
+
+    pub async fn delete_rate_limit(
+        &self,
+        name: &str,
+    ) -> Result<DeleteRateLimitQuotaResponse, Error> {
+        self.client
+            .delete(format!("/sys/quotas/rate-limit/{name}"))
+            .await
+    }
+}
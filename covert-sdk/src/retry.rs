@@ -0,0 +1,313 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+
+use crate::transport::TransportError;
+
+/// Which requests a [`RetryPolicy`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Retry {
+    /// Sent once, e.g. writes.
+    Never,
+    /// Safe to repeat: reads, lists, lookups, the status and requests with
+    /// an idempotency key.
+    Idempotent,
+    /// Lease and token renewals, only retried if the policy opts in.
+    Renewal,
+}
+
+/// How failed requests are sent again. Requests are retried when the
+/// connection fails, on `500`, `502`, `503` and `504`, and on `429` if the
+/// server says when to retry in `Retry-After`.
+///
+/// Only requests that are safe to repeat are retried: reads, lists, lookups,
+/// the status and requests with an idempotency key. Renewals can be opted
+/// into with [`RetryPolicy::retry_renewals`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of times a request is sent, including the first time.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every retry after it.
+    pub base_backoff: Duration,
+    /// Upper bound of the wait between two attempts. A `Retry-After` longer
+    /// than it is not waited for.
+    pub max_backoff: Duration,
+    /// Wait a random time between half and all of the backoff, so clients
+    /// failing at the same time don't retry in lockstep.
+    pub jitter: bool,
+    /// Also retry lease and token renewals. A renewal whose response was
+    /// lost is applied twice, which extends the lease from the time of the
+    /// second renewal.
+    pub retry_renewals: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            retry_renewals: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Send every request once.
+    #[must_use]
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn applies_to(&self, retry: Retry) -> bool {
+        match retry {
+            Retry::Never => false,
+            Retry::Idempotent => true,
+            Retry::Renewal => self.retry_renewals,
+        }
+    }
+
+    /// Time to wait before sending the request again after the outcome of
+    /// `attempt`, or `None` if it is not sent again.
+    pub(crate) fn delay(
+        &self,
+        attempt: u32,
+        outcome: &Result<Response, TransportError>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let resp = match outcome {
+            Ok(resp) => resp,
+            Err(err) => return err.retryable.then(|| self.backoff(attempt)),
+        };
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs)
+                .filter(|retry_after| *retry_after <= self.max_backoff),
+            StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Some(self.backoff(attempt)),
+            _ => None,
+        }
+    }
+
+    /// Exponential backoff before retry number `retry`, starting at 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_backoff
+            .saturating_mul(2_u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        if self.jitter {
+            backoff / 2 + (backoff / 2).mul_f64(random_fraction())
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Random number in `[0, 1)`, good enough to spread out retries.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = (hasher.finish() >> 11) as f64 / (1_u64 << 53) as f64;
+    fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        future::Future,
+        pin::Pin,
+        sync::{Arc, Mutex},
+    };
+
+    use covert_types::methods::system::StatusResponse;
+    use reqwest::Request;
+
+    use crate::{transport::Transport, Client, Error, ErrorCode};
+
+    use super::*;
+
+    /// Status and `Retry-After` of a response, or whether a failed request
+    /// is retryable.
+    type Outcome = Result<(u16, Option<&'static str>), bool>;
+
+    /// Answers the requests with the scripted outcomes and counts them.
+    #[derive(Clone)]
+    struct MockTransport {
+        outcomes: Arc<Mutex<VecDeque<Outcome>>>,
+        requests: Arc<Mutex<u32>>,
+    }
+
+    impl MockTransport {
+        fn new(outcomes: impl IntoIterator<Item = Outcome>) -> Self {
+            Self {
+                outcomes: Arc::new(Mutex::new(outcomes.into_iter().collect())),
+                requests: Arc::default(),
+            }
+        }
+
+        fn requests(&self) -> u32 {
+            *self.requests.lock().unwrap()
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn send(
+            &self,
+            _req: Request,
+        ) -> Pin<Box<dyn Future<Output = Result<Response, TransportError>> + Send + '_>> {
+            *self.requests.lock().unwrap() += 1;
+            let outcome = self.outcomes.lock().unwrap().pop_front().unwrap();
+            Box::pin(async move {
+                let (status, retry_after) = outcome.map_err(|retryable| TransportError {
+                    message: "connection reset".into(),
+                    retryable,
+                })?;
+                let body = if status == 200 {
+                    r#"{"data":{"state":"unsealed"}}"#
+                } else {
+                    r#"{"error":"Something failed","code":"internal"}"#
+                };
+                let mut resp = http::Response::builder().status(status);
+                if let Some(retry_after) = retry_after {
+                    resp = resp.header(RETRY_AFTER, retry_after);
+                }
+                Ok(Response::from(resp.body(body).unwrap()))
+            })
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            base_backoff: Duration::from_millis(1),
+            jitter: false,
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn client(transport: &MockTransport, policy: RetryPolicy) -> Client {
+        Client::builder("http://localhost/v1")
+            .retry_policy(policy)
+            .build_with_transport(Arc::new(transport.clone()))
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy {
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            max_attempts: 10,
+            ..policy()
+        };
+        let delays = (1..=4)
+            .map(|retry| policy.backoff(retry))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [100, 200, 300, 300].map(Duration::from_millis).to_vec()
+        );
+
+        let jittered = RetryPolicy {
+            jitter: true,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = jittered.backoff(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_idempotent_requests() {
+        let transport = MockTransport::new([Err(true), Ok((503, None)), Ok((200, None))]);
+        let status = client(&transport, policy()).status.status().await;
+        assert!(matches!(status, Ok(StatusResponse { .. })));
+        assert_eq!(transport.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn error_has_number_of_attempts() {
+        let transport = MockTransport::new([Ok((500, None)), Ok((502, None)), Ok((500, None))]);
+        let err = client(&transport, policy())
+            .status
+            .status()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::RetriesExhausted { attempts: 3, .. }));
+        assert_eq!(err.code(), Some(ErrorCode::Internal));
+        assert!(err.to_string().contains("after 3 attempts"), "{err}");
+        assert_eq!(transport.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_failures() {
+        // Errors of the request itself and failures that cannot succeed
+        for outcome in [
+            Err(false),
+            Ok((400, None)),
+            Ok((501, None)),
+            Ok((429, None)),
+        ] {
+            let transport = MockTransport::new([outcome]);
+            let err = client(&transport, policy())
+                .status
+                .status()
+                .await
+                .unwrap_err();
+            assert!(!matches!(err, Error::RetriesExhausted { .. }), "{err:?}");
+            assert_eq!(transport.requests(), 1);
+        }
+
+        // The server asks for a longer wait than the policy allows
+        let transport = MockTransport::new([Ok((429, Some("3600")))]);
+        assert!(client(&transport, policy()).status.status().await.is_err());
+        assert_eq!(transport.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn waits_for_retry_after() {
+        let transport = MockTransport::new([Ok((429, Some("0"))), Ok((200, None))]);
+        assert!(client(&transport, policy()).status.status().await.is_ok());
+        assert_eq!(transport.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn mutations_are_sent_once() {
+        let transport = MockTransport::new([Err(true)]);
+        let sdk = client(&transport, policy());
+        assert!(sdk.token.tidy().await.is_err());
+        assert_eq!(transport.requests(), 1);
+
+        // Renewals are only retried when opted in
+        let transport = MockTransport::new([Err(true)]);
+        let sdk = client(&transport, policy());
+        assert!(sdk.lease.renew("lease", None).await.is_err());
+        assert_eq!(transport.requests(), 1);
+
+        let transport = MockTransport::new([Err(true), Ok((503, None)), Ok((500, None))]);
+        let policy = RetryPolicy {
+            retry_renewals: true,
+            ..policy()
+        };
+        let sdk = client(&transport, policy);
+        assert!(sdk.lease.renew("lease", None).await.is_err());
+        assert_eq!(transport.requests(), 3);
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::system::{
+    DeleteStaticSecretResponse, ListStaticSecretsResponse, ReadPreviousStaticSecretResponse,
+    ReadStaticSecretResponse, RotationHandler, SetStaticSecretParams, StaticSecret,
+};
+
+use crate::{base::BaseClient, error::Error};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    /// Create a static secret or change how and when an existing one is
+    /// rotated. A new secret is given its first value right away.
+    pub async fn set(
+        &self,
+        name: &str,
+        params: &SetStaticSecretParams,
+    ) -> Result<StaticSecret, Error> {
+        self.client
+            .post(format!("/sys/static-secrets/{name}"), params)
+            .await
+    }
+
+    pub async fn list(&self) -> Result<ListStaticSecretsResponse, Error> {
+        self.client.get("/sys/static-secrets".into()).await
+    }
+
+    pub async fn read(&self, name: &str) -> Result<ReadStaticSecretResponse, Error> {
+        self.client.get(format!("/sys/static-secrets/{name}")).await
+    }
+
+    /// Read the value the secret had before the last rotation, while its
+    /// grace period lasts.
+    pub async fn read_previous(
+        &self,
+        name: &str,
+    ) -> Result<ReadPreviousStaticSecretResponse, Error> {
+        self.client
+            .get(format!("/sys/static-secrets/{name}/previous"))
+            .await
+    }
+
+    /// Rotate the secret now. The schedule restarts from this rotation.
+    pub async fn rotate(&self, name: &str) -> Result<StaticSecret, Error> {
+        self.client
+            .put(format!("/sys/static-secrets/{name}/rotate"), &())
+            .await
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<DeleteStaticSecretResponse, Error> {
+        self.client
+            .delete(format!("/sys/static-secrets/{name}"))
+            .await
+    }
+}
@@ -1,8 +1,13 @@
 use std::sync::Arc;
 
-pub use covert_types::methods::system::StatusResponse;
+pub use covert_types::{
+    methods::system::{
+        ConfigStateResponse, HaStatus, ReloadConfigResponse, StatusResponse, TamperConfig,
+    },
+    state::StorageState,
+};
 
-use crate::base::BaseClient;
+use crate::{base::BaseClient, error::Error};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -13,7 +18,35 @@ impl Client {
         Self { client }
     }
 
-    pub async fn status(&self) -> Result<StatusResponse, String> {
+    pub async fn status(&self) -> Result<StatusResponse, Error> {
         self.client.get("/sys/status".into()).await
     }
+
+    /// Effective configuration of the server with secrets redacted.
+    pub async fn config_state(&self) -> Result<ConfigStateResponse, Error> {
+        self.client.get("/sys/config/state/sanitized".into()).await
+    }
+
+    /// Reload the TLS certificates and the logging config of the server.
+    pub async fn reload_config(&self) -> Result<ReloadConfigResponse, Error> {
+        self.client.post("/sys/config/reload".into(), &()).await
+    }
+
+    /// Reload only the TLS certificate and key of the server from their
+    /// files.
+    pub async fn reload_certificates(&self) -> Result<ReloadConfigResponse, Error> {
+        self.client
+            .post("/sys/config/reload-cert".into(), &())
+            .await
+    }
+
+    /// When the server seals itself because it looks like it is being
+    /// tampered with.
+    pub async fn tamper_config(&self) -> Result<TamperConfig, Error> {
+        self.client.get("/sys/config/tamper".into()).await
+    }
+
+    pub async fn set_tamper_config(&self, config: &TamperConfig) -> Result<TamperConfig, Error> {
+        self.client.put("/sys/config/tamper".into(), config).await
+    }
 }
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+pub use covert_types::{
+    methods::{
+        system::{
+            CreateTokenParams, LookupTokenResponse, RenewLeaseResponse, RenewTokenSelfParams,
+            RevokeTokenSelfResponse, RevokeTokensByPolicyParams, RevokeTokensByPolicyResponse,
+            TidyTokensResponse, TokenPolicy, TokenRevocationJobState, TokenRevocationJobStatus,
+            TokenTargetParams,
+        },
+        AuthResponse,
+    },
+    policy::PolicySource,
+};
+
+use crate::{base::BaseClient, error::Error};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    /// Issue a token to an entity, e.g. a single use token that only becomes
+    /// valid at a scheduled time. Requires `sudo`.
+    pub async fn create(&self, params: &CreateTokenParams) -> Result<AuthResponse, Error> {
+        self.client.post("/sys/token/create".into(), params).await
+    }
+
+    pub async fn lookup_self(&self) -> Result<LookupTokenResponse, Error> {
+        self.client.get("/sys/token/lookup-self".into()).await
+    }
+
+    /// Lookup another token by the token or its accessor.
+    pub async fn lookup(&self, params: &TokenTargetParams) -> Result<LookupTokenResponse, Error> {
+        self.client.post("/sys/token/lookup".into(), params).await
+    }
+
+    pub async fn renew_self(
+        &self,
+        params: &RenewTokenSelfParams,
+    ) -> Result<RenewLeaseResponse, Error> {
+        self.client
+            .renew("/sys/token/renew-self".into(), params)
+            .await
+    }
+
+    /// Revoke the token of the client together with its child tokens and
+    /// leases.
+    pub async fn revoke_self(&self) -> Result<RevokeTokenSelfResponse, Error> {
+        self.client.put("/sys/token/revoke-self".into(), &()).await
+    }
+
+    /// Revoke another token, given by the token or its accessor, together
+    /// with its child tokens and leases.
+    pub async fn revoke(
+        &self,
+        params: &TokenTargetParams,
+    ) -> Result<RevokeTokenSelfResponse, Error> {
+        self.client.put("/sys/token/revoke".into(), params).await
+    }
+
+    pub async fn revoke_by_policy(
+        &self,
+        params: &RevokeTokensByPolicyParams,
+    ) -> Result<RevokeTokensByPolicyResponse, Error> {
+        self.client
+            .put("/sys/token/revoke-by-policy".into(), params)
+            .await
+    }
+
+    pub async fn revoke_by_policy_status(
+        &self,
+        job_id: &str,
+    ) -> Result<TokenRevocationJobStatus, Error> {
+        self.client
+            .get(format!("/sys/token/revoke-by-policy/{job_id}"))
+            .await
+    }
+
+    pub async fn tidy(&self) -> Result<TidyTokensResponse, Error> {
+        self.client.put("/sys/token/tidy".into(), &()).await
+    }
+}
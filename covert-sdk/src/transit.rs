@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+pub use covert_types::methods::transit::{
+    DecryptParams, DecryptResponse, EncryptParams, EncryptResponse, KeyResponse, UpdateKeyParams,
+};
+
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    pub async fn create_key(&self, mount: &str, name: &str) -> Result<KeyResponse, Error> {
+        let path = get_mount_path(mount, &format!("keys/{name}"));
+        self.client.post(path, &()).await
+    }
+
+    pub async fn read_key(&self, mount: &str, name: &str) -> Result<KeyResponse, Error> {
+        let path = get_mount_path(mount, &format!("keys/{name}"));
+        self.client.get(path).await
+    }
+
+    /// Disables or enables the key. Nothing can be encrypted or decrypted
+    /// with a disabled key.
+    pub async fn update_key(
+        &self,
+        mount: &str,
+        name: &str,
+        params: &UpdateKeyParams,
+    ) -> Result<KeyResponse, Error> {
+        let path = get_mount_path(mount, &format!("keys/{name}"));
+        self.client.put(path, params).await
+    }
+
+    pub async fn delete_key(&self, mount: &str, name: &str) -> Result<(), Error> {
+        let path = get_mount_path(mount, &format!("keys/{name}"));
+        self.client.delete(path).await
+    }
+
+    pub async fn encrypt(
+        &self,
+        mount: &str,
+        name: &str,
+        params: &EncryptParams,
+    ) -> Result<EncryptResponse, Error> {
+        let path = get_mount_path(mount, &format!("encrypt/{name}"));
+        self.client.put(path, params).await
+    }
+
+    pub async fn decrypt(
+        &self,
+        mount: &str,
+        name: &str,
+        params: &DecryptParams,
+    ) -> Result<DecryptResponse, Error> {
+        let path = get_mount_path(mount, &format!("decrypt/{name}"));
+        self.client.put(path, params).await
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fmt::Display, future::Future, pin::Pin, sync::Mutex};
+
+use bytes::Bytes;
+use reqwest::{Request, Response};
+use tower::{Service, ServiceExt};
+
+/// Sends the requests of the client. Implemented by [`reqwest::Client`] and
+/// [`InProcess`], the tests use it to script the responses of the server.
+pub(crate) trait Transport: Send + Sync {
+    fn send(
+        &self,
+        req: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, TransportError>> + Send + '_>>;
+}
+
+/// A request that did not get a response.
+#[derive(Debug, Clone)]
+pub(crate) struct TransportError {
+    pub message: String,
+    /// Whether sending the request again could succeed, e.g. the connection
+    /// failed or was reset.
+    pub retryable: bool,
+}
+
+impl Transport for reqwest::Client {
+    fn send(
+        &self,
+        req: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, TransportError>> + Send + '_>> {
+        Box::pin(async move {
+            self.execute(req).await.map_err(|err| TransportError {
+                retryable: err.is_connect() || err.is_timeout() || err.is_request(),
+                message: format!("{err:#?}"),
+            })
+        })
+    }
+}
+
+/// Sends the requests to a [`tower::Service`] in the same process instead of
+/// over the network, e.g. to test against the server without binding a
+/// port. The service gets the full URL of the request, including the host of
+/// the API URL.
+pub(crate) struct InProcess<S> {
+    // Only cloned for every request, the lock makes the transport `Sync`
+    // for services that are only `Send`
+    service: Mutex<S>,
+}
+
+impl<S> InProcess<S> {
+    pub fn new(service: S) -> Self {
+        Self {
+            service: Mutex::new(service),
+        }
+    }
+}
+
+impl<S> Transport for InProcess<S>
+where
+    S: Service<http::Request<Bytes>, Response = http::Response<Bytes>> + Clone + Send + 'static,
+    S::Error: Display,
+    S::Future: Send,
+{
+    fn send(
+        &self,
+        req: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, TransportError>> + Send + '_>> {
+        let service = self
+            .service
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone();
+        Box::pin(async move {
+            let req = into_http_request(req)?;
+            let resp = service.oneshot(req).await.map_err(|err| TransportError {
+                message: err.to_string(),
+                retryable: false,
+            })?;
+            Ok(Response::from(resp))
+        })
+    }
+}
+
+fn into_http_request(req: Request) -> Result<http::Request<Bytes>, TransportError> {
+    let body = match req.body() {
+        None => Bytes::new(),
+        Some(body) => {
+            body.as_bytes()
+                .map(Bytes::copy_from_slice)
+                .ok_or_else(|| TransportError {
+                    message: "Streamed bodies cannot be sent in process".into(),
+                    retryable: false,
+                })?
+        }
+    };
+    let mut builder = http::Request::builder()
+        .method(req.method().clone())
+        .uri(req.url().as_str())
+        .version(req.version());
+    if let Some(headers) = builder.headers_mut() {
+        headers.extend(req.headers().clone());
+    }
+    builder.body(body).map_err(|err| TransportError {
+        message: format!("{err:#?}"),
+        retryable: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use covert_types::state::StorageState;
+
+    use crate::Client;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn in_process_service() {
+        let service = tower::service_fn(|req: http::Request<Bytes>| async move {
+            assert_eq!(req.method(), http::Method::GET);
+            assert_eq!(req.uri().path(), "/v1/sys/status");
+            assert_eq!(req.headers()["X-Covert-Token"], "foo");
+            Ok::<_, Infallible>(http::Response::new(Bytes::from_static(
+                br#"{"data":{"state":"sealed"}}"#,
+            )))
+        });
+        let sdk = Client::builder("http://covert/v1")
+            .service(service)
+            .build()
+            .unwrap();
+        sdk.set_token(Some("foo".into())).await;
+
+        let status = sdk.status.status().await.unwrap();
+        assert_eq!(status.state, StorageState::Sealed);
+    }
+}
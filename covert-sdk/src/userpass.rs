@@ -2,13 +2,13 @@ use std::sync::Arc;
 
 pub use covert_types::methods::{
     userpass::{
-        CreateUserParams, CreateUserResponse, ListUsersResponse, LoginParams, RemoveUserResponse,
-        UpdateUserPasswordParams, UpdateUserPasswordResponse,
+        CreateUserParams, CreateUserResponse, ListUsersResponse, LoginParams, PasswordHashConfig,
+        RemoveUserResponse, UpdateUserPasswordParams, UpdateUserPasswordResponse,
     },
     AuthResponse,
 };
 
-use crate::{base::BaseClient, utils::get_mount_path};
+use crate::{base::BaseClient, error::Error, utils::get_mount_path};
 
 pub struct Client {
     client: Arc<BaseClient>,
@@ -23,22 +23,22 @@ impl Client {
         &self,
         mount: &str,
         params: &CreateUserParams,
-    ) -> Result<CreateUserResponse, String> {
+    ) -> Result<CreateUserResponse, Error> {
         let path = get_mount_path(mount, "users");
         self.client.post(path, params).await
     }
 
-    pub async fn list(&self, mount: &str) -> Result<ListUsersResponse, String> {
+    pub async fn list(&self, mount: &str) -> Result<ListUsersResponse, Error> {
         let path = get_mount_path(mount, "users");
         self.client.get(path).await
     }
 
-    pub async fn login(&self, mount: &str, params: &LoginParams) -> Result<AuthResponse, String> {
+    pub async fn login(&self, mount: &str, params: &LoginParams) -> Result<AuthResponse, Error> {
         let path = get_mount_path(mount, "login");
-        self.client.put(path, params).await
+        self.client.login(path, params).await
     }
 
-    pub async fn remove(&self, mount: &str, username: &str) -> Result<RemoveUserResponse, String> {
+    pub async fn remove(&self, mount: &str, username: &str) -> Result<RemoveUserResponse, Error> {
         let path = get_mount_path(mount, &format!("users/{username}"));
         self.client.delete(path).await
     }
@@ -48,8 +48,22 @@ impl Client {
         mount: &str,
         username: &str,
         params: &UpdateUserPasswordParams,
-    ) -> Result<UpdateUserPasswordResponse, String> {
+    ) -> Result<UpdateUserPasswordResponse, Error> {
         let path = get_mount_path(mount, &format!("users/{username}/password"));
         self.client.put(path, params).await
     }
+
+    pub async fn read_hash_config(&self, mount: &str) -> Result<PasswordHashConfig, Error> {
+        let path = get_mount_path(mount, "config/hash");
+        self.client.get(path).await
+    }
+
+    pub async fn set_hash_config(
+        &self,
+        mount: &str,
+        config: &PasswordHashConfig,
+    ) -> Result<PasswordHashConfig, Error> {
+        let path = get_mount_path(mount, "config/hash");
+        self.client.put(path, config).await
+    }
 }
@@ -0,0 +1,72 @@
+use std::{sync::Arc, time::Duration};
+
+pub use covert_types::methods::system::{
+    RewrapParams, UnwrapParams, WrapInfo, WrappingLookupParams, WrappingLookupResponse,
+};
+pub use covert_types::token::WrappingToken;
+pub use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{base::BaseClient, error::Error};
+
+pub struct Client {
+    client: Arc<BaseClient>,
+}
+
+impl Client {
+    pub(crate) fn new(client: Arc<BaseClient>) -> Self {
+        Self { client }
+    }
+
+    /// Send a request and wrap its response in a single-use wrapping token
+    /// that is valid for `ttl`.
+    pub async fn wrap<T: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&T>,
+        ttl: Duration,
+    ) -> Result<WrapInfo, Error> {
+        self.client
+            .wrapped(
+                method,
+                format!("/{}", path.trim_start_matches('/')),
+                body,
+                ttl,
+            )
+            .await
+    }
+
+    pub async fn unwrap<T: DeserializeOwned>(&self, token: &WrappingToken) -> Result<T, Error> {
+        self.client
+            .post(
+                "/sys/wrapping/unwrap".into(),
+                &UnwrapParams {
+                    token: token.clone(),
+                },
+            )
+            .await
+    }
+
+    pub async fn lookup(&self, token: &WrappingToken) -> Result<WrappingLookupResponse, Error> {
+        self.client
+            .post(
+                "/sys/wrapping/lookup".into(),
+                &WrappingLookupParams {
+                    token: token.clone(),
+                },
+            )
+            .await
+    }
+
+    pub async fn rewrap(&self, token: &WrappingToken) -> Result<WrapInfo, Error> {
+        self.client
+            .post(
+                "/sys/wrapping/rewrap".into(),
+                &RewrapParams {
+                    token: token.clone(),
+                },
+            )
+            .await
+    }
+}
@@ -0,0 +1,8 @@
+-- Monotonic counter bumped on every successful mutating request. It lives in
+-- the encrypted storage so replicas observe the same index as the primary.
+CREATE TABLE IF NOT EXISTS WRITE_INDEX (
+    "index" INTEGER NOT NULL,
+    lock INTEGER NOT NULL PRIMARY KEY CHECK (lock = 1)
+) STRICT;
+
+INSERT INTO WRITE_INDEX ("index", lock) VALUES (0, 1);
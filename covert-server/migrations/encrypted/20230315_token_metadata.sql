@@ -0,0 +1,2 @@
+-- Alias metadata returned by the auth backend on login, stored as a JSON object.
+ALTER TABLE TOKENS ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,2 @@
+-- Policies granted directly by the auth backend on login, stored as a JSON list.
+ALTER TABLE TOKENS ADD COLUMN policies TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,3 @@
+-- Entity metadata, e.g. set when importing entities from another system,
+-- stored as a JSON object.
+ALTER TABLE ENTITIES ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,12 @@
+-- Responses wrapped in single-use wrapping tokens. The row is kept after the
+-- response is unwrapped, without the response, so a second unwrap attempt can
+-- be detected until the token expires.
+CREATE TABLE IF NOT EXISTS WRAPPING_TOKENS (
+    token TEXT NOT NULL PRIMARY KEY,
+    namespace_id TEXT NOT NULL REFERENCES NAMESPACES(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    creation_path TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    response TEXT,
+    unwrapped_at TEXT
+) STRICT;
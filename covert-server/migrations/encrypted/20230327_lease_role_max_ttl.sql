@@ -0,0 +1,3 @@
+-- Max TTL in milliseconds of the role a lease was issued for. Renewals can
+-- not extend the lease past it.
+ALTER TABLE LEASES ADD COLUMN role_max_ttl INTEGER;
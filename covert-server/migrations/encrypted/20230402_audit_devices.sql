@@ -0,0 +1,8 @@
+-- Enabled audit devices. Devices are global and only managed from the root
+-- namespace.
+CREATE TABLE IF NOT EXISTS AUDIT_DEVICES (
+    path TEXT NOT NULL PRIMARY KEY,
+    -- JSON encoded device type and options
+    config TEXT NOT NULL,
+    created_at TEXT NOT NULL
+) STRICT;
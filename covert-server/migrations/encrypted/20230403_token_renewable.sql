@@ -0,0 +1,2 @@
+-- Tokens issued before the flag existed stay renewable.
+ALTER TABLE TOKENS ADD COLUMN renewable INTEGER NOT NULL DEFAULT 1;
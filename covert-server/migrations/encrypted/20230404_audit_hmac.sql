@@ -0,0 +1,7 @@
+-- Hex encoded key of the HMAC used to hash sensitive values written by the
+-- device. Devices enabled before this migration get a random salt.
+ALTER TABLE AUDIT_DEVICES ADD COLUMN salt TEXT;
+UPDATE AUDIT_DEVICES SET salt = lower(hex(randomblob(32))) WHERE salt IS NULL;
+
+-- JSON encoded list of the top level response fields written in plaintext.
+ALTER TABLE AUDIT_DEVICES ADD COLUMN hmac_exempt_response_fields TEXT NOT NULL DEFAULT '[]';
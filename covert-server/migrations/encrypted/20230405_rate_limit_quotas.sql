@@ -0,0 +1,11 @@
+-- Rate limit quotas. Quotas are global and only managed from the root
+-- namespace. The state of the token buckets is only kept in memory.
+CREATE TABLE IF NOT EXISTS RATE_LIMIT_QUOTAS (
+    name TEXT NOT NULL PRIMARY KEY,
+    -- JSON encoded scope of the quota
+    scope TEXT NOT NULL,
+    rate REAL NOT NULL,
+    burst INTEGER NOT NULL,
+    exempt_root INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+) STRICT;
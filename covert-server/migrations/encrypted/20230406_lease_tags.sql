@@ -0,0 +1,21 @@
+-- Tags attached to a lease when it is issued, stored as a JSON object.
+ALTER TABLE LEASES ADD COLUMN tags TEXT NOT NULL DEFAULT '{}';
+
+-- Index of the lease tags to look up leases by tag without scanning all the
+-- leases in the namespace.
+CREATE TABLE IF NOT EXISTS LEASE_TAGS (
+    lease_id TEXT NOT NULL,
+    namespace_id TEXT NOT NULL,
+    "key" TEXT NOT NULL,
+    "value" TEXT NOT NULL,
+    PRIMARY KEY(lease_id, "key")
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS LEASE_TAGS_BY_TAG ON LEASE_TAGS(namespace_id, "key", "value");
+
+-- Leases are removed from a number of places, keep the index in sync with
+-- all of them.
+CREATE TRIGGER IF NOT EXISTS DELETE_LEASE_TAGS AFTER DELETE ON LEASES
+BEGIN
+    DELETE FROM LEASE_TAGS WHERE lease_id = OLD.id AND namespace_id = OLD.namespace_id;
+END;
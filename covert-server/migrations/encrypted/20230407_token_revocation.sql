@@ -0,0 +1,8 @@
+-- Token that was used to issue the token, revoking it revokes its children.
+ALTER TABLE TOKENS ADD COLUMN parent TEXT;
+-- Set for the whole tree of tokens before any of them is removed, so an
+-- interrupted revocation can be completed later and the tokens can no longer
+-- be used in the meantime.
+ALTER TABLE TOKENS ADD COLUMN revoking INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS TOKENS_BY_PARENT ON TOKENS(parent);
@@ -0,0 +1,17 @@
+-- Logins through the auth mount have to be completed with a second factor
+ALTER TABLE MOUNTS ADD COLUMN require_mfa INTEGER NOT NULL DEFAULT 0;
+
+-- TOTP keys of the entities, used as the second factor whatever auth method
+-- the entity logged in with
+CREATE TABLE IF NOT EXISTS ENTITY_TOTP_KEYS (
+    namespace_id TEXT NOT NULL,
+    entity_name TEXT NOT NULL,
+    secret BLOB NOT NULL,
+    -- Time step of the last accepted code, codes can only be used once
+    last_used_step INTEGER,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY(namespace_id, entity_name),
+    FOREIGN KEY(namespace_id, entity_name)
+        REFERENCES ENTITIES(namespace_id, "name")
+        ON DELETE CASCADE ON UPDATE CASCADE
+) STRICT;
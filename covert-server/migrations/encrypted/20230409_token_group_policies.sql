@@ -0,0 +1,3 @@
+-- Policies mapped from the groups of the user by the auth backend on login,
+-- stored as a JSON list.
+ALTER TABLE TOKENS ADD COLUMN group_policies TEXT NOT NULL DEFAULT '[]';
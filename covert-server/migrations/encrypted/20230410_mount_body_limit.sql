@@ -0,0 +1,3 @@
+-- Limit of the request body size of the mount, overrides the limit of the
+-- server when set
+ALTER TABLE MOUNTS ADD COLUMN max_request_body_size INTEGER;
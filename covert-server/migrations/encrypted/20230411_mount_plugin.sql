@@ -0,0 +1,2 @@
+-- Name of the plugin serving the mount, only set for `plugin` mounts
+ALTER TABLE MOUNTS ADD COLUMN plugin TEXT;
@@ -0,0 +1,2 @@
+-- Who sees the mount when listing the mounts, see `ListingVisibility`
+ALTER TABLE MOUNTS ADD COLUMN listing_visibility TEXT NOT NULL DEFAULT 'default';
@@ -0,0 +1,4 @@
+-- Tokens are only valid from `not_before` and for `num_uses` requests, both
+-- unbounded when NULL.
+ALTER TABLE TOKENS ADD COLUMN not_before TEXT;
+ALTER TABLE TOKENS ADD COLUMN num_uses INTEGER;
@@ -0,0 +1,17 @@
+-- Mount paths are stored with a trailing slash. A mount created without it
+-- keeps its path if a mount with the slash exists in the same namespace.
+UPDATE LEASES SET issued_mount_path = issued_mount_path || '/'
+WHERE substr(issued_mount_path, -1) <> '/' AND EXISTS (
+    SELECT 1 FROM MOUNTS M
+    WHERE M.namespace_id = LEASES.namespace_id AND M.path = LEASES.issued_mount_path
+        AND NOT EXISTS (
+            SELECT 1 FROM MOUNTS N
+            WHERE N.namespace_id = M.namespace_id AND N.path = M.path || '/'
+        )
+);
+
+UPDATE MOUNTS SET path = path || '/'
+WHERE substr(path, -1) <> '/' AND NOT EXISTS (
+    SELECT 1 FROM MOUNTS N
+    WHERE N.namespace_id = MOUNTS.namespace_id AND N.path = MOUNTS.path || '/'
+);
@@ -0,0 +1,3 @@
+-- Max number of requests the mount handles at the same time, only limited
+-- by the server when unset
+ALTER TABLE MOUNTS ADD COLUMN max_concurrent_requests INTEGER;
@@ -0,0 +1,5 @@
+-- Accessors identify a token to look it up or revoke it without knowing the
+-- token itself.
+ALTER TABLE TOKENS ADD COLUMN accessor TEXT NOT NULL DEFAULT '';
+UPDATE TOKENS SET accessor = lower(hex(randomblob(16)));
+CREATE UNIQUE INDEX tokens_accessor ON TOKENS (accessor);
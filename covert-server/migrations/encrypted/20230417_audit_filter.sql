@@ -0,0 +1,2 @@
+-- JSON encoded list of the rules picking the requests written by the device.
+ALTER TABLE AUDIT_DEVICES ADD COLUMN filter TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,18 @@
+-- Secrets with a static value that the server replaces on a schedule. The
+-- previous value is kept to be read during the grace period after a rotation.
+CREATE TABLE IF NOT EXISTS STATIC_SECRETS (
+    namespace_id TEXT NOT NULL REFERENCES NAMESPACES(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    name TEXT NOT NULL,
+    -- JSON encoded rotation handler
+    handler TEXT NOT NULL,
+    interval_secs INTEGER NOT NULL,
+    grace_secs INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    current_value TEXT NOT NULL,
+    previous_value TEXT,
+    rotated_at TEXT NOT NULL,
+    next_rotation_at TEXT NOT NULL,
+    PRIMARY KEY (namespace_id, name)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS STATIC_SECRETS_NEXT_ROTATION ON STATIC_SECRETS (next_rotation_at);
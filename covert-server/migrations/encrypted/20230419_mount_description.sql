@@ -0,0 +1,2 @@
+-- What the mount is used for, see `MountConfig::description`
+ALTER TABLE MOUNTS ADD COLUMN description TEXT;
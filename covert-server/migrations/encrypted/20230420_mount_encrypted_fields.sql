@@ -0,0 +1,3 @@
+-- JSON object of the fields encrypted with transit keys, see
+-- `MountConfig::encrypted_fields`
+ALTER TABLE MOUNTS ADD COLUMN encrypted_fields TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS TAMPER_CONFIG (
+    lock INTEGER PRIMARY KEY DEFAULT 1,
+
+    enabled INTEGER NOT NULL,
+    decryption_failures INTEGER NOT NULL,
+    malformed_tokens INTEGER NOT NULL,
+    window_secs INTEGER NOT NULL,
+
+    -- Used to ensure that maximum one config is ever inserted
+    CONSTRAINT CONFIG_LOCK CHECK (lock=1)
+) STRICT;
@@ -0,0 +1,10 @@
+-- Master key wrapped by the seal of the server config, only stored when the
+-- server is not unsealed with key shares
+CREATE TABLE IF NOT EXISTS WRAPPED_MASTER_KEY (
+    lock INTEGER PRIMARY KEY DEFAULT 1,
+
+    "key" BLOB NOT NULL,
+
+    -- Used to ensure that maximum one key is ever inserted
+    CONSTRAINT KEY_LOCK CHECK (lock=1)
+) STRICT;
@@ -0,0 +1,13 @@
+-- Lock held by the active node of an HA cluster. Kept in the seal storage so
+-- the nodes can elect the active node and find it while they are sealed.
+CREATE TABLE IF NOT EXISTS HA_LOCK (
+    lock INTEGER PRIMARY KEY DEFAULT 1,
+
+    node_id TEXT NOT NULL,
+    api_address TEXT NOT NULL,
+    -- Unix timestamp in milliseconds
+    expires_at INTEGER NOT NULL,
+
+    -- Used to ensure that maximum one lock is ever inserted
+    CONSTRAINT HA_LOCK_LOCK CHECK (lock=1)
+) STRICT;
@@ -0,0 +1,82 @@
+use std::{io, os::unix::fs::PermissionsExt};
+
+use covert_types::methods::system::{AuditFormat, FileAuditConfig};
+use futures::future::BoxFuture;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+use crate::{
+    error::{Error, ErrorType},
+    FileMode,
+};
+
+use super::{AuditDevice, AuditEntry};
+
+/// Permissions of audit files created without an explicit mode.
+const DEFAULT_MODE: u32 = 0o600;
+
+/// Appends the audit entries to a local file.
+pub struct FileDevice {
+    file: Mutex<File>,
+    format: AuditFormat,
+}
+
+impl FileDevice {
+    pub async fn open(config: &FileAuditConfig) -> Result<Self, Error> {
+        let mode = config
+            .mode
+            .clone()
+            .map(FileMode::try_from)
+            .transpose()
+            .map_err(ErrorType::BadRequest)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(mode.as_ref().map_or(DEFAULT_MODE, |mode| mode.0))
+            .open(&config.file_path)
+            .await
+            .map_err(|err| {
+                ErrorType::BadRequest(format!(
+                    "Unable to open audit file `{}`: {err}",
+                    config.file_path
+                ))
+            })?;
+        // The mode given when opening only applies to new files
+        if let Some(mode) = mode {
+            file.set_permissions(std::fs::Permissions::from_mode(mode.0))
+                .await
+                .map_err(|err| {
+                    ErrorType::BadRequest(format!(
+                        "Unable to set permissions of audit file `{}`: {err}",
+                        config.file_path
+                    ))
+                })?;
+        }
+
+        Ok(Self {
+            file: Mutex::new(file),
+            format: config.format,
+        })
+    }
+}
+
+impl AuditDevice for FileDevice {
+    fn write<'a>(&'a self, entry: &'a AuditEntry) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let mut line = entry.to_vec(self.format)?;
+            line.push(b'\n');
+
+            let mut file = self.file.lock().await;
+            file.write_all(&line).await?;
+            file.flush().await
+        })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async move { self.file.lock().await.sync_all().await })
+    }
+}
@@ -0,0 +1,137 @@
+use covert_types::{error::ErrorCode, methods::system::AuditFilterRule};
+
+use super::{AuditEvent, AuditPolicyResult};
+
+/// Picks the events written by a device. Only looks at fields of the event
+/// that are available before the entry is built, so events that are not
+/// written are never hashed.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    rules: Vec<AuditFilterRule>,
+}
+
+impl AuditFilter {
+    pub fn new(rules: Vec<AuditFilterRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Whether the event should be written by the device.
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        if self.rules.is_empty() || is_security_relevant(event) {
+            return true;
+        }
+        let request = &event.request;
+        let Some(rule) = self.rules.iter().find(|rule| {
+            request.path.starts_with(&rule.path_prefix)
+                && (rule.operations.is_empty() || rule.operations.contains(&request.operation))
+        }) else {
+            return true;
+        };
+        // Sampled by request id so the request and response entries of a
+        // request are either both written or both dropped
+        match rule.sample_every {
+            0 => false,
+            1 => true,
+            n => request.id.as_u128() % u128::from(n) == 0,
+        }
+    }
+}
+
+/// Events that are written whatever the rules say: requests to paths that
+/// require `sudo`, requests with a token that is not authorized by the route
+/// alone and could be denied later on, and denials.
+fn is_security_relevant(event: &AuditEvent) -> bool {
+    let may_be_denied =
+        event.token.is_some() && event.policy_result == AuditPolicyResult::Unauthenticated;
+    // Unauthorized requests fail with the same code as denied requests
+    let denied = event
+        .error
+        .as_ref()
+        .is_some_and(|error| error.code == ErrorCode::PermissionDenied);
+    event.policy_result == AuditPolicyResult::Sudo || may_be_denied || denied
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Utc;
+    use covert_types::request::Operation;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::audit::{AuditEntryType, AuditError, AuditRequest};
+
+    fn event(operation: Operation, path: &str, id: u128) -> AuditEvent {
+        AuditEvent {
+            entry_type: AuditEntryType::Request,
+            time: Utc::now(),
+            token: Some("s.foo".to_string()),
+            token_header: None,
+            entity: Some("foo".to_string()),
+            metadata: BTreeMap::new(),
+            policies: vec!["kv".to_string()],
+            policy_result: AuditPolicyResult::Authenticated,
+            request: AuditRequest {
+                id: Uuid::from_u128(id),
+                operation,
+                namespace: "root".to_string(),
+                path: path.to_string(),
+                remote_address: None,
+                peer_credentials: None,
+                data: None,
+            },
+            response: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn samples_reads_and_keeps_security_relevant_events() {
+        let filter = AuditFilter::new(vec![
+            AuditFilterRule {
+                path_prefix: "kv/".to_string(),
+                operations: vec![Operation::Read, Operation::List],
+                sample_every: 4,
+            },
+            AuditFilterRule {
+                path_prefix: "sys/health".to_string(),
+                operations: vec![],
+                sample_every: 0,
+            },
+        ]);
+
+        // One in four reads, the same ones for the request and the response
+        let written = (0..100)
+            .filter(|id| filter.matches(&event(Operation::Read, "kv/data/foo", *id)))
+            .count();
+        assert_eq!(written, 25);
+        let mut response = event(Operation::Read, "kv/data/foo", 8);
+        response.entry_type = AuditEntryType::Response;
+        assert!(filter.matches(&response));
+
+        // Writes and other paths match no rule
+        assert!(filter.matches(&event(Operation::Update, "kv/data/foo", 1)));
+        assert!(filter.matches(&event(Operation::Read, "pki/cert/foo", 1)));
+        assert!(!filter.matches(&event(Operation::Read, "sys/health", 1)));
+
+        // Denials and sudo requests are always written
+        let mut denied = event(Operation::Read, "sys/health", 1);
+        denied.entry_type = AuditEntryType::Response;
+        denied.error = Some(AuditError {
+            code: ErrorCode::PermissionDenied,
+            message: "permission denied".to_string(),
+        });
+        assert!(filter.matches(&denied));
+        let mut unauthorized = event(Operation::Read, "kv/data/foo", 1);
+        unauthorized.policy_result = AuditPolicyResult::Unauthenticated;
+        assert!(filter.matches(&unauthorized));
+        let mut sudo = event(Operation::Read, "sys/health", 1);
+        sudo.policy_result = AuditPolicyResult::Sudo;
+        assert!(filter.matches(&sudo));
+
+        // Without a token the rules apply
+        unauthorized.token = None;
+        assert!(!filter.matches(&unauthorized));
+    }
+}
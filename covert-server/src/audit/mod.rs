@@ -0,0 +1,463 @@
+mod file;
+mod filter;
+mod socket;
+mod tail;
+
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use covert_types::{
+    auth::AuthPolicy,
+    error::ErrorCode,
+    methods::system::{AuditDeviceConfig, AuditDeviceError, AuditFilterRule, AuditFormat},
+    request::{Operation, PeerCredentials, TokenHeader},
+};
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use tokio::sync::Mutex;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorType};
+
+pub use file::FileDevice;
+pub use filter::AuditFilter;
+pub use socket::SocketDevice;
+pub use tail::AuditTail;
+
+/// Length in bytes of the salts generated for new devices.
+const SALT_LEN: usize = 32;
+
+/// Destination of audit entries.
+pub trait AuditDevice: Send + Sync {
+    /// Write the entry. The entry must be persisted by the device before the
+    /// returned future resolves.
+    fn write<'a>(&'a self, entry: &'a AuditEntry) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Flush any buffered entries to the destination. Called on shutdown.
+    fn flush(&self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Open the device described by the config.
+pub async fn open(config: &AuditDeviceConfig) -> Result<Arc<dyn AuditDevice>, Error> {
+    match config {
+        AuditDeviceConfig::File(config) => Ok(Arc::new(FileDevice::open(config).await?)),
+        AuditDeviceConfig::Socket(config) => Ok(Arc::new(SocketDevice::open(config).await?)),
+    }
+}
+
+/// Random salt for a new device.
+pub fn new_salt() -> Vec<u8> {
+    let mut salt = vec![0; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// An enabled device and the options used to turn events into the entries
+/// written by it.
+pub struct EnabledDevice {
+    device: Arc<dyn AuditDevice>,
+    salt: Vec<u8>,
+    hmac_exempt_response_fields: Vec<String>,
+    filter: AuditFilter,
+    tail: AuditTail,
+    entries_written: AtomicU64,
+    last_error: Mutex<Option<AuditDeviceError>>,
+}
+
+impl EnabledDevice {
+    pub fn new(
+        device: Arc<dyn AuditDevice>,
+        salt: Vec<u8>,
+        hmac_exempt_response_fields: Vec<String>,
+    ) -> Self {
+        Self {
+            device,
+            salt,
+            hmac_exempt_response_fields,
+            filter: AuditFilter::default(),
+            tail: AuditTail::default(),
+            entries_written: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    #[must_use]
+    pub fn with_filter(mut self, rules: Vec<AuditFilterRule>) -> Self {
+        self.filter = AuditFilter::new(rules);
+        self
+    }
+
+    /// Number of entries written since the device was opened.
+    pub fn entries_written(&self) -> u64 {
+        self.entries_written.load(Ordering::Relaxed)
+    }
+
+    /// The recent entries written by the device.
+    pub fn tail(&self) -> &AuditTail {
+        &self.tail
+    }
+
+    /// The last failed write of the device.
+    pub async fn last_error(&self) -> Option<AuditDeviceError> {
+        self.last_error.lock().await.clone()
+    }
+
+    /// HMAC-SHA256 of the input keyed by the salt of the device.
+    pub fn hash(&self, input: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.salt).expect("HMAC can take a key of any size");
+        mac.update(input.as_bytes());
+        format!("hmac-sha256:{}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Replace every string in the value with its hash. Only the structure and
+    /// non-string values are written in plaintext.
+    fn hash_value(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => *s = self.hash(s),
+            Value::Array(values) => values.iter_mut().for_each(|v| self.hash_value(v)),
+            Value::Object(map) => map.values_mut().for_each(|v| self.hash_value(v)),
+            Value::Null | Value::Bool(_) | Value::Number(_) => (),
+        }
+    }
+
+    fn entry(&self, event: &AuditEvent) -> AuditEntry {
+        let mut request = event.request.clone();
+        if let Some(data) = request.data.as_mut() {
+            self.hash_value(data);
+        }
+        let response = event.response.as_ref().map(|response| {
+            let mut response = response.clone();
+            match response.data.as_mut() {
+                Some(Value::Object(map)) => {
+                    for (field, value) in map.iter_mut() {
+                        if !self.hmac_exempt_response_fields.contains(field) {
+                            self.hash_value(value);
+                        }
+                    }
+                }
+                Some(data) => self.hash_value(data),
+                None => (),
+            }
+            response
+        });
+
+        AuditEntry {
+            entry_type: event.entry_type,
+            time: event.time,
+            auth: AuditAuth {
+                client_token: event.token.as_deref().map(|token| self.hash(token)),
+                token_header: event.token_header,
+                entity: event.entity.clone(),
+                metadata: event.metadata.clone(),
+                policies: event.policies.clone(),
+                policy_result: event.policy_result,
+            },
+            request,
+            response,
+            error: event.error.clone(),
+        }
+    }
+}
+
+/// The enabled audit devices, keyed by the path they were enabled at.
+#[derive(Default)]
+pub struct AuditBroker {
+    devices: DashMap<String, Arc<EnabledDevice>>,
+}
+
+impl AuditBroker {
+    pub fn enable(&self, path: String, device: EnabledDevice) {
+        self.devices.insert(path, Arc::new(device));
+    }
+
+    pub fn disable(&self, path: &str) -> bool {
+        self.devices.remove(path).is_some()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.devices.contains_key(path)
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.devices
+            .iter()
+            .map(|device| device.key().clone())
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.devices.clear();
+    }
+
+    pub fn get(&self, path: &str) -> Option<Arc<EnabledDevice>> {
+        self.devices
+            .get(path)
+            .map(|device| Arc::clone(device.value()))
+    }
+
+    /// Hash the input like the device at `path` hashes sensitive values.
+    pub fn hash(&self, path: &str, input: &str) -> Option<String> {
+        self.devices.get(path).map(|device| device.hash(input))
+    }
+
+    /// Flush every enabled device. Failures are logged, the remaining devices
+    /// are still flushed.
+    pub async fn flush(&self) {
+        for (path, device) in self.devices().0 {
+            if let Err(error) = device.device.flush().await {
+                error!(?error, path, "Failed to flush audit device");
+            }
+        }
+    }
+
+    /// Snapshot of the enabled devices. The request and response entries of a
+    /// request are written to the same snapshot so enabling or disabling a
+    /// device never splits a pair.
+    pub fn devices(&self) -> AuditDevices {
+        AuditDevices(
+            self.devices
+                .iter()
+                .map(|device| (device.key().clone(), Arc::clone(device.value())))
+                .collect(),
+        )
+    }
+}
+
+pub struct AuditDevices(Vec<(String, Arc<EnabledDevice>)>);
+
+impl AuditDevices {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Write the event to every device whose filter matches it. Succeeds as
+    /// long as at least one device persisted its entry or no device had to.
+    pub async fn log(&self, event: &AuditEvent) -> Result<(), Error> {
+        let mut written = false;
+        let mut filtered = true;
+        for (path, device) in &self.0 {
+            // Checked first as building the entry hashes every value
+            if !device.filter.matches(event) {
+                continue;
+            }
+            filtered = false;
+            let entry = device.entry(event);
+            let result = device.device.write(&entry).await;
+            match result {
+                Ok(()) => {
+                    written = true;
+                    device.entries_written.fetch_add(1, Ordering::Relaxed);
+                    device.tail.push(entry);
+                }
+                Err(error) => {
+                    error!(?error, path, "Failed to write audit entry");
+                    *device.last_error.lock().await = Some(AuditDeviceError {
+                        message: error.to_string(),
+                        time: Utc::now(),
+                    });
+                }
+            }
+        }
+        if written || filtered {
+            Ok(())
+        } else {
+            Err(ErrorType::AuditFailed.into())
+        }
+    }
+}
+
+/// Something that happened that should be audited. Sensitive values are kept
+/// in plaintext until the event is turned into an [`AuditEntry`] for a device.
+pub struct AuditEvent {
+    pub entry_type: AuditEntryType,
+    pub time: DateTime<Utc>,
+    pub token: Option<String>,
+    pub token_header: Option<TokenHeader>,
+    pub entity: Option<String>,
+    /// Alias metadata the auth backend attached to the token.
+    pub metadata: BTreeMap<String, String>,
+    pub policies: Vec<String>,
+    pub policy_result: AuditPolicyResult,
+    pub request: AuditRequest,
+    pub response: Option<AuditResponse>,
+    pub error: Option<AuditError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntryType {
+    Request,
+    Response,
+}
+
+/// Entry written by a device.
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    #[serde(rename = "type")]
+    pub entry_type: AuditEntryType,
+    pub time: DateTime<Utc>,
+    pub auth: AuditAuth,
+    pub request: AuditRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<AuditResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AuditError>,
+}
+
+impl AuditEntry {
+    /// Serialize the entry in the format, without a trailing newline.
+    pub fn to_vec(&self, format: AuditFormat) -> serde_json::Result<Vec<u8>> {
+        match format {
+            AuditFormat::Jsonl => serde_json::to_vec(self),
+            AuditFormat::Json => serde_json::to_vec_pretty(self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditAuth {
+    /// HMAC of the client token.
+    pub client_token: Option<String>,
+    /// Header the client token was sent in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_header: Option<TokenHeader>,
+    pub entity: Option<String>,
+    /// Alias metadata of the token, e.g. the username it was issued to.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Names of the policies evaluated for the request.
+    pub policies: Vec<String>,
+    pub policy_result: AuditPolicyResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditPolicyResult {
+    Unauthenticated,
+    Authenticated,
+    Sudo,
+}
+
+impl From<&AuthPolicy> for AuditPolicyResult {
+    fn from(policy: &AuthPolicy) -> Self {
+        match policy {
+            AuthPolicy::Unauthenticated => Self::Unauthenticated,
+            AuthPolicy::Authenticated => Self::Authenticated,
+            AuthPolicy::Sudo => Self::Sudo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRequest {
+    pub id: Uuid,
+    pub operation: Operation,
+    pub namespace: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_credentials: Option<PeerCredentials>,
+    /// JSON body of the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditResponse {
+    pub mount_path: String,
+    /// JSON body of the response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct NoopDevice;
+
+    impl AuditDevice for NoopDevice {
+        fn write<'a>(&'a self, _entry: &'a AuditEntry) -> BoxFuture<'a, io::Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn hashes_sensitive_values() {
+        let device = EnabledDevice::new(
+            Arc::new(NoopDevice),
+            b"salt".to_vec(),
+            vec!["lease_id".to_string()],
+        );
+        let other = EnabledDevice::new(Arc::new(NoopDevice), b"pepper".to_vec(), vec![]);
+        let token = "s.Zk1Vq8lWm0Tn3PzYb6Rx2AcD";
+
+        let event = AuditEvent {
+            entry_type: AuditEntryType::Response,
+            time: Utc::now(),
+            token: Some(token.to_string()),
+            token_header: Some(TokenHeader::Authorization),
+            entity: Some("foo".to_string()),
+            metadata: BTreeMap::from([("username".to_string(), "foo".to_string())]),
+            policies: vec![],
+            policy_result: AuditPolicyResult::Authenticated,
+            request: AuditRequest {
+                id: Uuid::default(),
+                operation: Operation::Create,
+                namespace: "root".to_string(),
+                path: "auth/userpass/login".to_string(),
+                remote_address: None,
+                peer_credentials: None,
+                data: Some(json!({ "username": "foo", "password": "bar", "renewable": true })),
+            },
+            response: Some(AuditResponse {
+                mount_path: "auth/userpass/".to_string(),
+                data: Some(json!({ "token": token, "lease_id": "abc", "ttl": "30m" })),
+            }),
+            error: None,
+        };
+        let entry = device.entry(&event);
+
+        // Same input hashes the same for a device, but differs between devices
+        let hashed_token = entry.auth.client_token.unwrap();
+        assert_eq!(hashed_token, device.hash(token));
+        // Alias metadata is chosen by the auth backend and written as is
+        assert_eq!(entry.auth.metadata["username"], "foo");
+        assert_ne!(hashed_token, other.hash(token));
+        assert!(hashed_token.starts_with("hmac-sha256:"));
+        assert_eq!(entry.auth.token_header, Some(TokenHeader::Authorization));
+
+        let request = entry.request.data.unwrap();
+        assert_eq!(request["password"], device.hash("bar"));
+        assert_eq!(request["renewable"], true);
+        let response = entry.response.unwrap().data.unwrap();
+        assert_eq!(response["token"], hashed_token);
+        assert_eq!(response["ttl"], device.hash("30m"));
+        assert_eq!(response["lease_id"], "abc");
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use covert_types::methods::system::{AuditFormat, SocketAuditConfig, SocketType};
+use futures::future::BoxFuture;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{lookup_host, TcpStream, UdpSocket, UnixDatagram},
+    sync::Mutex,
+    time::timeout,
+};
+
+use crate::error::{Error, ErrorType};
+
+use super::{AuditDevice, AuditEntry};
+
+/// Write timeout of devices created without an explicit timeout.
+const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay before the first reconnect after a TCP connection failed. It is
+/// doubled after every failed attempt.
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Sends the audit entries to a TCP, UDP or unix datagram socket.
+pub struct SocketDevice {
+    socket: Mutex<Socket>,
+    format: AuditFormat,
+    write_timeout: Duration,
+}
+
+enum Socket {
+    Tcp(TcpConnection),
+    Udp(UdpSocket),
+    Unixgram(UnixDatagram),
+}
+
+struct TcpConnection {
+    addr: SocketAddr,
+    stream: Option<TcpStream>,
+    backoff: Duration,
+    next_attempt: Instant,
+}
+
+impl TcpConnection {
+    /// The connected stream. Reconnects once the backoff after the last
+    /// failure has elapsed.
+    async fn stream(&mut self, connect_timeout: Duration) -> io::Result<&mut TcpStream> {
+        if self.stream.is_none() {
+            if Instant::now() < self.next_attempt {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    format!("waiting to reconnect to `{}`", self.addr),
+                ));
+            }
+            match with_timeout(connect_timeout, TcpStream::connect(self.addr)).await {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    self.backoff = MIN_RECONNECT_BACKOFF;
+                }
+                Err(err) => {
+                    self.failed();
+                    return Err(err);
+                }
+            }
+        }
+        self.stream
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+    }
+
+    fn failed(&mut self) {
+        self.stream = None;
+        self.next_attempt = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_RECONNECT_BACKOFF);
+    }
+}
+
+impl SocketDevice {
+    pub async fn open(config: &SocketAuditConfig) -> Result<Self, Error> {
+        let write_timeout = config.write_timeout.unwrap_or(DEFAULT_WRITE_TIMEOUT);
+        if write_timeout.is_zero() {
+            return Err(ErrorType::BadRequest("Write timeout must be positive".into()).into());
+        }
+        let unreachable = |err: io::Error| {
+            ErrorType::BadRequest(format!(
+                "Unable to connect to audit socket `{}`: {err}",
+                config.address
+            ))
+        };
+
+        let socket = match config.socket_type {
+            SocketType::Tcp => {
+                let addr = resolve(&config.address).await?;
+                let stream = with_timeout(write_timeout, TcpStream::connect(addr))
+                    .await
+                    .map_err(unreachable)?;
+                Socket::Tcp(TcpConnection {
+                    addr,
+                    stream: Some(stream),
+                    backoff: MIN_RECONNECT_BACKOFF,
+                    next_attempt: Instant::now(),
+                })
+            }
+            SocketType::Udp => {
+                let addr = resolve(&config.address).await?;
+                let local: SocketAddr = if addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0; 16], 0).into()
+                };
+                let socket = UdpSocket::bind(local).await.map_err(unreachable)?;
+                socket.connect(addr).await.map_err(unreachable)?;
+                Socket::Udp(socket)
+            }
+            SocketType::Unixgram => {
+                let socket = UnixDatagram::unbound().map_err(unreachable)?;
+                socket.connect(&config.address).map_err(unreachable)?;
+                Socket::Unixgram(socket)
+            }
+        };
+
+        Ok(Self {
+            socket: Mutex::new(socket),
+            format: config.format,
+            write_timeout,
+        })
+    }
+}
+
+async fn resolve(address: &str) -> Result<SocketAddr, Error> {
+    lookup_host(address)
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| {
+            ErrorType::BadRequest(format!("Unable to resolve audit socket `{address}`")).into()
+        })
+}
+
+async fn with_timeout<T>(
+    duration: Duration,
+    fut: impl std::future::Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    timeout(duration, fut)
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+impl AuditDevice for SocketDevice {
+    fn write<'a>(&'a self, entry: &'a AuditEntry) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let mut data = entry.to_vec(self.format)?;
+
+            let mut socket = self.socket.lock().await;
+            match &mut *socket {
+                Socket::Tcp(conn) => {
+                    data.push(b'\n');
+                    let stream = conn.stream(self.write_timeout).await?;
+                    let res = with_timeout(self.write_timeout, async {
+                        stream.write_all(&data).await?;
+                        stream.flush().await
+                    })
+                    .await;
+                    // A partially written entry leaves the stream unusable
+                    if res.is_err() {
+                        conn.failed();
+                    }
+                    res
+                }
+                Socket::Udp(socket) => {
+                    with_timeout(self.write_timeout, socket.send(&data)).await?;
+                    Ok(())
+                }
+                Socket::Unixgram(socket) => {
+                    with_timeout(self.write_timeout, socket.send(&data)).await?;
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async move {
+            // Datagrams are sent unbuffered
+            match &mut *self.socket.lock().await {
+                Socket::Tcp(TcpConnection {
+                    stream: Some(stream),
+                    ..
+                }) => with_timeout(self.write_timeout, stream.flush()).await,
+                _ => Ok(()),
+            }
+        })
+    }
+}
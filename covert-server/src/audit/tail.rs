@@ -0,0 +1,139 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use covert_types::methods::system::{AuditTailEvent, AuditTailParams};
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use serde_json::Value;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use super::AuditEntry;
+
+/// Number of recent entries kept in memory by every device.
+pub const TAIL_CAPACITY: usize = 1024;
+
+type Numbered = (u64, Arc<AuditEntry>);
+
+/// The recent entries written by a device, numbered in the order they were
+/// written, for clients following the device. The entries are the ones
+/// written by the device, sensitive values are hashed.
+pub struct AuditTail {
+    epoch: Uuid,
+    recent: Mutex<Recent>,
+    sender: broadcast::Sender<Numbered>,
+}
+
+struct Recent {
+    next_seq: u64,
+    entries: VecDeque<Numbered>,
+}
+
+impl Default for AuditTail {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(TAIL_CAPACITY);
+        Self {
+            epoch: Uuid::new_v4(),
+            recent: Mutex::new(Recent {
+                next_seq: 0,
+                entries: VecDeque::with_capacity(TAIL_CAPACITY),
+            }),
+            sender,
+        }
+    }
+}
+
+impl AuditTail {
+    pub fn push(&self, entry: AuditEntry) {
+        let mut recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = (recent.next_seq, Arc::new(entry));
+        recent.next_seq += 1;
+        if recent.entries.len() == TAIL_CAPACITY {
+            recent.entries.pop_front();
+        }
+        recent.entries.push_back(entry.clone());
+        // Only fails when nobody follows the device
+        let _ = self.sender.send(entry);
+    }
+
+    /// Stream of the entries written after the entry the params resume from,
+    /// or starting with the last entries written, followed by the entries
+    /// written from now on. The stream ends when the device is disabled.
+    pub fn follow(&self, params: &AuditTailParams) -> BoxStream<'static, AuditTailEvent> {
+        let mut events = vec![AuditTailEvent::Start { epoch: self.epoch }];
+
+        // Subscribed while holding the lock so no entry is missed or sent
+        // twice between the recent entries and the live ones
+        let recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        let receiver = self.sender.subscribe();
+        let oldest = recent
+            .entries
+            .front()
+            .map_or(recent.next_seq, |(seq, _)| *seq);
+        let start = match (params.epoch, params.since) {
+            (Some(epoch), Some(since)) if epoch == self.epoch => {
+                if since + 1 < oldest {
+                    events.push(AuditTailEvent::Gap {
+                        missed: Some(oldest - since - 1),
+                    });
+                }
+                since + 1
+            }
+            (Some(_), Some(_)) => {
+                events.push(AuditTailEvent::Gap { missed: None });
+                oldest
+            }
+            _ => recent
+                .next_seq
+                .saturating_sub(params.last.unwrap_or(0))
+                .max(oldest),
+        };
+        events.extend(
+            recent
+                .entries
+                .iter()
+                .filter(|(seq, _)| *seq >= start)
+                .map(entry_event),
+        );
+        let next_seq = recent.next_seq;
+        drop(recent);
+
+        let live = stream::unfold(
+            (receiver, next_seq),
+            |(mut receiver, expected)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(entry) => {
+                            let mut events = vec![];
+                            // Entries dropped while the client lagged behind
+                            if entry.0 > expected {
+                                events.push(AuditTailEvent::Gap {
+                                    missed: Some(entry.0 - expected),
+                                });
+                            }
+                            events.push(entry_event(&entry));
+                            return Some((stream::iter(events), (receiver, entry.0 + 1)));
+                        }
+                        // Noticed by the gap in the sequence numbers
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )
+        .flatten();
+
+        stream::iter(events).chain(live).boxed()
+    }
+}
+
+fn entry_event((seq, entry): &Numbered) -> AuditTailEvent {
+    AuditTailEvent::Entry {
+        seq: *seq,
+        entry: serde_json::to_value(entry.as_ref()).unwrap_or(Value::Null),
+    }
+}
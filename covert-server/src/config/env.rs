@@ -0,0 +1,315 @@
+//! Environment variables in the config: `${VAR}` references in the values of
+//! the config file and the `COVERT_` prefixed overrides of single keys.
+
+use toml::{Table, Value};
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    String,
+    Integer,
+    Bool,
+    /// Comma separated strings.
+    List,
+    /// Comma separated listener addresses, replacing the listeners of the
+    /// config file.
+    Listeners,
+}
+
+/// Environment variables overriding config values and the key they override.
+/// The name is the path of the key, upper cased, with `-` and `.` replaced by
+/// `_`.
+const OVERRIDES: &[(&str, &[&str], Kind)] = &[
+    ("COVERT_PORT", &["port"], Kind::Integer),
+    ("COVERT_STORAGE_PATH", &["storage-path"], Kind::String),
+    ("COVERT_LISTENER_ADDRESS", &["listener"], Kind::Listeners),
+    ("COVERT_TLS_DISABLE", &["tls-disable"], Kind::Bool),
+    ("COVERT_TLS_CERT_FILE", &["tls", "cert-file"], Kind::String),
+    ("COVERT_TLS_KEY_FILE", &["tls", "key-file"], Kind::String),
+    (
+        "COVERT_TLS_MIN_VERSION",
+        &["tls", "min-version"],
+        Kind::String,
+    ),
+    (
+        "COVERT_TLS_CLIENT_CA_FILE",
+        &["tls", "client-ca-file"],
+        Kind::String,
+    ),
+    (
+        "COVERT_TLS_CIPHER_SUITES",
+        &["tls", "cipher-suites"],
+        Kind::List,
+    ),
+    (
+        "COVERT_REPLICATION_ACCESS_KEY_ID",
+        &["replication", "access-key-id"],
+        Kind::String,
+    ),
+    (
+        "COVERT_REPLICATION_SECRET_ACCESS_KEY",
+        &["replication", "secret-access-key"],
+        Kind::String,
+    ),
+    (
+        "COVERT_REPLICATION_BUCKET_URL",
+        &["replication", "bucket-url"],
+        Kind::String,
+    ),
+    (
+        "COVERT_IGNORE_MIGRATION_CHECKSUMS",
+        &["ignore-migration-checksums"],
+        Kind::Bool,
+    ),
+    ("COVERT_MAX_LEASE_TTL", &["max-lease-ttl"], Kind::String),
+    (
+        "COVERT_METRICS_ENABLED",
+        &["metrics", "enabled"],
+        Kind::Bool,
+    ),
+    ("COVERT_CORS_ENABLED", &["cors", "enabled"], Kind::Bool),
+    (
+        "COVERT_CORS_ALLOWED_ORIGINS",
+        &["cors", "allowed-origins"],
+        Kind::List,
+    ),
+    ("COVERT_LOG_FORMAT", &["log-format"], Kind::String),
+    ("COVERT_LOG_LEVEL", &["log-level"], Kind::String),
+    ("COVERT_TRUSTED_PROXIES", &["trusted-proxies"], Kind::List),
+    (
+        "COVERT_SHUTDOWN_TIMEOUT",
+        &["shutdown-timeout"],
+        Kind::String,
+    ),
+    ("COVERT_REQUEST_TIMEOUT", &["request-timeout"], Kind::String),
+    (
+        "COVERT_MAX_CONCURRENT_REQUESTS",
+        &["max-concurrent-requests"],
+        Kind::Integer,
+    ),
+    (
+        "COVERT_HA_API_ADDRESS",
+        &["ha", "api-address"],
+        Kind::String,
+    ),
+];
+
+impl Kind {
+    fn parse(self, raw: &str) -> Result<Value, String> {
+        let list = || {
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+        };
+        match self {
+            Self::String => Ok(Value::String(raw.to_string())),
+            Self::Integer => raw
+                .trim()
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| format!("expected an integer, got `{raw}`")),
+            Self::Bool => raw
+                .trim()
+                .parse()
+                .map(Value::Boolean)
+                .map_err(|_| format!("expected `true` or `false`, got `{raw}`")),
+            Self::List => Ok(Value::Array(
+                list().map(|item| Value::String(item.to_string())).collect(),
+            )),
+            Self::Listeners => Ok(Value::Array(
+                list()
+                    .map(|address| {
+                        Value::Table(Table::from_iter([(
+                            "address".to_string(),
+                            Value::String(address.to_string()),
+                        )]))
+                    })
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Expand the `${VAR}` references in the values of the config file and
+/// apply the `COVERT_` overrides on top of them. Returns whether the config
+/// was changed.
+pub(super) fn apply_env(
+    table: &mut Table,
+    env: &impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<bool> {
+    let mut changed = false;
+    for (key, value) in table.iter_mut() {
+        changed |= expand_value(value, key, env)?;
+    }
+
+    for (name, path, kind) in OVERRIDES {
+        let Some(raw) = env(name) else {
+            continue;
+        };
+        let value = kind
+            .parse(&raw)
+            .map_err(|err| anyhow::Error::msg(format!("{name}: {err}")))?;
+        let (key, parents) = path.split_last().expect("override paths are not empty");
+        let mut parent = &mut *table;
+        for (i, part) in parents.iter().enumerate() {
+            parent = match parent
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(table) => table,
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "{name}: `{}` is not a table",
+                        path[..=i].join(".")
+                    )))
+                }
+            };
+        }
+        parent.insert(key.to_string(), value);
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+fn expand_value(
+    value: &mut Value,
+    path: &str,
+    env: &impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<bool> {
+    match value {
+        Value::String(s) => match expand(s, env) {
+            Ok(Some(expanded)) => {
+                *s = expanded;
+                Ok(true)
+            }
+            Ok(None) => Ok(false),
+            Err(err) => Err(anyhow::Error::msg(format!("{path}: {err}"))),
+        },
+        Value::Array(values) => {
+            let mut changed = false;
+            for (i, value) in values.iter_mut().enumerate() {
+                changed |= expand_value(value, &format!("{path}[{i}]"), env)?;
+            }
+            Ok(changed)
+        }
+        Value::Table(table) => {
+            let mut changed = false;
+            for (key, value) in table.iter_mut() {
+                changed |= expand_value(value, &format!("{path}.{key}"), env)?;
+            }
+            Ok(changed)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Replace the `${VAR}` references in `s` with the value of the environment
+/// variable. `$${` is a literal `${`. Returns `None` if there is nothing to
+/// expand.
+fn expand(s: &str, env: &impl Fn(&str) -> Option<String>) -> Result<Option<String>, String> {
+    if !s.contains("${") {
+        return Ok(None);
+    }
+
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| "unterminated `${` in value".to_string())?;
+            let name = &after[..end];
+            if !is_valid_name(name) {
+                return Err(format!("invalid environment variable name `{name}`"));
+            }
+            let value =
+                env(name).ok_or_else(|| format!("environment variable `{name}` is not set"))?;
+            expanded.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(Some(expanded))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn expand_references() {
+        let env = env(&[("HOST", "db"), ("PORT", "5432")]);
+        assert_eq!(expand("plain", &env), Ok(None));
+        assert_eq!(
+            expand("postgres://${HOST}:${PORT}/covert", &env),
+            Ok(Some("postgres://db:5432/covert".to_string()))
+        );
+        assert_eq!(
+            expand("$${HOST} costs $5", &env),
+            Ok(Some("${HOST} costs $5".to_string()))
+        );
+        assert_eq!(
+            expand("${MISSING}", &env),
+            Err("environment variable `MISSING` is not set".to_string())
+        );
+        assert!(expand("${HOST", &env).is_err());
+        assert!(expand("${1HOST}", &env).is_err());
+    }
+
+    #[test]
+    fn overrides_create_missing_tables() {
+        let mut table: Table = toml::from_str("port = 8080").unwrap();
+        let changed = apply_env(
+            &mut table,
+            &env(&[
+                ("COVERT_PORT", "9000"),
+                ("COVERT_TLS_CERT_FILE", "/etc/covert/tls.crt"),
+                (
+                    "COVERT_LISTENER_ADDRESS",
+                    "tcp://127.0.0.1:8201, unix:///run/covert.sock",
+                ),
+                ("COVERT_ADDR", "http://127.0.0.1:8080/v1"),
+            ]),
+        )
+        .unwrap();
+        assert!(changed);
+        assert_eq!(table["port"].as_integer(), Some(9000));
+        assert_eq!(
+            table["tls"]["cert-file"].as_str(),
+            Some("/etc/covert/tls.crt")
+        );
+        assert_eq!(
+            table["listener"][1]["address"].as_str(),
+            Some("unix:///run/covert.sock")
+        );
+
+        let err = apply_env(&mut table, &env(&[("COVERT_PORT", "http")])).unwrap_err();
+        assert!(err.to_string().starts_with("COVERT_PORT:"), "{err}");
+    }
+}
@@ -1,9 +1,20 @@
-use std::{process::Child, sync::Arc};
+use std::{
+    collections::HashMap,
+    process::Child,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use covert_types::methods::system::{TokenRevocationJobState, TokenRevocationJobStatus};
 use tokio::sync::RwLock;
 use tracing::error;
+use uuid::Uuid;
 
-use crate::{repos::Repos, Config, ExpirationManager, Router};
+use crate::{
+    audit::AuditBroker, ha::HaState, identity::SnapshotKey, layer::idempotency::IdempotencyCache,
+    mfa::PendingLogins, plugin::Plugins, quota::QuotaManager, reload::ConfigReload, repos::Repos,
+    tamper::TamperMonitor, Config, ExpirationManager, Router,
+};
 
 pub struct Context {
     pub config: Arc<Config>,
@@ -11,6 +22,16 @@ pub struct Context {
     pub child_processes: ChildProcesses,
     pub expiration_manager: Arc<ExpirationManager>,
     pub router: Arc<Router>,
+    pub token_revocation_jobs: TokenRevocationJobs,
+    pub audit: Arc<AuditBroker>,
+    pub quotas: Arc<QuotaManager>,
+    pub idempotency: Arc<IdempotencyCache>,
+    pub reload: Arc<ConfigReload>,
+    pub tamper: Arc<TamperMonitor>,
+    pub pending_logins: Arc<PendingLogins>,
+    pub snapshot_key: Arc<SnapshotKey>,
+    pub plugins: Arc<Plugins>,
+    pub ha: Arc<HaState>,
 }
 
 impl Clone for Context {
@@ -21,6 +42,16 @@ impl Clone for Context {
             child_processes: self.child_processes.clone(),
             expiration_manager: Arc::clone(&self.expiration_manager),
             router: Arc::clone(&self.router),
+            token_revocation_jobs: self.token_revocation_jobs.clone(),
+            audit: Arc::clone(&self.audit),
+            quotas: Arc::clone(&self.quotas),
+            idempotency: Arc::clone(&self.idempotency),
+            reload: Arc::clone(&self.reload),
+            tamper: Arc::clone(&self.tamper),
+            pending_logins: Arc::clone(&self.pending_logins),
+            snapshot_key: Arc::clone(&self.snapshot_key),
+            plugins: Arc::clone(&self.plugins),
+            ha: Arc::clone(&self.ha),
         }
     }
 }
@@ -78,3 +109,163 @@ impl ChildProcesses {
         }
     }
 }
+
+/// How long the status of a completed token revocation job can be read.
+const COMPLETED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Max number of completed token revocation jobs that are kept, the oldest
+/// are evicted first.
+const MAX_COMPLETED_JOBS: usize = 100;
+
+struct TokenRevocationJob {
+    namespace_id: String,
+    status: TokenRevocationJobStatus,
+    completed_at: Option<Instant>,
+}
+
+/// Progress of the asynchronous token revocation jobs, keyed by job id.
+/// Completed jobs are evicted after [`COMPLETED_JOB_TTL`] or once there are
+/// more than [`MAX_COMPLETED_JOBS`] of them.
+pub struct TokenRevocationJobs {
+    jobs: Arc<RwLock<HashMap<Uuid, TokenRevocationJob>>>,
+}
+
+impl Clone for TokenRevocationJobs {
+    fn clone(&self) -> Self {
+        Self {
+            jobs: Arc::clone(&self.jobs),
+        }
+    }
+}
+
+impl Default for TokenRevocationJobs {
+    fn default() -> Self {
+        Self {
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+impl TokenRevocationJobs {
+    pub async fn insert(&self, namespace_id: String, status: TokenRevocationJobStatus) {
+        let mut l = self.jobs.write().await;
+        prune(&mut l, Instant::now());
+        l.insert(
+            status.job_id,
+            TokenRevocationJob {
+                namespace_id,
+                status,
+                completed_at: None,
+            },
+        );
+    }
+
+    pub async fn get(&self, job_id: Uuid, namespace_id: &str) -> Option<TokenRevocationJobStatus> {
+        let mut l = self.jobs.write().await;
+        prune(&mut l, Instant::now());
+        l.get(&job_id)
+            .filter(|job| job.namespace_id == namespace_id)
+            .map(|job| job.status.clone())
+    }
+
+    pub async fn record_revocation(&self, job_id: Uuid, success: bool) {
+        let mut l = self.jobs.write().await;
+        if let Some(job) = l.get_mut(&job_id) {
+            if success {
+                job.status.revoked += 1;
+            } else {
+                job.status.failed += 1;
+            }
+        }
+    }
+
+    pub async fn complete(&self, job_id: Uuid) {
+        let mut l = self.jobs.write().await;
+        if let Some(job) = l.get_mut(&job_id) {
+            job.status.state = TokenRevocationJobState::Completed;
+            job.completed_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Evict the completed jobs that expired and the oldest completed jobs above
+/// the limit. Running jobs are kept.
+fn prune(jobs: &mut HashMap<Uuid, TokenRevocationJob>, now: Instant) {
+    jobs.retain(|_, job| {
+        job.completed_at
+            .is_none_or(|completed_at| now < completed_at + COMPLETED_JOB_TTL)
+    });
+    let mut completed = jobs
+        .iter()
+        .filter_map(|(id, job)| job.completed_at.map(|completed_at| (completed_at, *id)))
+        .collect::<Vec<_>>();
+    if completed.len() > MAX_COMPLETED_JOBS {
+        completed.sort_unstable();
+        for (_, id) in &completed[..completed.len() - MAX_COMPLETED_JOBS] {
+            jobs.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(completed_at: Option<Instant>) -> TokenRevocationJob {
+        TokenRevocationJob {
+            namespace_id: "root".to_string(),
+            status: TokenRevocationJobStatus {
+                job_id: Uuid::new_v4(),
+                policy: "foo".to_string(),
+                state: if completed_at.is_some() {
+                    TokenRevocationJobState::Completed
+                } else {
+                    TokenRevocationJobState::Running
+                },
+                total: 0,
+                revoked: 0,
+                failed: 0,
+            },
+            completed_at,
+        }
+    }
+
+    #[test]
+    fn prune_expired_jobs() {
+        let now = Instant::now();
+        let mut jobs = HashMap::new();
+        let running = job(None);
+        let completed = job(Some(now));
+        let (running_id, completed_id) = (running.status.job_id, completed.status.job_id);
+        jobs.insert(running_id, running);
+        jobs.insert(completed_id, completed);
+
+        prune(&mut jobs, now + COMPLETED_JOB_TTL - Duration::from_secs(1));
+        assert_eq!(jobs.len(), 2);
+
+        // Running jobs are kept regardless of their age
+        prune(&mut jobs, now + COMPLETED_JOB_TTL);
+        assert!(jobs.contains_key(&running_id));
+        assert!(!jobs.contains_key(&completed_id));
+    }
+
+    #[test]
+    fn prune_oldest_jobs_above_limit() {
+        let now = Instant::now();
+        let mut jobs = HashMap::new();
+        let ids = (0..=MAX_COMPLETED_JOBS)
+            .map(|i| {
+                let completed_at = now + Duration::from_secs(u64::try_from(i).unwrap());
+                let job = job(Some(completed_at));
+                let id = job.status.job_id;
+                jobs.insert(id, job);
+                id
+            })
+            .collect::<Vec<_>>();
+
+        prune(&mut jobs, now);
+        assert_eq!(jobs.len(), MAX_COMPLETED_JOBS);
+        assert!(!jobs.contains_key(&ids[0]));
+        assert!(jobs.contains_key(&ids[1]));
+    }
+}
@@ -1,14 +1,28 @@
 use std::fmt::Display;
 
+use chrono::{DateTime, Utc};
+use covert_plugin::PluginError;
 use covert_storage::{migrator::MigrationError, EncryptedPoolError};
 use covert_types::{
     backend::BackendType,
-    error::{ApiError, StatusCode},
+    error::{ApiError, ErrorCode, FieldError, StatusCode},
 };
 use sqlx::{error::DatabaseError, sqlite::SqliteError};
 use thiserror::Error;
 use tracing_error::SpanTrace;
 
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let suggestions = suggestions
+        .iter()
+        .map(|path| format!("`{path}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(". Did you mean one of: {suggestions}?")
+}
+
 #[derive(Error, Debug)]
 pub enum ErrorType {
     #[error("Internal error")]
@@ -21,6 +35,8 @@ pub enum ErrorType {
     BadResponseData(#[source] serde_json::Error),
     #[error("Internal error")]
     BadHttpResponseData(#[source] hyper::http::Error),
+    #[error("Internal error")]
+    UnregisteredLease,
     #[error("{0}")]
     Unauthorized(String),
     #[error("{0}")]
@@ -37,6 +53,16 @@ pub enum ErrorType {
         source: Box<dyn std::error::Error + Send + Sync + 'static>,
         lease_id: String,
     },
+    #[error(
+        "Failed to revoke lease `{lease_id}`, aborted after revoking {revoked} leases under `{prefix}`"
+    )]
+    RevokeLeasePrefix {
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+        lease_id: String,
+        prefix: String,
+        revoked: usize,
+    },
     #[error("{0}")]
     BadRequest(String),
     #[error("Internal error")]
@@ -49,6 +75,11 @@ pub enum ErrorType {
     },
     #[error("Mount for path `{path}` was not found")]
     MountNotFound { path: String },
+    #[error("No mount found for path `{path}`{}", did_you_mean(.suggestions))]
+    NoMountForPath {
+        path: String,
+        suggestions: Vec<String>,
+    },
     #[error(
         "Unable to mount backend at `{path}` because of path overlap with mount at `{existing_path}`"
     )]
@@ -57,12 +88,28 @@ pub enum ErrorType {
     InvalidMountPath { path: String, error: String },
     #[error("`{variant}` cannot be mounted or removed")]
     InvalidMountType { variant: BackendType },
-    #[error("Invalid initialize request")]
-    InvalidInitializeParams,
+    #[error("Invalid initialize request. Error: {error}")]
+    InvalidInitializeParams { field: &'static str, error: String },
     #[error("Unable to perform state transition. Error: {0}")]
     StateTransition(#[from] EncryptedPoolError),
     #[error("Unable to recover master key from the key shares")]
     MasterKeyRecovery,
+    #[error("The seal failed to wrap or unwrap the master key. Error: {0}")]
+    Seal(String),
+    #[error("Plugin `{name}` is not registered in the server config")]
+    PluginNotRegistered { name: String },
+    #[error("Plugin mounts need the name of a registered plugin in `config.plugin`")]
+    PluginNameRequired,
+    #[error(transparent)]
+    Plugin(#[from] PluginError),
+    #[error("No active node to forward the request to, no unsealed node holds the HA lock")]
+    NoActiveNode,
+    /// Returned by a performance standby for reads it cannot serve, see
+    /// [`crate::layer::standby_read`].
+    #[error("The request can only be served by the active node")]
+    ForwardToActive,
+    #[error("Failed to forward the request to the active node")]
+    Forward(#[source] reqwest::Error),
     #[error("A resource with that identifier already exists")]
     UniqueConstraintViolation {
         #[source]
@@ -76,6 +123,20 @@ pub enum ErrorType {
     },
     #[error("Only the root namespace can call seal")]
     SealInNonRootNamespace,
+    #[error("Only the root namespace can read or reload the server configuration")]
+    ConfigInNonRootNamespace,
+    #[error("Only the root namespace can manage audit devices")]
+    AuditInNonRootNamespace,
+    #[error("Audit device `{path}` was not found")]
+    AuditDeviceNotFound { path: String },
+    #[error("Audit device `{path}` is already enabled")]
+    AuditDeviceAlreadyEnabled { path: String },
+    #[error("Unable to write the request to any audit device")]
+    AuditFailed,
+    #[error("Only the root namespace can manage quotas")]
+    QuotaInNonRootNamespace,
+    #[error("Quota `{name}` was not found")]
+    QuotaNotFound { name: String },
     #[error("Failed to restore backup")]
     Recovery {
         #[source]
@@ -85,6 +146,50 @@ pub enum ErrorType {
     AuthBackendNotUnderAuthPath,
     #[error("Secret engines cannot be mounted under `auth/`")]
     LogicalBackendUnderAuthPath,
+    #[error("Storage did not reach write index `{index}` before the consistency timeout")]
+    ConsistencyTimeout { index: u64 },
+    #[error("Request was not handled within the request timeout of {0:?}")]
+    RequestTimeout(std::time::Duration),
+    #[error("Token is not renewable")]
+    TokenNotRenewable,
+    #[error("Invalid wrapping token")]
+    InvalidWrappingToken,
+    #[error("Wrapping token expired at `{expired_at}`")]
+    WrappingTokenExpired { expired_at: DateTime<Utc> },
+    #[error("Wrapping token was already unwrapped at `{unwrapped_at}`. The wrapped response may have been intercepted")]
+    WrappingTokenAlreadyUnwrapped { unwrapped_at: DateTime<Utc> },
+    #[error("Failed to reload config: {0:#}")]
+    ConfigReload(anyhow::Error),
+    #[error("A request with the same idempotency key is still in progress")]
+    IdempotencyKeyInUse,
+    #[error("The idempotency key was already used for a different request")]
+    IdempotencyKeyReused,
+    #[error("The auth method requires MFA but entity `{entity}` has no TOTP key")]
+    MfaNotConfigured { entity: String },
+    #[error("Invalid or expired MFA request")]
+    InvalidMfaRequest,
+    #[error("Invalid MFA code")]
+    InvalidMfaCode,
+    #[error("MFA can only be required by auth methods")]
+    MfaOnLogicalBackend,
+    #[error("Path `{path}` overlaps with namespace `{namespace}`")]
+    NamespacePathConflict { path: String, namespace: String },
+    #[error("Entity `{entity}` has a login waiting for the second factor")]
+    EntityLoginPending { entity: String },
+    #[error("Invalid identity snapshot, {0}")]
+    InvalidIdentitySnapshot(String),
+    #[error("`{name}` is not a valid namespace name. Error: `{error}`")]
+    InvalidNamespaceName { name: String, error: String },
+    #[error("Static secret `{name}` was not found")]
+    StaticSecretNotFound { name: String },
+    #[error("Static secret `{name}` has no previous value within its grace period")]
+    NoPreviousStaticSecret { name: String },
+    #[error("Failed to rotate static secret `{name}`: {message}")]
+    StaticSecretRotation { name: String, message: String },
+    #[error("Failed to encrypt field `{field}`: {message}")]
+    FieldEncryption { field: String, message: String },
+    #[error("Failed to decrypt field `{field}`: {message}")]
+    FieldDecryption { field: String, message: String },
 }
 
 #[derive(Error, Debug)]
@@ -151,6 +256,15 @@ impl From<EncryptedPoolError> for Error {
     }
 }
 
+impl From<PluginError> for Error {
+    fn from(err: PluginError) -> Self {
+        Self {
+            variant: err.into(),
+            span_trace: SpanTrace::capture(),
+        }
+    }
+}
+
 impl From<ErrorType> for Error {
     fn from(err: ErrorType) -> Self {
         Self {
@@ -162,36 +276,119 @@ impl From<ErrorType> for Error {
 
 impl From<Error> for ApiError {
     fn from(err: Error) -> Self {
-        let status_code = match err.variant {
+        let (status_code, code) = match err.variant {
             ErrorType::Storage(_)
             | ErrorType::InternalError(_)
             | ErrorType::BadData(_)
             | ErrorType::BadResponseData(_)
             | ErrorType::BadHttpResponseData(_)
-            | ErrorType::RenewLease { .. }
+            | ErrorType::UnregisteredLease
             | ErrorType::RevokeLease { .. }
+            | ErrorType::RevokeLeasePrefix { .. }
             | ErrorType::Migration { .. }
             | ErrorType::StateTransition(_)
             | ErrorType::BackendMigration { .. }
-            | ErrorType::Recovery { .. } => StatusCode::INTERNAL_SERVER_ERROR,
-            ErrorType::Unauthorized(_) | ErrorType::MasterKeyRecovery => StatusCode::UNAUTHORIZED,
-            ErrorType::NotFound(_) | ErrorType::MountNotFound { .. } => StatusCode::NOT_FOUND,
+            | ErrorType::Recovery { .. }
+            | ErrorType::AuditFailed
+            | ErrorType::ConfigReload(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal)
+            }
+            ErrorType::RenewLease { .. } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::LeaseNotRenewable,
+            ),
+            ErrorType::TokenNotRenewable => (StatusCode::BAD_REQUEST, ErrorCode::LeaseNotRenewable),
+            ErrorType::RequestTimeout(_) => (StatusCode::REQUEST_TIMEOUT, ErrorCode::Timeout),
+            ErrorType::Unauthorized(_)
+            | ErrorType::MasterKeyRecovery
+            | ErrorType::InvalidMfaRequest
+            | ErrorType::InvalidMfaCode => (StatusCode::UNAUTHORIZED, ErrorCode::PermissionDenied),
+            ErrorType::NotFound(_)
+            | ErrorType::MountNotFound { .. }
+            | ErrorType::NoMountForPath { .. }
+            | ErrorType::AuditDeviceNotFound { .. }
+            | ErrorType::QuotaNotFound { .. }
+            | ErrorType::StaticSecretNotFound { .. }
+            | ErrorType::NoPreviousStaticSecret { .. } => {
+                (StatusCode::NOT_FOUND, ErrorCode::NotFound)
+            }
             ErrorType::BadRequest(_)
             | ErrorType::InvalidMountPath { .. }
-            | ErrorType::InvalidInitializeParams
-            | ErrorType::InvalidMountType { .. } => StatusCode::BAD_REQUEST,
-            ErrorType::MountPathConflict { .. } | ErrorType::UniqueConstraintViolation { .. } => {
-                StatusCode::CONFLICT
+            | ErrorType::InvalidInitializeParams { .. }
+            | ErrorType::InvalidMountType { .. }
+            | ErrorType::PluginNotRegistered { .. }
+            | ErrorType::PluginNameRequired
+            | ErrorType::InvalidWrappingToken
+            | ErrorType::InvalidNamespaceName { .. }
+            | ErrorType::InvalidIdentitySnapshot(_)
+            | ErrorType::FieldEncryption { .. }
+            | ErrorType::FieldDecryption { .. } => (StatusCode::BAD_REQUEST, ErrorCode::BadRequest),
+            ErrorType::WrappingTokenExpired { .. } => {
+                (StatusCode::BAD_REQUEST, ErrorCode::WrappingTokenExpired)
             }
-            ErrorType::ForeignKeyViolation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorType::WrappingTokenAlreadyUnwrapped { .. } => {
+                (StatusCode::BAD_REQUEST, ErrorCode::WrappingTokenUnwrapped)
+            }
+            ErrorType::MountPathConflict { .. }
+            | ErrorType::UniqueConstraintViolation { .. }
+            | ErrorType::AuditDeviceAlreadyEnabled { .. }
+            | ErrorType::NamespacePathConflict { .. }
+            | ErrorType::EntityLoginPending { .. }
+            | ErrorType::IdempotencyKeyInUse => (StatusCode::CONFLICT, ErrorCode::Conflict),
+            ErrorType::ForeignKeyViolation { .. } | ErrorType::IdempotencyKeyReused => {
+                (StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::BadRequest)
+            }
+            ErrorType::ConsistencyTimeout { .. } => (
+                StatusCode::PRECONDITION_FAILED,
+                ErrorCode::PreconditionFailed,
+            ),
             ErrorType::SealInNonRootNamespace
-            | ErrorType::AuthBackendNotUnderAuthPath
-            | ErrorType::LogicalBackendUnderAuthPath => StatusCode::FORBIDDEN,
+            | ErrorType::ConfigInNonRootNamespace
+            | ErrorType::AuditInNonRootNamespace
+            | ErrorType::QuotaInNonRootNamespace
+            | ErrorType::MfaNotConfigured { .. } => {
+                (StatusCode::FORBIDDEN, ErrorCode::PermissionDenied)
+            }
+            ErrorType::AuthBackendNotUnderAuthPath
+            | ErrorType::LogicalBackendUnderAuthPath
+            | ErrorType::MfaOnLogicalBackend => (StatusCode::FORBIDDEN, ErrorCode::BadRequest),
+            ErrorType::Seal(_)
+            | ErrorType::Plugin(_)
+            | ErrorType::Forward(_)
+            | ErrorType::StaticSecretRotation { .. } => {
+                (StatusCode::BAD_GATEWAY, ErrorCode::UpstreamError)
+            }
+            ErrorType::NoActiveNode | ErrorType::ForwardToActive => {
+                (StatusCode::SERVICE_UNAVAILABLE, ErrorCode::UpstreamError)
+            }
+        };
+        let details = match &err.variant {
+            ErrorType::InvalidMountPath { error, .. } => vec![FieldError {
+                field: "path".to_string(),
+                message: error.clone(),
+            }],
+            ErrorType::InvalidNamespaceName { error, .. } => vec![FieldError {
+                field: "name".to_string(),
+                message: error.clone(),
+            }],
+            ErrorType::InvalidInitializeParams { field, error } => vec![FieldError {
+                field: (*field).to_string(),
+                message: error.clone(),
+            }],
+            ErrorType::FieldEncryption { field, message }
+            | ErrorType::FieldDecryption { field, message } => vec![FieldError {
+                field: format!("data.{field}"),
+                message: message.clone(),
+            }],
+            _ => vec![],
         };
 
         ApiError {
             error: err.variant.into(),
+            code,
+            details,
             status_code,
+            retry_after: None,
             span_trace: Some(err.span_trace),
         }
     }
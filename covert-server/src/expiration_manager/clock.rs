@@ -1,19 +1,41 @@
-use std::{pin::Pin, time::Duration};
+use std::{fmt::Debug, pin::Pin, sync::Arc, time::Duration};
 
 use chrono::{DateTime, Utc};
 use futures::Future;
 
-pub trait Clock: Send + Sync + 'static {
+/// Source of time for the lease subsystem.
+///
+/// All TTL math in the [`ExpirationManager`](super::ExpirationManager) and
+/// the expiry checks of the token store go through the clock so tests can
+/// control when leases and tokens expire.
+pub trait Clock: Debug + Send + Sync + 'static {
     fn now(&self) -> DateTime<Utc>;
+    /// Resolves once the clock has moved forward by `duration`.
     fn sleep(
         &self,
         duration: Duration,
     ) -> Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>>;
 }
 
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+
+    fn sleep(
+        &self,
+        duration: Duration,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>> {
+        (**self).sleep(duration)
+    }
+}
+
+/// Wall-clock time.
+#[derive(Debug, Default, Clone, Copy)]
 pub struct SystemClock {}
 
 impl SystemClock {
+    #[must_use]
     pub fn new() -> Self {
         Self {}
     }
@@ -32,93 +54,111 @@ impl Clock for SystemClock {
     }
 }
 
-#[cfg(test)]
-pub mod test {
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockClock;
+
+#[cfg(any(test, feature = "test-util"))]
+mod mock {
     use std::sync::{
         atomic::{AtomicI64, Ordering},
         Arc,
     };
 
-    use chrono::{Duration, TimeZone};
+    use chrono::Duration;
     use tokio::sync::Notify;
 
     use super::*;
 
-    pub struct TestClock {
-        duration: Arc<AtomicI64>,
+    /// Clock that only moves when told to. It starts at the Unix epoch.
+    ///
+    /// Clones share the same time, so a test can keep one handle and give
+    /// another to the [`ExpirationManager`](super::super::ExpirationManager).
+    /// Moving the clock wakes everything sleeping on it.
+    #[derive(Debug, Default)]
+    pub struct MockClock {
+        millis: Arc<AtomicI64>,
         background_task: Arc<Notify>,
     }
 
-    impl Clone for TestClock {
+    impl Clone for MockClock {
         fn clone(&self) -> Self {
             Self {
-                duration: Arc::clone(&self.duration),
+                millis: Arc::clone(&self.millis),
                 background_task: Arc::clone(&self.background_task),
             }
         }
     }
 
-    impl TestClock {
+    impl MockClock {
+        #[must_use]
         pub fn new() -> Self {
-            Self {
-                duration: Arc::new(AtomicI64::new(0)),
-                background_task: Arc::new(Notify::new()),
-            }
+            Self::default()
         }
 
-        pub fn advance(&self, millis: i64) {
-            self.duration.fetch_add(millis, Ordering::SeqCst);
+        /// Move the clock forward.
+        pub fn advance(&self, duration: Duration) {
+            self.millis
+                .fetch_add(duration.num_milliseconds(), Ordering::SeqCst);
             self.background_task.notify_waiters();
         }
 
-        pub fn set(&self, millis: i64) {
-            self.duration.store(millis, Ordering::SeqCst);
+        /// Move the clock to the given time.
+        pub fn set(&self, time: DateTime<Utc>) {
+            self.millis.store(time.timestamp_millis(), Ordering::SeqCst);
             self.background_task.notify_waiters();
         }
     }
 
-    impl Clock for TestClock {
+    impl Clock for MockClock {
         fn now(&self) -> DateTime<Utc> {
-            let start = Utc.timestamp_opt(0, 0).unwrap();
-            let duration = Duration::milliseconds(self.duration.load(Ordering::SeqCst));
-
-            start + duration
+            // The default time is the Unix epoch
+            DateTime::<Utc>::default() + Duration::milliseconds(self.millis.load(Ordering::SeqCst))
         }
 
         fn sleep(
             &self,
             duration: std::time::Duration,
         ) -> Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>> {
-            let start = self.duration.load(Ordering::SeqCst);
-            let millis = duration.as_millis();
-            let until = start + i64::try_from(millis).unwrap();
+            let millis = i64::try_from(duration.as_millis()).unwrap_or(i64::MAX);
+            let until = self.millis.load(Ordering::SeqCst).saturating_add(millis);
 
             let this = self.clone();
 
             Box::pin(async move {
                 loop {
-                    this.background_task.notified().await;
-                    let timer = this.duration.load(Ordering::SeqCst);
-                    if timer >= until {
+                    // Register for the notification before reading the time so
+                    // a concurrent move of the clock is never missed.
+                    let notified = this.background_task.notified();
+                    tokio::pin!(notified);
+                    notified.as_mut().enable();
+                    if this.millis.load(Ordering::SeqCst) >= until {
                         break;
                     }
+                    notified.await;
                 }
             })
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
 
     #[tokio::test]
-    async fn test_clock() {
-        let clock = TestClock::new();
+    async fn mock_clock() {
+        let clock = MockClock::new();
         let clock_cloned = clock.clone();
         assert_eq!(clock.now(), Utc.timestamp_millis_opt(0).unwrap());
         assert_eq!(clock_cloned.now(), Utc.timestamp_millis_opt(0).unwrap());
 
-        clock.advance(500);
+        clock.advance(Duration::milliseconds(500));
         assert_eq!(clock.now(), Utc.timestamp_millis_opt(500).unwrap());
         assert_eq!(clock_cloned.now(), Utc.timestamp_millis_opt(500).unwrap());
 
-        clock.advance(500);
+        clock.advance(Duration::milliseconds(500));
         assert_eq!(clock.now(), Utc.timestamp_millis_opt(1000).unwrap());
         assert_eq!(clock_cloned.now(), Utc.timestamp_millis_opt(1000).unwrap());
 
@@ -141,17 +181,27 @@ pub mod test {
 
         assert!(rx1.try_recv().is_err());
         assert!(rx2.try_recv().is_err());
-        let sleep = Duration::hours(1);
-        clock.advance(sleep.num_milliseconds());
+        clock.advance(Duration::hours(1));
         assert!(rx1.try_recv().is_err());
         assert!(rx2.try_recv().is_err());
 
-        let sleep = Duration::hours(1);
-        clock.advance(sleep.num_milliseconds());
+        clock.advance(Duration::hours(1));
 
         // Yield to make sure other tasks can complete
         tokio::task::yield_now().await;
         assert!(rx1.try_recv().is_ok());
         assert!(rx2.try_recv().is_ok());
     }
+
+    #[tokio::test]
+    async fn sleep_completes_if_clock_moved_before_first_poll() {
+        let clock = MockClock::new();
+        let sleep = clock.sleep(std::time::Duration::from_mins(1));
+        clock.set(Utc.timestamp_opt(60, 0).unwrap());
+        // Would hang if the move of the clock was missed
+        sleep.await;
+
+        // Sleeping for nothing completes right away
+        clock.sleep(std::time::Duration::ZERO).await;
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -17,6 +19,10 @@ pub struct LeaseEntry {
     pub last_renewal_time: DateTime<Utc>,
     pub failed_revocation_attempts: u32,
     pub namespace_id: String,
+    /// Max TTL in milliseconds of the role the lease was issued for.
+    pub role_max_ttl: Option<i64>,
+    /// Tags attached to the lease when it was issued, stored as a JSON object.
+    pub tags: String,
 }
 
 impl LeaseEntry {
@@ -53,9 +59,41 @@ impl LeaseEntry {
             last_renewal_time,
             failed_revocation_attempts: 0,
             namespace_id,
+            role_max_ttl: None,
+            tags: "{}".to_string(),
         })
     }
 
+    /// Bound the lease by the max TTL of the role it is issued for.
+    #[must_use]
+    pub fn with_role_max_ttl(mut self, max_ttl: Option<std::time::Duration>) -> Self {
+        self.role_max_ttl =
+            max_ttl.map(|max_ttl| i64::try_from(max_ttl.as_millis()).unwrap_or(i64::MAX));
+        self
+    }
+
+    /// Max TTL of the role the lease was issued for.
+    #[must_use]
+    pub fn role_max_ttl(&self) -> Option<std::time::Duration> {
+        self.role_max_ttl
+            .map(|millis| std::time::Duration::from_millis(u64::try_from(millis).unwrap_or(0)))
+    }
+
+    /// Tag the lease, e.g. with the team or application the leased
+    /// credentials are issued for.
+    pub fn with_tags(mut self, tags: &BTreeMap<String, String>) -> Result<Self, Error> {
+        self.tags = serde_json::to_string(tags)
+            .map_err(|_| ErrorType::BadData("Unable to serialize lease tags".into()))?;
+        Ok(self)
+    }
+
+    /// Tags attached to the lease.
+    #[must_use]
+    pub fn tags(&self) -> BTreeMap<String, String> {
+        // TODO: tags that can't be deserialized should be reported
+        serde_json::from_str(&self.tags).unwrap_or_default()
+    }
+
     #[must_use]
     pub fn id(&self) -> &str {
         &self.id
@@ -1,19 +1,21 @@
 pub mod clock;
 mod lease;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use covert_types::auth::AuthPolicy;
 use covert_types::error::ApiError;
 use covert_types::methods::psql::RenewLeaseResponse;
 use covert_types::methods::RenewLeaseParams;
+use covert_types::mount::MountConfig;
 use covert_types::request::{Operation, Request};
 use covert_types::state::StorageState;
-use covert_types::ttl::calculate_ttl;
-use futures::stream::FuturesOrdered;
+use covert_types::token::Token;
+use covert_types::ttl::{compute_ttl, TtlLimits};
 use futures::{Future, StreamExt};
 use hyper::http;
 use tokio::sync::RwLock;
@@ -24,6 +26,7 @@ use uuid::Uuid;
 
 use crate::error::{Error, ErrorType};
 use crate::repos::Repos;
+use crate::system::RevokeTokenParams;
 
 use self::clock::Clock;
 pub use self::lease::LeaseEntry;
@@ -52,6 +55,12 @@ pub struct ExpirationManager {
     revocation_worker_concurrency: usize,
     /// Provides time information. Gives us deterministic time in tests.
     clock: Arc<dyn Clock>,
+    /// Max TTL of any lease
+    max_lease_ttl: Option<std::time::Duration>,
+    /// Number of leases revoked
+    revocations: AtomicU64,
+    /// Number of failed attempts to revoke a lease
+    revocation_failures: AtomicU64,
 }
 
 impl ExpirationManager {
@@ -70,9 +79,71 @@ impl ExpirationManager {
             revocation_timeout: std::time::Duration::from_secs(10),
             revocation_worker_concurrency: 100,
             clock: Arc::new(clock),
+            max_lease_ttl: None,
+            revocations: AtomicU64::new(0),
+            revocation_failures: AtomicU64::new(0),
         }
     }
 
+    /// Bound the TTL of all leases by a server wide max TTL.
+    #[must_use]
+    pub fn with_max_lease_ttl(mut self, max_lease_ttl: Option<std::time::Duration>) -> Self {
+        self.max_lease_ttl = max_lease_ttl;
+        self
+    }
+
+    /// Max TTL of any lease issued by the server.
+    #[must_use]
+    pub fn max_lease_ttl(&self) -> Option<std::time::Duration> {
+        self.max_lease_ttl
+    }
+
+    /// Compute the TTL of a lease issued at `issued_at` from now. The TTL is
+    /// bounded by the max TTL of the role, the mount and the server, see
+    /// [`compute_ttl`].
+    pub fn compute_ttl(
+        &self,
+        issued_at: DateTime<Utc>,
+        mount_config: &MountConfig,
+        requested: Option<std::time::Duration>,
+        role_max_ttl: Option<std::time::Duration>,
+    ) -> Result<Duration, Error> {
+        let limits = TtlLimits {
+            system_max: self.max_lease_ttl,
+            role_max: role_max_ttl,
+        };
+        compute_ttl(
+            self.clock.now(),
+            issued_at,
+            mount_config,
+            requested,
+            &limits,
+        )
+        .map_err(|error| {
+            ErrorType::InternalError(anyhow::Error::msg(format!(
+                "Failed to calculate TTL: {error}"
+            )))
+            .into()
+        })
+    }
+
+    /// Number of leases revoked.
+    pub(crate) fn revocations(&self) -> u64 {
+        self.revocations.load(Ordering::Relaxed)
+    }
+
+    /// Number of failed attempts to revoke a lease.
+    pub(crate) fn revocation_failures(&self) -> u64 {
+        self.revocation_failures.load(Ordering::Relaxed)
+    }
+
+    /// Current time according to the clock of the expiration manager. Lease
+    /// TTLs must be calculated from this time.
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
     /// Register a new [`LeaseEntry`].
     ///
     /// This is the only way to register new leases, leases should *not* be inserted
@@ -84,50 +155,104 @@ impl ExpirationManager {
         Ok(())
     }
 
-    /// Revoke all leases issued by mounts under a given path prefix.
+    /// Revoke all leases issued by mounts under a given path prefix that have
+    /// all the given tags.
     pub async fn revoke_leases_by_mount_prefix(
         &self,
         prefix: &str,
         namespace_id: &str,
+        tags: &BTreeMap<String, String>,
     ) -> Result<Vec<LeaseEntry>, Error> {
         let leases = self
             .repos
             .lease
-            .list_by_mount_prefix(prefix, namespace_id)
+            .list_by_mount_prefix(prefix, namespace_id, tags)
             .await?;
 
-        let mut revoke_futures = FuturesOrdered::new();
-
-        for lease in leases {
-            revoke_futures
-                .push_back(async move { self.revoke_lease_entry(&lease).await.map(|_| lease) });
+        let mut revoked_leases = Vec::with_capacity(leases.len());
+        for le in leases {
+            if let Err(error) = self.revoke_lease_entry(&le).await {
+                tracing::error!(?error, lease_id = le.id, prefix, "Aborted revoking leases");
+                return Err(ErrorType::RevokeLeasePrefix {
+                    source: Box::new(error),
+                    lease_id: le.id,
+                    prefix: prefix.to_string(),
+                    revoked: revoked_leases.len(),
+                }
+                .into());
+            }
+            revoked_leases.push(le);
         }
 
-        let revoked_leases = revoke_futures
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .filter_map(|res| match res {
-                Ok(le) => Some(le),
+        Ok(revoked_leases)
+    }
+
+    /// Remove all leases issued by mounts under a given path prefix, also
+    /// those the backend fails to revoke. Meant for when the backend is
+    /// permanently gone, the credentials of the leases that failed to be
+    /// revoked may still be valid in the external system.
+    ///
+    /// Returns the removed leases and those of them the backend failed to
+    /// revoke.
+    pub async fn force_revoke_leases_by_mount_prefix(
+        &self,
+        prefix: &str,
+        namespace_id: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<(Vec<LeaseEntry>, Vec<LeaseEntry>), Error> {
+        let leases = self
+            .repos
+            .lease
+            .list_by_mount_prefix(prefix, namespace_id, tags)
+            .await?;
+
+        let mut backend_failures = vec![];
+        for le in &leases {
+            match self.send_lease_revoke_request(le).await {
+                Ok(()) => {
+                    self.revocations.fetch_add(1, Ordering::Relaxed);
+                }
                 Err(error) => {
-                    tracing::error!(?error, "Failed to revoke lease");
-                    None
+                    self.revocation_failures.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        ?error,
+                        lease_id = le.id,
+                        "Backend failed to revoke lease, removing it anyway. The credentials of the lease may still be valid."
+                    );
+                    backend_failures.push(le.clone());
                 }
-            })
-            .collect::<Vec<_>>();
+            }
+            self.repos.lease.delete(&le.id, &le.namespace_id).await?;
+        }
 
-        Ok(revoked_leases)
+        Ok((leases, backend_failures))
     }
 
-    /// List all leases issued by mounts under a given path prefix.
+    /// List all leases issued by mounts under a given path prefix that have
+    /// all the given tags.
     pub async fn list_by_mount_prefix(
         &self,
         prefix: &str,
         namespace_id: &str,
+        tags: &BTreeMap<String, String>,
     ) -> Result<Vec<LeaseEntry>, Error> {
         self.repos
             .lease
-            .list_by_mount_prefix(prefix, namespace_id)
+            .list_by_mount_prefix(prefix, namespace_id, tags)
+            .await
+    }
+
+    /// Count the leases issued by mounts under a given path prefix that have
+    /// all the given tags.
+    pub async fn count_by_mount_prefix(
+        &self,
+        prefix: &str,
+        namespace_id: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<u64, Error> {
+        self.repos
+            .lease
+            .count_by_mount_prefix(prefix, namespace_id, tags)
             .await
     }
 
@@ -164,6 +289,92 @@ impl ExpirationManager {
             })
     }
 
+    /// Revoke a token together with the leases tracking it and the tokens
+    /// issued with it.
+    pub async fn revoke_token(&self, token: &Token, namespace_id: &str) -> Result<(), Error> {
+        self.revoke_token_tree(token, namespace_id, true).await
+    }
+
+    /// Revoke a token and its children, depth-first.
+    ///
+    /// The whole tree is marked as being revoked before anything is removed,
+    /// so none of the tokens can be used anymore and an interrupted revocation
+    /// is picked up by [`ExpirationManager::tidy_tokens`]. Each token is only
+    /// removed after the leases tracking it and all of its children, so a
+    /// crash never leaves a live token with a removed parent.
+    ///
+    /// `revoke_own_leases` is false when the token is revoked because the
+    /// lease tracking it is being revoked, the lease is removed by the caller.
+    pub(crate) async fn revoke_token_tree(
+        &self,
+        token: &Token,
+        namespace_id: &str,
+        revoke_own_leases: bool,
+    ) -> Result<(), Error> {
+        let tree = self.repos.token.mark_revoking(token, namespace_id).await?;
+        for (child, child_namespace_id) in tree {
+            if revoke_own_leases || child != *token {
+                let leases = self
+                    .repos
+                    .lease
+                    .list_by_token(&child.to_string(), &child_namespace_id)
+                    .await?;
+                for le in leases {
+                    self.revoke_lease_entry(&le).await?;
+                }
+            }
+            self.repos.token.remove(&child, &child_namespace_id).await?;
+        }
+
+        // Tokens are not required to have a lease (e.g. the root token), so
+        // make sure it is removed even if it was not found above.
+        self.repos.token.remove(token, namespace_id).await?;
+        Ok(())
+    }
+
+    /// Complete the token revocations that were interrupted. Returns the
+    /// number of token trees that were revoked.
+    pub async fn tidy_tokens(&self) -> Result<usize, Error> {
+        let tokens = self.repos.token.list_revoking().await?;
+        let count = tokens.len();
+        for (token, namespace_id) in tokens {
+            self.revoke_token(&token, &namespace_id).await?;
+        }
+        Ok(count)
+    }
+
+    /// Renew the lease tracking a token. `increment` is how long the token
+    /// should live from now, see [`ExpirationManager::renew_lease_entry`].
+    pub async fn renew_token(
+        &self,
+        token: &Token,
+        namespace_id: &str,
+        increment: Option<std::time::Duration>,
+    ) -> Result<LeaseEntry, Error> {
+        let le = self
+            .repos
+            .lease
+            .list_by_token(&token.to_string(), namespace_id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorType::BadRequest("Token does not expire".to_string()))?;
+        self.renew_lease_entry(&le.id, namespace_id, increment)
+            .await
+    }
+
+    /// Tokens issued as non-renewable cannot have the lease tracking them
+    /// renewed.
+    async fn check_token_renewable(&self, le: &LeaseEntry) -> Result<(), Error> {
+        let Ok(params) = serde_json::from_str::<RevokeTokenParams>(&le.renew_data) else {
+            return Ok(());
+        };
+        match self.repos.token.lookup(&params.token).await? {
+            Some(te) if !te.renewable => Err(ErrorType::TokenNotRenewable.into()),
+            _ => Ok(()),
+        }
+    }
+
     /// Send a revoke request to the backend that is resposible for revoking the
     /// leased data.
     #[tracing::instrument(skip_all, fields(lease_id = le.id, issued_mount_path = le.issued_mount_path))]
@@ -209,13 +420,15 @@ impl ExpirationManager {
         }
     }
 
-    /// Renew a lease by its id.
+    /// Renew a lease by its id. The lease will expire `increment` from now,
+    /// defaulting to the default lease TTL of the mount, but never later than
+    /// the max TTL allows.
     #[allow(clippy::too_many_lines)]
     pub async fn renew_lease_entry(
         &self,
         lease_id: &str,
         namespace_id: &str,
-        ttl: Option<std::time::Duration>,
+        increment: Option<std::time::Duration>,
     ) -> Result<LeaseEntry, Error> {
         let mut le = self
             .repos
@@ -233,12 +446,12 @@ impl ExpirationManager {
             })?
             .config;
 
-        let ttl =
-            calculate_ttl(self.clock.now(), le.issued_at, &mount_config, ttl).map_err(|_| {
-                ErrorType::InternalError(anyhow::Error::msg(
-                    "Failed to calculate TTL when renewing lease",
-                ))
-            })?;
+        // Token leases are renewed by the system backend
+        if le.renew_path.is_none() {
+            self.check_token_renewable(&le).await?;
+        }
+
+        let ttl = self.compute_ttl(le.issued_at, &mount_config, increment, le.role_max_ttl())?;
 
         let ns = self
             .repos
@@ -292,17 +505,12 @@ impl ExpirationManager {
                     ErrorType::InternalError(anyhow::Error::msg("Unexpected renew response"))
                 })?;
 
-                let ttl = calculate_ttl(
-                    self.clock.now(),
+                let ttl = self.compute_ttl(
                     le.issued_at,
                     &mount_config,
                     Some(resp.ttl),
-                )
-                .map_err(|_| {
-                    ErrorType::InternalError(anyhow::Error::msg(
-                        "Failed to calculate TTL when renewing lease",
-                    ))
-                })?;
+                    le.role_max_ttl(),
+                )?;
 
                 let now = self.clock.now();
 
@@ -337,6 +545,11 @@ impl ExpirationManager {
         let mut shutdown_rx = self.shutdown_rx.write().await;
 
         loop {
+            // Stop before dequeuing more work, the leases of the last batch
+            // have all been revoked or rescheduled at this point
+            if shutdown_rx.try_recv().is_ok() {
+                break;
+            }
             let now = self.clock.now();
             #[allow(clippy::cast_possible_truncation)]
             let leases = match self
@@ -405,6 +618,7 @@ impl ExpirationManager {
         let res = self.send_lease_revoke_request(le).await;
         match res {
             Ok(_) => {
+                self.revocations.fetch_add(1, Ordering::Relaxed);
                 self.repos
                     .lease
                     .delete(&le.id, &le.namespace_id)
@@ -419,6 +633,7 @@ impl ExpirationManager {
                     .map(|_| ())
             }
             Err(error) => {
+                self.revocation_failures.fetch_add(1, Ordering::Relaxed);
                 error!(?error, "failed to revoke lease entry from backend");
                 // TODO: why is +1 needed here
                 if le.failed_revocation_attempts + 1 >= self.revocation_max_retries {
@@ -447,27 +662,32 @@ impl ExpirationManager {
         }
     }
 
-    /// Shutdown the expiration manager.
+    /// Shutdown the expiration manager. Waits for the revocations in flight to
+    /// finish, no new leases are dequeued after this is called.
     #[tracing::instrument(skip(self), name = "stop_expiration_manager")]
     pub async fn stop(&self) {
-        // TODO: wait for expiration manager to shutdown fully.
-        let _ = self.shutdown_tx.send(()).await;
+        // The worker holds the receiver while it is running
+        if self.shutdown_rx.try_write().is_ok() {
+            return;
+        }
+        // A stop is already pending if the channel is full
+        let _ = self.shutdown_tx.try_send(());
+        let _ = self.shutdown_rx.read().await;
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::{DateTime, Utc};
     use covert_framework::{Backend, SyncService};
     use covert_types::{
         backend::{BackendCategory, BackendType},
-        mount::{MountConfig, MountEntry},
+        mount::MountEntry,
         response::Response,
     };
     use sqlx::SqlitePool;
 
     use crate::{
-        expiration_manager::clock::test::TestClock,
+        expiration_manager::clock::MockClock,
         repos::{mount::tests::pool, namespace::Namespace},
         system::SYSTEM_MOUNT_PATH,
     };
@@ -478,7 +698,7 @@ mod tests {
         req: Request,
         recorder: Arc<RequestRecorder>,
         renew_ttl: Option<std::time::Duration>,
-        clock: TestClock,
+        clock: MockClock,
     ) -> Result<Response, ApiError> {
         let mut requests = recorder.0.write().await;
         requests.push(RequestInfo {
@@ -520,7 +740,7 @@ mod tests {
         req: Request,
         recorder: Arc<RequestRecorder>,
         renew_ttl: Option<std::time::Duration>,
-        clock: TestClock,
+        clock: MockClock,
     ) -> Result<Response, ApiError> {
         let mut requests = recorder.0.write().await;
         requests.push(RequestInfo {
@@ -578,14 +798,14 @@ mod tests {
 
     pub struct RequestRecorder(RwLock<Vec<RequestInfo>>);
 
-    async fn advance(clock: &TestClock, duration: Duration) {
-        clock.advance(duration.num_milliseconds());
+    async fn advance(clock: &MockClock, duration: Duration) {
+        clock.advance(duration);
         // Yield and give some time for expiration manager to wake up and revoke
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
     }
 
-    async fn advance_to(clock: &TestClock, duration: DateTime<Utc>) {
-        clock.set(duration.timestamp_millis());
+    async fn advance_to(clock: &MockClock, duration: DateTime<Utc>) {
+        clock.set(duration);
         // Yield and give some time for expiration manager to wake up and revoke
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
     }
@@ -593,7 +813,7 @@ mod tests {
     #[tokio::test]
     async fn revoke_secret_after_ttl_expires() {
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
-        let clock = TestClock::new();
+        let clock = MockClock::new();
 
         let pool = Arc::new(pool().await);
         let u_pool = SqlitePool::connect(":memory:").await.unwrap();
@@ -639,6 +859,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
@@ -688,7 +909,7 @@ mod tests {
 
     #[tokio::test]
     async fn revoke_token_after_ttl_expires() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
 
         let pool = Arc::new(pool().await);
@@ -735,6 +956,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
@@ -781,7 +1003,7 @@ mod tests {
 
     #[tokio::test]
     async fn revoke_before_ttl_expires() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
 
         let pool = Arc::new(pool().await);
@@ -828,6 +1050,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
@@ -876,7 +1099,7 @@ mod tests {
     #[tokio::test]
     #[allow(clippy::too_many_lines)]
     async fn renew() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
 
         let pool = Arc::new(pool().await);
@@ -930,6 +1153,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
@@ -1007,9 +1231,217 @@ mod tests {
         assert_eq!(leases, vec![]);
     }
 
+    #[tokio::test]
+    async fn renewal_is_capped_by_smallest_max_ttl() {
+        let clock = MockClock::new();
+        let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
+
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let router = Arc::new(Router::new(repos.mount.clone()));
+        let exp_m = ExpirationManager::new(Arc::clone(&router), repos.clone(), clock.clone())
+            .with_max_lease_ttl(Some(Duration::hours(5).to_std().unwrap()));
+
+        let me = MountEntry {
+            id: Uuid::new_v4(),
+            backend_type: BackendType::Postgres,
+            config: MountConfig {
+                max_lease_ttl: Duration::hours(24).to_std().unwrap(),
+                ..Default::default()
+            },
+            path: "psql/".into(),
+            namespace_id: ns.id.clone(),
+        };
+        repos.mount.create(&me).await.unwrap();
+
+        // The backend always asks for more than any of the max TTLs
+        let renew_ttl = Duration::hours(10);
+        let recorder_moved = Arc::clone(&recorder);
+        let clock_moved = clock.clone();
+        let handler = SyncService::new(tower::service_fn(move |req| {
+            let recorder = Arc::clone(&recorder_moved);
+            let clock = clock_moved.clone();
+            async move {
+                secret_engine_handle(req, recorder, Some(renew_ttl.to_std().unwrap()), clock).await
+            }
+        }));
+        router.mount(
+            me.id,
+            Arc::new(Backend {
+                category: BackendCategory::Logical,
+                migrations: vec![],
+                paths: vec![],
+                variant: me.backend_type,
+                handler,
+            }),
+        );
+
+        let issued_at = clock.now();
+        let lease = |role_max_ttl: Duration| {
+            LeaseEntry::new(
+                me.path.clone(),
+                Some("creds".into()),
+                &(),
+                Some("creds".into()),
+                &(),
+                issued_at,
+                Duration::hours(1),
+                ns.id.clone(),
+            )
+            .unwrap()
+            .with_role_max_ttl(Some(role_max_ttl.to_std().unwrap()))
+        };
+        // System max wins over the role max
+        let system_capped = lease(Duration::hours(6));
+        // Role max wins over the system max
+        let role_capped = lease(Duration::hours(4));
+        repos.lease.create(&system_capped).await.unwrap();
+        repos.lease.create(&role_capped).await.unwrap();
+
+        clock.advance(Duration::minutes(30));
+        let requested = Some(renew_ttl.to_std().unwrap());
+        let renewed = exp_m
+            .renew_lease_entry(system_capped.id(), &ns.id, requested)
+            .await
+            .unwrap();
+        assert_eq!(renewed.expires_at, issued_at + Duration::hours(5));
+        let renewed = exp_m
+            .renew_lease_entry(role_capped.id(), &ns.id, requested)
+            .await
+            .unwrap();
+        assert_eq!(renewed.expires_at, issued_at + Duration::hours(4));
+
+        // The role max is kept with the lease
+        let stored = repos
+            .lease
+            .lookup(role_capped.id(), &ns.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            stored.role_max_ttl(),
+            Some(Duration::hours(4).to_std().unwrap())
+        );
+        assert_eq!(stored.expires_at, issued_at + Duration::hours(4));
+    }
+
+    #[tokio::test]
+    async fn revoke_restored_leases_after_restart() {
+        let clock = MockClock::new();
+        let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
+
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let router = Arc::new(Router::new(repos.mount.clone()));
+        let me = MountEntry {
+            id: Uuid::new_v4(),
+            backend_type: BackendType::Postgres,
+            config: MountConfig::default(),
+            path: "psql/".into(),
+            namespace_id: ns.id.clone(),
+        };
+        repos.mount.create(&me).await.unwrap();
+
+        let recorder_moved = Arc::clone(&recorder);
+        let clock_moved = clock.clone();
+        let handler = SyncService::new(tower::service_fn(move |req| {
+            let recorder = Arc::clone(&recorder_moved);
+            let clock = clock_moved.clone();
+            async move { secret_engine_handle(req, recorder, None, clock).await }
+        }));
+        router.mount(
+            me.id,
+            Arc::new(Backend {
+                category: BackendCategory::Logical,
+                migrations: vec![],
+                paths: vec![],
+                variant: me.backend_type,
+                handler,
+            }),
+        );
+
+        let lease = |ttl: Duration| {
+            LeaseEntry::new(
+                me.path.clone(),
+                Some("creds".into()),
+                &(),
+                Some("creds".into()),
+                &(),
+                clock.now(),
+                ttl,
+                ns.id.clone(),
+            )
+            .unwrap()
+        };
+        let short = lease(Duration::hours(1));
+        let long = lease(Duration::hours(4));
+
+        let exp_m = Arc::new(ExpirationManager::new(
+            Arc::clone(&router),
+            repos.clone(),
+            clock.clone(),
+        ));
+        let expiration_manager = Arc::clone(&exp_m);
+        let worker = tokio::spawn(async move { expiration_manager.start().await });
+        tokio::task::yield_now().await;
+        exp_m.register(short.clone()).await.unwrap();
+        exp_m.register(long.clone()).await.unwrap();
+
+        // Nothing is revoked while the expiration manager is down
+        exp_m.stop().await;
+        worker.await.unwrap().unwrap();
+        advance_to(&clock, short.expires_at + Duration::minutes(30)).await;
+        assert!(recorder.0.read().await.is_empty());
+        assert_eq!(repos.lease.list().await.unwrap().len(), 2);
+
+        // The expired lease is revoked as soon as the leases are restored
+        let exp_m = Arc::new(ExpirationManager::new(
+            Arc::clone(&router),
+            repos.clone(),
+            clock.clone(),
+        ));
+        let expiration_manager = Arc::clone(&exp_m);
+        tokio::spawn(async move {
+            expiration_manager.start().await.unwrap();
+        });
+        advance(&clock, Duration::zero()).await;
+        assert_eq!(
+            *recorder.0.read().await,
+            vec![RequestInfo {
+                path: "creds".into(),
+                operation: Operation::Revoke,
+                reveived_at: Some(clock.now())
+            }]
+        );
+        assert_eq!(repos.lease.list().await.unwrap(), vec![long.clone()]);
+
+        // And the other one when it expires
+        advance_to(&clock, long.expires_at).await;
+        assert_eq!(recorder.0.read().await.len(), 2);
+        assert_eq!(repos.lease.list().await.unwrap(), vec![]);
+    }
+
     #[tokio::test]
     async fn retry_failed_revocation() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
 
         let pool = Arc::new(pool().await);
@@ -1054,6 +1486,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
@@ -1094,7 +1527,7 @@ mod tests {
 
     #[tokio::test]
     async fn revoke_for_mount() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
 
         let pool = Arc::new(pool().await);
@@ -1140,6 +1573,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
@@ -1166,7 +1600,7 @@ mod tests {
 
         assert_eq!(
             exp_m
-                .revoke_leases_by_mount_prefix(&me.path, &ns.id)
+                .revoke_leases_by_mount_prefix(&me.path, &ns.id, &BTreeMap::new())
                 .await
                 .unwrap()
                 .len(),
@@ -1191,9 +1625,109 @@ mod tests {
         drop(requests);
     }
 
+    #[tokio::test]
+    async fn revoke_for_mount_aborts_unless_forced() {
+        let clock = MockClock::new();
+        let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
+
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let router = Arc::new(Router::new(repos.mount.clone()));
+        let exp_m = Arc::new(ExpirationManager::new(
+            Arc::clone(&router),
+            repos.clone(),
+            clock.clone(),
+        ));
+
+        let me = MountEntry {
+            id: Uuid::new_v4(),
+            backend_type: BackendType::Postgres,
+            config: MountConfig::default(),
+            path: "psql/".into(),
+            namespace_id: ns.id.clone(),
+        };
+        repos.mount.create(&me).await.unwrap();
+
+        let recorder_moved = Arc::clone(&recorder);
+        let clock_moved = clock.clone();
+        let handler = SyncService::new(tower::service_fn(move |req| {
+            let recorder = Arc::clone(&recorder_moved);
+            let clock = clock_moved.clone();
+            async move { secret_engine_handle(req, recorder, None, clock).await }
+        }));
+        let backend = Arc::new(Backend {
+            category: BackendCategory::Logical,
+            migrations: vec![],
+            paths: vec![],
+            variant: me.backend_type,
+            handler,
+        });
+
+        router.mount(me.id, Arc::clone(&backend));
+
+        // The third lease is issued by the mount but it fails to revoke it
+        let ttl = Duration::hours(4);
+        let revoke_paths = ["creds", "creds", "invalid-revoke-path", "creds"];
+        for (i, revoke_path) in revoke_paths.into_iter().enumerate() {
+            let le = LeaseEntry::new(
+                me.path.clone(),
+                Some(revoke_path.into()),
+                &(),
+                Some("creds".into()),
+                &(),
+                clock.now() + Duration::minutes(i64::try_from(i).unwrap()),
+                ttl,
+                ns.id.clone(),
+            )
+            .unwrap();
+            assert!(exp_m.register(le).await.is_ok());
+        }
+
+        // Stops at the first failure
+        let err = exp_m
+            .revoke_leases_by_mount_prefix(&me.path, &ns.id, &BTreeMap::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.variant, ErrorType::RevokeLeasePrefix { revoked: 2, .. }),
+            "{err:?}"
+        );
+        let leases = repos.lease.list().await.unwrap();
+        assert_eq!(leases.len(), 2);
+        assert_eq!(
+            leases[0].revoke_path.as_deref(),
+            Some("invalid-revoke-path")
+        );
+        assert_eq!(recorder.0.read().await.len(), 3);
+
+        // Forced revocation removes the leases the backend fails to revoke
+        let (removed, backend_failures) = exp_m
+            .force_revoke_leases_by_mount_prefix(&me.path, &ns.id, &BTreeMap::new())
+            .await
+            .unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(backend_failures.len(), 1);
+        assert_eq!(
+            backend_failures[0].revoke_path.as_deref(),
+            Some("invalid-revoke-path")
+        );
+        assert_eq!(repos.lease.list().await.unwrap(), vec![]);
+        assert_eq!(recorder.0.read().await.len(), 5);
+        assert_eq!(exp_m.revocation_failures(), 2);
+    }
+
     #[tokio::test]
     async fn slow_revoke_endpoint_does_not_halt_other_revocations() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
         let recorder = Arc::new(RequestRecorder(RwLock::new(Vec::new())));
 
         let pool = Arc::new(pool().await);
@@ -1238,6 +1772,7 @@ mod tests {
         let backend = Arc::new(Backend {
             category: BackendCategory::Logical,
             migrations: vec![],
+            paths: vec![],
             variant: me.backend_type,
             handler,
         });
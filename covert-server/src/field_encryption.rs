@@ -0,0 +1,234 @@
+//! Fields of KV secrets encrypted with the key of a transit mount, see
+//! [`MountConfig::encrypted_fields`]. The fields are encrypted before a write
+//! reaches the KV mount and decrypted in the response to a read if the caller
+//! may decrypt with the key itself. Other callers get the ciphertext.
+//!
+//! Every ciphertext is bound to the KV mount, the secret and the field it was
+//! written to, so it cannot be decrypted after it is copied to another field.
+//! Writes and reads of a field fail while its key is disabled or missing, the
+//! plaintext is never stored.
+
+use std::collections::{BTreeMap, HashMap};
+
+use covert_types::{
+    auth::AuthPolicy,
+    backend::BackendType,
+    methods::transit::{DecryptParams, DecryptResponse, EncryptParams, EncryptResponse},
+    mount::{MountConfig, TransitKey},
+    request::{Operation, Request},
+    response::Response,
+    state::StorageState,
+};
+use hyper::http;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, ErrorType},
+    layer::auth_service::TokenPolicies,
+    repos::namespace::Namespace,
+    router::Router,
+};
+
+/// Path of the secrets below a KV mount.
+const KV_DATA_PATH: &str = "data/";
+
+/// Encryption of the fields of a request to the secrets of a KV mount.
+pub struct FieldEncryption {
+    fields: BTreeMap<String, TransitKey>,
+    /// Path of the KV mount.
+    mount: String,
+    /// Key of the secret below the KV mount.
+    secret: String,
+    operation: Operation,
+    /// The transit mounts are looked up in the namespace of the request.
+    namespace: Vec<String>,
+    ns: Namespace,
+    policies: TokenPolicies,
+}
+
+impl FieldEncryption {
+    /// `None` if the request is not for the secrets of a KV mount that
+    /// encrypts fields. The mount path must already be removed from the path
+    /// of the request.
+    pub fn new(
+        variant: BackendType,
+        mount: &str,
+        config: &MountConfig,
+        req: &Request,
+    ) -> Option<Self> {
+        if variant != BackendType::Kv || config.encrypted_fields.is_empty() {
+            return None;
+        }
+        let secret = req.path.strip_prefix(KV_DATA_PATH)?;
+        Some(Self {
+            fields: config.encrypted_fields.clone(),
+            mount: mount.to_string(),
+            secret: secret.to_string(),
+            operation: req.operation,
+            namespace: req.namespace.clone(),
+            ns: req.extensions.get::<Namespace>()?.clone(),
+            policies: req
+                .extensions
+                .get::<TokenPolicies>()
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Replace the plaintext of the encrypted fields written by the request
+    /// with their ciphertext.
+    pub async fn encrypt_request(&self, router: &Router, req: &mut Request) -> Result<(), Error> {
+        if !matches!(
+            self.operation,
+            Operation::Create | Operation::Update | Operation::Patch
+        ) {
+            return Ok(());
+        }
+        // Malformed bodies are left for the KV mount to reject
+        let Ok(mut body) = serde_json::from_slice::<Value>(&req.data) else {
+            return Ok(());
+        };
+        let Some(data) = body.get_mut("data").and_then(Value::as_object_mut) else {
+            return Ok(());
+        };
+
+        let mut encrypted = false;
+        for (field, value) in data.iter_mut() {
+            let (Some(transit), Value::String(plaintext)) = (self.fields.get(field), &*value)
+            else {
+                continue;
+            };
+            let params = EncryptParams {
+                plaintext: base64::encode(plaintext),
+                context: Some(self.context(field)),
+            };
+            let resp: EncryptResponse = self
+                .call_transit(router, transit, "encrypt", &params)
+                .await
+                .map_err(|message| ErrorType::FieldEncryption {
+                    field: field.clone(),
+                    message,
+                })?;
+            *value = Value::String(resp.ciphertext);
+            encrypted = true;
+        }
+
+        if encrypted {
+            req.data = serde_json::to_vec(&body)
+                .map_err(ErrorType::BadResponseData)?
+                .into();
+        }
+        Ok(())
+    }
+
+    /// Decrypt the encrypted fields of a read secret that the caller may
+    /// decrypt. The other fields keep their ciphertext.
+    pub async fn decrypt_response(
+        &self,
+        router: &Router,
+        response: &mut Response,
+    ) -> Result<(), Error> {
+        if self.operation != Operation::Read {
+            return Ok(());
+        }
+        let Response::Raw(body) = response else {
+            return Ok(());
+        };
+        let Some(data) = body.get_mut("data").and_then(Value::as_object_mut) else {
+            return Ok(());
+        };
+
+        for (field, value) in data.iter_mut() {
+            let (Some(transit), Value::String(ciphertext)) = (self.fields.get(field), &*value)
+            else {
+                continue;
+            };
+            if !self.may_decrypt(transit) {
+                continue;
+            }
+            let params = DecryptParams {
+                ciphertext: ciphertext.clone(),
+                context: Some(self.context(field)),
+            };
+            let plaintext = self
+                .call_transit::<DecryptResponse>(router, transit, "decrypt", &params)
+                .await
+                .and_then(|resp| {
+                    base64::decode(resp.plaintext)
+                        .ok()
+                        .and_then(|plaintext| String::from_utf8(plaintext).ok())
+                        .ok_or_else(|| "The plaintext is not valid UTF-8".to_string())
+                })
+                .map_err(|message| ErrorType::FieldDecryption {
+                    field: field.clone(),
+                    message,
+                })?;
+            *value = Value::String(plaintext);
+        }
+        Ok(())
+    }
+
+    /// Base64 encoded context the field is encrypted with, which binds the
+    /// ciphertext to the mount, the secret and the field.
+    fn context(&self, field: &str) -> String {
+        let context = serde_json::json!([self.mount, self.secret, field]);
+        base64::encode(context.to_string())
+    }
+
+    /// Whether the caller could decrypt with the key on the transit mount.
+    fn may_decrypt(&self, transit: &TransitKey) -> bool {
+        let path = format!(
+            "{}/{}decrypt/{}",
+            self.namespace.join("/"),
+            transit.mount,
+            transit.key
+        );
+        self.policies.allow(&path, Operation::Update)
+    }
+
+    /// Call the transit mount on behalf of the caller. Only mounts of the
+    /// transit engine are called so the mount config cannot be used to make
+    /// requests to other mounts.
+    async fn call_transit<T: DeserializeOwned>(
+        &self,
+        router: &Router,
+        transit: &TransitKey,
+        action: &str,
+        params: &impl Serialize,
+    ) -> Result<T, String> {
+        let path = format!("{}{action}/{}", transit.mount, transit.key);
+        let backend_type = router
+            .backend_type(&path, &self.ns.id)
+            .await
+            .map_err(|err| err.variant.to_string())?;
+        if backend_type != Some(BackendType::Transit) {
+            return Err(format!("`{}` is not a transit mount", transit.mount));
+        }
+
+        let mut extensions = http::Extensions::new();
+        extensions.insert(AuthPolicy::Authenticated);
+        extensions.insert(StorageState::Unsealed);
+        extensions.insert(self.ns.clone());
+        let req = Request {
+            id: Uuid::new_v4(),
+            namespace: self.namespace.clone(),
+            operation: Operation::Update,
+            path,
+            data: serde_json::to_vec(params)
+                .map_err(|err| err.to_string())?
+                .into(),
+            extensions,
+            token: None,
+            params: Vec::default(),
+            query_string: String::default(),
+            headers: HashMap::default(),
+        };
+        let resp = router
+            .route_internal(req)
+            .await
+            .map_err(|err| err.error.to_string())?;
+        resp.response.data().map_err(|err| err.error.to_string())
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use anyhow::Context as _;
+use covert_types::{methods::system::HaStatus, state::StorageState};
+use tokio::{
+    sync::{Mutex, MutexGuard},
+    time::MissedTickBehavior,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    context::Context,
+    error::Error,
+    repos::ha::{HaLock, HaLockRepo},
+    HaConfig,
+};
+
+/// Role of the node in an HA cluster, see [`HaConfig`]. Outside of HA mode the
+/// node is always active.
+pub struct HaState {
+    config: Option<HaConfig>,
+    node_id: Uuid,
+    active: AtomicBool,
+    /// Write index the state of a performance standby caught up to.
+    replicated_index: AtomicU64,
+    /// Held while the node is unsealed, promoted or steps down, so the
+    /// expiration manager is started and stopped in order.
+    transition: Mutex<()>,
+    /// Client forwarding the requests of a standby to the active node.
+    client: Option<reqwest::Client>,
+}
+
+impl HaState {
+    pub fn new(config: Option<HaConfig>) -> anyhow::Result<Self> {
+        let client = config.as_ref().map(forward_client).transpose()?;
+        Ok(Self {
+            config,
+            node_id: Uuid::new_v4(),
+            active: AtomicBool::new(false),
+            replicated_index: AtomicU64::new(0),
+            transition: Mutex::new(()),
+            client,
+        })
+    }
+
+    pub fn config(&self) -> Option<&HaConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Whether the node serves requests itself.
+    pub fn is_active(&self) -> bool {
+        self.config.is_none() || self.active.load(Ordering::SeqCst)
+    }
+
+    /// Whether the node serves reads while it is a standby.
+    pub fn serves_reads(&self) -> bool {
+        self.config
+            .as_ref()
+            .is_some_and(|config| config.performance_standby)
+    }
+
+    /// Write index the state of the node caught up to while it is a
+    /// performance standby.
+    pub fn replicated_index(&self) -> u64 {
+        self.replicated_index.load(Ordering::SeqCst)
+    }
+
+    pub fn client(&self) -> Option<&reqwest::Client> {
+        self.client.as_ref()
+    }
+
+    pub async fn transition(&self) -> MutexGuard<'_, ()> {
+        self.transition.lock().await
+    }
+
+    /// The active node, unless this node is the active node or no node holds
+    /// the lock.
+    pub async fn active_node(&self, repo: &HaLockRepo) -> Result<Option<HaLock>, Error> {
+        let node_id = self.node_id.to_string();
+        Ok(repo.holder().await?.filter(|lock| lock.node_id != node_id))
+    }
+}
+
+fn forward_client(config: &HaConfig) -> anyhow::Result<reqwest::Client> {
+    // Redirects are for the client to follow
+    let mut builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    if let Some(ca_file) = &config.forward_ca_file {
+        let pem = std::fs::read(ca_file).with_context(|| {
+            format!("ha.forward-ca-file: failed to read `{}`", ca_file.display())
+        })?;
+        for cert in reqwest::Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// HA role of the node reported by `sys/status`, unset outside of HA mode.
+pub async fn status(ctx: &Context) -> Result<Option<HaStatus>, Error> {
+    if !ctx.ha.enabled() {
+        return Ok(None);
+    }
+    let holder = ctx.repos.ha.holder().await?;
+    Ok(Some(HaStatus {
+        node_id: ctx.ha.node_id.to_string(),
+        active: ctx.ha.is_active(),
+        performance_standby: ctx.ha.serves_reads(),
+        active_address: holder.map(|lock| lock.api_address),
+    }))
+}
+
+/// Acquire and renew the HA lock for as long as the server runs. The node is
+/// promoted when it acquires the lock and steps down when it fails to renew
+/// it. Sealed nodes don't take part in the election, sealing steps down.
+/// Performance standbys catch up with the active node on every heartbeat.
+pub async fn elect(ctx: Context) {
+    let Some(config) = ctx.ha.config().cloned() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(config.heartbeat_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if ctx.repos.pool.state() != StorageState::Unsealed {
+            continue;
+        }
+
+        let acquired = match ctx
+            .repos
+            .ha
+            .try_acquire(ctx.ha.node_id, &config.api_address, config.lock_ttl)
+            .await
+        {
+            Ok(acquired) => acquired,
+            Err(error) => {
+                warn!(?error, "Failed to acquire the HA lock");
+                false
+            }
+        };
+        let active = ctx.ha.active.load(Ordering::SeqCst);
+        if acquired && !active {
+            if let Err(error) = promote(&ctx).await {
+                error!(?error, "Failed to take over as the active node");
+                step_down(&ctx).await;
+            }
+        } else if !acquired && active {
+            warn!("Lost the HA lock, stepping down to standby");
+            step_down(&ctx).await;
+        }
+
+        if config.performance_standby {
+            if let Err(error) = replicate(&ctx).await {
+                warn!(?error, "Failed to catch up with the active node");
+            }
+        }
+    }
+}
+
+/// Load the changes of the active node if the write index moved since the
+/// last time, so reads served by a performance standby see them.
+async fn replicate(ctx: &Context) -> Result<(), Error> {
+    let _transition = ctx.ha.transition().await;
+    if ctx.repos.pool.state() != StorageState::Unsealed || ctx.ha.active.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    // Read before catching up, changes made meanwhile are loaded next time
+    let index = ctx.repos.write_index.current().await?;
+    if index == ctx.ha.replicated_index() {
+        return Ok(());
+    }
+    crate::system::replicate(ctx).await?;
+    ctx.ha.replicated_index.store(index, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Reload the state the node may have missed as a standby and start revoking
+/// leases.
+async fn promote(ctx: &Context) -> Result<(), Error> {
+    let _transition = ctx.ha.transition().await;
+    if ctx.repos.pool.state() != StorageState::Unsealed {
+        return Ok(());
+    }
+    info!(node_id = %ctx.ha.node_id, "Acquired the HA lock, taking over as the active node");
+    crate::system::restore(ctx).await?;
+    ctx.ha.active.store(true, Ordering::SeqCst);
+    crate::system::start_expiration_manager(ctx);
+    Ok(())
+}
+
+/// Stop revoking leases and release the lock if the node holds it, so a
+/// standby can take over right away.
+pub async fn step_down(ctx: &Context) {
+    if !ctx.ha.enabled() {
+        return;
+    }
+    let _transition = ctx.ha.transition().await;
+    if ctx.ha.active.swap(false, Ordering::SeqCst) {
+        ctx.expiration_manager.stop().await;
+    }
+    // Retries are sent to the new active node, which has its own results
+    ctx.idempotency.clear();
+    if let Err(error) = ctx.repos.ha.release(ctx.ha.node_id).await {
+        warn!(?error, "Failed to release the HA lock");
+    }
+}
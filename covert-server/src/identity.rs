@@ -0,0 +1,217 @@
+use std::sync::RwLock;
+
+use aes_gcm::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, Aes256Gcm, KeyInit, Nonce,
+};
+use chrono::{DateTime, Utc};
+use covert_types::{
+    methods::system::{IdentitySnapshot, ImportEntity, IDENTITY_SNAPSHOT_VERSION},
+    policy::PathPolicy,
+};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::error::{Error, ErrorType};
+
+const NONCE_LEN: usize = 12;
+
+/// Context of the key derivation, the snapshot key is only used for snapshots.
+const SNAPSHOT_KEY_INFO: &[u8] = b"covert identity snapshot";
+
+/// Contents of an identity snapshot before it is encrypted.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityStore {
+    pub policies: Vec<SnapshotPolicy>,
+    pub entities: Vec<ImportEntity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotPolicy {
+    pub name: String,
+    pub paths: Vec<PathPolicy>,
+}
+
+/// Key the identity snapshots are encrypted with. It is derived from the
+/// master key on unseal and forgotten on seal, so snapshots can only be
+/// created and imported while unsealed with the same master key.
+#[derive(Default)]
+pub struct SnapshotKey(RwLock<Option<Zeroizing<[u8; 32]>>>);
+
+impl SnapshotKey {
+    pub fn derive(&self, master_key: &str) {
+        let mut key = Zeroizing::new([0; 32]);
+        Hkdf::<Sha256>::new(None, master_key.as_bytes())
+            .expand(SNAPSHOT_KEY_INFO, key.as_mut())
+            .expect("32 bytes is a valid output length of HKDF-SHA256");
+        *self
+            .0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(key);
+    }
+
+    pub fn clear(&self) {
+        *self
+            .0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, Error> {
+        self.0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .map(|key| Aes256Gcm::new(key.as_ref().into()))
+            .ok_or_else(|| {
+                ErrorType::InternalError(anyhow::Error::msg("Snapshot key is not derived")).into()
+            })
+    }
+
+    pub fn encrypt(
+        &self,
+        store: &IdentityStore,
+        now: DateTime<Utc>,
+    ) -> Result<IdentitySnapshot, Error> {
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(store).map_err(|err| ErrorType::InternalError(err.into()))?,
+        );
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = associated_data(IDENTITY_SNAPSHOT_VERSION, now);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                ErrorType::InternalError(anyhow::Error::msg("Failed to encrypt the snapshot"))
+            })?;
+
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        Ok(IdentitySnapshot {
+            version: IDENTITY_SNAPSHOT_VERSION,
+            created_at: now,
+            data: hex::encode(data),
+        })
+    }
+
+    pub fn decrypt(&self, snapshot: &IdentitySnapshot) -> Result<IdentityStore, Error> {
+        if snapshot.version != IDENTITY_SNAPSHOT_VERSION {
+            return Err(ErrorType::InvalidIdentitySnapshot(format!(
+                "unsupported version {}, expected {IDENTITY_SNAPSHOT_VERSION}",
+                snapshot.version
+            ))
+            .into());
+        }
+        let data = hex::decode(&snapshot.data)
+            .map_err(|_| ErrorType::InvalidIdentitySnapshot("data is not hex encoded".into()))?;
+        if data.len() < NONCE_LEN {
+            return Err(ErrorType::InvalidIdentitySnapshot("data is truncated".into()).into());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let aad = associated_data(snapshot.version, snapshot.created_at);
+        let plaintext = Zeroizing::new(
+            self.cipher()?
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: aad.as_bytes(),
+                    },
+                )
+                .map_err(|_| {
+                    ErrorType::InvalidIdentitySnapshot(
+                        "it was not created with the master key of this server or was modified"
+                            .into(),
+                    )
+                })?,
+        );
+        serde_json::from_slice(&plaintext).map_err(|err| {
+            ErrorType::InvalidIdentitySnapshot(format!("malformed contents: {err}")).into()
+        })
+    }
+}
+
+/// The version and creation time are authenticated with the contents.
+fn associated_data(version: u32, created_at: DateTime<Utc>) -> String {
+    format!("{version}:{}", created_at.timestamp_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use covert_types::{entity::EntityAlias, request::Operation};
+
+    use super::*;
+
+    fn store() -> IdentityStore {
+        IdentityStore {
+            policies: vec![SnapshotPolicy {
+                name: "reader".into(),
+                paths: vec![PathPolicy {
+                    path: "kv/*".into(),
+                    operations: vec![Operation::Read],
+                }],
+            }],
+            entities: vec![ImportEntity {
+                name: "foo".into(),
+                metadata: HashMap::from([("team".into(), "web".into())]),
+                policies: vec!["reader".into()],
+                aliases: vec![EntityAlias {
+                    name: "foo".into(),
+                    mount_path: "auth/userpass/".into(),
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = SnapshotKey::default();
+        key.derive("master key");
+        let snapshot = key.encrypt(&store(), Utc::now()).unwrap();
+        assert_eq!(snapshot.version, IDENTITY_SNAPSHOT_VERSION);
+        assert_eq!(key.decrypt(&snapshot).unwrap(), store());
+
+        // The key is derived from the master key only
+        let other = SnapshotKey::default();
+        other.derive("master key");
+        assert_eq!(other.decrypt(&snapshot).unwrap(), store());
+
+        other.derive("another master key");
+        assert!(matches!(
+            other.decrypt(&snapshot).unwrap_err().variant,
+            ErrorType::InvalidIdentitySnapshot(_)
+        ));
+
+        other.clear();
+        assert!(other.decrypt(&snapshot).is_err());
+    }
+
+    #[test]
+    fn reject_modified_snapshots() {
+        let key = SnapshotKey::default();
+        key.derive("master key");
+        let snapshot = key.encrypt(&store(), Utc::now()).unwrap();
+
+        let mut modified = snapshot.clone();
+        modified.created_at += chrono::Duration::seconds(1);
+        assert!(key.decrypt(&modified).is_err());
+
+        let mut modified = snapshot.clone();
+        modified.version += 1;
+        assert!(key.decrypt(&modified).is_err());
+
+        let mut modified = snapshot;
+        modified.data.truncate(10);
+        assert!(key.decrypt(&modified).is_err());
+    }
+}
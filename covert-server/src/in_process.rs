@@ -0,0 +1,83 @@
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use hyper::Body;
+use tower::{util::BoxCloneService, Service, ServiceExt};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Answers requests in the same process, without binding a port, e.g. with
+/// the in-process transport of the SDK in tests. Sent on
+/// [`Config::service_tx`](crate::Config::service_tx) once the server started.
+///
+/// Takes the request and response types of `http` 1.0, which the SDK uses.
+#[derive(Clone)]
+pub struct InProcessService {
+    inner: BoxCloneService<hyper::Request<Body>, hyper::Response<Body>, BoxError>,
+}
+
+impl std::fmt::Debug for InProcessService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InProcessService").finish_non_exhaustive()
+    }
+}
+
+impl InProcessService {
+    pub(crate) fn new<S>(svc: S) -> Self
+    where
+        S: Service<hyper::Request<Body>, Response = hyper::Response<Body>> + Clone + Send + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+    {
+        Self {
+            inner: BoxCloneService::new(svc.map_err(Into::into)),
+        }
+    }
+}
+
+impl Service<http1::Request<Bytes>> for InProcessService {
+    type Response = http1::Response<Bytes>;
+
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http1::Request<Bytes>) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let resp = inner.oneshot(into_hyper_request(req)?).await?;
+            into_http_response(resp).await
+        })
+    }
+}
+
+// `covert-types` and the server are still on `http` 0.2, the requests and
+// responses are converted by their parts
+
+fn into_hyper_request(req: http1::Request<Bytes>) -> Result<hyper::Request<Body>, BoxError> {
+    let (parts, body) = req.into_parts();
+    let mut builder = hyper::Request::builder()
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    Ok(builder.body(Body::from(body))?)
+}
+
+async fn into_http_response(
+    resp: hyper::Response<Body>,
+) -> Result<http1::Response<Bytes>, BoxError> {
+    let (parts, body) = resp.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    let mut builder = http1::Response::builder().status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    Ok(builder.body(body)?)
+}
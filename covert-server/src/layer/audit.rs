@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use covert_types::{
+    auth::AuthPolicy,
+    error::ApiError,
+    request::{ClientAddr, PeerCredentials, Request, TokenHeader},
+    response::Response,
+    token::Token,
+};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tower::{Layer, Service};
+
+use crate::{
+    audit::{
+        AuditBroker, AuditEntryType, AuditError, AuditEvent, AuditPolicyResult, AuditRequest,
+        AuditResponse,
+    },
+    repos::token::TokenRepo,
+    response::ResponseWithCtx,
+};
+
+use super::auth_service::TokenPolicies;
+
+/// Writes an entry to the enabled audit devices before the request is handled
+/// and another one before the response is returned. The request fails if no
+/// device could persist an entry.
+#[derive(Clone)]
+pub struct AuditService<S> {
+    inner: S,
+    broker: Arc<AuditBroker>,
+    token_repo: TokenRepo,
+}
+
+impl<S> AuditService<S> {
+    pub fn new(inner: S, broker: Arc<AuditBroker>, token_repo: TokenRepo) -> Self {
+        Self {
+            inner,
+            broker,
+            token_repo,
+        }
+    }
+}
+
+impl<S> Service<Request> for AuditService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut this = self.clone();
+        Box::pin(async move {
+            let devices = this.broker.devices();
+            if devices.is_empty() {
+                return this.inner.call(req).await;
+            }
+
+            let mut event = request_event(&req, &this.token_repo).await;
+            devices.log(&event).await?;
+
+            let resp = this.inner.call(req).await;
+
+            event.entry_type = AuditEntryType::Response;
+            event.time = Utc::now();
+            match &resp {
+                Ok(resp) => {
+                    event.response = Some(AuditResponse {
+                        mount_path: resp.ctx.backend_mount_path.clone(),
+                        data: match &resp.response {
+                            Response::Raw(data) if !data.is_null() => Some(data.clone()),
+                            _ => None,
+                        },
+                    });
+                }
+                Err(err) => {
+                    event.error = Some(AuditError {
+                        code: err.code,
+                        message: err.error.to_string(),
+                    });
+                }
+            }
+            devices.log(&event).await?;
+
+            resp
+        })
+    }
+}
+
+async fn request_event(req: &Request, token_repo: &TokenRepo) -> AuditEvent {
+    let entry = match req.extensions.get::<Token>() {
+        Some(token) => token_repo.lookup(token).await.ok().flatten(),
+        None => None,
+    };
+    let (entity, metadata) = entry.map_or_else(Default::default, |entry| {
+        (
+            Some(entry.entity_name),
+            entry.metadata.into_iter().collect(),
+        )
+    });
+    AuditEvent {
+        entry_type: AuditEntryType::Request,
+        time: Utc::now(),
+        token: req.token.clone(),
+        token_header: req.extensions.get::<TokenHeader>().copied(),
+        entity,
+        metadata,
+        policies: req
+            .extensions
+            .get::<TokenPolicies>()
+            .map(|policies| {
+                policies
+                    .0
+                    .iter()
+                    .map(|policy| policy.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        policy_result: req
+            .extensions
+            .get::<AuthPolicy>()
+            .map_or(AuditPolicyResult::Unauthenticated, Into::into),
+        request: AuditRequest {
+            id: req.id,
+            operation: req.operation,
+            namespace: req.namespace.join("/"),
+            path: req.path.clone(),
+            remote_address: req
+                .extensions
+                .get::<ClientAddr>()
+                .map(|addr| addr.0.to_string()),
+            peer_credentials: req.extensions.get::<PeerCredentials>().copied(),
+            data: serde_json::from_slice(&req.data)
+                .ok()
+                .filter(|data: &Value| !data.is_null()),
+        },
+        response: None,
+        error: None,
+    }
+}
+
+pub struct AuditLayer {
+    broker: Arc<AuditBroker>,
+    token_repo: TokenRepo,
+}
+
+impl AuditLayer {
+    pub fn new(broker: Arc<AuditBroker>, token_repo: TokenRepo) -> Self {
+        Self { broker, token_repo }
+    }
+}
+
+impl<S> Layer<S> for AuditLayer {
+    type Service = AuditService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuditService::new(inner, Arc::clone(&self.broker), self.token_repo.clone())
+    }
+}
@@ -1,15 +1,22 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
 use covert_types::{
-    auth::AuthPolicy, error::ApiError, request::Request, state::StorageState, token::Token,
+    auth::AuthPolicy,
+    error::ApiError,
+    policy::Policy,
+    request::{Operation, Request},
+    state::StorageState,
+    token::Token,
 };
 use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
 use tower::{Layer, Service};
-use tracing::error;
+use tracing::{error, Span};
 
 use crate::{
-    repos::{namespace::NamespaceRepo, token::TokenRepo},
+    repos::{namespace::NamespaceRepo, policy::ROOT_POLICY, token::TokenRepo},
     response::ResponseWithCtx,
+    tamper::{TamperEvent, TamperMonitor},
 };
 
 #[derive(Clone)]
@@ -17,14 +24,21 @@ pub struct AuthService<S: Service<Request>> {
     inner: S,
     token_repo: TokenRepo,
     namespace_repo: NamespaceRepo,
+    tamper: Arc<TamperMonitor>,
 }
 
 impl<S: Service<Request>> AuthService<S> {
-    pub fn new(inner: S, token_repo: TokenRepo, namespace_repo: NamespaceRepo) -> Self {
+    pub fn new(
+        inner: S,
+        token_repo: TokenRepo,
+        namespace_repo: NamespaceRepo,
+        tamper: Arc<TamperMonitor>,
+    ) -> Self {
         Self {
             inner,
             token_repo,
             namespace_repo,
+            tamper,
         }
     }
 }
@@ -50,11 +64,24 @@ where
     fn call(&mut self, mut req: Request) -> Self::Future {
         let mut this = self.clone();
         Box::pin(async move {
-            let authorized = is_authorized(&req, &this.token_repo, &this.namespace_repo).await?;
-            if authorized {
-                req.extensions.insert(AuthPolicy::Authenticated);
-            } else {
-                req.extensions.insert(AuthPolicy::Unauthenticated);
+            match req.token.as_ref().map(|t| Token::from_str(t)) {
+                Some(Ok(token)) => {
+                    record_token(&token, &this.token_repo).await;
+                    req.extensions.insert(token);
+                }
+                Some(Err(_))
+                    if req.extensions.get::<StorageState>() == Some(&StorageState::Unsealed) =>
+                {
+                    this.tamper.record(TamperEvent::MalformedToken);
+                }
+                _ => (),
+            }
+            let (policy, policies, entity) =
+                authorize(&req, &this.token_repo, &this.namespace_repo).await?;
+            req.extensions.insert(policy);
+            req.extensions.insert(policies);
+            if let Some(entity) = entity {
+                req.extensions.insert(entity);
             }
 
             this.inner.call(req).await
@@ -65,13 +92,19 @@ where
 pub struct AuthServiceLayer {
     token_repo: TokenRepo,
     namespace_repo: NamespaceRepo,
+    tamper: Arc<TamperMonitor>,
 }
 
 impl AuthServiceLayer {
-    pub fn new(token_repo: TokenRepo, namespace_repo: NamespaceRepo) -> Self {
+    pub fn new(
+        token_repo: TokenRepo,
+        namespace_repo: NamespaceRepo,
+        tamper: Arc<TamperMonitor>,
+    ) -> Self {
         Self {
             token_repo,
             namespace_repo,
+            tamper,
         }
     }
 }
@@ -80,50 +113,157 @@ impl<S: Service<Request>> Layer<S> for AuthServiceLayer {
     type Service = AuthService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        AuthService::new(inner, self.token_repo.clone(), self.namespace_repo.clone())
+        AuthService::new(
+            inner,
+            self.token_repo.clone(),
+            self.namespace_repo.clone(),
+            Arc::clone(&self.tamper),
+        )
+    }
+}
+
+/// Record the accessor and entity of the token on the `request` span created
+/// by the [`RequestIdService`](super::request_id::RequestIdService).
+async fn record_token(token: &Token, token_repo: &TokenRepo) {
+    let span = Span::current();
+    if span.is_disabled() {
+        return;
+    }
+    span.record("token_accessor", token_accessor(token).as_str());
+    if let Ok(Some(entry)) = token_repo.lookup(token).await {
+        span.record("entity", entry.entity_name.as_str());
     }
 }
 
-async fn is_authorized(
+/// Identifier of the token that is safe to log. It is the start of the SHA-256
+/// digest of the token, so it can be correlated with the token without
+/// revealing it.
+fn token_accessor(token: &Token) -> String {
+    let digest = Sha256::digest(token.to_string().as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Policies attached to the token of the request. The policy paths are
+/// prefixed with the full path of the namespace the policies were created in.
+#[derive(Debug, Clone, Default)]
+pub struct TokenPolicies(pub Vec<Policy>);
+
+impl TokenPolicies {
+    /// Whether any policy allows the operation on the path and none denies
+    /// it. The path must be prefixed with the namespace path.
+    #[must_use]
+    pub fn allow(&self, path: &str, operation: Operation) -> bool {
+        !self.0.iter().any(|policy| policy.denies(path))
+            && self
+                .0
+                .iter()
+                .any(|policy| policy.is_authorized(path, &[operation]))
+    }
+}
+
+/// Entity the token of the request belongs to, resolved together with the
+/// policies of the token so the layers after the [`AuthService`] don't have to
+/// look up the token again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenEntity {
+    pub name: String,
+    /// Namespace the token was issued in.
+    pub namespace_id: String,
+}
+
+/// Resolve the [`AuthPolicy`] of the request from the policies attached to the
+/// token. The request is only granted [`AuthPolicy::Sudo`] if a policy grants
+/// the `sudo` capability together with the requested operation on the path.
+async fn authorize(
     req: &Request,
     token_repo: &TokenRepo,
     namespace_repo: &NamespaceRepo,
-) -> Result<bool, ApiError> {
+) -> Result<(AuthPolicy, TokenPolicies, Option<TokenEntity>), ApiError> {
     if req.extensions.get::<StorageState>() != Some(&StorageState::Unsealed) {
-        return Ok(false);
+        return Ok((AuthPolicy::Unauthenticated, TokenPolicies::default(), None));
     }
 
     let Some(token) = req.token.as_ref() else {
-        return Ok(false);
+        return Ok((AuthPolicy::Unauthenticated, TokenPolicies::default(), None));
     };
     let token = Token::from_str(token)?;
+    // Tokens that are expired or not valid yet are not found
+    let Some(entry) = token_repo.lookup(&token).await? else {
+        return Ok((AuthPolicy::Unauthenticated, TokenPolicies::default(), None));
+    };
+    // Every request with a token limited in uses uses one up, the token is
+    // rejected once they are used up
+    if entry.num_uses.is_some() && !token_repo.consume_use(&token).await? {
+        return Err(ApiError::unauthorized());
+    }
+    let entity = TokenEntity {
+        name: entry.entity_name,
+        namespace_id: entry.namespace_id,
+    };
     let mut policies = token_repo.lookup_policies(&token).await?;
 
     let Some(policy_namespace_id) = policies.get(0).map(|p| &p.namespace_id).cloned() else {
-        return Ok(false);
+        return Ok((
+            AuthPolicy::Unauthenticated,
+            TokenPolicies::default(),
+            Some(entity),
+        ));
     };
     let policy_namespace_prefix = namespace_repo.get_full_path(&policy_namespace_id).await?;
 
-    let namespace_prefix = req.namespace.join("/");
-    let path = format!("{}/{}", namespace_prefix, req.path);
-
-    let is_authorized = policies.iter_mut().any(|policy| {
-        // This should never happen, but it is a nice extra safeguard.
+    // This should never happen, but it is a nice extra safeguard.
+    policies.retain(|policy| {
         if policy.namespace_id != policy_namespace_id {
             error!("Token had attached policies from different namespaces");
             return false;
         }
+        true
+    });
+
+    // The root policy grants everything, the other policies don't need to be
+    // merged with it. Policies denying paths are kept as a deny takes
+    // precedence over the root policy too.
+    if policy_namespace_prefix == "root" && policies.iter().any(|p| p.name == ROOT_POLICY) {
+        policies.retain(|policy| {
+            policy.name == ROOT_POLICY
+                || policy
+                    .paths
+                    .iter()
+                    .any(|path| path.operations.contains(&Operation::Deny))
+        });
+    }
 
-        // Attach the namespace prefix to the policy paths from where the
-        // namespace they were created in.
+    // Attach the namespace prefix to the policy paths from where the
+    // namespace they were created in.
+    for policy in &mut policies {
         for path in &mut policy.paths {
             let maybe_slash = if path.path.starts_with('/') { "" } else { "/" };
             path.path = format!("{policy_namespace_prefix}{maybe_slash}{}", path.path);
         }
-        policy.is_authorized(&path, &[req.operation])
-    });
+    }
+
+    let namespace_prefix = req.namespace.join("/");
+    let path = format!("{}/{}", namespace_prefix, req.path);
+
+    // A path denied by any policy cannot be accessed, whatever the other
+    // policies grant
+    let auth = if policies.iter().any(|policy| policy.denies(&path)) {
+        AuthPolicy::Unauthenticated
+    } else if policies
+        .iter()
+        .any(|policy| policy.is_authorized(&path, &[req.operation, Operation::Sudo]))
+    {
+        AuthPolicy::Sudo
+    } else if policies
+        .iter()
+        .any(|policy| policy.is_authorized(&path, &[req.operation]))
+    {
+        AuthPolicy::Authenticated
+    } else {
+        AuthPolicy::Unauthenticated
+    };
 
-    Ok(is_authorized)
+    Ok((auth, TokenPolicies(policies), Some(entity)))
 }
 
 #[cfg(test)]
@@ -141,7 +281,12 @@ mod tests {
     use sqlx::SqlitePool;
     use uuid::Uuid;
 
-    use crate::repos::{mount::tests::pool, namespace::Namespace, token::TokenEntry, Repos};
+    use crate::repos::{
+        mount::tests::pool,
+        namespace::Namespace,
+        token::{new_accessor, TokenEntry},
+        Repos,
+    };
 
     use super::*;
 
@@ -189,6 +334,14 @@ mod tests {
             expires_at: None,
             issued_at: Utc::now(),
             namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         };
         repos.token.create(&token).await.unwrap();
 
@@ -206,10 +359,104 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(authorized);
+        assert_eq!(policy, AuthPolicy::Authenticated);
+    }
+
+    #[tokio::test]
+    async fn grants_sudo_only_with_sudo_capability() {
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        // Setup root namespace
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        // Create entity and policy
+        let entity = Entity {
+            name: "foo".to_string(),
+
+            namespace_id: ns.id.clone(),
+        };
+        repos.entity.create(&entity).await.unwrap();
+
+        let policy = Policy {
+            name: "foo-policy".to_string(),
+            paths: vec![
+                PathPolicy {
+                    path: "*".to_string(),
+                    operations: vec![Operation::Create, Operation::Read],
+                },
+                PathPolicy {
+                    path: "sys/*".to_string(),
+                    operations: vec![Operation::Create, Operation::Sudo],
+                },
+            ],
+            namespace_id: ns.id.clone(),
+        };
+        repos.policy.create(&policy).await.unwrap();
+        repos
+            .entity
+            .attach_policy(&entity.name, &policy.name, &ns.id)
+            .await
+            .unwrap();
+
+        // Create token for entity
+        let token = TokenEntry {
+            id: Token::new(),
+            entity_name: entity.name.clone(),
+            expires_at: None,
+            issued_at: Utc::now(),
+            namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
+        };
+        repos.token.create(&token).await.unwrap();
+
+        // Unsealed and we can authenticate
+        let mut req = Request {
+            id: Uuid::default(),
+            operation: Operation::Create,
+            namespace: vec![ns.name.clone()],
+            path: String::default(),
+            data: Bytes::default(),
+            extensions: Extensions::default(),
+            token: Some(token.id.to_string()),
+            params: Vec::default(),
+            query_string: String::default(),
+            headers: HashMap::default(),
+        };
+        req.extensions.insert(StorageState::Unsealed);
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(policy, AuthPolicy::Authenticated);
+
+        req.path = "sys/seal".to_string();
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(policy, AuthPolicy::Sudo);
+
+        // Sudo must be granted together with the operation
+        req.operation = Operation::Read;
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(policy, AuthPolicy::Authenticated);
     }
 
     #[tokio::test]
@@ -256,6 +503,14 @@ mod tests {
             expires_at: Some(Utc::now() - Duration::hours(1)),
             issued_at: Utc::now() - Duration::hours(2),
             namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         };
         repos.token.create(&token).await.unwrap();
 
@@ -273,10 +528,10 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
     }
 
     #[tokio::test]
@@ -307,10 +562,10 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
     }
 
     #[tokio::test]
@@ -357,6 +612,14 @@ mod tests {
             expires_at: None,
             issued_at: Utc::now(),
             namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         };
         repos.token.create(&token).await.unwrap();
 
@@ -372,25 +635,25 @@ mod tests {
             query_string: String::default(),
             headers: HashMap::default(),
         };
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
 
         for state in [StorageState::Uninitialized, StorageState::Sealed] {
             req.extensions.insert(state);
-            let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+            let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
                 .await
                 .unwrap();
-            assert!(!authorized);
+            assert_eq!(policy, AuthPolicy::Unauthenticated);
         }
 
         // Unsealed and we can authenticate
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(authorized);
+        assert_eq!(policy, AuthPolicy::Authenticated);
     }
 
     #[tokio::test]
@@ -445,6 +708,14 @@ mod tests {
             expires_at: None,
             issued_at: Utc::now(),
             namespace_id: foo_ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         };
         repos.token.create(&token).await.unwrap();
 
@@ -463,10 +734,10 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(authorized);
+        assert_eq!(policy, AuthPolicy::Authenticated);
 
         // But accessing sys/ in root namespace does not work
         let mut req = Request {
@@ -482,10 +753,10 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
     }
 
     #[tokio::test]
@@ -532,6 +803,14 @@ mod tests {
             expires_at: None,
             issued_at: Utc::now(),
             namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         };
         repos.token.create(&token).await.unwrap();
 
@@ -550,25 +829,25 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(authorized);
+        assert_eq!(policy, AuthPolicy::Authenticated);
 
         // Accessing secrets/marketing/* with read is *not* allowed by policy
         req.operation = Operation::Read;
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
 
         // Accessing secrets/not-marketing/* with create is *not* allowed by policy
         req.operation = Operation::Create;
         req.path = "secrets/not-marketing/some-key".to_string();
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
     }
 
     #[tokio::test]
@@ -630,6 +909,14 @@ mod tests {
             expires_at: None,
             issued_at: Utc::now(),
             namespace_id: f_ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         };
         repos.token.create(&token).await.unwrap();
 
@@ -647,16 +934,276 @@ mod tests {
             headers: HashMap::default(),
         };
         req.extensions.insert(StorageState::Unsealed);
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
             .await
             .unwrap();
-        assert!(authorized);
+        assert_eq!(policy, AuthPolicy::Authenticated);
 
         // Is *not* authorized in foo_ns
         req.namespace = vec![ns.name.clone(), foo_ns.name.clone()];
-        let authorized = is_authorized(&req, &repos.token, &repos.namespace)
+        let (policy, ..) = authorize(&req, &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn root_policy_is_not_merged_with_other_policies() {
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let entity = Entity::new("admin".to_string(), ns.id.clone());
+        repos.entity.create(&entity).await.unwrap();
+        for name in [ROOT_POLICY, "reader"] {
+            let policy = Policy::new(
+                name.to_string(),
+                vec![PathPolicy {
+                    path: "*".to_string(),
+                    operations: vec![Operation::Read, Operation::Create, Operation::Sudo],
+                }],
+                ns.id.clone(),
+            );
+            repos.policy.create(&policy).await.unwrap();
+            repos
+                .entity
+                .attach_policy(&entity.name, &policy.name, &ns.id)
+                .await
+                .unwrap();
+        }
+
+        let token = TokenEntry {
+            id: Token::new(),
+            entity_name: entity.name.clone(),
+            expires_at: None,
+            issued_at: Utc::now(),
+            namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
+        };
+        repos.token.create(&token).await.unwrap();
+
+        let mut req = Request {
+            id: Uuid::default(),
+            operation: Operation::Create,
+            namespace: vec![ns.name.clone()],
+            path: "sys/entity".to_string(),
+            data: Bytes::default(),
+            extensions: Extensions::default(),
+            token: Some(token.id.to_string()),
+            params: Vec::default(),
+            query_string: String::default(),
+            headers: HashMap::default(),
+        };
+        req.extensions.insert(StorageState::Unsealed);
+        let (auth, TokenPolicies(policies), _) = authorize(&req, &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(auth, AuthPolicy::Sudo);
+        assert_eq!(
+            policies.iter().map(Policy::name).collect::<Vec<_>>(),
+            vec![ROOT_POLICY]
+        );
+    }
+
+    #[tokio::test]
+    async fn deny_policy_applies_to_root_token() {
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let entity = Entity::new("admin".to_string(), ns.id.clone());
+        repos.entity.create(&entity).await.unwrap();
+        let root = Policy::new(
+            ROOT_POLICY.to_string(),
+            vec![PathPolicy {
+                path: "*".to_string(),
+                operations: vec![Operation::Read, Operation::Create, Operation::Sudo],
+            }],
+            ns.id.clone(),
+        );
+        let deny = Policy::new(
+            "no-secrets".to_string(),
+            vec![PathPolicy {
+                path: "kv/*".to_string(),
+                operations: vec![Operation::Deny],
+            }],
+            ns.id.clone(),
+        );
+        for policy in [&root, &deny] {
+            repos.policy.create(policy).await.unwrap();
+            repos
+                .entity
+                .attach_policy(&entity.name, &policy.name, &ns.id)
+                .await
+                .unwrap();
+        }
+
+        let token = TokenEntry {
+            id: Token::new(),
+            entity_name: entity.name.clone(),
+            expires_at: None,
+            issued_at: Utc::now(),
+            namespace_id: ns.id.clone(),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
+        };
+        repos.token.create(&token).await.unwrap();
+
+        let request = |path: &str| {
+            let mut req = Request {
+                id: Uuid::default(),
+                operation: Operation::Read,
+                namespace: vec![ns.name.clone()],
+                path: path.to_string(),
+                data: Bytes::default(),
+                extensions: Extensions::default(),
+                token: Some(token.id.to_string()),
+                params: Vec::default(),
+                query_string: String::default(),
+                headers: HashMap::default(),
+            };
+            req.extensions.insert(StorageState::Unsealed);
+            req
+        };
+
+        let (auth, TokenPolicies(policies), _) =
+            authorize(&request("kv/data/foo"), &repos.token, &repos.namespace)
+                .await
+                .unwrap();
+        assert_eq!(auth, AuthPolicy::Unauthenticated);
+        assert!(!TokenPolicies(policies).allow("root/kv/data/foo", Operation::Read));
+
+        // Paths the deny policy does not cover are still granted by root
+        let (auth, ..) = authorize(&request("sys/entity"), &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(auth, AuthPolicy::Sudo);
+    }
+
+    #[tokio::test]
+    async fn rejects_token_outside_of_its_limits() {
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        // Setup root namespace
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        // Create entity and policy
+        let entity = Entity {
+            name: "foo".to_string(),
+
+            namespace_id: ns.id.clone(),
+        };
+        repos.entity.create(&entity).await.unwrap();
+
+        let policy = Policy {
+            name: "foo-policy".to_string(),
+            paths: vec![PathPolicy {
+                path: "*".to_string(),
+                operations: vec![Operation::Create],
+            }],
+            namespace_id: ns.id.clone(),
+        };
+        repos.policy.create(&policy).await.unwrap();
+        repos
+            .entity
+            .attach_policy(&entity.name, &policy.name, &ns.id)
+            .await
+            .unwrap();
+
+        let request = |token: &TokenEntry| {
+            let mut req = Request {
+                id: Uuid::default(),
+                operation: Operation::Create,
+                namespace: vec![ns.name.clone()],
+                path: String::default(),
+                data: Bytes::default(),
+                extensions: Extensions::default(),
+                token: Some(token.id.to_string()),
+                params: Vec::default(),
+                query_string: String::default(),
+                headers: HashMap::default(),
+            };
+            req.extensions.insert(StorageState::Unsealed);
+            req
+        };
+
+        // Single use token
+        let token = TokenEntry::new(
+            entity.name.clone(),
+            repos.token.now(),
+            Duration::minutes(5),
+            ns.id.clone(),
+            HashMap::new(),
+            vec![],
+            false,
+        )
+        .with_limits(None, Some(1));
+        repos.token.create(&token).await.unwrap();
+        let (policy, ..) = authorize(&request(&token), &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(policy, AuthPolicy::Authenticated);
+        let err = authorize(&request(&token), &repos.token, &repos.namespace)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, hyper::StatusCode::UNAUTHORIZED);
+
+        // Token that is not valid yet
+        let token = TokenEntry::new(
+            entity.name.clone(),
+            repos.token.now(),
+            Duration::minutes(65),
+            ns.id.clone(),
+            HashMap::new(),
+            vec![],
+            false,
+        )
+        .with_limits(Some(Utc::now() + Duration::hours(1)), Some(1));
+        repos.token.create(&token).await.unwrap();
+        let (policy, ..) = authorize(&request(&token), &repos.token, &repos.namespace)
+            .await
+            .unwrap();
+        assert_eq!(policy, AuthPolicy::Unauthenticated);
+        // No use was used up
+        let uses: Option<u32> = sqlx::query_scalar("SELECT num_uses FROM TOKENS WHERE token = ?")
+            .bind(token.id.to_string())
+            .fetch_one(repos.pool.as_ref())
             .await
             .unwrap();
-        assert!(!authorized);
+        assert_eq!(uses, Some(1));
     }
 }
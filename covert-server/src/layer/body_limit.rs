@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use covert_framework::normalize_path;
+use covert_storage::EncryptedPool;
+use covert_types::{
+    error::ApiError,
+    request::{namespace_from_headers, BodyLimit},
+    state::StorageState,
+};
+use futures::future::BoxFuture;
+use http_body::{Body as _, Limited};
+use hyper::{header::CONTENT_LENGTH, http, Body};
+use tower::{Layer, Service, ServiceExt};
+
+use crate::{
+    error::Error,
+    repos::{mount::MountRepo, namespace::NamespaceRepo},
+};
+
+use super::namespace_extension::select_namespace;
+
+/// Limits of the request body sizes of the server and the mounts.
+struct BodyLimits {
+    default_limit: usize,
+    storage_pool: Arc<EncryptedPool>,
+    ns_repo: NamespaceRepo,
+    mount_repo: MountRepo,
+}
+
+impl BodyLimits {
+    /// Limit of the mount the request is routed to. Requests to the system
+    /// backend, requests while sealed and requests that don't resolve to a
+    /// mount get the default limit.
+    async fn limit(&self, parts: &http::request::Parts) -> Result<usize, Error> {
+        if self.storage_pool.state() != StorageState::Unsealed {
+            return Ok(self.default_limit);
+        }
+        let path = parts.uri.path();
+        let Ok(mut path) = normalize_path(path.strip_prefix("/v1/").unwrap_or(path)) else {
+            return Ok(self.default_limit);
+        };
+        let mut namespace = namespace_from_headers(&parts.headers);
+        let Some(ns) = select_namespace(&self.ns_repo, &mut namespace, &mut path).await? else {
+            return Ok(self.default_limit);
+        };
+        let limit = self
+            .mount_repo
+            .longest_prefix(&path, &ns.id)
+            .await?
+            .and_then(|mount| mount.config.max_request_body_size)
+            .map_or(self.default_limit, |limit| {
+                usize::try_from(limit).unwrap_or(usize::MAX)
+            });
+        Ok(limit)
+    }
+}
+
+/// Limits the size of the request bodies to the limit of the mount the request
+/// is routed to, or the limit of the server if the mount has none. The body is
+/// limited while it is streamed, requests announcing a larger `Content-Length`
+/// are refused before anything is read.
+#[derive(Clone)]
+pub struct BodyLimitService<S> {
+    inner: S,
+    limits: Arc<BodyLimits>,
+}
+
+impl<S> Service<http::Request<Body>> for BodyLimitService<S>
+where
+    S: Service<http::Request<Limited<Body>>, Response = http::Response<Body>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = http::Response<Body>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let inner = self.inner.clone();
+        let limits = Arc::clone(&self.limits);
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            // Requests without a body don't need the mount to be looked up
+            let limit = if body.size_hint().exact() == Some(0) {
+                limits.default_limit
+            } else {
+                match limits.limit(&parts).await {
+                    Ok(limit) => limit,
+                    Err(error) => return Ok(ApiError::from(error).into()),
+                }
+            };
+
+            let content_length = parts
+                .headers
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            if content_length.is_some_and(|length| length > limit as u64) {
+                return Ok(ApiError::payload_too_large(limit).into());
+            }
+
+            parts.extensions.insert(BodyLimit(limit));
+            let req = http::Request::from_parts(parts, Limited::new(body, limit));
+            inner.oneshot(req).await
+        })
+    }
+}
+
+pub struct BodyLimitLayer {
+    limits: Arc<BodyLimits>,
+}
+
+impl BodyLimitLayer {
+    pub fn new(
+        default_limit: usize,
+        storage_pool: Arc<EncryptedPool>,
+        ns_repo: NamespaceRepo,
+        mount_repo: MountRepo,
+    ) -> Self {
+        Self {
+            limits: Arc::new(BodyLimits {
+                default_limit,
+                storage_pool,
+                ns_repo,
+                mount_repo,
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for BodyLimitLayer {
+    type Service = BodyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BodyLimitService {
+            inner,
+            limits: Arc::clone(&self.limits),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use covert_types::error::ApiError;
+use futures::future::BoxFuture;
+use hyper::{http, Body};
+use tokio::sync::Semaphore;
+use tower::{Layer, Service, ServiceExt};
+
+/// Paths served even when the server is saturated, so it can still be
+/// monitored. `/metrics` is the scrape path before it is rewritten to
+/// `sys/metrics`.
+const UNLIMITED_PATHS: &[&str] = &["/v1/sys/status", "/v1/sys/metrics", "/metrics"];
+
+/// Sheds load once the limit of in-flight requests is reached. Further
+/// requests are refused right away with `503 Service Unavailable` instead of
+/// being queued, which would only add to their latency and to the memory held
+/// by the server.
+#[derive(Clone)]
+pub struct ConcurrencyLimitService<S> {
+    inner: S,
+    semaphore: Arc<Semaphore>,
+}
+
+impl<S> Service<http::Request<Body>> for ConcurrencyLimitService<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = http::Response<Body>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let inner = self.inner.clone();
+        if UNLIMITED_PATHS.contains(&req.uri().path()) {
+            return Box::pin(inner.oneshot(req));
+        }
+        let Ok(permit) = Arc::clone(&self.semaphore).try_acquire_owned() else {
+            tracing::warn!(path = req.uri().path(), "Server busy, request refused");
+            return Box::pin(async { Ok(ApiError::server_busy().into()) });
+        };
+        Box::pin(async move {
+            let resp = inner.oneshot(req).await;
+            drop(permit);
+            resp
+        })
+    }
+}
+
+pub struct ConcurrencyLimitLayer {
+    semaphore: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimitLayer {
+    pub fn new(max_concurrent_requests: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent_requests)),
+        }
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyLimitService {
+            inner,
+            semaphore: Arc::clone(&self.semaphore),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, time::Duration};
+
+    use hyper::StatusCode;
+    use tokio::sync::Notify;
+    use tower::service_fn;
+
+    use super::*;
+
+    fn request(path: &str) -> http::Request<Body> {
+        http::Request::builder()
+            .uri(path)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn refuses_requests_over_the_limit() {
+        let release = Arc::new(Notify::new());
+        let svc = ConcurrencyLimitLayer::new(1).layer(service_fn({
+            let release = Arc::clone(&release);
+            move |req: http::Request<Body>| {
+                let release = Arc::clone(&release);
+                async move {
+                    if req.uri().path() == "/v1/kv/slow" {
+                        release.notified().await;
+                    }
+                    Ok::<_, Infallible>(http::Response::new(Body::empty()))
+                }
+            }
+        }));
+
+        let slow = tokio::spawn(svc.clone().oneshot(request("/v1/kv/slow")));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let resp = svc.clone().oneshot(request("/v1/kv/foo")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(resp.headers().contains_key(http::header::RETRY_AFTER));
+
+        // Monitoring works while saturated
+        for path in UNLIMITED_PATHS {
+            let resp = svc.clone().oneshot(request(path)).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        release.notify_one();
+        slow.await.unwrap().unwrap();
+        let resp = svc.oneshot(request("/v1/kv/foo")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use covert_types::{
+    error::ApiError,
+    request::{Operation, Request},
+};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::{
+    error::{Error, ErrorType},
+    repos::write_index::WriteIndexRepo,
+    response::ResponseWithCtx,
+};
+
+/// Header containing the storage write index. Returned on mutating responses
+/// and read from requests that require read-your-writes consistency.
+pub const INDEX_HEADER: &str = "X-Covert-Index";
+
+/// Header used by clients to require that the storage has caught up to the
+/// index given in [`INDEX_HEADER`] before the request is served.
+pub const CONSISTENCY_HEADER: &str = "X-Covert-Consistency";
+
+const CONSISTENCY_REQUIRE: &str = "require";
+
+/// Default duration to wait for the storage to catch up before giving up.
+pub const CONSISTENCY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONSISTENCY_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Clone)]
+pub struct ConsistencyService<S> {
+    inner: S,
+    write_index_repo: WriteIndexRepo,
+    timeout: Duration,
+}
+
+impl<S> ConsistencyService<S> {
+    pub fn new(inner: S, write_index_repo: WriteIndexRepo, timeout: Duration) -> Self {
+        Self {
+            inner,
+            write_index_repo,
+            timeout,
+        }
+    }
+}
+
+/// Returns the write index the request requires, if any.
+pub(crate) fn required_index(req: &Request) -> Result<Option<u64>, Error> {
+    let require = req
+        .headers
+        .get(&CONSISTENCY_HEADER.to_lowercase())
+        .is_some_and(|val| val.trim().eq_ignore_ascii_case(CONSISTENCY_REQUIRE));
+    if !require {
+        return Ok(None);
+    }
+
+    let index = req
+        .headers
+        .get(&INDEX_HEADER.to_lowercase())
+        .ok_or_else(|| {
+            ErrorType::BadRequest(format!(
+                "`{CONSISTENCY_HEADER}` requires the `{INDEX_HEADER}` header"
+            ))
+        })?
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| ErrorType::BadRequest(format!("`{INDEX_HEADER}` is not a valid index")))?;
+
+    Ok(Some(index))
+}
+
+fn is_mutating(operation: Operation) -> bool {
+    match operation {
+        Operation::Create
+        | Operation::Update
+        | Operation::Patch
+        | Operation::Delete
+        | Operation::Revoke
+        | Operation::Renew => true,
+        Operation::Read | Operation::List | Operation::Sudo | Operation::Deny => false,
+    }
+}
+
+/// Waits until the local storage has caught up to the given write index.
+async fn wait_for_index(
+    write_index_repo: &WriteIndexRepo,
+    index: u64,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let wait = async {
+        loop {
+            if write_index_repo.current().await? >= index {
+                return Ok::<_, Error>(());
+            }
+            tokio::time::sleep(CONSISTENCY_POLL_INTERVAL).await;
+        }
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .map_err(|_| ErrorType::ConsistencyTimeout { index })?
+}
+
+impl<S> Service<Request> for ConsistencyService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut this = self.clone();
+        Box::pin(async move {
+            if let Some(index) = required_index(&req)? {
+                wait_for_index(&this.write_index_repo, index, this.timeout).await?;
+            }
+
+            let mutating = is_mutating(req.operation);
+            let mut resp = this.inner.call(req).await?;
+
+            if mutating {
+                // The storage is not available before unseal, so failing to
+                // bump the index should not fail an otherwise successful request.
+                match this.write_index_repo.increment().await {
+                    Ok(index) => resp.ctx.index = Some(index),
+                    Err(error) => tracing::debug!(?error, "Unable to increment write index"),
+                }
+            }
+
+            Ok(resp)
+        })
+    }
+}
+
+pub struct ConsistencyLayer {
+    write_index_repo: WriteIndexRepo,
+    timeout: Duration,
+}
+
+impl ConsistencyLayer {
+    pub fn new(write_index_repo: WriteIndexRepo, timeout: Duration) -> Self {
+        Self {
+            write_index_repo,
+            timeout,
+        }
+    }
+}
+
+impl<S> Layer<S> for ConsistencyLayer {
+    type Service = ConsistencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConsistencyService::new(inner, self.write_index_repo.clone(), self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use covert_types::response::Response;
+    use hyper::http::Extensions;
+    use serde_json::Value;
+    use tower::{service_fn, ServiceExt};
+    use uuid::Uuid;
+
+    use crate::repos::mount::tests::pool;
+
+    use super::*;
+
+    #[allow(clippy::unused_async)]
+    async fn handler(_req: Request) -> Result<ResponseWithCtx, ApiError> {
+        Ok(ResponseWithCtx {
+            response: Response::Raw(Value::Null),
+            ctx: crate::response::ResponseContext::default(),
+        })
+    }
+
+    fn request(operation: Operation, headers: HashMap<String, String>) -> Request {
+        Request {
+            id: Uuid::default(),
+            operation,
+            path: String::default(),
+            namespace: vec![],
+            data: Vec::default().into(),
+            extensions: Extensions::default(),
+            token: None,
+            params: vec![],
+            query_string: String::default(),
+            headers,
+        }
+    }
+
+    #[tokio::test]
+    async fn read_your_writes() {
+        let repo = WriteIndexRepo::new(Arc::new(pool().await));
+        let svc = ConsistencyLayer::new(repo.clone(), Duration::from_millis(100))
+            .layer(service_fn(handler));
+
+        // Mutating requests return the new index
+        let resp = svc
+            .clone()
+            .oneshot(request(Operation::Update, HashMap::new()))
+            .await
+            .unwrap();
+        assert_eq!(resp.ctx.index, Some(1));
+
+        // Reads does not bump the index
+        let resp = svc
+            .clone()
+            .oneshot(request(Operation::Read, HashMap::new()))
+            .await
+            .unwrap();
+        assert_eq!(resp.ctx.index, None);
+
+        let headers = HashMap::from([
+            (CONSISTENCY_HEADER.to_lowercase(), "require".to_string()),
+            (INDEX_HEADER.to_lowercase(), "1".to_string()),
+        ]);
+        assert!(svc
+            .clone()
+            .oneshot(request(Operation::Read, headers))
+            .await
+            .is_ok());
+
+        // Storage never reaches the requested index
+        let headers = HashMap::from([
+            (CONSISTENCY_HEADER.to_lowercase(), "require".to_string()),
+            (INDEX_HEADER.to_lowercase(), "5".to_string()),
+        ]);
+        let err = svc
+            .clone()
+            .oneshot(request(Operation::Read, headers))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, hyper::StatusCode::PRECONDITION_FAILED);
+
+        // Missing index
+        let headers = HashMap::from([(CONSISTENCY_HEADER.to_lowercase(), "require".to_string())]);
+        let err = svc
+            .oneshot(request(Operation::Read, headers))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, hyper::StatusCode::BAD_REQUEST);
+    }
+}
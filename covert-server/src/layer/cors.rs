@@ -0,0 +1,344 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use hyper::{
+    header::{self, HeaderName, HeaderValue},
+    http, Method, StatusCode,
+};
+use tower::{Layer, Service};
+
+use crate::CorsConfig;
+
+/// Request headers of the API that browsers are always allowed to send.
+const API_HEADERS: &[&str] = &[
+    "Content-Type",
+    "Authorization",
+    "X-Covert-Token",
+    "X-Covert-Namespace",
+    "X-Covert-Wrap-TTL",
+    "X-Covert-Consistency",
+    "X-Covert-Index",
+    "X-HTTP-Method-Override",
+    "X-Request-Id",
+    "Idempotency-Key",
+];
+
+/// Response headers of the API that scripts are allowed to read.
+const EXPOSED_HEADERS: &str = "X-Request-Id, X-Covert-Index";
+
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE";
+
+/// Whether `origin` matches the allowed origin `pattern`. A `*` in the pattern
+/// matches any sequence of characters except `/`, so `https://*.example.com`
+/// matches the subdomains of `example.com` and `*` matches any origin.
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.split_once('*') {
+        None => pattern.eq_ignore_ascii_case(origin),
+        Some((prefix, rest)) => {
+            if origin.len() < prefix.len() || !origin[..prefix.len()].eq_ignore_ascii_case(prefix) {
+                return false;
+            }
+            let origin = &origin[prefix.len()..];
+            origin
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(origin.len()))
+                .take_while(|&i| !origin[..i].contains('/'))
+                .any(|i| origin_matches(rest, &origin[i..]))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Cors {
+    allowed_origins: Vec<String>,
+    allowed_headers: HeaderValue,
+    max_age: HeaderValue,
+}
+
+impl Cors {
+    fn new(config: &CorsConfig) -> Self {
+        let allowed_headers = API_HEADERS
+            .iter()
+            .copied()
+            .chain(config.allowed_headers.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self {
+            allowed_origins: config.allowed_origins.clone(),
+            // Header names are checked when the config is validated
+            allowed_headers: HeaderValue::from_str(&allowed_headers)
+                .unwrap_or_else(|_| HeaderValue::from_static("")),
+            max_age: HeaderValue::from(config.max_age.as_secs()),
+        }
+    }
+
+    /// The origin of the request if it is allowed.
+    fn allowed_origin<B>(&self, req: &http::Request<B>) -> Option<HeaderValue> {
+        let origin = req.headers().get(header::ORIGIN)?;
+        let allowed = origin.to_str().ok().is_some_and(|origin| {
+            self.allowed_origins
+                .iter()
+                .any(|pattern| origin_matches(pattern, origin))
+        });
+        allowed.then(|| origin.clone())
+    }
+}
+
+/// Adds the CORS headers to the responses of requests from allowed origins
+/// and answers their preflight requests without passing them on to the
+/// backends. Requests from other origins are served without CORS headers,
+/// which makes browsers refuse them.
+#[derive(Debug, Clone)]
+pub struct CorsService<S> {
+    inner: S,
+    cors: Option<Arc<Cors>>,
+}
+
+impl<S> CorsService<S> {
+    pub fn new(inner: S, config: &CorsConfig) -> Self {
+        Self {
+            inner,
+            cors: config.enabled.then(|| Arc::new(Cors::new(config))),
+        }
+    }
+}
+
+impl<S, B, ResBody> Service<http::Request<B>> for CorsService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = http::Response<ResBody>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let Some(cors) = self.cors.clone() else {
+            return Box::pin(self.inner.call(req));
+        };
+        if !req.headers().contains_key(header::ORIGIN) {
+            return Box::pin(self.inner.call(req));
+        }
+        let origin = cors.allowed_origin(&req);
+
+        let preflight = req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+        if preflight {
+            let mut resp = http::Response::new(ResBody::default());
+            *resp.status_mut() = StatusCode::NO_CONTENT;
+            let headers = resp.headers_mut();
+            headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+            if let Some(origin) = origin {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_METHODS,
+                    HeaderValue::from_static(ALLOWED_METHODS),
+                );
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_HEADERS,
+                    cors.allowed_headers.clone(),
+                );
+                headers.insert(header::ACCESS_CONTROL_MAX_AGE, cors.max_age.clone());
+            }
+            return Box::pin(async move { Ok(resp) });
+        }
+
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let mut resp = fut.await?;
+            let headers = resp.headers_mut();
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+            if let Some(origin) = origin {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                headers.insert(
+                    header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                    HeaderValue::from_static(EXPOSED_HEADERS),
+                );
+            }
+            Ok(resp)
+        })
+    }
+}
+
+/// [`Layer`] that applies the [`CorsConfig`] with [`CorsService`].
+pub struct CorsLayer {
+    config: CorsConfig,
+}
+
+impl CorsLayer {
+    pub fn new(config: CorsConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Layer<S> for CorsLayer {
+    type Service = CorsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CorsService::new(inner, &self.config)
+    }
+}
+
+/// Check that a request header allowed by the config is a valid header name.
+pub(crate) fn validate_header(name: &str) -> Result<(), String> {
+    HeaderName::from_bytes(name.as_bytes())
+        .map(|_| ())
+        .map_err(|_| format!("`{name}` is not a valid header name"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use hyper::Body;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    fn config() -> CorsConfig {
+        CorsConfig {
+            enabled: true,
+            allowed_origins: vec![
+                "https://ui.example.com".to_string(),
+                "https://*.dev.example.com".to_string(),
+            ],
+            allowed_headers: vec!["X-Custom".to_string()],
+            max_age: Duration::from_mins(10),
+        }
+    }
+
+    async fn call(
+        config: CorsConfig,
+        method: Method,
+        origin: Option<&str>,
+    ) -> (http::Response<Body>, usize) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = CorsLayer::new(config).layer(service_fn({
+            let calls = Arc::clone(&calls);
+            move |_: http::Request<Body>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Ok::<_, Infallible>(http::Response::new(Body::from("ok"))) }
+            }
+        }));
+        let mut req = http::Request::builder()
+            .method(method.clone())
+            .uri("/v1/sys/status");
+        if let Some(origin) = origin {
+            req = req.header(header::ORIGIN, origin);
+        }
+        if method == Method::OPTIONS {
+            req = req.header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT");
+        }
+        let resp = svc.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        (resp, calls.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn match_origins() {
+        assert!(origin_matches(
+            "https://ui.example.com",
+            "https://UI.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://ui.example.com",
+            "https://ui.example.com:8443"
+        ));
+        assert!(origin_matches("*", "http://localhost:3000"));
+        assert!(origin_matches(
+            "https://*.example.com",
+            "https://a.b.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://example.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://evil.com/.example.com"
+        ));
+        assert!(origin_matches(
+            "http://localhost:*",
+            "http://localhost:3000"
+        ));
+    }
+
+    #[tokio::test]
+    async fn preflight_is_answered_without_backends() {
+        let (resp, calls) =
+            call(config(), Method::OPTIONS, Some("https://a.dev.example.com")).await;
+        assert_eq!(calls, 0);
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let headers = resp.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://a.dev.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        let allowed_headers = headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed_headers.contains("X-Covert-Token"));
+        assert!(allowed_headers.contains("X-Custom"));
+
+        // Disallowed origins get an empty answer without CORS headers
+        let (resp, calls) = call(config(), Method::OPTIONS, Some("https://evil.com")).await;
+        assert_eq!(calls, 0);
+        assert!(!resp
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert!(!resp
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_HEADERS));
+    }
+
+    #[tokio::test]
+    async fn headers_on_responses() {
+        let (resp, calls) = call(config(), Method::GET, Some("https://ui.example.com")).await;
+        assert_eq!(calls, 1);
+        assert_eq!(
+            resp.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://ui.example.com"
+        );
+        assert_eq!(resp.headers()[header::VARY], "Origin");
+
+        for origin in [Some("https://evil.com"), None] {
+            let (resp, calls) = call(config(), Method::GET, origin).await;
+            assert_eq!(calls, 1);
+            assert!(!resp
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let (resp, calls) = call(
+            CorsConfig::default(),
+            Method::OPTIONS,
+            Some("https://ui.example.com"),
+        )
+        .await;
+        assert_eq!(calls, 1);
+        assert!(resp.headers().is_empty());
+    }
+}
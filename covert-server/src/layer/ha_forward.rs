@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use covert_types::{
+    error::ApiError,
+    request::{BodyLimit, ClientAddr, RequestId, REQUEST_ID_HEADER},
+};
+use futures::future::BoxFuture;
+use http_body::{LengthLimitError, Limited};
+use hyper::{
+    body::Bytes,
+    header::LOCATION,
+    http::{self, request::Parts, uri::PathAndQuery},
+    Body, Method, StatusCode,
+};
+use tower::{Layer, Service, ServiceExt};
+
+use crate::{
+    error::{Error, ErrorType},
+    ha::HaState,
+    layer::standby_read::ForwardToActive,
+    repos::ha::HaLockRepo,
+    StandbyMode,
+};
+
+/// Paths a standby serves itself, every node is initialized, sealed,
+/// unsealed and monitored on its own.
+const STANDBY_PATHS: &[&str] = &[
+    "/v1/sys/status",
+    "/v1/sys/init",
+    "/v1/sys/seal",
+    "/v1/sys/unseal",
+    "/v1/sys/metrics",
+];
+
+/// Headers that only apply to a single connection and are not forwarded.
+const HOP_BY_HOP_HEADERS: &[&str] = &["connection", "content-length", "host", "transfer-encoding"];
+
+/// Sends the requests a standby receives to the active node of the HA
+/// cluster, either by proxying them or by redirecting the client, see
+/// [`StandbyMode`]. The active node and nodes outside of HA mode serve every
+/// request themselves. Performance standbys serve reads themselves, unless
+/// the [`StandbyReadLayer`](super::standby_read::StandbyReadLayer) refuses
+/// them.
+#[derive(Clone)]
+pub struct HaForwardService<S> {
+    inner: S,
+    ha: Arc<HaState>,
+    repo: HaLockRepo,
+}
+
+impl<S> Service<http::Request<Limited<Body>>> for HaForwardService<S>
+where
+    S: Service<http::Request<Limited<Body>>, Response = http::Response<Body>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = http::Response<Body>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Limited<Body>>) -> Self::Future {
+        let inner = self.inner.clone();
+        let ha = Arc::clone(&self.ha);
+        let repo = self.repo.clone();
+        Box::pin(async move {
+            if ha.is_active() || STANDBY_PATHS.contains(&req.uri().path()) {
+                return inner.oneshot(req).await;
+            }
+            let is_read = req.method() == Method::GET || req.method().as_str() == "LIST";
+            let res = match read_body(req).await {
+                Ok((parts, body)) if ha.serves_reads() && is_read => {
+                    let forwarded = ForwardedRequest::new(&parts, body.clone());
+                    let local =
+                        http::Request::from_parts(parts, Limited::new(body.into(), usize::MAX));
+                    let resp = inner.oneshot(local).await?;
+                    if resp.extensions().get::<ForwardToActive>().is_none() {
+                        return Ok(resp);
+                    }
+                    forward(&ha, &repo, forwarded).await
+                }
+                Ok((parts, body)) => forward(&ha, &repo, ForwardedRequest::new(&parts, body)).await,
+                Err(error) => Err(error),
+            };
+            match res {
+                Ok(resp) => Ok(resp),
+                Err(error) => Ok(error.into()),
+            }
+        })
+    }
+}
+
+/// The parts of a request sent to the active node.
+struct ForwardedRequest {
+    method: Method,
+    path_and_query: String,
+    headers: http::HeaderMap,
+    request_id: Option<RequestId>,
+    client_addr: Option<ClientAddr>,
+    body: Bytes,
+}
+
+impl ForwardedRequest {
+    fn new(parts: &Parts, body: Bytes) -> Self {
+        Self {
+            method: parts.method.clone(),
+            path_and_query: parts
+                .uri
+                .path_and_query()
+                .map_or("/", PathAndQuery::as_str)
+                .to_string(),
+            headers: parts.headers.clone(),
+            request_id: parts.extensions.get::<RequestId>().copied(),
+            client_addr: parts.extensions.get::<ClientAddr>().copied(),
+            body,
+        }
+    }
+}
+
+/// Read the body, which is already limited to the limit of the path.
+async fn read_body(req: http::Request<Limited<Body>>) -> Result<(Parts, Bytes), ApiError> {
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await.map_err(|err| {
+        match (
+            err.downcast_ref::<LengthLimitError>(),
+            parts.extensions.get::<BodyLimit>(),
+        ) {
+            (Some(_), Some(BodyLimit(limit))) => ApiError::payload_too_large(*limit),
+            _ => ApiError::bad_request(),
+        }
+    })?;
+    Ok((parts, body))
+}
+
+async fn forward(
+    ha: &HaState,
+    repo: &HaLockRepo,
+    req: ForwardedRequest,
+) -> Result<http::Response<Body>, ApiError> {
+    let (Some(config), Some(client)) = (ha.config(), ha.client()) else {
+        return Err(ApiError::internal_error());
+    };
+    let active = ha
+        .active_node(repo)
+        .await?
+        .ok_or_else(|| Error::from(ErrorType::NoActiveNode))?;
+    let url = format!(
+        "{}{}",
+        active.api_address.trim_end_matches('/'),
+        req.path_and_query
+    );
+
+    if config.standby_mode == StandbyMode::Redirect {
+        return http::Response::builder()
+            .status(StatusCode::TEMPORARY_REDIRECT)
+            .header(LOCATION, url)
+            .body(Body::empty())
+            .map_err(|_| ApiError::internal_error());
+    }
+
+    let method = reqwest::Method::from_bytes(req.method.as_str().as_bytes())
+        .map_err(|_| ApiError::bad_request())?;
+    let mut forwarded = client.request(method, url).body(req.body);
+    for (name, value) in &req.headers {
+        let name = name.as_str();
+        if !HOP_BY_HOP_HEADERS.contains(&name) && !name.eq_ignore_ascii_case(REQUEST_ID_HEADER) {
+            forwarded = forwarded.header(name, value.as_bytes());
+        }
+    }
+    // The active node uses the id and client address if the standby is one
+    // of its trusted proxies
+    if let Some(RequestId(id)) = req.request_id {
+        forwarded = forwarded.header(REQUEST_ID_HEADER, id.to_string());
+    }
+    if let Some(ClientAddr(addr)) = req.client_addr {
+        forwarded = forwarded.header("X-Forwarded-For", addr.ip().to_string());
+    }
+    let resp = forwarded
+        .send()
+        .await
+        .map_err(|err| Error::from(ErrorType::Forward(err)))?;
+
+    let mut builder = http::Response::builder().status(resp.status().as_u16());
+    for (name, value) in resp.headers() {
+        if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+    }
+    builder
+        .body(Body::wrap_stream(resp.bytes_stream()))
+        .map_err(|_| ApiError::internal_error())
+}
+
+pub struct HaForwardLayer {
+    ha: Arc<HaState>,
+    repo: HaLockRepo,
+}
+
+impl HaForwardLayer {
+    pub fn new(ha: Arc<HaState>, repo: HaLockRepo) -> Self {
+        Self { ha, repo }
+    }
+}
+
+impl<S> Layer<S> for HaForwardLayer {
+    type Service = HaForwardService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HaForwardService {
+            inner,
+            ha: Arc::clone(&self.ha),
+            repo: self.repo.clone(),
+        }
+    }
+}
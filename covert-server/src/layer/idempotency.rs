@@ -0,0 +1,577 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use covert_types::{
+    error::ApiError,
+    request::{Operation, Request},
+    response::Response,
+};
+use dashmap::{mapref::entry::Entry, DashMap};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tower::{Layer, Service};
+
+use crate::{
+    error::{Error, ErrorType},
+    repos::namespace::Namespace,
+    response::{ResponseContext, ResponseWithCtx},
+};
+
+/// How long the result of a request is returned for retries with the same
+/// idempotency key.
+pub const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_hours(24);
+
+/// Max number of results kept. Requests with a new key are handled without
+/// the idempotency guarantee while the cache is full.
+const MAX_IDEMPOTENCY_KEYS: usize = 10_000;
+
+/// Max size in bytes of the results kept, so a few large responses can't use
+/// up the memory of the server either.
+const MAX_IDEMPOTENCY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Idempotency keys are scoped to the namespace and the token of the request
+/// so clients can't see the results of other clients.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ScopedKey {
+    namespace_id: String,
+    token_digest: [u8; 32],
+    key: String,
+}
+
+enum CachedResult {
+    /// The first request with the key has not finished yet.
+    InFlight { fingerprint: [u8; 32] },
+    Completed {
+        fingerprint: [u8; 32],
+        data: Value,
+        ctx: ResponseContext,
+        expires_at: Instant,
+        /// Size of the serialized data, counted against the size limit.
+        size: usize,
+    },
+}
+
+impl CachedResult {
+    fn size(&self) -> usize {
+        match self {
+            CachedResult::InFlight { .. } => 0,
+            CachedResult::Completed { size, .. } => *size,
+        }
+    }
+}
+
+/// Results of the `create` requests with an idempotency key. The results are
+/// only kept in memory, they can contain secrets such as newly created
+/// tokens, and are cleared when the server is sealed or steps down.
+pub struct IdempotencyCache {
+    results: DashMap<ScopedKey, CachedResult>,
+    bytes: AtomicUsize,
+    ttl: Duration,
+    max_entries: usize,
+    max_bytes: usize,
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new(
+            IDEMPOTENCY_KEY_TTL,
+            MAX_IDEMPOTENCY_KEYS,
+            MAX_IDEMPOTENCY_BYTES,
+        )
+    }
+}
+
+/// What to do with a request with an idempotency key.
+enum Claim {
+    /// Handle the request and store the result.
+    Claimed,
+    /// Return the result of the first request.
+    Replay(Value, ResponseContext),
+    /// Handle the request without storing the result.
+    Full,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration, max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            results: DashMap::new(),
+            bytes: AtomicUsize::new(0),
+            ttl,
+            max_entries,
+            max_bytes,
+        }
+    }
+
+    /// Forget every result, e.g. when the server is sealed.
+    pub fn clear(&self) {
+        self.results.retain(|_, result| {
+            self.bytes.fetch_sub(result.size(), Ordering::SeqCst);
+            false
+        });
+    }
+
+    fn is_full(&self) -> bool {
+        self.results.len() >= self.max_entries
+            || self.bytes.load(Ordering::SeqCst) >= self.max_bytes
+    }
+
+    /// Remove the expired results.
+    fn prune(&self, now: Instant) {
+        self.results.retain(|_, result| match result {
+            CachedResult::InFlight { .. } => true,
+            CachedResult::Completed { expires_at, .. } if *expires_at > now => true,
+            CachedResult::Completed { size, .. } => {
+                self.bytes.fetch_sub(*size, Ordering::SeqCst);
+                false
+            }
+        });
+    }
+
+    fn claim(&self, key: &ScopedKey, fingerprint: [u8; 32], now: Instant) -> Result<Claim, Error> {
+        if !self.results.contains_key(key) && self.is_full() {
+            self.prune(now);
+            if self.is_full() {
+                return Ok(Claim::Full);
+            }
+        }
+
+        match self.results.entry(key.clone()) {
+            Entry::Vacant(entry) => {
+                entry.insert(CachedResult::InFlight { fingerprint });
+                Ok(Claim::Claimed)
+            }
+            Entry::Occupied(mut entry) => match entry.get() {
+                CachedResult::Completed { expires_at, .. } if *expires_at <= now => {
+                    let expired = entry.insert(CachedResult::InFlight { fingerprint });
+                    self.bytes.fetch_sub(expired.size(), Ordering::SeqCst);
+                    Ok(Claim::Claimed)
+                }
+                CachedResult::InFlight {
+                    fingerprint: original,
+                }
+                | CachedResult::Completed {
+                    fingerprint: original,
+                    ..
+                } if *original != fingerprint => Err(ErrorType::IdempotencyKeyReused.into()),
+                CachedResult::InFlight { .. } => Err(ErrorType::IdempotencyKeyInUse.into()),
+                CachedResult::Completed { data, ctx, .. } => {
+                    Ok(Claim::Replay(data.clone(), ctx.clone()))
+                }
+            },
+        }
+    }
+
+    /// Store the result of a claimed request. Results that don't fit in the
+    /// cache are not stored and the claim is released.
+    fn complete(&self, key: ScopedKey, fingerprint: [u8; 32], data: Value, ctx: ResponseContext) {
+        let now = Instant::now();
+        let size = key.key.len() + data.to_string().len();
+        if self.bytes.load(Ordering::SeqCst).saturating_add(size) > self.max_bytes {
+            self.prune(now);
+            if self.bytes.load(Ordering::SeqCst).saturating_add(size) > self.max_bytes {
+                tracing::warn!("Idempotency key cache is full, not storing the result");
+                return;
+            }
+        }
+
+        self.bytes.fetch_add(size, Ordering::SeqCst);
+        let replaced = self.results.insert(
+            key,
+            CachedResult::Completed {
+                fingerprint,
+                data,
+                ctx,
+                expires_at: now + self.ttl,
+                size,
+            },
+        );
+        if let Some(replaced) = replaced {
+            self.bytes.fetch_sub(replaced.size(), Ordering::SeqCst);
+        }
+    }
+
+    /// Forget the claim of a failed request so it can be retried.
+    fn release(&self, key: &ScopedKey) {
+        self.results.remove_if(key, |_, result| {
+            matches!(result, CachedResult::InFlight { .. })
+        });
+    }
+}
+
+/// Releases the claim on the key when the request is done, unless its result
+/// was stored. Also releases it when the request is dropped before it
+/// finishes, e.g. when the client disconnects.
+struct ClaimGuard<'a> {
+    cache: &'a IdempotencyCache,
+    key: &'a ScopedKey,
+}
+
+impl Drop for ClaimGuard<'_> {
+    fn drop(&mut self) {
+        self.cache.release(self.key);
+    }
+}
+
+/// Digest of what makes a request the same request as the first one.
+fn fingerprint(req: &Request) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in [req.path.as_bytes(), req.query_string.as_bytes(), &req.data] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Returns the result of the first `create` request with an idempotency key
+/// to retries of it, instead of creating a duplicate.
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    cache: Arc<IdempotencyCache>,
+}
+
+impl<S> IdempotencyService<S> {
+    pub fn new(inner: S, cache: Arc<IdempotencyCache>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl<S> Service<Request> for IdempotencyService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut this = self.clone();
+        Box::pin(async move {
+            let key = match req.idempotency_key()? {
+                Some(key) if req.operation == Operation::Create => key.to_string(),
+                _ => return this.inner.call(req).await,
+            };
+            let ns = req
+                .extensions
+                .get::<Namespace>()
+                .ok_or_else(ApiError::bad_request)?;
+            let key = ScopedKey {
+                namespace_id: ns.id.clone(),
+                token_digest: Sha256::digest(req.token.as_deref().unwrap_or_default()).into(),
+                key,
+            };
+            let fingerprint = fingerprint(&req);
+
+            match this.cache.claim(&key, fingerprint, Instant::now())? {
+                Claim::Claimed => (),
+                Claim::Replay(data, ctx) => {
+                    return Ok(ResponseWithCtx {
+                        response: Response::Raw(data),
+                        ctx,
+                    })
+                }
+                Claim::Full => {
+                    tracing::warn!("Idempotency key cache is full, handling request without it");
+                    return this.inner.call(req).await;
+                }
+            }
+
+            // Errors and streamed responses are not replayed
+            let _guard = ClaimGuard {
+                cache: &this.cache,
+                key: &key,
+            };
+            let resp = this.inner.call(req).await?;
+            if let Response::Raw(data) = &resp.response {
+                this.cache
+                    .complete(key.clone(), fingerprint, data.clone(), resp.ctx.clone());
+            }
+            Ok(resp)
+        })
+    }
+}
+
+pub struct IdempotencyLayer {
+    cache: Arc<IdempotencyCache>,
+}
+
+impl IdempotencyLayer {
+    pub fn new(cache: Arc<IdempotencyCache>) -> Self {
+        Self { cache }
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService::new(inner, Arc::clone(&self.cache))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use bytes::Bytes;
+    use hyper::{http::Extensions, StatusCode};
+    use serde_json::json;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn request(ns: &str, token: &str, key: Option<&str>, body: &'static str) -> Request {
+        let mut extensions = Extensions::default();
+        extensions.insert(Namespace {
+            id: ns.to_string(),
+            name: ns.to_string(),
+            parent_namespace_id: None,
+        });
+        let mut headers = HashMap::new();
+        if let Some(key) = key {
+            headers.insert("idempotency-key".to_string(), key.to_string());
+        }
+        Request {
+            id: Uuid::new_v4(),
+            operation: Operation::Create,
+            path: "sys/entity".to_string(),
+            namespace: vec![ns.to_string()],
+            data: Bytes::from_static(body.as_bytes()),
+            query_string: String::new(),
+            extensions,
+            params: vec![],
+            token: Some(token.to_string()),
+            headers,
+        }
+    }
+
+    /// Service that counts the calls and fails requests with a `fail` body.
+    fn counting_service(
+        calls: Arc<AtomicUsize>,
+    ) -> IdempotencyService<
+        impl Service<Request, Response = ResponseWithCtx, Error = ApiError, Future = impl Send>
+            + Clone
+            + Send,
+    > {
+        let inner = tower::service_fn(move |req: Request| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if req.data == "fail" {
+                    return Err(ApiError::internal_error());
+                }
+                Ok(ResponseWithCtx {
+                    response: Response::Raw(json!({ "call": call })),
+                    ctx: ResponseContext::default(),
+                })
+            }
+        });
+        IdempotencyService::new(inner, Arc::new(IdempotencyCache::default()))
+    }
+
+    async fn call<S>(svc: &S, req: Request) -> Result<Value, ApiError>
+    where
+        S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Clone,
+    {
+        let resp = svc.clone().oneshot(req).await?;
+        match resp.response {
+            Response::Raw(data) => Ok(data),
+            _ => panic!("expected raw response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_result_of_first_request() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = counting_service(Arc::clone(&calls));
+
+        let first = call(&svc, request("root", "t1", Some("abc"), "{}"))
+            .await
+            .unwrap();
+        let retry = call(&svc, request("root", "t1", Some("abc"), "{}"))
+            .await
+            .unwrap();
+        assert_eq!(first, retry);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Reusing the key for another request is rejected
+        let err = call(&svc, request("root", "t1", Some("abc"), r#"{"a":1}"#))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Requests without a key are not deduplicated
+        call(&svc, request("root", "t1", None, "{}")).await.unwrap();
+        call(&svc, request("root", "t1", None, "{}")).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_namespace_and_token() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = counting_service(Arc::clone(&calls));
+
+        let first = call(&svc, request("root", "t1", Some("abc"), "{}"))
+            .await
+            .unwrap();
+        let other_token = call(&svc, request("root", "t2", Some("abc"), "{}"))
+            .await
+            .unwrap();
+        let other_ns = call(&svc, request("foo", "t1", Some("abc"), "{}"))
+            .await
+            .unwrap();
+        assert_ne!(first, other_token);
+        assert_ne!(first, other_ns);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_requests_can_be_retried() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = counting_service(Arc::clone(&calls));
+
+        assert!(call(&svc, request("root", "t1", Some("abc"), "fail"))
+            .await
+            .is_err());
+        assert!(call(&svc, request("root", "t1", Some("abc"), "fail"))
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The key is free again for a different request
+        call(&svc, request("root", "t1", Some("abc"), "{}"))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_keys() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = counting_service(Arc::clone(&calls));
+
+        for key in ["", "with space", &"a".repeat(256)] {
+            let err = call(&svc, request("root", "t1", Some(key), "{}"))
+                .await
+                .unwrap_err();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn claim_in_flight_and_expired_keys() {
+        let cache = IdempotencyCache::new(Duration::from_mins(1), 1, MAX_IDEMPOTENCY_BYTES);
+        let key = ScopedKey {
+            namespace_id: "root".to_string(),
+            token_digest: [0; 32],
+            key: "abc".to_string(),
+        };
+        let other = ScopedKey {
+            key: "def".to_string(),
+            ..key.clone()
+        };
+        let now = Instant::now();
+
+        assert!(matches!(
+            cache.claim(&key, [1; 32], now),
+            Ok(Claim::Claimed)
+        ));
+        let err = cache.claim(&key, [1; 32], now).err().unwrap();
+        assert!(matches!(err.variant, ErrorType::IdempotencyKeyInUse));
+        let err = cache.claim(&key, [2; 32], now).err().unwrap();
+        assert!(matches!(err.variant, ErrorType::IdempotencyKeyReused));
+        assert!(matches!(cache.claim(&other, [1; 32], now), Ok(Claim::Full)));
+
+        cache.complete(key.clone(), [1; 32], json!({}), ResponseContext::default());
+        assert!(matches!(
+            cache.claim(&key, [1; 32], now),
+            Ok(Claim::Replay(..))
+        ));
+
+        // Expired results are replaced by new claims
+        let later = now + Duration::from_mins(2);
+        assert!(matches!(
+            cache.claim(&other, [1; 32], later),
+            Ok(Claim::Claimed)
+        ));
+        cache.release(&other);
+        assert!(matches!(
+            cache.claim(&key, [2; 32], later),
+            Ok(Claim::Claimed)
+        ));
+    }
+
+    #[test]
+    fn results_are_bounded_by_size() {
+        let cache = IdempotencyCache::new(Duration::from_mins(1), 10, 100);
+        let key = |key: &str| ScopedKey {
+            namespace_id: "root".to_string(),
+            token_digest: [0; 32],
+            key: key.to_string(),
+        };
+        let now = Instant::now();
+
+        // Results larger than the cache are not stored
+        assert!(matches!(
+            cache.claim(&key("large"), [1; 32], now),
+            Ok(Claim::Claimed)
+        ));
+        cache.complete(
+            key("large"),
+            [1; 32],
+            json!("a".repeat(100)),
+            ResponseContext::default(),
+        );
+        cache.release(&key("large"));
+        assert!(matches!(
+            cache.claim(&key("large"), [1; 32], now),
+            Ok(Claim::Claimed)
+        ));
+        cache.release(&key("large"));
+
+        assert!(matches!(
+            cache.claim(&key("a"), [1; 32], now),
+            Ok(Claim::Claimed)
+        ));
+        cache.complete(
+            key("a"),
+            [1; 32],
+            json!("a".repeat(97)),
+            ResponseContext::default(),
+        );
+        assert_eq!(cache.bytes.load(Ordering::SeqCst), 100);
+        assert!(matches!(
+            cache.claim(&key("b"), [1; 32], now),
+            Ok(Claim::Full)
+        ));
+
+        // Clearing the cache frees the space
+        cache.clear();
+        assert_eq!(cache.bytes.load(Ordering::SeqCst), 0);
+        assert!(matches!(
+            cache.claim(&key("a"), [1; 32], now),
+            Ok(Claim::Claimed)
+        ));
+    }
+}
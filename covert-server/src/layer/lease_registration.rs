@@ -1,21 +1,29 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use covert_types::{
-    entity::EntityAlias,
+    entity::{Entity, EntityAlias},
     error::ApiError,
-    methods::{AuthResponse, SecretLeaseResponse},
+    methods::{
+        AuthResponse, MfaMethod, MfaRequirement, MfaRequirementResponse, SecretLeaseResponse,
+    },
     request::Request,
     response::Response,
-    ttl::calculate_ttl,
+    token::Token,
 };
 use futures::future::BoxFuture;
+use serde_json::Value;
 use tower::{Layer, Service};
 
 use crate::{
     error::{Error, ErrorType},
+    mfa::{PendingLogins, MFA_REQUEST_TTL},
     repos::{
         entity::EntityRepo,
+        mfa::MfaRepo,
         namespace::Namespace,
         token::{TokenEntry, TokenRepo},
     },
@@ -24,12 +32,175 @@ use crate::{
     ExpirationManager, LeaseEntry,
 };
 
+/// Lookup the entity with the alias attached. If `create_entity` is set and no
+/// entity has the alias, a new entity named after the mount path and alias is
+/// created.
+async fn entity_for_alias(
+    entity_repo: &EntityRepo,
+    alias: &EntityAlias,
+    create_entity: bool,
+    namespace_id: &str,
+) -> Result<Option<Entity>, Error> {
+    let entity = entity_repo
+        .get_entity_from_alias(alias, namespace_id)
+        .await?;
+    if entity.is_some() || !create_entity {
+        return Ok(entity);
+    }
+
+    let mount_prefix = alias.mount_path.trim_end_matches('/').replace('/', "-");
+    let entity = Entity::new(
+        format!("{mount_prefix}-{}", alias.name),
+        namespace_id.to_string(),
+    );
+    entity_repo.create(&entity).await?;
+    entity_repo
+        .attach_alias(entity.name(), alias, namespace_id)
+        .await?;
+    Ok(Some(entity))
+}
+
+/// Tags derived from the token a secret is issued with: the alias metadata
+/// the auth backend attached to the token, e.g. the username, and the
+/// metadata of the entity the token belongs to, e.g. the team of the entity.
+/// The entity metadata takes precedence.
+async fn token_tags(
+    token_repo: &TokenRepo,
+    entity_repo: &EntityRepo,
+    token: &Token,
+) -> Result<BTreeMap<String, String>, Error> {
+    let Some(te) = token_repo.lookup(token).await? else {
+        return Ok(BTreeMap::new());
+    };
+    let entity = entity_repo
+        .lookup(&te.entity_name, &te.namespace_id)
+        .await?;
+    let mut tags: BTreeMap<_, _> = te.metadata.into_iter().collect();
+    if let Some(entity) = entity {
+        tags.extend(entity.metadata);
+    }
+    Ok(tags)
+}
+
+/// A login the token is issued for.
+#[derive(Debug, Clone)]
+pub struct Login {
+    pub entity_name: String,
+    pub namespace_id: String,
+    pub mount_path: String,
+    pub ttl: chrono::Duration,
+    pub metadata: HashMap<String, String>,
+    pub policies: Vec<String>,
+    pub group_policies: Vec<String>,
+    pub renewable: bool,
+    pub parent: Option<Token>,
+}
+
+/// Issue the token of the login with a lease that revokes it.
+pub async fn issue_token(
+    expiration_manager: &ExpirationManager,
+    token_repo: &TokenRepo,
+    login: Login,
+) -> Result<AuthResponse, Error> {
+    let token_entry = TokenEntry::new(
+        login.entity_name,
+        expiration_manager.now(),
+        login.ttl,
+        login.namespace_id.clone(),
+        login.metadata,
+        login.policies,
+        login.renewable,
+    )
+    .with_group_policies(login.group_policies)
+    .with_parent(login.parent);
+    register_token(
+        expiration_manager,
+        token_repo,
+        &token_entry,
+        login.mount_path,
+        login.ttl,
+    )
+    .await
+}
+
+/// Store the token with a lease that revokes it once `ttl` has passed.
+pub async fn register_token(
+    expiration_manager: &ExpirationManager,
+    token_repo: &TokenRepo,
+    token_entry: &TokenEntry,
+    mount_path: String,
+    ttl: chrono::Duration,
+) -> Result<AuthResponse, Error> {
+    token_repo.create(token_entry).await?;
+    let token = token_entry.id();
+
+    let revoke_data = RevokeTokenParams {
+        token: token.clone(),
+    };
+    // TODO: renew token endpoint not implemented yet
+    let renew_data = RevokeTokenParams {
+        token: token.clone(),
+    };
+    let lease = LeaseEntry::new(
+        mount_path,
+        None,
+        &revoke_data,
+        None,
+        &renew_data,
+        expiration_manager.now(),
+        ttl,
+        token_entry.namespace_id.clone(),
+    )?;
+    let lease_id = lease.id().to_string();
+    expiration_manager.register(lease).await?;
+
+    Ok(AuthResponse {
+        token: token.clone(),
+        accessor: token_entry.accessor.clone(),
+        lease_id,
+        lease_duration: ttl
+            .to_std()
+            .map_err(|_| ErrorType::InternalError(anyhow::Error::msg("Negative token TTL")))?,
+        renewable: token_entry.renewable,
+    })
+}
+
+/// Hold the login until it is completed with the second factor of the entity.
+/// The login is refused if the entity has no second factor.
+async fn require_mfa(
+    mfa_repo: &MfaRepo,
+    pending_logins: &PendingLogins,
+    login: Login,
+    now: DateTime<Utc>,
+) -> Result<MfaRequirementResponse, Error> {
+    if mfa_repo
+        .totp_key(&login.entity_name, &login.namespace_id)
+        .await?
+        .is_none()
+    {
+        return Err(ErrorType::MfaNotConfigured {
+            entity: login.entity_name,
+        }
+        .into());
+    }
+    let request_id = pending_logins.insert(login, now);
+    Ok(MfaRequirementResponse {
+        mfa_requirement: MfaRequirement {
+            request_id,
+            methods: vec![MfaMethod::Totp],
+            ttl: MFA_REQUEST_TTL,
+        },
+    })
+}
+
 #[derive(Clone)]
 pub struct LeaseRegistrationService<S> {
     inner: S,
     expiration_manager: Arc<ExpirationManager>,
     token_repo: TokenRepo,
     entity_repo: EntityRepo,
+    mfa_repo: MfaRepo,
+    pending_logins: Arc<PendingLogins>,
 }
 
 impl<S> LeaseRegistrationService<S> {
@@ -38,12 +209,16 @@ impl<S> LeaseRegistrationService<S> {
         expiration_manager: Arc<ExpirationManager>,
         token_repo: TokenRepo,
         entity_repo: EntityRepo,
+        mfa_repo: MfaRepo,
+        pending_logins: Arc<PendingLogins>,
     ) -> Self {
         Self {
             inner,
             expiration_manager,
             token_repo,
             entity_repo,
+            mfa_repo,
+            pending_logins,
         }
     }
 }
@@ -70,112 +245,161 @@ where
         let mut this = self.clone();
         Box::pin(async move {
             let ns = req.extensions.get::<Namespace>().cloned();
+            let token = req.extensions.get::<Token>().cloned();
 
             let resp = this.inner.call(req).await?;
-            let backend_mount_path = &resp.ctx.backend_mount_path;
-            let backend_config = &resp.ctx.backend_config;
-
-            match resp.response {
-                Response::Lease(lease) => {
-                    let ns = ns.ok_or_else(ApiError::internal_error)?;
-
-                    let now = Utc::now();
-                    let issued_at = now;
-                    let ttl = calculate_ttl(now, issued_at, backend_config, lease.ttl)
-                        .map_err(|_| ApiError::internal_error())?;
-
-                    let le = LeaseEntry::new(
-                        backend_mount_path.clone(),
-                        Some(lease.revoke.path),
-                        &lease.revoke.data,
-                        Some(lease.renew.path),
-                        &lease.renew.data,
-                        issued_at,
-                        ttl,
-                        ns.id.clone(),
-                    )?;
-                    let lease_id = le.id().to_string();
-                    this.expiration_manager.register(le).await?;
-
-                    let data = SecretLeaseResponse {
-                        data: lease.data,
-                        lease_id,
-                        ttl: ttl.to_std().map_err(|_| ApiError::internal_error())?,
-                    };
-                    let data = serde_json::to_value(&data)
-                        .map_err(|err| Error::from(ErrorType::BadResponseData(err)))?;
-
-                    Ok(ResponseWithCtx {
-                        response: Response::Raw(data),
-                        ctx: resp.ctx,
-                    })
+            match &resp.response {
+                Response::Lease(_) | Response::Auth(_) => {
+                    // The backend may have issued a secret or a login by now,
+                    // so it is registered in a task of its own that completes
+                    // even if the request is cancelled
+                    tokio::spawn(this.register(ns, token, resp))
+                        .await
+                        .map_err(|err| Error::from(ErrorType::InternalError(err.into())))?
                 }
-                Response::Auth(auth) => {
-                    let ns = ns.ok_or_else(ApiError::internal_error)?;
-
-                    let alias = EntityAlias {
-                        name: auth.alias.clone(),
-                        mount_path: backend_mount_path.clone(),
-                    };
-                    let entity = this
-                        .entity_repo
-                        .get_entity_from_alias(&alias, &ns.id)
-                        .await?;
-                    match entity {
-                        Some(entity) => {
-                            let now = Utc::now();
-                            let issued_at = now;
-                            let ttl = calculate_ttl(now, issued_at, backend_config, auth.ttl)
-                                .map_err(|_| ApiError::internal_error())?;
-
-                            let token_entry =
-                                TokenEntry::new(entity.name().to_string(), ttl, ns.id.clone());
-                            this.token_repo.create(&token_entry).await?;
-                            let token = token_entry.id();
-
-                            let revoke_data = RevokeTokenParams {
-                                token: token.clone(),
-                            };
-                            // TODO: renew token endpoint not implemented yet
-                            let renew_data = RevokeTokenParams {
-                                token: token.clone(),
-                            };
-                            let lease = LeaseEntry::new(
-                                backend_mount_path.clone(),
-                                None,
-                                &revoke_data,
-                                None,
-                                &renew_data,
-                                issued_at,
-                                ttl,
-                                ns.id.clone(),
-                            )?;
-                            let lease_id = lease.id().to_string();
-                            this.expiration_manager.register(lease).await?;
-
-                            let data = AuthResponse {
-                                token: token.clone(),
-                                lease_id,
-                                ttl: ttl.to_std().map_err(|_| ApiError::internal_error())?,
-                            };
-                            let data = serde_json::to_value(&data)
-                                .map_err(|err| Error::from(ErrorType::BadResponseData(err)))?;
-
-                            Ok(ResponseWithCtx {
-                                response: Response::Raw(data),
-                                ctx: resp.ctx,
-                            })
-                        }
-                        None => Err(ApiError::bad_request()),
-                    }
+                Response::Raw(data) => {
+                    this.check_claimed_lease(ns.as_ref(), data).await?;
+                    Ok(resp)
                 }
-                // Just passthrough the raw data
-                Response::Raw(data) => Ok(ResponseWithCtx {
+                _ => Ok(resp),
+            }
+        })
+    }
+}
+
+impl<S> LeaseRegistrationService<S> {
+    /// Refuse raw responses with a `lease_id` that was never registered,
+    /// e.g. a backend building the lease envelope on its own instead of
+    /// returning a [`Response::Lease`]. Clients would otherwise never get
+    /// the secret revoked.
+    async fn check_claimed_lease(&self, ns: Option<&Namespace>, data: &Value) -> Result<(), Error> {
+        let (Some(ns), Some(lease_id)) = (ns, data.get("lease_id").and_then(Value::as_str)) else {
+            return Ok(());
+        };
+        if self
+            .expiration_manager
+            .lookup(lease_id, &ns.id)
+            .await?
+            .is_none()
+        {
+            tracing::error!(lease_id, "Response claims a lease that was not registered");
+            return Err(ErrorType::UnregisteredLease.into());
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    async fn register(
+        self,
+        ns: Option<Namespace>,
+        token: Option<Token>,
+        resp: ResponseWithCtx,
+    ) -> Result<ResponseWithCtx, ApiError> {
+        let backend_mount_path = &resp.ctx.backend_mount_path;
+        let backend_config = &resp.ctx.backend_config;
+
+        match resp.response {
+            Response::Lease(lease) => {
+                let ns = ns.ok_or_else(ApiError::internal_error)?;
+
+                let issued_at = self.expiration_manager.now();
+                let ttl = self.expiration_manager.compute_ttl(
+                    issued_at,
+                    backend_config,
+                    lease.ttl,
+                    lease.max_ttl,
+                )?;
+
+                let le = LeaseEntry::new(
+                    backend_mount_path.clone(),
+                    Some(lease.revoke.path),
+                    &lease.revoke.data,
+                    Some(lease.renew.path),
+                    &lease.renew.data,
+                    issued_at,
+                    ttl,
+                    ns.id.clone(),
+                )?
+                .with_role_max_ttl(lease.max_ttl);
+
+                let mut tags = match &token {
+                    Some(token) => token_tags(&self.token_repo, &self.entity_repo, token).await?,
+                    None => BTreeMap::new(),
+                };
+                tags.extend(lease.tags);
+                let le = le.with_tags(&tags)?;
+                let lease_id = le.id().to_string();
+                self.expiration_manager.register(le).await?;
+
+                let data = SecretLeaseResponse {
+                    lease_id,
+                    lease_duration: ttl.to_std().map_err(|_| ApiError::internal_error())?,
+                    // The backend always tells where to renew the secret
+                    renewable: true,
+                    data: lease.data,
+                };
+                let data = serde_json::to_value(&data)
+                    .map_err(|err| Error::from(ErrorType::BadResponseData(err)))?;
+
+                Ok(ResponseWithCtx {
                     response: Response::Raw(data),
                     ctx: resp.ctx,
-                }),
+                })
             }
-        })
+            Response::Auth(auth) => {
+                let ns = ns.ok_or_else(ApiError::internal_error)?;
+
+                let alias = EntityAlias {
+                    name: auth.alias.clone(),
+                    mount_path: backend_mount_path.clone(),
+                };
+                let Some(entity) =
+                    entity_for_alias(&self.entity_repo, &alias, auth.create_entity, &ns.id).await?
+                else {
+                    return Err(ApiError::bad_request());
+                };
+                let issued_at = self.expiration_manager.now();
+                let ttl = self.expiration_manager.compute_ttl(
+                    issued_at,
+                    backend_config,
+                    auth.ttl,
+                    None,
+                )?;
+                let login = Login {
+                    entity_name: entity.name().to_string(),
+                    namespace_id: ns.id.clone(),
+                    mount_path: backend_mount_path.clone(),
+                    ttl,
+                    metadata: auth.metadata,
+                    policies: auth.policies,
+                    group_policies: auth.group_policies,
+                    renewable: auth.renewable,
+                    // Logging in with a token issues a child of it
+                    parent: token,
+                };
+
+                let data = if backend_config.require_mfa {
+                    let requirement =
+                        require_mfa(&self.mfa_repo, &self.pending_logins, login, issued_at).await?;
+                    serde_json::to_value(&requirement)
+                } else {
+                    let auth =
+                        issue_token(&self.expiration_manager, &self.token_repo, login).await?;
+                    serde_json::to_value(&auth)
+                }
+                .map_err(|err| Error::from(ErrorType::BadResponseData(err)))?;
+
+                Ok(ResponseWithCtx {
+                    response: Response::Raw(data),
+                    ctx: resp.ctx,
+                })
+            }
+            // Just passthrough the raw and binary data
+            response => Ok(ResponseWithCtx {
+                response,
+                ctx: resp.ctx,
+            }),
+        }
     }
 }
 
@@ -183,6 +407,8 @@ pub struct LeaseRegistrationLayer {
     expiration_manager: Arc<ExpirationManager>,
     token_repo: TokenRepo,
     entity_repo: EntityRepo,
+    mfa_repo: MfaRepo,
+    pending_logins: Arc<PendingLogins>,
 }
 
 impl LeaseRegistrationLayer {
@@ -190,11 +416,15 @@ impl LeaseRegistrationLayer {
         expiration_manager: Arc<ExpirationManager>,
         token_repo: TokenRepo,
         entity_repo: EntityRepo,
+        mfa_repo: MfaRepo,
+        pending_logins: Arc<PendingLogins>,
     ) -> Self {
         Self {
             expiration_manager,
             token_repo,
             entity_repo,
+            mfa_repo,
+            pending_logins,
         }
     }
 }
@@ -208,13 +438,15 @@ impl<S> Layer<S> for LeaseRegistrationLayer {
             Arc::clone(&self.expiration_manager),
             self.token_repo.clone(),
             self.entity_repo.clone(),
+            self.mfa_repo.clone(),
+            Arc::clone(&self.pending_logins),
         )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     use bytes::Bytes;
     use covert_sdk::{
@@ -223,6 +455,7 @@ mod tests {
     };
     use covert_types::{
         entity::Entity,
+        methods::system::ImportEntity,
         mount::MountEntry,
         policy::{PathPolicy, Policy},
         psql::RoleCredentials,
@@ -236,7 +469,7 @@ mod tests {
     use uuid::Uuid;
 
     use crate::{
-        expiration_manager::clock::test::TestClock,
+        expiration_manager::clock::{Clock, MockClock},
         repos::{mount::tests::pool, Repos},
         response::ResponseContext,
         Router,
@@ -262,11 +495,25 @@ mod tests {
                     path: "revoke".into(),
                 },
                 ttl: None,
+                max_ttl: None,
+                tags: BTreeMap::from([("app".to_string(), "web".to_string())]),
             }),
             "auth" => Response::Auth(covert_types::response::AuthResponse {
                 alias: "foo".to_string(),
                 ttl: None,
+                metadata: HashMap::from([("username".to_string(), "foo".to_string())]),
+                policies: vec![],
+                group_policies: vec![],
+                create_entity: false,
+                renewable: true,
             }),
+            "unregistered-lease" => Response::raw(SecretLeaseResponse {
+                lease_id: Uuid::new_v4().to_string(),
+                lease_duration: std::time::Duration::from_secs(60),
+                renewable: true,
+                data: Value::Null,
+            })
+            .unwrap(),
             _ => panic!("Invalid response type"),
         };
         Ok(ResponseWithCtx {
@@ -274,13 +521,14 @@ mod tests {
             ctx: ResponseContext {
                 backend_config: MountConfig::default(),
                 backend_mount_path: req.headers["mount-path"].to_string(),
+                index: None,
             },
         })
     }
 
     #[tokio::test]
     async fn register_lease_for_lease_responses() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
 
         let pool = Arc::new(pool().await);
         let u_pool = SqlitePool::connect(":memory:").await.unwrap();
@@ -310,7 +558,14 @@ mod tests {
         repos.mount.create(&mount).await.unwrap();
 
         let inner_handler = tower::service_fn(handler);
-        let svc = LeaseRegistrationService::new(inner_handler, exp_m, repos.token, repos.entity);
+        let svc = LeaseRegistrationService::new(
+            inner_handler,
+            exp_m,
+            repos.token,
+            repos.entity,
+            repos.mfa,
+            Arc::default(),
+        );
 
         let mut headers = HashMap::new();
         headers.insert("response-type".to_string(), "lease".to_string());
@@ -334,7 +589,8 @@ mod tests {
         let resp = svc.oneshot(req).await.unwrap();
 
         let lease_resp = resp.response.data::<CreateRoleCredsResponse>().unwrap();
-        assert_eq!(lease_resp.ttl, mount.config.default_lease_ttl);
+        assert_eq!(lease_resp.lease_duration, mount.config.default_lease_ttl);
+        assert!(lease_resp.renewable);
         assert_eq!(lease_resp.data.username, "foo");
         assert_eq!(lease_resp.data.password, "bar");
 
@@ -346,11 +602,129 @@ mod tests {
             .unwrap()
             .unwrap();
         assert_eq!(lease.issued_mount_path, mount.path);
+        assert_eq!(lease.issued_at, clock.now());
+        assert_eq!(
+            lease.expires_at,
+            clock.now() + chrono::Duration::from_std(mount.config.default_lease_ttl).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn lease_tags_include_token_and_entity_metadata() {
+        let clock = MockClock::new();
+
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let router = Arc::new(Router::new(repos.mount.clone()));
+        let exp_m = Arc::new(ExpirationManager::new(
+            Arc::clone(&router),
+            repos.clone(),
+            clock.clone(),
+        ));
+
+        let mount = MountEntry {
+            backend_type: BackendType::Postgres,
+            config: MountConfig::default(),
+            id: Uuid::new_v4(),
+            path: "psql/".to_string(),
+            namespace_id: ns.id.clone(),
+        };
+        repos.mount.create(&mount).await.unwrap();
+
+        repos
+            .entity
+            .import(
+                &[ImportEntity {
+                    name: "john".to_string(),
+                    metadata: HashMap::from([
+                        ("team".to_string(), "payments".to_string()),
+                        ("app".to_string(), "batch".to_string()),
+                    ]),
+                    policies: vec![],
+                    aliases: vec![],
+                }],
+                &ns.id,
+            )
+            .await
+            .unwrap();
+        let token_entry = TokenEntry::new(
+            "john".to_string(),
+            repos.token.now(),
+            chrono::Duration::hours(1),
+            ns.id.clone(),
+            HashMap::from([
+                ("username".to_string(), "john".to_string()),
+                ("team".to_string(), "ops".to_string()),
+            ]),
+            vec![],
+            true,
+        );
+        repos.token.create(&token_entry).await.unwrap();
+
+        let inner_handler = tower::service_fn(handler);
+        let svc = LeaseRegistrationService::new(
+            inner_handler,
+            exp_m,
+            repos.token,
+            repos.entity,
+            repos.mfa,
+            Arc::default(),
+        );
+
+        let mut headers = HashMap::new();
+        headers.insert("response-type".to_string(), "lease".to_string());
+        headers.insert("mount-path".to_string(), mount.path.clone());
+
+        let mut extensions = Extensions::default();
+        extensions.insert(ns.clone());
+        extensions.insert(token_entry.id().clone());
+
+        let req = Request {
+            id: Uuid::new_v4(),
+            namespace: vec!["root".to_string()],
+            data: Bytes::default(),
+            extensions,
+            headers,
+            operation: Operation::Read,
+            params: Vec::default(),
+            path: String::default(),
+            query_string: String::default(),
+            token: None,
+        };
+        let resp = svc.oneshot(req).await.unwrap();
+        let lease_resp = resp.response.data::<CreateRoleCredsResponse>().unwrap();
+
+        // Tags set by the backend take precedence over the entity metadata,
+        // which takes precedence over the alias metadata of the token
+        let lease = repos
+            .lease
+            .lookup(&lease_resp.lease_id, &ns.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            lease.tags(),
+            BTreeMap::from([
+                ("app".to_string(), "web".to_string()),
+                ("team".to_string(), "payments".to_string()),
+                ("username".to_string(), "john".to_string()),
+            ])
+        );
     }
 
     #[tokio::test]
+    #[allow(clippy::too_many_lines)]
     async fn register_lease_for_auth_responses() {
-        let clock = TestClock::new();
+        let clock = MockClock::new();
 
         let pool = Arc::new(pool().await);
         let u_pool = SqlitePool::connect(":memory:").await.unwrap();
@@ -410,8 +784,14 @@ mod tests {
             .unwrap();
 
         let inner_handler = tower::service_fn(handler);
-        let svc =
-            LeaseRegistrationService::new(inner_handler, exp_m, repos.token.clone(), repos.entity);
+        let svc = LeaseRegistrationService::new(
+            inner_handler,
+            exp_m,
+            repos.token.clone(),
+            repos.entity,
+            repos.mfa,
+            Arc::default(),
+        );
 
         let mut headers = HashMap::new();
         headers.insert("response-type".to_string(), "auth".to_string());
@@ -435,7 +815,8 @@ mod tests {
         let resp = svc.oneshot(req).await.unwrap();
 
         let auth_resp = resp.response.data::<AuthResponse>().unwrap();
-        assert_eq!(auth_resp.ttl, mount.config.default_lease_ttl);
+        assert_eq!(auth_resp.lease_duration, mount.config.default_lease_ttl);
+        assert!(auth_resp.renewable);
 
         // Lookup lease
         let lease = repos
@@ -458,5 +839,142 @@ mod tests {
                 .collect::<Vec<_>>(),
             vec![policy.name]
         );
+
+        // Alias metadata is stored on the token
+        let token_entry = repos.token.lookup(&auth_resp.token).await.unwrap().unwrap();
+        assert_eq!(token_entry.metadata["username"], "foo");
+    }
+
+    #[tokio::test]
+    async fn refuse_responses_claiming_unregistered_leases() {
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let router = Arc::new(Router::new(repos.mount.clone()));
+        let exp_m = Arc::new(ExpirationManager::new(
+            Arc::clone(&router),
+            repos.clone(),
+            MockClock::new(),
+        ));
+        let svc = LeaseRegistrationService::new(
+            tower::service_fn(handler),
+            exp_m,
+            repos.token,
+            repos.entity,
+            repos.mfa,
+            Arc::default(),
+        );
+
+        let mut headers = HashMap::new();
+        headers.insert(
+            "response-type".to_string(),
+            "unregistered-lease".to_string(),
+        );
+        headers.insert("mount-path".to_string(), "psql/".to_string());
+
+        let mut extensions = Extensions::default();
+        extensions.insert(ns);
+
+        let req = Request {
+            id: Uuid::new_v4(),
+            namespace: vec!["root".to_string()],
+            data: Bytes::default(),
+            extensions,
+            headers,
+            operation: Operation::Read,
+            params: Vec::default(),
+            path: String::default(),
+            query_string: String::default(),
+            token: None,
+        };
+        let err = svc.oneshot(req).await.unwrap_err();
+        assert_eq!(err.status_code, hyper::StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn register_lease_of_cancelled_request() {
+        let pool = Arc::new(pool().await);
+        let u_pool = SqlitePool::connect(":memory:").await.unwrap();
+        let repos = Repos::new(pool, u_pool);
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        repos.namespace.create(&ns).await.unwrap();
+
+        let router = Arc::new(Router::new(repos.mount.clone()));
+        let exp_m = Arc::new(ExpirationManager::new(
+            Arc::clone(&router),
+            repos.clone(),
+            MockClock::new(),
+        ));
+
+        let mount = MountEntry {
+            backend_type: BackendType::Postgres,
+            config: MountConfig::default(),
+            id: Uuid::new_v4(),
+            path: "psql/".to_string(),
+            namespace_id: ns.id.clone(),
+        };
+        repos.mount.create(&mount).await.unwrap();
+
+        let mut svc = LeaseRegistrationService::new(
+            tower::service_fn(handler),
+            exp_m,
+            repos.token,
+            repos.entity,
+            repos.mfa,
+            Arc::default(),
+        );
+
+        let mut headers = HashMap::new();
+        headers.insert("response-type".to_string(), "lease".to_string());
+        headers.insert("mount-path".to_string(), mount.path.clone());
+
+        let mut extensions = Extensions::default();
+        extensions.insert(ns.clone());
+
+        let req = Request {
+            id: Uuid::new_v4(),
+            namespace: vec!["root".to_string()],
+            data: Bytes::default(),
+            extensions,
+            headers,
+            operation: Operation::Read,
+            params: Vec::default(),
+            path: String::default(),
+            query_string: String::default(),
+            token: None,
+        };
+
+        // The backend responds on the first poll, then the request is dropped
+        // while the lease is being registered
+        let mut resp = svc.call(req);
+        assert!(futures::poll!(&mut resp).is_pending());
+        drop(resp);
+
+        let mut leases = vec![];
+        for _ in 0..100 {
+            leases = repos
+                .lease
+                .list_by_mount_prefix(&mount.path, &ns.id, &BTreeMap::new())
+                .await
+                .unwrap();
+            if !leases.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(leases.len(), 1);
     }
 }
@@ -1,5 +1,19 @@
+pub mod audit;
 pub mod auth_service;
+pub mod body_limit;
+pub mod concurrency_limit;
+pub mod consistency;
+pub mod cors;
+pub mod ha_forward;
+pub mod idempotency;
 pub mod lease_registration;
 pub mod namespace_extension;
+pub mod quota;
+pub mod request_id;
 pub mod request_mapper;
+pub mod response_wrapping;
+pub mod standby_read;
 pub mod storage_state_extension;
+pub mod timeout;
+#[cfg(feature = "ui")]
+pub mod ui;
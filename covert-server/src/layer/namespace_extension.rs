@@ -1,10 +1,39 @@
-use covert_types::{error::ApiError, request::Request, state::StorageState};
+use covert_types::{
+    error::{ApiError, ErrorCode},
+    request::Request,
+    state::StorageState,
+};
 use futures::future::BoxFuture;
-use hyper::StatusCode;
 use tower::{Layer, Service};
-use tracing_error::SpanTrace;
 
-use crate::{repos::namespace::NamespaceRepo, response::ResponseWithCtx};
+use crate::{
+    error::Error,
+    repos::namespace::{Namespace, NamespaceRepo},
+    response::ResponseWithCtx,
+};
+
+/// Looks up the namespace of the request. Leading path segments naming child
+/// namespaces select the namespace too, `/v1/team-a/kv/foo` is `/v1/kv/foo`
+/// with the namespace header set to `root/team-a`. The segments are moved from
+/// `path` to `namespace`.
+pub async fn select_namespace(
+    ns_repo: &NamespaceRepo,
+    namespace: &mut Vec<String>,
+    path: &mut String,
+) -> Result<Option<Namespace>, Error> {
+    let Some(mut ns) = ns_repo.find_by_path(namespace).await? else {
+        return Ok(None);
+    };
+    while let Some((name, rest)) = path.split_once('/') {
+        let Some(child) = ns_repo.find_child(&ns.id, name).await? else {
+            break;
+        };
+        namespace.push(child.name.clone());
+        *path = rest.to_string();
+        ns = child;
+    }
+    Ok(Some(ns))
+}
 
 #[derive(Clone)]
 pub struct NamespaceExtensionService<S> {
@@ -40,14 +69,13 @@ where
         let mut this = self.clone();
         Box::pin(async move {
             if req.extensions.get::<StorageState>() == Some(&StorageState::Unsealed) {
-                let ns = this
-                    .ns_repo
-                    .find_by_path(&req.namespace)
+                let ns = select_namespace(&this.ns_repo, &mut req.namespace, &mut req.path)
                     .await?
-                    .ok_or_else(|| ApiError {
-                        status_code: StatusCode::BAD_REQUEST,
-                        span_trace: Some(SpanTrace::capture()),
-                        error: anyhow::Error::msg("Invalid namespace"),
+                    .ok_or_else(|| {
+                        ApiError::new(
+                            ErrorCode::BadRequest,
+                            anyhow::Error::msg("Invalid namespace"),
+                        )
                     })?;
                 req.extensions.insert(ns);
             }
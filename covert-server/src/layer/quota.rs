@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use covert_types::{error::ApiError, request::Request};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::{
+    quota::{QuotaManager, QuotaRequest},
+    repos::policy::ROOT_POLICY,
+    response::ResponseWithCtx,
+};
+
+use super::auth_service::{TokenEntity, TokenPolicies};
+
+/// Rejects requests that exceed a rate limit quota before they are handled.
+#[derive(Clone)]
+pub struct QuotaService<S> {
+    inner: S,
+    quotas: Arc<QuotaManager>,
+}
+
+impl<S> QuotaService<S> {
+    pub fn new(inner: S, quotas: Arc<QuotaManager>) -> Self {
+        Self { inner, quotas }
+    }
+}
+
+impl<S> Service<Request> for QuotaService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut this = self.clone();
+        Box::pin(async move {
+            if this.quotas.is_empty() {
+                return this.inner.call(req).await;
+            }
+
+            let root = req
+                .extensions
+                .get::<TokenPolicies>()
+                .is_some_and(|policies| {
+                    policies.0.iter().any(|policy| {
+                        policy.name == ROOT_POLICY
+                            && this.quotas.is_root_namespace(&policy.namespace_id)
+                    })
+                });
+            // Resolved with the policies of the token by the auth service
+            let entity = req
+                .extensions
+                .get::<TokenEntity>()
+                .filter(|entity| this.quotas.is_root_namespace(&entity.namespace_id))
+                .map(|entity| entity.name.as_str());
+            let path = req
+                .namespace
+                .iter()
+                .skip(1)
+                .map(|ns| format!("{ns}/"))
+                .chain(std::iter::once(req.path.clone()))
+                .collect::<String>();
+
+            this.quotas
+                .check(&QuotaRequest {
+                    path: &path,
+                    entity,
+                    root,
+                })
+                .map_err(|exceeded| {
+                    ApiError::rate_limited(
+                        anyhow::Error::msg(format!(
+                            "Rate limit quota `{}` exceeded",
+                            exceeded.name
+                        )),
+                        exceeded.retry_after,
+                    )
+                })?;
+
+            this.inner.call(req).await
+        })
+    }
+}
+
+pub struct QuotaLayer {
+    quotas: Arc<QuotaManager>,
+}
+
+impl QuotaLayer {
+    pub fn new(quotas: Arc<QuotaManager>) -> Self {
+        Self { quotas }
+    }
+}
+
+impl<S> Layer<S> for QuotaLayer {
+    type Service = QuotaService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QuotaService::new(inner, Arc::clone(&self.quotas))
+    }
+}
@@ -0,0 +1,162 @@
+use std::{net::IpAddr, sync::Arc};
+
+use covert_types::request::{ClientAddr, RequestId, REQUEST_ID_HEADER};
+use futures::future::BoxFuture;
+use hyper::{header::HeaderValue, http};
+use tower::{Layer, Service};
+use tracing::{field::Empty, info_span, Instrument};
+use uuid::Uuid;
+
+/// Assigns every request an id and handles it inside a `request` span
+/// carrying the id, method and path, so logs emitted anywhere while handling
+/// the request can be correlated with it. The id is returned in the
+/// [`REQUEST_ID_HEADER`] response header.
+///
+/// A valid id sent by a trusted proxy is used instead of generating a new one.
+/// The `token_accessor` and `entity` fields of the span are recorded once the
+/// token of the request has been resolved.
+#[derive(Debug, Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+    trusted_proxies: Arc<[IpAddr]>,
+}
+
+impl<S> RequestIdService<S> {
+    pub fn new(inner: S, trusted_proxies: Arc<[IpAddr]>) -> Self {
+        Self {
+            inner,
+            trusted_proxies,
+        }
+    }
+
+    /// The id sent by the client if the client is a trusted proxy and the id
+    /// is a valid UUID.
+    fn forwarded_id<B>(&self, req: &http::Request<B>) -> Option<Uuid> {
+        let ClientAddr(addr) = req.extensions().get::<ClientAddr>()?;
+        if !self.trusted_proxies.contains(&addr.ip()) {
+            return None;
+        }
+        let value = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?;
+        Uuid::parse_str(value.trim()).ok()
+    }
+}
+
+impl<S, B, ResBody> Service<http::Request<B>> for RequestIdService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<ResBody>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        let id = self.forwarded_id(&req).unwrap_or_else(Uuid::new_v4);
+        req.extensions_mut().insert(RequestId(id));
+
+        let span = info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            path = %req.uri().path(),
+            token_accessor = Empty,
+            entity = Empty,
+        );
+        let fut = span.in_scope(|| self.inner.call(req));
+        Box::pin(
+            async move {
+                let mut resp = fut.await?;
+                if let Ok(value) = HeaderValue::from_str(&id.to_string()) {
+                    resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+                Ok(resp)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// [`Layer`] that assigns request ids with [`RequestIdService`].
+pub struct RequestIdLayer {
+    trusted_proxies: Arc<[IpAddr]>,
+}
+
+impl RequestIdLayer {
+    pub fn new(trusted_proxies: &[IpAddr]) -> Self {
+        Self {
+            trusted_proxies: trusted_proxies.into(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService::new(inner, Arc::clone(&self.trusted_proxies))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, net::SocketAddr};
+
+    use hyper::Body;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    async fn call(client: IpAddr, header: Option<&str>) -> (Uuid, String) {
+        let svc = RequestIdLayer::new(&["10.0.0.1".parse().unwrap()]).layer(service_fn(
+            |req: http::Request<Body>| async move {
+                let RequestId(id) = *req.extensions().get::<RequestId>().unwrap();
+                Ok::<_, Infallible>(http::Response::new(id))
+            },
+        ));
+        let mut req = http::Request::builder().uri("/v1/sys/status");
+        if let Some(header) = header {
+            req = req.header(REQUEST_ID_HEADER, header);
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ClientAddr(SocketAddr::new(client, 1234)));
+
+        let resp = svc.oneshot(req).await.unwrap();
+        let header = resp.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        (resp.into_body(), header)
+    }
+
+    #[tokio::test]
+    async fn assigns_request_ids() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "10.0.0.2".parse().unwrap();
+        let forwarded = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+        // Generated ids are returned in the header
+        let (id, header) = call(client, None).await;
+        assert_eq!(id.to_string(), header);
+        assert_ne!(call(client, None).await.0, id);
+
+        // Only trusted proxies can set the id
+        let (id, header) = call(proxy, Some(forwarded)).await;
+        assert_eq!(id.to_string(), forwarded);
+        assert_eq!(header, forwarded);
+        assert_ne!(call(client, Some(forwarded)).await.0.to_string(), forwarded);
+
+        // Invalid ids are replaced
+        let (id, header) = call(proxy, Some("not-a-uuid")).await;
+        assert_eq!(id.to_string(), header);
+    }
+}
@@ -1,12 +1,13 @@
 use std::convert::Infallible;
 
+use covert_framework::normalize_path;
 use covert_types::{error::ApiError, request::Request};
 use futures::future::BoxFuture;
 use http_body::Limited;
 use hyper::{http, Body};
 use tower::{Layer, Service, ServiceExt};
 
-use crate::response::ResponseWithCtx;
+use crate::{error::ErrorType, layer::standby_read::ForwardToActive, response::ResponseWithCtx};
 
 #[derive(Debug, Clone)]
 pub struct LogicalRequestResponseService<S> {
@@ -40,12 +41,28 @@ where
     fn call(&mut self, req: http::Request<Limited<Body>>) -> Self::Future {
         let this = self.clone();
         Box::pin(async move {
-            let logical_req = match Request::new(req).await {
+            let mut logical_req = match Request::new(req).await {
                 Ok(req) => req,
                 Err(e) => return Ok(e.into()),
             };
+            // Normalize before the policies are checked so the request is
+            // authorized against the path that is actually routed.
+            logical_req.path = match normalize_path(&logical_req.path) {
+                Ok(path) => path,
+                Err(e) => return Ok(e.into()),
+            };
             match this.inner.oneshot(logical_req).await {
                 Ok(resp) => Ok(resp.into()),
+                Err(error)
+                    if matches!(
+                        error.error.downcast_ref::<ErrorType>(),
+                        Some(ErrorType::ForwardToActive)
+                    ) =>
+                {
+                    let mut resp: http::Response<Body> = error.into();
+                    resp.extensions_mut().insert(ForwardToActive);
+                    Ok(resp)
+                }
                 Err(error) => {
                     let error_report = error.report();
                     tracing::error!(?error_report, "API error encountered");
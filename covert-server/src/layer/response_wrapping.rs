@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use covert_types::{
+    error::ApiError, methods::system::WrapInfo, request::Request, response::Response,
+};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::{
+    error::{Error, ErrorType},
+    repos::{namespace::Namespace, wrapping::WrappingRepo},
+    response::ResponseWithCtx,
+};
+
+/// Max TTL of a wrapping token. Wrapped responses are meant to be handed off
+/// and unwrapped right away.
+const MAX_WRAP_TTL: Duration = Duration::from_hours(24);
+
+/// Replaces the response of requests that set a wrap TTL with a single-use
+/// wrapping token. The response is stored until the token is unwrapped or
+/// expires.
+#[derive(Clone)]
+pub struct ResponseWrappingService<S> {
+    inner: S,
+    wrapping_repo: WrappingRepo,
+}
+
+impl<S> ResponseWrappingService<S> {
+    pub fn new(inner: S, wrapping_repo: WrappingRepo) -> Self {
+        Self {
+            inner,
+            wrapping_repo,
+        }
+    }
+}
+
+impl<S> Service<Request> for ResponseWrappingService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut this = self.clone();
+        Box::pin(async move {
+            let Some(ttl) = req.wrap_ttl()? else {
+                return this.inner.call(req).await;
+            };
+            if ttl > MAX_WRAP_TTL {
+                return Err(Error::from(ErrorType::BadRequest(format!(
+                    "Wrap TTL cannot be longer than {}",
+                    humantime_serde::re::humantime::format_duration(MAX_WRAP_TTL)
+                )))
+                .into());
+            }
+            let ns = req
+                .extensions
+                .get::<Namespace>()
+                .cloned()
+                .ok_or_else(ApiError::bad_request)?;
+            let creation_path = req.path.clone();
+
+            // Routes returning binary bodies reject wrapped requests before
+            // their handler runs, see `MethodRouter::binary`
+            let resp = this.inner.call(req).await?;
+            let Response::Raw(data) = resp.response else {
+                return Err(Error::from(ErrorType::BadRequest(
+                    "Only JSON responses can be wrapped".to_string(),
+                ))
+                .into());
+            };
+
+            let ttl = chrono::Duration::from_std(ttl).map_err(|_| ApiError::bad_request())?;
+            let entry = this
+                .wrapping_repo
+                .wrap(&data, &creation_path, ttl, &ns.id)
+                .await?;
+            let info = WrapInfo {
+                ttl: entry
+                    .ttl()
+                    .to_std()
+                    .map_err(|_| ApiError::internal_error())?,
+                token: entry.token,
+                creation_time: entry.created_at,
+                creation_path: entry.creation_path,
+            };
+            let response =
+                Response::raw(info).map_err(|err| Error::from(ErrorType::BadResponseData(err)))?;
+            Ok(ResponseWithCtx {
+                response,
+                ctx: resp.ctx,
+            })
+        })
+    }
+}
+
+pub struct ResponseWrappingLayer {
+    wrapping_repo: WrappingRepo,
+}
+
+impl ResponseWrappingLayer {
+    pub fn new(wrapping_repo: WrappingRepo) -> Self {
+        Self { wrapping_repo }
+    }
+}
+
+impl<S> Layer<S> for ResponseWrappingLayer {
+    type Service = ResponseWrappingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseWrappingService::new(inner, self.wrapping_repo.clone())
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use covert_types::{
+    backend::BackendType,
+    error::ApiError,
+    request::{Request, WRAP_TTL_HEADER},
+};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::{
+    error::{Error, ErrorType},
+    ha::HaState,
+    layer::consistency::required_index,
+    repos::{mount::MountRepo, namespace::Namespace},
+    response::ResponseWithCtx,
+    router::Router,
+    system::SYSTEM_MOUNT_PATH,
+};
+
+/// Backends whose reads never write to the storage or issue secrets, so a
+/// performance standby can serve them.
+const STANDBY_READ_BACKENDS: &[BackendType] =
+    &[BackendType::Kv, BackendType::Ldap, BackendType::Userpass];
+
+/// Set on the response of a read a performance standby cannot serve, the
+/// [`HaForwardLayer`](super::ha_forward::HaForwardLayer) sends it to the
+/// active node instead.
+#[derive(Debug, Clone, Copy)]
+pub struct ForwardToActive;
+
+/// Refuses the reads a performance standby cannot serve with
+/// [`ErrorType::ForwardToActive`]: reads of backends issuing secrets, reads
+/// of mounts the standby has not caught up with, wrapped responses and reads
+/// requiring a write index the standby has not caught up to.
+#[derive(Clone)]
+pub struct StandbyReadService<S> {
+    inner: S,
+    ha: Arc<HaState>,
+    mount_repo: MountRepo,
+    router: Arc<Router>,
+}
+
+impl<S> StandbyReadService<S> {
+    async fn served_locally(&self, req: &Request) -> Result<bool, Error> {
+        if self.ha.is_active() || req.path.starts_with(SYSTEM_MOUNT_PATH) {
+            return Ok(true);
+        }
+        if req.headers.contains_key(&WRAP_TTL_HEADER.to_lowercase())
+            || required_index(req)?.is_some_and(|index| index > self.ha.replicated_index())
+        {
+            return Ok(false);
+        }
+        let Some(ns) = req.extensions.get::<Namespace>() else {
+            return Ok(true);
+        };
+        let Some(mount) = self.mount_repo.longest_prefix(&req.path, &ns.id).await? else {
+            return Ok(true);
+        };
+        Ok(STANDBY_READ_BACKENDS.contains(&mount.backend_type)
+            && self.router.get(mount.id).is_some())
+    }
+}
+
+impl<S> Service<Request> for StandbyReadService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut this = self.clone();
+        Box::pin(async move {
+            if !this.served_locally(&req).await? {
+                return Err(Error::from(ErrorType::ForwardToActive).into());
+            }
+            this.inner.call(req).await
+        })
+    }
+}
+
+pub struct StandbyReadLayer {
+    ha: Arc<HaState>,
+    mount_repo: MountRepo,
+    router: Arc<Router>,
+}
+
+impl StandbyReadLayer {
+    pub fn new(ha: Arc<HaState>, mount_repo: MountRepo, router: Arc<Router>) -> Self {
+        Self {
+            ha,
+            mount_repo,
+            router,
+        }
+    }
+}
+
+impl<S> Layer<S> for StandbyReadLayer {
+    type Service = StandbyReadService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        StandbyReadService {
+            inner,
+            ha: Arc::clone(&self.ha),
+            mount_repo: self.mount_repo.clone(),
+            router: Arc::clone(&self.router),
+        }
+    }
+}
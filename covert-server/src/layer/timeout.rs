@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use covert_types::{error::ApiError, request::Request};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::{
+    error::{Error, ErrorType},
+    response::ResponseWithCtx,
+};
+
+/// Fails the request with a timeout error if the backend does not respond
+/// within the timeout. The future of the handler is dropped when the timeout
+/// fires, which cancels the I/O it is waiting on, e.g. a transaction against
+/// an external database is rolled back.
+#[derive(Clone)]
+pub struct TimeoutService<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S> TimeoutService<S> {
+    pub fn new(inner: S, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<S> Service<Request> for TimeoutService<S>
+where
+    S: Service<Request, Response = ResponseWithCtx, Error = ApiError> + Send + Clone + 'static,
+    S::Future: Send,
+{
+    type Response = ResponseWithCtx;
+
+    type Error = ApiError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let timeout = self.timeout;
+        let path = req.path.clone();
+        let resp = self.inner.call(req);
+        Box::pin(async move {
+            if let Ok(resp) = tokio::time::timeout(timeout, resp).await {
+                resp
+            } else {
+                tracing::warn!(
+                    path,
+                    ?timeout,
+                    "Request timed out, the handler was cancelled"
+                );
+                Err(Error::from(ErrorType::RequestTimeout(timeout)).into())
+            }
+        })
+    }
+}
+
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = TimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimeoutService::new(inner, self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use covert_types::{error::ErrorCode, request::Operation, response::Response};
+    use hyper::http::Extensions;
+    use serde_json::Value;
+    use tower::{service_fn, ServiceExt};
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Sets the flag when the handler future is dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn request() -> Request {
+        Request {
+            id: Uuid::default(),
+            operation: Operation::Read,
+            path: "psql/creds/foo".to_string(),
+            namespace: vec![],
+            data: Vec::default().into(),
+            extensions: Extensions::default(),
+            token: None,
+            params: vec![],
+            query_string: String::default(),
+            headers: HashMap::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_handler_on_timeout() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&dropped);
+        let svc =
+            TimeoutLayer::new(Duration::from_millis(20)).layer(service_fn(move |_req: Request| {
+                let guard = DropFlag(Arc::clone(&flag));
+                async move {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    drop(guard);
+                    Ok::<_, ApiError>(ResponseWithCtx {
+                        response: Response::Raw(Value::Null),
+                        ctx: crate::response::ResponseContext::default(),
+                    })
+                }
+            }));
+
+        let err = svc.oneshot(request()).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::Timeout);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn respond_within_timeout() {
+        let svc =
+            TimeoutLayer::new(Duration::from_secs(1)).layer(service_fn(|_req: Request| async {
+                Ok::<_, ApiError>(ResponseWithCtx {
+                    response: Response::Raw(Value::Null),
+                    ctx: crate::response::ResponseContext::default(),
+                })
+            }));
+
+        assert!(svc.oneshot(request()).await.is_ok());
+    }
+}
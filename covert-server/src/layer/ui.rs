@@ -0,0 +1,212 @@
+use futures::future::BoxFuture;
+use hyper::{
+    header::{
+        ALLOW, CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, LOCATION,
+        X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    },
+    http, Body, Method, StatusCode,
+};
+use rust_embed::RustEmbed;
+use tower::{Layer, Service};
+
+/// Path the web UI is served at.
+pub const UI_PATH: &str = "/ui";
+
+#[derive(RustEmbed)]
+#[folder = "ui/"]
+struct Assets;
+
+/// Serves the embedded web UI under [`UI_PATH`] and passes every other request
+/// on to the API.
+#[derive(Debug, Clone)]
+pub struct UiService<S> {
+    inner: S,
+}
+
+impl<S> UiService<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for UiService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<Body>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        match asset_path(req.uri().path()) {
+            Some(path) => {
+                let resp = serve(req.method(), path);
+                Box::pin(async move { Ok(resp) })
+            }
+            None => Box::pin(self.inner.call(req)),
+        }
+    }
+}
+
+/// Path of the request relative to [`UI_PATH`], if the request is for the UI.
+fn asset_path(path: &str) -> Option<&str> {
+    path.strip_prefix(UI_PATH)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn serve(method: &Method, path: &str) -> http::Response<Body> {
+    if method != Method::GET && method != Method::HEAD {
+        return http::Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(ALLOW, "GET, HEAD")
+            .body(Body::empty())
+            .unwrap_or_default();
+    }
+    // Relative asset URLs in the index only resolve under the trailing slash
+    let Some(path) = path.strip_prefix('/') else {
+        return http::Response::builder()
+            .status(StatusCode::PERMANENT_REDIRECT)
+            .header(LOCATION, format!("{UI_PATH}/"))
+            .body(Body::empty())
+            .unwrap_or_default();
+    };
+    if path.split('/').any(|segment| segment == "..") {
+        return not_found();
+    }
+    let path = if path.is_empty() || path.ends_with('/') {
+        format!("{path}index.html")
+    } else {
+        path.to_string()
+    };
+    let Some(asset) = Assets::get(&path) else {
+        return not_found();
+    };
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::from(asset.data.into_owned())
+    };
+    http::Response::builder()
+        .header(CONTENT_TYPE, content_type(&path))
+        .header(CACHE_CONTROL, "no-cache")
+        .header(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(X_FRAME_OPTIONS, "DENY")
+        .header(
+            CONTENT_SECURITY_POLICY,
+            "default-src 'self'; frame-ancestors 'none'",
+        )
+        .body(body)
+        .unwrap_or_default()
+}
+
+fn not_found() -> http::Response<Body> {
+    http::Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .unwrap_or_default()
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+pub struct UiLayer {}
+
+impl UiLayer {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl<S> Layer<S> for UiLayer {
+    type Service = UiService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        UiService::new(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use hyper::{Body, Request};
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    async fn call(method: Method, path: &str) -> http::Response<Body> {
+        let svc = UiLayer::new().layer(service_fn(|_: Request<Body>| async {
+            Ok::<_, Infallible>(http::Response::new(Body::from("api")))
+        }));
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        svc.oneshot(req).await.unwrap()
+    }
+
+    async fn body(resp: http::Response<Body>) -> String {
+        let bytes = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_assets() {
+        let resp = call(Method::GET, "/ui/").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert!(body(resp).await.contains("<title>Covert</title>"));
+
+        let resp = call(Method::GET, "/ui/app.js").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()[CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+
+        let resp = call(Method::HEAD, "/ui/style.css").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(body(resp).await.is_empty());
+
+        let resp = call(Method::GET, "/ui").await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()[LOCATION], "/ui/");
+
+        assert_eq!(
+            call(Method::GET, "/ui/missing.js").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            call(Method::POST, "/ui/").await.status(),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_api_requests_through() {
+        for path in ["/v1/sys/status", "/uix", "/v1/ui/"] {
+            assert_eq!(body(call(Method::GET, path).await).await, "api");
+        }
+    }
+}
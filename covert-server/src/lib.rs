@@ -5,69 +5,155 @@
 #![allow(clippy::module_name_repetitions)]
 #![allow(clippy::missing_errors_doc)]
 
+mod audit;
 mod config;
 mod context;
 mod error;
 mod expiration_manager;
+mod field_encryption;
+mod ha;
 mod helpers;
+mod identity;
+mod in_process;
 mod layer;
+mod metrics;
+mod mfa;
 mod migrations;
+mod plugin;
+mod quota;
 mod recovery;
+mod redact;
+mod reload;
 mod repos;
 mod response;
+mod rotation;
 mod router;
+mod seal;
+mod support_bundle;
 mod system;
+mod tamper;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod tls;
+mod unix;
 
-use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::Context as _;
 
 pub use config::*;
-use context::ChildProcesses;
+use context::{ChildProcesses, TokenRevocationJobs};
+use covert_framework::{compression::compression_layer, request_log::RequestLogLayer};
 use covert_storage::EncryptedPool;
-pub use expiration_manager::{ExpirationManager, LeaseEntry};
+use covert_types::request::ClientAddr;
+#[cfg(feature = "test-util")]
+pub use expiration_manager::clock::MockClock;
+pub use expiration_manager::{
+    clock::{Clock, SystemClock},
+    ExpirationManager, LeaseEntry,
+};
+use futures::{future::Either, FutureExt};
+use hyper::{server::conn::AddrStream, service::make_service_fn};
+pub use in_process::InProcessService;
+pub use reload::{ConfigReloader, Reloader};
 pub use router::{Router, RouterService};
 use sqlx::sqlite::SqliteConnectOptions;
-use tower::{make::Shared, ServiceBuilder};
-use tower_http::{cors::CorsLayer, limit::RequestBodyLimitLayer};
+use tokio::net::{TcpStream, UnixStream};
+use tokio_rustls::server::TlsStream;
+use tower::{util::MapRequest, ServiceBuilder};
 use tracing::info;
 
 use crate::{
+    audit::AuditBroker,
     context::Context,
-    expiration_manager::clock::SystemClock,
+    ha::HaState,
     layer::{
-        auth_service::AuthServiceLayer, lease_registration::LeaseRegistrationLayer,
-        namespace_extension::NamespaceExtensionLayer, request_mapper::LogicalRequestResponseLayer,
+        audit::AuditLayer,
+        auth_service::AuthServiceLayer,
+        body_limit::BodyLimitLayer,
+        concurrency_limit::ConcurrencyLimitLayer,
+        consistency::{ConsistencyLayer, CONSISTENCY_TIMEOUT},
+        cors::CorsLayer,
+        ha_forward::HaForwardLayer,
+        idempotency::{IdempotencyCache, IdempotencyLayer},
+        lease_registration::LeaseRegistrationLayer,
+        namespace_extension::NamespaceExtensionLayer,
+        quota::QuotaLayer,
+        request_id::RequestIdLayer,
+        request_mapper::LogicalRequestResponseLayer,
+        response_wrapping::ResponseWrappingLayer,
+        standby_read::StandbyReadLayer,
         storage_state_extension::StorageStateExtensionLayer,
+        timeout::TimeoutLayer,
     },
+    mfa::PendingLogins,
+    plugin::Plugins,
+    quota::QuotaManager,
     recovery::{recover, recover_encrypted_storage_snapshot, replicate},
+    reload::{reload_on_sighup, ConfigReload},
     repos::Repos,
+    rotation::rotate_static_secrets,
     system::new_system_backend,
+    tamper::{seal_on_tamper, TamperMonitor},
 };
 
+/// Resolves when the process receives SIGINT or SIGTERM.
 pub async fn shutdown_signal() {
-    // Wait for the CTRL+C signal
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to install CTRL+C signal handler");
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM signal handler");
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res.expect("failed to install CTRL+C signal handler"),
+        _ = sigterm.recv() => (),
+    }
 }
 
-async fn shutdown_handler(child_processes: ChildProcesses) {
-    child_processes.kill_all().await;
+/// Returned by [`start`] when the in-flight requests and revocations did not
+/// finish within the [`Config::shutdown_timeout`].
+#[derive(Debug, thiserror::Error)]
+#[error("Shutdown timed out after {0:?}, in-flight work was abandoned")]
+pub struct ShutdownTimedOut(pub Duration);
+
+/// Attach a value describing the connection, e.g. the address of the client,
+/// to every request received on it.
+fn with_connection_info<S, T>(
+    svc: S,
+    info: Option<T>,
+) -> MapRequest<S, impl FnMut(hyper::Request<hyper::Body>) -> hyper::Request<hyper::Body> + Clone>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    ServiceBuilder::new()
+        .map_request(move |mut req: hyper::Request<hyper::Body>| {
+            if let Some(info) = info.clone() {
+                req.extensions_mut().insert(info);
+            }
+            req
+        })
+        .service(svc)
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn start(
     mut config: Config,
     shutdown_signal: impl Future<Output = ()>,
 ) -> anyhow::Result<()> {
     config.sanitize()?;
+    let tls_config = config
+        .tls
+        .as_ref()
+        .map(tls::server_config)
+        .transpose()?
+        .map(|server_config| Arc::new(tls::ReloadableServerConfig::new(server_config)));
 
     let child_processes = ChildProcesses::default();
-    let shutdown_handler = async {
+    let shutdown_signal = async {
         shutdown_signal.await;
-        info!("Shutdown signal received");
-        shutdown_handler(child_processes.clone()).await;
-    };
+        info!("Shutdown signal received, draining in-flight requests");
+    }
+    .shared();
 
-    let port_tx = config.port_tx.take();
+    let mut port_tx = config.port_tx.take();
+    let service_tx = config.service_tx.take();
     let config = Arc::new(config);
 
     // Try to recover as far as possible if replication has configured and we
@@ -110,16 +196,39 @@ pub async fn start(
     }
 
     let encrypted_pool = Arc::new(EncryptedPool::new(&config.encrypted_storage_path()));
-    let repos = Repos::new(encrypted_pool, seal_db);
+    // Tokens expire on the same clock as the leases that revoke them
+    let clock: Arc<dyn Clock> = config
+        .clock
+        .clone()
+        .unwrap_or_else(|| Arc::new(SystemClock::new()));
+    let repos = Repos::new(encrypted_pool, seal_db)
+        .with_policy_limits(config.policy_limits)
+        .with_clock(Arc::clone(&clock));
 
     // Run migration
     crate::migrations::migrate_unecrypted_db(&repos.unecrypted_pool).await?;
 
-    let router = Arc::new(Router::new(repos.mount.clone()));
-    let expiration = Arc::new(ExpirationManager::new(
-        Arc::clone(&router),
-        repos.clone(),
-        SystemClock::new(),
+    let tamper = Arc::new(TamperMonitor::default());
+    if let Some(tamper_config) = repos.seal.get_tamper_config().await? {
+        tamper.set_config(tamper_config);
+    }
+
+    let router =
+        Arc::new(Router::new(repos.mount.clone()).with_path_disclosure(config.path_disclosure));
+    let expiration = Arc::new(
+        ExpirationManager::new(Arc::clone(&router), repos.clone(), clock)
+            .with_max_lease_ttl(config.max_lease_ttl),
+    );
+    let audit = Arc::new(AuditBroker::default());
+    let quotas = Arc::new(QuotaManager::default());
+    let idempotency = Arc::new(IdempotencyCache::default());
+    let pending_logins = Arc::new(PendingLogins::default());
+    let plugins = Arc::new(Plugins::default());
+    let ha = Arc::new(HaState::new(config.ha.clone())?);
+    let reload = Arc::new(ConfigReload::new(
+        tls_config.clone(),
+        config.tls.clone(),
+        config.reloader.clone(),
     ));
     let ctx = Context {
         config: Arc::clone(&config),
@@ -127,45 +236,226 @@ pub async fn start(
         child_processes: child_processes.clone(),
         expiration_manager: Arc::clone(&expiration),
         router: Arc::clone(&router),
+        token_revocation_jobs: TokenRevocationJobs::default(),
+        audit: Arc::clone(&audit),
+        quotas: Arc::clone(&quotas),
+        idempotency: Arc::clone(&idempotency),
+        reload: Arc::clone(&reload),
+        tamper: Arc::clone(&tamper),
+        pending_logins: Arc::clone(&pending_logins),
+        snapshot_key: Arc::default(),
+        plugins: Arc::clone(&plugins),
+        ha: Arc::clone(&ha),
     };
+    let tamper_task = tokio::spawn(seal_on_tamper(ctx.clone()));
+    let election_task = tokio::spawn(ha::elect(ctx.clone()));
+    let rotation_task = tokio::spawn(rotate_static_secrets(ctx.clone()));
 
     // Mount system backend
-    let system = new_system_backend(ctx);
+    let system = new_system_backend(ctx.clone());
     router.mount_system(Arc::new(system));
 
+    // Fail closed, the server stays sealed if the seal cannot unwrap the key
+    if let Err(error) = system::auto_unseal(&ctx).await {
+        tracing::error!(?error, "Failed to unseal with the configured seal");
+    }
+
+    #[cfg(feature = "ui")]
+    let ui_layer = layer::ui::UiLayer::new();
+    #[cfg(not(feature = "ui"))]
+    let ui_layer = tower::layer::util::Identity::new();
+
     let server_router_svc = ServiceBuilder::new()
-        .concurrency_limit(1000)
-        .timeout(Duration::from_secs(30))
-        .layer(RequestBodyLimitLayer::new(1024 * 16))
-        .layer(CorsLayer::permissive())
+        .layer(RequestIdLayer::new(&config.trusted_proxies))
+        .layer(compression_layer(config.compression))
+        .layer(ConcurrencyLimitLayer::new(config.max_concurrent_requests))
+        .layer(BodyLimitLayer::new(
+            config.max_request_body_size,
+            Arc::clone(&repos.pool),
+            repos.namespace.clone(),
+            repos.mount.clone(),
+        ))
+        .layer(CorsLayer::new(config.cors.clone()))
+        .layer(ui_layer)
+        .map_request(metrics::rewrite_scrape_path)
+        .layer(HaForwardLayer::new(Arc::clone(&ha), repos.ha.clone()))
         .layer(LogicalRequestResponseLayer::new())
+        .layer(RequestLogLayer::new(config.request_log))
+        .layer(ConsistencyLayer::new(
+            repos.write_index.clone(),
+            CONSISTENCY_TIMEOUT,
+        ))
         .layer(StorageStateExtensionLayer::new(Arc::clone(&repos.pool)))
         .layer(NamespaceExtensionLayer::new(repos.namespace.clone()))
+        .layer(StandbyReadLayer::new(
+            ha,
+            repos.mount.clone(),
+            Arc::clone(&router),
+        ))
         .layer(AuthServiceLayer::new(
             repos.token.clone(),
             repos.namespace.clone(),
+            tamper,
         ))
+        .layer(QuotaLayer::new(quotas))
+        .layer(AuditLayer::new(Arc::clone(&audit), repos.token.clone()))
+        .layer(IdempotencyLayer::new(idempotency))
+        .layer(ResponseWrappingLayer::new(repos.wrapping.clone()))
         .layer(LeaseRegistrationLayer::new(
             expiration.clone(),
             repos.token.clone(),
             repos.entity.clone(),
+            repos.mfa.clone(),
+            pending_logins,
         ))
+        // Only the backend is cancelled, a secret or login it issued is always
+        // registered with a lease
+        .layer(TimeoutLayer::new(config.request_timeout))
         .service(RouterService::new(router.clone()));
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
-    let covert_server = hyper::Server::bind(&addr).serve(Shared::new(server_router_svc));
-    let addr = covert_server.local_addr();
-    let covert_server = covert_server.with_graceful_shutdown(shutdown_handler);
-
-    info!("listening on {addr}");
-    if let Some(tx) = port_tx {
-        let _ = tx.send(addr.port());
+    // Only the address of `port` is announced on `port_tx`, it comes before
+    // the TCP `listener`s
+    let mut tcp_servers = vec![];
+    for addr in config.tcp_addresses() {
+        let svc = server_router_svc.clone();
+        let port_tx = port_tx.take().filter(|_| config.port.is_some());
+        let announce = |addr: SocketAddr| {
+            info!("listening on {addr}");
+            if let Some(tx) = port_tx {
+                let _ = tx.send(addr.port());
+            }
+        };
+        if let Some(tls_config) = tls_config.clone() {
+            let (incoming, addr) = tls::bind(addr, tls_config).await?;
+            announce(addr);
+            let make_service = make_service_fn(move |conn: &TlsStream<TcpStream>| {
+                let client_addr = conn.get_ref().0.peer_addr().ok().map(ClientAddr);
+                let svc = with_connection_info(svc.clone(), client_addr);
+                async move { Ok::<_, Infallible>(svc) }
+            });
+            tcp_servers.push(Either::Left(
+                hyper::Server::builder(incoming)
+                    .serve(make_service)
+                    .with_graceful_shutdown(shutdown_signal.clone()),
+            ));
+        } else {
+            let make_service = make_service_fn(move |conn: &AddrStream| {
+                let client_addr = Some(ClientAddr(conn.remote_addr()));
+                let svc = with_connection_info(svc.clone(), client_addr);
+                async move { Ok::<_, Infallible>(svc) }
+            });
+            let covert_server = hyper::Server::try_bind(&addr)
+                .with_context(|| format!("Failed to bind listener `{addr}`"))?
+                .serve(make_service);
+            announce(covert_server.local_addr());
+            tcp_servers.push(Either::Right(
+                covert_server.with_graceful_shutdown(shutdown_signal.clone()),
+            ));
+        }
     }
+    let tcp_server = futures::future::try_join_all(tcp_servers);
+
+    let unix_servers = config
+        .listeners
+        .iter()
+        .filter_map(|listener| match &listener.address {
+            ListenerAddress::Unix(path) => Some((listener, path)),
+            ListenerAddress::Tcp(_) => None,
+        })
+        .map(|(listener, path)| {
+            let incoming = unix::bind(path, listener)
+                .with_context(|| format!("Failed to bind listener `{}`", listener.address))?;
+            info!("listening on {}", listener.address);
+
+            // Attach the credentials of the peer process to every request
+            // received on the connection
+            let svc = server_router_svc.clone();
+            let make_service = make_service_fn(move |conn: &UnixStream| {
+                let svc = with_connection_info(svc.clone(), unix::peer_credentials(conn));
+                async move { Ok::<_, Infallible>(svc) }
+            });
+            let server = hyper::Server::builder(incoming)
+                .serve(make_service)
+                .with_graceful_shutdown(shutdown_signal.clone());
+            let path = path.clone();
+            Ok(async move {
+                let res = server.await;
+                let _ = std::fs::remove_file(path);
+                res
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // Requests answered in process are served until the shutdown signal
+    let in_process_server = {
+        let in_process = service_tx.is_some_and(|tx| {
+            tx.send(InProcessService::new(server_router_svc.clone()))
+                .is_ok()
+        });
+        let shutdown_signal = shutdown_signal.clone();
+        async move {
+            if in_process {
+                shutdown_signal.await;
+            }
+            Ok::<_, hyper::Error>(())
+        }
+    };
+
+    let sighup_task = tokio::spawn(reload_on_sighup(reload));
+
+    // And run until the shutdown signal. The servers stop accepting new
+    // connections on the signal and finish once the in-flight requests are
+    // done.
+    let servers = futures::future::try_join3(
+        tcp_server,
+        futures::future::try_join_all(unix_servers),
+        in_process_server,
+    );
+    tokio::pin!(servers);
+    let servers_finished = tokio::select! {
+        res = &mut servers => {
+            if let Err(error) = res {
+                tracing::error!(?error, "Encountered server error. Shutting down.");
+                sighup_task.abort();
+                tamper_task.abort();
+                election_task.abort();
+                rotation_task.abort();
+                ha::step_down(&ctx).await;
+                plugins.stop_all().await;
+                child_processes.kill_all().await;
+                return Err(error.into());
+            }
+            true
+        }
+        () = shutdown_signal.clone() => false,
+    };
+    sighup_task.abort();
+    tamper_task.abort();
+    election_task.abort();
+    rotation_task.abort();
+
+    let deadline = tokio::time::Instant::now() + config.shutdown_timeout;
+    let drained = async {
+        if !servers_finished {
+            if let Err(error) = (&mut servers).await {
+                tracing::error!(?error, "Encountered server error while draining requests.");
+            }
+        }
+        // Let the revocations in flight finish and stop dequeuing new ones,
+        // then hand over to a standby
+        expiration.stop().await;
+        ha::step_down(&ctx).await;
+    };
+    let timed_out = tokio::time::timeout_at(deadline, drained).await.is_err();
+
+    audit.flush().await;
+    plugins.stop_all().await;
+    child_processes.kill_all().await;
 
-    // And run forever...
-    if let Err(error) = covert_server.await {
-        tracing::error!(?error, "Encountered server error. Shutting down.");
-        return Err(error.into());
+    if timed_out {
+        tracing::warn!("Shutdown timed out, in-flight work was abandoned");
+        return Err(ShutdownTimedOut(config.shutdown_timeout).into());
     }
+    info!("Shutdown complete");
     Ok(())
 }
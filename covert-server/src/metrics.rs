@@ -0,0 +1,342 @@
+use std::{
+    fmt::{Display, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use covert_types::request::Operation;
+use dashmap::DashMap;
+
+/// Requests routed to a mount, labeled by `namespace`, `mount` and
+/// `operation`.
+pub const REQUESTS_TOTAL: &str = "covert_requests_total";
+/// Requests routed to a mount that failed, with the labels of
+/// [`REQUESTS_TOTAL`].
+pub const REQUEST_ERRORS_TOTAL: &str = "covert_request_errors_total";
+/// Histogram of the time the mount took to handle the request, with the
+/// labels of [`REQUESTS_TOTAL`].
+pub const REQUEST_DURATION_SECONDS: &str = "covert_request_duration_seconds";
+/// Number of active leases.
+pub const LEASES: &str = "covert_leases";
+/// Number of expired leases waiting to be revoked by the expiration manager.
+pub const EXPIRATION_QUEUE_DEPTH: &str = "covert_expiration_queue_depth";
+/// Number of tokens.
+pub const TOKENS: &str = "covert_tokens";
+/// Leases revoked by the expiration manager.
+pub const LEASE_REVOCATIONS_TOTAL: &str = "covert_lease_revocations_total";
+/// Failed attempts of the expiration manager to revoke a lease.
+pub const LEASE_REVOCATION_FAILURES_TOTAL: &str = "covert_lease_revocation_failures_total";
+/// Times a tamper threshold was reached and the server sealed itself.
+pub const TAMPER_TRIPS_TOTAL: &str = "covert_tamper_trips_total";
+/// 1 for the current storage state, labeled by `state`.
+pub const STORAGE_STATE: &str = "covert_storage_state";
+/// Size of the storage in bytes, labeled by `storage` which is either `seal`
+/// or `encrypted`.
+pub const STORAGE_SIZE_BYTES: &str = "covert_storage_size_bytes";
+
+/// Content type of the Prometheus text format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Path scrapers expect the metrics at, served by the `sys/metrics` route.
+const SCRAPE_PATH: &str = "/metrics";
+
+/// Upper bounds in seconds of the buckets of [`REQUEST_DURATION_SECONDS`].
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The operations requests are recorded for, in the order of
+/// [`operation_index`].
+const OPERATIONS: [Operation; 8] = [
+    Operation::Create,
+    Operation::Read,
+    Operation::Update,
+    Operation::Patch,
+    Operation::Delete,
+    Operation::List,
+    Operation::Revoke,
+    Operation::Renew,
+];
+
+fn operation_index(operation: Operation) -> Option<usize> {
+    OPERATIONS.iter().position(|op| *op == operation)
+}
+
+#[derive(Default)]
+struct OperationMetrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    /// Non-cumulative bucket counts, the last bucket is `+Inf`.
+    buckets: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    duration_micros: AtomicU64,
+}
+
+#[derive(Default)]
+struct MountMetrics {
+    operations: [OperationMetrics; OPERATIONS.len()],
+}
+
+/// Counts and durations of the requests handled by the mounts.
+///
+/// Recording only increments atomic counters. The entry of a mount is
+/// allocated by its first request and looked up by reference afterwards.
+#[derive(Default)]
+pub struct RequestMetrics {
+    // namespace -> mount path -> metrics
+    mounts: DashMap<String, DashMap<String, Arc<MountMetrics>>>,
+}
+
+impl RequestMetrics {
+    pub fn record(
+        &self,
+        namespace: &str,
+        mount: &str,
+        operation: Operation,
+        duration: Duration,
+        success: bool,
+    ) {
+        let Some(index) = operation_index(operation) else {
+            return;
+        };
+        let metrics = self.mount(namespace, mount);
+        let metrics = &metrics.operations[index];
+
+        metrics.requests.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            metrics.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        let secs = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        metrics.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        #[allow(clippy::cast_possible_truncation)]
+        metrics
+            .duration_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn mount(&self, namespace: &str, mount: &str) -> Arc<MountMetrics> {
+        if let Some(metrics) = self
+            .mounts
+            .get(namespace)
+            .and_then(|mounts| mounts.get(mount).map(|metrics| Arc::clone(&metrics)))
+        {
+            return metrics;
+        }
+        let mounts = self.mounts.entry(namespace.to_string()).or_default();
+        let metrics = mounts.entry(mount.to_string()).or_default();
+        Arc::clone(&metrics)
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        let mut series = self
+            .mounts
+            .iter()
+            .flat_map(|mounts| {
+                let namespace = mounts.key().clone();
+                mounts
+                    .iter()
+                    .map(|metrics| {
+                        (
+                            namespace.clone(),
+                            metrics.key().clone(),
+                            Arc::clone(metrics.value()),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        series.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        let series = series
+            .iter()
+            .flat_map(|(namespace, mount, metrics)| {
+                OPERATIONS
+                    .iter()
+                    .zip(&metrics.operations)
+                    .filter(|(_, metrics)| metrics.requests.load(Ordering::Relaxed) > 0)
+                    .map(move |(operation, metrics)| {
+                        let labels = [
+                            ("namespace", namespace.clone()),
+                            ("mount", mount.clone()),
+                            ("operation", format!("{operation:?}").to_lowercase()),
+                        ];
+                        (labels, metrics)
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        encoder.header(REQUESTS_TOTAL, "Requests routed to a mount.", "counter");
+        for (labels, metrics) in &series {
+            encoder.sample(
+                REQUESTS_TOTAL,
+                labels,
+                metrics.requests.load(Ordering::Relaxed),
+            );
+        }
+        encoder.header(
+            REQUEST_ERRORS_TOTAL,
+            "Requests routed to a mount that failed.",
+            "counter",
+        );
+        for (labels, metrics) in &series {
+            encoder.sample(
+                REQUEST_ERRORS_TOTAL,
+                labels,
+                metrics.errors.load(Ordering::Relaxed),
+            );
+        }
+        encoder.header(
+            REQUEST_DURATION_SECONDS,
+            "Time the mount took to handle the request.",
+            "histogram",
+        );
+        for (labels, metrics) in &series {
+            let mut count = 0;
+            for (i, bucket) in metrics.buckets.iter().enumerate() {
+                count += bucket.load(Ordering::Relaxed);
+                let le = DURATION_BUCKETS
+                    .get(i)
+                    .map_or_else(|| "+Inf".to_string(), ToString::to_string);
+                let mut labels = labels.to_vec();
+                labels.push(("le", le));
+                encoder.sample(
+                    &format!("{REQUEST_DURATION_SECONDS}_bucket"),
+                    &labels,
+                    count,
+                );
+            }
+            #[allow(clippy::cast_precision_loss)]
+            let sum = metrics.duration_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+            encoder.sample(&format!("{REQUEST_DURATION_SECONDS}_sum"), labels, sum);
+            encoder.sample(&format!("{REQUEST_DURATION_SECONDS}_count"), labels, count);
+        }
+    }
+}
+
+/// Writes metrics in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Encoder(String);
+
+impl Encoder {
+    pub fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, String)], value: impl Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels = labels
+                .iter()
+                .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = write!(self.0, "{{{labels}}}");
+        }
+        let _ = writeln!(self.0, " {value}");
+    }
+
+    /// Write a metric that has a single sample.
+    pub fn single(&mut self, name: &str, help: &str, kind: &str, value: impl Display) {
+        self.header(name, help, kind);
+        self.sample(name, &[], value);
+    }
+
+    pub fn finish(self) -> String {
+        self.0
+    }
+}
+
+/// Rewrite requests for [`SCRAPE_PATH`] to the system backend route.
+pub fn rewrite_scrape_path<B>(mut req: hyper::Request<B>) -> hyper::Request<B> {
+    if req.uri().path() == SCRAPE_PATH {
+        let uri = match req.uri().query() {
+            Some(query) => format!("/v1/sys/metrics?{query}"),
+            None => "/v1/sys/metrics".to_string(),
+        };
+        if let Ok(uri) = uri.parse() {
+            *req.uri_mut() = uri;
+        }
+    }
+    req
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_request_metrics() {
+        let metrics = RequestMetrics::default();
+        metrics.record(
+            "root",
+            "kv/",
+            Operation::Read,
+            Duration::from_millis(3),
+            true,
+        );
+        metrics.record(
+            "root",
+            "kv/",
+            Operation::Read,
+            Duration::from_millis(30),
+            false,
+        );
+        metrics.record("root", "sys/", Operation::Sudo, Duration::ZERO, true);
+
+        let mut encoder = Encoder::default();
+        metrics.encode(&mut encoder);
+        let text = encoder.finish();
+
+        let labels = r#"namespace="root",mount="kv/",operation="read""#;
+        assert!(text.contains(&format!("covert_requests_total{{{labels}}} 2\n")));
+        assert!(text.contains(&format!("covert_request_errors_total{{{labels}}} 1\n")));
+        assert!(text.contains(&format!(
+            "covert_request_duration_seconds_bucket{{{labels},le=\"0.005\"}} 1\n"
+        )));
+        assert!(text.contains(&format!(
+            "covert_request_duration_seconds_bucket{{{labels},le=\"0.05\"}} 2\n"
+        )));
+        assert!(text.contains(&format!(
+            "covert_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 2\n"
+        )));
+        assert!(text.contains(&format!(
+            "covert_request_duration_seconds_count{{{labels}}} 2\n"
+        )));
+        assert!(text.contains(&format!(
+            "covert_request_duration_seconds_sum{{{labels}}} 0.033\n"
+        )));
+        // Only operations of requests are recorded
+        assert!(!text.contains("sudo"));
+    }
+
+    #[test]
+    fn rewrites_scrape_path() {
+        let rewrite = |uri: &str| {
+            let req = hyper::Request::builder().uri(uri).body(()).unwrap();
+            rewrite_scrape_path(req).uri().to_string()
+        };
+        assert_eq!(rewrite("/metrics"), "/v1/sys/metrics");
+        assert_eq!(rewrite("/metrics?foo=bar"), "/v1/sys/metrics?foo=bar");
+        assert_eq!(rewrite("/v1/metrics"), "/v1/metrics");
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let mut encoder = Encoder::default();
+        encoder.sample("foo", &[("path", "a\"b\\c\nd".to_string())], 1);
+        assert_eq!(encoder.finish(), "foo{path=\"a\\\"b\\\\c\\nd\"} 1\n");
+    }
+}
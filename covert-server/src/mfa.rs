@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use uuid::Uuid;
+
+use crate::layer::lease_registration::Login;
+
+/// Number of digits of a TOTP code.
+const TOTP_DIGITS: u32 = 6;
+
+/// Seconds a TOTP code is valid for.
+const TOTP_PERIOD: i64 = 30;
+
+/// Size of the generated TOTP secrets, the size of a SHA-1 digest as
+/// recommended by RFC 4226.
+const TOTP_SECRET_LEN: usize = 20;
+
+/// Time a login has to be completed with the second factor in.
+pub const MFA_REQUEST_TTL: Duration = Duration::from_mins(2);
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_totp_secret() -> Vec<u8> {
+    let mut secret = vec![0; TOTP_SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// Unpadded base32 as expected by authenticator apps.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    for chunk in bytes.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            #[allow(clippy::cast_possible_truncation)]
+            encoded.push(char::from(BASE32_ALPHABET[index as usize]));
+        }
+    }
+    encoded
+}
+
+/// Key URI of the secret, usually shown as a QR code to add the key to an
+/// authenticator app.
+pub fn totp_url(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD}",
+        base32_encode(secret)
+    )
+}
+
+/// The TOTP code of the time step as defined by RFC 6238.
+fn totp(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC can take a key of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = usize::from(hash[hash.len() - 1] & 0xf);
+    let code = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]) % 10u32.pow(TOTP_DIGITS);
+    format!("{code:0width$}", width = TOTP_DIGITS as usize)
+}
+
+/// Check the code against the secret and return the time step it belongs
+/// to. Codes of the previous and next time step are accepted to allow for
+/// clock skew.
+pub fn verify_totp(secret: &[u8], code: &str, now: DateTime<Utc>) -> Option<i64> {
+    let step = now.timestamp() / TOTP_PERIOD;
+    [step, step - 1, step + 1]
+        .into_iter()
+        .find(|step| totp(secret, *step) == code.trim())
+}
+
+/// A login waiting for the second factor.
+#[derive(Debug, Clone)]
+pub struct PendingLogin {
+    pub login: Login,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Logins waiting for the second factor, keyed by the id of the MFA request
+/// returned to the client.
+#[derive(Default)]
+pub struct PendingLogins(DashMap<Uuid, PendingLogin>);
+
+impl PendingLogins {
+    /// Hold the login until it is completed with [`PendingLogins::take`] or it
+    /// expires after [`MFA_REQUEST_TTL`].
+    pub fn insert(&self, login: Login, now: DateTime<Utc>) -> Uuid {
+        self.0.retain(|_, pending| pending.expires_at > now);
+        let id = Uuid::new_v4();
+        self.0.insert(
+            id,
+            PendingLogin {
+                login,
+                expires_at: now
+                    + chrono::Duration::from_std(MFA_REQUEST_TTL)
+                        .expect("MFA request TTL is in range"),
+            },
+        );
+        id
+    }
+
+    /// Whether a login of the entity is waiting for the second factor.
+    pub fn has_entity(&self, entity_name: &str, namespace_id: &str, now: DateTime<Utc>) -> bool {
+        self.0.iter().any(|pending| {
+            pending.expires_at > now
+                && pending.login.entity_name == entity_name
+                && pending.login.namespace_id == namespace_id
+        })
+    }
+
+    /// Remove the login. A request can only be used once, whether the second
+    /// factor turns out to be valid or not.
+    pub fn take(&self, id: Uuid, now: DateTime<Utc>) -> Option<Login> {
+        self.0
+            .remove(&id)
+            .map(|(_, pending)| pending)
+            .filter(|pending| pending.expires_at > now)
+            .map(|pending| pending.login)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn base32() {
+        // Test vectors of RFC 4648 without padding
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"fo"), "MZXQ");
+        assert_eq!(base32_encode(b"foo"), "MZXW6");
+        assert_eq!(base32_encode(b"foob"), "MZXW6YQ");
+        assert_eq!(base32_encode(b"fooba"), "MZXW6YTB");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    }
+
+    #[test]
+    fn totp_codes() {
+        // Test vectors of RFC 6238 for SHA-1, truncated to six digits
+        let secret = b"12345678901234567890";
+        let at = |secs| Utc.timestamp_opt(secs, 0).unwrap();
+        for (time, code) in [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            assert_eq!(totp(secret, time / TOTP_PERIOD), code);
+            assert_eq!(
+                verify_totp(secret, code, at(time)),
+                Some(time / TOTP_PERIOD)
+            );
+        }
+
+        // One step of clock skew is allowed
+        assert_eq!(verify_totp(secret, "287082", at(89)), Some(1));
+        assert_eq!(verify_totp(secret, "287082", at(90)), None);
+        assert_eq!(verify_totp(secret, "287083", at(59)), None);
+        assert_eq!(verify_totp(b"other", "287082", at(59)), None);
+    }
+
+    #[test]
+    fn pending_logins_are_single_use() {
+        let logins = PendingLogins::default();
+        let login = Login {
+            entity_name: "foo".to_string(),
+            namespace_id: "root".to_string(),
+            mount_path: "auth/userpass/".to_string(),
+            ttl: chrono::Duration::minutes(30),
+            metadata: HashMap::new(),
+            policies: vec![],
+            group_policies: vec![],
+            renewable: true,
+            parent: None,
+        };
+        let now = Utc::now();
+
+        let id = logins.insert(login.clone(), now);
+        assert!(logins.has_entity("foo", "root", now));
+        assert!(!logins.has_entity("bar", "root", now));
+        assert!(logins.take(id, now).is_some());
+        assert!(logins.take(id, now).is_none());
+        assert!(!logins.has_entity("foo", "root", now));
+
+        let id = logins.insert(login, now);
+        assert!(logins
+            .take(id, now + chrono::Duration::minutes(2))
+            .is_none());
+    }
+}
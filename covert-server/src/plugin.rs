@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use covert_framework::{Backend, SyncService};
+use covert_plugin::{PluginClient, RequestContext};
+use covert_types::{
+    backend::{BackendCategory, BackendType},
+    error::ApiError,
+    mount::MountConfig,
+    request::Request,
+    token::Token,
+};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    layer::auth_service::TokenPolicies,
+    repos::token::TokenRepo,
+};
+
+/// Plugin processes serving the mounted `plugin` backends, keyed by mount id.
+#[derive(Default)]
+pub struct Plugins {
+    clients: DashMap<Uuid, Arc<PluginClient>>,
+}
+
+impl Plugins {
+    /// Tear down the mount and stop its plugin process.
+    pub async fn stop(&self, mount_id: Uuid) {
+        if let Some((_, client)) = self.clients.remove(&mount_id) {
+            client.teardown().await;
+        }
+    }
+
+    /// Stop the plugin processes of every mount, e.g. when the server is
+    /// sealed.
+    pub async fn stop_all(&self) {
+        let mount_ids = self
+            .clients
+            .iter()
+            .map(|client| *client.key())
+            .collect::<Vec<_>>();
+        for mount_id in mount_ids {
+            self.stop(mount_id).await;
+        }
+    }
+}
+
+/// Returns an error unless the mount config names a registered plugin.
+pub fn validate_plugin_config(ctx: &Context, config: &MountConfig) -> Result<(), Error> {
+    let name = config
+        .plugin
+        .as_ref()
+        .ok_or(ErrorType::PluginNameRequired)?;
+    if ctx.config.plugins.iter().any(|plugin| &plugin.name == name) {
+        Ok(())
+    } else {
+        Err(ErrorType::PluginNotRegistered { name: name.clone() }.into())
+    }
+}
+
+/// Start the plugin of the mount and return a backend proxying the requests
+/// of the mount to it. A plugin already running for the mount is stopped
+/// first.
+pub async fn new_plugin_backend(
+    ctx: &Context,
+    mount_id: Uuid,
+    namespace_id: &str,
+    config: &MountConfig,
+) -> Result<Backend, Error> {
+    validate_plugin_config(ctx, config)?;
+    let name = config.plugin.as_deref().unwrap_or_default();
+    let command = ctx
+        .config
+        .plugins
+        .iter()
+        .find(|plugin| plugin.name == name)
+        .map(crate::PluginConfig::command)
+        .ok_or_else(|| ErrorType::PluginNotRegistered { name: name.into() })?;
+
+    ctx.plugins.stop(mount_id).await;
+    let client = Arc::new(PluginClient::start(command, mount_id, namespace_id, config).await?);
+    ctx.plugins.clients.insert(mount_id, Arc::clone(&client));
+
+    let paths = client.routes().to_vec();
+    let token_repo = ctx.repos.token.clone();
+    let handler = tower::service_fn(move |req: Request| {
+        let client = Arc::clone(&client);
+        let token_repo = token_repo.clone();
+        async move {
+            let context = request_context(&req, &token_repo).await?;
+            client.handle_request(req, context).await
+        }
+    });
+
+    Ok(Backend {
+        handler: SyncService::new(handler),
+        category: BackendCategory::Logical,
+        variant: BackendType::Plugin,
+        migrations: vec![],
+        paths,
+    })
+}
+
+/// Who made the request, sent to the plugin instead of the token.
+async fn request_context(
+    req: &Request,
+    token_repo: &TokenRepo,
+) -> Result<RequestContext, ApiError> {
+    let entity_name = match req.extensions.get::<Token>() {
+        Some(token) => token_repo
+            .lookup(token)
+            .await?
+            .map(|token_entry| token_entry.entity_name),
+        None => None,
+    };
+    let policies = req
+        .extensions
+        .get::<TokenPolicies>()
+        .map(|TokenPolicies(policies)| policies.iter().map(|policy| policy.name.clone()).collect())
+        .unwrap_or_default();
+
+    Ok(RequestContext {
+        entity_name,
+        policies,
+    })
+}
@@ -0,0 +1,453 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use covert_types::methods::system::{
+    EntityQuotaUsage, QuotaScope, RateLimitQuota, RateLimitQuotaUsage,
+};
+use dashmap::DashMap;
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// A token bucket with the requests it allowed and rejected.
+struct Bucket {
+    state: Mutex<TokenBucket>,
+    allowed: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Bucket {
+    fn new(burst: u32) -> Self {
+        Self {
+            state: Mutex::new(TokenBucket {
+                tokens: f64::from(burst),
+                updated_at: Instant::now(),
+            }),
+            allowed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    fn usage(&self, quota: &RateLimitQuota) -> RateLimitQuotaUsage {
+        RateLimitQuotaUsage {
+            available: self.refill(quota, Instant::now(), |_| ()),
+            allowed_requests: self.allowed.load(Ordering::Relaxed),
+            rejected_requests: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Refill the bucket up to `now` and apply `f` to the tokens. Returns the
+    /// tokens left.
+    fn refill(&self, quota: &RateLimitQuota, now: Instant, f: impl FnOnce(&mut f64)) -> f64 {
+        let mut bucket = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * quota.rate).min(f64::from(quota.burst));
+        bucket.updated_at = bucket.updated_at.max(now);
+        f(&mut bucket.tokens);
+        bucket.tokens
+    }
+
+    /// Take a token from the bucket, or return how long until one is
+    /// available.
+    fn try_acquire(&self, quota: &RateLimitQuota, now: Instant) -> Result<(), Duration> {
+        let mut acquired = false;
+        let tokens = self.refill(quota, now, |tokens| {
+            if *tokens >= 1.0 {
+                *tokens -= 1.0;
+                acquired = true;
+            }
+        });
+        if acquired {
+            Ok(())
+        } else {
+            // Rates close to zero wait longer than a `Duration` can hold
+            Err(Duration::try_from_secs_f64((1.0 - tokens) / quota.rate).unwrap_or(Duration::MAX))
+        }
+    }
+
+    /// Give back a token taken by a request that was rejected by another
+    /// quota.
+    fn release(&self, quota: &RateLimitQuota, now: Instant) {
+        self.refill(quota, now, |tokens| {
+            *tokens = (*tokens + 1.0).min(f64::from(quota.burst));
+        });
+    }
+}
+
+/// A rate limit quota with the token bucket it is enforced with. The bucket
+/// starts full with `burst` tokens and is refilled with `rate` tokens per
+/// second. Per-entity quotas have a bucket for every entity, created on the
+/// first request of the entity.
+pub struct RateLimiter {
+    quota: RateLimitQuota,
+    bucket: Arc<Bucket>,
+    entities: DashMap<String, Arc<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(quota: RateLimitQuota) -> Self {
+        Self {
+            bucket: Arc::new(Bucket::new(quota.burst)),
+            quota,
+            entities: DashMap::new(),
+        }
+    }
+
+    pub fn quota(&self) -> &RateLimitQuota {
+        &self.quota
+    }
+
+    /// Usage of the quota. The requests of per-entity quotas are summed over
+    /// the entities and the available requests are the ones of an entity
+    /// without requests.
+    pub fn usage(&self) -> RateLimitQuotaUsage {
+        if self.quota.scope != QuotaScope::PerEntity {
+            return self.bucket.usage(&self.quota);
+        }
+        let mut usage = RateLimitQuotaUsage {
+            available: f64::from(self.quota.burst),
+            allowed_requests: 0,
+            rejected_requests: 0,
+        };
+        for bucket in &self.entities {
+            usage.allowed_requests += bucket.allowed.load(Ordering::Relaxed);
+            usage.rejected_requests += bucket.rejected.load(Ordering::Relaxed);
+        }
+        usage
+    }
+
+    /// Usage of every entity that made requests, for per-entity quotas.
+    pub fn entity_usage(&self) -> Vec<EntityQuotaUsage> {
+        let mut usage = self
+            .entities
+            .iter()
+            .map(|bucket| EntityQuotaUsage {
+                entity: bucket.key().clone(),
+                usage: bucket.usage(&self.quota),
+            })
+            .collect::<Vec<_>>();
+        usage.sort_by(|a, b| a.entity.cmp(&b.entity));
+        usage
+    }
+
+    /// The bucket the request is limited with, if the quota applies to it.
+    fn bucket_for(&self, req: &QuotaRequest<'_>) -> Option<Arc<Bucket>> {
+        if self.quota.exempt_root && req.root {
+            return None;
+        }
+        let applies = match &self.quota.scope {
+            QuotaScope::Global => true,
+            QuotaScope::Mount { path } => req.path.starts_with(path.as_str()),
+            QuotaScope::Entity { name } => req.entity == Some(name.as_str()),
+            // The buckets are bounded by the entities of the root namespace
+            QuotaScope::PerEntity => {
+                let entity = req.entity?;
+                if let Some(bucket) = self.entities.get(entity) {
+                    return Some(Arc::clone(bucket.value()));
+                }
+                let bucket = self
+                    .entities
+                    .entry(entity.to_string())
+                    .or_insert_with(|| Arc::new(Bucket::new(self.quota.burst)));
+                return Some(Arc::clone(bucket.value()));
+            }
+        };
+        applies.then(|| Arc::clone(&self.bucket))
+    }
+}
+
+/// What quotas are matched against.
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaRequest<'a> {
+    /// Path of the request prefixed with the path of its namespace relative
+    /// to the root namespace.
+    pub path: &'a str,
+    /// Entity of the token, if it belongs to the root namespace.
+    pub entity: Option<&'a str>,
+    /// Whether the request is made with a root token.
+    pub root: bool,
+}
+
+/// A request rejected by the quota `name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub name: String,
+    pub retry_after: Duration,
+}
+
+/// The rate limit quotas, keyed by name. The quotas are loaded from storage on
+/// unseal and their buckets are only kept in memory, so enforcing them never
+/// writes to storage.
+#[derive(Default)]
+pub struct QuotaManager {
+    quotas: DashMap<String, Arc<RateLimiter>>,
+    root_namespace_id: RwLock<Option<String>>,
+}
+
+impl QuotaManager {
+    /// Add the quota, replacing the quota with the same name and its usage.
+    pub fn set(&self, quota: RateLimitQuota) {
+        self.quotas
+            .insert(quota.name.clone(), Arc::new(RateLimiter::new(quota)));
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        self.quotas.remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<RateLimiter>> {
+        self.quotas
+            .get(name)
+            .map(|limiter| Arc::clone(limiter.value()))
+    }
+
+    pub fn clear(&self) {
+        self.quotas.clear();
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.quotas
+            .iter()
+            .map(|limiter| limiter.key().clone())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quotas.is_empty()
+    }
+
+    pub fn set_root_namespace_id(&self, id: String) {
+        *self
+            .root_namespace_id
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(id);
+    }
+
+    pub fn is_root_namespace(&self, id: &str) -> bool {
+        self.root_namespace_id
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_deref()
+            == Some(id)
+    }
+
+    /// Take a token from every quota that applies to the request. Fails
+    /// without using up any of the quotas if one of them is exceeded.
+    pub fn check(&self, req: &QuotaRequest<'_>) -> Result<(), QuotaExceeded> {
+        let limiters = self
+            .quotas
+            .iter()
+            .filter_map(|limiter| {
+                limiter
+                    .bucket_for(req)
+                    .map(|bucket| (Arc::clone(limiter.value()), bucket))
+            })
+            .collect::<Vec<_>>();
+
+        let now = Instant::now();
+        for (i, (limiter, bucket)) in limiters.iter().enumerate() {
+            if let Err(retry_after) = bucket.try_acquire(&limiter.quota, now) {
+                bucket.rejected.fetch_add(1, Ordering::Relaxed);
+                for (acquired, bucket) in &limiters[..i] {
+                    bucket.release(&acquired.quota, now);
+                }
+                return Err(QuotaExceeded {
+                    name: limiter.quota.name.clone(),
+                    retry_after,
+                });
+            }
+        }
+        for (_, bucket) in &limiters {
+            bucket.allowed.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn quota(name: &str, scope: QuotaScope, rate: f64, burst: u32) -> RateLimitQuota {
+        RateLimitQuota {
+            name: name.to_string(),
+            scope,
+            rate,
+            burst,
+            exempt_root: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn request(path: &str) -> QuotaRequest<'_> {
+        QuotaRequest {
+            path,
+            entity: None,
+            root: false,
+        }
+    }
+
+    #[test]
+    fn token_bucket_refills() {
+        let quota = quota("global", QuotaScope::Global, 2.0, 2);
+        let bucket = Bucket::new(quota.burst);
+        let start = bucket.state.lock().unwrap().updated_at;
+
+        assert!(bucket.try_acquire(&quota, start).is_ok());
+        assert!(bucket.try_acquire(&quota, start).is_ok());
+        assert_eq!(
+            bucket.try_acquire(&quota, start),
+            Err(Duration::from_millis(500))
+        );
+
+        // Half a token per 250ms
+        let later = start + Duration::from_millis(250);
+        assert_eq!(
+            bucket.try_acquire(&quota, later),
+            Err(Duration::from_millis(250))
+        );
+        assert!(bucket
+            .try_acquire(&quota, later + Duration::from_millis(250))
+            .is_ok());
+
+        // Never refilled above the burst
+        let much_later = start + Duration::from_mins(1);
+        assert!(bucket.try_acquire(&quota, much_later).is_ok());
+        assert!(bucket.try_acquire(&quota, much_later).is_ok());
+        assert!(bucket.try_acquire(&quota, much_later).is_err());
+    }
+
+    #[test]
+    fn tiny_rates_do_not_overflow() {
+        let quota = quota("global", QuotaScope::Global, 1e-300, 1);
+        let bucket = Bucket::new(quota.burst);
+        let start = bucket.state.lock().unwrap().updated_at;
+
+        assert!(bucket.try_acquire(&quota, start).is_ok());
+        assert_eq!(bucket.try_acquire(&quota, start), Err(Duration::MAX));
+    }
+
+    #[test]
+    fn applies_quotas_by_scope() {
+        let quotas = QuotaManager::default();
+        quotas.set(quota(
+            "kv",
+            QuotaScope::Mount {
+                path: "kv/".to_string(),
+            },
+            0.001,
+            1,
+        ));
+        quotas.set(quota(
+            "alice",
+            QuotaScope::Entity {
+                name: "alice".to_string(),
+            },
+            0.001,
+            1,
+        ));
+
+        assert!(quotas.check(&request("kv/foo")).is_ok());
+        let err = quotas.check(&request("kv/bar")).unwrap_err();
+        assert_eq!(err.name, "kv");
+        assert!(err.retry_after > Duration::from_mins(15));
+
+        // Other mounts are not limited
+        assert!(quotas.check(&request("other/foo")).is_ok());
+        assert!(quotas.check(&request("other/foo")).is_ok());
+
+        // Root tokens are exempt
+        let root = QuotaRequest {
+            root: true,
+            ..request("kv/foo")
+        };
+        assert!(quotas.check(&root).is_ok());
+
+        let alice = QuotaRequest {
+            entity: Some("alice"),
+            ..request("other/foo")
+        };
+        assert!(quotas.check(&alice).is_ok());
+        assert_eq!(quotas.check(&alice).unwrap_err().name, "alice");
+
+        let usage = quotas.get("kv").unwrap().usage();
+        assert_eq!(usage.allowed_requests, 1);
+        assert_eq!(usage.rejected_requests, 1);
+        assert!(usage.available < 1.0);
+    }
+
+    #[test]
+    fn rejected_request_does_not_use_other_quotas() {
+        let quotas = QuotaManager::default();
+        quotas.set(quota("global", QuotaScope::Global, 0.001, 2));
+        quotas.set(quota(
+            "kv",
+            QuotaScope::Mount {
+                path: "kv/".to_string(),
+            },
+            0.001,
+            1,
+        ));
+
+        assert!(quotas.check(&request("kv/foo")).is_ok());
+        assert_eq!(quotas.check(&request("kv/foo")).unwrap_err().name, "kv");
+        // The global quota still has the token the rejected request took
+        assert!(quotas.check(&request("other/foo")).is_ok());
+        assert_eq!(
+            quotas.check(&request("other/foo")).unwrap_err().name,
+            "global"
+        );
+        assert_eq!(quotas.get("global").unwrap().usage().allowed_requests, 2);
+    }
+
+    #[test]
+    fn limits_every_entity_on_its_own() {
+        let quotas = QuotaManager::default();
+        quotas.set(quota("per-entity", QuotaScope::PerEntity, 0.001, 2));
+
+        // Requests without an entity are not limited
+        for _ in 0..3 {
+            assert!(quotas.check(&request("kv/foo")).is_ok());
+        }
+
+        let alice = QuotaRequest {
+            entity: Some("alice"),
+            ..request("kv/foo")
+        };
+        let bob = QuotaRequest {
+            entity: Some("bob"),
+            ..request("other/foo")
+        };
+        assert!(quotas.check(&alice).is_ok());
+        assert!(quotas.check(&alice).is_ok());
+        assert_eq!(quotas.check(&alice).unwrap_err().name, "per-entity");
+        // Other entities are limited with their own bucket
+        assert!(quotas.check(&bob).is_ok());
+
+        let limiter = quotas.get("per-entity").unwrap();
+        let usage = limiter.usage();
+        assert_eq!(usage.allowed_requests, 3);
+        assert_eq!(usage.rejected_requests, 1);
+        let entities = limiter.entity_usage();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0].entity, "alice");
+        assert_eq!(entities[0].usage.allowed_requests, 2);
+        assert_eq!(entities[0].usage.rejected_requests, 1);
+        assert!(entities[0].usage.available < 1.0);
+        assert_eq!(entities[1].entity, "bob");
+        assert_eq!(entities[1].usage.allowed_requests, 1);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fmt::Debug, sync::Arc};
+
+use covert_types::methods::system::ReloadConfigResponse;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::{
+    error::{Error, ErrorType},
+    tls::{self, ReloadableServerConfig},
+    Config, TlsConfig,
+};
+
+/// Hooks of the binary running the server to reload the config.
+pub trait ConfigReloader: Send + Sync {
+    /// Load the config again, e.g. by reading the config file.
+    fn load(&self) -> anyhow::Result<Config>;
+
+    /// Apply the log level and format of the config.
+    fn apply_logging(&self, config: &Config) -> anyhow::Result<()>;
+}
+
+#[derive(Clone)]
+pub struct Reloader(pub Arc<dyn ConfigReloader>);
+
+impl Debug for Reloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Reloader").finish_non_exhaustive()
+    }
+}
+
+/// Reloads the parts of the config that can change without a restart: the
+/// TLS certificates and key and the logging config. Without a [`Reloader`]
+/// only the certificates are read again from the configured files.
+#[derive(Default)]
+pub struct ConfigReload {
+    tls: Option<Arc<ReloadableServerConfig>>,
+    reloader: Option<Reloader>,
+    /// TLS config the current certificates were loaded with.
+    tls_config: Mutex<Option<TlsConfig>>,
+}
+
+impl ConfigReload {
+    pub fn new(
+        tls: Option<Arc<ReloadableServerConfig>>,
+        tls_config: Option<TlsConfig>,
+        reloader: Option<Reloader>,
+    ) -> Self {
+        Self {
+            tls,
+            reloader,
+            tls_config: Mutex::new(tls_config),
+        }
+    }
+
+    /// Reload the config. Nothing is applied if any part of it fails to load,
+    /// the server keeps serving with the current config.
+    pub async fn reload(&self) -> Result<ReloadConfigResponse, Error> {
+        let mut tls_config = self.tls_config.lock().await;
+        log_reload(self.try_reload(&mut tls_config))
+    }
+
+    /// Read the certificate and key again from the files of the current TLS
+    /// config, without loading the rest of the config. The current
+    /// certificate is kept if the new one fails to load.
+    pub async fn reload_certificates(&self) -> Result<ReloadConfigResponse, Error> {
+        let tls_config = self.tls_config.lock().await;
+        let res = match (&self.tls, tls_config.as_ref()) {
+            (Some(tls), Some(tls_config)) => tls::server_config(tls_config).map(|server_config| {
+                tls.replace(server_config);
+                ReloadConfigResponse {
+                    tls: true,
+                    logging: false,
+                }
+            }),
+            _ => Err(anyhow::Error::msg("TLS is not configured")),
+        };
+        log_reload(res)
+    }
+
+    fn try_reload(
+        &self,
+        tls_config: &mut Option<TlsConfig>,
+    ) -> anyhow::Result<ReloadConfigResponse> {
+        let config = self
+            .reloader
+            .as_ref()
+            .map(|reloader| {
+                let config = reloader.0.load()?;
+                config.validate()?;
+                Ok::<_, anyhow::Error>(config)
+            })
+            .transpose()?;
+        let new_tls_config = match &config {
+            Some(config) => config.tls.clone(),
+            None => tls_config.clone(),
+        };
+
+        let server_config = match (&self.tls, &new_tls_config) {
+            (Some(_), Some(new_tls_config)) => Some(tls::server_config(new_tls_config)?),
+            (None, None) => None,
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "TLS cannot be enabled or disabled without a restart",
+                ))
+            }
+        };
+
+        let logging = match (&self.reloader, &config) {
+            (Some(reloader), Some(config)) => {
+                reloader.0.apply_logging(config)?;
+                true
+            }
+            _ => false,
+        };
+
+        let tls = match (&self.tls, server_config) {
+            (Some(tls), Some(server_config)) => {
+                tls.replace(server_config);
+                *tls_config = new_tls_config;
+                true
+            }
+            _ => false,
+        };
+
+        Ok(ReloadConfigResponse { tls, logging })
+    }
+}
+
+fn log_reload(res: anyhow::Result<ReloadConfigResponse>) -> Result<ReloadConfigResponse, Error> {
+    match res {
+        Ok(resp) => {
+            info!(tls = resp.tls, logging = resp.logging, "Reloaded config");
+            Ok(resp)
+        }
+        Err(error) => {
+            error!(
+                error = format!("{error:#}"),
+                "Failed to reload config, keeping the current config"
+            );
+            Err(ErrorType::ConfigReload(error).into())
+        }
+    }
+}
+
+/// Reload the config whenever the process receives SIGHUP.
+pub async fn reload_on_sighup(reload: Arc<ConfigReload>) {
+    let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(error) => {
+            error!(?error, "Failed to install SIGHUP signal handler");
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        info!("SIGHUP received, reloading config");
+        // Failures are logged and the current config is kept
+        let _ = reload.reload().await;
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use covert_storage::EncryptedPool;
+use covert_types::methods::system::AuditDevice;
+
+use crate::error::{Error, ErrorType};
+
+/// An audit device together with the salt of the HMAC used to hash the
+/// sensitive values it writes. The salt is only stored in the encrypted
+/// storage and never returned from the API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditDeviceEntry {
+    pub device: AuditDevice,
+    pub salt: Vec<u8>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct AuditDeviceRaw {
+    path: String,
+    config: String,
+    created_at: DateTime<Utc>,
+    salt: Option<String>,
+    hmac_exempt_response_fields: String,
+    filter: String,
+}
+
+impl TryFrom<AuditDeviceRaw> for AuditDeviceEntry {
+    type Error = Error;
+
+    fn try_from(raw: AuditDeviceRaw) -> Result<Self, Self::Error> {
+        let config = serde_json::from_str(&raw.config).map_err(|_| {
+            ErrorType::BadData(format!(
+                "Unable to parse config of audit device `{}`",
+                raw.path
+            ))
+        })?;
+        let hmac_exempt_response_fields = serde_json::from_str(&raw.hmac_exempt_response_fields)
+            .map_err(|_| {
+                ErrorType::BadData(format!(
+                    "Unable to parse HMAC exempt fields of audit device `{}`",
+                    raw.path
+                ))
+            })?;
+        let filter = serde_json::from_str(&raw.filter).map_err(|_| {
+            ErrorType::BadData(format!(
+                "Unable to parse filter of audit device `{}`",
+                raw.path
+            ))
+        })?;
+        let salt = raw
+            .salt
+            .as_deref()
+            .and_then(|salt| hex::decode(salt).ok())
+            .ok_or_else(|| {
+                ErrorType::BadData(format!("Invalid salt of audit device `{}`", raw.path))
+            })?;
+        Ok(AuditDeviceEntry {
+            device: AuditDevice {
+                path: raw.path,
+                config,
+                hmac_exempt_response_fields,
+                filter,
+                created_at: raw.created_at,
+            },
+            salt,
+        })
+    }
+}
+
+pub struct AuditRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for AuditRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl AuditRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip_all, fields(path = entry.device.path))]
+    pub async fn create(&self, entry: &AuditDeviceEntry) -> Result<(), Error> {
+        let device = &entry.device;
+        let config = serde_json::to_string(&device.config).map_err(ErrorType::BadResponseData)?;
+        let hmac_exempt_response_fields =
+            serde_json::to_string(&device.hmac_exempt_response_fields)
+                .map_err(ErrorType::BadResponseData)?;
+        let filter = serde_json::to_string(&device.filter).map_err(ErrorType::BadResponseData)?;
+        sqlx::query(
+            "INSERT INTO AUDIT_DEVICES
+                (path, config, created_at, salt, hmac_exempt_response_fields, filter)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&device.path)
+        .bind(config)
+        .bind(device.created_at)
+        .bind(hex::encode(&entry.salt))
+        .bind(hmac_exempt_response_fields)
+        .bind(filter)
+        .execute(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .map(|_| ())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self) -> Result<Vec<AuditDeviceEntry>, Error> {
+        sqlx::query_as("SELECT * FROM AUDIT_DEVICES ORDER BY path")
+            .fetch_all(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .and_then(|devices: Vec<AuditDeviceRaw>| {
+                devices.into_iter().map(TryInto::try_into).collect()
+            })
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn lookup(&self, path: &str) -> Result<Option<AuditDeviceEntry>, Error> {
+        sqlx::query_as("SELECT * FROM AUDIT_DEVICES WHERE path = ?")
+            .bind(path)
+            .fetch_optional(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .and_then(|device: Option<AuditDeviceRaw>| device.map(TryInto::try_into).transpose())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn remove(&self, path: &str) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM AUDIT_DEVICES WHERE path = ?")
+            .bind(path)
+            .execute(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .map(|res| res.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use covert_types::{
+        methods::system::{AuditDeviceConfig, AuditFilterRule, AuditFormat, FileAuditConfig},
+        request::Operation,
+    };
+
+    use crate::repos::mount::tests::pool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let pool = Arc::new(pool().await);
+        let repo = AuditRepo::new(Arc::clone(&pool));
+
+        let entry = AuditDeviceEntry {
+            device: AuditDevice {
+                path: "file".to_string(),
+                config: AuditDeviceConfig::File(FileAuditConfig {
+                    file_path: "/var/log/covert/audit.log".to_string(),
+                    format: AuditFormat::Jsonl,
+                    mode: Some("0600".to_string()),
+                }),
+                hmac_exempt_response_fields: vec!["lease_id".to_string()],
+                filter: vec![AuditFilterRule {
+                    path_prefix: "kv/".to_string(),
+                    operations: vec![Operation::Read],
+                    sample_every: 10,
+                }],
+                created_at: Utc::now(),
+            },
+            salt: vec![1, 2, 3],
+        };
+        repo.create(&entry).await.unwrap();
+        assert!(repo.create(&entry).await.is_err());
+        assert_eq!(repo.list().await.unwrap(), vec![entry.clone()]);
+        assert_eq!(
+            repo.lookup(&entry.device.path).await.unwrap(),
+            Some(entry.clone())
+        );
+
+        assert!(repo.remove(&entry.device.path).await.unwrap());
+        assert!(!repo.remove(&entry.device.path).await.unwrap());
+        assert!(repo.list().await.unwrap().is_empty());
+        assert!(repo.lookup(&entry.device.path).await.unwrap().is_none());
+    }
+}
@@ -1,19 +1,32 @@
 use std::{collections::HashMap, sync::Arc};
 
 use covert_storage::EncryptedPool;
-use covert_types::entity::{Entity, EntityAlias};
+use covert_types::{
+    entity::{Entity, EntityAlias},
+    methods::system::ImportEntity,
+};
 use itertools::Itertools;
 
-use crate::error::Error;
+use crate::{
+    error::{Error, ErrorType},
+    PolicyLimitsConfig,
+};
+
+use super::policy::validate_policies;
+
+/// Name of the entity the root token is issued for.
+pub const ROOT_ENTITY: &str = "root";
 
 pub struct EntityRepo {
     pool: Arc<EncryptedPool>,
+    policy_limits: PolicyLimitsConfig,
 }
 
 impl Clone for EntityRepo {
     fn clone(&self) -> Self {
         Self {
             pool: Arc::clone(&self.pool),
+            policy_limits: self.policy_limits,
         }
     }
 }
@@ -21,6 +34,7 @@ impl Clone for EntityRepo {
 #[derive(Debug, sqlx::FromRow)]
 pub struct EntityWithPolicyAndAliasRaw {
     pub name: String,
+    pub metadata: String,
     pub policy_name: String,
     pub alias_name: String,
     pub alias_mount_path: String,
@@ -29,13 +43,88 @@ pub struct EntityWithPolicyAndAliasRaw {
 #[derive(Debug, PartialEq, Eq)]
 pub struct EntityWithPolicyAndAlias {
     pub name: String,
+    pub metadata: HashMap<String, String>,
     pub policies: Vec<String>,
     pub aliases: Vec<EntityAlias>,
 }
 
+fn parse_metadata(metadata: &str) -> HashMap<String, String> {
+    // TODO: metadata that can't be deserialized should be reported
+    serde_json::from_str(metadata).unwrap_or_default()
+}
+
 impl EntityRepo {
     pub fn new(pool: Arc<EncryptedPool>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            policy_limits: PolicyLimitsConfig::default(),
+        }
+    }
+
+    pub fn with_policy_limits(mut self, limits: PolicyLimitsConfig) -> Self {
+        self.policy_limits = limits;
+        self
+    }
+
+    /// Check that the policies can be attached to an entity.
+    pub fn validate_policies(&self, policies: &[String]) -> Result<(), Error> {
+        validate_policies(
+            policies,
+            self.policy_limits.max_policies_per_entity,
+            &self.policy_limits,
+            "Entity",
+        )
+    }
+
+    /// Create the entities together with their metadata, policies and aliases
+    /// in a single transaction. Either all entities are created or none.
+    #[tracing::instrument(skip_all, fields(entities = entities.len()))]
+    pub async fn import(&self, entities: &[ImportEntity], namespace_id: &str) -> Result<(), Error> {
+        for entity in entities {
+            self.validate_policies(&entity.policies)?;
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for entity in entities {
+            let metadata = serde_json::to_string(&entity.metadata)
+                .map_err(|err| ErrorType::InternalError(err.into()))?;
+            sqlx::query(
+                "INSERT INTO ENTITIES (name, namespace_id, metadata)
+                VALUES (?, ?, ?)",
+            )
+            .bind(&entity.name)
+            .bind(namespace_id)
+            .bind(metadata)
+            .execute(&mut tx)
+            .await?;
+
+            for policy in &entity.policies {
+                sqlx::query(
+                    "INSERT INTO ENTITY_POLICIES (entity_name, policy_name, namespace_id)
+                    VALUES (?, ?, ?)",
+                )
+                .bind(&entity.name)
+                .bind(policy)
+                .bind(namespace_id)
+                .execute(&mut tx)
+                .await?;
+            }
+
+            for alias in &entity.aliases {
+                sqlx::query(
+                    "INSERT INTO ENTITY_ALIASES (name, mount_path, entity_name, namespace_id)
+                    VALUES (?, ?, ?, ?)",
+                )
+                .bind(&alias.name)
+                .bind(&alias.mount_path)
+                .bind(&entity.name)
+                .bind(namespace_id)
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
     }
 
     #[tracing::instrument(skip(self))]
@@ -52,6 +141,19 @@ impl EntityRepo {
         .map_err(Into::into)
     }
 
+    /// Remove the entity with its aliases, policies and TOTP key. The tokens
+    /// of the entity are removed too, revoke them first.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete(&self, name: &str, namespace_id: &str) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM ENTITIES WHERE name = ? AND namespace_id = ?")
+            .bind(name)
+            .bind(namespace_id)
+            .execute(self.pool.as_ref())
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn attach_alias(
         &self,
@@ -80,17 +182,31 @@ impl EntityRepo {
         policy: &str,
         namespace_id: &str,
     ) -> Result<(), Error> {
-        sqlx::query(
+        self.validate_policies(&[policy.to_string()])?;
+        let max_policies = self.policy_limits.max_policies_per_entity;
+        // The limit is checked by the insert so concurrent attaches can't
+        // exceed it
+        let res = sqlx::query(
             "INSERT INTO ENTITY_POLICIES (entity_name, policy_name, namespace_id)
-            VALUES (?, ?, ?)",
+            SELECT ?, ?, ? WHERE (
+                SELECT COUNT(*) FROM ENTITY_POLICIES WHERE entity_name = ? AND namespace_id = ?
+            ) < ?",
         )
         .bind(name)
         .bind(policy)
         .bind(namespace_id)
+        .bind(name)
+        .bind(namespace_id)
+        .bind(i64::try_from(max_policies).unwrap_or(i64::MAX))
         .execute(self.pool.as_ref())
-        .await
-        .map(|_| ())
-        .map_err(Into::into)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(ErrorType::BadRequest(format!(
+                "Entity cannot have more than {max_policies} policies"
+            ))
+            .into());
+        }
+        Ok(())
     }
 
     #[tracing::instrument(skip(self))]
@@ -158,6 +274,7 @@ impl EntityRepo {
         sqlx::query_as::<_, EntityWithPolicyAndAliasRaw>(
             r#"SELECT 
                 E.name AS name,
+                E.metadata AS metadata,
                 P.name AS policy_name,
                 EA.name AS alias_name,
                 EA.mount_path AS alias_mount_path
@@ -181,17 +298,20 @@ impl EntityRepo {
                         .entry(e.name.clone())
                         .or_insert_with(|| EntityWithPolicyAndAlias {
                             name: e.name.to_string(),
+                            metadata: parse_metadata(&e.metadata),
                             policies: vec![],
                             aliases: vec![],
                         });
-                if !e.policy_name.is_empty() {
+                // The rows are the product of the policies and aliases
+                if !e.policy_name.is_empty() && !entry.policies.contains(&e.policy_name) {
                     entry.policies.push(e.policy_name.clone());
                 }
-                if !e.alias_name.is_empty() {
-                    entry.aliases.push(EntityAlias {
-                        name: e.alias_name.clone(),
-                        mount_path: e.alias_mount_path.clone(),
-                    });
+                let alias = EntityAlias {
+                    name: e.alias_name.clone(),
+                    mount_path: e.alias_mount_path.clone(),
+                };
+                if !alias.name.is_empty() && !entry.aliases.contains(&alias) {
+                    entry.aliases.push(alias);
                 }
             }
             grouped
@@ -211,6 +331,7 @@ impl EntityRepo {
         sqlx::query_as::<_, EntityWithPolicyAndAliasRaw>(
             r#"SELECT 
                 E.name AS name,
+                E.metadata AS metadata,
                 P.name AS policy_name,
                 EA.name AS alias_name,
                 EA.mount_path AS alias_mount_path
@@ -231,18 +352,21 @@ impl EntityRepo {
             if let Some(entity) = entities.first() {
                 let mut entity = EntityWithPolicyAndAlias {
                     name: entity.name.clone(),
+                    metadata: parse_metadata(&entity.metadata),
                     aliases: vec![],
                     policies: vec![],
                 };
                 for e in entities {
-                    if !e.policy_name.is_empty() {
+                    // The rows are the product of the policies and aliases
+                    if !e.policy_name.is_empty() && !entity.policies.contains(&e.policy_name) {
                         entity.policies.push(e.policy_name.clone());
                     }
-                    if !e.alias_name.is_empty() {
-                        entity.aliases.push(EntityAlias {
-                            name: e.alias_name.clone(),
-                            mount_path: e.alias_mount_path.clone(),
-                        });
+                    let alias = EntityAlias {
+                        name: e.alias_name.clone(),
+                        mount_path: e.alias_mount_path.clone(),
+                    };
+                    if !alias.name.is_empty() && !entity.aliases.contains(&alias) {
+                        entity.aliases.push(alias);
                     }
                 }
                 Some(entity)
@@ -420,6 +544,7 @@ mod tests {
             entities,
             vec![EntityWithPolicyAndAlias {
                 name: entity.name.clone(),
+                metadata: HashMap::new(),
                 policies: vec![bar_policy.name.clone(), foo_policy.name.clone()],
                 aliases: vec![]
             }]
@@ -435,11 +560,13 @@ mod tests {
             vec![
                 EntityWithPolicyAndAlias {
                     name: james.name.clone(),
+                    metadata: HashMap::new(),
                     policies: vec![],
                     aliases: vec![]
                 },
                 EntityWithPolicyAndAlias {
                     name: entity.name.clone(),
+                    metadata: HashMap::new(),
                     policies: vec![bar_policy.name.clone(), foo_policy.name.clone()],
                     aliases: vec![]
                 },
@@ -458,11 +585,13 @@ mod tests {
             vec![
                 EntityWithPolicyAndAlias {
                     name: james.name.clone(),
+                    metadata: HashMap::new(),
                     policies: vec![bar_policy.name.clone()],
                     aliases: vec![]
                 },
                 EntityWithPolicyAndAlias {
                     name: entity.name.clone(),
+                    metadata: HashMap::new(),
                     policies: vec![bar_policy.name.clone(), foo_policy.name.clone()],
                     aliases: vec![]
                 },
@@ -494,11 +623,13 @@ mod tests {
             vec![
                 EntityWithPolicyAndAlias {
                     name: james.name.clone(),
+                    metadata: HashMap::new(),
                     policies: vec![bar_policy.name.clone()],
                     aliases: vec![alias.clone()]
                 },
                 EntityWithPolicyAndAlias {
                     name: entity.name.clone(),
+                    metadata: HashMap::new(),
                     policies: vec![bar_policy.name.clone(), foo_policy.name.clone()],
                     aliases: vec![]
                 },
@@ -536,6 +667,7 @@ mod tests {
             entities,
             Some(EntityWithPolicyAndAlias {
                 name: entity.name.clone(),
+                metadata: HashMap::new(),
                 policies: vec![],
                 aliases: vec![]
             })
@@ -569,6 +701,7 @@ mod tests {
             resp,
             Some(EntityWithPolicyAndAlias {
                 name: entity.name.clone(),
+                metadata: HashMap::new(),
                 policies: vec![bar_policy.name.clone(), foo_policy.name.clone()],
                 aliases: vec![]
             })
@@ -583,6 +716,7 @@ mod tests {
             resp,
             Some(EntityWithPolicyAndAlias {
                 name: james.name.clone(),
+                metadata: HashMap::new(),
                 policies: vec![],
                 aliases: vec![]
             })
@@ -618,6 +752,7 @@ mod tests {
             resp,
             Some(EntityWithPolicyAndAlias {
                 name: james.name.clone(),
+                metadata: HashMap::new(),
                 policies: vec![bar_policy.name.clone()],
                 aliases: vec![alias.clone()]
             })
@@ -629,9 +764,127 @@ mod tests {
             resp,
             Some(EntityWithPolicyAndAlias {
                 name: entity.name.clone(),
+                metadata: HashMap::new(),
                 policies: vec![bar_policy.name.clone(), foo_policy.name.clone()],
                 aliases: vec![]
             })
         );
     }
+
+    #[tokio::test]
+    async fn import_is_atomic() {
+        let pool = Arc::new(pool().await);
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        let john = ImportEntity {
+            name: "John".into(),
+            metadata: HashMap::from([("team".to_string(), "ops".to_string())]),
+            policies: vec![],
+            aliases: vec![],
+        };
+        let james = ImportEntity {
+            name: "James".into(),
+            metadata: HashMap::new(),
+            policies: vec!["not-existing".into()],
+            aliases: vec![],
+        };
+
+        // The policy of the second entity does not exist
+        assert!(entity_repo
+            .import(&[john.clone(), james], &ns.id)
+            .await
+            .is_err());
+        assert!(entity_repo.list(&ns.id).await.unwrap().is_empty());
+
+        entity_repo.import(&[john], &ns.id).await.unwrap();
+        assert_eq!(
+            entity_repo.lookup("John", &ns.id).await.unwrap(),
+            Some(EntityWithPolicyAndAlias {
+                name: "John".into(),
+                metadata: HashMap::from([("team".to_string(), "ops".to_string())]),
+                policies: vec![],
+                aliases: vec![]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn policy_limits() {
+        let pool = Arc::new(pool().await);
+        let limits = PolicyLimitsConfig {
+            max_policies_per_token: 64,
+            max_policies_per_entity: 2,
+            max_policy_name_length: 8,
+        };
+        let entity_repo = EntityRepo::new(Arc::clone(&pool)).with_policy_limits(limits);
+        let policy_repo = PolicyRepo::new(Arc::clone(&pool)).with_limits(limits);
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        for name in ["foo", "bar", "baz"] {
+            policy_repo
+                .create(&Policy::new(name.into(), vec![], ns.id.clone()))
+                .await
+                .unwrap();
+        }
+        let err = policy_repo
+            .create(&Policy::new("too-long-name".into(), vec![], ns.id.clone()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.variant, ErrorType::BadRequest(_)));
+
+        let entity = Entity::new("John".into(), ns.id.clone());
+        entity_repo.create(&entity).await.unwrap();
+        entity_repo
+            .attach_policy(&entity.name, "foo", &ns.id)
+            .await
+            .unwrap();
+        entity_repo
+            .attach_policy(&entity.name, "bar", &ns.id)
+            .await
+            .unwrap();
+        let err = entity_repo
+            .attach_policy(&entity.name, "baz", &ns.id)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.variant, ErrorType::BadRequest(_)));
+        assert_eq!(
+            entity_repo
+                .lookup(&entity.name, &ns.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .policies,
+            vec!["bar".to_string(), "foo".to_string()]
+        );
+
+        // Imports are checked as well
+        let err = entity_repo
+            .import(
+                &[ImportEntity {
+                    name: "James".into(),
+                    metadata: HashMap::new(),
+                    policies: vec!["foo".into(), "bar".into(), "baz".into()],
+                    aliases: vec![],
+                }],
+                &ns.id,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err.variant, ErrorType::BadRequest(_)));
+    }
 }
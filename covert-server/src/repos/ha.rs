@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::error::Error;
+
+const HA_LOCK_TABLE: &str = "HA_LOCK";
+
+/// Node holding the HA lock.
+#[derive(Debug, sqlx::FromRow, PartialEq, Eq)]
+pub struct HaLock {
+    pub node_id: String,
+    pub api_address: String,
+}
+
+#[derive(Clone)]
+pub struct HaLockRepo {
+    pool: Pool<Sqlite>,
+}
+
+impl HaLockRepo {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    /// Acquire the lock, or renew it if the node already holds it. Returns
+    /// false if another node holds a lock that has not expired.
+    pub async fn try_acquire(
+        &self,
+        node_id: Uuid,
+        api_address: &str,
+        ttl: Duration,
+    ) -> Result<bool, Error> {
+        let now = Utc::now().timestamp_millis();
+        let expires_at = now.saturating_add(i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX));
+        sqlx::query(&format!(
+            "INSERT INTO {HA_LOCK_TABLE} (node_id, api_address, expires_at, lock)
+                VALUES ($1, $2, $3, 1)
+                ON CONFLICT (lock) DO UPDATE SET
+                    node_id = excluded.node_id,
+                    api_address = excluded.api_address,
+                    expires_at = excluded.expires_at
+                WHERE {HA_LOCK_TABLE}.node_id = excluded.node_id
+                    OR {HA_LOCK_TABLE}.expires_at <= $4"
+        ))
+        .bind(node_id.to_string())
+        .bind(api_address)
+        .bind(expires_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|res| res.rows_affected() == 1)
+        .map_err(Into::into)
+    }
+
+    /// Release the lock if the node holds it.
+    pub async fn release(&self, node_id: Uuid) -> Result<(), Error> {
+        sqlx::query(&format!("DELETE FROM {HA_LOCK_TABLE} WHERE node_id = $1"))
+            .bind(node_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    /// The node holding the lock, unless the lock has expired.
+    pub async fn holder(&self) -> Result<Option<HaLock>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT node_id, api_address FROM {HA_LOCK_TABLE} WHERE expires_at > $1"
+        ))
+        .bind(Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn acquire_renew_and_take_over() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::migrations::migrate_unecrypted_db(&pool)
+            .await
+            .unwrap();
+        let repo = HaLockRepo::new(pool);
+        let (node_a, node_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let ttl = Duration::from_millis(200);
+        let acquire = |node_id| repo.try_acquire(node_id, "https://covert:8080", ttl);
+
+        assert_eq!(repo.holder().await.unwrap(), None);
+        assert!(acquire(node_a).await.unwrap());
+        assert!(!acquire(node_b).await.unwrap());
+        // Renewing keeps the lock
+        assert!(acquire(node_a).await.unwrap());
+        assert_eq!(
+            repo.holder().await.unwrap(),
+            Some(HaLock {
+                node_id: node_a.to_string(),
+                api_address: "https://covert:8080".into(),
+            })
+        );
+
+        // The lock can be taken over once it expires
+        tokio::time::sleep(ttl).await;
+        assert_eq!(repo.holder().await.unwrap(), None);
+        assert!(acquire(node_b).await.unwrap());
+        assert!(!acquire(node_a).await.unwrap());
+
+        // Only the holder releases the lock
+        repo.release(node_a).await.unwrap();
+        assert!(repo.holder().await.unwrap().is_some());
+        repo.release(node_b).await.unwrap();
+        assert!(acquire(node_a).await.unwrap());
+    }
+}
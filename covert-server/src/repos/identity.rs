@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use covert_storage::EncryptedPool;
+use covert_types::{methods::system::ImportEntity, policy::Policy};
+
+use crate::error::{Error, ErrorType};
+
+/// Changes to the identity store of a namespace made by importing a snapshot.
+#[derive(Debug, Default)]
+pub struct IdentityImport {
+    /// Policies to create or overwrite.
+    pub policies: Vec<Policy>,
+    /// Entities to create or update. The metadata of existing entities is
+    /// overwritten.
+    pub entities: Vec<ImportEntity>,
+    /// Detach the policies and aliases of the existing entities before the
+    /// ones of the snapshot are attached.
+    pub replace_attachments: bool,
+    pub removed_policies: Vec<String>,
+    /// Entities to remove, their tokens and TOTP keys are removed with them.
+    pub removed_entities: Vec<String>,
+}
+
+pub struct IdentityRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for IdentityRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl IdentityRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Apply the changes in a single transaction. Either the whole snapshot is
+    /// imported or nothing changes.
+    #[tracing::instrument(skip_all, fields(
+        policies = import.policies.len(),
+        entities = import.entities.len(),
+    ))]
+    pub async fn import(&self, import: &IdentityImport, namespace_id: &str) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for name in &import.removed_entities {
+            sqlx::query("DELETE FROM ENTITIES WHERE name = ? AND namespace_id = ?")
+                .bind(name)
+                .bind(namespace_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        for name in &import.removed_policies {
+            sqlx::query("DELETE FROM POLICIES WHERE name = ? AND namespace_id = ?")
+                .bind(name)
+                .bind(namespace_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        for policy in &import.policies {
+            let paths = serde_json::to_string(&policy.paths)
+                .map_err(|err| ErrorType::InternalError(err.into()))?;
+            sqlx::query(
+                "INSERT INTO POLICIES (name, policy, namespace_id) VALUES (?, ?, ?)
+                ON CONFLICT (namespace_id, name) DO UPDATE SET policy = excluded.policy",
+            )
+            .bind(&policy.name)
+            .bind(paths)
+            .bind(namespace_id)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for entity in &import.entities {
+            let metadata = serde_json::to_string(&entity.metadata)
+                .map_err(|err| ErrorType::InternalError(err.into()))?;
+            sqlx::query(
+                "INSERT INTO ENTITIES (name, namespace_id, metadata) VALUES (?, ?, ?)
+                ON CONFLICT (namespace_id, name) DO UPDATE SET metadata = excluded.metadata",
+            )
+            .bind(&entity.name)
+            .bind(namespace_id)
+            .bind(metadata)
+            .execute(&mut tx)
+            .await?;
+
+            if import.replace_attachments {
+                for table in ["ENTITY_POLICIES", "ENTITY_ALIASES"] {
+                    sqlx::query(&format!(
+                        "DELETE FROM {table} WHERE entity_name = ? AND namespace_id = ?"
+                    ))
+                    .bind(&entity.name)
+                    .bind(namespace_id)
+                    .execute(&mut tx)
+                    .await?;
+                }
+            }
+
+            for policy in &entity.policies {
+                sqlx::query(
+                    "INSERT INTO ENTITY_POLICIES (entity_name, policy_name, namespace_id)
+                    VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+                )
+                .bind(&entity.name)
+                .bind(policy)
+                .bind(namespace_id)
+                .execute(&mut tx)
+                .await?;
+            }
+
+            // An entity has one alias per mount, the alias of the snapshot wins
+            for alias in &entity.aliases {
+                sqlx::query(
+                    "INSERT INTO ENTITY_ALIASES (name, mount_path, entity_name, namespace_id)
+                    VALUES (?, ?, ?, ?)
+                    ON CONFLICT (namespace_id, entity_name, mount_path)
+                        DO UPDATE SET name = excluded.name",
+                )
+                .bind(&alias.name)
+                .bind(&alias.mount_path)
+                .bind(&entity.name)
+                .bind(namespace_id)
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use uuid::Uuid;
+
+    use crate::repos::{
+        entity::EntityRepo,
+        mount::tests::pool,
+        namespace::{Namespace, NamespaceRepo},
+        policy::PolicyRepo,
+    };
+
+    use super::*;
+
+    fn entity(name: &str, policies: &[&str]) -> ImportEntity {
+        ImportEntity {
+            name: name.into(),
+            metadata: HashMap::from([("team".into(), "web".into())]),
+            policies: policies.iter().map(ToString::to_string).collect(),
+            aliases: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn import() {
+        let pool = Arc::new(pool().await);
+        let store = IdentityRepo::new(Arc::clone(&pool));
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let policy_repo = PolicyRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        let policy = |name: &str| Policy::new(name.into(), vec![], ns.id.clone());
+        let import = IdentityImport {
+            policies: vec![policy("foo"), policy("bar")],
+            entities: vec![entity("alice", &["foo"]), entity("bob", &["foo", "bar"])],
+            ..Default::default()
+        };
+        store.import(&import, &ns.id).await.unwrap();
+        assert_eq!(policy_repo.list(&ns.id).await.unwrap().len(), 2);
+        let alice = entity_repo.lookup("alice", &ns.id).await.unwrap().unwrap();
+        assert_eq!(alice.policies, vec!["foo".to_string()]);
+        assert_eq!(alice.metadata["team"], "web");
+
+        // Nothing is imported if any part of the import fails
+        let import = IdentityImport {
+            policies: vec![policy("baz")],
+            entities: vec![entity("carol", &[]), entity("dave", &["missing"])],
+            removed_entities: vec!["alice".into()],
+            ..Default::default()
+        };
+        assert!(store.import(&import, &ns.id).await.is_err());
+        assert!(policy_repo.lookup("baz", &ns.id).await.unwrap().is_none());
+        assert!(entity_repo.lookup("carol", &ns.id).await.unwrap().is_none());
+        assert!(entity_repo.lookup("alice", &ns.id).await.unwrap().is_some());
+
+        // Replace the attachments and remove what is not imported
+        let import = IdentityImport {
+            policies: vec![policy("bar")],
+            entities: vec![entity("bob", &["bar"])],
+            replace_attachments: true,
+            removed_policies: vec!["foo".into()],
+            removed_entities: vec!["alice".into()],
+        };
+        store.import(&import, &ns.id).await.unwrap();
+        assert!(policy_repo.lookup("foo", &ns.id).await.unwrap().is_none());
+        assert!(entity_repo.lookup("alice", &ns.id).await.unwrap().is_none());
+        let bob = entity_repo.lookup("bob", &ns.id).await.unwrap().unwrap();
+        assert_eq!(bob.policies, vec!["bar".to_string()]);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cmp::min, sync::Arc};
+use std::{cmp::min, collections::BTreeMap, sync::Arc};
 
 use chrono::{DateTime, Utc};
 use covert_storage::EncryptedPool;
@@ -8,6 +8,47 @@ use crate::{
     LeaseEntry,
 };
 
+/// Max number of tags that can be attached to a lease.
+const MAX_LEASE_TAGS: usize = 64;
+
+/// Max length in bytes of a lease tag key.
+const MAX_LEASE_TAG_KEY_LEN: usize = 128;
+
+/// Max length in bytes of a lease tag value.
+const MAX_LEASE_TAG_VALUE_LEN: usize = 512;
+
+fn validate_tags(tags: &BTreeMap<String, String>) -> Result<(), Error> {
+    if tags.len() > MAX_LEASE_TAGS {
+        return Err(ErrorType::BadRequest(format!(
+            "Lease cannot have more than {MAX_LEASE_TAGS} tags"
+        ))
+        .into());
+    }
+    for (key, value) in tags {
+        if key.is_empty() || key.len() > MAX_LEASE_TAG_KEY_LEN {
+            return Err(ErrorType::BadRequest(format!(
+                "Lease tag key `{key}` must be between 1 and {MAX_LEASE_TAG_KEY_LEN} bytes"
+            ))
+            .into());
+        }
+        if value.len() > MAX_LEASE_TAG_VALUE_LEN {
+            return Err(ErrorType::BadRequest(format!(
+                "Lease tag value for `{key}` is longer than {MAX_LEASE_TAG_VALUE_LEN} bytes"
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Condition matching the leases that have all the given tags. Every tag is
+/// looked up in the tag index, bind the namespace, key and value of each tag
+/// in order.
+fn tags_condition(tags: &BTreeMap<String, String>) -> String {
+    r#" AND id IN (SELECT lease_id FROM LEASE_TAGS WHERE namespace_id = ? AND "key" = ? AND "value" = ?)"#
+        .repeat(tags.len())
+}
+
 pub struct LeaseRepo {
     pool: Arc<EncryptedPool>,
 }
@@ -27,9 +68,13 @@ impl LeaseRepo {
 
     #[tracing::instrument(skip_all, fields(lease_id = le.id))]
     pub async fn create(&self, le: &LeaseEntry) -> Result<(), Error> {
-        sqlx::query(
-            "INSERT INTO LEASES (id, issued_mount_path, revoke_path, revoke_data, renew_path, renew_data, issued_at, expires_at, last_renewal_time, failed_revocation_attempts, namespace_id)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        let tags = le.tags();
+        validate_tags(&tags)?;
+
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query(
+            "INSERT INTO LEASES (id, issued_mount_path, revoke_path, revoke_data, renew_path, renew_data, issued_at, expires_at, last_renewal_time, failed_revocation_attempts, namespace_id, role_max_ttl, tags)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&le.id)
         .bind(&le.issued_mount_path)
@@ -42,14 +87,29 @@ impl LeaseRepo {
         .bind(le.last_renewal_time)
         .bind(le.failed_revocation_attempts)
         .bind(&le.namespace_id)
-        .execute(self.pool.as_ref())
-        .await
-        .map_err(Into::into)
-        .and_then(|res| if res.rows_affected() == 1 {
-            Ok(())
-        } else {
-            Err(ErrorType::InternalError(anyhow::Error::msg("failed to insert lease")).into())
-        })
+        .bind(le.role_max_ttl)
+        .bind(&le.tags)
+        .execute(&mut tx)
+        .await?;
+        if res.rows_affected() != 1 {
+            return Err(
+                ErrorType::InternalError(anyhow::Error::msg("failed to insert lease")).into(),
+            );
+        }
+
+        for (key, value) in &tags {
+            sqlx::query(
+                r#"INSERT INTO LEASE_TAGS (lease_id, namespace_id, "key", "value")
+                VALUES (?, ?, ?, ?)"#,
+            )
+            .bind(&le.id)
+            .bind(&le.namespace_id)
+            .bind(key)
+            .bind(value)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await.map_err(Into::into)
     }
 
     #[tracing::instrument(skip(self))]
@@ -162,21 +222,118 @@ impl LeaseRepo {
             .map_err(Into::into)
     }
 
+    /// Number of leases, and the number of them that expired before
+    /// `before`.
+    #[tracing::instrument(skip(self))]
+    pub async fn count(&self, before: DateTime<Utc>) -> Result<(u64, u64), Error> {
+        sqlx::query_as("SELECT COUNT(*), COUNT(CASE WHEN expires_at <= $1 THEN 1 END) FROM LEASES")
+            .bind(before)
+            .fetch_one(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .map(|(total, expired): (i64, i64)| (total.unsigned_abs(), expired.unsigned_abs()))
+    }
+
+    /// List the leases issued by mounts under the path prefix that have all
+    /// the given tags.
     #[tracing::instrument(skip(self))]
     pub async fn list_by_mount_prefix(
         &self,
         path_prefix: &str,
         namespace_id: &str,
+        tags: &BTreeMap<String, String>,
     ) -> Result<Vec<LeaseEntry>, Error> {
         let prefix_pattern = format!("{path_prefix}%");
-        sqlx::query_as("SELECT * FROM LEASES WHERE issued_mount_path LIKE ? AND namespace_id = ?")
+        let sql = format!(
+            "SELECT * FROM LEASES WHERE issued_mount_path LIKE ? AND namespace_id = ?{} ORDER BY issued_at",
+            tags_condition(tags)
+        );
+        let mut query = sqlx::query_as(&sql).bind(prefix_pattern).bind(namespace_id);
+        for (key, value) in tags {
+            query = query.bind(namespace_id).bind(key).bind(value);
+        }
+        query
+            .fetch_all(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// List at most `limit` leases issued by mounts under the path prefix
+    /// that have all the given tags, ordered by id and starting after the
+    /// lease `after`. Leases removed in the meantime don't shift the pages.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_page_by_mount_prefix(
+        &self,
+        path_prefix: &str,
+        namespace_id: &str,
+        tags: &BTreeMap<String, String>,
+        after: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<LeaseEntry>, Error> {
+        let prefix_pattern = format!("{path_prefix}%");
+        let sql = format!(
+            "SELECT * FROM LEASES WHERE issued_mount_path LIKE ? AND namespace_id = ? AND id > ?{} ORDER BY id LIMIT ?",
+            tags_condition(tags)
+        );
+        let mut query = sqlx::query_as(&sql)
             .bind(prefix_pattern)
             .bind(namespace_id)
+            .bind(after.unwrap_or_default());
+        for (key, value) in tags {
+            query = query.bind(namespace_id).bind(key).bind(value);
+        }
+        query
+            .bind(limit)
             .fetch_all(self.pool.as_ref())
             .await
             .map_err(Into::into)
     }
 
+    /// Number of leases issued by mounts under the path prefix that have all
+    /// the given tags.
+    #[tracing::instrument(skip(self))]
+    pub async fn count_by_mount_prefix(
+        &self,
+        path_prefix: &str,
+        namespace_id: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<u64, Error> {
+        let prefix_pattern = format!("{path_prefix}%");
+        let sql = format!(
+            "SELECT COUNT(*) FROM LEASES WHERE issued_mount_path LIKE ? AND namespace_id = ?{}",
+            tags_condition(tags)
+        );
+        let mut query = sqlx::query_as(&sql).bind(prefix_pattern).bind(namespace_id);
+        for (key, value) in tags {
+            query = query.bind(namespace_id).bind(key).bind(value);
+        }
+        query
+            .fetch_one(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .map(|(count,): (i64,)| count.unsigned_abs())
+    }
+
+    /// List the leases that revoke the given token when they expire.
+    #[tracing::instrument(skip_all)]
+    pub async fn list_by_token(
+        &self,
+        token: &str,
+        namespace_id: &str,
+    ) -> Result<Vec<LeaseEntry>, Error> {
+        sqlx::query_as(
+            "SELECT * FROM LEASES
+                WHERE revoke_path IS NULL
+                AND json_extract(revoke_data, '$.token') = ?
+                AND namespace_id = ?",
+        )
+        .bind(token)
+        .bind(namespace_id)
+        .fetch_all(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn lookup(
         &self,
@@ -251,6 +408,8 @@ mod tests {
             last_renewal_time: Utc::now(),
             failed_revocation_attempts: 0,
             namespace_id: ns.id.clone(),
+            role_max_ttl: None,
+            tags: "{}".into(),
         };
         assert!(lease_repo.create(&lease_foo_bar).await.is_ok());
         assert_eq!(
@@ -270,6 +429,8 @@ mod tests {
             last_renewal_time: Utc::now(),
             failed_revocation_attempts: 0,
             namespace_id: ns.id.clone(),
+            role_max_ttl: None,
+            tags: "{}".into(),
         };
         assert!(lease_repo.create(&lease_bar_foo).await.is_ok());
         assert_eq!(
@@ -298,14 +459,30 @@ mod tests {
         // List by mount path prefix
         assert_eq!(
             lease_repo
-                .list_by_mount_prefix(&userpass_mount.path, &ns.id)
+                .list_by_mount_prefix(&userpass_mount.path, &ns.id, &BTreeMap::new())
                 .await
                 .unwrap(),
             vec![lease_foo_bar.clone(), lease_bar_foo.clone()]
         );
+        // Pages are ordered by id
+        let page = |after| {
+            lease_repo.list_page_by_mount_prefix(
+                &userpass_mount.path,
+                &ns.id,
+                &BTreeMap::new(),
+                after,
+                1,
+            )
+        };
+        assert_eq!(page(None).await.unwrap(), vec![lease_bar_foo.clone()]);
+        assert_eq!(
+            page(Some("psql/bar/foo")).await.unwrap(),
+            vec![lease_foo_bar.clone()]
+        );
+        assert!(page(Some("psql/foo/bar")).await.unwrap().is_empty());
         assert_eq!(
             lease_repo
-                .list_by_mount_prefix("random_foo_bar/", &ns.id)
+                .list_by_mount_prefix("random_foo_bar/", &ns.id, &BTreeMap::new())
                 .await
                 .unwrap(),
             vec![]
@@ -371,4 +548,130 @@ mod tests {
             lease_bar_foo_from_store.failed_revocation_attempts
         );
     }
+
+    #[tokio::test]
+    #[allow(clippy::too_many_lines)]
+    async fn tags() {
+        let pool = Arc::new(pool().await);
+        let mount_repo = MountRepo::new(Arc::clone(&pool));
+        let lease_repo = LeaseRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        let mount = MountEntry {
+            id: Uuid::new_v4(),
+            backend_type: BackendType::Postgres,
+            config: MountConfig::default(),
+            path: "psql/".into(),
+            namespace_id: ns.id.clone(),
+        };
+        mount_repo.create(&mount).await.unwrap();
+
+        let tagged = |team: &str, app: &str| {
+            LeaseEntry::new(
+                mount.path.clone(),
+                Some("psql/creds".into()),
+                &(),
+                Some("psql/creds".into()),
+                &(),
+                Utc::now(),
+                Duration::hours(1),
+                ns.id.clone(),
+            )
+            .unwrap()
+            .with_tags(&BTreeMap::from([
+                ("team".to_string(), team.to_string()),
+                ("app".to_string(), app.to_string()),
+            ]))
+            .unwrap()
+        };
+        let payments_web = tagged("payments", "web");
+        let payments_batch = tagged("payments", "batch");
+        let search_web = tagged("search", "web");
+        for le in [&payments_web, &payments_batch, &search_web] {
+            lease_repo.create(le).await.unwrap();
+        }
+        assert_eq!(
+            lease_repo
+                .lookup(payments_web.id(), &ns.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .tags(),
+            payments_web.tags()
+        );
+
+        let filter = |tags: &[(&str, &str)]| {
+            tags.iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let team_payments = filter(&[("team", "payments")]);
+        assert_eq!(
+            lease_repo
+                .list_by_mount_prefix("psql/", &ns.id, &team_payments)
+                .await
+                .unwrap(),
+            vec![payments_web.clone(), payments_batch.clone()]
+        );
+        assert_eq!(
+            lease_repo
+                .list_by_mount_prefix("", &ns.id, &filter(&[("team", "payments"), ("app", "web")]))
+                .await
+                .unwrap(),
+            vec![payments_web.clone()]
+        );
+        assert_eq!(
+            lease_repo
+                .count_by_mount_prefix("psql/", &ns.id, &filter(&[("app", "web")]))
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            lease_repo
+                .count_by_mount_prefix("psql/", &ns.id, &BTreeMap::new())
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            lease_repo
+                .count_by_mount_prefix("psql/", &ns.id, &filter(&[("team", "unknown")]))
+                .await
+                .unwrap(),
+            0
+        );
+
+        // The tag index is cleaned up with the lease
+        assert!(lease_repo.delete(payments_web.id(), &ns.id).await.unwrap());
+        assert_eq!(
+            lease_repo
+                .count_by_mount_prefix("psql/", &ns.id, &team_payments)
+                .await
+                .unwrap(),
+            1
+        );
+        let (indexed,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM LEASE_TAGS WHERE lease_id = ?")
+                .bind(payments_web.id())
+                .fetch_one(pool.as_ref())
+                .await
+                .unwrap();
+        assert_eq!(indexed, 0);
+
+        // Tags are bounded
+        let too_many = (0..=MAX_LEASE_TAGS)
+            .map(|i| (i.to_string(), String::new()))
+            .collect();
+        let le = tagged("payments", "web").with_tags(&too_many).unwrap();
+        assert!(lease_repo.create(&le).await.is_err());
+        assert!(lease_repo.lookup(le.id(), &ns.id).await.unwrap().is_none());
+    }
 }
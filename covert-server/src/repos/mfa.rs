@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use covert_storage::EncryptedPool;
+
+use crate::error::Error;
+
+/// TOTP key of an entity.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct TotpKey {
+    pub entity_name: String,
+    pub namespace_id: String,
+    pub secret: Vec<u8>,
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct MfaRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for MfaRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl MfaRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Set the TOTP key of the entity, replacing any existing key.
+    #[tracing::instrument(skip_all, fields(entity = key.entity_name))]
+    pub async fn set_totp_key(&self, key: &TotpKey) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO ENTITY_TOTP_KEYS (namespace_id, entity_name, secret, last_used_step, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (namespace_id, entity_name) DO UPDATE SET
+                secret = excluded.secret,
+                last_used_step = excluded.last_used_step,
+                created_at = excluded.created_at",
+        )
+        .bind(&key.namespace_id)
+        .bind(&key.entity_name)
+        .bind(&key.secret)
+        .bind(key.last_used_step)
+        .bind(key.created_at)
+        .execute(self.pool.as_ref())
+        .await
+        .map(|_| ())
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn totp_key(
+        &self,
+        entity_name: &str,
+        namespace_id: &str,
+    ) -> Result<Option<TotpKey>, Error> {
+        sqlx::query_as("SELECT * FROM ENTITY_TOTP_KEYS WHERE entity_name = ? AND namespace_id = ?")
+            .bind(entity_name)
+            .bind(namespace_id)
+            .fetch_optional(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Returns whether the entity had a TOTP key.
+    #[tracing::instrument(skip(self))]
+    pub async fn remove_totp_key(
+        &self,
+        entity_name: &str,
+        namespace_id: &str,
+    ) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM ENTITY_TOTP_KEYS WHERE entity_name = ? AND namespace_id = ?")
+            .bind(entity_name)
+            .bind(namespace_id)
+            .execute(self.pool.as_ref())
+            .await
+            .map(|res| res.rows_affected() == 1)
+            .map_err(Into::into)
+    }
+
+    /// Mark the time step of a code as used. Returns false if a code of the
+    /// same or a later time step was already used.
+    #[tracing::instrument(skip(self))]
+    pub async fn use_totp_step(
+        &self,
+        entity_name: &str,
+        namespace_id: &str,
+        step: i64,
+    ) -> Result<bool, Error> {
+        sqlx::query(
+            "UPDATE ENTITY_TOTP_KEYS SET last_used_step = ?
+            WHERE entity_name = ? AND namespace_id = ?
+                AND (last_used_step IS NULL OR last_used_step < ?)",
+        )
+        .bind(step)
+        .bind(entity_name)
+        .bind(namespace_id)
+        .bind(step)
+        .execute(self.pool.as_ref())
+        .await
+        .map(|res| res.rows_affected() == 1)
+        .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use covert_types::entity::Entity;
+
+    use crate::repos::{
+        entity::EntityRepo,
+        mount::tests::pool,
+        namespace::{Namespace, NamespaceRepo},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let pool = Arc::new(pool().await);
+        let repo = MfaRepo::new(Arc::clone(&pool));
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        NamespaceRepo::new(Arc::clone(&pool))
+            .create(&Namespace {
+                id: "root".to_string(),
+                name: "root".to_string(),
+                parent_namespace_id: None,
+            })
+            .await
+            .unwrap();
+        let entity = Entity::new("foo".to_string(), "root".to_string());
+
+        let key = TotpKey {
+            entity_name: entity.name.clone(),
+            namespace_id: entity.namespace_id.clone(),
+            secret: vec![1, 2, 3],
+            last_used_step: None,
+            created_at: Utc::now(),
+        };
+        // The entity has to exist
+        assert!(repo.set_totp_key(&key).await.is_err());
+        entity_repo.create(&entity).await.unwrap();
+
+        repo.set_totp_key(&key).await.unwrap();
+        assert_eq!(
+            repo.totp_key("foo", "root").await.unwrap(),
+            Some(key.clone())
+        );
+
+        // Codes can only be used once and in order
+        assert!(repo.use_totp_step("foo", "root", 10).await.unwrap());
+        assert!(!repo.use_totp_step("foo", "root", 10).await.unwrap());
+        assert!(!repo.use_totp_step("foo", "root", 9).await.unwrap());
+        assert!(repo.use_totp_step("foo", "root", 11).await.unwrap());
+        assert!(!repo.use_totp_step("bar", "root", 12).await.unwrap());
+
+        // Replacing the key forgets the used codes
+        repo.set_totp_key(&key).await.unwrap();
+        assert!(repo.use_totp_step("foo", "root", 10).await.unwrap());
+
+        assert!(repo.remove_totp_key("foo", "root").await.unwrap());
+        assert!(!repo.remove_totp_key("foo", "root").await.unwrap());
+        assert_eq!(repo.totp_key("foo", "root").await.unwrap(), None);
+    }
+}
@@ -1,30 +1,50 @@
 use std::sync::Arc;
 
 use covert_storage::EncryptedPool;
-use sqlx::{Pool, Sqlite};
+use sqlx::{Executor, Pool, Sqlite};
+
+use crate::{error::Error, expiration_manager::clock::Clock, PolicyLimitsConfig};
 
 use self::{
-    entity::EntityRepo, lease::LeaseRepo, mount::MountRepo, namespace::NamespaceRepo,
-    policy::PolicyRepo, seal::SealRepo, token::TokenRepo,
+    audit::AuditRepo, entity::EntityRepo, ha::HaLockRepo, identity::IdentityRepo, lease::LeaseRepo,
+    mfa::MfaRepo, mount::MountRepo, namespace::NamespaceRepo, policy::PolicyRepo, quota::QuotaRepo,
+    seal::SealRepo, static_secret::StaticSecretRepo, token::TokenRepo, wrapping::WrappingRepo,
+    write_index::WriteIndexRepo,
 };
 
+pub mod audit;
 pub mod entity;
+pub mod ha;
+pub mod identity;
 pub mod lease;
+pub mod mfa;
 pub mod mount;
 pub mod namespace;
 pub mod policy;
+pub mod quota;
 pub mod seal;
+pub mod static_secret;
 pub mod token;
+pub mod wrapping;
+pub mod write_index;
 
 #[derive(Clone)]
 pub struct Repos {
+    pub audit: AuditRepo,
     pub entity: EntityRepo,
+    pub ha: HaLockRepo,
+    pub identity: IdentityRepo,
     pub lease: LeaseRepo,
+    pub mfa: MfaRepo,
     pub mount: MountRepo,
     pub policy: PolicyRepo,
+    pub quota: QuotaRepo,
+    pub static_secret: StaticSecretRepo,
     pub token: TokenRepo,
+    pub wrapping: WrappingRepo,
     pub namespace: NamespaceRepo,
     pub seal: SealRepo,
+    pub write_index: WriteIndexRepo,
     pub pool: Arc<EncryptedPool>,
     pub unecrypted_pool: Pool<Sqlite>,
 }
@@ -32,15 +52,51 @@ pub struct Repos {
 impl Repos {
     pub fn new(pool: Arc<EncryptedPool>, unecrypted_pool: Pool<Sqlite>) -> Self {
         Self {
+            audit: AuditRepo::new(Arc::clone(&pool)),
             entity: EntityRepo::new(Arc::clone(&pool)),
+            ha: HaLockRepo::new(unecrypted_pool.clone()),
+            identity: IdentityRepo::new(Arc::clone(&pool)),
             lease: LeaseRepo::new(Arc::clone(&pool)),
+            mfa: MfaRepo::new(Arc::clone(&pool)),
             mount: MountRepo::new(Arc::clone(&pool)),
             policy: PolicyRepo::new(Arc::clone(&pool)),
+            quota: QuotaRepo::new(Arc::clone(&pool)),
+            static_secret: StaticSecretRepo::new(Arc::clone(&pool)),
             token: TokenRepo::new(Arc::clone(&pool)),
+            wrapping: WrappingRepo::new(Arc::clone(&pool)),
             namespace: NamespaceRepo::new(Arc::clone(&pool)),
             seal: SealRepo::new(unecrypted_pool.clone()),
+            write_index: WriteIndexRepo::new(Arc::clone(&pool)),
             pool,
             unecrypted_pool,
         }
     }
+
+    /// Enforce the limits on the policies attached to tokens and entities.
+    #[must_use]
+    pub fn with_policy_limits(mut self, limits: PolicyLimitsConfig) -> Self {
+        self.entity = self.entity.with_policy_limits(limits);
+        self.policy = self.policy.with_limits(limits);
+        self.token = self.token.with_policy_limits(limits);
+        self
+    }
+
+    /// Check the expiry of tokens against the clock of the
+    /// [`ExpirationManager`](crate::ExpirationManager).
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.token = self.token.with_clock(clock);
+        self
+    }
+}
+
+/// Size in bytes of the database.
+pub async fn database_size<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+) -> Result<u64, Error> {
+    sqlx::query_as("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()")
+        .fetch_one(executor)
+        .await
+        .map_err(Into::into)
+        .map(|(size,): (i64,)| size.unsigned_abs())
 }
@@ -3,7 +3,7 @@ use std::{str::FromStr, sync::Arc, time::Duration};
 use covert_storage::EncryptedPool;
 use covert_types::{
     backend::BackendType,
-    mount::{MountConfig, MountEntry},
+    mount::{ListingVisibility, MountConfig, MountEntry},
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -16,6 +16,14 @@ pub struct MountEntryRaw {
     pub path: String,
     pub default_lease_ttl: i64,
     pub max_lease_ttl: i64,
+    pub require_mfa: bool,
+    pub max_request_body_size: Option<i64>,
+    pub max_concurrent_requests: Option<i64>,
+    pub plugin: Option<String>,
+    pub listing_visibility: String,
+    pub description: Option<String>,
+    /// JSON object of the encrypted fields.
+    pub encrypted_fields: String,
     pub variant: String,
     pub namespace_id: String,
 }
@@ -30,6 +38,19 @@ impl TryFrom<MountEntryRaw> for MountEntry {
         let backend_type = BackendType::from_str(&value.variant).map_err(|_| {
             ErrorType::BadData(format!("`{}` is not a valid backend type", value.variant))
         })?;
+        let listing_visibility =
+            ListingVisibility::from_str(&value.listing_visibility).map_err(|_| {
+                ErrorType::BadData(format!(
+                    "`{}` is not a valid listing visibility",
+                    value.listing_visibility
+                ))
+            })?;
+        let encrypted_fields = serde_json::from_str(&value.encrypted_fields).map_err(|_| {
+            ErrorType::BadData(format!(
+                "`{}` are not valid encrypted fields",
+                value.encrypted_fields
+            ))
+        })?;
         let default_lease_ttl = u64::try_from(value.default_lease_ttl).unwrap_or(u64::MAX);
         let max_lease_ttl = u64::try_from(value.max_lease_ttl).unwrap_or(u64::MAX);
 
@@ -39,6 +60,17 @@ impl TryFrom<MountEntryRaw> for MountEntry {
             config: MountConfig {
                 default_lease_ttl: Duration::from_millis(default_lease_ttl),
                 max_lease_ttl: Duration::from_millis(max_lease_ttl),
+                require_mfa: value.require_mfa,
+                max_request_body_size: value
+                    .max_request_body_size
+                    .map(|size| u64::try_from(size).unwrap_or_default()),
+                max_concurrent_requests: value
+                    .max_concurrent_requests
+                    .map(|limit| u32::try_from(limit).unwrap_or(u32::MAX)),
+                plugin: value.plugin,
+                listing_visibility,
+                description: value.description,
+                encrypted_fields,
             },
             backend_type,
             namespace_id: value.namespace_id,
@@ -46,6 +78,17 @@ impl TryFrom<MountEntryRaw> for MountEntry {
     }
 }
 
+fn max_request_body_size(config: &MountConfig) -> Option<i64> {
+    config
+        .max_request_body_size
+        .map(|size| i64::try_from(size).unwrap_or(i64::MAX))
+}
+
+fn encrypted_fields(config: &MountConfig) -> Result<String, Error> {
+    serde_json::to_string(&config.encrypted_fields)
+        .map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 pub struct MountRepo {
     pool: Arc<EncryptedPool>,
 }
@@ -70,14 +113,21 @@ impl MountRepo {
         let default_lease_ttl =
             i64::try_from(mount.config.default_lease_ttl.as_millis()).unwrap_or(i64::MAX);
         sqlx::query(
-            "INSERT INTO MOUNTS (id, path, variant, max_lease_ttl, default_lease_ttl, namespace_id)
-            VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO MOUNTS (id, path, variant, max_lease_ttl, default_lease_ttl, require_mfa, max_request_body_size, max_concurrent_requests, plugin, listing_visibility, description, encrypted_fields, namespace_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(mount.id.to_string())
         .bind(&mount.path)
         .bind(mount.backend_type.to_string())
         .bind(max_lease_ttl)
         .bind(default_lease_ttl)
+        .bind(mount.config.require_mfa)
+        .bind(max_request_body_size(&mount.config))
+        .bind(mount.config.max_concurrent_requests)
+        .bind(&mount.config.plugin)
+        .bind(mount.config.listing_visibility.to_string())
+        .bind(&mount.config.description)
+        .bind(encrypted_fields(&mount.config)?)
         .bind(&mount.namespace_id)
         .execute(self.pool.as_ref())
         .await
@@ -99,11 +149,23 @@ impl MountRepo {
         sqlx::query(
             "UPDATE MOUNTS SET 
                     max_lease_ttl = ?,
-                    default_lease_ttl = ?
+                    default_lease_ttl = ?,
+                    require_mfa = ?,
+                    max_request_body_size = ?,
+                    max_concurrent_requests = ?,
+                    listing_visibility = ?,
+                    description = ?,
+                    encrypted_fields = ?
                 WHERE path = ? AND namespace_id = ?",
         )
         .bind(max_lease_ttl)
         .bind(default_lease_ttl)
+        .bind(config.require_mfa)
+        .bind(max_request_body_size(config))
+        .bind(config.max_concurrent_requests)
+        .bind(config.listing_visibility.to_string())
+        .bind(&config.description)
+        .bind(encrypted_fields(config)?)
         .bind(path)
         .bind(namespace_id)
         .execute(self.pool.as_ref())
@@ -133,6 +195,21 @@ impl MountRepo {
             .map_err(Into::into)
     }
 
+    /// Mounts of every namespace.
+    #[tracing::instrument(skip_all)]
+    pub async fn list_all(&self) -> Result<Vec<MountEntry>, Error> {
+        sqlx::query_as("SELECT * FROM MOUNTS ORDER BY path ASC")
+            .fetch_all(self.pool.as_ref())
+            .await
+            .map(|mounts: Vec<MountEntryRaw>| {
+                mounts
+                    .into_iter()
+                    .filter_map(|m| m.try_into().ok())
+                    .collect()
+            })
+            .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn get_by_path(
         &self,
@@ -154,9 +231,11 @@ impl MountRepo {
         path: &str,
         namespace_id: &str,
     ) -> Result<Option<MountEntry>, Error> {
+        // The path of the mount itself matches with or without the trailing
+        // slash
         sqlx::query_as(
             "SELECT * FROM MOUNTS 
-            WHERE namespace_id = ? AND ? LIKE (path || '%')
+            WHERE namespace_id = ? AND substr(? || '/', 1, length(path)) = path
             ORDER BY length(path) DESC LIMIT 1",
         )
         .bind(namespace_id)
@@ -181,7 +260,9 @@ impl MountRepo {
 
 #[cfg(test)]
 pub mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
+
+    use covert_types::mount::TransitKey;
 
     use crate::repos::namespace::{Namespace, NamespaceRepo};
 
@@ -214,6 +295,13 @@ pub mod tests {
             config: MountConfig {
                 default_lease_ttl: Duration::from_secs(30),
                 max_lease_ttl: Duration::from_secs(60),
+                require_mfa: false,
+                max_request_body_size: None,
+                max_concurrent_requests: None,
+                plugin: None,
+                listing_visibility: ListingVisibility::default(),
+                description: None,
+                encrypted_fields: BTreeMap::new(),
             },
             path: "foo".into(),
             namespace_id: ns.id.clone(),
@@ -224,6 +312,19 @@ pub mod tests {
         let new_config = MountConfig {
             default_lease_ttl: Duration::ZERO,
             max_lease_ttl: Duration::ZERO,
+            require_mfa: true,
+            max_request_body_size: Some(1024 * 1024 * 16),
+            max_concurrent_requests: Some(8),
+            plugin: None,
+            listing_visibility: ListingVisibility::Hidden,
+            description: None,
+            encrypted_fields: BTreeMap::from([(
+                "password".to_string(),
+                TransitKey {
+                    mount: "transit/".to_string(),
+                    key: "app".to_string(),
+                },
+            )]),
         };
         me.config = new_config.clone();
 
@@ -266,13 +367,20 @@ pub mod tests {
 
         let mut ids = HashMap::new();
 
-        for path in ["/foo", "/foo/bar", "/foo/bar/baz"] {
+        for path in ["/foo", "/foo/bar", "/foo/bar/baz", "/a_b/"] {
             let me = MountEntry {
                 id: Uuid::new_v4(),
                 backend_type: BackendType::Kv,
                 config: MountConfig {
                     default_lease_ttl: Duration::from_secs(30),
                     max_lease_ttl: Duration::from_secs(60),
+                    require_mfa: false,
+                    max_request_body_size: None,
+                    max_concurrent_requests: None,
+                    plugin: None,
+                    listing_visibility: ListingVisibility::default(),
+                    description: None,
+                    encrypted_fields: BTreeMap::new(),
                 },
                 path: path.into(),
                 namespace_id: ns.id.clone(),
@@ -290,6 +398,8 @@ pub mod tests {
             ("/foo/bar/ba", "/foo/bar"),
             ("/foo/bar/baz", "/foo/bar/baz"),
             ("/foo/bar/baz/", "/foo/bar/baz"),
+            ("/a_b", "/a_b/"),
+            ("/a_b/c", "/a_b/"),
         ];
         for (p1, p2) in tests {
             assert_eq!(
@@ -301,5 +411,11 @@ pub mod tests {
                 Some(ids.get(p2).copied().unwrap())
             );
         }
+        // Only matches the path literally
+        assert!(store
+            .longest_prefix("/axb/", &ns.id)
+            .await
+            .unwrap()
+            .is_none());
     }
 }
@@ -148,6 +148,18 @@ impl NamespaceRepo {
         .await
         .map_err(Into::into)
     }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_child(&self, id: &str, name: &str) -> Result<Option<Namespace>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT * FROM {NAMESPACE_TABLE} WHERE parent_namespace_id = ? AND name = ?"
+        ))
+        .bind(id)
+        .bind(name)
+        .fetch_optional(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+    }
 }
 
 #[cfg(test)]
@@ -3,23 +3,74 @@ use std::sync::Arc;
 use covert_storage::EncryptedPool;
 use covert_types::policy::Policy;
 
-use crate::error::{Error, ErrorType};
+use crate::{
+    error::{Error, ErrorType},
+    PolicyLimitsConfig,
+};
+
+/// Name of the policy granting every operation on every path in the root
+/// namespace.
+pub const ROOT_POLICY: &str = "root";
+
+/// Name of the policy granted to every token of the namespace, if the
+/// namespace has one.
+pub const DEFAULT_POLICY: &str = "default";
+
+/// Check the policies granted to a token or attached to an entity against the
+/// limits. `owner` names what the policies are attached to in the errors.
+pub(crate) fn validate_policies(
+    policies: &[String],
+    max_policies: usize,
+    limits: &PolicyLimitsConfig,
+    owner: &str,
+) -> Result<(), Error> {
+    if policies.len() > max_policies {
+        return Err(ErrorType::BadRequest(format!(
+            "{owner} cannot have more than {max_policies} policies"
+        ))
+        .into());
+    }
+    policies
+        .iter()
+        .try_for_each(|name| validate_policy_name(name, limits))
+}
+
+fn validate_policy_name(name: &str, limits: &PolicyLimitsConfig) -> Result<(), Error> {
+    if name.len() > limits.max_policy_name_length {
+        return Err(ErrorType::BadRequest(format!(
+            "Policy name is longer than {} bytes",
+            limits.max_policy_name_length
+        ))
+        .into());
+    }
+    Ok(())
+}
 
 pub struct PolicyRepo {
     pool: Arc<EncryptedPool>,
+    limits: PolicyLimitsConfig,
 }
 
 impl Clone for PolicyRepo {
     fn clone(&self) -> Self {
         Self {
             pool: Arc::clone(&self.pool),
+            limits: self.limits,
         }
     }
 }
 
 impl PolicyRepo {
     pub fn new(pool: Arc<EncryptedPool>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            limits: PolicyLimitsConfig::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: PolicyLimitsConfig) -> Self {
+        self.limits = limits;
+        self
     }
 
     #[tracing::instrument(skip(self))]
@@ -64,6 +115,10 @@ impl PolicyRepo {
             })
     }
 
+    pub fn validate_name(&self, name: &str) -> Result<(), Error> {
+        validate_policy_name(name, &self.limits)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn create(&self, policy: &Policy) -> Result<(), Error> {
         let Policy {
@@ -71,6 +126,7 @@ impl PolicyRepo {
             paths,
             namespace_id,
         } = policy;
+        validate_policy_name(name, &self.limits)?;
 
         let policies = serde_json::to_string(paths)
             .map_err(|_| ErrorType::BadRequest("Invalid policy format".to_string()))?;
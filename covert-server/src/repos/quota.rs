@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use covert_storage::EncryptedPool;
+use covert_types::methods::system::RateLimitQuota;
+
+use crate::error::{Error, ErrorType};
+
+#[derive(Debug, sqlx::FromRow)]
+struct RateLimitQuotaRaw {
+    name: String,
+    scope: String,
+    rate: f64,
+    burst: u32,
+    exempt_root: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<RateLimitQuotaRaw> for RateLimitQuota {
+    type Error = Error;
+
+    fn try_from(raw: RateLimitQuotaRaw) -> Result<Self, Self::Error> {
+        let scope = serde_json::from_str(&raw.scope).map_err(|_| {
+            ErrorType::BadData(format!("Unable to parse scope of quota `{}`", raw.name))
+        })?;
+        Ok(RateLimitQuota {
+            name: raw.name,
+            scope,
+            rate: raw.rate,
+            burst: raw.burst,
+            exempt_root: raw.exempt_root,
+            created_at: raw.created_at,
+        })
+    }
+}
+
+/// Columns of the quotas. Rates without a fractional part are stored as
+/// integers and have to be cast back.
+const COLUMNS: &str = "name, scope, CAST(rate AS REAL) AS rate, burst, exempt_root, created_at";
+
+pub struct QuotaRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for QuotaRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl QuotaRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Create the quota or replace the configuration of an existing quota
+    /// with the same name. The creation time of an existing quota is kept.
+    #[tracing::instrument(skip_all, fields(name = quota.name))]
+    pub async fn upsert(&self, quota: &RateLimitQuota) -> Result<RateLimitQuota, Error> {
+        let scope = serde_json::to_string(&quota.scope).map_err(ErrorType::BadResponseData)?;
+        sqlx::query_as(&format!(
+            "INSERT INTO RATE_LIMIT_QUOTAS (name, scope, rate, burst, exempt_root, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET
+                scope = excluded.scope,
+                rate = excluded.rate,
+                burst = excluded.burst,
+                exempt_root = excluded.exempt_root
+            RETURNING {COLUMNS}"
+        ))
+        .bind(&quota.name)
+        .bind(scope)
+        .bind(quota.rate)
+        .bind(quota.burst)
+        .bind(quota.exempt_root)
+        .bind(quota.created_at)
+        .fetch_one(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|quota: RateLimitQuotaRaw| quota.try_into())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self) -> Result<Vec<RateLimitQuota>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM RATE_LIMIT_QUOTAS ORDER BY name"
+        ))
+        .fetch_all(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|quotas: Vec<RateLimitQuotaRaw>| {
+            quotas.into_iter().map(TryInto::try_into).collect()
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn lookup(&self, name: &str) -> Result<Option<RateLimitQuota>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM RATE_LIMIT_QUOTAS WHERE name = ?"
+        ))
+        .bind(name)
+        .fetch_optional(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|quota: Option<RateLimitQuotaRaw>| quota.map(TryInto::try_into).transpose())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn remove(&self, name: &str) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM RATE_LIMIT_QUOTAS WHERE name = ?")
+            .bind(name)
+            .execute(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .map(|res| res.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use covert_types::methods::system::QuotaScope;
+
+    use crate::repos::mount::tests::pool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let pool = Arc::new(pool().await);
+        let repo = QuotaRepo::new(Arc::clone(&pool));
+
+        let quota = RateLimitQuota {
+            name: "kv".to_string(),
+            scope: QuotaScope::Mount {
+                path: "kv/".to_string(),
+            },
+            rate: 10.5,
+            burst: 20,
+            exempt_root: true,
+            created_at: Utc::now(),
+        };
+        assert_eq!(repo.upsert(&quota).await.unwrap(), quota);
+        assert_eq!(repo.list().await.unwrap(), vec![quota.clone()]);
+        assert_eq!(repo.lookup(&quota.name).await.unwrap(), Some(quota.clone()));
+
+        // Updating keeps the creation time
+        let updated = RateLimitQuota {
+            scope: QuotaScope::Global,
+            rate: 1.0,
+            burst: 1,
+            exempt_root: false,
+            created_at: Utc::now() + chrono::Duration::hours(1),
+            ..quota.clone()
+        };
+        assert_eq!(
+            repo.upsert(&updated).await.unwrap(),
+            RateLimitQuota {
+                created_at: quota.created_at,
+                ..updated
+            }
+        );
+
+        assert!(repo.remove(&quota.name).await.unwrap());
+        assert!(!repo.remove(&quota.name).await.unwrap());
+        assert!(repo.list().await.unwrap().is_empty());
+        assert!(repo.lookup(&quota.name).await.unwrap().is_none());
+    }
+}
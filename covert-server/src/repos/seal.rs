@@ -1,7 +1,10 @@
+use std::time::Duration;
+
 use aes_gcm::{
     aead::{Aead, OsRng},
     Aes256Gcm, KeyInit, Nonce,
 };
+use covert_types::methods::system::TamperConfig;
 use rand::distributions::{Alphanumeric, DistString};
 use sqlx::{Pool, Sqlite};
 
@@ -11,6 +14,10 @@ const SEAL_CONFIGURATION_TABLE: &str = "SEAL_CONFIG";
 
 const KEY_SHARES_TABLE: &str = "KEY_SHARES";
 
+const TAMPER_CONFIG_TABLE: &str = "TAMPER_CONFIG";
+
+const WRAPPED_MASTER_KEY_TABLE: &str = "WRAPPED_MASTER_KEY";
+
 #[derive(Debug, sqlx::FromRow, PartialEq, Eq)]
 pub struct SealConfig {
     pub threshold: u8,
@@ -58,6 +65,65 @@ impl SealRepo {
             .map_err(Into::into)
     }
 
+    /// The tamper config is kept with the seal config so it also applies
+    /// while the storage is sealed.
+    pub async fn get_tamper_config(&self) -> Result<Option<TamperConfig>, Error> {
+        let row: Option<(bool, u32, u32, i64)> = sqlx::query_as(&format!(
+            "SELECT enabled, decryption_failures, malformed_tokens, window_secs
+                FROM {TAMPER_CONFIG_TABLE}"
+        ))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(
+            |(enabled, decryption_failures, malformed_tokens, window_secs)| TamperConfig {
+                enabled,
+                decryption_failures,
+                malformed_tokens,
+                window: Duration::from_secs(u64::try_from(window_secs).unwrap_or_default()),
+            },
+        ))
+    }
+
+    pub async fn set_tamper_config(&self, config: &TamperConfig) -> Result<(), Error> {
+        sqlx::query(&format!(
+            "INSERT INTO {TAMPER_CONFIG_TABLE}
+                (enabled, decryption_failures, malformed_tokens, window_secs, lock)
+                VALUES ($1, $2, $3, $4, 1)
+                ON CONFLICT (lock) DO UPDATE SET
+                    enabled = excluded.enabled,
+                    decryption_failures = excluded.decryption_failures,
+                    malformed_tokens = excluded.malformed_tokens,
+                    window_secs = excluded.window_secs"
+        ))
+        .bind(config.enabled)
+        .bind(config.decryption_failures)
+        .bind(config.malformed_tokens)
+        .bind(i64::try_from(config.window.as_secs()).unwrap_or(i64::MAX))
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(Into::into)
+    }
+
+    /// Store the master key wrapped by the seal of the server config.
+    pub async fn set_wrapped_master_key(&self, key: &[u8]) -> Result<(), Error> {
+        sqlx::query(&format!(
+            "INSERT INTO {WRAPPED_MASTER_KEY_TABLE} (\"key\", lock) VALUES ($1, 1)"
+        ))
+        .bind(key)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(Into::into)
+    }
+
+    pub async fn get_wrapped_master_key(&self) -> Result<Option<Vec<u8>>, Error> {
+        sqlx::query_scalar(&format!("SELECT \"key\" FROM {WRAPPED_MASTER_KEY_TABLE}"))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Into::into)
+    }
+
     pub async fn clear_key_shares(&self) -> Result<u64, Error> {
         sqlx::query(&format!("DELETE FROM {KEY_SHARES_TABLE}"))
             .execute(&self.pool)
@@ -167,4 +233,46 @@ mod tests {
         assert!(seal.clear_key_shares().await.is_ok());
         assert!(seal.get_key_shares().await.unwrap().is_empty());
     }
+
+    #[tokio::test]
+    async fn tamper_config() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::migrations::migrate_unecrypted_db(&pool)
+            .await
+            .unwrap();
+        let seal = SealRepo::new(pool);
+
+        assert!(seal.get_tamper_config().await.unwrap().is_none());
+
+        let mut config = TamperConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        seal.set_tamper_config(&config).await.unwrap();
+        assert_eq!(seal.get_tamper_config().await.unwrap(), Some(config));
+
+        // Only one config is kept
+        config.malformed_tokens = 10;
+        config.window = Duration::from_secs(5);
+        seal.set_tamper_config(&config).await.unwrap();
+        assert_eq!(seal.get_tamper_config().await.unwrap(), Some(config));
+    }
+
+    #[tokio::test]
+    async fn wrapped_master_key() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::migrations::migrate_unecrypted_db(&pool)
+            .await
+            .unwrap();
+        let seal = SealRepo::new(pool);
+
+        assert!(seal.get_wrapped_master_key().await.unwrap().is_none());
+        seal.set_wrapped_master_key(b"wrapped").await.unwrap();
+        assert_eq!(
+            seal.get_wrapped_master_key().await.unwrap(),
+            Some(b"wrapped".to_vec())
+        );
+        // The wrapped key is never replaced
+        assert!(seal.set_wrapped_master_key(b"other").await.is_err());
+    }
 }
@@ -0,0 +1,293 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use covert_storage::EncryptedPool;
+use covert_types::methods::system::{RotationHandler, StaticSecret};
+use serde_json::Value;
+
+use crate::error::{Error, ErrorType};
+
+#[derive(Debug, sqlx::FromRow)]
+struct StaticSecretRaw {
+    namespace_id: String,
+    name: String,
+    handler: String,
+    interval_secs: i64,
+    grace_secs: i64,
+    version: i64,
+    current_value: String,
+    previous_value: Option<String>,
+    rotated_at: DateTime<Utc>,
+    next_rotation_at: DateTime<Utc>,
+}
+
+/// A static secret with its current and previous value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticSecretEntry {
+    pub namespace_id: String,
+    pub secret: StaticSecret,
+    pub current: Value,
+    pub previous: Option<Value>,
+}
+
+impl StaticSecretEntry {
+    /// Until when the previous value can be read.
+    #[must_use]
+    pub fn grace_until(&self) -> DateTime<Utc> {
+        after(self.secret.rotated_at, self.secret.grace)
+    }
+
+    /// When the secret is due again if it is rotated at `rotated_at`.
+    #[must_use]
+    pub fn next_rotation_after(&self, rotated_at: DateTime<Utc>) -> DateTime<Utc> {
+        after(rotated_at, self.secret.interval)
+    }
+}
+
+impl TryFrom<StaticSecretRaw> for StaticSecretEntry {
+    type Error = Error;
+
+    fn try_from(raw: StaticSecretRaw) -> Result<Self, Self::Error> {
+        let bad_data = |field: &str| {
+            ErrorType::BadData(format!(
+                "Unable to parse {field} of static secret `{}`",
+                raw.name
+            ))
+        };
+        let handler = serde_json::from_str(&raw.handler).map_err(|_| bad_data("handler"))?;
+        let current = serde_json::from_str(&raw.current_value).map_err(|_| bad_data("value"))?;
+        let previous = raw
+            .previous_value
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|_| bad_data("previous value"))?;
+        Ok(StaticSecretEntry {
+            namespace_id: raw.namespace_id,
+            secret: StaticSecret {
+                name: raw.name,
+                handler,
+                interval: Duration::from_secs(raw.interval_secs.unsigned_abs()),
+                grace: Duration::from_secs(raw.grace_secs.unsigned_abs()),
+                version: raw.version.unsigned_abs(),
+                rotated_at: raw.rotated_at,
+                next_rotation_at: raw.next_rotation_at,
+            },
+            current,
+            previous,
+        })
+    }
+}
+
+const COLUMNS: &str = "namespace_id, name, handler, interval_secs, grace_secs, version, \
+                       current_value, previous_value, rotated_at, next_rotation_at";
+
+/// `time` plus `duration`, saturating at the latest representable time.
+pub fn after(time: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| time.checked_add_signed(duration))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn secs(duration: Duration) -> i64 {
+    i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, Error> {
+    serde_json::to_string(value).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub struct StaticSecretRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for StaticSecretRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl StaticSecretRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Store a new static secret with its first value. Returns `false` if a
+    /// secret with the same name exists.
+    #[tracing::instrument(skip(self, secret, value), fields(name = secret.name))]
+    pub async fn create(
+        &self,
+        namespace_id: &str,
+        secret: &StaticSecret,
+        value: &Value,
+    ) -> Result<bool, Error> {
+        sqlx::query(
+            "INSERT INTO STATIC_SECRETS (namespace_id, name, handler, interval_secs, grace_secs,
+                version, current_value, rotated_at, next_rotation_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (namespace_id, name) DO NOTHING",
+        )
+        .bind(namespace_id)
+        .bind(&secret.name)
+        .bind(to_json(&secret.handler)?)
+        .bind(secs(secret.interval))
+        .bind(secs(secret.grace))
+        .bind(i64::try_from(secret.version).unwrap_or(i64::MAX))
+        .bind(to_json(value)?)
+        .bind(secret.rotated_at)
+        .bind(secret.next_rotation_at)
+        .execute(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .map(|res| res.rows_affected() == 1)
+    }
+
+    /// Change how and when the secret is rotated. The values are kept and the
+    /// next rotation is rescheduled from the last one.
+    #[tracing::instrument(skip(self, handler))]
+    pub async fn configure(
+        &self,
+        namespace_id: &str,
+        name: &str,
+        handler: &RotationHandler,
+        interval: Duration,
+        grace: Duration,
+    ) -> Result<Option<StaticSecretEntry>, Error> {
+        let Some(entry) = self.lookup(namespace_id, name).await? else {
+            return Ok(None);
+        };
+        let next_rotation_at = after(entry.secret.rotated_at, interval);
+        sqlx::query_as(&format!(
+            "UPDATE STATIC_SECRETS SET handler = ?, interval_secs = ?, grace_secs = ?,
+                next_rotation_at = ?
+            WHERE namespace_id = ? AND name = ?
+            RETURNING {COLUMNS}"
+        ))
+        .bind(to_json(handler)?)
+        .bind(secs(interval))
+        .bind(secs(grace))
+        .bind(next_rotation_at)
+        .bind(namespace_id)
+        .bind(name)
+        .fetch_optional(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|raw: Option<StaticSecretRaw>| raw.map(TryInto::try_into).transpose())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn lookup(
+        &self,
+        namespace_id: &str,
+        name: &str,
+    ) -> Result<Option<StaticSecretEntry>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM STATIC_SECRETS WHERE namespace_id = ? AND name = ?"
+        ))
+        .bind(namespace_id)
+        .bind(name)
+        .fetch_optional(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|raw: Option<StaticSecretRaw>| raw.map(TryInto::try_into).transpose())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self, namespace_id: &str) -> Result<Vec<StaticSecret>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM STATIC_SECRETS WHERE namespace_id = ? ORDER BY name"
+        ))
+        .bind(namespace_id)
+        .fetch_all(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|raws: Vec<StaticSecretRaw>| {
+            raws.into_iter()
+                .map(|raw| StaticSecretEntry::try_from(raw).map(|entry| entry.secret))
+                .collect()
+        })
+    }
+
+    /// The secrets of every namespace that are due for rotation, the most
+    /// overdue first.
+    #[tracing::instrument(skip(self))]
+    pub async fn due(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<StaticSecretEntry>, Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM STATIC_SECRETS WHERE next_rotation_at <= ?
+            ORDER BY next_rotation_at LIMIT ?"
+        ))
+        .bind(now)
+        .bind(limit)
+        .fetch_all(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|raws: Vec<StaticSecretRaw>| raws.into_iter().map(TryInto::try_into).collect())
+    }
+
+    /// Replace the value of the secret, keeping the current value as the
+    /// previous one. Only applied if the secret is still at the version of
+    /// `entry`, so a value is never replaced twice by concurrent rotations.
+    /// Returns the rotated secret, if it was rotated.
+    #[tracing::instrument(skip_all, fields(name = entry.secret.name))]
+    pub async fn rotate(
+        &self,
+        entry: &StaticSecretEntry,
+        value: &Value,
+        rotated_at: DateTime<Utc>,
+    ) -> Result<Option<StaticSecretEntry>, Error> {
+        sqlx::query_as(&format!(
+            "UPDATE STATIC_SECRETS SET previous_value = current_value, current_value = ?,
+                version = version + 1, rotated_at = ?, next_rotation_at = ?
+            WHERE namespace_id = ? AND name = ? AND version = ?
+            RETURNING {COLUMNS}"
+        ))
+        .bind(to_json(value)?)
+        .bind(rotated_at)
+        .bind(entry.next_rotation_after(rotated_at))
+        .bind(&entry.namespace_id)
+        .bind(&entry.secret.name)
+        .bind(i64::try_from(entry.secret.version).unwrap_or(i64::MAX))
+        .fetch_optional(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .and_then(|raw: Option<StaticSecretRaw>| raw.map(TryInto::try_into).transpose())
+    }
+
+    /// Try the rotation again later, after it failed.
+    #[tracing::instrument(skip(self))]
+    pub async fn postpone(
+        &self,
+        namespace_id: &str,
+        name: &str,
+        next_rotation_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        sqlx::query(
+            "UPDATE STATIC_SECRETS SET next_rotation_at = ? WHERE namespace_id = ? AND name = ?",
+        )
+        .bind(next_rotation_at)
+        .bind(namespace_id)
+        .bind(name)
+        .execute(self.pool.as_ref())
+        .await
+        .map_err(Into::into)
+        .map(|res| res.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn remove(&self, namespace_id: &str, name: &str) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM STATIC_SECRETS WHERE namespace_id = ? AND name = ?")
+            .bind(namespace_id)
+            .bind(name)
+            .execute(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .map(|res| res.rows_affected() == 1)
+    }
+}
@@ -1,42 +1,144 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::Arc,
+};
 
 use chrono::{DateTime, Duration, Utc};
 use covert_storage::EncryptedPool;
-use covert_types::{policy::Policy, token::Token};
+use covert_types::{
+    policy::{Policy, PolicySource},
+    token::Token,
+};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::error::Error;
+use crate::{
+    error::{Error, ErrorType},
+    expiration_manager::clock::{Clock, SystemClock},
+    PolicyLimitsConfig,
+};
 
-use super::policy::PolicyRaw;
+use super::policy::{validate_policies, PolicyRaw, DEFAULT_POLICY};
+
+/// The policy names of a valid token with their namespace and source. The
+/// first parameter is the token, the second the current time. A token is not
+/// valid before its `not_before` time. Names that are
+/// not the name of a policy of the namespace grant nothing.
+const TOKEN_POLICY_SOURCES: &str = "
+    SELECT EP.policy_name AS name, T.namespace_id AS namespace_id, 'entity' AS source
+    FROM TOKENS T
+    INNER JOIN ENTITY_POLICIES EP ON T.entity_name = EP.entity_name AND T.namespace_id = EP.namespace_id
+    WHERE T.token = ?1 AND NOT T.revoking AND (T.expires_at IS NULL OR T.expires_at > ?2)
+        AND (T.not_before IS NULL OR T.not_before <= ?2)
+    UNION ALL
+    SELECT TP.value, T.namespace_id, 'role' FROM TOKENS T, json_each(T.policies) TP
+    WHERE T.token = ?1 AND NOT T.revoking AND (T.expires_at IS NULL OR T.expires_at > ?2)
+        AND (T.not_before IS NULL OR T.not_before <= ?2)
+    UNION ALL
+    SELECT GP.value, T.namespace_id, 'group' FROM TOKENS T, json_each(T.group_policies) GP
+    WHERE T.token = ?1 AND NOT T.revoking AND (T.expires_at IS NULL OR T.expires_at > ?2)
+        AND (T.not_before IS NULL OR T.not_before <= ?2)
+    UNION ALL
+    SELECT ?3, T.namespace_id, 'default' FROM TOKENS T
+    WHERE T.token = ?1 AND NOT T.revoking AND (T.expires_at IS NULL OR T.expires_at > ?2)
+        AND (T.not_before IS NULL OR T.not_before <= ?2)";
+
+/// Max number of alias metadata entries an auth backend can attach to a token.
+const MAX_ALIAS_METADATA_ENTRIES: usize = 64;
+
+/// Max length in bytes of an alias metadata key.
+const MAX_ALIAS_METADATA_KEY_LEN: usize = 128;
+
+/// Max length in bytes of an alias metadata value.
+const MAX_ALIAS_METADATA_VALUE_LEN: usize = 512;
+
+fn validate_alias_metadata(metadata: &HashMap<String, String>) -> Result<(), Error> {
+    if metadata.len() > MAX_ALIAS_METADATA_ENTRIES {
+        return Err(ErrorType::BadRequest(format!(
+            "Alias metadata cannot have more than {MAX_ALIAS_METADATA_ENTRIES} entries"
+        ))
+        .into());
+    }
+    for (key, value) in metadata {
+        if key.len() > MAX_ALIAS_METADATA_KEY_LEN {
+            return Err(ErrorType::BadRequest(format!(
+                "Alias metadata key `{key}` is longer than {MAX_ALIAS_METADATA_KEY_LEN} bytes"
+            ))
+            .into());
+        }
+        if value.len() > MAX_ALIAS_METADATA_VALUE_LEN {
+            return Err(ErrorType::BadRequest(format!(
+                "Alias metadata value for `{key}` is longer than {MAX_ALIAS_METADATA_VALUE_LEN} bytes"
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn parse_token(token: &str) -> Result<Token, Error> {
+    Token::from_str(token)
+        .map_err(|_| ErrorType::BadData(format!("Invalid token stored: `{token}`")).into())
+}
 
 pub struct TokenRepo {
     pool: Arc<EncryptedPool>,
+    policy_limits: PolicyLimitsConfig,
+    /// Time the expiry and `not_before` of the tokens are checked against.
+    clock: Arc<dyn Clock>,
 }
 
 impl Clone for TokenRepo {
     fn clone(&self) -> Self {
         Self {
             pool: Arc::clone(&self.pool),
+            policy_limits: self.policy_limits,
+            clock: Arc::clone(&self.clock),
         }
     }
 }
 
 impl TokenRepo {
     pub fn new(pool: Arc<EncryptedPool>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            policy_limits: PolicyLimitsConfig::default(),
+            clock: Arc::new(SystemClock::new()),
+        }
+    }
+
+    pub fn with_policy_limits(mut self, limits: PolicyLimitsConfig) -> Self {
+        self.policy_limits = limits;
+        self
+    }
+
+    /// Check the tokens against the clock of the leases that revoke them.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The time tokens are issued and checked at.
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
     }
 
+    /// The policies of the token, the union of the policies granted by the
+    /// auth backend, the groups of the user, the entity and the `default`
+    /// policy of the namespace.
     #[tracing::instrument(skip_all)]
     pub async fn lookup_policies(&self, id: &Token) -> Result<Vec<Policy>, Error> {
-        sqlx::query_as(
-            "SELECT P.* FROM TOKENS T
-            INNER JOIN ENTITIES E ON T.entity_name = E.name AND T.namespace_id = E.namespace_id
-            INNER JOIN ENTITY_POLICIES EP ON E.name = EP.entity_name AND E.namespace_id = EP.namespace_id
-            INNER JOIN POLICIES P ON EP.policy_name = P.name AND EP.namespace_id = P.namespace_id
-            WHERE T.token = ? AND (T.expires_at IS NULL OR T.expires_at > ?)",
-        )
+        sqlx::query_as(&format!(
+            "SELECT P.* FROM POLICIES P
+            WHERE (P.name, P.namespace_id) IN (SELECT name, namespace_id FROM ({TOKEN_POLICY_SOURCES}))
+            ORDER BY P.name"
+        ))
         .bind(id.to_string())
-        .bind(Utc::now())
+        .bind(self.clock.now())
+        .bind(DEFAULT_POLICY)
         .fetch_all(self.pool.as_ref())
         .await
         .map_err(Into::into)
@@ -49,21 +151,290 @@ impl TokenRepo {
         })
     }
 
+    /// The names of the policies of the token with the sources that granted
+    /// them.
+    #[tracing::instrument(skip_all)]
+    pub async fn lookup_policy_sources(
+        &self,
+        id: &Token,
+    ) -> Result<BTreeMap<String, Vec<PolicySource>>, Error> {
+        let sources: Vec<(String, String)> = sqlx::query_as(&format!(
+            "SELECT DISTINCT S.name, S.source FROM ({TOKEN_POLICY_SOURCES}) S
+            INNER JOIN POLICIES P ON P.name = S.name AND P.namespace_id = S.namespace_id"
+        ))
+        .bind(id.to_string())
+        .bind(self.clock.now())
+        .bind(DEFAULT_POLICY)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        let mut policies = BTreeMap::<_, Vec<_>>::new();
+        for (name, source) in sources {
+            let source = PolicySource::from_str(&source)
+                .map_err(|_| ErrorType::BadData(format!("Invalid policy source `{source}`")))?;
+            policies.entry(name).or_default().push(source);
+        }
+        for sources in policies.values_mut() {
+            sources.sort();
+        }
+        Ok(policies)
+    }
+
+    /// Create a token. A token with a parent can only be created while the
+    /// parent is not being revoked, so no token outlives its parent.
     #[tracing::instrument(skip_all)]
     pub async fn create(&self, te: &TokenEntry) -> Result<(), Error> {
-        sqlx::query(
-            "INSERT INTO TOKENS (token, issued_at, expires_at, entity_name, namespace_id)
-            VALUES (?, ?, ?, ?, ?)",
+        validate_alias_metadata(&te.metadata)?;
+        validate_policies(
+            &te.policies,
+            self.policy_limits.max_policies_per_token,
+            &self.policy_limits,
+            "Token",
+        )?;
+        validate_policies(
+            &te.group_policies,
+            self.policy_limits.max_policies_per_token,
+            &self.policy_limits,
+            "Token",
+        )?;
+        let metadata = serde_json::to_string(&te.metadata)
+            .map_err(|err| ErrorType::InternalError(err.into()))?;
+        let policies = serde_json::to_string(&te.policies)
+            .map_err(|err| ErrorType::InternalError(err.into()))?;
+        let group_policies = serde_json::to_string(&te.group_policies)
+            .map_err(|err| ErrorType::InternalError(err.into()))?;
+        let parent = te.parent.as_ref().map(Token::to_string);
+        let res = sqlx::query(
+            "INSERT INTO TOKENS (token, issued_at, expires_at, entity_name, namespace_id, metadata, policies, group_policies, renewable, parent, not_before, num_uses, accessor)
+            SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+            WHERE ? IS NULL OR EXISTS (SELECT 1 FROM TOKENS WHERE token = ? AND NOT revoking)",
         )
         .bind(te.id.to_string())
         .bind(te.issued_at)
         .bind(te.expires_at)
         .bind(&te.entity_name)
         .bind(&te.namespace_id)
+        .bind(metadata)
+        .bind(policies)
+        .bind(group_policies)
+        .bind(te.renewable)
+        .bind(&parent)
+        .bind(te.not_before)
+        .bind(te.num_uses)
+        .bind(&te.accessor)
+        .bind(&parent)
+        .bind(&parent)
+        .execute(self.pool.as_ref())
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(
+                ErrorType::Unauthorized("Parent token has been revoked".to_string()).into(),
+            );
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn lookup(&self, id: &Token) -> Result<Option<TokenEntry>, Error> {
+        let entry: Option<TokenEntryRaw> = sqlx::query_as(
+            "SELECT * FROM TOKENS
+            WHERE token = ?1 AND NOT revoking AND (expires_at IS NULL OR expires_at > ?2)
+                AND (not_before IS NULL OR not_before <= ?2)",
+        )
+        .bind(id.to_string())
+        .bind(self.clock.now())
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        entry.map(TryInto::try_into).transpose()
+    }
+
+    /// Lookup a valid token of the namespace by its accessor.
+    #[tracing::instrument(skip_all)]
+    pub async fn lookup_by_accessor(
+        &self,
+        accessor: &str,
+        namespace_id: &str,
+    ) -> Result<Option<TokenEntry>, Error> {
+        let entry: Option<TokenEntryRaw> = sqlx::query_as(
+            "SELECT * FROM TOKENS
+            WHERE accessor = ?1 AND namespace_id = ?2 AND NOT revoking
+                AND (expires_at IS NULL OR expires_at > ?3)",
+        )
+        .bind(accessor)
+        .bind(namespace_id)
+        .bind(self.clock.now())
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        entry.map(TryInto::try_into).transpose()
+    }
+
+    /// Use up one use of a token limited in uses. Returns `false` if the
+    /// token has no uses left.
+    #[tracing::instrument(skip_all)]
+    pub async fn consume_use(&self, id: &Token) -> Result<bool, Error> {
+        let res = sqlx::query(
+            "UPDATE TOKENS SET num_uses = num_uses - 1
+            WHERE token = ? AND num_uses > 0",
+        )
+        .bind(id.to_string())
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    /// List the non-expired tokens that have been granted the given policy,
+    /// through their entity, directly by the auth backend, through the groups
+    /// of the user or as the `default` policy.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_by_policy(
+        &self,
+        policy_name: &str,
+        namespace_id: &str,
+    ) -> Result<Vec<Token>, Error> {
+        let tokens: Vec<String> = sqlx::query_scalar(
+            "SELECT T.token FROM TOKENS T
+            WHERE T.namespace_id = ? AND NOT T.revoking
+                AND (T.expires_at IS NULL OR T.expires_at > ?) AND (
+                EXISTS (
+                    SELECT 1 FROM ENTITY_POLICIES EP
+                    WHERE EP.entity_name = T.entity_name AND EP.namespace_id = T.namespace_id
+                        AND EP.policy_name = ?
+                )
+                OR EXISTS (SELECT 1 FROM json_each(T.policies) TP WHERE TP.value = ?)
+                OR EXISTS (SELECT 1 FROM json_each(T.group_policies) GP WHERE GP.value = ?)
+                OR ? = ?
+            )",
+        )
+        .bind(namespace_id)
+        .bind(self.clock.now())
+        .bind(policy_name)
+        .bind(policy_name)
+        .bind(policy_name)
+        .bind(policy_name)
+        .bind(DEFAULT_POLICY)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        tokens.iter().map(|token| parse_token(token)).collect()
+    }
+
+    /// List the tokens of the entity that are not being revoked.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_by_entity(
+        &self,
+        entity_name: &str,
+        namespace_id: &str,
+    ) -> Result<Vec<Token>, Error> {
+        let tokens: Vec<String> = sqlx::query_scalar(
+            "SELECT token FROM TOKENS
+            WHERE entity_name = ? AND namespace_id = ? AND NOT revoking",
+        )
+        .bind(entity_name)
+        .bind(namespace_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        tokens.iter().map(|token| parse_token(token)).collect()
+    }
+
+    /// Detach the tokens of other entities from their parent if it is a token
+    /// of the entity, so they are kept when its tokens are revoked. Returns
+    /// the number of detached tokens.
+    #[tracing::instrument(skip(self))]
+    pub async fn orphan_children(
+        &self,
+        entity_name: &str,
+        namespace_id: &str,
+    ) -> Result<u64, Error> {
+        sqlx::query(
+            "UPDATE TOKENS SET parent = NULL
+            WHERE NOT revoking AND NOT (entity_name = ? AND namespace_id = ?)
+                AND parent IN (
+                    SELECT token FROM TOKENS WHERE entity_name = ? AND namespace_id = ?
+                )",
+        )
+        .bind(entity_name)
+        .bind(namespace_id)
+        .bind(entity_name)
+        .bind(namespace_id)
         .execute(self.pool.as_ref())
         .await
+        .map(|res| res.rows_affected())
         .map_err(Into::into)
-        .map(|_| ())
+    }
+
+    /// Mark a token and all the tokens issued with it, recursively, as being
+    /// revoked. Marked tokens can no longer be used or get children.
+    ///
+    /// Returns the marked tokens with their namespace, children before their
+    /// parents, so removing them in order never leaves a token without its
+    /// parent.
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_revoking(
+        &self,
+        id: &Token,
+        namespace_id: &str,
+    ) -> Result<Vec<(Token, String)>, Error> {
+        const TREE: &str = "WITH RECURSIVE TREE(token, namespace_id, depth) AS (
+                SELECT token, namespace_id, 0 FROM TOKENS WHERE token = ? AND namespace_id = ?
+                UNION
+                SELECT T.token, T.namespace_id, TREE.depth + 1 FROM TOKENS T
+                INNER JOIN TREE ON T.parent = TREE.token
+            )";
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(&format!(
+            "{TREE} UPDATE TOKENS SET revoking = 1 WHERE token IN (SELECT token FROM TREE)"
+        ))
+        .bind(id.to_string())
+        .bind(namespace_id)
+        .execute(&mut tx)
+        .await?;
+        let tokens: Vec<(String, String)> = sqlx::query_as(&format!(
+            "{TREE} SELECT token, namespace_id FROM TREE ORDER BY depth DESC"
+        ))
+        .bind(id.to_string())
+        .bind(namespace_id)
+        .fetch_all(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        tokens
+            .into_iter()
+            .map(|(token, namespace_id)| parse_token(&token).map(|token| (token, namespace_id)))
+            .collect()
+    }
+
+    /// List the tokens whose revocation was interrupted, with their
+    /// namespace. Only the top of each marked tree is returned, revoking them
+    /// again revokes the rest of the tree.
+    #[tracing::instrument(skip_all)]
+    pub async fn list_revoking(&self) -> Result<Vec<(Token, String)>, Error> {
+        let tokens: Vec<(String, String)> = sqlx::query_as(
+            "SELECT T.token, T.namespace_id FROM TOKENS T
+            WHERE T.revoking AND NOT EXISTS (
+                SELECT 1 FROM TOKENS P WHERE P.token = T.parent AND P.revoking
+            )",
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        tokens
+            .into_iter()
+            .map(|(token, namespace_id)| parse_token(&token).map(|token| (token, namespace_id)))
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn count(&self) -> Result<u64, Error> {
+        sqlx::query_as("SELECT COUNT(*) FROM TOKENS")
+            .fetch_one(self.pool.as_ref())
+            .await
+            .map_err(Into::into)
+            .map(|(count,): (i64,)| count.unsigned_abs())
     }
 
     #[tracing::instrument(skip_all)]
@@ -111,20 +482,128 @@ pub struct TokenEntry {
     pub issued_at: DateTime<Utc>,
     /// Namespace
     pub namespace_id: String,
+    /// Alias metadata provided by the auth backend that issued the token
+    pub metadata: HashMap<String, String>,
+    /// Policies granted by the auth backend in addition to the entity policies
+    pub policies: Vec<String>,
+    /// Policies mapped from the groups of the user by the auth backend
+    pub group_policies: Vec<String>,
+    /// Whether the lease of the token can be renewed
+    pub renewable: bool,
+    /// Token the token was issued with, the token is revoked together with
+    /// its parent
+    pub parent: Option<Token>,
+    /// Identifies the token to look it up or revoke it without the token
+    pub accessor: String,
+    /// The token cannot be used before this time
+    pub not_before: Option<DateTime<Utc>>,
+    /// Number of requests the token can still be used for, unlimited if unset
+    pub num_uses: Option<u32>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TokenEntryRaw {
+    token: String,
+    entity_name: String,
+    expires_at: Option<DateTime<Utc>>,
+    issued_at: DateTime<Utc>,
+    namespace_id: String,
+    metadata: String,
+    policies: String,
+    group_policies: String,
+    renewable: bool,
+    parent: Option<String>,
+    not_before: Option<DateTime<Utc>>,
+    num_uses: Option<u32>,
+    accessor: String,
+}
+
+impl TryFrom<TokenEntryRaw> for TokenEntry {
+    type Error = Error;
+
+    fn try_from(raw: TokenEntryRaw) -> Result<Self, Self::Error> {
+        let id = parse_token(&raw.token)?;
+        let parent = raw.parent.as_deref().map(parse_token).transpose()?;
+        let metadata = serde_json::from_str(&raw.metadata)
+            .map_err(|_| ErrorType::BadData("Invalid token metadata stored".to_string()))?;
+        let policies = serde_json::from_str(&raw.policies)
+            .map_err(|_| ErrorType::BadData("Invalid token policies stored".to_string()))?;
+        let group_policies = serde_json::from_str(&raw.group_policies)
+            .map_err(|_| ErrorType::BadData("Invalid token policies stored".to_string()))?;
+        Ok(Self {
+            id,
+            entity_name: raw.entity_name,
+            expires_at: raw.expires_at,
+            issued_at: raw.issued_at,
+            namespace_id: raw.namespace_id,
+            metadata,
+            policies,
+            group_policies,
+            renewable: raw.renewable,
+            parent,
+            accessor: raw.accessor,
+            not_before: raw.not_before,
+            num_uses: raw.num_uses,
+        })
+    }
+}
+
+/// A new random token accessor.
+#[must_use]
+pub fn new_accessor() -> String {
+    Uuid::new_v4().simple().to_string()
 }
 
 impl TokenEntry {
-    pub fn new(entity_name: String, ttl: Duration, namespace_id: String) -> Self {
-        let now = Utc::now();
+    pub fn new(
+        entity_name: String,
+        issued_at: DateTime<Utc>,
+        ttl: Duration,
+        namespace_id: String,
+        metadata: HashMap<String, String>,
+        policies: Vec<String>,
+        renewable: bool,
+    ) -> Self {
         Self {
             id: Token::new(),
             entity_name,
-            issued_at: now,
-            expires_at: Some(now + ttl),
+            issued_at,
+            expires_at: Some(issued_at + ttl),
             namespace_id,
+            metadata,
+            policies,
+            group_policies: vec![],
+            renewable,
+            parent: None,
+            accessor: new_accessor(),
+            not_before: None,
+            num_uses: None,
         }
     }
 
+    /// Grant the policies mapped from the groups of the user.
+    #[must_use]
+    pub fn with_group_policies(mut self, group_policies: Vec<String>) -> Self {
+        self.group_policies = group_policies;
+        self
+    }
+
+    /// Issue the token as a child of `parent`.
+    #[must_use]
+    pub fn with_parent(mut self, parent: Option<Token>) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    /// Only accept the token from `not_before` until the expiry and for
+    /// `num_uses` requests.
+    #[must_use]
+    pub fn with_limits(mut self, not_before: Option<DateTime<Utc>>, num_uses: Option<u32>) -> Self {
+        self.not_before = not_before;
+        self.num_uses = num_uses;
+        self
+    }
+
     pub fn id(&self) -> &Token {
         &self.id
     }
@@ -135,11 +614,14 @@ mod tests {
     use covert_types::{entity::Entity, policy::PathPolicy, request::Operation};
     use uuid::Uuid;
 
-    use crate::repos::{
-        entity::EntityRepo,
-        mount::tests::pool,
-        namespace::{Namespace, NamespaceRepo},
-        policy::PolicyRepo,
+    use crate::{
+        expiration_manager::clock::MockClock,
+        repos::{
+            entity::EntityRepo,
+            mount::tests::pool,
+            namespace::{Namespace, NamespaceRepo},
+            policy::PolicyRepo,
+        },
     };
 
     use super::*;
@@ -185,9 +667,22 @@ mod tests {
             .unwrap();
 
         // Now create token for "John"
-        let token = TokenEntry::new(entity.name().to_string(), Duration::hours(1), ns.id.clone());
+        let token = TokenEntry::new(
+            entity.name().to_string(),
+            Utc::now(),
+            Duration::hours(1),
+            ns.id.clone(),
+            HashMap::from([("username".to_string(), "john".to_string())]),
+            vec![],
+            true,
+        );
         assert!(store.create(&token).await.is_ok());
 
+        // Lookup the token entry
+        let entry = store.lookup(token.id()).await.unwrap().unwrap();
+        assert_eq!(entry.entity_name, "John");
+        assert_eq!(entry.metadata, token.metadata);
+
         // Lookup the attached policies for token
         assert_eq!(
             store.lookup_policies(token.id()).await.unwrap(),
@@ -197,14 +692,16 @@ mod tests {
         // Delete token
         assert!(store.remove(token.id(), &ns.id).await.unwrap());
 
-        // No policies should be returned for token after deletion
+        // No policies or entry should be returned for token after deletion
+        assert!(store.lookup(token.id()).await.unwrap().is_none());
         assert!(store.lookup_policies(token.id()).await.unwrap().is_empty());
     }
 
     #[tokio::test]
     async fn no_policies_for_expired_token() {
+        let clock = MockClock::new();
         let pool = Arc::new(pool().await);
-        let store = TokenRepo::new(Arc::clone(&pool));
+        let store = TokenRepo::new(Arc::clone(&pool)).with_clock(Arc::new(clock.clone()));
         let policy_repo = Arc::new(PolicyRepo::new(Arc::clone(&pool)));
         let entity_repo = EntityRepo::new(Arc::clone(&pool));
         let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
@@ -242,26 +739,339 @@ mod tests {
             .unwrap();
 
         // Now create token for "John"
-        let token = TokenEntry::new(entity.name().to_string(), Duration::hours(1), ns.id.clone());
+        let token = TokenEntry::new(
+            entity.name().to_string(),
+            clock.now(),
+            Duration::hours(1),
+            ns.id.clone(),
+            HashMap::from([("username".to_string(), "john".to_string())]),
+            vec![],
+            true,
+        )
+        .with_limits(Some(clock.now() + Duration::minutes(10)), None);
         assert!(store.create(&token).await.is_ok());
 
+        // Not valid yet
+        assert!(store.lookup_policies(token.id()).await.unwrap().is_empty());
+
         // Lookup the attached policies for token
+        clock.advance(Duration::minutes(10));
         assert_eq!(store.lookup_policies(token.id()).await.unwrap().len(), 2);
+        assert!(store.lookup(token.id()).await.unwrap().is_some());
 
-        // Hack to update the token to be expired
-        let update_resp = sqlx::query(
-            "UPDATE TOKENS SET
-            expires_at = ?
-            WHERE token = ?",
+        // Token no longer has any policies once it expires
+        clock.advance(Duration::minutes(50));
+        assert!(store.lookup_policies(token.id()).await.unwrap().is_empty());
+        assert!(store.lookup(token.id()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn policies_granted_by_auth_backend() {
+        let pool = Arc::new(pool().await);
+        let store = TokenRepo::new(Arc::clone(&pool));
+        let policy_repo = Arc::new(PolicyRepo::new(Arc::clone(&pool)));
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        let foo_policy = Policy::new(
+            "foo".into(),
+            vec![PathPolicy::new("foo/".into(), vec![Operation::Read])],
+            ns.id.clone(),
+        );
+        policy_repo.create(&foo_policy).await.unwrap();
+
+        // Entity without any policies attached
+        let entity = Entity::new("John".into(), ns.id.clone());
+        entity_repo.create(&entity).await.unwrap();
+
+        let token = TokenEntry::new(
+            entity.name().to_string(),
+            Utc::now(),
+            Duration::hours(1),
+            ns.id.clone(),
+            HashMap::new(),
+            vec!["foo".to_string(), "not-existing".to_string()],
+            true,
+        );
+        assert!(store.create(&token).await.is_ok());
+
+        // Only policies that exist are returned
+        assert_eq!(
+            store.lookup_policies(token.id()).await.unwrap(),
+            vec![foo_policy]
+        );
+        let tokens = store.list_by_policy("foo", &ns.id).await.unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].to_string(), token.id().to_string());
+        assert!(store
+            .list_by_policy("bar", &ns.id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn policy_sources() {
+        let pool = Arc::new(pool().await);
+        let store = TokenRepo::new(Arc::clone(&pool));
+        let policy_repo = Arc::new(PolicyRepo::new(Arc::clone(&pool)));
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+        for name in ["foo", "bar", DEFAULT_POLICY] {
+            let policy = Policy::new(
+                name.into(),
+                vec![PathPolicy::new(format!("{name}/"), vec![Operation::Read])],
+                ns.id.clone(),
+            );
+            policy_repo.create(&policy).await.unwrap();
+        }
+        let entity = Entity::new("John".into(), ns.id.clone());
+        entity_repo.create(&entity).await.unwrap();
+        entity_repo
+            .attach_policy(entity.name(), "foo", &ns.id)
+            .await
+            .unwrap();
+
+        let token = TokenEntry::new(
+            entity.name().to_string(),
+            Utc::now(),
+            Duration::hours(1),
+            ns.id.clone(),
+            HashMap::new(),
+            vec!["foo".to_string(), "not-existing".to_string()],
+            true,
         )
-        .bind(Utc::now() - Duration::hours(1))
-        .bind(token.id().to_string())
-        .execute(pool.as_ref())
-        .await
-        .unwrap();
-        assert_eq!(update_resp.rows_affected(), 1);
+        .with_group_policies(vec!["bar".to_string()]);
+        store.create(&token).await.unwrap();
 
-        // Token no longer has any policies
-        assert!(store.lookup_policies(token.id()).await.unwrap().is_empty());
+        assert_eq!(
+            store.lookup_policy_sources(token.id()).await.unwrap(),
+            BTreeMap::from([
+                ("bar".to_string(), vec![PolicySource::Group]),
+                (DEFAULT_POLICY.to_string(), vec![PolicySource::Default]),
+                (
+                    "foo".to_string(),
+                    vec![PolicySource::Role, PolicySource::Entity]
+                ),
+            ])
+        );
+        let names = store
+            .lookup_policies(token.id())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|policy| policy.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["bar", DEFAULT_POLICY, "foo"]);
+        assert_eq!(store.list_by_policy("bar", &ns.id).await.unwrap().len(), 1);
+        assert_eq!(
+            store
+                .list_by_policy(DEFAULT_POLICY, &ns.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn alias_metadata_limits() {
+        let metadata = HashMap::from([("username".to_string(), "john".to_string())]);
+        assert!(validate_alias_metadata(&metadata).is_ok());
+
+        let metadata = HashMap::from([("k".repeat(MAX_ALIAS_METADATA_KEY_LEN + 1), String::new())]);
+        assert!(validate_alias_metadata(&metadata).is_err());
+
+        let metadata = HashMap::from([(
+            "key".to_string(),
+            "v".repeat(MAX_ALIAS_METADATA_VALUE_LEN + 1),
+        )]);
+        assert!(validate_alias_metadata(&metadata).is_err());
+
+        let metadata = (0..=MAX_ALIAS_METADATA_ENTRIES)
+            .map(|i| (i.to_string(), String::new()))
+            .collect();
+        assert!(validate_alias_metadata(&metadata).is_err());
+    }
+
+    #[tokio::test]
+    async fn policy_limits() {
+        let pool = Arc::new(pool().await);
+        let store = TokenRepo::new(Arc::clone(&pool)).with_policy_limits(PolicyLimitsConfig {
+            max_policies_per_token: 2,
+            max_policies_per_entity: 64,
+            max_policy_name_length: 8,
+        });
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+        let entity = Entity::new("John".into(), ns.id.clone());
+        entity_repo.create(&entity).await.unwrap();
+
+        let token = |policies: &[&str]| {
+            TokenEntry::new(
+                entity.name().to_string(),
+                Utc::now(),
+                Duration::hours(1),
+                ns.id.clone(),
+                HashMap::new(),
+                policies.iter().map(ToString::to_string).collect(),
+                true,
+            )
+        };
+        assert!(store.create(&token(&["foo", "bar"])).await.is_ok());
+        for policies in [&["foo", "bar", "baz"][..], &["too-long-name"]] {
+            let err = store.create(&token(policies)).await.unwrap_err();
+            assert!(matches!(err.variant, ErrorType::BadRequest(_)), "{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn mark_revoking_tree() {
+        let pool = Arc::new(pool().await);
+        let store = TokenRepo::new(Arc::clone(&pool));
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+        let entity = Entity::new("John".into(), ns.id.clone());
+        entity_repo.create(&entity).await.unwrap();
+
+        let token = |parent: Option<&TokenEntry>| {
+            TokenEntry::new(
+                entity.name().to_string(),
+                Utc::now(),
+                Duration::hours(1),
+                ns.id.clone(),
+                HashMap::new(),
+                vec![],
+                true,
+            )
+            .with_parent(parent.map(|parent| parent.id().clone()))
+        };
+        let root = token(None);
+        let child = token(Some(&root));
+        let grandchild = token(Some(&child));
+        let other = token(None);
+        for te in [&root, &child, &grandchild, &other] {
+            store.create(te).await.unwrap();
+        }
+        assert_eq!(
+            store.lookup(grandchild.id()).await.unwrap().unwrap().parent,
+            Some(child.id().clone())
+        );
+
+        // Children come before their parents
+        let tree = store.mark_revoking(child.id(), &ns.id).await.unwrap();
+        assert_eq!(
+            tree,
+            vec![
+                (grandchild.id().clone(), ns.id.clone()),
+                (child.id().clone(), ns.id.clone())
+            ]
+        );
+
+        // Marked tokens can't be used or get new children
+        assert!(store.lookup(child.id()).await.unwrap().is_none());
+        assert!(store.lookup(grandchild.id()).await.unwrap().is_none());
+        assert!(store.lookup(root.id()).await.unwrap().is_some());
+        let err = store.create(&token(Some(&child))).await.unwrap_err();
+        assert!(matches!(err.variant, ErrorType::Unauthorized(_)), "{err}");
+
+        // Only the top of the interrupted revocation is listed
+        assert_eq!(
+            store.list_revoking().await.unwrap(),
+            vec![(child.id().clone(), ns.id.clone())]
+        );
+        store.remove(grandchild.id(), &ns.id).await.unwrap();
+        store.remove(child.id(), &ns.id).await.unwrap();
+        assert!(store.list_revoking().await.unwrap().is_empty());
+        assert!(store.lookup(other.id()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn orphan_children_of_entity() {
+        let pool = Arc::new(pool().await);
+        let store = TokenRepo::new(Arc::clone(&pool));
+        let entity_repo = EntityRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+        for name in ["John", "Jane"] {
+            let entity = Entity::new(name.into(), ns.id.clone());
+            entity_repo.create(&entity).await.unwrap();
+        }
+
+        let token = |entity_name: &str, parent: Option<&TokenEntry>| {
+            TokenEntry::new(
+                entity_name.to_string(),
+                Utc::now(),
+                Duration::hours(1),
+                ns.id.clone(),
+                HashMap::new(),
+                vec![],
+                true,
+            )
+            .with_parent(parent.map(|parent| parent.id().clone()))
+        };
+        let john = token("John", None);
+        let john_child = token("John", Some(&john));
+        let jane_child = token("Jane", Some(&john));
+        for te in [&john, &john_child, &jane_child] {
+            store.create(te).await.unwrap();
+        }
+
+        let mut tokens = store.list_by_entity("John", &ns.id).await.unwrap();
+        tokens.sort_by_key(ToString::to_string);
+        let mut expected = vec![john.id().clone(), john_child.id().clone()];
+        expected.sort_by_key(ToString::to_string);
+        assert_eq!(tokens, expected);
+
+        // Only the children of other entities are detached
+        assert_eq!(store.orphan_children("John", &ns.id).await.unwrap(), 1);
+        assert_eq!(
+            store.lookup(jane_child.id()).await.unwrap().unwrap().parent,
+            None
+        );
+        assert_eq!(
+            store.lookup(john_child.id()).await.unwrap().unwrap().parent,
+            Some(john.id().clone())
+        );
+
+        // Deleting the entity removes its tokens
+        assert!(entity_repo.delete("John", &ns.id).await.unwrap());
+        assert!(!entity_repo.delete("John", &ns.id).await.unwrap());
+        assert!(store.lookup(john.id()).await.unwrap().is_none());
+        assert!(store.lookup(jane_child.id()).await.unwrap().is_some());
     }
 }
@@ -0,0 +1,337 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use covert_storage::EncryptedPool;
+use covert_types::token::WrappingToken;
+use serde_json::Value;
+use sqlx::{Sqlite, Transaction};
+
+use crate::error::{Error, ErrorType};
+
+#[derive(Debug, sqlx::FromRow)]
+struct WrappingEntryRaw {
+    creation_path: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    response: Option<String>,
+    unwrapped_at: Option<DateTime<Utc>>,
+}
+
+/// A response wrapped in a single-use wrapping token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappingEntry {
+    pub token: WrappingToken,
+    pub creation_path: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl WrappingEntry {
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.expires_at - self.created_at
+    }
+}
+
+pub struct WrappingRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for WrappingRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl WrappingRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Store the response under a new wrapping token.
+    #[tracing::instrument(skip(self, response))]
+    pub async fn wrap(
+        &self,
+        response: &Value,
+        creation_path: &str,
+        ttl: Duration,
+        namespace_id: &str,
+    ) -> Result<WrappingEntry, Error> {
+        let mut tx = self.pool.begin().await?;
+        let entry = insert(&mut tx, response, creation_path, ttl, namespace_id).await?;
+        tx.commit().await?;
+        Ok(entry)
+    }
+
+    /// Lookup a wrapping token without consuming it.
+    #[tracing::instrument(skip_all)]
+    pub async fn lookup(
+        &self,
+        token: &WrappingToken,
+        namespace_id: &str,
+    ) -> Result<WrappingEntry, Error> {
+        let mut tx = self.pool.begin().await?;
+        let (entry, _) = fetch_valid(&mut tx, token, namespace_id).await?;
+        Ok(entry)
+    }
+
+    /// Return the wrapped response and invalidate the wrapping token.
+    #[tracing::instrument(skip_all)]
+    pub async fn unwrap(&self, token: &WrappingToken, namespace_id: &str) -> Result<Value, Error> {
+        let mut tx = self.pool.begin().await?;
+        let (_, response) = take(&mut tx, token, namespace_id).await?;
+        tx.commit().await?;
+        Ok(response)
+    }
+
+    /// Move the wrapped response to a new wrapping token with the same TTL and
+    /// invalidate the old token.
+    #[tracing::instrument(skip_all)]
+    pub async fn rewrap(
+        &self,
+        token: &WrappingToken,
+        namespace_id: &str,
+    ) -> Result<WrappingEntry, Error> {
+        let mut tx = self.pool.begin().await?;
+        let (entry, response) = take(&mut tx, token, namespace_id).await?;
+        let entry = insert(
+            &mut tx,
+            &response,
+            &entry.creation_path,
+            entry.ttl(),
+            namespace_id,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(entry)
+    }
+}
+
+async fn insert(
+    tx: &mut Transaction<'static, Sqlite>,
+    response: &Value,
+    creation_path: &str,
+    ttl: Duration,
+    namespace_id: &str,
+) -> Result<WrappingEntry, Error> {
+    let now = Utc::now();
+
+    // Tokens are kept until they expire to detect repeated unwrap attempts
+    sqlx::query("DELETE FROM WRAPPING_TOKENS WHERE expires_at <= ?")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = WrappingEntry {
+        token: WrappingToken::new(),
+        creation_path: creation_path.to_string(),
+        created_at: now,
+        expires_at: now + ttl,
+    };
+    let response = serde_json::to_string(response).map_err(ErrorType::BadResponseData)?;
+    sqlx::query(
+        "INSERT INTO WRAPPING_TOKENS (token, namespace_id, creation_path, created_at, expires_at, response)
+        VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(entry.token.to_string())
+    .bind(namespace_id)
+    .bind(&entry.creation_path)
+    .bind(entry.created_at)
+    .bind(entry.expires_at)
+    .bind(response)
+    .execute(&mut *tx)
+    .await?;
+    Ok(entry)
+}
+
+/// Fetch a wrapping token that has not expired or been unwrapped.
+async fn fetch_valid(
+    tx: &mut Transaction<'static, Sqlite>,
+    token: &WrappingToken,
+    namespace_id: &str,
+) -> Result<(WrappingEntry, String), Error> {
+    let raw: WrappingEntryRaw = sqlx::query_as(
+        "SELECT creation_path, created_at, expires_at, response, unwrapped_at
+        FROM WRAPPING_TOKENS WHERE token = ? AND namespace_id = ?",
+    )
+    .bind(token.to_string())
+    .bind(namespace_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ErrorType::InvalidWrappingToken)?;
+
+    if let Some(unwrapped_at) = raw.unwrapped_at {
+        tracing::warn!(
+            creation_path = raw.creation_path,
+            %unwrapped_at,
+            "Attempt to unwrap an already unwrapped wrapping token"
+        );
+        return Err(ErrorType::WrappingTokenAlreadyUnwrapped { unwrapped_at }.into());
+    }
+    if raw.expires_at <= Utc::now() {
+        return Err(ErrorType::WrappingTokenExpired {
+            expired_at: raw.expires_at,
+        }
+        .into());
+    }
+    let response = raw.response.ok_or(ErrorType::InvalidWrappingToken)?;
+
+    Ok((
+        WrappingEntry {
+            token: token.clone(),
+            creation_path: raw.creation_path,
+            created_at: raw.created_at,
+            expires_at: raw.expires_at,
+        },
+        response,
+    ))
+}
+
+/// Consume the wrapping token and return the wrapped response. The token is
+/// checked and invalidated by a single statement so concurrent attempts to
+/// unwrap it cannot both succeed.
+async fn take(
+    tx: &mut Transaction<'static, Sqlite>,
+    token: &WrappingToken,
+    namespace_id: &str,
+) -> Result<(WrappingEntry, Value), Error> {
+    let now = Utc::now();
+    let raw: Option<WrappingEntryRaw> = sqlx::query_as(
+        "UPDATE WRAPPING_TOKENS SET response = NULL, unwrapped_at = ?
+        WHERE token = ? AND namespace_id = ? AND unwrapped_at IS NULL AND expires_at > ?
+        RETURNING creation_path, created_at, expires_at, response, unwrapped_at",
+    )
+    .bind(now)
+    .bind(token.to_string())
+    .bind(namespace_id)
+    .bind(now)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(raw) = raw else {
+        // Tell why the token cannot be unwrapped
+        fetch_valid(tx, token, namespace_id).await?;
+        return Err(ErrorType::InvalidWrappingToken.into());
+    };
+
+    let response = raw.response.ok_or(ErrorType::InvalidWrappingToken)?;
+    let response = serde_json::from_str(&response)
+        .map_err(|_| ErrorType::BadData("Invalid wrapped response stored".to_string()))?;
+    let entry = WrappingEntry {
+        token: token.clone(),
+        creation_path: raw.creation_path,
+        created_at: raw.created_at,
+        expires_at: raw.expires_at,
+    };
+    Ok((entry, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::repos::{
+        mount::tests::pool,
+        namespace::{Namespace, NamespaceRepo},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn wrap_and_unwrap_once() {
+        let pool = Arc::new(pool().await);
+        let wrapping_repo = WrappingRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        let response = json!({ "password": "secret" });
+        let entry = wrapping_repo
+            .wrap(&response, "psql/creds/foo", Duration::minutes(5), &ns.id)
+            .await
+            .unwrap();
+        assert_eq!(entry.ttl(), Duration::minutes(5));
+
+        // Lookup does not consume the token
+        assert_eq!(
+            wrapping_repo.lookup(&entry.token, &ns.id).await.unwrap(),
+            entry
+        );
+
+        // Rewrap invalidates the old token
+        let rewrapped = wrapping_repo.rewrap(&entry.token, &ns.id).await.unwrap();
+        assert_eq!(rewrapped.creation_path, "psql/creds/foo");
+        assert_eq!(rewrapped.ttl(), Duration::minutes(5));
+        assert!(matches!(
+            wrapping_repo
+                .unwrap(&entry.token, &ns.id)
+                .await
+                .map_err(|e| e.variant),
+            Err(ErrorType::WrappingTokenAlreadyUnwrapped { .. })
+        ));
+
+        // Tokens are scoped to the namespace
+        assert!(matches!(
+            wrapping_repo
+                .unwrap(&rewrapped.token, "other")
+                .await
+                .map_err(|e| e.variant),
+            Err(ErrorType::InvalidWrappingToken)
+        ));
+
+        assert_eq!(
+            wrapping_repo
+                .unwrap(&rewrapped.token, &ns.id)
+                .await
+                .unwrap(),
+            response
+        );
+        assert!(matches!(
+            wrapping_repo
+                .unwrap(&rewrapped.token, &ns.id)
+                .await
+                .map_err(|e| e.variant),
+            Err(ErrorType::WrappingTokenAlreadyUnwrapped { .. })
+        ));
+        assert!(matches!(
+            wrapping_repo
+                .lookup(&WrappingToken::new(), &ns.id)
+                .await
+                .map_err(|e| e.variant),
+            Err(ErrorType::InvalidWrappingToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_cannot_be_unwrapped() {
+        let pool = Arc::new(pool().await);
+        let wrapping_repo = WrappingRepo::new(Arc::clone(&pool));
+        let ns_repo = NamespaceRepo::new(Arc::clone(&pool));
+
+        let ns = Namespace {
+            id: Uuid::new_v4().to_string(),
+            name: "root".to_string(),
+            parent_namespace_id: None,
+        };
+        ns_repo.create(&ns).await.unwrap();
+
+        let entry = wrapping_repo
+            .wrap(&json!("foo"), "kv/foo", Duration::seconds(-1), &ns.id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            wrapping_repo
+                .unwrap(&entry.token, &ns.id)
+                .await
+                .map_err(|e| e.variant),
+            Err(ErrorType::WrappingTokenExpired { .. })
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use covert_storage::EncryptedPool;
+
+use crate::error::Error;
+
+pub const WRITE_INDEX_TABLE: &str = "WRITE_INDEX";
+
+pub struct WriteIndexRepo {
+    pool: Arc<EncryptedPool>,
+}
+
+impl Clone for WriteIndexRepo {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl WriteIndexRepo {
+    pub fn new(pool: Arc<EncryptedPool>) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn current(&self) -> Result<u64, Error> {
+        let index: i64 = sqlx::query_scalar(&format!(
+            "SELECT \"index\" FROM {WRITE_INDEX_TABLE} WHERE lock = 1"
+        ))
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        Ok(u64::try_from(index).unwrap_or_default())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn increment(&self) -> Result<u64, Error> {
+        let index: i64 = sqlx::query_scalar(&format!(
+            "UPDATE {WRITE_INDEX_TABLE} SET \"index\" = \"index\" + 1 WHERE lock = 1
+                RETURNING \"index\""
+        ))
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        Ok(u64::try_from(index).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repos::mount::tests::pool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn increment_write_index() {
+        let pool = Arc::new(pool().await);
+        let repo = WriteIndexRepo::new(pool);
+
+        assert_eq!(repo.current().await.unwrap(), 0);
+        assert_eq!(repo.increment().await.unwrap(), 1);
+        assert_eq!(repo.increment().await.unwrap(), 2);
+        assert_eq!(repo.current().await.unwrap(), 2);
+    }
+}
@@ -1,13 +1,21 @@
 use covert_types::{error::ApiError, mount::MountConfig, response::Response};
-use hyper::{header::CONTENT_TYPE, Body, StatusCode};
+use hyper::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    Body, StatusCode,
+};
 use serde::Serialize;
 
-use crate::error::{Error, ErrorType};
+use crate::{
+    error::{Error, ErrorType},
+    layer::consistency::INDEX_HEADER,
+};
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Default)]
 pub struct ResponseContext {
     pub backend_mount_path: String,
     pub backend_config: MountConfig,
+    /// Storage write index produced by a mutating request.
+    pub index: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -20,16 +28,88 @@ pub struct ResponseWithCtx {
 
 impl From<ResponseWithCtx> for hyper::Response<Body> {
     fn from(resp: ResponseWithCtx) -> Self {
-        match serde_json::to_vec(&resp) {
-            Ok(body) => match hyper::Response::builder()
-                .status(StatusCode::OK)
-                .header(CONTENT_TYPE, "application/json")
-                .body(body.into())
-            {
-                Ok(resp) => resp,
-                Err(err) => ApiError::from(Error::from(ErrorType::BadHttpResponseData(err))).into(),
+        let index = resp.ctx.index;
+        let (content_type, length, body) = match resp.response {
+            Response::Bytes(raw) => (
+                raw.content_type,
+                Some(raw.body.len() as u64),
+                Body::from(raw.body),
+            ),
+            // The stream is forwarded to the client as it is produced
+            Response::Stream(stream) => (
+                stream.content_type,
+                stream.length,
+                Body::wrap_stream(stream.body),
+            ),
+            response => match serde_json::to_vec(&ResponseWithCtx { response, ..resp }) {
+                Ok(body) => ("application/json".to_string(), None, Body::from(body)),
+                Err(err) => {
+                    return ApiError::from(Error::from(ErrorType::BadResponseData(err))).into()
+                }
             },
-            Err(err) => ApiError::from(Error::from(ErrorType::BadResponseData(err))).into(),
+        };
+
+        let mut builder = hyper::Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type);
+        if let Some(length) = length {
+            builder = builder.header(CONTENT_LENGTH, length);
+        }
+        if let Some(index) = index {
+            builder = builder.header(INDEX_HEADER, index);
+        }
+        match builder.body(body) {
+            Ok(resp) => resp,
+            Err(err) => ApiError::from(Error::from(ErrorType::BadHttpResponseData(err))).into(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::stream;
+
+    use super::*;
+
+    async fn body(resp: ResponseWithCtx) -> (hyper::Response<Body>, Bytes) {
+        let mut resp = hyper::Response::<Body>::from(resp);
+        let body = hyper::body::to_bytes(resp.body_mut()).await.unwrap();
+        (resp, body)
+    }
+
+    #[tokio::test]
+    async fn binary_responses() {
+        let (resp, data) = body(ResponseWithCtx {
+            response: Response::bytes("application/pkix-crl", vec![1, 2, 3]),
+            ctx: ResponseContext::default(),
+        })
+        .await;
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/pkix-crl");
+        assert_eq!(resp.headers()[CONTENT_LENGTH], "3");
+        assert_eq!(&data[..], &[1, 2, 3]);
+
+        let chunks = vec![Ok(Bytes::from("foo")), Ok(Bytes::from("bar"))];
+        let (resp, data) = body(ResponseWithCtx {
+            response: Response::stream(
+                "application/octet-stream",
+                None,
+                Box::pin(stream::iter(chunks)),
+            ),
+            ctx: ResponseContext::default(),
+        })
+        .await;
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/octet-stream");
+        assert!(resp.headers().get(CONTENT_LENGTH).is_none());
+        assert_eq!(&data[..], b"foobar");
+
+        // JSON responses are wrapped in the data field
+        let (resp, data) = body(ResponseWithCtx {
+            response: Response::raw("foo").unwrap(),
+            ctx: ResponseContext::default(),
+        })
+        .await;
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(&data[..], br#"{"data":"foo"}"#);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+use covert_types::{
+    auth::AuthPolicy,
+    methods::system::{RotationHandler, StaticSecret},
+    request::{Operation, Request},
+    response::Response,
+    state::StorageState,
+};
+use hyper::http;
+use rand::{distributions::Alphanumeric, Rng};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    repos::{
+        namespace::Namespace,
+        static_secret::{after, StaticSecretEntry},
+    },
+};
+
+/// How often the server looks for static secrets that are due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Wait before a failed rotation is tried again.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Most secrets rotated per check.
+const BATCH_SIZE: u32 = 100;
+
+/// Rotations are done one at a time. A value replaced by a backend, e.g. a
+/// database password, must never be lost to a concurrent rotation.
+static ROTATION: Mutex<()> = Mutex::const_new(());
+
+/// Ask the handler for a new value.
+async fn new_value(
+    ctx: &Context,
+    ns: &Namespace,
+    name: &str,
+    handler: &RotationHandler,
+) -> Result<Value, Error> {
+    let path = match handler {
+        RotationHandler::Random { length } => {
+            let value: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(*length)
+                .map(char::from)
+                .collect();
+            return Ok(json!({ "value": value }));
+        }
+        RotationHandler::Backend { path } => path,
+    };
+
+    let ns_path = ctx.repos.namespace.get_full_path(&ns.id).await?;
+    let mut extensions = http::Extensions::new();
+    extensions.insert(AuthPolicy::Authenticated);
+    extensions.insert(StorageState::Unsealed);
+    extensions.insert(ns.clone());
+    let req = Request {
+        id: Uuid::new_v4(),
+        namespace: ns_path.split('/').map(From::from).collect(),
+        operation: Operation::Update,
+        path: path.clone(),
+        data: "{}".into(),
+        extensions,
+        token: None,
+        params: Vec::default(),
+        query_string: String::default(),
+        headers: HashMap::default(),
+    };
+    let failed = |message: String| ErrorType::StaticSecretRotation {
+        name: name.to_string(),
+        message,
+    };
+    match ctx.router.route(req).await {
+        Ok(resp) => match resp.response {
+            Response::Raw(value) => Ok(value),
+            // Leases would never be registered and secrets never revoked
+            _ => Err(failed(format!("`{path}` did not return plain data")).into()),
+        },
+        Err(error) => Err(failed(error.error.to_string()).into()),
+    }
+}
+
+/// Create the static secret with a first value from its handler. Returns
+/// `None` if a secret with the same name exists.
+pub async fn create(
+    ctx: &Context,
+    ns: &Namespace,
+    secret: StaticSecret,
+) -> Result<Option<StaticSecretEntry>, Error> {
+    let _guard = ROTATION.lock().await;
+    if ctx
+        .repos
+        .static_secret
+        .lookup(&ns.id, &secret.name)
+        .await?
+        .is_some()
+    {
+        return Ok(None);
+    }
+    let value = new_value(ctx, ns, &secret.name, &secret.handler).await?;
+    let now = Utc::now();
+    let secret = StaticSecret {
+        version: 1,
+        rotated_at: now,
+        next_rotation_at: after(now, secret.interval),
+        ..secret
+    };
+    if !ctx
+        .repos
+        .static_secret
+        .create(&ns.id, &secret, &value)
+        .await?
+    {
+        return Ok(None);
+    }
+    Ok(Some(StaticSecretEntry {
+        namespace_id: ns.id.clone(),
+        secret,
+        current: value,
+        previous: None,
+    }))
+}
+
+/// Replace the value of the secret with a new value from its handler. With
+/// `version` the secret is only rotated if it is still at that version, e.g.
+/// when it was found due before. Returns `None` if the secret was removed or
+/// already rotated.
+pub async fn rotate(
+    ctx: &Context,
+    namespace_id: &str,
+    name: &str,
+    version: Option<u64>,
+) -> Result<Option<StaticSecretEntry>, Error> {
+    let _guard = ROTATION.lock().await;
+    let Some(entry) = ctx.repos.static_secret.lookup(namespace_id, name).await? else {
+        return Ok(None);
+    };
+    if version.is_some_and(|version| version != entry.secret.version) {
+        return Ok(None);
+    }
+    let ns = ctx
+        .repos
+        .namespace
+        .lookup(namespace_id)
+        .await?
+        .ok_or_else(|| ErrorType::InternalError(anyhow::Error::msg("Missing namespace")))?;
+    let value = new_value(ctx, &ns, name, &entry.secret.handler).await?;
+    ctx.repos
+        .static_secret
+        .rotate(&entry, &value, Utc::now())
+        .await
+}
+
+/// Rotate the static secrets that are due. Only the active node rotates them,
+/// while it is unsealed. A failed rotation is tried again after a delay, the
+/// current value stays valid in the meantime.
+pub async fn rotate_static_secrets(ctx: Context) {
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        if ctx.repos.pool.state() != StorageState::Unsealed || !ctx.ha.is_active() {
+            continue;
+        }
+        let due = match ctx.repos.static_secret.due(Utc::now(), BATCH_SIZE).await {
+            Ok(due) => due,
+            Err(error) => {
+                error!(?error, "Failed to look up the static secrets to rotate");
+                continue;
+            }
+        };
+        for entry in due {
+            let name = &entry.secret.name;
+            let res = rotate(&ctx, &entry.namespace_id, name, Some(entry.secret.version)).await;
+            match res {
+                Ok(Some(rotated)) => info!(
+                    %name,
+                    version = rotated.secret.version,
+                    "Rotated static secret"
+                ),
+                Ok(None) => (),
+                Err(error) => {
+                    error!(?error, %name, "Failed to rotate static secret");
+                    let retry_at = after(Utc::now(), RETRY_DELAY);
+                    if let Err(error) = ctx
+                        .repos
+                        .static_secret
+                        .postpone(&entry.namespace_id, name, retry_at)
+                        .await
+                    {
+                        error!(
+                            ?error,
+                            %name,
+                            "Failed to postpone the rotation of static secret"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
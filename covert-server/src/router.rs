@@ -1,24 +1,72 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use covert_framework::Backend;
-use covert_types::{error::ApiError, mount::MountConfig, request::Request};
+use covert_types::{
+    auth::AuthPolicy,
+    backend::BackendType,
+    error::{ApiError, ErrorCode},
+    mount::MountConfig,
+    request::{Operation, Request},
+};
 use dashmap::DashMap;
 use futures::future::BoxFuture;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tower::Service;
 use uuid::Uuid;
 
 use crate::{
+    config::PathDisclosure,
     error::{Error, ErrorType},
+    field_encryption::FieldEncryption,
+    layer::auth_service::TokenPolicies,
+    metrics::RequestMetrics,
     repos::{mount::MountRepo, namespace::Namespace},
     response::{ResponseContext, ResponseWithCtx},
     system::SYSTEM_MOUNT_PATH,
 };
 
+/// Upper bound on the number of mounts suggested when no mount matches.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Maximum edit distance between the requested and a suggested mount path.
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(current)
+            };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Path one segment up, with a trailing slash. The parent of a top level
+/// path is the empty path.
+fn parent_path(path: &str) -> &str {
+    let path = path.strip_suffix('/').unwrap_or(path);
+    path.rfind('/').map_or("", |i| &path[..=i])
+}
+
 /// Router is used to do prefix based routing of a request to a logical backend
 pub struct Router {
     // mount id -> Backend
     backend_lookup: DashMap<String, Arc<Backend>>,
+    // mount id -> concurrency limit of the mount and its permits
+    mount_limits: DashMap<Uuid, (u32, Arc<Semaphore>)>,
     mount_repo: MountRepo,
+    metrics: RequestMetrics,
+    path_disclosure: PathDisclosure,
 }
 
 impl Router {
@@ -26,10 +74,24 @@ impl Router {
     pub fn new(mount_repo: MountRepo) -> Self {
         Router {
             backend_lookup: DashMap::default(),
+            mount_limits: DashMap::default(),
             mount_repo,
+            metrics: RequestMetrics::default(),
+            path_disclosure: PathDisclosure::default(),
         }
     }
 
+    #[must_use]
+    pub fn with_path_disclosure(mut self, path_disclosure: PathDisclosure) -> Self {
+        self.path_disclosure = path_disclosure;
+        self
+    }
+
+    /// Counts and durations of the requests handled by the mounts.
+    pub(crate) fn metrics(&self) -> &RequestMetrics {
+        &self.metrics
+    }
+
     #[tracing::instrument(
         skip(self, req),
         fields(
@@ -38,7 +100,8 @@ impl Router {
         )
     )]
     pub async fn route(&self, mut req: Request) -> Result<ResponseWithCtx, ApiError> {
-        let (backend, path, config) = match req.extensions.get::<Namespace>() {
+        let conceal = self.conceals(&req);
+        let (backend, path, config, _permit) = match req.extensions.get::<Namespace>() {
             Some(_) if req.path.starts_with(SYSTEM_MOUNT_PATH) => {
                 let backend = self
                     .get_system_mount()
@@ -48,25 +111,41 @@ impl Router {
                     backend,
                     SYSTEM_MOUNT_PATH.to_string(),
                     MountConfig::default(),
+                    None,
                 )
             }
             Some(ns) => {
-                let mount = self
-                    .mount_repo
-                    .longest_prefix(&req.path, &ns.id)
-                    .await?
-                    .ok_or_else(|| {
-                        Error::from(ErrorType::MountNotFound {
-                            path: req.path.clone(),
-                        })
-                    })?;
+                let Some(mount) = self.mount_repo.longest_prefix(&req.path, &ns.id).await? else {
+                    if conceal {
+                        return Err(ApiError::unauthorized());
+                    }
+                    let suggestions = self.suggest_mounts(&req, &ns.id).await?;
+                    return Err(Error::from(ErrorType::NoMountForPath {
+                        path: req.path.clone(),
+                        suggestions,
+                    })
+                    .into());
+                };
                 let backend = self
                     .backend_lookup
                     .get(&mount.id.to_string())
                     .map(|b| Arc::clone(&b))
                     .ok_or_else(ApiError::internal_error)?;
 
-                (backend, mount.path, mount.config)
+                // The mount path without the trailing slash is the root of
+                // the mount
+                if mount.path.strip_suffix('/') == Some(req.path.as_str()) {
+                    req.path.push('/');
+                }
+                let permit = match mount.config.max_concurrent_requests {
+                    Some(limit) => Some(
+                        self.mount_permit(mount.id, limit)
+                            .ok_or_else(ApiError::server_busy)?,
+                    ),
+                    None => None,
+                };
+
+                (backend, mount.path, mount.config, permit)
             }
             // Namespace can be null if not unsealed
             None => {
@@ -83,13 +162,21 @@ impl Router {
                     backend,
                     SYSTEM_MOUNT_PATH.to_string(),
                     MountConfig::default(),
+                    None,
                 )
             }
         };
 
+        let namespace = req.namespace.join("/");
+        let operation = req.operation;
         req.advance_path(&path);
         req.extensions.insert(config.clone());
 
+        let field_encryption = FieldEncryption::new(backend.variant(), &path, &config, &req);
+        if let Some(field_encryption) = &field_encryption {
+            field_encryption.encrypt_request(self, &mut req).await?;
+        }
+
         let span = tracing::span!(
             tracing::Level::DEBUG,
             "backend_handle_request",
@@ -98,13 +185,150 @@ impl Router {
         );
         let _enter = span.enter();
 
-        backend.handle_request(req).await.map(|response| {
+        let start = Instant::now();
+        let mut res = backend.handle_request(req).await;
+        self.metrics
+            .record(&namespace, &path, operation, start.elapsed(), res.is_ok());
+        if let (Ok(response), Some(field_encryption)) = (&mut res, &field_encryption) {
+            field_encryption.decrypt_response(self, response).await?;
+        }
+
+        res.map(|response| {
             let ctx = ResponseContext {
                 backend_config: config,
                 backend_mount_path: path,
+                index: None,
             };
             ResponseWithCtx { response, ctx }
         })
+        .map_err(|err| {
+            // A missing route, an unsupported operation and a denied request
+            // look the same
+            let reveals_path = matches!(
+                err.code,
+                ErrorCode::NotFound | ErrorCode::MethodNotAllowed | ErrorCode::PermissionDenied
+            );
+            if conceal && reveals_path {
+                ApiError::unauthorized()
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Route a request the server makes on its own while it handles another
+    /// request, e.g. to encrypt the fields of a secret with a transit mount.
+    pub(crate) fn route_internal(
+        &self,
+        req: Request,
+    ) -> BoxFuture<'_, Result<ResponseWithCtx, ApiError>> {
+        Box::pin(self.route(req))
+    }
+
+    /// Type of the backend mounted at the longest prefix of the path.
+    pub(crate) async fn backend_type(
+        &self,
+        path: &str,
+        namespace_id: &str,
+    ) -> Result<Option<BackendType>, Error> {
+        Ok(self
+            .mount_repo
+            .longest_prefix(path, namespace_id)
+            .await?
+            .map(|mount| mount.backend_type))
+    }
+
+    /// A permit to handle a request with the mount, if it handles less than
+    /// `limit` requests. A new limit applies to the requests received after
+    /// it was changed.
+    fn mount_permit(&self, mount_id: Uuid, limit: u32) -> Option<OwnedSemaphorePermit> {
+        let semaphore = {
+            let mut entry = self
+                .mount_limits
+                .entry(mount_id)
+                .or_insert_with(|| (limit, Arc::new(Semaphore::new(limit as usize))));
+            if entry.0 != limit {
+                *entry = (limit, Arc::new(Semaphore::new(limit as usize)));
+            }
+            Arc::clone(&entry.1)
+        };
+        semaphore.try_acquire_owned().ok()
+    }
+
+    /// Whether the caller must not learn if the path of the request exists
+    /// with [`PathDisclosure::Conceal`]. The decision only depends on the
+    /// policies of the token, so it is made before the mount is looked up.
+    /// Callers allowed to perform the operation on the path or to list its
+    /// parent path can tell a missing path from a forbidden one.
+    fn conceals(&self, req: &Request) -> bool {
+        if self.path_disclosure == PathDisclosure::Reveal
+            || req.extensions.get::<Namespace>().is_none()
+        {
+            return false;
+        }
+        if matches!(
+            req.extensions.get::<AuthPolicy>(),
+            Some(AuthPolicy::Authenticated | AuthPolicy::Sudo)
+        ) {
+            return false;
+        }
+        let Some(policies) = req.extensions.get::<TokenPolicies>() else {
+            return true;
+        };
+        let parent = format!("{}/{}", req.namespace.join("/"), parent_path(&req.path));
+        !policies.allow(&parent, Operation::List)
+    }
+
+    /// Mounts with a path close to the requested path, or all mounts if the
+    /// root path was requested. Only mounts the token of the request has
+    /// access to are suggested so the error does not reveal the existence of
+    /// any other mounts.
+    async fn suggest_mounts(
+        &self,
+        req: &Request,
+        namespace_id: &str,
+    ) -> Result<Vec<String>, Error> {
+        let Some(TokenPolicies(policies)) = req.extensions.get::<TokenPolicies>() else {
+            return Ok(vec![]);
+        };
+        if policies.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let namespace_prefix = req.namespace.join("/");
+        let mut suggestions = self
+            .mount_repo
+            .list(namespace_id)
+            .await?
+            .into_iter()
+            .filter(|mount| {
+                let path = format!("{namespace_prefix}/{}", mount.path);
+                policies
+                    .iter()
+                    .any(|policy| policy.can_access_prefix(&path))
+            })
+            .filter_map(|mount| {
+                if req.path.is_empty() {
+                    return Some((0, mount.path));
+                }
+                // Compare with as many segments of the request as the mount
+                // path has.
+                let segments = mount.path.matches('/').count();
+                let requested = req
+                    .path
+                    .split_inclusive('/')
+                    .take(segments)
+                    .collect::<String>();
+                let distance = edit_distance(&requested, &mount.path);
+                (distance <= MAX_SUGGESTION_DISTANCE).then_some((distance, mount.path))
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort();
+        Ok(suggestions
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, path)| path)
+            .collect())
     }
 
     pub fn clear_mounts(&self) {
@@ -124,8 +348,25 @@ impl Router {
         self.backend_lookup.get("system").map(|b| Arc::clone(&b))
     }
 
+    #[must_use]
+    pub fn get(&self, mount_id: Uuid) -> Option<Arc<Backend>> {
+        self.backend_lookup
+            .get(&mount_id.to_string())
+            .map(|b| Arc::clone(&b))
+    }
+
+    /// Ids of the mounted backends, without the system backend.
+    #[must_use]
+    pub fn mount_ids(&self) -> Vec<Uuid> {
+        self.backend_lookup
+            .iter()
+            .filter_map(|backend| Uuid::parse_str(backend.key()).ok())
+            .collect()
+    }
+
     #[must_use]
     pub fn remove(&self, mount_id: Uuid) -> bool {
+        self.mount_limits.remove(&mount_id);
         self.backend_lookup.remove(&mount_id.to_string()).is_some()
     }
 }
@@ -0,0 +1,93 @@
+//! Seals that wrap the master key with a key kept outside of the server, so
+//! the server can be unsealed without key shares.
+
+#[cfg(feature = "pkcs11")]
+mod pkcs11;
+
+use zeroize::Zeroizing;
+
+use crate::{
+    config::SealConfig,
+    error::{Error, ErrorType},
+};
+
+/// Wrap the master key with the key of the seal.
+pub async fn wrap_master_key(seal: &SealConfig, master_key: &[u8]) -> Result<Vec<u8>, Error> {
+    let seal = seal.clone();
+    let master_key = Zeroizing::new(master_key.to_vec());
+    tokio::task::spawn_blocking(move || wrap(&seal, &master_key))
+        .await
+        .map_err(|err| ErrorType::InternalError(err.into()))?
+}
+
+/// Unwrap a master key wrapped by [`wrap_master_key`]. Fails if the seal is
+/// unreachable or refuses to unwrap the key, the server then stays sealed.
+pub async fn unwrap_master_key(
+    seal: &SealConfig,
+    wrapped: Vec<u8>,
+) -> Result<Zeroizing<String>, Error> {
+    let seal = seal.clone();
+    let master_key = tokio::task::spawn_blocking(move || unwrap(&seal, &wrapped))
+        .await
+        .map_err(|err| ErrorType::InternalError(err.into()))??;
+    String::from_utf8(master_key.to_vec())
+        .map(Zeroizing::new)
+        .map_err(|_| ErrorType::Seal("The unwrapped master key is invalid".into()).into())
+}
+
+/// Check that the seal can wrap and unwrap a key before the storage is
+/// initialized, a master key that cannot be wrapped would be lost.
+pub async fn check_seal(seal: &SealConfig) -> Result<(), Error> {
+    let probe = "covert seal check";
+    let wrapped = wrap_master_key(seal, probe.as_bytes()).await?;
+    if unwrap_master_key(seal, wrapped).await?.as_str() == probe {
+        Ok(())
+    } else {
+        Err(ErrorType::Seal("The seal returned a different key".into()).into())
+    }
+}
+
+#[cfg(feature = "pkcs11")]
+fn wrap(seal: &SealConfig, master_key: &[u8]) -> Result<Vec<u8>, Error> {
+    match seal {
+        SealConfig::Pkcs11 {
+            lib_path,
+            slot,
+            key_label,
+            pin,
+        } => pkcs11::encrypt(lib_path, *slot, pin, key_label, master_key)
+            .map_err(|err| ErrorType::Seal(err.to_string()).into()),
+    }
+}
+
+#[cfg(feature = "pkcs11")]
+fn unwrap(seal: &SealConfig, wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    match seal {
+        SealConfig::Pkcs11 {
+            lib_path,
+            slot,
+            key_label,
+            pin,
+        } => pkcs11::decrypt(lib_path, *slot, pin, key_label, wrapped)
+            .map_err(|err| ErrorType::Seal(err.to_string()).into()),
+    }
+}
+
+#[cfg(not(feature = "pkcs11"))]
+fn wrap(seal: &SealConfig, _master_key: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(unavailable(seal))
+}
+
+#[cfg(not(feature = "pkcs11"))]
+fn unwrap(seal: &SealConfig, _wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    Err(unavailable(seal))
+}
+
+#[cfg(not(feature = "pkcs11"))]
+fn unavailable(seal: &SealConfig) -> Error {
+    ErrorType::Seal(format!(
+        "Covert was built without the `{}` feature",
+        seal.name()
+    ))
+    .into()
+}
@@ -0,0 +1,158 @@
+//! Encrypts and decrypts with an AES key of a PKCS#11 token through
+//! [`cryptoki`]. The vendor library is loaded at runtime for each operation.
+
+use std::path::Path;
+
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    error::{Error as CryptokiError, RvError},
+    mechanism::{aead::GcmParams, Mechanism},
+    object::{Attribute, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    slot::Slot,
+    types::{AuthPin, Ulong},
+};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const TAG_BITS: u64 = 128;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Pkcs11Error {
+    #[error("Failed to load the PKCS#11 library: {0}")]
+    Load(String),
+    #[error("PKCS#11 error: {0}")]
+    Cryptoki(#[from] CryptokiError),
+    #[error("No secret key labelled `{0}` found on the token")]
+    KeyNotFound(String),
+    #[error("More than one secret key labelled `{0}` found on the token")]
+    AmbiguousKey(String),
+    #[error("The wrapped key is too short")]
+    InvalidCiphertext,
+}
+
+/// Load and initialize the library, then open a session logged in as the
+/// user of the token.
+fn open_session(lib_path: &Path, slot: u64, pin: &str) -> Result<Session, Pkcs11Error> {
+    let pkcs11 = Pkcs11::new(lib_path).map_err(|err| Pkcs11Error::Load(err.to_string()))?;
+    match pkcs11.initialize(CInitializeArgs::OsThreads) {
+        Ok(()) | Err(CryptokiError::Pkcs11(RvError::CryptokiAlreadyInitialized, _)) => (),
+        Err(err) => return Err(err.into()),
+    }
+
+    let session = pkcs11.open_ro_session(Slot::try_from(slot)?)?;
+    match session.login(UserType::User, Some(&AuthPin::new(pin.into()))) {
+        Ok(()) | Err(CryptokiError::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => Ok(session),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn find_secret_key(session: &Session, label: &str) -> Result<ObjectHandle, Pkcs11Error> {
+    let template = [
+        Attribute::Class(ObjectClass::SECRET_KEY),
+        Attribute::Label(label.as_bytes().to_vec()),
+    ];
+    match session.find_objects(&template)?.as_slice() {
+        [] => Err(Pkcs11Error::KeyNotFound(label.to_string())),
+        [key] => Ok(*key),
+        _ => Err(Pkcs11Error::AmbiguousKey(label.to_string())),
+    }
+}
+
+/// Encrypt with the AES key labelled `key_label`. The random IV is prepended
+/// to the ciphertext.
+pub fn encrypt(
+    lib_path: &Path,
+    slot: u64,
+    pin: &str,
+    key_label: &str,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Pkcs11Error> {
+    let session = open_session(lib_path, slot, pin)?;
+    let key = find_secret_key(&session, key_label)?;
+
+    let mut iv = [0; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+    let mechanism = Mechanism::AesGcm(GcmParams::new(&iv, &[], Ulong::from(TAG_BITS)));
+    let ciphertext = session.encrypt(&mechanism, key, plaintext)?;
+
+    let mut wrapped = iv.to_vec();
+    wrapped.extend(ciphertext);
+    Ok(wrapped)
+}
+
+/// Decrypt what [`encrypt`] returned with the same key.
+pub fn decrypt(
+    lib_path: &Path,
+    slot: u64,
+    pin: &str,
+    key_label: &str,
+    wrapped: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, Pkcs11Error> {
+    if wrapped.len() < IV_LEN + TAG_LEN {
+        return Err(Pkcs11Error::InvalidCiphertext);
+    }
+    let (iv, ciphertext) = wrapped.split_at(IV_LEN);
+
+    let session = open_session(lib_path, slot, pin)?;
+    let key = find_secret_key(&session, key_label)?;
+
+    let mechanism = Mechanism::AesGcm(GcmParams::new(iv, &[], Ulong::from(TAG_BITS)));
+    Ok(Zeroizing::new(
+        session.decrypt(&mechanism, key, ciphertext)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn missing_library_fails() {
+        let err = encrypt(
+            Path::new("/does/not/exist/libpkcs11.so"),
+            0,
+            "1234",
+            "covert",
+            b"master key",
+        )
+        .unwrap_err();
+        assert!(matches!(err, Pkcs11Error::Load(_)));
+
+        let err = decrypt(
+            Path::new("/does/not/exist/libpkcs11.so"),
+            0,
+            "1234",
+            "covert",
+            &[0; IV_LEN + TAG_LEN],
+        )
+        .unwrap_err();
+        assert!(matches!(err, Pkcs11Error::Load(_)));
+    }
+
+    #[test]
+    fn file_that_is_not_a_library_fails() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"not a shared library").unwrap();
+        let err = encrypt(file.path(), 0, "1234", "covert", b"master key").unwrap_err();
+        assert!(matches!(err, Pkcs11Error::Load(_)));
+    }
+
+    #[test]
+    fn short_ciphertext_fails() {
+        // Rejected before the library is loaded
+        let err = decrypt(
+            Path::new("/does/not/exist/libpkcs11.so"),
+            0,
+            "1234",
+            "covert",
+            &[0; 8],
+        )
+        .unwrap_err();
+        assert!(matches!(err, Pkcs11Error::InvalidCiphertext));
+    }
+}
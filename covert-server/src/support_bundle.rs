@@ -0,0 +1,100 @@
+use std::io;
+
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use tar::{Builder, EntryType, Header};
+
+use crate::redact::redact;
+
+/// Content type of the gzip compressed tarball.
+pub const CONTENT_TYPE: &str = "application/gzip";
+
+/// A gzip compressed tarball of JSON documents.
+///
+/// Every document is redacted before it is written, nothing can be added to
+/// the bundle without passing through [`redact`].
+pub struct SupportBundle {
+    root: String,
+    mtime: u64,
+    archive: Builder<GzEncoder<Vec<u8>>>,
+}
+
+impl SupportBundle {
+    /// Start a bundle whose files are placed in the `root` directory.
+    pub fn new(root: impl Into<String>, mtime: u64) -> Self {
+        Self {
+            root: root.into(),
+            mtime,
+            archive: Builder::new(GzEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+
+    /// Add the redacted value as a pretty printed JSON file.
+    pub fn add_json(&mut self, name: &str, value: &impl Serialize) -> io::Result<()> {
+        let mut value = serde_json::to_value(value)?;
+        redact(&mut value);
+        let data = serde_json::to_vec_pretty(&value)?;
+
+        let mut header = Header::new_ustar();
+        header.set_entry_type(EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_mtime(self.mtime);
+        header.set_size(data.len() as u64);
+        self.archive.append_data(
+            &mut header,
+            format!("{}/{name}", self.root),
+            data.as_slice(),
+        )
+    }
+
+    /// Finish the archive and return the compressed bytes.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        self.archive.into_inner()?.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use covert_types::methods::system::REDACTED;
+    use flate2::read::GzDecoder;
+    use serde_json::{json, Value};
+    use tar::Archive;
+
+    use super::*;
+
+    #[test]
+    fn writes_tarball() {
+        let mut bundle = SupportBundle::new("bundle", 1_680_000_000);
+        bundle
+            .add_json("host.json", &json!({ "os": "linux" }))
+            .unwrap();
+        bundle
+            .add_json("config.json", &json!({ "password": "hunter2" }))
+            .unwrap();
+        let bytes = bundle.finish().unwrap();
+
+        let mut archive = Archive::new(GzDecoder::new(&bytes[..]));
+        let files = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                assert_eq!(entry.header().mtime().unwrap(), 1_680_000_000);
+                assert_eq!(entry.header().mode().unwrap(), 0o644);
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let value: Value = serde_json::from_reader(&mut entry).unwrap();
+                (name, value)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                ("bundle/host.json".to_string(), json!({ "os": "linux" })),
+                (
+                    "bundle/config.json".to_string(),
+                    json!({ "password": REDACTED })
+                ),
+            ]
+        );
+    }
+}
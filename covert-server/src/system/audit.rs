@@ -0,0 +1,226 @@
+use bytes::Bytes;
+use chrono::Utc;
+use covert_framework::{
+    create,
+    extract::{Extension, Json, Path, Query},
+    read, Router,
+};
+use covert_types::{
+    methods::system::{
+        AuditDevice, AuditHashParams, AuditHashResponse, AuditTailParams,
+        DisableAuditDeviceResponse, EnableAuditDeviceParams, ListAuditDevicesResponse,
+        ReadAuditDeviceResponse,
+    },
+    response::Response,
+};
+use futures::StreamExt;
+
+use crate::{
+    audit::{self, EnabledDevice},
+    context::Context,
+    error::{Error, ErrorType},
+    repos::{audit::AuditDeviceEntry, namespace::Namespace},
+};
+
+/// Content type of the stream of `/audit-tail/*path`.
+const TAIL_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Routes for managing audit devices, nested under `/audit`. The hash and tail
+/// routes are mounted separately at `/audit-hash/*path` and
+/// `/audit-tail/*path`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", read(handle_list_audit_devices))
+        .route(
+            "/*path",
+            create(handle_enable_audit_device)
+                .read(handle_read_audit_device)
+                .update(handle_enable_audit_device)
+                .delete(handle_disable_audit_device),
+        )
+}
+
+pub async fn handle_enable_audit_device(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(path): Path<String>,
+    Json(body): Json<EnableAuditDeviceParams>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::AuditInNonRootNamespace.into());
+    }
+    if ctx.audit.contains(&path) {
+        return Err(ErrorType::AuditDeviceAlreadyEnabled { path }.into());
+    }
+
+    // Make sure the device works before it is persisted
+    let device = audit::open(&body.config).await?;
+    let entry = AuditDeviceEntry {
+        device: AuditDevice {
+            path,
+            config: body.config,
+            hmac_exempt_response_fields: body.hmac_exempt_response_fields,
+            filter: body.filter,
+            created_at: Utc::now(),
+        },
+        salt: audit::new_salt(),
+    };
+    ctx.repos.audit.create(&entry).await?;
+    ctx.audit.enable(
+        entry.device.path.clone(),
+        EnabledDevice::new(
+            device,
+            entry.salt,
+            entry.device.hmac_exempt_response_fields.clone(),
+        )
+        .with_filter(entry.device.filter.clone()),
+    );
+
+    Response::raw(entry.device).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_list_audit_devices(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::AuditInNonRootNamespace.into());
+    }
+    let devices = ctx.repos.audit.list().await?;
+    let resp = ListAuditDevicesResponse {
+        devices: devices.into_iter().map(|entry| entry.device).collect(),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_read_audit_device(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(path): Path<String>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::AuditInNonRootNamespace.into());
+    }
+    let (Some(entry), Some(device)) = (ctx.repos.audit.lookup(&path).await?, ctx.audit.get(&path))
+    else {
+        return Err(ErrorType::AuditDeviceNotFound { path }.into());
+    };
+
+    let resp = ReadAuditDeviceResponse {
+        device: entry.device,
+        entries_written: device.entries_written(),
+        last_error: device.last_error().await,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_disable_audit_device(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(path): Path<String>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::AuditInNonRootNamespace.into());
+    }
+    if !ctx.repos.audit.remove(&path).await? {
+        return Err(ErrorType::AuditDeviceNotFound { path }.into());
+    }
+    ctx.audit.disable(&path);
+
+    let resp = DisableAuditDeviceResponse { path };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Hash the input like the device hashes the sensitive values it writes, so
+/// the log can be searched for a known value.
+pub async fn handle_audit_hash(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(path): Path<String>,
+    Json(body): Json<AuditHashParams>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::AuditInNonRootNamespace.into());
+    }
+    let hash = ctx
+        .audit
+        .hash(&path, &body.input)
+        .ok_or(ErrorType::AuditDeviceNotFound { path })?;
+
+    let resp = AuditHashResponse { hash };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Stream the entries written by the device as newline delimited JSON, see
+/// [`AuditTailEvent`](covert_types::methods::system::AuditTailEvent). The
+/// entries are the ones written by the device, with the sensitive values
+/// hashed.
+pub async fn handle_audit_tail(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(path): Path<String>,
+    Query(params): Query<AuditTailParams>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::AuditInNonRootNamespace.into());
+    }
+    let device = ctx
+        .audit
+        .get(&path)
+        .ok_or(ErrorType::AuditDeviceNotFound { path })?;
+
+    let body = device.tail().follow(&params).map(|event| {
+        let mut line = serde_json::to_vec(&event)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+        line.push(b'\n');
+        Ok(Bytes::from(line))
+    });
+    Ok(Response::stream(TAIL_CONTENT_TYPE, None, body.boxed()))
+}
+
+/// Open the persisted audit devices. Called on unseal, a device that cannot be
+/// opened fails the unseal as requests could not be audited.
+pub async fn load_audit_devices(ctx: &Context) -> Result<(), Error> {
+    for AuditDeviceEntry { device, salt } in ctx.repos.audit.list().await? {
+        let opened = audit::open(&device.config).await.map_err(|error| {
+            ErrorType::InternalError(anyhow::Error::msg(format!(
+                "Failed to open audit device `{}`: {}",
+                device.path, error.variant
+            )))
+        })?;
+        ctx.audit.enable(
+            device.path,
+            EnabledDevice::new(opened, salt, device.hmac_exempt_response_fields)
+                .with_filter(device.filter),
+        );
+    }
+    Ok(())
+}
+
+/// Open the audit devices enabled and close the devices disabled since the
+/// last call, e.g. by the active node of an HA cluster.
+pub async fn sync_audit_devices(ctx: &Context) -> Result<(), Error> {
+    let entries = ctx.repos.audit.list().await?;
+    for path in ctx.audit.paths() {
+        if !entries.iter().any(|entry| entry.device.path == path) {
+            ctx.audit.disable(&path);
+        }
+    }
+    for AuditDeviceEntry { device, salt } in entries {
+        if ctx.audit.contains(&device.path) {
+            continue;
+        }
+        let opened = audit::open(&device.config).await.map_err(|error| {
+            ErrorType::InternalError(anyhow::Error::msg(format!(
+                "Failed to open audit device `{}`: {}",
+                device.path, error.variant
+            )))
+        })?;
+        ctx.audit.enable(
+            device.path,
+            EnabledDevice::new(opened, salt, device.hmac_exempt_response_fields)
+                .with_filter(device.filter),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use covert_framework::extract::{Extension, Json};
+use covert_types::{
+    methods::system::{
+        ConfigStateResponse, ListenerState, ReplicationState, SealState, StorageConfigState,
+        TamperConfig, TlsState, TtlState, REDACTED,
+    },
+    mount::MountConfig,
+    response::Response,
+};
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    repos::namespace::Namespace,
+    ListenerAddress, SealConfig, TlsVersion,
+};
+
+/// Read the effective configuration of the running server. Secrets are never
+/// returned.
+pub async fn handle_config_state(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::ConfigInNonRootNamespace.into());
+    }
+
+    let resp = config_state(&ctx).await?;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Reload the TLS certificates and the logging config, like on SIGHUP.
+pub async fn handle_config_reload(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::ConfigInNonRootNamespace.into());
+    }
+
+    let resp = ctx.reload.reload().await?;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Reload only the TLS certificate and key, e.g. after they were rotated.
+pub async fn handle_certificate_reload(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::ConfigInNonRootNamespace.into());
+    }
+
+    let resp = ctx.reload.reload_certificates().await?;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_tamper_config_read(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::ConfigInNonRootNamespace.into());
+    }
+
+    Response::raw(ctx.tamper.config()).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Configure when the server seals itself because it looks like it is being
+/// tampered with.
+pub async fn handle_tamper_config_update(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(config): Json<TamperConfig>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::ConfigInNonRootNamespace.into());
+    }
+    if config.enabled && config.window.as_secs() == 0 {
+        return Err(
+            ErrorType::BadRequest("The tamper window must be at least one second".into()).into(),
+        );
+    }
+
+    ctx.repos.seal.set_tamper_config(&config).await?;
+    ctx.tamper.set_config(config);
+    Response::raw(config).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// The effective configuration with the secrets redacted.
+pub async fn config_state(ctx: &Context) -> Result<ConfigStateResponse, Error> {
+    let seal_config = ctx.repos.seal.get_config().await?;
+    let mount_defaults = MountConfig::default();
+    let config = &ctx.config;
+
+    Ok(ConfigStateResponse {
+        listener: ListenerState {
+            port: config.port,
+            tls: config.tls.as_ref().map(|tls| TlsState {
+                cert_file: tls.cert_file.display().to_string(),
+                min_version: match tls.min_version {
+                    TlsVersion::Tls12 => "1.2",
+                    TlsVersion::Tls13 => "1.3",
+                }
+                .to_string(),
+                request_client_cert: tls.request_client_cert,
+                require_client_cert: tls.require_client_cert,
+                cipher_suites: tls.cipher_suites.clone(),
+            }),
+            unix_sockets: config
+                .listeners
+                .iter()
+                .filter(|listener| matches!(listener.address, ListenerAddress::Unix(_)))
+                .map(|listener| listener.address.to_string())
+                .collect(),
+            tcp_listeners: config
+                .listeners
+                .iter()
+                .filter(|listener| matches!(listener.address, ListenerAddress::Tcp(_)))
+                .map(|listener| listener.address.to_string())
+                .collect(),
+        },
+        storage: StorageConfigState {
+            path: config.storage_path.clone(),
+            in_memory: config.using_inmemory_storage(),
+        },
+        seal: SealState {
+            variant: config
+                .seal
+                .as_ref()
+                .map_or("shamir", SealConfig::name)
+                .to_string(),
+            shares: seal_config.as_ref().map(|seal| seal.shares),
+            threshold: seal_config.as_ref().map(|seal| seal.threshold),
+        },
+        ttl: TtlState {
+            default_lease_ttl: mount_defaults.default_lease_ttl,
+            mount_max_lease_ttl: mount_defaults.max_lease_ttl,
+            system_max_lease_ttl: ctx.expiration_manager.max_lease_ttl(),
+        },
+        replication: config
+            .replication
+            .as_ref()
+            .map(|replication| ReplicationState {
+                bucket_url: replication.bucket_url.clone(),
+                access_key_id: replication.access_key_id.clone(),
+                secret_access_key: REDACTED.to_string(),
+            }),
+        ignore_migration_checksums: config.ignore_migration_checksums,
+    })
+}
@@ -1,11 +1,19 @@
-use covert_framework::extract::{Extension, Json, Path};
+use std::collections::{HashMap, HashSet};
+
+use covert_framework::{
+    create, delete,
+    extract::{Extension, Json, Path, Query},
+    update, Router,
+};
 use covert_types::{
-    entity::Entity,
+    entity::{Entity, EntityAlias},
     methods::system::{
         AttachEntityAliasParams, AttachEntityAliasResponse, AttachEntityPolicyParams,
-        AttachEntityPolicyResponse, CreateEntityParams, CreateEntityResponse,
-        EntityWithPolicyAndAlias, ListEntitiesResponse, RemoveEntityAliasParams,
-        RemoveEntityAliasResponse, RemoveEntityPolicyParams, RemoveEntityPolicyResponse,
+        AttachEntityPolicyResponse, ChildTokens, CreateEntityParams, CreateEntityResponse,
+        DeleteEntityParams, DeleteEntityResponse, EntityWithPolicyAndAlias, ImportEntitiesParams,
+        ImportEntitiesResponse, ImportEntity, ImportEntityResult, ImportEntityStatus,
+        ListEntitiesResponse, RemoveEntityAliasParams, RemoveEntityAliasResponse,
+        RemoveEntityPolicyParams, RemoveEntityPolicyResponse,
     },
     response::Response,
 };
@@ -13,9 +21,24 @@ use covert_types::{
 use crate::{
     context::Context,
     error::{Error, ErrorType},
-    repos::{namespace::Namespace, Repos},
+    repos::{entity::EntityRepo, namespace::Namespace, Repos},
 };
 
+/// Max number of entities that can be imported in a single request.
+const MAX_IMPORT_ENTITIES: usize = 1000;
+
+/// Routes for managing entities, nested under `/entity`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", create(handle_entity_create).read(handle_list_entities))
+        .route("/import", create(handle_entity_import))
+        .route("/policy", update(handle_attach_entity_policy))
+        .route("/policy/*name", update(handle_remove_entity_policy))
+        .route("/alias", update(handle_attach_entity_alias))
+        .route("/alias/*name", update(handle_remove_entity_alias))
+        .route("/*name", delete(handle_entity_delete))
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn handle_entity_create(
     Extension(ctx): Extension<Context>,
@@ -31,6 +54,7 @@ pub async fn handle_entity_create(
     let resp = CreateEntityResponse {
         entity: EntityWithPolicyAndAlias {
             name: entity.name,
+            metadata: HashMap::new(),
             policies: vec![],
             aliases: vec![],
         },
@@ -38,6 +62,128 @@ pub async fn handle_entity_create(
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
 
+#[tracing::instrument(skip_all)]
+pub async fn handle_entity_import(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(params): Json<ImportEntitiesParams>,
+) -> Result<Response, Error> {
+    if params.entities.len() > MAX_IMPORT_ENTITIES {
+        return Err(ErrorType::BadRequest(format!(
+            "Cannot import more than {MAX_IMPORT_ENTITIES} entities at once"
+        ))
+        .into());
+    }
+
+    let existing_entities = ctx.repos.entity.list(&ns.id).await?;
+    let mut known = KnownIdentities {
+        entity_repo: ctx.repos.entity.clone(),
+        entities: existing_entities.iter().map(|e| e.name.clone()).collect(),
+        aliases: existing_entities
+            .into_iter()
+            .flat_map(|e| e.aliases)
+            .collect(),
+        policies: ctx
+            .repos
+            .policy
+            .list(&ns.id)
+            .await?
+            .into_iter()
+            .map(|p| p.name)
+            .collect(),
+        mounts: ctx
+            .repos
+            .mount
+            .list(&ns.id)
+            .await?
+            .into_iter()
+            .map(|m| m.path)
+            .collect(),
+    };
+
+    let mut results = Vec::with_capacity(params.entities.len());
+    let mut entities = vec![];
+    for mut entity in params.entities {
+        let status = match known.validate_import(&mut entity) {
+            Ok(skipped_aliases) => {
+                entities.push(entity.clone());
+                ImportEntityStatus::Created { skipped_aliases }
+            }
+            Err(error) => ImportEntityStatus::Failed { error },
+        };
+        results.push(ImportEntityResult {
+            name: entity.name,
+            status,
+        });
+    }
+
+    ctx.repos.entity.import(&entities, &ns.id).await?;
+
+    let resp = ImportEntitiesResponse { entities: results };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Identities that exist in the namespace or are created by the entries of an
+/// import that were already validated.
+struct KnownIdentities {
+    entity_repo: EntityRepo,
+    entities: HashSet<String>,
+    aliases: HashSet<EntityAlias>,
+    policies: HashSet<String>,
+    mounts: HashSet<String>,
+}
+
+impl KnownIdentities {
+    /// Check that the entity can be created. Aliases that already belong to
+    /// another entity are removed from the entity and returned.
+    fn validate_import(&mut self, entity: &mut ImportEntity) -> Result<Vec<EntityAlias>, String> {
+        if !is_valid_name(&entity.name) {
+            return Err(format!("Invalid entity name `{}`", entity.name));
+        }
+        if self.entities.contains(&entity.name) {
+            return Err(format!("Entity `{}` already exists", entity.name));
+        }
+        if let Some(policy) = entity.policies.iter().find(|p| !self.policies.contains(*p)) {
+            return Err(format!("Could not find policy `{policy}`"));
+        }
+        entity.policies.sort();
+        entity.policies.dedup();
+        self.entity_repo
+            .validate_policies(&entity.policies)
+            .map_err(|err| err.to_string())?;
+
+        let mut mount_paths = HashSet::new();
+        for alias in &entity.aliases {
+            if !is_valid_name(&alias.name) {
+                return Err(format!("Invalid alias name `{}`", alias.name));
+            }
+            if !self.mounts.contains(&alias.mount_path) {
+                return Err(format!("Could not find mount `{}`", alias.mount_path));
+            }
+            if !mount_paths.insert(&alias.mount_path) {
+                return Err(format!(
+                    "Entity can only have one alias for mount `{}`",
+                    alias.mount_path
+                ));
+            }
+        }
+
+        let (aliases, skipped_aliases) = std::mem::take(&mut entity.aliases)
+            .into_iter()
+            .partition(|alias| !self.aliases.contains(alias));
+        entity.aliases = aliases;
+
+        self.entities.insert(entity.name.clone());
+        self.aliases.extend(entity.aliases.iter().cloned());
+        Ok(skipped_aliases)
+    }
+}
+
+/// Mirrors the name constraints of the entity and alias tables.
+pub(super) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(' ') && !name.contains('/')
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn handle_attach_entity_policy(
     Extension(ctx): Extension<Context>,
@@ -67,6 +213,15 @@ pub async fn handle_attach_entity_policy(
         .into());
     }
 
+    // Check the limits up front instead of attaching only some of the policies
+    if let Some(entity) = ctx.repos.entity.lookup(&params.name, &ns.id).await? {
+        let mut policies = entity.policies;
+        policies.extend(params.policy_names.iter().cloned());
+        policies.sort();
+        policies.dedup();
+        ctx.repos.entity.validate_policies(&policies)?;
+    }
+
     let mut attached_policies = vec![];
     for policy in &params.policy_names {
         if let Err(error) = ctx
@@ -171,6 +326,49 @@ pub async fn handle_remove_entity_alias(
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
 
+/// Delete the entity with its aliases and policies. The tokens of the entity
+/// are revoked, the tokens issued with them to other entities are revoked or
+/// kept as orphans depending on [`ChildTokens`].
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_entity_delete(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+    Query(params): Query<DeleteEntityParams>,
+) -> Result<Response, Error> {
+    if ctx.repos.entity.lookup(&name, &ns.id).await?.is_none() {
+        return Err(ErrorType::NotFound(format!("Entity `{name}` not found")).into());
+    }
+    // The token of the login would be issued to an entity that no longer
+    // exists
+    if ctx
+        .pending_logins
+        .has_entity(&name, &ns.id, ctx.expiration_manager.now())
+    {
+        return Err(ErrorType::EntityLoginPending { entity: name }.into());
+    }
+
+    let orphaned_tokens = match params.child_tokens {
+        ChildTokens::Revoke => 0,
+        ChildTokens::Orphan => ctx.repos.token.orphan_children(&name, &ns.id).await?,
+    };
+    let tokens = ctx.repos.token.list_by_entity(&name, &ns.id).await?;
+    for token in &tokens {
+        ctx.expiration_manager.revoke_token(token, &ns.id).await?;
+    }
+
+    if !ctx.repos.entity.delete(&name, &ns.id).await? {
+        return Err(ErrorType::NotFound(format!("Entity `{name}` not found")).into());
+    }
+
+    let resp = DeleteEntityResponse {
+        name,
+        revoked_tokens: tokens.len(),
+        orphaned_tokens: usize::try_from(orphaned_tokens).unwrap_or(usize::MAX),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn handle_list_entities(
     Extension(ctx): Extension<Context>,
@@ -183,6 +381,7 @@ pub async fn handle_list_entities(
             .into_iter()
             .map(|e| EntityWithPolicyAndAlias {
                 name: e.name,
+                metadata: e.metadata,
                 policies: e.policies,
                 aliases: e.aliases,
             })
@@ -207,6 +406,7 @@ async fn lookup_entity(
         })?;
     Ok(EntityWithPolicyAndAlias {
         name: entity.name,
+        metadata: entity.metadata,
         policies: entity.policies,
         aliases: entity.aliases,
     })
@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+
+use covert_framework::{
+    create,
+    extract::{Extension, Json},
+    read, Router,
+};
+use covert_types::{
+    entity::EntityAlias,
+    methods::system::{
+        ImportEntity, ImportIdentityMode, ImportIdentityParams, ImportIdentityResponse,
+    },
+    policy::Policy,
+    response::Response,
+};
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    identity::{IdentityStore, SnapshotPolicy},
+    repos::{
+        entity::{EntityWithPolicyAndAlias, ROOT_ENTITY},
+        identity::IdentityImport,
+        namespace::Namespace,
+        policy::ROOT_POLICY,
+    },
+};
+
+use super::entity::is_valid_name;
+
+/// Routes for exporting the identity store of a namespace and importing it
+/// into another server, nested under `/identity`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/export", read(handle_identity_export))
+        .route(
+            "/import",
+            create(handle_identity_import).update(handle_identity_import),
+        )
+}
+
+/// Export the policies and entities of the namespace. The `root` policy and
+/// entity are created on unseal and are left out.
+#[tracing::instrument(skip_all)]
+pub async fn handle_identity_export(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    let policies = ctx
+        .repos
+        .policy
+        .list(&ns.id)
+        .await?
+        .into_iter()
+        .filter(|policy| policy.name != ROOT_POLICY)
+        .map(|policy| SnapshotPolicy {
+            name: policy.name,
+            paths: policy.paths,
+        })
+        .collect();
+    let entities = ctx
+        .repos
+        .entity
+        .list(&ns.id)
+        .await?
+        .into_iter()
+        .filter(|entity| entity.name != ROOT_ENTITY)
+        .map(|entity| ImportEntity {
+            name: entity.name,
+            metadata: entity.metadata,
+            policies: entity.policies,
+            aliases: entity.aliases,
+        })
+        .collect();
+
+    let store = IdentityStore { policies, entities };
+    let snapshot = ctx
+        .snapshot_key
+        .encrypt(&store, ctx.expiration_manager.now())?;
+    Response::raw(snapshot).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all, fields(mode = ?params.mode))]
+pub async fn handle_identity_import(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(params): Json<ImportIdentityParams>,
+) -> Result<Response, Error> {
+    let store = ctx.snapshot_key.decrypt(&params.snapshot)?;
+    let replace = params.mode == ImportIdentityMode::Replace;
+
+    let existing_policies = ctx
+        .repos
+        .policy
+        .list(&ns.id)
+        .await?
+        .into_iter()
+        .map(|policy| policy.name)
+        .collect::<HashSet<_>>();
+    let existing_entities = ctx
+        .repos
+        .entity
+        .list(&ns.id)
+        .await?
+        .into_iter()
+        .map(|entity| (entity.name.clone(), entity))
+        .collect::<HashMap<_, _>>();
+    let mounts = ctx
+        .repos
+        .mount
+        .list(&ns.id)
+        .await?
+        .into_iter()
+        .map(|mount| mount.path)
+        .collect::<HashSet<_>>();
+
+    let mut policies = Vec::with_capacity(store.policies.len());
+    let mut policy_names = HashSet::new();
+    for policy in store.policies {
+        if !is_valid_name(&policy.name) || policy.name == ROOT_POLICY {
+            return Err(invalid(format!("invalid policy name `{}`", policy.name)));
+        }
+        ctx.repos.policy.validate_name(&policy.name)?;
+        if !policy_names.insert(policy.name.clone()) {
+            return Err(invalid(format!("duplicate policy `{}`", policy.name)));
+        }
+        policies.push(Policy::new(policy.name, policy.paths, ns.id.clone()));
+    }
+    // Policies the entities of the snapshot can have attached
+    let known_policies = existing_policies
+        .iter()
+        .filter(|name| !replace || *name == ROOT_POLICY)
+        .chain(&policy_names)
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    // Aliases of the entities that are not replaced by the import
+    let mut taken_aliases = existing_entities
+        .values()
+        .filter(|entity| !replace || entity.name == ROOT_ENTITY)
+        .flat_map(|entity| {
+            entity
+                .aliases
+                .iter()
+                .map(|alias| (alias.clone(), entity.name.clone()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut entities = Vec::with_capacity(store.entities.len());
+    let mut skipped_aliases = vec![];
+    for entity in store.entities {
+        if !is_valid_name(&entity.name) || entity.name == ROOT_ENTITY {
+            return Err(invalid(format!("invalid entity name `{}`", entity.name)));
+        }
+        if entities
+            .iter()
+            .any(|e: &ImportEntity| e.name == entity.name)
+        {
+            return Err(invalid(format!("duplicate entity `{}`", entity.name)));
+        }
+        if let Some(policy) = entity
+            .policies
+            .iter()
+            .find(|p| !known_policies.contains(*p))
+        {
+            return Err(invalid(format!(
+                "entity `{}` has unknown policy `{policy}`",
+                entity.name
+            )));
+        }
+
+        let existing = existing_entities.get(&entity.name).filter(|_| !replace);
+        let mut entity = merge_entity(existing, entity);
+        ctx.repos.entity.validate_policies(&entity.policies)?;
+
+        let mut mount_paths = HashSet::new();
+        let mut aliases = vec![];
+        for alias in std::mem::take(&mut entity.aliases) {
+            if !is_valid_name(&alias.name) || !mount_paths.insert(alias.mount_path.clone()) {
+                return Err(invalid(format!(
+                    "entity `{}` has an invalid alias `{}` for mount `{}`",
+                    entity.name, alias.name, alias.mount_path
+                )));
+            }
+            let owner = taken_aliases
+                .entry(alias.clone())
+                .or_insert_with(|| entity.name.clone());
+            if !mounts.contains(&alias.mount_path) || *owner != entity.name {
+                skipped_aliases.push(alias);
+            } else {
+                aliases.push(alias);
+            }
+        }
+        entity.aliases = aliases;
+        entities.push(entity);
+    }
+
+    let (removed_policies, removed_entities) = if replace {
+        let removed_policies = existing_policies
+            .into_iter()
+            .filter(|name| name != ROOT_POLICY && !policy_names.contains(name))
+            .collect::<Vec<_>>();
+        let removed_entities = existing_entities
+            .into_keys()
+            .filter(|name| name != ROOT_ENTITY && !entities.iter().any(|e| e.name == *name))
+            .collect::<Vec<_>>();
+        (removed_policies, removed_entities)
+    } else {
+        (vec![], vec![])
+    };
+
+    let import = IdentityImport {
+        policies,
+        entities,
+        replace_attachments: replace,
+        removed_policies,
+        removed_entities,
+    };
+    ctx.repos.identity.import(&import, &ns.id).await?;
+
+    let mut resp = ImportIdentityResponse {
+        mode: params.mode,
+        policies: import.policies.len(),
+        entities: import.entities.len(),
+        removed_policies: import.removed_policies,
+        removed_entities: import.removed_entities,
+        skipped_aliases,
+    };
+    resp.removed_policies.sort();
+    resp.removed_entities.sort();
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Add the metadata, policies and aliases of the imported entity to the
+/// existing entity. The imported metadata values and aliases win.
+fn merge_entity(
+    existing: Option<&EntityWithPolicyAndAlias>,
+    imported: ImportEntity,
+) -> ImportEntity {
+    let Some(existing) = existing else {
+        return imported;
+    };
+    let mut metadata = existing.metadata.clone();
+    metadata.extend(imported.metadata);
+    let mut policies = existing.policies.clone();
+    policies.extend(imported.policies);
+    policies.sort();
+    policies.dedup();
+    let mut aliases: Vec<EntityAlias> = imported.aliases;
+    aliases.extend(
+        existing
+            .aliases
+            .iter()
+            .filter(|alias| !aliases.iter().any(|a| a.mount_path == alias.mount_path))
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+    ImportEntity {
+        name: imported.name,
+        metadata,
+        policies,
+        aliases,
+    }
+}
+
+fn invalid(message: String) -> Error {
+    ErrorType::InvalidIdentitySnapshot(message).into()
+}
@@ -2,23 +2,32 @@ use covert_framework::extract::{Extension, Json};
 use covert_types::{
     methods::system::{
         InitializeParams, InitializeResponse, InitializedKeyShares, InitializedWithExistingKey,
+        InitializedWithSeal,
     },
     response::Response,
 };
 
+use zeroize::Zeroizing;
+
 use crate::{
+    config::{self, KeySharesConfig},
     context::Context,
     error::{Error, ErrorType},
     repos::seal::SealConfig,
+    seal::{check_seal, wrap_master_key},
 };
 
+use super::unseal::{generate_root_token, unseal_with_master_key};
+
 pub async fn handle_initialize(
     Extension(ctx): Extension<Context>,
     Json(body): Json<InitializeParams>,
 ) -> Result<Response, Error> {
     // Sanity check params before making real master key
-    if body.threshold == 0 || body.shares < body.threshold {
-        return Err(ErrorType::InvalidInitializeParams.into());
+    validate_params(&ctx.config.key_shares, ctx.config.seal.is_some(), &body)?;
+
+    if let Some(seal) = &ctx.config.seal {
+        return initialize_with_seal(&ctx, seal).await;
     }
 
     ctx.repos
@@ -45,3 +54,138 @@ pub async fn handle_initialize(
         Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
     }
 }
+
+/// Check the key share params against the bounds of the config. With a seal
+/// the params are only used if `stored_shares` is set, as the seal keeps the
+/// whole master key.
+fn validate_params(
+    config: &KeySharesConfig,
+    sealed: bool,
+    params: &InitializeParams,
+) -> Result<(), Error> {
+    let invalid = |field, error: String| ErrorType::InvalidInitializeParams { field, error };
+
+    match params.stored_shares {
+        Some(stored_shares) if !sealed && stored_shares > 0 => {
+            return Err(invalid("stored_shares", "stored shares require a seal".into()).into());
+        }
+        Some(stored_shares) if sealed && stored_shares != params.shares => {
+            return Err(invalid(
+                "stored_shares",
+                "the seal keeps every share, stored shares must equal the share count".into(),
+            )
+            .into());
+        }
+        None if sealed => return Ok(()),
+        _ => (),
+    }
+
+    if params.shares == 0 {
+        return Err(invalid("shares", "share count must be at least 1".into()).into());
+    }
+    if params.shares > config.max_shares {
+        return Err(invalid(
+            "shares",
+            format!("share count cannot exceed {}", config.max_shares),
+        )
+        .into());
+    }
+    if params.threshold < config.min_threshold {
+        return Err(invalid(
+            "threshold",
+            format!("threshold must be at least {}", config.min_threshold),
+        )
+        .into());
+    }
+    if params.threshold > params.shares {
+        return Err(invalid("threshold", "threshold cannot exceed share count".into()).into());
+    }
+    Ok(())
+}
+
+/// Initialize the storage with a master key wrapped by the seal and unseal it
+/// right away. The key shares are not used.
+async fn initialize_with_seal(ctx: &Context, seal: &config::SealConfig) -> Result<Response, Error> {
+    // The master key would be lost if the seal fails after the storage is
+    // initialized
+    check_seal(seal).await?;
+
+    let Some(master_key) = ctx.repos.pool.initialize()? else {
+        let resp = InitializeResponse::ExistingKey(InitializedWithExistingKey {
+            message: "Initialized with stored master key".into(),
+        });
+        return Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into());
+    };
+    let master_key = Zeroizing::new(master_key);
+    let wrapped = wrap_master_key(seal, master_key.as_bytes()).await?;
+    ctx.repos.seal.set_wrapped_master_key(&wrapped).await?;
+
+    unseal_with_master_key(ctx, &master_key).await?;
+    let root_token = generate_root_token(&ctx.repos).await?;
+
+    let resp = InitializeResponse::Unsealed(InitializedWithSeal {
+        seal: seal.name().to_string(),
+        root_token,
+    });
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(shares: u8, threshold: u8, stored_shares: Option<u8>) -> InitializeParams {
+        InitializeParams {
+            shares,
+            threshold,
+            stored_shares,
+        }
+    }
+
+    fn invalid_field(res: Result<(), Error>) -> &'static str {
+        match res.unwrap_err().variant {
+            ErrorType::InvalidInitializeParams { field, .. } => field,
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn validate_key_shares() {
+        let config = KeySharesConfig {
+            max_shares: 20,
+            min_threshold: 2,
+        };
+        assert!(validate_params(&config, false, &params(20, 11, None)).is_ok());
+        assert!(validate_params(&config, false, &params(3, 2, Some(0))).is_ok());
+
+        for (params, field) in [
+            (params(0, 0, None), "shares"),
+            (params(21, 11, None), "shares"),
+            (params(5, 1, None), "threshold"),
+            (params(5, 6, None), "threshold"),
+            (params(5, 3, Some(5)), "stored_shares"),
+        ] {
+            assert_eq!(
+                invalid_field(validate_params(&config, false, &params)),
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn validate_stored_shares() {
+        let config = KeySharesConfig::default();
+        // The params are not used by the seal unless shares are stored
+        assert!(validate_params(&config, true, &params(0, 0, None)).is_ok());
+        assert!(validate_params(&config, true, &params(5, 3, Some(5))).is_ok());
+
+        assert_eq!(
+            invalid_field(validate_params(&config, true, &params(5, 3, Some(3)))),
+            "stored_shares"
+        );
+        assert_eq!(
+            invalid_field(validate_params(&config, true, &params(5, 6, Some(5)))),
+            "threshold"
+        );
+    }
+}
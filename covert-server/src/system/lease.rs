@@ -1,11 +1,17 @@
-use covert_framework::extract::{Extension, Json, Path};
+use std::collections::BTreeMap;
+
+use covert_framework::{
+    extract::{Extension, Json, Path, Query},
+    read, update, Router,
+};
 use covert_types::{
     methods::system::{
-        LeaseEntry as LeaseEntryDTO, ListLeasesResponse, LookupLeaseResponse, RenewLeaseParams,
-        RenewLeaseResponse, RevokedLeaseResponse, RevokedLeasesResponse,
+        CountLeasesResponse, LeaseEntry as LeaseEntryDTO, ListLeasesResponse, LookupLeaseResponse,
+        RenewLeaseParams, RenewLeaseResponse, RevokedLeaseResponse, RevokedLeasesResponse,
     },
     response::Response,
 };
+use serde::Deserialize;
 
 use crate::{
     context::Context,
@@ -14,6 +20,103 @@ use crate::{
     repos::namespace::Namespace,
 };
 
+/// Routes for managing leases, nested under `/leases`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/revoke/*lease_id", update(handle_lease_revocation))
+        .route("/renew/*lease_id", update(handle_lease_renew))
+        .route("/lookup/*lease_id", read(handle_lease_lookup))
+        .route(
+            "/revoke-mount/*prefix",
+            update(handle_lease_revocation_by_mount),
+        )
+        .route(
+            "/revoke-force/*prefix",
+            update(handle_lease_force_revocation_by_mount),
+        )
+        .route("/lookup-mount/*prefix", read(handle_list_leases))
+        .route("/count-mount/*prefix", read(handle_count_leases_by_mount))
+        .route("/count", read(handle_count_leases))
+        .route("/revoke-tag", update(handle_lease_revocation_by_tag))
+}
+
+/// Path parameters of the routes operating on a single lease.
+#[derive(Debug, Deserialize)]
+pub struct LeasePath {
+    lease_id: String,
+}
+
+/// Path parameters of the routes operating on all leases under a mount.
+#[derive(Debug, Deserialize)]
+pub struct LeasePrefixPath {
+    prefix: String,
+}
+
+/// Query parameters selecting the leases that have all the given tags, e.g.
+/// `?tag=team=payments&tag=app=web`.
+#[derive(Debug, Deserialize)]
+pub struct LeaseTagFilter {
+    #[serde(default)]
+    tag: Vec<String>,
+}
+
+impl LeaseTagFilter {
+    fn tags(&self) -> Result<BTreeMap<String, String>, Error> {
+        self.tag
+            .iter()
+            .map(|tag| {
+                tag.split_once('=')
+                    .filter(|(key, _)| !key.is_empty())
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .ok_or_else(|| {
+                        ErrorType::BadRequest(format!(
+                            "Invalid tag filter `{tag}`, expected `key=value`"
+                        ))
+                        .into()
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Largest page of leases returned at once.
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Query parameters of a paginated listing. Without them every lease is
+/// returned at once.
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    /// Max number of leases in the page, capped at [`MAX_PAGE_SIZE`].
+    limit: Option<u32>,
+    /// The `next_cursor` of the previous page.
+    cursor: Option<String>,
+}
+
+impl PageQuery {
+    /// The size of the page and the id of the lease the page starts after,
+    /// or `None` if the listing is not paginated.
+    fn page(&self) -> Result<Option<(u32, Option<String>)>, Error> {
+        if self.limit.is_none() && self.cursor.is_none() {
+            return Ok(None);
+        }
+        let limit = self.limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE);
+        if limit == 0 {
+            return Err(ErrorType::BadRequest("The page limit must be at least 1".into()).into());
+        }
+        let after = self
+            .cursor
+            .as_deref()
+            .map(|cursor| {
+                hex::decode(cursor)
+                    .ok()
+                    .and_then(|id| String::from_utf8(id).ok())
+                    .ok_or_else(|| ErrorType::BadRequest(format!("Invalid cursor `{cursor}`")))
+            })
+            .transpose()?;
+        Ok(Some((limit, after)))
+    }
+}
+
 impl From<&LeaseEntry> for LeaseEntryDTO {
     fn from(le: &LeaseEntry) -> Self {
         Self {
@@ -21,7 +124,9 @@ impl From<&LeaseEntry> for LeaseEntryDTO {
             issued_mount_path: le.issued_mount_path.clone(),
             issue_time: le.issued_at.to_rfc3339(),
             expire_time: le.expires_at.to_rfc3339(),
-            last_renewal_time: le.expires_at.to_rfc3339(),
+            last_renewal_time: le.last_renewal_time.to_rfc3339(),
+            renewable: le.renew_path.is_some(),
+            tags: le.tags(),
         }
     }
 }
@@ -29,14 +134,57 @@ impl From<&LeaseEntry> for LeaseEntryDTO {
 pub async fn handle_lease_revocation_by_mount(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(prefix): Path<String>,
+    Path(LeasePrefixPath { prefix }): Path<LeasePrefixPath>,
+    Query(filter): Query<LeaseTagFilter>,
+) -> Result<Response, Error> {
+    let revoked_leases = ctx
+        .expiration_manager
+        .revoke_leases_by_mount_prefix(&prefix, &ns.id, &filter.tags()?)
+        .await?;
+    let resp = RevokedLeasesResponse {
+        leases: revoked_leases.iter().map(LeaseEntryDTO::from).collect(),
+        backend_failures: vec![],
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_lease_force_revocation_by_mount(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(LeasePrefixPath { prefix }): Path<LeasePrefixPath>,
+    Query(filter): Query<LeaseTagFilter>,
+) -> Result<Response, Error> {
+    let (removed_leases, backend_failures) = ctx
+        .expiration_manager
+        .force_revoke_leases_by_mount_prefix(&prefix, &ns.id, &filter.tags()?)
+        .await?;
+    let resp = RevokedLeasesResponse {
+        leases: removed_leases.iter().map(LeaseEntryDTO::from).collect(),
+        backend_failures: backend_failures.iter().map(LeaseEntryDTO::from).collect(),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Revoke the leases with the given tags across all mounts in the namespace.
+pub async fn handle_lease_revocation_by_tag(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Query(filter): Query<LeaseTagFilter>,
 ) -> Result<Response, Error> {
+    let tags = filter.tags()?;
+    if tags.is_empty() {
+        return Err(ErrorType::BadRequest(
+            "At least one tag is required to revoke leases by tag".into(),
+        )
+        .into());
+    }
     let revoked_leases = ctx
         .expiration_manager
-        .revoke_leases_by_mount_prefix(&prefix, &ns.id)
+        .revoke_leases_by_mount_prefix("", &ns.id, &tags)
         .await?;
     let resp = RevokedLeasesResponse {
         leases: revoked_leases.iter().map(LeaseEntryDTO::from).collect(),
+        backend_failures: vec![],
     };
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
@@ -44,7 +192,7 @@ pub async fn handle_lease_revocation_by_mount(
 pub async fn handle_lease_revocation(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(lease_id): Path<String>,
+    Path(LeasePath { lease_id }): Path<LeasePath>,
 ) -> Result<Response, Error> {
     let revoked_lease = ctx
         .expiration_manager
@@ -59,22 +207,74 @@ pub async fn handle_lease_revocation(
 pub async fn handle_list_leases(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(prefix): Path<String>,
+    Path(LeasePrefixPath { prefix }): Path<LeasePrefixPath>,
+    Query(filter): Query<LeaseTagFilter>,
+    Query(page): Query<PageQuery>,
 ) -> Result<Response, Error> {
-    let leases = ctx
-        .expiration_manager
-        .list_by_mount_prefix(&prefix, &ns.id)
+    let tags = filter.tags()?;
+    let Some((limit, after)) = page.page()? else {
+        let leases = ctx
+            .expiration_manager
+            .list_by_mount_prefix(&prefix, &ns.id, &tags)
+            .await?;
+        let resp = ListLeasesResponse {
+            leases: leases.iter().map(LeaseEntryDTO::from).collect(),
+            next_cursor: None,
+        };
+        return Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into());
+    };
+
+    // Fetch one more lease to know if there is a next page
+    let mut leases = ctx
+        .repos
+        .lease
+        .list_page_by_mount_prefix(&prefix, &ns.id, &tags, after.as_deref(), limit + 1)
         .await?;
+    let next_cursor = if leases.len() > limit as usize {
+        leases.truncate(limit as usize);
+        leases.last().map(|le| hex::encode(&le.id))
+    } else {
+        None
+    };
     let resp = ListLeasesResponse {
         leases: leases.iter().map(LeaseEntryDTO::from).collect(),
+        next_cursor,
     };
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
 
+pub async fn handle_count_leases_by_mount(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(LeasePrefixPath { prefix }): Path<LeasePrefixPath>,
+    Query(filter): Query<LeaseTagFilter>,
+) -> Result<Response, Error> {
+    let count = ctx
+        .expiration_manager
+        .count_by_mount_prefix(&prefix, &ns.id, &filter.tags()?)
+        .await?;
+    Response::raw(CountLeasesResponse { count })
+        .map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Count the leases across all mounts in the namespace.
+pub async fn handle_count_leases(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Query(filter): Query<LeaseTagFilter>,
+) -> Result<Response, Error> {
+    let count = ctx
+        .expiration_manager
+        .count_by_mount_prefix("", &ns.id, &filter.tags()?)
+        .await?;
+    Response::raw(CountLeasesResponse { count })
+        .map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 pub async fn handle_lease_lookup(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(lease_id): Path<String>,
+    Path(LeasePath { lease_id }): Path<LeasePath>,
 ) -> Result<Response, Error> {
     let lease = ctx
         .expiration_manager
@@ -91,11 +291,11 @@ pub async fn handle_lease_renew(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
     Json(body): Json<RenewLeaseParams>,
-    Path(lease_id): Path<String>,
+    Path(LeasePath { lease_id }): Path<LeasePath>,
 ) -> Result<Response, Error> {
     let lease = ctx
         .expiration_manager
-        .renew_lease_entry(&lease_id, &ns.id, body.ttl)
+        .renew_lease_entry(&lease_id, &ns.id, body.increment)
         .await?;
     let resp = RenewLeaseResponse {
         lease: LeaseEntryDTO::from(&lease),
@@ -0,0 +1,89 @@
+use covert_framework::extract::Extension;
+use covert_types::{response::Response, state::StorageState};
+
+use crate::{
+    context::Context,
+    error::Error,
+    metrics::{self, Encoder},
+    repos::database_size,
+};
+
+pub async fn handle_metrics(Extension(ctx): Extension<Context>) -> Result<Response, Error> {
+    let mut encoder = Encoder::default();
+    ctx.router.metrics().encode(&mut encoder);
+
+    encoder.single(
+        metrics::LEASE_REVOCATIONS_TOTAL,
+        "Leases revoked by the expiration manager.",
+        "counter",
+        ctx.expiration_manager.revocations(),
+    );
+    encoder.single(
+        metrics::LEASE_REVOCATION_FAILURES_TOTAL,
+        "Failed attempts of the expiration manager to revoke a lease.",
+        "counter",
+        ctx.expiration_manager.revocation_failures(),
+    );
+
+    encoder.single(
+        metrics::TAMPER_TRIPS_TOTAL,
+        "Times a tamper threshold was reached and the server sealed itself.",
+        "counter",
+        ctx.tamper.trips(),
+    );
+
+    let state = ctx.repos.pool.state();
+    encoder.header(
+        metrics::STORAGE_STATE,
+        "1 for the current storage state.",
+        "gauge",
+    );
+    for s in [
+        StorageState::Uninitialized,
+        StorageState::Sealed,
+        StorageState::Unsealed,
+    ] {
+        encoder.sample(
+            metrics::STORAGE_STATE,
+            &[("state", s.to_string())],
+            u8::from(s == state),
+        );
+    }
+
+    encoder.header(
+        metrics::STORAGE_SIZE_BYTES,
+        "Size of the storage in bytes.",
+        "gauge",
+    );
+    encoder.sample(
+        metrics::STORAGE_SIZE_BYTES,
+        &[("storage", "seal".to_string())],
+        database_size(&ctx.repos.unecrypted_pool).await?,
+    );
+
+    // The leases and tokens are only readable while unsealed
+    if state == StorageState::Unsealed {
+        encoder.sample(
+            metrics::STORAGE_SIZE_BYTES,
+            &[("storage", "encrypted".to_string())],
+            database_size(ctx.repos.pool.as_ref()).await?,
+        );
+
+        let (leases, expired) = ctx.repos.lease.count(ctx.expiration_manager.now()).await?;
+        encoder.single(metrics::LEASES, "Number of active leases.", "gauge", leases);
+        encoder.single(
+            metrics::EXPIRATION_QUEUE_DEPTH,
+            "Number of expired leases waiting to be revoked.",
+            "gauge",
+            expired,
+        );
+        encoder.single(
+            metrics::TOKENS,
+            "Number of tokens.",
+            "gauge",
+            ctx.repos.token.count().await?,
+        );
+    }
+
+    Ok(Response::bytes(metrics::CONTENT_TYPE, encoder.finish()))
+}
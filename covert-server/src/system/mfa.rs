@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use covert_framework::{
+    create, create_with_config,
+    extract::{Extension, Json, Path},
+    RouteConfig, Router,
+};
+use covert_types::{
+    auth::AuthPolicy,
+    methods::system::{
+        GenerateTotpKeyParams, GenerateTotpKeyResponse, TotpKeyResponse, ValidateMfaParams,
+    },
+    response::Response,
+    state::StorageState,
+};
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    layer::lease_registration::issue_token,
+    mfa::{base32_encode, generate_totp_secret, totp_url, verify_totp},
+    repos::{mfa::TotpKey, namespace::Namespace},
+};
+
+/// Routes for completing logins with a second factor and managing the TOTP
+/// keys of entities, nested under `/mfa`.
+pub fn router() -> Router {
+    let unauthenticated = || RouteConfig {
+        policy: AuthPolicy::Unauthenticated,
+        state: vec![StorageState::Unsealed],
+    };
+    Router::new()
+        .route(
+            "/validate",
+            create_with_config(handle_mfa_validate, unauthenticated())
+                .update_with_config(handle_mfa_validate, unauthenticated()),
+        )
+        .route(
+            "/totp/*name",
+            create(handle_totp_key_generate)
+                .read(handle_totp_key_read)
+                .delete(handle_totp_key_remove),
+        )
+}
+
+#[tracing::instrument(skip_all, fields(request_id = %params.request_id))]
+pub async fn handle_mfa_validate(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(params): Json<ValidateMfaParams>,
+) -> Result<Response, Error> {
+    let now = ctx.expiration_manager.now();
+    let login = ctx
+        .pending_logins
+        .take(params.request_id, now)
+        .filter(|login| login.namespace_id == ns.id)
+        .ok_or(ErrorType::InvalidMfaRequest)?;
+
+    let key = ctx
+        .repos
+        .mfa
+        .totp_key(&login.entity_name, &login.namespace_id)
+        .await?
+        .ok_or(ErrorType::InvalidMfaRequest)?;
+    let step = verify_totp(&key.secret, &params.code, now).ok_or(ErrorType::InvalidMfaCode)?;
+    // A code cannot be replayed, not even for another login
+    if !ctx
+        .repos
+        .mfa
+        .use_totp_step(&login.entity_name, &login.namespace_id, step)
+        .await?
+    {
+        return Err(ErrorType::InvalidMfaCode.into());
+    }
+
+    // The token and its lease are created even if the request is cancelled
+    let expiration_manager = Arc::clone(&ctx.expiration_manager);
+    let token_repo = ctx.repos.token.clone();
+    let resp =
+        tokio::spawn(async move { issue_token(&expiration_manager, &token_repo, login).await })
+            .await
+            .map_err(|err| ErrorType::InternalError(err.into()))??;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip(ctx, params))]
+pub async fn handle_totp_key_generate(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+    Json(params): Json<GenerateTotpKeyParams>,
+) -> Result<Response, Error> {
+    if ctx.repos.entity.lookup(&name, &ns.id).await?.is_none() {
+        return Err(ErrorType::NotFound(format!("Entity `{name}` not found")).into());
+    }
+
+    let key = TotpKey {
+        entity_name: name,
+        namespace_id: ns.id,
+        secret: generate_totp_secret(),
+        last_used_step: None,
+        created_at: ctx.expiration_manager.now(),
+    };
+    ctx.repos.mfa.set_totp_key(&key).await?;
+
+    let issuer = params.issuer.as_deref().unwrap_or("Covert");
+    let resp = GenerateTotpKeyResponse {
+        url: totp_url(issuer, &key.entity_name, &key.secret),
+        secret: base32_encode(&key.secret),
+        entity: key.entity_name,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_totp_key_read(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let key = ctx
+        .repos
+        .mfa
+        .totp_key(&name, &ns.id)
+        .await?
+        .ok_or_else(|| ErrorType::NotFound(format!("Entity `{name}` has no TOTP key")))?;
+
+    let resp = TotpKeyResponse {
+        entity: key.entity_name,
+        created_at: key.created_at,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_totp_key_remove(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let key = ctx
+        .repos
+        .mfa
+        .totp_key(&name, &ns.id)
+        .await?
+        .ok_or_else(|| ErrorType::NotFound(format!("Entity `{name}` has no TOTP key")))?;
+    ctx.repos.mfa.remove_totp_key(&name, &ns.id).await?;
+
+    let resp = TotpKeyResponse {
+        entity: key.entity_name,
+        created_at: key.created_at,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
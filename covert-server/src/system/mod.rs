@@ -1,17 +1,26 @@
+mod audit;
+mod config;
 mod entity;
+mod identity;
 mod initialize;
 mod lease;
+mod metrics;
+mod mfa;
 mod mount;
 mod namespace;
 mod policy;
+mod quota;
 mod seal;
+mod static_secret;
 mod status;
+mod support_bundle;
 mod token;
 mod unseal;
+mod wrapping;
 
 use covert_framework::{
     create, create_with_config, delete, extract::Extension, read, read_with_config, renew, revoke,
-    update, Backend, RouteConfig, Router,
+    update, update_with_config, Backend, RouteConfig, Router,
 };
 use covert_types::{
     auth::AuthPolicy,
@@ -22,30 +31,64 @@ use covert_types::{
 use crate::context::Context;
 
 use self::{
-    entity::{
-        handle_attach_entity_alias, handle_attach_entity_policy, handle_entity_create,
-        handle_list_entities, handle_remove_entity_alias, handle_remove_entity_policy,
+    config::{
+        handle_certificate_reload, handle_config_reload, handle_config_state,
+        handle_tamper_config_read, handle_tamper_config_update,
     },
     initialize::handle_initialize,
-    lease::{
-        handle_lease_lookup, handle_lease_renew, handle_lease_revocation,
-        handle_lease_revocation_by_mount, handle_list_leases,
+    metrics::handle_metrics,
+    mount::{
+        handle_mount, handle_mount_disable, handle_mount_migrations, handle_mount_paths,
+        handle_mount_read, handle_mount_tune, handle_mount_tune_read, handle_mounts_list,
+        handle_update_mount,
     },
-    mount::{handle_mount, handle_mount_disable, handle_mounts_list, handle_update_mount},
     namespace::{create_namespace_handler, delete_namespace_handler, list_namespaces_handler},
-    policy::{handle_create_policy, handle_delete_policy, handle_list_policies},
     seal::handle_seal,
     status::handle_status,
-    token::{handle_token_renewal, handle_token_revocation},
+    support_bundle::handle_support_bundle,
+    token::{
+        handle_token_create, handle_token_lookup, handle_token_lookup_self,
+        handle_token_renew_self, handle_token_renewal, handle_token_revocation,
+        handle_token_revocation_by_policy, handle_token_revocation_job_status, handle_token_revoke,
+        handle_token_revoke_self, handle_token_tidy,
+    },
     unseal::handle_unseal,
 };
 pub use mount::mount;
+pub(crate) use seal::seal;
 pub use token::RevokeTokenParams;
+pub(crate) use unseal::{auto_unseal, replicate, restore, start_expiration_manager};
 
 pub const SYSTEM_MOUNT_PATH: &str = "sys/";
 
+/// Privileged routes of the system backend. Besides the capability for the
+/// operation the policy must also grant `sudo` on the path. Routes where only
+/// the changes are privileged use [`RouteConfig::sudo`] for those handlers.
+const SUDO_PATHS: &[&str] = &[
+    "/seal",
+    "/leases/revoke-mount/*prefix",
+    "/leases/revoke-force/*prefix",
+    "/token/create",
+    "/token/revoke-by-policy",
+    "/token/tidy",
+    "/config/state/sanitized",
+    "/config/reload",
+    "/config/reload-cert",
+    "/config/tamper",
+    "/audit",
+    "/audit/*path",
+    "/audit-hash/*path",
+    "/audit-tail/*path",
+    "/quotas/rate-limit",
+    "/quotas/rate-limit/*name",
+    "/support-bundle",
+    "/mfa/totp/*name",
+    "/identity/export",
+    "/identity/import",
+];
+
 pub fn new_system_backend(context: Context) -> Backend {
-    let router = Router::new()
+    let mut router = Router::new()
         .route(
             "/unseal",
             create_with_config(
@@ -111,49 +154,140 @@ pub fn new_system_backend(context: Context) -> Backend {
                 },
             ),
         )
+        .route("/config/state/sanitized", read(handle_config_state))
+        .route(
+            "/config/reload",
+            create(handle_config_reload).update(handle_config_reload),
+        )
+        .route(
+            "/config/reload-cert",
+            create(handle_certificate_reload).update(handle_certificate_reload),
+        )
+        .route(
+            "/config/tamper",
+            read(handle_tamper_config_read).update(handle_tamper_config_update),
+        )
+        .route("/support-bundle", read(handle_support_bundle).binary())
         .route("/mounts", read(handle_mounts_list))
         .route(
             "/mounts/*path",
-            create(handle_mount)
-                .update(handle_update_mount)
-                .delete(handle_mount_disable),
+            create_with_config(handle_mount, RouteConfig::sudo())
+                .read(handle_mount_read)
+                .update_with_config(handle_update_mount, RouteConfig::sudo())
+                .delete_with_config(handle_mount_disable, RouteConfig::sudo()),
         )
+        .route("/mounts/*path/migrations", read(handle_mount_migrations))
+        .route("/mounts/*path/paths", read(handle_mount_paths))
         .route(
-            "/policies",
-            update(handle_create_policy)
-                .create(handle_create_policy)
-                .read(handle_list_policies),
+            "/mounts/*path/tune",
+            read(handle_mount_tune_read).update_with_config(handle_mount_tune, RouteConfig::sudo()),
+        )
+        .nest("/policies", policy::router())
+        .route(
+            "/token/revoke",
+            revoke(handle_token_revocation)
+                .create(handle_token_revoke)
+                .update(handle_token_revoke),
+        )
+        .route(
+            "/token/lookup",
+            create(handle_token_lookup).update(handle_token_lookup),
         )
-        .route("/policies/*name", delete(handle_delete_policy))
-        .route("/token/revoke", revoke(handle_token_revocation))
         .route("/token/renew", renew(handle_token_renewal))
-        .route("/leases/revoke/*lease_id", update(handle_lease_revocation))
-        .route("/leases/renew/*lease_id", update(handle_lease_renew))
-        .route("/leases/lookup/*lease_id", read(handle_lease_lookup))
+        .route("/token/tidy", update(handle_token_tidy))
+        .route(
+            "/token/create",
+            create(handle_token_create).update(handle_token_create),
+        )
+        .route(
+            "/token/revoke-by-policy",
+            update(handle_token_revocation_by_policy),
+        )
+        .route(
+            "/token/revoke-by-policy/*job_id",
+            read(handle_token_revocation_job_status),
+        )
+        .route(
+            "/token/lookup-self",
+            read_with_config(
+                handle_token_lookup_self,
+                RouteConfig {
+                    policy: AuthPolicy::Unauthenticated,
+                    state: vec![StorageState::Unsealed],
+                },
+            ),
+        )
         .route(
-            "/leases/revoke-mount/*prefix",
-            update(handle_lease_revocation_by_mount),
+            "/token/renew-self",
+            update_with_config(
+                handle_token_renew_self,
+                RouteConfig {
+                    policy: AuthPolicy::Unauthenticated,
+                    state: vec![StorageState::Unsealed],
+                },
+            ),
         )
-        .route("/leases/lookup-mount/*prefix", read(handle_list_leases))
         .route(
-            "/entity",
-            create(handle_entity_create).read(handle_list_entities),
+            "/token/revoke-self",
+            update_with_config(
+                handle_token_revoke_self,
+                RouteConfig {
+                    policy: AuthPolicy::Unauthenticated,
+                    state: vec![StorageState::Unsealed],
+                },
+            ),
+        )
+        .nest("/leases", lease::router())
+        .nest("/entity", entity::router())
+        .nest("/identity", identity::router())
+        .nest("/mfa", mfa::router())
+        .nest("/wrapping", wrapping::router())
+        .nest("/audit", audit::router())
+        .nest("/quotas", quota::router())
+        .nest("/static-secrets", static_secret::router())
+        .route(
+            "/audit-hash/*path",
+            create(audit::handle_audit_hash).update(audit::handle_audit_hash),
         )
-        .route("/entity/policy", update(handle_attach_entity_policy))
-        .route("/entity/policy/*name", update(handle_remove_entity_policy))
-        .route("/entity/alias", update(handle_attach_entity_alias))
-        .route("/entity/alias/*name", update(handle_remove_entity_alias))
+        .route("/audit-tail/*path", read(audit::handle_audit_tail).binary())
         .route(
             "/namespaces",
             create(create_namespace_handler).read(list_namespaces_handler),
         )
-        .route("/namespaces/*name", delete(delete_namespace_handler))
+        .route("/namespaces/*name", delete(delete_namespace_handler));
+
+    let metrics = context.config.metrics;
+    if metrics.enabled {
+        router = if metrics.unauthenticated {
+            router.route(
+                "/metrics",
+                read_with_config(
+                    handle_metrics,
+                    RouteConfig {
+                        policy: AuthPolicy::Unauthenticated,
+                        state: vec![
+                            StorageState::Uninitialized,
+                            StorageState::Sealed,
+                            StorageState::Unsealed,
+                        ],
+                    },
+                )
+                .binary(),
+            )
+        } else {
+            router.route("/metrics", read(handle_metrics).binary())
+        };
+    }
+
+    let router = router
+        .sudo_paths(SUDO_PATHS)
+        .leaseless()
         .layer(Extension(context))
-        .build()
-        .into_service();
+        .build();
 
     Backend {
-        handler: router,
+        paths: router.paths(),
+        handler: router.into_service(),
         category: BackendCategory::Logical,
         variant: BackendType::System,
         migrations: vec![],
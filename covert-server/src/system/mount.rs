@@ -1,41 +1,103 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
 
 use covert_framework::{
     extract::{Extension, Json, Path},
     Backend,
 };
 use covert_kv::new_versioned_kv_backend;
+use covert_ldap_auth::new_ldap_backend;
+use covert_oidc_auth::new_oidc_backend;
 use covert_psql::new_psql_backend;
-use covert_storage::{migrator::MigrationError, BackendStoragePool, EncryptedPool};
+use covert_storage::{migrator::list_migrations, BackendStoragePool, EncryptedPool};
+use covert_transit::new_transit_backend;
 use covert_types::{
     backend::BackendCategory,
     backend::BackendType,
     methods::system::{
-        CreateMountParams, CreateMountResponse, DisableMountResponse, MountsListItemResponse,
-        MountsListResponse, UpdateMountParams, UpdateMountResponse,
+        AppliedMigration, CreateMountParams, CreateMountResponse, DisableMountResponse,
+        MountMigrationsResponse, MountPathsResponse, MountResponse, MountTuneResponse, MountTuning,
+        MountsListItemResponse, MountsListResponse, PendingMigration, TuneMountParams,
+        UpdateMountParams, UpdateMountResponse,
     },
-    mount::{MountConfig, MountEntry},
+    mount::{ListingVisibility, MountConfig, MountEntry},
     response::Response,
 };
 use covert_userpass_auth::new_userpass_backend;
+use serde::Deserialize;
 use tracing::info;
 use uuid::Uuid;
 
 use crate::{
     context::Context,
     error::{Error, ErrorType},
+    layer::auth_service::TokenPolicies,
+    plugin::{new_plugin_backend, validate_plugin_config},
     repos::{namespace::Namespace, Repos},
 };
 
 use super::{new_system_backend, SYSTEM_MOUNT_PATH};
 
+/// The canonical form of a mount path, ending with a single slash, so `kv`
+/// and `kv/` are the same mount.
+pub(crate) fn normalize_mount_path(path: &str) -> Result<String, Error> {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() || trimmed.split('/').any(str::is_empty) {
+        return Err(ErrorType::BadRequest(format!("Invalid mount path `{path}`")).into());
+    }
+    Ok(format!("{trimmed}/"))
+}
+
+/// A mount limited to no concurrent requests could not serve any request.
+fn validate_concurrency_limit(config: &MountConfig) -> Result<(), Error> {
+    if config.max_concurrent_requests == Some(0) {
+        return Err(ErrorType::BadRequest(
+            "The max number of concurrent requests must be at least 1".into(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Only the fields of KV secrets can be encrypted, with keys of transit
+/// mounts given by their normalized path.
+fn validate_encrypted_fields(variant: BackendType, config: &MountConfig) -> Result<(), Error> {
+    if config.encrypted_fields.is_empty() {
+        return Ok(());
+    }
+    if variant != BackendType::Kv {
+        return Err(ErrorType::BadRequest(
+            "Only the fields of `kv` mounts can be encrypted".into(),
+        )
+        .into());
+    }
+    for (field, transit) in &config.encrypted_fields {
+        if normalize_mount_path(&transit.mount).ok().as_ref() != Some(&transit.mount)
+            || transit.key.is_empty()
+        {
+            return Err(ErrorType::BadRequest(format!(
+                "Field `{field}` must be encrypted with a key of a transit mount path like \
+                 `transit/`"
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Path parameters of the routes operating on a single mount.
+#[derive(Debug, Deserialize)]
+pub struct MountPath {
+    path: String,
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn handle_mount(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(path): Path<String>,
+    Path(MountPath { path }): Path<MountPath>,
     Json(body): Json<CreateMountParams>,
 ) -> Result<Response, Error> {
+    let path = normalize_mount_path(&path)?;
     let id = mount(
         &ctx,
         path.clone(),
@@ -56,9 +118,10 @@ pub async fn handle_mount(
 pub async fn handle_update_mount(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(path): Path<String>,
+    Path(MountPath { path }): Path<MountPath>,
     Json(body): Json<UpdateMountParams>,
 ) -> Result<Response, Error> {
+    let path = normalize_mount_path(&path)?;
     let me = update_mount(&ctx.repos, &path, &ns.id, body.config).await?;
     let resp = UpdateMountResponse {
         variant: me.backend_type,
@@ -72,11 +135,31 @@ pub async fn handle_update_mount(
 pub async fn handle_mounts_list(
     Extension(ns): Extension<Namespace>,
     Extension(ctx): Extension<Context>,
+    policies: Option<Extension<TokenPolicies>>,
 ) -> Result<Response, Error> {
+    let mut resp = mounts_list(&ctx, &ns.id).await?;
+
+    // Hidden mounts are only listed to callers with a policy for the mount
+    let policies = policies.map(|Extension(TokenPolicies(policies))| policies);
+    let namespace_prefix = ctx.repos.namespace.get_full_path(&ns.id).await?;
+    let visible = |mount: &MountsListItemResponse| {
+        mount.config.listing_visibility != ListingVisibility::Hidden
+            || policies.iter().flatten().any(|policy| {
+                policy.can_access_prefix(&format!("{namespace_prefix}/{}", mount.path))
+            })
+    };
+    resp.auth.retain(visible);
+    resp.secret.retain(visible);
+
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// The mounts of the namespace grouped by category.
+pub async fn mounts_list(ctx: &Context, namespace_id: &str) -> Result<MountsListResponse, Error> {
     let mut auth = vec![];
     let mut secret = vec![];
 
-    let mounts = ctx.repos.mount.list(&ns.id).await?;
+    let mounts = ctx.repos.mount.list(namespace_id).await?;
 
     for mount in mounts {
         let mount = MountsListItemResponse {
@@ -93,15 +176,15 @@ pub async fn handle_mounts_list(
         }
     }
 
-    let resp = MountsListResponse { auth, secret };
-    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+    Ok(MountsListResponse { auth, secret })
 }
 
 pub async fn handle_mount_disable(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
-    Path(path): Path<String>,
+    Path(MountPath { path }): Path<MountPath>,
 ) -> Result<Response, Error> {
+    let path = normalize_mount_path(&path)?;
     let mount = remove_mount(&ctx, &path, &ns.id).await?;
     let resp = DisableMountResponse {
         mount: MountsListItemResponse {
@@ -126,7 +209,24 @@ async fn update_mount(
         .get_by_path(path, namespace_id)
         .await?
         .ok_or_else(|| ErrorType::MountNotFound { path: path.into() })?;
-    me.config = config;
+    if config.require_mfa && BackendCategory::from(me.backend_type) != BackendCategory::Credential {
+        return Err(ErrorType::MfaOnLogicalBackend.into());
+    }
+    validate_concurrency_limit(&config)?;
+    validate_encrypted_fields(me.backend_type, &config)?;
+    // The plugin is kept when it is left out
+    match &config.plugin {
+        Some(plugin) if me.config.plugin.as_ref() != Some(plugin) => {
+            return Err(
+                ErrorType::BadRequest("The plugin of a mount cannot be changed".into()).into(),
+            );
+        }
+        _ => (),
+    }
+    me.config = MountConfig {
+        plugin: me.config.plugin.take(),
+        ..config
+    };
     repos
         .mount
         .set_config(&me.path, namespace_id, &me.config)
@@ -135,6 +235,68 @@ async fn update_mount(
     Ok(me)
 }
 
+/// Apply the changes to the tuning of the mount, the other settings are kept.
+async fn tune_mount(
+    repos: &Repos,
+    path: &str,
+    namespace_id: &str,
+    params: TuneMountParams,
+) -> Result<MountEntry, Error> {
+    let me = repos
+        .mount
+        .get_by_path(path, namespace_id)
+        .await?
+        .ok_or_else(|| ErrorType::MountNotFound { path: path.into() })?;
+    let mut config = me.config;
+    if let Some(ttl) = params.default_lease_ttl {
+        config.default_lease_ttl = ttl;
+    }
+    if let Some(ttl) = params.max_lease_ttl {
+        config.max_lease_ttl = ttl;
+    }
+    if config.default_lease_ttl > config.max_lease_ttl {
+        return Err(ErrorType::BadRequest(
+            "The default lease TTL cannot exceed the max lease TTL".into(),
+        )
+        .into());
+    }
+    if let Some(description) = params.description {
+        config.description = Some(description).filter(|description| !description.is_empty());
+    }
+    if let Some(listing_visibility) = params.listing_visibility {
+        config.listing_visibility = listing_visibility;
+    }
+    if let Some(require_mfa) = params.require_mfa {
+        config.require_mfa = require_mfa;
+    }
+    if let Some(size) = params.max_request_body_size {
+        config.max_request_body_size = size;
+    }
+    if let Some(limit) = params.max_concurrent_requests {
+        config.max_concurrent_requests = limit;
+    }
+    update_mount(repos, path, namespace_id, config).await
+}
+
+/// The tuning of the mount with the limits of the server applied.
+fn tune_response(ctx: &Context, me: MountEntry) -> Result<Response, Error> {
+    let config = &me.config;
+    let effective_max_lease_ttl = ctx
+        .expiration_manager
+        .max_lease_ttl()
+        .map_or(config.max_lease_ttl, |max| max.min(config.max_lease_ttl));
+    let effective_max_request_body_size = config
+        .max_request_body_size
+        .unwrap_or_else(|| u64::try_from(ctx.config.max_request_body_size).unwrap_or(u64::MAX));
+    let resp = MountTuneResponse {
+        tuning: MountTuning::from(config),
+        path: me.path,
+        effective_max_lease_ttl,
+        effective_max_request_body_size,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn remove_mount(
     ctx: &Context,
@@ -158,11 +320,13 @@ pub async fn remove_mount(
     }
 
     ctx.expiration_manager
-        .revoke_leases_by_mount_prefix(path, namespace_id)
+        .revoke_leases_by_mount_prefix(path, namespace_id, &BTreeMap::new())
         .await?;
     if !ctx.router.remove(me.id) {
         return Err(ErrorType::MountNotFound { path: path.into() }.into());
     }
+    // Stopped once the leases of the plugin are revoked
+    ctx.plugins.stop(me.id).await;
     ctx.repos.mount.remove_by_path(path, namespace_id).await?;
 
     // Delete all storage for the mount
@@ -194,16 +358,18 @@ pub async fn mount_route_entry(
     ctx: &Context,
     id: Uuid,
     variant: BackendType,
+    config: &MountConfig,
     namespace_id: &str,
 ) -> Result<(Arc<Backend>, String), Error> {
-    let namespace_id = Uuid::from_str(namespace_id).map_err(|_| {
+    let namespace_uuid = Uuid::from_str(namespace_id).map_err(|_| {
         ErrorType::InternalError(anyhow::Error::msg("Namespace id was not a valid UUID"))
     })?;
     let backend_storage =
-        storage_pool_for_backend(Arc::clone(&ctx.repos.pool), namespace_id, variant, id);
+        storage_pool_for_backend(Arc::clone(&ctx.repos.pool), namespace_uuid, variant, id);
 
     let prefix = backend_storage.prefix().to_string();
-    let backend = Arc::new(new_backend(ctx, backend_storage, variant).await?);
+    let backend =
+        Arc::new(new_backend(ctx, id, backend_storage, variant, config, namespace_id).await?);
 
     ctx.router.mount(id, Arc::clone(&backend));
 
@@ -212,15 +378,23 @@ pub async fn mount_route_entry(
 
 async fn new_backend(
     ctx: &Context,
+    id: Uuid,
     storage: BackendStoragePool,
     variant: BackendType,
-) -> Result<Backend, MigrationError> {
-    match variant {
-        BackendType::Kv => new_versioned_kv_backend(storage),
-        BackendType::Postgres => new_psql_backend(storage).await,
-        BackendType::System => Ok(new_system_backend(ctx.clone())),
-        BackendType::Userpass => new_userpass_backend(storage),
-    }
+    config: &MountConfig,
+    namespace_id: &str,
+) -> Result<Backend, Error> {
+    let backend = match variant {
+        BackendType::Kv => new_versioned_kv_backend(storage)?,
+        BackendType::Ldap => new_ldap_backend(storage)?,
+        BackendType::Oidc => new_oidc_backend(storage)?,
+        BackendType::Plugin => new_plugin_backend(ctx, id, namespace_id, config).await?,
+        BackendType::Postgres => new_psql_backend(storage).await?,
+        BackendType::System => new_system_backend(ctx.clone()),
+        BackendType::Transit => new_transit_backend(storage)?,
+        BackendType::Userpass => new_userpass_backend(storage)?,
+    };
+    Ok(backend)
 }
 
 /// Mount a new backend
@@ -232,6 +406,9 @@ pub async fn mount(
     variant: BackendType,
     mount_config: MountConfig,
 ) -> Result<Uuid, Error> {
+    let path = normalize_mount_path(&path)?;
+    validate_concurrency_limit(&mount_config)?;
+    validate_encrypted_fields(variant, &mount_config)?;
     if variant == BackendType::System {
         return Err(ErrorType::InvalidMountType {
             variant: BackendType::System,
@@ -256,6 +433,19 @@ pub async fn mount(
         .into());
     }
 
+    if let Some(namespace) = ctx
+        .repos
+        .namespace
+        .find_child(&namespace_id, path.split('/').next().unwrap_or_default())
+        .await?
+    {
+        return Err(ErrorType::NamespacePathConflict {
+            path,
+            namespace: namespace.name,
+        }
+        .into());
+    }
+
     let is_auth_path = path.starts_with("auth/");
     let is_auth_backend = BackendCategory::from(variant) == BackendCategory::Credential;
     if is_auth_backend != is_auth_path {
@@ -265,10 +455,21 @@ pub async fn mount(
 
         return Err(ErrorType::LogicalBackendUnderAuthPath)?;
     }
+    if mount_config.require_mfa && !is_auth_backend {
+        return Err(ErrorType::MfaOnLogicalBackend)?;
+    }
+    if variant == BackendType::Plugin {
+        validate_plugin_config(ctx, &mount_config)?;
+    } else if mount_config.plugin.is_some() {
+        return Err(ErrorType::BadRequest(
+            "Only plugin mounts can set `config.plugin`".into(),
+        ))?;
+    }
 
     // Mount internally
     let uuid = Uuid::new_v4();
-    let (backend, prefix) = mount_route_entry(ctx, uuid, variant, &namespace_id).await?;
+    let (backend, prefix) =
+        mount_route_entry(ctx, uuid, variant, &mount_config, &namespace_id).await?;
 
     let entry = MountEntry {
         id: uuid,
@@ -280,16 +481,192 @@ pub async fn mount(
     // TODO: remove entry from the internal router if it fails to store in db
     ctx.repos.mount.create(&entry).await?;
 
-    if !backend.migrations.is_empty() {
-        backend
-            .migrate(Arc::clone(&ctx.repos.pool), &uuid.to_string(), &prefix)
-            .await
-            .map_err(|error| ErrorType::BackendMigration { error, variant })?;
+    // Abort the mount if the backend storage cannot be set up
+    if let Err(error) = migrate_backend(ctx, &backend, uuid, &prefix).await {
+        let _ = ctx.router.remove(uuid);
+        ctx.plugins.stop(uuid).await;
+        ctx.repos
+            .mount
+            .remove_by_path(&entry.path, &entry.namespace_id)
+            .await?;
+        return Err(error);
     }
 
     Ok(uuid)
 }
 
+/// Mount the backends of the mounts created and unmount the backends of the
+/// mounts removed since the last call, e.g. by the active node of an HA
+/// cluster. The migrations of new mounts are left to the node that created
+/// them.
+pub async fn sync_mounts(ctx: &Context) -> Result<(), Error> {
+    let mounts = ctx.repos.mount.list_all().await?;
+    for id in ctx.router.mount_ids() {
+        if !mounts.iter().any(|mount| mount.id == id) {
+            let _ = ctx.router.remove(id);
+            ctx.plugins.stop(id).await;
+        }
+    }
+    for mount in mounts {
+        if ctx.router.get(mount.id).is_some() {
+            continue;
+        }
+        if let Err(error) = mount_route_entry(
+            ctx,
+            mount.id,
+            mount.backend_type,
+            &mount.config,
+            &mount.namespace_id,
+        )
+        .await
+        {
+            tracing::error!(?error, path = mount.path, "Failed to mount backend");
+        }
+    }
+    Ok(())
+}
+
+/// Apply the pending migrations for a mounted backend. The migrations are
+/// applied in a single transaction so a failure leaves the backend storage
+/// untouched. A mount whose applied migrations don't match the migrations of
+/// the backend is refused unless `ignore-migration-checksums` is set.
+#[tracing::instrument(skip(ctx, backend))]
+pub async fn migrate_backend(
+    ctx: &Context,
+    backend: &Backend,
+    id: Uuid,
+    prefix: &str,
+) -> Result<(), Error> {
+    if backend.migrations.is_empty() {
+        return Ok(());
+    }
+
+    backend
+        .migrate(
+            Arc::clone(&ctx.repos.pool),
+            &id.to_string(),
+            prefix,
+            !ctx.config.ignore_migration_checksums,
+        )
+        .await
+        .map_err(|error| {
+            ErrorType::BackendMigration {
+                error,
+                variant: backend.variant,
+            }
+            .into()
+        })
+}
+
+/// The mount at the path and the backend serving it.
+async fn mount_with_backend(
+    ctx: &Context,
+    path: &str,
+    namespace_id: &str,
+) -> Result<(MountEntry, Arc<Backend>), Error> {
+    let path = normalize_mount_path(path)?;
+    let me = ctx
+        .repos
+        .mount
+        .get_by_path(&path, namespace_id)
+        .await?
+        .ok_or_else(|| ErrorType::MountNotFound { path: path.clone() })?;
+    let backend = ctx
+        .router
+        .get(me.id)
+        .ok_or(ErrorType::MountNotFound { path })?;
+    Ok((me, backend))
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_mount_read(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(MountPath { path }): Path<MountPath>,
+) -> Result<Response, Error> {
+    let (me, _) = mount_with_backend(&ctx, &path, &ns.id).await?;
+
+    let applied = list_migrations(ctx.repos.pool.as_ref(), &me.id.to_string()).await?;
+    let schema_version = applied.last().map(|migration| migration.version);
+
+    let resp = MountResponse {
+        id: me.id,
+        path: me.path,
+        category: me.backend_type.into(),
+        variant: me.backend_type,
+        config: me.config,
+        schema_version: schema_version.and_then(|version| u64::try_from(version).ok()),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_mount_migrations(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(MountPath { path }): Path<MountPath>,
+) -> Result<Response, Error> {
+    let (me, backend) = mount_with_backend(&ctx, &path, &ns.id).await?;
+    let applied = list_migrations(ctx.repos.pool.as_ref(), &me.id.to_string()).await?;
+    let schema_version = applied.last().map(|migration| migration.version);
+
+    let pending = backend
+        .migrations
+        .iter()
+        .filter(|migration| schema_version.is_none_or(|version| migration.version > version))
+        .map(|migration| PendingMigration {
+            version: u64::try_from(migration.version).unwrap_or_default(),
+            description: migration.description.clone(),
+        })
+        .collect();
+    let applied = applied
+        .into_iter()
+        .map(|migration| AppliedMigration {
+            version: u64::try_from(migration.version).unwrap_or_default(),
+            description: migration.description,
+            applied_at: migration.created_at,
+        })
+        .collect();
+
+    let resp = MountMigrationsResponse { applied, pending };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_mount_paths(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(MountPath { path }): Path<MountPath>,
+) -> Result<Response, Error> {
+    let (_, backend) = mount_with_backend(&ctx, &path, &ns.id).await?;
+    let resp = MountPathsResponse {
+        paths: backend.paths.clone(),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_mount_tune_read(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(MountPath { path }): Path<MountPath>,
+) -> Result<Response, Error> {
+    let (me, _) = mount_with_backend(&ctx, &path, &ns.id).await?;
+    tune_response(&ctx, me)
+}
+
+#[tracing::instrument(skip(ctx))]
+pub async fn handle_mount_tune(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(MountPath { path }): Path<MountPath>,
+    Json(body): Json<TuneMountParams>,
+) -> Result<Response, Error> {
+    let path = normalize_mount_path(&path)?;
+    let me = tune_mount(&ctx.repos, &path, &ns.id, body).await?;
+    tune_response(&ctx, me)
+}
+
 pub fn storage_pool_for_backend(
     pool: Arc<EncryptedPool>,
     namespace_id: Uuid,
@@ -311,7 +688,9 @@ mod tests {
     use sqlx::SqlitePool;
 
     use crate::{
-        context::ChildProcesses, expiration_manager::clock::SystemClock, repos::mount::tests::pool,
+        context::{ChildProcesses, TokenRevocationJobs},
+        expiration_manager::clock::SystemClock,
+        repos::mount::tests::pool,
         Config, ExpirationManager, Router,
     };
 
@@ -325,10 +704,9 @@ mod tests {
 
         Context {
             config: Arc::new(Config {
-                port: 0,
-                port_tx: None,
-                replication: None,
-                storage_path: String::new(),
+                port: Some(0),
+                tls_disable: true,
+                ..Default::default()
             }),
             child_processes: ChildProcesses::default(),
             expiration_manager: Arc::new(ExpirationManager::new(
@@ -338,6 +716,16 @@ mod tests {
             )),
             repos,
             router,
+            token_revocation_jobs: TokenRevocationJobs::default(),
+            audit: Arc::default(),
+            quotas: Arc::default(),
+            idempotency: Arc::default(),
+            reload: Arc::default(),
+            tamper: Arc::default(),
+            pending_logins: Arc::default(),
+            snapshot_key: Arc::default(),
+            plugins: Arc::default(),
+            ha: Arc::new(crate::ha::HaState::new(None).unwrap()),
         }
     }
 
@@ -389,11 +777,59 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn plugin_needs_to_be_registered() {
+        let ctx = create_context().await;
+
+        let namespace_id = Uuid::new_v4().to_string();
+        let variant = BackendType::Plugin;
+        let err = mount(
+            &ctx,
+            "internal/".to_string(),
+            namespace_id.clone(),
+            variant,
+            MountConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err.variant, ErrorType::PluginNameRequired));
+
+        let config = MountConfig {
+            plugin: Some("internal".to_string()),
+            ..Default::default()
+        };
+        let err = mount(
+            &ctx,
+            "internal/".to_string(),
+            namespace_id.clone(),
+            variant,
+            config.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.variant,
+            ErrorType::PluginNotRegistered { name } if name == "internal"
+        ));
+
+        // Only plugin mounts name a plugin
+        let err = mount(
+            &ctx,
+            "kv/".to_string(),
+            namespace_id,
+            BackendType::Kv,
+            config,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err.variant, ErrorType::BadRequest(_)));
+    }
+
     #[tokio::test]
     async fn cannot_mount_at_path_that_collides_with_sys() {
         let ctx = create_context().await;
 
-        let bad_paths = ["", "sy", "sys", "sys/", "sys/new/"];
+        let bad_paths = ["sys", "sys/", "sys//", "sys/new/"];
 
         for path in bad_paths {
             let namespace_id = Uuid::new_v4().to_string();
@@ -413,4 +849,20 @@ mod tests {
             ));
         }
     }
+
+    #[test]
+    fn normalize_mount_paths() {
+        for (path, expected) in [
+            ("secret", "secret/"),
+            ("secret/", "secret/"),
+            ("secret//", "secret/"),
+            ("auth/userpass", "auth/userpass/"),
+        ] {
+            assert_eq!(normalize_mount_path(path).unwrap(), expected);
+        }
+        for path in ["", "/", "a//b/", "/secret/"] {
+            let err = normalize_mount_path(path).unwrap_err();
+            assert!(matches!(err.variant, ErrorType::BadRequest(_)));
+        }
+    }
 }
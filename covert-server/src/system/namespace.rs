@@ -20,12 +20,57 @@ use crate::{
 
 use super::mount::remove_mount;
 
+/// Names that would shadow the system backend or the auth methods when the
+/// namespace is selected with a path prefix.
+const RESERVED_NAMESPACE_NAMES: &[&str] = &["sys", "auth"];
+
+fn validate_namespace_name(name: &str) -> Result<(), Error> {
+    let error = if name.is_empty() {
+        Some("Name cannot be empty")
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        Some("Name can only contain lowercase letters, digits, `-` and `_`")
+    } else if RESERVED_NAMESPACE_NAMES.contains(&name) {
+        Some("Name is reserved")
+    } else {
+        None
+    };
+    match error {
+        Some(error) => Err(ErrorType::InvalidNamespaceName {
+            name: name.to_string(),
+            error: error.to_string(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn create_namespace_handler(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
     Json(params): Json<CreateNamespaceParams>,
 ) -> Result<Response, Error> {
+    validate_namespace_name(&params.name)?;
+    // The namespace would hide the mount from requests selecting namespaces
+    // with a path prefix
+    if let Some(mount) = ctx
+        .repos
+        .mount
+        .list(&ns.id)
+        .await?
+        .into_iter()
+        .find(|mount| mount.path.split('/').next() == Some(params.name.as_str()))
+    {
+        return Err(ErrorType::NamespacePathConflict {
+            path: mount.path,
+            namespace: params.name,
+        }
+        .into());
+    }
+
     let new_namespace = Namespace {
         id: Uuid::new_v4().to_string(),
         name: params.name,
@@ -1,7 +1,12 @@
-use covert_framework::extract::{Extension, Json, Path};
+use covert_framework::{
+    delete,
+    extract::{Extension, Json, Path},
+    read, update, Router,
+};
 use covert_types::{
     methods::system::{
-        CreatePolicyParams, CreatePolicyResponse, ListPolicyResponse, RemovePolicyResponse,
+        CreatePolicyParams, CreatePolicyResponse, FormatPolicyParams, FormatPolicyResponse,
+        ListPolicyResponse, ReadPolicyResponse, RemovePolicyResponse,
     },
     policy::{PathPolicy, Policy},
     response::Response,
@@ -13,19 +18,49 @@ use crate::{
     repos::namespace::Namespace,
 };
 
+/// Routes for managing policies, nested under `/policies`.
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/",
+            update(handle_create_policy)
+                .create(handle_create_policy)
+                .read(handle_list_policies),
+        )
+        .route(
+            "/format",
+            update(handle_format_policy).create(handle_format_policy),
+        )
+        .route(
+            "/*name",
+            read(handle_read_policy).delete(handle_delete_policy),
+        )
+}
+
 pub async fn handle_create_policy(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
     Json(body): Json<CreatePolicyParams>,
 ) -> Result<Response, Error> {
     let path_policies = PathPolicy::parse(&body.policy)
-        .map_err(|_| ErrorType::BadRequest("Malformed policy".into()))?;
+        .map(PathPolicy::canonicalize)
+        .map_err(|err| ErrorType::BadRequest(format!("Malformed policy, {err}")))?;
     let policy = Policy::new(body.name, path_policies, ns.id.clone());
     ctx.repos.policy.create(&policy).await?;
     let resp = CreatePolicyResponse { policy };
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
 
+pub async fn handle_format_policy(Json(body): Json<FormatPolicyParams>) -> Result<Response, Error> {
+    let path_policies = PathPolicy::parse(&body.policy)
+        .map(PathPolicy::canonicalize)
+        .map_err(|err| ErrorType::BadRequest(format!("Malformed policy, {err}")))?;
+    let resp = FormatPolicyResponse {
+        policy: PathPolicy::format(&path_policies),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 pub async fn handle_list_policies(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
@@ -35,6 +70,24 @@ pub async fn handle_list_policies(
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
 
+pub async fn handle_read_policy(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let policy = ctx
+        .repos
+        .policy
+        .lookup(&name, &ns.id)
+        .await?
+        .ok_or_else(|| ErrorType::NotFound(format!("Policy `{name}` not found")))?;
+    let resp = ReadPolicyResponse {
+        document: PathPolicy::format(&policy.paths),
+        policy,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 pub async fn handle_delete_policy(
     Extension(ctx): Extension<Context>,
     Extension(ns): Extension<Namespace>,
@@ -0,0 +1,159 @@
+use chrono::Utc;
+use covert_framework::{
+    create,
+    extract::{Extension, Json, Path},
+    read, Router,
+};
+use covert_types::{
+    methods::system::{
+        DeleteRateLimitQuotaResponse, ListRateLimitQuotasResponse, QuotaScope, RateLimitQuota,
+        RateLimitQuotaParams, ReadRateLimitQuotaResponse,
+    },
+    response::Response,
+};
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    repos::namespace::Namespace,
+};
+
+/// Routes for managing rate limit quotas, nested under `/quotas`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/rate-limit", read(handle_list_rate_limit_quotas))
+        .route(
+            "/rate-limit/*name",
+            create(handle_set_rate_limit_quota)
+                .read(handle_read_rate_limit_quota)
+                .update(handle_set_rate_limit_quota)
+                .delete(handle_delete_rate_limit_quota),
+        )
+}
+
+fn validate(params: &RateLimitQuotaParams) -> Result<u32, Error> {
+    if !params.rate.is_finite() || params.rate <= 0.0 {
+        return Err(ErrorType::BadRequest("Rate must be a positive number".into()).into());
+    }
+    if let QuotaScope::Mount { path } | QuotaScope::Entity { name: path } = &params.scope {
+        if path.is_empty() {
+            return Err(ErrorType::BadRequest("Quota scope cannot be empty".into()).into());
+        }
+    }
+    match params.burst {
+        Some(0) => Err(ErrorType::BadRequest("Burst must be at least 1".into()).into()),
+        Some(burst) => Ok(burst),
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        None => Ok(params.rate.ceil().min(f64::from(u32::MAX)) as u32),
+    }
+}
+
+pub async fn handle_set_rate_limit_quota(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+    Json(body): Json<RateLimitQuotaParams>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::QuotaInNonRootNamespace.into());
+    }
+    let burst = validate(&body)?;
+    let quota = ctx
+        .repos
+        .quota
+        .upsert(&RateLimitQuota {
+            name,
+            scope: body.scope,
+            rate: body.rate,
+            burst,
+            exempt_root: body.exempt_root,
+            created_at: Utc::now(),
+        })
+        .await?;
+    ctx.quotas.set(quota.clone());
+
+    Response::raw(quota).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_list_rate_limit_quotas(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::QuotaInNonRootNamespace.into());
+    }
+    let resp = ListRateLimitQuotasResponse {
+        quotas: ctx.repos.quota.list().await?,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_read_rate_limit_quota(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::QuotaInNonRootNamespace.into());
+    }
+    let limiter = ctx
+        .quotas
+        .get(&name)
+        .ok_or(ErrorType::QuotaNotFound { name })?;
+
+    let resp = ReadRateLimitQuotaResponse {
+        quota: limiter.quota().clone(),
+        usage: limiter.usage(),
+        entities: limiter.entity_usage(),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_delete_rate_limit_quota(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::QuotaInNonRootNamespace.into());
+    }
+    let Some(quota) = ctx.repos.quota.lookup(&name).await? else {
+        return Err(ErrorType::QuotaNotFound { name }.into());
+    };
+    ctx.repos.quota.remove(&name).await?;
+    ctx.quotas.remove(&name);
+
+    let resp = DeleteRateLimitQuotaResponse { quota };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Load the persisted quotas. Called on unseal, the buckets of the quotas
+/// start full.
+pub async fn load_quotas(ctx: &Context, root_namespace: &Namespace) -> Result<(), Error> {
+    ctx.quotas.set_root_namespace_id(root_namespace.id.clone());
+    for quota in ctx.repos.quota.list().await? {
+        ctx.quotas.set(quota);
+    }
+    Ok(())
+}
+
+/// Apply the quotas set and removed since the last call, e.g. by the active
+/// node of an HA cluster. The buckets of unchanged quotas are kept.
+pub async fn sync_quotas(ctx: &Context) -> Result<(), Error> {
+    let quotas = ctx.repos.quota.list().await?;
+    for name in ctx.quotas.names() {
+        if !quotas.iter().any(|quota| quota.name == name) {
+            ctx.quotas.remove(&name);
+        }
+    }
+    for quota in quotas {
+        let changed = ctx
+            .quotas
+            .get(&quota.name)
+            .is_none_or(|limiter| limiter.quota() != &quota);
+        if changed {
+            ctx.quotas.set(quota);
+        }
+    }
+    Ok(())
+}
@@ -24,14 +24,32 @@ pub async fn handle_seal(
 }
 
 #[tracing::instrument(skip_all)]
-async fn seal(ctx: &Context) -> Result<(), Error> {
+pub(crate) async fn seal(ctx: &Context) -> Result<(), Error> {
     info!("Sealing the storage");
     ctx.repos.pool.seal()?;
+    ctx.snapshot_key.clear();
+    // Unsealing always starts over without any key shares
+    ctx.repos.seal.clear_key_shares().await?;
 
     // Stop expiration manager
     ctx.expiration_manager.stop().await;
 
-    // Clear all the route entries except system
+    // A sealed node cannot be the active node of an HA cluster
+    crate::ha::step_down(ctx).await;
+
+    unmount_all(ctx)?;
+    ctx.plugins.stop_all().await;
+
+    // Reopened from storage on unseal
+    ctx.audit.clear();
+    ctx.quotas.clear();
+    ctx.idempotency.clear();
+
+    Ok(())
+}
+
+/// Clear all the route entries except system.
+pub(super) fn unmount_all(ctx: &Context) -> Result<(), Error> {
     let system = ctx.router.get_system_mount().ok_or_else(|| {
         ErrorType::InternalError(anyhow::Error::msg(
             "router does not contain the system backend",
@@ -39,6 +57,5 @@ async fn seal(ctx: &Context) -> Result<(), Error> {
     })?;
     ctx.router.clear_mounts();
     ctx.router.mount_system(system);
-
     Ok(())
 }
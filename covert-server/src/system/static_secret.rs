@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use covert_framework::{
+    create,
+    extract::{Extension, Json, Path},
+    read, update, Router,
+};
+use covert_types::{
+    methods::system::{
+        DeleteStaticSecretResponse, ListStaticSecretsResponse, ReadPreviousStaticSecretResponse,
+        ReadStaticSecretResponse, RotationHandler, SetStaticSecretParams, StaticSecret,
+    },
+    request::Operation,
+    response::Response,
+};
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    layer::auth_service::TokenPolicies,
+    repos::namespace::Namespace,
+    rotation,
+};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+/// About 10 years.
+const MAX_DURATION: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+const MIN_RANDOM_LENGTH: usize = 8;
+const MAX_RANDOM_LENGTH: usize = 4096;
+
+/// Routes for managing static secrets, nested under `/static-secrets`.
+pub fn router() -> Router {
+    Router::new()
+        .route("/", read(handle_list_static_secrets))
+        .route(
+            "/:name",
+            create(handle_set_static_secret)
+                .read(handle_read_static_secret)
+                .update(handle_set_static_secret)
+                .delete(handle_delete_static_secret),
+        )
+        .route("/:name/previous", read(handle_read_previous_static_secret))
+        .route(
+            "/:name/rotate",
+            update(handle_rotate_static_secret).create(handle_rotate_static_secret),
+        )
+}
+
+fn validate(params: &SetStaticSecretParams) -> Result<Duration, Error> {
+    if params.interval < MIN_INTERVAL || params.interval > MAX_DURATION {
+        return Err(ErrorType::BadRequest(
+            "Rotation interval must be between 1 minute and 10 years".into(),
+        )
+        .into());
+    }
+    let grace = params.grace.unwrap_or(params.interval);
+    if grace > MAX_DURATION {
+        return Err(ErrorType::BadRequest("Grace period cannot exceed 10 years".into()).into());
+    }
+    match &params.handler {
+        RotationHandler::Random { length }
+            if !(MIN_RANDOM_LENGTH..=MAX_RANDOM_LENGTH).contains(length) =>
+        {
+            Err(ErrorType::BadRequest(format!(
+                "Length of random values must be between {MIN_RANDOM_LENGTH} and \
+                 {MAX_RANDOM_LENGTH}"
+            ))
+            .into())
+        }
+        RotationHandler::Backend { path } if path.is_empty() || path.starts_with("sys/") => {
+            Err(ErrorType::BadRequest("Rotation path must be a path of a mount".into()).into())
+        }
+        _ => Ok(grace),
+    }
+}
+
+/// The backend request of a rotation is made by the server, so the caller
+/// must be allowed to make it.
+async fn authorize_handler(
+    ctx: &Context,
+    ns: &Namespace,
+    handler: &RotationHandler,
+    policies: Option<Extension<TokenPolicies>>,
+) -> Result<(), Error> {
+    let RotationHandler::Backend { path } = handler else {
+        return Ok(());
+    };
+    let policies = policies.map_or_else(Vec::new, |Extension(TokenPolicies(policies))| policies);
+    let namespace_prefix = ctx.repos.namespace.get_full_path(&ns.id).await?;
+    let path = format!("{namespace_prefix}/{path}");
+    let allowed = !policies.iter().any(|policy| policy.denies(&path))
+        && policies
+            .iter()
+            .any(|policy| policy.is_authorized(&path, &[Operation::Update]));
+    if allowed {
+        Ok(())
+    } else {
+        Err(ErrorType::Unauthorized(format!("Not allowed to update `{path}`")).into())
+    }
+}
+
+pub async fn handle_set_static_secret(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+    policies: Option<Extension<TokenPolicies>>,
+    Json(mut body): Json<SetStaticSecretParams>,
+) -> Result<Response, Error> {
+    if let RotationHandler::Backend { path } = &mut body.handler {
+        *path = path.trim_start_matches('/').to_string();
+    }
+    let grace = validate(&body)?;
+    authorize_handler(&ctx, &ns, &body.handler, policies).await?;
+
+    let configured = ctx
+        .repos
+        .static_secret
+        .configure(&ns.id, &name, &body.handler, body.interval, grace)
+        .await?;
+    let entry = match configured {
+        Some(entry) => entry,
+        None => {
+            let now = Utc::now();
+            let secret = StaticSecret {
+                name: name.clone(),
+                handler: body.handler,
+                interval: body.interval,
+                grace,
+                version: 0,
+                rotated_at: now,
+                next_rotation_at: now,
+            };
+            rotation::create(&ctx, &ns, secret)
+                .await?
+                // Created by a concurrent request in the meantime
+                .ok_or_else(|| ErrorType::BadRequest(format!("Static secret `{name}` exists")))?
+        }
+    };
+
+    Response::raw(entry.secret).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_list_static_secrets(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    let resp = ListStaticSecretsResponse {
+        secrets: ctx.repos.static_secret.list(&ns.id).await?,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_read_static_secret(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let Some(entry) = ctx.repos.static_secret.lookup(&ns.id, &name).await? else {
+        return Err(ErrorType::StaticSecretNotFound { name }.into());
+    };
+    let resp = ReadStaticSecretResponse {
+        secret: entry.secret,
+        data: entry.current,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_read_previous_static_secret(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let Some(entry) = ctx.repos.static_secret.lookup(&ns.id, &name).await? else {
+        return Err(ErrorType::StaticSecretNotFound { name }.into());
+    };
+    let grace_until = entry.grace_until();
+    let previous = entry.previous.filter(|_| Utc::now() < grace_until);
+    let Some(data) = previous else {
+        return Err(ErrorType::NoPreviousStaticSecret { name }.into());
+    };
+    let resp = ReadPreviousStaticSecretResponse {
+        version: entry.secret.version - 1,
+        data,
+        grace_until,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_rotate_static_secret(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let Some(entry) = rotation::rotate(&ctx, &ns.id, &name, None).await? else {
+        return Err(ErrorType::StaticSecretNotFound { name }.into());
+    };
+    Response::raw(entry.secret).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+pub async fn handle_delete_static_secret(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(name): Path<String>,
+) -> Result<Response, Error> {
+    let Some(entry) = ctx.repos.static_secret.lookup(&ns.id, &name).await? else {
+        return Err(ErrorType::StaticSecretNotFound { name }.into());
+    };
+    ctx.repos.static_secret.remove(&ns.id, &name).await?;
+
+    let resp = DeleteStaticSecretResponse {
+        secret: entry.secret,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
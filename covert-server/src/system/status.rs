@@ -6,10 +6,11 @@ use crate::{
     error::{Error, ErrorType},
 };
 
-#[allow(clippy::unused_async)]
 pub async fn handle_status(Extension(ctx): Extension<Context>) -> Result<Response, Error> {
     let resp = StatusResponse {
         state: ctx.repos.pool.state(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ha: crate::ha::status(&ctx).await?,
     };
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
@@ -0,0 +1,203 @@
+use chrono::Utc;
+use covert_framework::extract::Extension;
+use covert_types::{
+    methods::system::{ReadAuditDeviceResponse, StatusResponse},
+    response::Response,
+};
+use serde::Serialize;
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    repos::{database_size, namespace::Namespace},
+    support_bundle::{self, SupportBundle},
+};
+
+use super::{config::config_state, mount::mounts_list};
+
+#[derive(Debug, Serialize)]
+struct HostInfo {
+    version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    cpus: Option<usize>,
+    pid: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+struct DiagnoseCheck {
+    name: &'static str,
+    status: CheckStatus,
+    message: String,
+}
+
+impl DiagnoseCheck {
+    fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Export a gzip compressed tarball with the information needed to triage an
+/// issue: the sanitized config, host info, seal status, mounts, audit device
+/// summaries and diagnose results. Every file is redacted before it is added.
+pub async fn handle_support_bundle(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+) -> Result<Response, Error> {
+    if ns.parent_namespace_id.is_some() {
+        return Err(ErrorType::ConfigInNonRootNamespace.into());
+    }
+
+    let now = Utc::now();
+    let mut bundle = SupportBundle::new(
+        format!("covert-support-bundle-{}", now.format("%Y%m%dT%H%M%SZ")),
+        u64::try_from(now.timestamp()).unwrap_or_default(),
+    );
+
+    let host = HostInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        cpus: std::thread::available_parallelism().ok().map(Into::into),
+        pid: std::process::id(),
+    };
+    let seal = StatusResponse {
+        state: ctx.repos.pool.state(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ha: crate::ha::status(&ctx).await?,
+    };
+    let audit = audit_summaries(&ctx).await?;
+
+    let files: [(&str, serde_json::Value); 6] = [
+        ("host.json", to_value(&host)?),
+        ("config.json", to_value(&config_state(&ctx).await?)?),
+        ("seal-status.json", to_value(&seal)?),
+        ("mounts.json", to_value(&mounts_list(&ctx, &ns.id).await?)?),
+        ("audit.json", to_value(&audit)?),
+        ("diagnose.json", to_value(&diagnose(&ctx, &audit).await)?),
+    ];
+    for (name, value) in files {
+        bundle.add_json(name, &value).map_err(internal)?;
+    }
+    let bytes = bundle.finish().map_err(internal)?;
+
+    Ok(Response::bytes(support_bundle::CONTENT_TYPE, bytes))
+}
+
+fn to_value(value: &impl Serialize) -> Result<serde_json::Value, Error> {
+    serde_json::to_value(value).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+fn internal(err: std::io::Error) -> Error {
+    ErrorType::InternalError(anyhow::Error::new(err)).into()
+}
+
+/// The enabled audit devices and their write statistics. The salts of the
+/// devices are never included.
+async fn audit_summaries(ctx: &Context) -> Result<Vec<ReadAuditDeviceResponse>, Error> {
+    let mut summaries = vec![];
+    for entry in ctx.repos.audit.list().await? {
+        let device = ctx.audit.get(&entry.device.path);
+        summaries.push(ReadAuditDeviceResponse {
+            device: entry.device,
+            entries_written: device.as_ref().map_or(0, |device| device.entries_written()),
+            last_error: match device {
+                Some(device) => device.last_error().await,
+                None => None,
+            },
+        });
+    }
+    Ok(summaries)
+}
+
+async fn diagnose(ctx: &Context, audit: &[ReadAuditDeviceResponse]) -> Vec<DiagnoseCheck> {
+    let mut checks = vec![];
+
+    checks.push(match database_size(ctx.repos.pool.as_ref()).await {
+        Ok(size) => DiagnoseCheck::new(
+            "storage",
+            CheckStatus::Ok,
+            format!("Encrypted storage is {size} bytes"),
+        ),
+        Err(error) => DiagnoseCheck::new(
+            "storage",
+            CheckStatus::Error,
+            format!("Unable to read the encrypted storage: {error}"),
+        ),
+    });
+
+    checks.push(if ctx.config.tls_disable {
+        DiagnoseCheck::new("tls", CheckStatus::Warning, "TLS is disabled")
+    } else {
+        DiagnoseCheck::new("tls", CheckStatus::Ok, "TLS is enabled")
+    });
+
+    checks.push(if audit.is_empty() {
+        DiagnoseCheck::new(
+            "audit",
+            CheckStatus::Warning,
+            "No audit devices are enabled",
+        )
+    } else {
+        match audit.iter().find(|summary| summary.last_error.is_some()) {
+            Some(summary) => DiagnoseCheck::new(
+                "audit",
+                CheckStatus::Warning,
+                format!(
+                    "Audit device `{}` failed to write an entry",
+                    summary.device.path
+                ),
+            ),
+            None => DiagnoseCheck::new(
+                "audit",
+                CheckStatus::Ok,
+                format!("{} audit devices are enabled", audit.len()),
+            ),
+        }
+    });
+
+    let failures = ctx.expiration_manager.revocation_failures();
+    checks.push(
+        match ctx.repos.lease.count(ctx.expiration_manager.now()).await {
+            Ok((_, expired)) if expired > 0 || failures > 0 => DiagnoseCheck::new(
+                "expiration",
+                CheckStatus::Warning,
+                format!(
+                    "{expired} expired leases are waiting to be revoked, {failures} revocations failed"
+                ),
+            ),
+            Ok((leases, _)) => DiagnoseCheck::new(
+                "expiration",
+                CheckStatus::Ok,
+                format!("{leases} active leases"),
+            ),
+            Err(error) => DiagnoseCheck::new(
+                "expiration",
+                CheckStatus::Error,
+                format!("Unable to count the leases: {error}"),
+            ),
+        },
+    );
+
+    if ctx.config.ignore_migration_checksums {
+        checks.push(DiagnoseCheck::new(
+            "migrations",
+            CheckStatus::Warning,
+            "Mounts are served even if their migrations don't match the backend",
+        ));
+    }
+
+    checks
+}
@@ -1,18 +1,35 @@
-use chrono::Utc;
-use covert_framework::extract::{Extension, Json};
+use std::{str::FromStr, sync::Arc};
+
+use covert_framework::extract::{Extension, Json, Path};
 use covert_types::{
-    methods::{psql::RenewLeaseResponse, RenewLeaseParams},
+    methods::{
+        psql::RenewLeaseResponse,
+        system::{
+            CreateTokenParams, LeaseEntry as LeaseEntryDTO, LookupTokenResponse,
+            RenewLeaseResponse as RenewLeaseEntryResponse, RenewTokenSelfParams,
+            RevokeTokenSelfResponse, RevokeTokensByPolicyParams, RevokeTokensByPolicyResponse,
+            TidyTokensResponse, TokenPolicy, TokenRevocationJobState, TokenRevocationJobStatus,
+            TokenTargetParams,
+        },
+        RenewLeaseParams,
+    },
+    mount::MountConfig,
     response::Response,
     token::Token,
 };
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::{
     context::Context,
     error::{Error, ErrorType},
-    repos::namespace::Namespace,
+    layer::lease_registration::register_token,
+    repos::{namespace::Namespace, token::TokenEntry},
 };
 
+/// Lease mount path of the tokens issued with `sys/token/create`.
+const TOKEN_CREATE_MOUNT_PATH: &str = "sys/token/create/";
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RevokeTokenParams {
     pub token: Token,
@@ -24,10 +41,96 @@ pub async fn handle_token_revocation(
     Extension(ns): Extension<Namespace>,
     Json(body): Json<RevokeTokenParams>,
 ) -> Result<Response, Error> {
-    ctx.repos.token.remove(&body.token, &ns.id).await?;
+    // Called when the lease tracking the token is revoked, which removes the
+    // lease itself once the token is gone
+    ctx.expiration_manager
+        .revoke_token_tree(&body.token, &ns.id, false)
+        .await?;
     Ok(Response::ok())
 }
 
+/// Issue a token to an entity of the namespace as a child of the token of the
+/// request. The token is valid for `ttl` from `not_before`, or from now if it
+/// is not set.
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_create(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    token: Option<Extension<Token>>,
+    Json(body): Json<CreateTokenParams>,
+) -> Result<Response, Error> {
+    if body.num_uses == Some(0) {
+        return Err(ErrorType::BadRequest("`num_uses` must be at least 1".to_string()).into());
+    }
+    if ctx
+        .repos
+        .entity
+        .lookup(&body.entity_name, &ns.id)
+        .await?
+        .is_none()
+    {
+        return Err(
+            ErrorType::NotFound(format!("Entity `{}` was not found", body.entity_name)).into(),
+        );
+    }
+
+    let now = ctx.expiration_manager.now();
+    // The lease covers the wait until the token becomes valid
+    let delay = body
+        .not_before
+        .map_or_else(chrono::Duration::zero, |not_before| {
+            (not_before - now).max(chrono::Duration::zero())
+        });
+    let requested = chrono::Duration::from_std(body.ttl)
+        .ok()
+        .and_then(|ttl| delay.checked_add(&ttl))
+        .and_then(|lifetime| lifetime.to_std().ok())
+        .ok_or_else(|| ErrorType::BadRequest("`ttl` is too long".to_string()))?;
+    // Bounded by the max TTL of the system mount and the server like any
+    // other lease
+    let lifetime =
+        ctx.expiration_manager
+            .compute_ttl(now, &MountConfig::default(), Some(requested), None)?;
+    if lifetime <= delay {
+        return Err(ErrorType::BadRequest(
+            "The token would expire before `not_before`".to_string(),
+        )
+        .into());
+    }
+
+    let token_entry = TokenEntry::new(
+        body.entity_name,
+        now,
+        lifetime,
+        ns.id.clone(),
+        body.metadata,
+        body.policies,
+        body.renewable,
+    )
+    .with_parent(if body.orphan {
+        None
+    } else {
+        token.map(|Extension(token)| token)
+    })
+    .with_limits(body.not_before, body.num_uses);
+    let resp = register_token(
+        &ctx.expiration_manager,
+        &ctx.repos.token,
+        &token_entry,
+        TOKEN_CREATE_MOUNT_PATH.to_string(),
+        lifetime,
+    )
+    .await?;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_tidy(Extension(ctx): Extension<Context>) -> Result<Response, Error> {
+    let revoked = ctx.expiration_manager.tidy_tokens().await?;
+    let resp = TidyTokensResponse { revoked };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RenewTokenParams {
     pub token: Token,
@@ -47,7 +150,7 @@ pub async fn handle_token_renewal(
 
     let ttl = chrono::Duration::from_std(body.ttl)
         .map_err(|_| ErrorType::InternalError(anyhow::Error::msg("Unable to create TTL")))?;
-    let expires_at = Utc::now() + ttl;
+    let expires_at = ctx.expiration_manager.now() + ttl;
 
     ctx.repos
         .token
@@ -57,3 +160,219 @@ pub async fn handle_token_renewal(
     let resp = RenewLeaseResponse { ttl: body.ttl };
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_lookup_self(
+    Extension(ctx): Extension<Context>,
+    token: Option<Extension<Token>>,
+) -> Result<Response, Error> {
+    let Some(Extension(token)) = token else {
+        return Err(ErrorType::Unauthorized("Missing token".to_string()).into());
+    };
+    let te = ctx
+        .repos
+        .token
+        .lookup(&token)
+        .await?
+        .ok_or_else(|| ErrorType::Unauthorized("Invalid token".to_string()))?;
+    let resp = lookup_response(&ctx, te).await?;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+async fn lookup_response(ctx: &Context, te: TokenEntry) -> Result<LookupTokenResponse, Error> {
+    let policies = ctx
+        .repos
+        .token
+        .lookup_policy_sources(te.id())
+        .await?
+        .into_iter()
+        .map(|(name, sources)| TokenPolicy { name, sources })
+        .collect();
+
+    Ok(LookupTokenResponse {
+        entity_name: te.entity_name,
+        accessor: te.accessor,
+        issue_time: te.issued_at.to_rfc3339(),
+        expire_time: te.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+        metadata: te.metadata,
+        renewable: te.renewable,
+        policies,
+        not_before: te.not_before.map(|not_before| not_before.to_rfc3339()),
+        num_uses: te.num_uses,
+    })
+}
+
+/// The valid token of the namespace given by the token or its accessor.
+async fn target_token(
+    ctx: &Context,
+    ns: &Namespace,
+    params: &TokenTargetParams,
+) -> Result<TokenEntry, Error> {
+    let not_found = || ErrorType::NotFound("Token not found".to_string());
+    let te = match (&params.token, &params.accessor) {
+        (Some(token), None) => {
+            let token = Token::from_str(token).map_err(|_| not_found())?;
+            ctx.repos
+                .token
+                .lookup(&token)
+                .await?
+                .filter(|te| te.namespace_id == ns.id)
+        }
+        (None, Some(accessor)) => ctx.repos.token.lookup_by_accessor(accessor, &ns.id).await?,
+        _ => {
+            return Err(ErrorType::BadRequest(
+                "Exactly one of `token` and `accessor` must be set".to_string(),
+            )
+            .into())
+        }
+    };
+    te.ok_or_else(|| not_found().into())
+}
+
+/// Lookup another token of the namespace by the token or its accessor.
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_lookup(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(body): Json<TokenTargetParams>,
+) -> Result<Response, Error> {
+    let te = target_token(&ctx, &ns, &body).await?;
+    let resp = lookup_response(&ctx, te).await?;
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+/// Revoke another token of the namespace, given by the token or its
+/// accessor, together with its child tokens and leases.
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_revoke(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(body): Json<TokenTargetParams>,
+) -> Result<Response, Error> {
+    let te = target_token(&ctx, &ns, &body).await?;
+    ctx.expiration_manager.revoke_token(te.id(), &ns.id).await?;
+    let resp = RevokeTokenSelfResponse {
+        entity_name: te.entity_name,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_renew_self(
+    Extension(ctx): Extension<Context>,
+    token: Option<Extension<Token>>,
+    Json(body): Json<RenewTokenSelfParams>,
+) -> Result<Response, Error> {
+    let Some(Extension(token)) = token else {
+        return Err(ErrorType::Unauthorized("Missing token".to_string()).into());
+    };
+    let te = ctx
+        .repos
+        .token
+        .lookup(&token)
+        .await?
+        .ok_or_else(|| ErrorType::Unauthorized("Invalid token".to_string()))?;
+
+    let lease = ctx
+        .expiration_manager
+        .renew_token(&token, &te.namespace_id, body.increment)
+        .await?;
+    let resp = RenewLeaseEntryResponse {
+        lease: LeaseEntryDTO::from(&lease),
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_revoke_self(
+    Extension(ctx): Extension<Context>,
+    token: Option<Extension<Token>>,
+) -> Result<Response, Error> {
+    let Some(Extension(token)) = token else {
+        return Err(ErrorType::Unauthorized("Missing token".to_string()).into());
+    };
+    let te = ctx
+        .repos
+        .token
+        .lookup(&token)
+        .await?
+        .ok_or_else(|| ErrorType::Unauthorized("Invalid token".to_string()))?;
+
+    ctx.expiration_manager
+        .revoke_token(&token, &te.namespace_id)
+        .await?;
+    let resp = RevokeTokenSelfResponse {
+        entity_name: te.entity_name,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all, fields(policy = body.policy, dry_run = body.dry_run))]
+pub async fn handle_token_revocation_by_policy(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(body): Json<RevokeTokensByPolicyParams>,
+) -> Result<Response, Error> {
+    let tokens = ctx.repos.token.list_by_policy(&body.policy, &ns.id).await?;
+    let matched = tokens.len();
+
+    if body.dry_run {
+        let resp = RevokeTokensByPolicyResponse {
+            job_id: None,
+            matched,
+        };
+        return Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into());
+    }
+
+    let job_id = Uuid::new_v4();
+    ctx.token_revocation_jobs
+        .insert(
+            ns.id.clone(),
+            TokenRevocationJobStatus {
+                job_id,
+                policy: body.policy,
+                state: TokenRevocationJobState::Running,
+                total: matched,
+                revoked: 0,
+                failed: 0,
+            },
+        )
+        .await;
+
+    // Revoking every token can take a while, so run it in the background and
+    // let the caller poll the job status.
+    let jobs = ctx.token_revocation_jobs.clone();
+    let expiration_manager = Arc::clone(&ctx.expiration_manager);
+    tokio::spawn(async move {
+        for token in tokens {
+            let res = expiration_manager.revoke_token(&token, &ns.id).await;
+            if let Err(error) = &res {
+                tracing::error!(?error, %job_id, "Failed to revoke token");
+            }
+            jobs.record_revocation(job_id, res.is_ok()).await;
+        }
+        jobs.complete(job_id).await;
+    });
+
+    let resp = RevokeTokensByPolicyResponse {
+        job_id: Some(job_id),
+        matched,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_token_revocation_job_status(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Path(job_id): Path<String>,
+) -> Result<Response, Error> {
+    let not_found = || ErrorType::NotFound(format!("Token revocation job `{job_id}` not found"));
+    let id = Uuid::from_str(&job_id).map_err(|_| not_found())?;
+    let status = ctx
+        .token_revocation_jobs
+        .get(id, &ns.id)
+        .await
+        .ok_or_else(not_found)?;
+    Response::raw(status).map_err(|err| ErrorType::BadResponseData(err).into())
+}
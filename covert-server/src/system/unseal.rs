@@ -1,31 +1,51 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use chrono::Utc;
 use covert_framework::extract::{Extension, Json};
 use covert_types::{
+    backend::BackendType,
     entity::Entity,
     methods::system::{UnsealParams, UnsealResponse},
     policy::{PathPolicy, Policy},
     request::Operation,
     response::Response,
+    state::StorageState,
     token::Token,
 };
 use tracing::error;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
 use crate::{
+    config::SealConfig,
     context::Context,
     error::{Error, ErrorType},
     recovery::replicate,
-    repos::{namespace::Namespace, token::TokenEntry, Repos},
+    repos::{
+        entity::ROOT_ENTITY,
+        namespace::Namespace,
+        policy::ROOT_POLICY,
+        token::{new_accessor, TokenEntry},
+        Repos,
+    },
+    tamper::TamperEvent,
 };
 
-use super::mount::mount_route_entry;
+use super::mount::{migrate_backend, mount_route_entry};
 
 pub async fn handle_unseal(
     Extension(ctx): Extension<Context>,
     Json(body): Json<UnsealParams>,
 ) -> Result<Response, Error> {
+    if let Some(seal) = &ctx.config.seal {
+        // The seal unwraps the master key, key shares are not used
+        let master_key = unwrap_stored_master_key(&ctx, seal).await?;
+        unseal_with_master_key(&ctx, &master_key).await?;
+        let root_token = generate_root_token(&ctx.repos).await?;
+
+        let resp = UnsealResponse::Complete { root_token };
+        return Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into());
+    }
+
     let seal_config = ctx.repos.seal.get_config().await?.ok_or_else(|| {
         ErrorType::InternalError(anyhow::Error::msg(
             "Seal config was not found when unseal handler was called",
@@ -36,16 +56,19 @@ pub async fn handle_unseal(
         ctx.repos.seal.insert_key_share(key.as_bytes()).await?;
     }
 
-    let Ok(shares) = ctx.repos
+    let Ok(shares) = ctx
+        .repos
         .seal
         .get_key_shares()
         .await?
         .into_iter()
         .map(|k| String::from_utf8(k.key))
-        .collect::<Result<Vec<_>, _>>() else {
-            ctx.repos.seal.clear_key_shares().await?;
-            return Err(ErrorType::BadData("Invalid share key found".into()).into());
-        };
+        .collect::<Result<Vec<_>, _>>()
+        .map(Zeroizing::new)
+    else {
+        ctx.repos.seal.clear_key_shares().await?;
+        return Err(ErrorType::BadData("Invalid share key found".into()).into());
+    };
 
     if usize::from(seal_config.threshold) > shares.len() {
         // Return progress
@@ -59,12 +82,16 @@ pub async fn handle_unseal(
 
     let Ok(master_key) = construct_master_key(&shares, seal_config.threshold) else {
         ctx.repos.seal.clear_key_shares().await?;
-        return Err(ErrorType::BadData("Unable to construct master key from key shares".into()).into());
+        ctx.tamper.record(TamperEvent::DecryptionFailure);
+        return Err(
+            ErrorType::BadData("Unable to construct master key from key shares".into()).into(),
+        );
     };
     // No longer needed so just clear them
     ctx.repos.seal.clear_key_shares().await?;
+    drop(shares);
 
-    unseal(&ctx, master_key).await?;
+    unseal_with_master_key(&ctx, &master_key).await?;
 
     let root_token = generate_root_token(&ctx.repos).await?;
 
@@ -72,7 +99,7 @@ pub async fn handle_unseal(
     Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
 }
 
-fn construct_master_key(key_shares: &[String], threshold: u8) -> Result<String, Error> {
+fn construct_master_key(key_shares: &[String], threshold: u8) -> Result<Zeroizing<String>, Error> {
     let key_shares = key_shares
         .iter()
         .map(|s| {
@@ -89,12 +116,55 @@ fn construct_master_key(key_shares: &[String], threshold: u8) -> Result<String,
         .recover(key_shares.as_slice())
         .map_err(|_| ErrorType::MasterKeyRecovery)?;
     let master_key = String::from_utf8(master_key).map_err(|_| ErrorType::MasterKeyRecovery)?;
-    Ok(master_key)
+    Ok(Zeroizing::new(master_key))
+}
+
+/// Unseal the server on start with the master key wrapped by the seal of the
+/// config. The server stays sealed if the seal fails.
+pub async fn auto_unseal(ctx: &Context) -> Result<(), Error> {
+    let Some(seal) = &ctx.config.seal else {
+        return Ok(());
+    };
+    if ctx.repos.pool.state() != StorageState::Sealed {
+        return Ok(());
+    }
+    let master_key = unwrap_stored_master_key(ctx, seal).await?;
+    unseal_with_master_key(ctx, &master_key).await
 }
 
-async fn unseal(ctx: &Context, master_key: String) -> Result<(), Error> {
-    ctx.repos.pool.unseal(master_key.clone())?;
+async fn unwrap_stored_master_key(
+    ctx: &Context,
+    seal: &SealConfig,
+) -> Result<Zeroizing<String>, Error> {
+    let wrapped = ctx
+        .repos
+        .seal
+        .get_wrapped_master_key()
+        .await?
+        .ok_or_else(|| {
+            ErrorType::Seal(format!(
+                "The storage was not initialized with the `{}` seal",
+                seal.name()
+            ))
+        })?;
+    crate::seal::unwrap_master_key(seal, wrapped).await
+}
 
+/// Unseal the storage with the master key and prepare the server to serve
+/// requests.
+pub(super) async fn unseal_with_master_key(ctx: &Context, master_key: &str) -> Result<(), Error> {
+    // Not promoted to the active node before it is ready to serve
+    let _transition = ctx.ha.transition().await;
+    if let Err(err) = ctx.repos.pool.unseal(master_key.to_string()) {
+        ctx.tamper.record(TamperEvent::DecryptionFailure);
+        return Err(err.into());
+    }
+    ctx.snapshot_key.derive(master_key);
+    unseal(ctx, master_key).await
+}
+
+/// Prepare the server to serve requests once the storage is unsealed.
+async fn unseal(ctx: &Context, master_key: &str) -> Result<(), Error> {
     // Clear all shares now that master key is constructed
     ctx.repos.seal.clear_key_shares().await?;
 
@@ -113,7 +183,7 @@ async fn unseal(ctx: &Context, master_key: String) -> Result<(), Error> {
         // Setup replication
         match replicate(
             replication,
-            Some(master_key.clone()),
+            Some(master_key.to_string()),
             &ctx.config.encrypted_storage_path(),
             &replication.encrypted_bucket_url(),
         ) {
@@ -149,20 +219,79 @@ async fn unseal(ctx: &Context, master_key: String) -> Result<(), Error> {
         ns
     };
 
+    load_state(ctx, &ns).await?;
+
+    // In HA mode only the active node revokes leases, it is started once the
+    // node acquires the lock
+    if !ctx.ha.enabled() {
+        start_expiration_manager(ctx);
+    }
+
+    Ok(())
+}
+
+/// Reload the audit devices, quotas and mounts from the storage, e.g. when a
+/// standby that may have missed the changes of the active node takes over.
+pub(crate) async fn restore(ctx: &Context) -> Result<(), Error> {
+    let ns = ctx
+        .repos
+        .namespace
+        .find_by_path(&["root".to_string()])
+        .await?
+        .ok_or_else(|| ErrorType::InternalError(anyhow::Error::msg("Missing root namespace")))?;
+
+    super::seal::unmount_all(ctx)?;
+    ctx.plugins.stop_all().await;
+    ctx.audit.clear();
+    ctx.quotas.clear();
+    load_state(ctx, &ns).await
+}
+
+/// Catch up a performance standby with the audit devices, quotas and mounts
+/// changed by the active node. Unlike [`restore`] the unchanged state is
+/// kept, so the standby serves reads while it catches up.
+pub(crate) async fn replicate(ctx: &Context) -> Result<(), Error> {
+    super::audit::sync_audit_devices(ctx).await?;
+    super::quota::sync_quotas(ctx).await?;
+    super::mount::sync_mounts(ctx).await
+}
+
+async fn load_state(ctx: &Context, ns: &Namespace) -> Result<(), Error> {
+    // Requests are only served once every audit device is ready
+    super::audit::load_audit_devices(ctx).await?;
+    super::quota::load_quotas(ctx, ns).await?;
+
     let mounts = ctx.repos.mount.list(&ns.id).await?;
     for mount in mounts {
-        mount_route_entry(ctx, mount.id, mount.backend_type, &ns.id).await?;
+        let entry =
+            mount_route_entry(ctx, mount.id, mount.backend_type, &mount.config, &ns.id).await;
+        let (backend, prefix) = match entry {
+            Ok(entry) => entry,
+            // A plugin that fails to start does not keep the server sealed
+            Err(error) if mount.backend_type == BackendType::Plugin => {
+                error!(?error, path = mount.path, "Failed to start plugin");
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        // Upgrade the backend storage to the latest version. A mount that
+        // fails to upgrade is not served rather than served half-upgraded.
+        if let Err(error) = migrate_backend(ctx, &backend, mount.id, &prefix).await {
+            tracing::error!(?error, path = mount.path, "Failed to migrate mount");
+            let _ = ctx.router.remove(mount.id);
+        }
     }
 
-    // Start expiration manager
+    Ok(())
+}
+
+pub(crate) fn start_expiration_manager(ctx: &Context) {
     let expiration_manager = Arc::clone(&ctx.expiration_manager);
     tokio::spawn(async move {
         if expiration_manager.start().await.is_err() {
             // TODO: stop the server
         }
     });
-
-    Ok(())
 }
 
 pub async fn generate_root_token(repos: &Repos) -> Result<Token, Error> {
@@ -174,7 +303,7 @@ pub async fn generate_root_token(repos: &Repos) -> Result<Token, Error> {
 
     // Generate root policy if not exist
     let policy = Policy::new(
-        "root".into(),
+        ROOT_POLICY.into(),
         vec![PathPolicy {
             path: "*".to_string(),
             operations: vec![
@@ -182,6 +311,9 @@ pub async fn generate_root_token(repos: &Repos) -> Result<Token, Error> {
                 Operation::Delete,
                 Operation::Create,
                 Operation::Update,
+                Operation::Patch,
+                Operation::List,
+                Operation::Sudo,
             ],
         }],
         ns.id.clone(),
@@ -189,7 +321,7 @@ pub async fn generate_root_token(repos: &Repos) -> Result<Token, Error> {
     let _res = repos.policy.create(&policy).await;
 
     // Generate root entity if not exist
-    let entity = Entity::new("root".into(), ns.id.clone());
+    let entity = Entity::new(ROOT_ENTITY.into(), ns.id.clone());
     let _res = repos.entity.create(&entity).await;
 
     // Attach root policy to root entity
@@ -202,8 +334,16 @@ pub async fn generate_root_token(repos: &Repos) -> Result<Token, Error> {
         id: Token::new(),
         entity_name: entity.name,
         expires_at: None,
-        issued_at: Utc::now(),
+        issued_at: repos.token.now(),
         namespace_id: ns.id.clone(),
+        metadata: HashMap::new(),
+        policies: vec![],
+        group_policies: vec![],
+        renewable: true,
+        parent: None,
+        accessor: new_accessor(),
+        not_before: None,
+        num_uses: None,
     };
     let token = te.id().clone();
     repos.token.create(&te).await?;
@@ -0,0 +1,91 @@
+use covert_framework::{
+    create, create_with_config,
+    extract::{Extension, Json},
+    RouteConfig, Router,
+};
+use covert_types::{
+    auth::AuthPolicy,
+    methods::system::{
+        RewrapParams, UnwrapParams, WrapInfo, WrappingLookupParams, WrappingLookupResponse,
+    },
+    response::Response,
+    state::StorageState,
+};
+
+use crate::{
+    context::Context,
+    error::{Error, ErrorType},
+    repos::namespace::Namespace,
+};
+
+/// Routes for response wrapping tokens, nested under `/wrapping`. Unwrapping
+/// and looking up a wrapping token only requires the wrapping token, the
+/// recipient of a wrapped response may not have a token of its own yet.
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/unwrap",
+            create_with_config(
+                handle_unwrap,
+                RouteConfig {
+                    policy: AuthPolicy::Unauthenticated,
+                    state: vec![StorageState::Unsealed],
+                },
+            ),
+        )
+        .route(
+            "/lookup",
+            create_with_config(
+                handle_wrapping_lookup,
+                RouteConfig {
+                    policy: AuthPolicy::Unauthenticated,
+                    state: vec![StorageState::Unsealed],
+                },
+            ),
+        )
+        .route("/rewrap", create(handle_rewrap))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_unwrap(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(params): Json<UnwrapParams>,
+) -> Result<Response, Error> {
+    let data = ctx.repos.wrapping.unwrap(&params.token, &ns.id).await?;
+    Ok(Response::Raw(data))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_wrapping_lookup(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(params): Json<WrappingLookupParams>,
+) -> Result<Response, Error> {
+    let entry = ctx.repos.wrapping.lookup(&params.token, &ns.id).await?;
+    let resp = WrappingLookupResponse {
+        creation_time: entry.created_at,
+        creation_path: entry.creation_path,
+        expire_time: entry.expires_at,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn handle_rewrap(
+    Extension(ctx): Extension<Context>,
+    Extension(ns): Extension<Namespace>,
+    Json(params): Json<RewrapParams>,
+) -> Result<Response, Error> {
+    let entry = ctx.repos.wrapping.rewrap(&params.token, &ns.id).await?;
+    let resp = WrapInfo {
+        ttl: entry
+            .ttl()
+            .to_std()
+            .map_err(|err| ErrorType::InternalError(err.into()))?,
+        token: entry.token,
+        creation_time: entry.created_at,
+        creation_path: entry.creation_path,
+    };
+    Response::raw(resp).map_err(|err| ErrorType::BadResponseData(err).into())
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::Instant,
+};
+
+use covert_types::{methods::system::TamperConfig, state::StorageState};
+use tokio::sync::Notify;
+use tracing::error;
+
+use crate::context::Context;
+
+/// Something that could mean that the server is being tampered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TamperEvent {
+    /// The key shares provided to unseal did not decrypt the storage.
+    DecryptionFailure,
+    /// A request carried a token that is not a token at all.
+    MalformedToken,
+}
+
+#[derive(Default)]
+struct Events {
+    decryption_failures: VecDeque<Instant>,
+    malformed_tokens: VecDeque<Instant>,
+}
+
+/// Counts the [`TamperEvent`]s and wakes up [`seal_on_tamper`] when one of
+/// the thresholds of the [`TamperConfig`] is reached.
+#[derive(Default)]
+pub struct TamperMonitor {
+    config: RwLock<TamperConfig>,
+    events: Mutex<Events>,
+    tripped: Notify,
+    trips: AtomicU64,
+}
+
+impl TamperMonitor {
+    pub fn config(&self) -> TamperConfig {
+        *self
+            .config
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Replace the config. Events recorded so far are forgotten.
+    pub fn set_config(&self, config: TamperConfig) {
+        *self
+            .config
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = config;
+        *self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Events::default();
+    }
+
+    /// Number of times a threshold was reached.
+    pub fn trips(&self) -> u64 {
+        self.trips.load(Ordering::Relaxed)
+    }
+
+    /// Record the event and return whether it tripped the threshold.
+    pub fn record(&self, event: TamperEvent) -> bool {
+        self.record_at(event, Instant::now())
+    }
+
+    fn record_at(&self, event: TamperEvent, now: Instant) -> bool {
+        let config = self.config();
+        let threshold = match event {
+            TamperEvent::DecryptionFailure => config.decryption_failures,
+            TamperEvent::MalformedToken => config.malformed_tokens,
+        };
+        if !config.enabled || threshold == 0 {
+            return false;
+        }
+
+        let mut events = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let events = match event {
+            TamperEvent::DecryptionFailure => &mut events.decryption_failures,
+            TamperEvent::MalformedToken => &mut events.malformed_tokens,
+        };
+        while events
+            .front()
+            .is_some_and(|at| now.saturating_duration_since(*at) >= config.window)
+        {
+            events.pop_front();
+        }
+        events.push_back(now);
+        if events.len() < threshold as usize {
+            return false;
+        }
+        events.clear();
+
+        error!(
+            ?event,
+            threshold,
+            window = ?config.window,
+            "Tamper threshold reached"
+        );
+        self.trips.fetch_add(1, Ordering::Relaxed);
+        self.tripped.notify_one();
+        true
+    }
+}
+
+/// Seal the server every time the [`TamperMonitor`] trips. Sealing goes
+/// through the same path as `sys/seal`, the server has to be unsealed by the
+/// operators again.
+pub async fn seal_on_tamper(ctx: Context) {
+    loop {
+        ctx.tamper.tripped.notified().await;
+        if ctx.repos.pool.state() != StorageState::Unsealed {
+            // The key shares that failed to decrypt the storage are already
+            // discarded
+            continue;
+        }
+        if let Err(error) = crate::system::seal(&ctx).await {
+            error!(
+                ?error,
+                "Failed to seal the server after tampering was detected"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn monitor(config: TamperConfig) -> TamperMonitor {
+        let monitor = TamperMonitor::default();
+        monitor.set_config(config);
+        monitor
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let monitor = TamperMonitor::default();
+        for _ in 0..1000 {
+            assert!(!monitor.record(TamperEvent::MalformedToken));
+            assert!(!monitor.record(TamperEvent::DecryptionFailure));
+        }
+        assert_eq!(monitor.trips(), 0);
+    }
+
+    #[test]
+    fn trips_within_window() {
+        let monitor = monitor(TamperConfig {
+            enabled: true,
+            decryption_failures: 3,
+            malformed_tokens: 0,
+            window: Duration::from_secs(10),
+        });
+        let start = Instant::now();
+
+        // Events are counted separately and a zero threshold never trips
+        for i in 0..10 {
+            assert!(!monitor.record_at(
+                TamperEvent::MalformedToken,
+                start + Duration::from_millis(i)
+            ));
+        }
+
+        let at = |secs| start + Duration::from_secs(secs);
+        assert!(!monitor.record_at(TamperEvent::DecryptionFailure, at(0)));
+        assert!(!monitor.record_at(TamperEvent::DecryptionFailure, at(5)));
+        // The first failure is outside of the window
+        assert!(!monitor.record_at(TamperEvent::DecryptionFailure, at(10)));
+        assert!(monitor.record_at(TamperEvent::DecryptionFailure, at(11)));
+        assert_eq!(monitor.trips(), 1);
+
+        // Counting starts over after a trip
+        assert!(!monitor.record_at(TamperEvent::DecryptionFailure, at(12)));
+        assert!(!monitor.record_at(TamperEvent::DecryptionFailure, at(13)));
+        assert!(monitor.record_at(TamperEvent::DecryptionFailure, at(14)));
+        assert_eq!(monitor.trips(), 2);
+    }
+}
@@ -0,0 +1,356 @@
+//! In-process server for integration tests of backends and policies.
+//!
+//! The server runs on an in-memory database, is initialized and unsealed with
+//! a single key share and answers the SDK without binding a port. Leases and
+//! tokens are issued on a [`MockClock`], so tests can expire them with
+//! [`TestServer::advance_time`] instead of waiting.
+//!
+//! ```ignore
+//! let server = TestServer::builder()
+//!     .mount("kv/", BackendType::Kv)
+//!     .start()
+//!     .await;
+//! server
+//!     .create_policy("reader", r#"path "kv/*" { capabilities = ["read"] }"#)
+//!     .await;
+//! let reader = server.client_with_policies(&["reader"]).await;
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use covert_sdk::{
+    entity::CreateEntityParams,
+    lease::LeaseEntry,
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    policy::CreatePolicyParams,
+    token::CreateTokenParams,
+    Client, ErrorCode,
+};
+use tokio::sync::oneshot;
+
+use crate::{start, Clock, Config, InProcessService, MockClock};
+
+/// Base URL of the SDK clients, the requests never leave the process.
+const API_URL: &str = "http://covert/v1";
+/// TTL of the tokens created by [`TestServer::create_token_with_policies`].
+const TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long [`TestServer::advance_time`] waits for expired leases to be
+/// revoked.
+const REVOCATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Builds a [`TestServer`] with the chosen mounts.
+pub struct TestServerBuilder {
+    config: Config,
+    mounts: Vec<(String, CreateMountParams)>,
+}
+
+impl TestServerBuilder {
+    /// Mount a backend at the path with the default mount config, e.g. at
+    /// `kv/` or `auth/userpass/`.
+    #[must_use]
+    pub fn mount(self, path: &str, variant: BackendType) -> Self {
+        self.mount_with_config(path, variant, MountConfig::default())
+    }
+
+    #[must_use]
+    pub fn mount_with_config(
+        mut self,
+        path: &str,
+        variant: BackendType,
+        config: MountConfig,
+    ) -> Self {
+        self.mounts
+            .push((path.to_string(), CreateMountParams { variant, config }));
+        self
+    }
+
+    /// Change the config of the server before it starts.
+    #[must_use]
+    pub fn configure(mut self, configure: impl FnOnce(&mut Config)) -> Self {
+        configure(&mut self.config);
+        self
+    }
+
+    /// Start, initialize and unseal the server and create the mounts.
+    ///
+    /// # Panics
+    ///
+    /// If the server fails to start or a mount cannot be created.
+    pub async fn start(mut self) -> TestServer {
+        // The clock starts at the current time, so leases and tokens can be
+        // compared with the time of static secrets
+        let clock = MockClock::new();
+        clock.set(Utc::now());
+        self.config.clock = Some(Arc::new(clock.clone()));
+
+        let (service_tx, service_rx) = oneshot::channel();
+        self.config.service_tx = Some(service_tx);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let config = self.config;
+        tokio::spawn(async move {
+            let shutdown_signal = async move {
+                let _ = shutdown_rx.await;
+            };
+            if let Err(err) = start(config, shutdown_signal).await {
+                panic!("server error: {err:?}");
+            }
+        });
+        let service = service_rx.await.expect("server should start");
+
+        let sdk = new_client(&service);
+        let root_token = unseal(&sdk).await;
+        sdk.set_token(Some(root_token.clone())).await;
+        for (path, params) in &self.mounts {
+            sdk.mount
+                .create(path, params)
+                .await
+                .unwrap_or_else(|err| panic!("failed to mount `{path}`: {err}"));
+        }
+
+        TestServer {
+            sdk,
+            service,
+            root_token,
+            clock,
+            entities: AtomicUsize::new(0),
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+}
+
+/// Unsealed in-memory server, shut down when dropped.
+pub struct TestServer {
+    sdk: Client,
+    service: InProcessService,
+    root_token: String,
+    clock: MockClock,
+    /// Number of entities created for tokens, to name them uniquely.
+    entities: AtomicUsize,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl TestServer {
+    #[must_use]
+    pub fn builder() -> TestServerBuilder {
+        TestServerBuilder {
+            config: config(),
+            mounts: vec![],
+        }
+    }
+
+    /// Client with the root token.
+    #[must_use]
+    pub fn sdk(&self) -> &Client {
+        &self.sdk
+    }
+
+    #[must_use]
+    pub fn root_token(&self) -> &str {
+        &self.root_token
+    }
+
+    /// Clock the leases are issued and expired with.
+    #[must_use]
+    pub fn clock(&self) -> &MockClock {
+        &self.clock
+    }
+
+    /// Client with the token.
+    pub async fn client(&self, token: &str) -> Client {
+        let client = new_client(&self.service);
+        client.set_token(Some(token.to_string())).await;
+        client
+    }
+
+    /// # Panics
+    ///
+    /// If the policy is invalid.
+    pub async fn create_policy(&self, name: &str, policy: &str) {
+        self.sdk
+            .policy
+            .create(&CreatePolicyParams {
+                name: name.to_string(),
+                policy: policy.to_string(),
+            })
+            .await
+            .unwrap_or_else(|err| panic!("failed to create policy `{name}`: {err}"));
+    }
+
+    /// Issue a token with the policies to a new entity. The token is valid
+    /// for an hour, which only passes with [`TestServer::advance_time`].
+    ///
+    /// # Panics
+    ///
+    /// If a policy does not exist.
+    pub async fn create_token_with_policies(&self, policies: &[&str]) -> String {
+        let entity_name = format!(
+            "test-entity-{}",
+            self.entities.fetch_add(1, Ordering::SeqCst)
+        );
+        self.sdk
+            .entity
+            .create(&CreateEntityParams {
+                name: entity_name.clone(),
+            })
+            .await
+            .expect("failed to create entity");
+        let auth = self
+            .sdk
+            .token
+            .create(&CreateTokenParams {
+                entity_name,
+                policies: policies.iter().map(ToString::to_string).collect(),
+                ttl: TOKEN_TTL,
+                not_before: None,
+                num_uses: None,
+                renewable: false,
+                metadata: HashMap::new(),
+                orphan: true,
+            })
+            .await
+            .unwrap_or_else(|err| panic!("failed to create token with {policies:?}: {err}"));
+        auth.token.to_string()
+    }
+
+    /// Client with a token that has the policies.
+    pub async fn client_with_policies(&self, policies: &[&str]) -> Client {
+        let token = self.create_token_with_policies(policies).await;
+        self.client(&token).await
+    }
+
+    /// Move the clock forward and wait until the leases that expired in the
+    /// meantime have been revoked.
+    ///
+    /// # Panics
+    ///
+    /// If the expired leases are not revoked within a few seconds, e.g.
+    /// because their backend fails to revoke them.
+    pub async fn advance_time(&self, duration: Duration) {
+        self.clock
+            .advance(chrono::Duration::from_std(duration).expect("duration should be in range"));
+        let now = self.clock.now();
+        let deadline = tokio::time::Instant::now() + REVOCATION_TIMEOUT;
+        loop {
+            let expired = self
+                .all_leases()
+                .await
+                .into_iter()
+                .filter(|lease| expire_time(lease) <= now)
+                .map(|lease| lease.id)
+                .collect::<Vec<_>>();
+            if expired.is_empty() {
+                return;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "expired leases were not revoked: {expired:?}"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// The leases issued by the mounts under the prefix, e.g. `kv/`.
+    ///
+    /// # Panics
+    ///
+    /// If the leases cannot be listed.
+    pub async fn leases(&self, prefix: &str) -> Vec<LeaseEntry> {
+        self.sdk
+            .lease
+            .list_by_mount(prefix)
+            .await
+            .unwrap_or_else(|err| panic!("failed to list leases of `{prefix}`: {err}"))
+            .leases
+    }
+
+    /// # Panics
+    ///
+    /// If the mounts under the prefix do not have `count` leases.
+    pub async fn assert_leases(&self, prefix: &str, count: usize) {
+        let leases = self.leases(prefix).await;
+        assert_eq!(
+            leases.len(),
+            count,
+            "expected {count} leases under `{prefix}`, found {leases:?}"
+        );
+    }
+
+    /// # Panics
+    ///
+    /// If the lease still exists.
+    pub async fn assert_lease_revoked(&self, lease_id: &str) {
+        let res = self.sdk.lease.lookup(lease_id).await;
+        match res {
+            Err(err) if err.code() == Some(ErrorCode::NotFound) => (),
+            Err(err) => panic!("failed to look up lease `{lease_id}`: {err}"),
+            Ok(resp) => panic!("lease `{lease_id}` was not revoked: {:?}", resp.lease),
+        }
+    }
+
+    /// The leases of every mount and of the tokens created through `sys/`.
+    async fn all_leases(&self) -> Vec<LeaseEntry> {
+        let mounts = self.sdk.mount.list().await.expect("failed to list mounts");
+        let mut leases = self.leases("sys/").await;
+        for mount in mounts.auth.iter().chain(&mounts.secret) {
+            leases.extend(self.leases(&mount.path).await);
+        }
+        leases
+    }
+}
+
+fn expire_time(lease: &LeaseEntry) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&lease.expire_time)
+        .expect("expire time should be RFC 3339")
+        .with_timezone(&Utc)
+}
+
+fn new_client(service: &InProcessService) -> Client {
+    Client::builder(API_URL)
+        .service(service.clone())
+        .build()
+        .expect("client should build")
+}
+
+/// Initialize and unseal the server with one key share and return the root
+/// token.
+async fn unseal(sdk: &Client) -> String {
+    let shares = match sdk
+        .operator
+        .initialize(&InitializeParams {
+            shares: 1,
+            threshold: 1,
+            stored_shares: None,
+        })
+        .await
+        .expect("failed to initialize")
+    {
+        InitializeResponse::NewKeyShares(shares) => shares.shares,
+        _ => panic!("should get new shares"),
+    };
+    let resp = sdk
+        .operator
+        .unseal(&UnsealParams { shares })
+        .await
+        .expect("failed to unseal");
+    let UnsealResponse::Complete { root_token } = resp else {
+        panic!("should be unsealed");
+    };
+    root_token.to_string()
+}
+
+fn config() -> Config {
+    Config {
+        tls_disable: true,
+        storage_path: ":memory:".into(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use hyper::server::accept::Accept;
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    InconsistentKeys, RootCertStore, ServerConfig, SupportedCipherSuite,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tracing::{debug, warn};
+
+use crate::config::{TlsConfig, TlsVersion};
+
+/// Max time a client gets to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of established connections waiting to be served.
+const ACCEPT_BACKLOG: usize = 128;
+
+/// TLS server config that can be replaced while serving, e.g. when the
+/// certificates are rotated. Handshakes use the config that is current when
+/// they start, established connections are not affected.
+pub struct ReloadableServerConfig {
+    current: RwLock<Arc<ServerConfig>>,
+}
+
+impl ReloadableServerConfig {
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        Self {
+            current: RwLock::new(config),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ServerConfig> {
+        Arc::clone(&self.current.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub fn replace(&self, config: Arc<ServerConfig>) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = config;
+    }
+}
+
+/// Cipher suites allowed by the config, in order of preference. All suites
+/// supported by the server are allowed when the config has no allowlist.
+///
+/// # Errors
+///
+/// Returns an error if a suite is not supported or if no allowed suite can be
+/// used with one of the enabled TLS versions.
+pub fn cipher_suites(config: &TlsConfig) -> anyhow::Result<Vec<SupportedCipherSuite>> {
+    let name = |suite: &SupportedCipherSuite| suite.suite().as_str().unwrap_or_default();
+    if config.cipher_suites.is_empty() {
+        return Ok(ring::ALL_CIPHER_SUITES.to_vec());
+    }
+
+    let mut suites = Vec::with_capacity(config.cipher_suites.len());
+    for wanted in &config.cipher_suites {
+        let suite = ring::ALL_CIPHER_SUITES
+            .iter()
+            .find(|suite| name(suite).eq_ignore_ascii_case(wanted.trim()))
+            .ok_or_else(|| {
+                let supported = ring::ALL_CIPHER_SUITES
+                    .iter()
+                    .map(name)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                anyhow::Error::msg(format!(
+                    "tls.cipher-suites: unsupported cipher suite `{wanted}`, supported suites are {supported}"
+                ))
+            })?;
+        if !suites.contains(suite) {
+            suites.push(*suite);
+        }
+    }
+
+    // TLS 1.3 is always enabled and TLS 1.2 unless it is the min version
+    if !suites
+        .iter()
+        .any(|suite| matches!(suite, SupportedCipherSuite::Tls13(_)))
+    {
+        return Err(anyhow::Error::msg(
+            "tls.cipher-suites: at least one TLS 1.3 cipher suite must be allowed",
+        ));
+    }
+    if config.min_version == TlsVersion::Tls12
+        && !suites
+            .iter()
+            .any(|suite| matches!(suite, SupportedCipherSuite::Tls12(_)))
+    {
+        return Err(anyhow::Error::msg(
+            "tls.cipher-suites: no TLS 1.2 cipher suite is allowed, set `min-version = \"1.3\"` to only serve TLS 1.3",
+        ));
+    }
+
+    Ok(suites)
+}
+
+/// Load the certificates and key and build the TLS server config.
+pub fn server_config(config: &TlsConfig) -> anyhow::Result<Arc<ServerConfig>> {
+    let provider = Arc::new(rustls::crypto::CryptoProvider {
+        cipher_suites: cipher_suites(config)?,
+        ..ring::default_provider()
+    });
+
+    let cert_chain = CertificateDer::pem_file_iter(&config.cert_file)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .with_context(|| {
+            format!(
+                "Failed to read TLS certificate chain from `{}`",
+                config.cert_file.display()
+            )
+        })?;
+    if cert_chain.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "No certificates found in `{}`",
+            config.cert_file.display()
+        )));
+    }
+    let key = PrivateKeyDer::from_pem_file(&config.key_file).with_context(|| {
+        format!(
+            "Failed to read TLS private key from `{}`",
+            config.key_file.display()
+        )
+    })?;
+
+    let versions: &[&rustls::SupportedProtocolVersion] = match config.min_version {
+        TlsVersion::Tls12 => &[&rustls::version::TLS13, &rustls::version::TLS12],
+        TlsVersion::Tls13 => &[&rustls::version::TLS13],
+    };
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_protocol_versions(versions)?;
+
+    let builder = match config.client_ca_file.as_ref() {
+        Some(client_ca_file) if config.request_client_cert || config.require_client_cert => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(client_ca_file)? {
+                roots.add(cert?)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.require_client_cert {
+                verifier.build()?
+            } else {
+                verifier.allow_unauthenticated().build()?
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(cert_chain, key)
+        .map_err(|err| match err {
+            rustls::Error::InconsistentKeys(InconsistentKeys::KeyMismatch) => {
+                anyhow::Error::msg(format!(
+                    "TLS private key `{}` does not match the certificate `{}`",
+                    config.key_file.display(),
+                    config.cert_file.display()
+                ))
+            }
+            err => anyhow::Error::new(err).context("Invalid TLS certificate or key"),
+        })?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(Arc::new(server_config))
+}
+
+/// Bind a TCP listener and return the TLS connections accepted on it.
+///
+/// Handshakes run concurrently so a slow client cannot block the accept loop.
+/// The listener is closed once the returned acceptor is dropped.
+pub async fn bind(
+    addr: SocketAddr,
+    config: Arc<ReloadableServerConfig>,
+) -> io::Result<(
+    impl Accept<Conn = TlsStream<TcpStream>, Error = io::Error>,
+    SocketAddr,
+)> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let (tx, rx) = mpsc::channel::<io::Result<TlsStream<TcpStream>>>(ACCEPT_BACKLOG);
+
+    tokio::spawn(async move {
+        loop {
+            let accepted = tokio::select! {
+                () = tx.closed() => break,
+                accepted = listener.accept() => accepted,
+            };
+            let (stream, peer) = match accepted {
+                Ok(conn) => conn,
+                Err(error) => {
+                    warn!(?error, "Failed to accept connection");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            let acceptor = TlsAcceptor::from(config.current());
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = tx.send(Ok(stream)).await;
+                    }
+                    Ok(Err(error)) => debug!(?error, %peer, "TLS handshake failed"),
+                    Err(_) => debug!(%peer, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+
+    let incoming = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|conn| (conn, rx))
+    });
+    Ok((hyper::server::accept::from_stream(incoming), local_addr))
+}
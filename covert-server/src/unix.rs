@@ -0,0 +1,114 @@
+use std::{
+    io,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::Path,
+    time::Duration,
+};
+
+use covert_types::request::PeerCredentials;
+use hyper::server::accept::Accept;
+use tokio::net::{UnixListener, UnixStream};
+use tracing::warn;
+
+use crate::config::ListenerConfig;
+
+/// Bind a unix socket listener at `path` and return the connections accepted
+/// on it.
+///
+/// A socket file left behind by a previous run is replaced, any other file at
+/// `path` is left untouched and binding fails.
+pub fn bind(
+    path: &Path,
+    config: &ListenerConfig,
+) -> io::Result<impl Accept<Conn = UnixStream, Error = io::Error>> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("`{}` exists and is not a socket", path.display()),
+            ))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err),
+    }
+
+    let listener = UnixListener::bind(path)?;
+    if let Some(mode) = config.mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode.0))?;
+    }
+    if config.owner.is_some() || config.group.is_some() {
+        std::os::unix::fs::chown(path, config.owner, config.group)?;
+    }
+
+    let incoming = futures::stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => return Some((Ok(stream), listener)),
+                Err(error) => {
+                    warn!(?error, "Failed to accept connection");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(hyper::server::accept::from_stream(incoming))
+}
+
+/// Credentials of the peer process, read with `SO_PEERCRED` or its platform
+/// equivalent.
+pub fn peer_credentials(stream: &UnixStream) -> Option<PeerCredentials> {
+    match stream.peer_cred() {
+        Ok(cred) => Some(PeerCredentials {
+            uid: cred.uid(),
+            gid: cred.gid(),
+            pid: cred.pid(),
+        }),
+        Err(error) => {
+            warn!(?error, "Failed to read peer credentials");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use crate::config::{FileMode, ListenerAddress};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn bind_and_read_peer_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("covert.sock");
+        let config = ListenerConfig {
+            address: ListenerAddress::Unix(path.clone()),
+            mode: Some(FileMode(0o600)),
+            owner: None,
+            group: None,
+        };
+
+        // Stale sockets are replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let mut incoming = Box::pin(bind(&path, &config).unwrap());
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o600);
+
+        let _client = UnixStream::connect(&path).await.unwrap();
+        let conn = futures::future::poll_fn(|cx| incoming.as_mut().poll_accept(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let cred = peer_credentials(&conn).unwrap();
+        assert_eq!(cred.uid, metadata.uid());
+        assert_eq!(cred.pid, Some(i32::try_from(std::process::id()).unwrap()));
+
+        // Other files are never removed
+        let file = dir.path().join("file");
+        std::fs::write(&file, "foo").unwrap();
+        assert!(bind(&file, &config).is_err());
+        assert!(file.exists());
+    }
+}
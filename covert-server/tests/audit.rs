@@ -0,0 +1,477 @@
+mod common;
+
+use covert_sdk::{
+    audit::{
+        AuditDeviceConfig, AuditFormat, AuditHashParams, AuditTailEvent, AuditTailParams,
+        EnableAuditDeviceParams, FileAuditConfig, SocketAuditConfig, SocketType,
+    },
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    userpass::LoginParams,
+    Client, ErrorCode, TokenHeader,
+};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::{TcpListener, UdpSocket, UnixDatagram},
+};
+
+use common::{login_with_policy, setup_unseal, start, unseal};
+
+fn file_device(file_path: &str) -> EnableAuditDeviceParams {
+    EnableAuditDeviceParams {
+        config: AuditDeviceConfig::File(FileAuditConfig {
+            file_path: file_path.to_string(),
+            format: AuditFormat::Jsonl,
+            mode: None,
+        }),
+        hmac_exempt_response_fields: vec![],
+        filter: vec![],
+    }
+}
+
+fn socket_device(address: &str, socket_type: SocketType) -> EnableAuditDeviceParams {
+    EnableAuditDeviceParams {
+        config: AuditDeviceConfig::Socket(SocketAuditConfig {
+            address: address.to_string(),
+            socket_type,
+            format: AuditFormat::Jsonl,
+            write_timeout: None,
+        }),
+        hmac_exempt_response_fields: vec![],
+        filter: vec![],
+    }
+}
+
+async fn audit_hash(sdk: &Client, path: &str, input: &str) -> String {
+    sdk.audit
+        .hash(
+            path,
+            &AuditHashParams {
+                input: input.to_string(),
+            },
+        )
+        .await
+        .unwrap()
+        .hash
+}
+
+fn read_entries(path: &std::path::Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn file_audit_device() {
+    // Over TCP, requests answered in process have no remote address
+    let port = start(":memory:", covert_system::shutdown_signal(), None).await;
+    let sdk = Client::new(format!("http://localhost:{port}/v1"));
+    unseal(&sdk).await;
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("audit.log");
+
+    let device = sdk
+        .audit
+        .enable("file", &file_device(log_path.to_str().unwrap()))
+        .await
+        .unwrap();
+    assert_eq!(device.path, "file");
+    assert_eq!(
+        sdk.audit.list().await.unwrap().devices,
+        vec![device.clone()]
+    );
+
+    // Enabling twice is a conflict
+    let err = sdk
+        .audit
+        .enable("file", &file_device(log_path.to_str().unwrap()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Conflict));
+
+    // Request and response entries are written for every request
+    let entries = read_entries(&log_path);
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0]["type"], "request");
+    assert_eq!(entries[0]["request"]["operation"], "Read");
+    assert_eq!(entries[0]["request"]["path"], "sys/audit");
+    assert_eq!(entries[0]["request"]["namespace"], "root");
+    assert_eq!(entries[0]["auth"]["entity"], "root");
+    assert_eq!(entries[0]["auth"]["policies"][0], "root");
+    assert_eq!(entries[0]["auth"]["policy_result"], "sudo");
+    assert_eq!(entries[0]["auth"]["token_header"], "covert");
+    assert!(entries[0]["request"]["remote_address"]
+        .as_str()
+        .unwrap()
+        .starts_with("127.0.0.1:"));
+    assert_eq!(entries[1]["type"], "response");
+    assert_eq!(entries[1]["request"]["id"], entries[0]["request"]["id"]);
+    assert_eq!(entries[1]["response"]["mount_path"], "sys/");
+    assert!(entries[1].get("error").is_none());
+    assert_eq!(entries[3]["error"]["code"], "conflict");
+
+    // The token is never written
+    let client_token = entries[0]["auth"]["client_token"].as_str().unwrap();
+    assert!(client_token.starts_with("hmac-sha256:"));
+    assert!(!std::fs::read_to_string(&log_path).unwrap().contains("\"s."));
+
+    // One device that can write the entry is enough
+    sdk.audit
+        .enable("full", &file_device("/dev/full"))
+        .await
+        .unwrap();
+    assert_eq!(sdk.audit.list().await.unwrap().devices.len(), 2);
+
+    // Requests fail when no device can write the entry
+    sdk.audit.disable("file").await.unwrap();
+    let err = sdk.audit.list().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Internal));
+}
+
+#[tokio::test]
+async fn audit_entries_hash_sensitive_values() {
+    let sdk = setup_unseal().await;
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("audit.log");
+
+    let mount_path = "auth/userpass/";
+    sdk.mount
+        .create(
+            mount_path,
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let mut params = file_device(log_path.to_str().unwrap());
+    params.hmac_exempt_response_fields = vec!["lease_id".to_string()];
+    let device = sdk.audit.enable("file", &params).await.unwrap();
+    assert_eq!(device.hmac_exempt_response_fields, vec!["lease_id"]);
+
+    let token = login_with_policy(&sdk, "foo", r#"path "sys/*" { capabilities = ["read"] }"#).await;
+    let auth = sdk
+        .userpass
+        .login(
+            mount_path,
+            &LoginParams {
+                username: "foo".to_string(),
+                password: "password".to_string(),
+                renewable: true,
+            },
+        )
+        .await
+        .unwrap();
+    let hashed_password = audit_hash(&sdk, "file", "password").await;
+    let hashed_login_token = audit_hash(&sdk, "file", &auth.token.to_string()).await;
+    let hashed_token = audit_hash(&sdk, "file", &token).await;
+
+    // Hashing requires an enabled device
+    let err = sdk
+        .audit
+        .hash(
+            "missing",
+            &AuditHashParams {
+                input: "foo".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    sdk.set_token(Some(token.clone())).await;
+    sdk.mount.list().await.unwrap();
+
+    // Sensitive values can only be found through their hash
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    assert!(!log.contains(&token));
+    assert!(!log.contains(&auth.token.to_string()));
+    assert!(!log.contains("\"password\":\"password\""));
+
+    let entries = read_entries(&log_path);
+    let login = entries
+        .iter()
+        .rev()
+        .find(|entry| entry["request"]["path"] == "auth/userpass/login")
+        .unwrap();
+    assert_eq!(login["request"]["data"]["password"], hashed_password);
+    assert_eq!(login["response"]["data"]["token"], hashed_login_token);
+    // Exempt response fields are written in plaintext
+    assert_eq!(login["response"]["data"]["lease_id"], auth.lease_id);
+
+    let last = entries.last().unwrap();
+    assert_eq!(last["request"]["path"], "sys/mounts");
+    assert_eq!(last["auth"]["client_token"], hashed_token);
+    assert_eq!(last["auth"]["entity"], "foo");
+    assert_eq!(last["auth"]["metadata"]["username"], "foo");
+}
+
+#[tokio::test]
+async fn token_in_authorization_header() {
+    let port = start(":memory:", covert_system::shutdown_signal(), None).await;
+    let sdk = Client::builder(format!("http://localhost:{port}/v1"))
+        .token_header(TokenHeader::Authorization)
+        .build()
+        .unwrap();
+    let token = unseal(&sdk).await;
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("audit.log");
+    sdk.audit
+        .enable("file", &file_device(log_path.to_str().unwrap()))
+        .await
+        .unwrap();
+
+    assert_eq!(sdk.token.lookup_self().await.unwrap().entity_name, "root");
+    let entries = read_entries(&log_path);
+    let last = entries.last().unwrap();
+    assert_eq!(last["request"]["path"], "sys/token/lookup-self");
+    assert_eq!(last["auth"]["token_header"], "authorization");
+    assert_eq!(
+        last["auth"]["client_token"],
+        audit_hash(&sdk, "file", &token).await
+    );
+
+    // Both headers can be sent as long as they carry the same token
+    let send = |covert_token: &str| {
+        let req = hyper::Request::get(format!("http://localhost:{port}/v1/sys/token/lookup-self"))
+            .header("X-Covert-Token", covert_token)
+            .header("Authorization", format!("Bearer {token}"))
+            .body(hyper::Body::empty())
+            .unwrap();
+        hyper::Client::new().request(req)
+    };
+    let resp = send(&token).await.unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::OK);
+    let resp = send("s.other").await.unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn file_audit_device_must_be_writable() {
+    let sdk = setup_unseal().await;
+
+    let err = sdk
+        .audit
+        .enable("file", &file_device("/does/not/exist/audit.log"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    let mut params = file_device("/tmp/audit.log");
+    let AuditDeviceConfig::File(config) = &mut params.config else {
+        unreachable!()
+    };
+    config.mode = Some("999".to_string());
+    let err = sdk.audit.enable("file", &params).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    assert!(sdk.audit.list().await.unwrap().devices.is_empty());
+}
+
+#[tokio::test]
+async fn disable_unknown_audit_device() {
+    let sdk = setup_unseal().await;
+
+    let err = sdk.audit.disable("missing").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
+
+#[tokio::test]
+async fn tcp_audit_device() {
+    let sdk = setup_unseal().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    sdk.audit
+        .enable("tcp", &socket_device(&addr, SocketType::Tcp))
+        .await
+        .unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut lines = BufReader::new(stream).lines();
+
+    sdk.audit.list().await.unwrap();
+    let request: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(request["type"], "request");
+    assert_eq!(request["request"]["path"], "sys/audit");
+    assert_eq!(response["type"], "response");
+    assert_eq!(response["request"]["id"], request["request"]["id"]);
+
+    // The request entry of the read is written before the status is read
+    let status = sdk.audit.read("tcp").await.unwrap();
+    assert_eq!(status.device.path, "tcp");
+    assert_eq!(status.entries_written, 3);
+    assert!(status.last_error.is_none());
+
+    let err = sdk.audit.read("missing").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
+
+#[tokio::test]
+async fn udp_audit_device() {
+    let sdk = setup_unseal().await;
+    let collector = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = collector.local_addr().unwrap().to_string();
+
+    sdk.audit
+        .enable("udp", &socket_device(&addr, SocketType::Udp))
+        .await
+        .unwrap();
+    sdk.audit.list().await.unwrap();
+
+    // One entry per datagram
+    let mut buf = vec![0; 64 * 1024];
+    let len = collector.recv(&mut buf).await.unwrap();
+    let entry: Value = serde_json::from_slice(&buf[..len]).unwrap();
+    assert_eq!(entry["type"], "request");
+    assert_eq!(entry["request"]["path"], "sys/audit");
+}
+
+#[tokio::test]
+async fn socket_audit_device_fails_closed() {
+    let sdk = setup_unseal().await;
+    let dir = tempfile::tempdir().unwrap();
+    let socket_path = dir.path().join("audit.sock");
+    let log_path = dir.path().join("audit.log");
+
+    // Socket must be reachable when the device is enabled
+    let err = sdk
+        .audit
+        .enable(
+            "unix",
+            &socket_device(socket_path.to_str().unwrap(), SocketType::Unixgram),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    let collector = UnixDatagram::bind(&socket_path).unwrap();
+    sdk.audit
+        .enable("file", &file_device(log_path.to_str().unwrap()))
+        .await
+        .unwrap();
+    sdk.audit
+        .enable(
+            "unix",
+            &socket_device(socket_path.to_str().unwrap(), SocketType::Unixgram),
+        )
+        .await
+        .unwrap();
+    sdk.audit.list().await.unwrap();
+    let mut buf = vec![0; 64 * 1024];
+    let len = collector.recv(&mut buf).await.unwrap();
+    let entry: Value = serde_json::from_slice(&buf[..len]).unwrap();
+    assert_eq!(entry["request"]["path"], "sys/audit");
+
+    // The file device keeps requests going while the collector is gone
+    drop(collector);
+    std::fs::remove_file(&socket_path).unwrap();
+    sdk.audit.list().await.unwrap();
+    let status = sdk.audit.read("unix").await.unwrap();
+    assert_eq!(status.entries_written, 2);
+    assert!(status.last_error.is_some());
+
+    // Requests fail when no device can write the entry
+    sdk.audit.disable("file").await.unwrap();
+    let err = sdk.audit.list().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Internal));
+}
+
+#[tokio::test]
+async fn tail_audit_device() {
+    let port = start(":memory:", covert_system::shutdown_signal(), None).await;
+    let sdk = Client::new(format!("http://localhost:{port}/v1"));
+    unseal(&sdk).await;
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("audit.log");
+    sdk.audit
+        .enable("file", &file_device(log_path.to_str().unwrap()))
+        .await
+        .unwrap();
+    sdk.audit.list().await.unwrap();
+
+    // Starts with the last entries, including the request entry of the tail
+    let mut tail = sdk
+        .audit
+        .tail(
+            "file",
+            &AuditTailParams {
+                last: Some(3),
+                ..AuditTailParams::default()
+            },
+        )
+        .await
+        .unwrap();
+    let Some(AuditTailEvent::Start { epoch }) = tail.next().await.unwrap() else {
+        panic!("stream does not start with the epoch");
+    };
+    let mut entries = vec![];
+    for expected in 0..4 {
+        let Some(AuditTailEvent::Entry { seq, entry }) = tail.next().await.unwrap() else {
+            panic!("expected entry {expected}");
+        };
+        assert_eq!(seq, expected);
+        entries.push(entry);
+    }
+    assert_eq!(entries[0]["request"]["path"], "sys/audit");
+    assert_eq!(entries[2]["type"], "request");
+    assert_eq!(entries[2]["request"]["path"], "sys/audit-tail/file");
+    assert_eq!(entries[3]["type"], "response");
+    // Same entries as written by the device, with hashed tokens
+    assert_eq!(entries, read_entries(&log_path)[..4]);
+
+    // An interrupted stream resumes after the last entry received
+    drop(tail);
+    sdk.audit.list().await.unwrap();
+    let mut resumed = sdk
+        .audit
+        .tail(
+            "file",
+            &AuditTailParams {
+                epoch: Some(epoch),
+                since: Some(3),
+                last: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        resumed.next().await.unwrap(),
+        Some(AuditTailEvent::Start { epoch })
+    );
+    let Some(AuditTailEvent::Entry { seq, entry }) = resumed.next().await.unwrap() else {
+        panic!("expected the entry after the last one received");
+    };
+    assert_eq!(seq, 4);
+    assert_eq!(entry["request"]["path"], "sys/audit");
+
+    // Entries of another epoch cannot be resumed from
+    let mut other = sdk
+        .audit
+        .tail(
+            "file",
+            &AuditTailParams {
+                epoch: Some(uuid::Uuid::new_v4()),
+                since: Some(3),
+                last: None,
+            },
+        )
+        .await
+        .unwrap();
+    other.next().await.unwrap();
+    assert_eq!(
+        other.next().await.unwrap(),
+        Some(AuditTailEvent::Gap { missed: None })
+    );
+
+    let err = sdk
+        .audit
+        .tail("missing", &AuditTailParams::default())
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
@@ -1,5 +1,8 @@
 use covert_sdk::{
+    entity::{AttachEntityAliasParams, AttachEntityPolicyParams, CreateEntityParams, EntityAlias},
     operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    policy::CreatePolicyParams,
+    userpass::{CreateUserParams, LoginParams},
     Client,
 };
 use covert_system::ReplicationConfig;
@@ -7,40 +10,92 @@ use tokio::sync::oneshot;
 
 use std::future::Future;
 
+/// Start a server and return a client sending its requests to the server in
+/// process, without binding a port.
 pub async fn setup(
     storage_path: &str,
     shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
     replication: Option<ReplicationConfig>,
 ) -> Client {
+    serve(config(storage_path, replication), shutdown_signal).await
+}
+
+async fn serve(
+    mut config: covert_system::Config,
+    shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
+) -> Client {
+    let (service_tx, service_rx) = oneshot::channel();
+    config.service_tx = Some(service_tx);
+    spawn(config, shutdown_signal);
+
+    Client::builder("http://covert/v1")
+        .service(service_rx.await.unwrap())
+        .build()
+        .unwrap()
+}
+
+/// Start a server and return the port it listens on.
+pub async fn start(
+    storage_path: &str,
+    shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
+    replication: Option<ReplicationConfig>,
+) -> u16 {
     let (port_tx, port_rx) = oneshot::channel();
+    let mut config = config(storage_path, replication);
+    config.port = Some(0);
+    config.port_tx = Some(port_tx);
+    spawn(config, shutdown_signal);
 
-    let config = covert_system::Config {
-        port: 0,
-        port_tx: Some(port_tx),
+    port_rx.await.unwrap()
+}
+
+fn config(storage_path: &str, replication: Option<ReplicationConfig>) -> covert_system::Config {
+    covert_system::Config {
+        tls_disable: true,
         storage_path: storage_path.into(),
         replication,
-    };
+        ..Default::default()
+    }
+}
 
+fn spawn(
+    config: covert_system::Config,
+    shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
+) {
     tokio::spawn(async move {
         if let Err(err) = covert_system::start(config, shutdown_signal).await {
             panic!("server error: {}", err);
         }
     });
+}
 
-    let port = port_rx.await.unwrap();
-    let sdk = Client::new(format!("http://localhost:{port}/v1"));
+#[allow(dead_code)]
+pub async fn setup_unseal() -> Client {
+    let sdk = setup(":memory:", covert_system::shutdown_signal(), None).await;
+    unseal(&sdk).await;
+    sdk
+}
 
+/// Like [`setup_unseal`], with changes to the config of the server.
+#[allow(dead_code)]
+pub async fn setup_unseal_with(configure: impl FnOnce(&mut covert_system::Config)) -> Client {
+    let mut config = config(":memory:", None);
+    configure(&mut config);
+    let sdk = serve(config, covert_system::shutdown_signal()).await;
+    unseal(&sdk).await;
     sdk
 }
 
+/// Initialize and unseal the server with one key share and use the root token,
+/// which is returned.
 #[allow(dead_code)]
-pub async fn setup_unseal() -> Client {
-    let sdk = setup(":memory:", covert_system::shutdown_signal(), None).await;
+pub async fn unseal(sdk: &Client) -> String {
     let shares = match sdk
         .operator
         .initialize(&InitializeParams {
             shares: 1,
             threshold: 1,
+            stored_shares: None,
         })
         .await
         .unwrap()
@@ -49,9 +104,69 @@ pub async fn setup_unseal() -> Client {
         _ => panic!("should get new shares"),
     };
     let resp = sdk.operator.unseal(&UnsealParams { shares }).await.unwrap();
-    if let UnsealResponse::Complete { root_token } = resp {
-        sdk.set_token(Some(root_token.to_string())).await;
-    }
+    let UnsealResponse::Complete { root_token } = resp else {
+        panic!("should be unsealed");
+    };
+    sdk.set_token(Some(root_token.to_string())).await;
+    root_token.to_string()
+}
 
-    sdk
+/// Create a userpass user with an entity carrying the policy and return a
+/// token for it. A userpass backend must be mounted at `auth/userpass/`.
+#[allow(dead_code)]
+pub async fn login_with_policy(sdk: &Client, name: &str, policy: &str) -> String {
+    let mount_path = "auth/userpass/";
+    sdk.policy
+        .create(&CreatePolicyParams {
+            name: name.to_string(),
+            policy: policy.to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.userpass
+        .create(
+            mount_path,
+            &CreateUserParams {
+                username: name.to_string(),
+                password: "password".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: name.to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_policies(&AttachEntityPolicyParams {
+            name: name.to_string(),
+            policy_names: vec![name.to_string()],
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: name.to_string(),
+            aliases: vec![EntityAlias {
+                name: name.to_string(),
+                mount_path: mount_path.to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+    let auth = sdk
+        .userpass
+        .login(
+            mount_path,
+            &LoginParams {
+                username: name.to_string(),
+                password: "password".to_string(),
+                renewable: true,
+            },
+        )
+        .await
+        .unwrap();
+    auth.token.to_string()
 }
@@ -1,8 +1,19 @@
 mod common;
 
-use covert_sdk::entity::CreateEntityParams;
+use std::collections::HashMap;
 
-use common::setup_unseal;
+use covert_sdk::{
+    entity::{
+        AttachEntityAliasParams, ChildTokens, CreateEntityParams, DeleteEntityParams, EntityAlias,
+        ImportEntitiesParams, ImportEntity, ImportEntityStatus,
+    },
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    policy::CreatePolicyParams,
+    userpass::LoginParams,
+    ErrorCode,
+};
+
+use common::{login_with_policy, setup, setup_unseal, unseal};
 
 #[tokio::test]
 async fn entity() {
@@ -18,3 +29,234 @@ async fn entity() {
 
     assert_eq!(entity.name, name);
 }
+
+#[tokio::test]
+async fn delete_entity() {
+    let sdk = setup(":memory:", covert_system::shutdown_signal(), None).await;
+    let root_token = unseal(&sdk).await;
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Userpass,
+            },
+        )
+        .await
+        .unwrap();
+    let policy = r#"path "sys/*" { capabilities = ["read"] }"#;
+    let foo_token = login_with_policy(&sdk, "foo", policy).await;
+    let bar_token = login_with_policy(&sdk, "bar", policy).await;
+
+    // Logging in with the token of `foo` issues a child token to `bar`
+    sdk.set_token(Some(foo_token.clone())).await;
+    let child_token = sdk
+        .userpass
+        .login(
+            "auth/userpass/",
+            &LoginParams {
+                username: "bar".to_string(),
+                password: "password".to_string(),
+                renewable: true,
+            },
+        )
+        .await
+        .unwrap()
+        .token
+        .to_string();
+
+    sdk.set_token(Some(root_token.clone())).await;
+    let resp = sdk
+        .entity
+        .delete(
+            "foo",
+            &DeleteEntityParams {
+                child_tokens: ChildTokens::Orphan,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.name, "foo");
+    assert_eq!(resp.revoked_tokens, 1);
+    assert_eq!(resp.orphaned_tokens, 1);
+
+    let err = sdk
+        .entity
+        .delete("foo", &DeleteEntityParams::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+    let entities = sdk.entity.list().await.unwrap().entities;
+    assert!(entities.iter().all(|entity| entity.name != "foo"));
+
+    // The tokens of the entity are revoked, the child token of `bar` is kept
+    sdk.set_token(Some(foo_token)).await;
+    assert!(sdk.token.lookup_self().await.is_err());
+    sdk.set_token(Some(child_token.clone())).await;
+    assert!(sdk.token.lookup_self().await.is_ok());
+
+    // By default all the tokens of the entity are revoked
+    sdk.set_token(Some(root_token)).await;
+    let resp = sdk
+        .entity
+        .delete("bar", &DeleteEntityParams::default())
+        .await
+        .unwrap();
+    assert_eq!(resp.revoked_tokens, 2);
+    assert_eq!(resp.orphaned_tokens, 0);
+    for token in [bar_token, child_token] {
+        sdk.set_token(Some(token)).await;
+        assert!(sdk.token.lookup_self().await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn import_entities() {
+    let sdk = setup_unseal().await;
+
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Userpass,
+            },
+        )
+        .await
+        .unwrap();
+    sdk.policy
+        .create(&CreatePolicyParams {
+            name: "reader".to_string(),
+            policy: r#"path "kv/*" { capabilities = ["read"] }"#.to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: "existing".to_string(),
+        })
+        .await
+        .unwrap();
+    let taken = EntityAlias {
+        name: "taken".to_string(),
+        mount_path: "auth/userpass/".to_string(),
+    };
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: "existing".to_string(),
+            aliases: vec![taken.clone()],
+        })
+        .await
+        .unwrap();
+
+    let alias = |name: &str| EntityAlias {
+        name: name.to_string(),
+        mount_path: "auth/userpass/".to_string(),
+    };
+    let entity = |name: &str, policies: &[&str], aliases: Vec<EntityAlias>| ImportEntity {
+        name: name.to_string(),
+        metadata: HashMap::from([("source".to_string(), "import".to_string())]),
+        policies: policies.iter().map(ToString::to_string).collect(),
+        aliases,
+    };
+    let resp = sdk
+        .entity
+        .import(&ImportEntitiesParams {
+            entities: vec![
+                entity("alice", &["reader"], vec![alias("alice")]),
+                entity("bob", &[], vec![taken.clone()]),
+                entity("existing", &[], vec![]),
+                entity("carol", &["unknown"], vec![]),
+                entity("dave", &[], vec![alias("alice")]),
+                entity("alice", &[], vec![]),
+            ],
+        })
+        .await
+        .unwrap();
+    let statuses = resp
+        .entities
+        .into_iter()
+        .map(|result| (result.name, result.status))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses[..2],
+        [
+            (
+                "alice".to_string(),
+                ImportEntityStatus::Created {
+                    skipped_aliases: vec![]
+                }
+            ),
+            (
+                "bob".to_string(),
+                ImportEntityStatus::Created {
+                    skipped_aliases: vec![taken.clone()]
+                }
+            ),
+        ]
+    );
+    for (name, status) in &statuses[2..4] {
+        assert!(
+            matches!(status, ImportEntityStatus::Failed { .. }),
+            "{name}: {status:?}"
+        );
+    }
+    // Aliases are deduplicated within the import as well
+    assert_eq!(
+        statuses[4].1,
+        ImportEntityStatus::Created {
+            skipped_aliases: vec![alias("alice")]
+        }
+    );
+    assert!(matches!(statuses[5].1, ImportEntityStatus::Failed { .. }));
+
+    let entities = sdk.entity.list().await.unwrap().entities;
+    let alice = entities.iter().find(|e| e.name == "alice").unwrap();
+    assert_eq!(alice.policies, vec!["reader".to_string()]);
+    assert_eq!(alice.aliases, vec![alias("alice")]);
+    assert_eq!(alice.metadata["source"], "import");
+    let bob = entities.iter().find(|e| e.name == "bob").unwrap();
+    assert!(bob.aliases.is_empty());
+    assert!(entities.iter().any(|e| e.name == "dave"));
+    assert!(!entities.iter().any(|e| e.name == "carol"));
+}
+
+#[tokio::test]
+async fn create_entity_with_idempotency_key() {
+    let sdk = setup_unseal().await;
+
+    let params = CreateEntityParams {
+        name: "foo".to_string(),
+    };
+    let entity = sdk
+        .entity
+        .create_idempotent(&params, "create-foo")
+        .await
+        .unwrap()
+        .entity;
+
+    // Retries return the original entity instead of failing on the duplicate
+    let retry = sdk
+        .entity
+        .create_idempotent(&params, "create-foo")
+        .await
+        .unwrap()
+        .entity;
+    assert_eq!(retry, entity);
+
+    // Without the key the duplicate is still rejected
+    assert!(sdk.entity.create(&params).await.is_err());
+
+    // The key can't be reused for another request
+    let err = sdk
+        .entity
+        .create_idempotent(
+            &CreateEntityParams {
+                name: "bar".to_string(),
+            },
+            "create-foo",
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("idempotency key"), "{err}");
+}
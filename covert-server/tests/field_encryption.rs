@@ -0,0 +1,264 @@
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+
+use covert_sdk::{
+    kv::{CreateSecretParams, PatchSecretParams},
+    mounts::{BackendType, CreateMountParams, MountConfig, TransitKey, UpdateMountParams},
+    transit::UpdateKeyParams,
+    Client,
+};
+
+use common::{login_with_policy, setup_unseal};
+
+/// Mount a transit engine with the key `app` and a KV engine that encrypts
+/// the `password` field with it.
+async fn setup_mounts(sdk: &Client) {
+    sdk.mount
+        .create(
+            "transit/",
+            &CreateMountParams {
+                variant: BackendType::Transit,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.transit.create_key("transit/", "app").await.unwrap();
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                variant: BackendType::Kv,
+                config: MountConfig {
+                    encrypted_fields: encrypted_fields(),
+                    ..Default::default()
+                },
+            },
+        )
+        .await
+        .unwrap();
+}
+
+fn encrypted_fields() -> BTreeMap<String, TransitKey> {
+    BTreeMap::from([(
+        "password".to_string(),
+        TransitKey {
+            mount: "transit/".to_string(),
+            key: "app".to_string(),
+        },
+    )])
+}
+
+async fn set_encrypted_fields(sdk: &Client, encrypted_fields: BTreeMap<String, TransitKey>) {
+    sdk.mount
+        .update(
+            "kv/",
+            &UpdateMountParams {
+                config: MountConfig {
+                    encrypted_fields,
+                    ..Default::default()
+                },
+            },
+        )
+        .await
+        .unwrap();
+}
+
+fn secret() -> CreateSecretParams {
+    CreateSecretParams {
+        data: HashMap::from([
+            ("username".to_string(), "admin".to_string()),
+            ("password".to_string(), "hunter2".to_string()),
+        ]),
+    }
+}
+
+#[tokio::test]
+async fn fields_are_encrypted_with_transit() {
+    let sdk = setup_unseal().await;
+    setup_mounts(&sdk).await;
+    let config = sdk.mount.get("kv/").await.unwrap().config;
+    assert_eq!(config.encrypted_fields, encrypted_fields());
+
+    sdk.kv.create("kv/", "db", &secret()).await.unwrap();
+
+    // The root token may decrypt with the key
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert_eq!(data, secret().data);
+
+    // Patched fields are encrypted as well
+    sdk.kv
+        .patch(
+            "kv/",
+            "db",
+            &PatchSecretParams {
+                data: HashMap::from([("password".to_string(), Some("hunter3".to_string()))]),
+            },
+        )
+        .await
+        .unwrap();
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert_eq!(data["password"], "hunter3");
+
+    // The stored field is the ciphertext of the key
+    set_encrypted_fields(&sdk, BTreeMap::new()).await;
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert!(data["password"].starts_with("covert:v1:"));
+    assert_eq!(data["username"], "admin");
+}
+
+#[tokio::test]
+async fn callers_without_decrypt_capability_get_ciphertext() {
+    let sdk = setup_unseal().await;
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    setup_mounts(&sdk).await;
+    sdk.kv.create("kv/", "db", &secret()).await.unwrap();
+
+    let reader =
+        login_with_policy(&sdk, "reader", r#"path "kv/*" { capabilities = ["read"] }"#).await;
+    let decrypter = login_with_policy(
+        &sdk,
+        "decrypter",
+        r#"
+        path "kv/*" { capabilities = ["read"] }
+        path "transit/decrypt/app" { capabilities = ["update"] }
+        "#,
+    )
+    .await;
+
+    sdk.set_token(Some(reader)).await;
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert!(data["password"].starts_with("covert:v1:"));
+    assert_eq!(data["username"], "admin");
+
+    sdk.set_token(Some(decrypter)).await;
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert_eq!(data, secret().data);
+}
+
+#[tokio::test]
+async fn disabled_key() {
+    let sdk = setup_unseal().await;
+    setup_mounts(&sdk).await;
+    sdk.kv.create("kv/", "db", &secret()).await.unwrap();
+
+    sdk.transit
+        .update_key("transit/", "app", &UpdateKeyParams { disabled: true })
+        .await
+        .unwrap();
+
+    // Writes are refused instead of storing the plaintext
+    let err = sdk.kv.create("kv/", "db", &secret()).await.unwrap_err();
+    assert!(err.message().contains("`password`"), "{err}");
+    assert!(err.message().contains("disabled"), "{err}");
+    // Fields that are not encrypted can still be written
+    sdk.kv
+        .patch(
+            "kv/",
+            "db",
+            &PatchSecretParams {
+                data: HashMap::from([("username".to_string(), Some("root".to_string()))]),
+            },
+        )
+        .await
+        .unwrap();
+
+    // Reads fail instead of returning the ciphertext as the field
+    let err = sdk.kv.read("kv/", "db", None).await.unwrap_err();
+    assert!(err.message().contains("`password`"), "{err}");
+    assert!(err.message().contains("disabled"), "{err}");
+
+    sdk.transit
+        .update_key("transit/", "app", &UpdateKeyParams { disabled: false })
+        .await
+        .unwrap();
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert_eq!(data["password"], "hunter2");
+    assert_eq!(data["username"], "root");
+}
+
+#[tokio::test]
+async fn ciphertexts_are_bound_to_their_field() {
+    let sdk = setup_unseal().await;
+    setup_mounts(&sdk).await;
+    sdk.kv.create("kv/", "db", &secret()).await.unwrap();
+
+    // Copy the ciphertext to another secret and field without encryption
+    set_encrypted_fields(&sdk, BTreeMap::new()).await;
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    let ciphertext = data["password"].clone();
+    let copy = CreateSecretParams {
+        data: HashMap::from([
+            ("username".to_string(), ciphertext.clone()),
+            ("password".to_string(), ciphertext),
+        ]),
+    };
+    sdk.kv.create("kv/", "copy", &copy).await.unwrap();
+
+    let mut fields = encrypted_fields();
+    fields.insert("username".to_string(), fields["password"].clone());
+    set_encrypted_fields(&sdk, fields).await;
+    let err = sdk.kv.read("kv/", "copy", None).await.unwrap_err();
+    assert!(err.message().contains("Failed to decrypt"), "{err}");
+
+    // The field it was written to is still decrypted
+    sdk.kv
+        .patch(
+            "kv/",
+            "db",
+            &PatchSecretParams {
+                data: HashMap::from([("username".to_string(), Some("admin".to_string()))]),
+            },
+        )
+        .await
+        .unwrap();
+    let data = sdk.kv.read("kv/", "db", None).await.unwrap().data.unwrap();
+    assert_eq!(data, secret().data);
+}
+
+#[tokio::test]
+async fn only_kv_mounts_encrypt_fields() {
+    let sdk = setup_unseal().await;
+    setup_mounts(&sdk).await;
+
+    let err = sdk
+        .mount
+        .create(
+            "transit2/",
+            &CreateMountParams {
+                variant: BackendType::Transit,
+                config: MountConfig {
+                    encrypted_fields: encrypted_fields(),
+                    ..Default::default()
+                },
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("`kv` mounts"), "{err}");
+
+    // Fields can only be encrypted with transit mounts
+    set_encrypted_fields(
+        &sdk,
+        BTreeMap::from([(
+            "password".to_string(),
+            TransitKey {
+                mount: "kv/".to_string(),
+                key: "app".to_string(),
+            },
+        )]),
+    )
+    .await;
+    let err = sdk.kv.create("kv/", "db", &secret()).await.unwrap_err();
+    assert!(err.message().contains("not a transit mount"), "{err}");
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use covert_sdk::{
+    entity::{AttachEntityAliasParams, AttachEntityPolicyParams, CreateEntityParams, EntityAlias},
+    identity::{ImportIdentityMode, ImportIdentityParams},
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    policy::CreatePolicyParams,
+    ErrorCode,
+};
+
+use common::setup_unseal;
+
+async fn create_policy(sdk: &covert_sdk::Client, name: &str) {
+    sdk.policy
+        .create(&CreatePolicyParams {
+            name: name.to_string(),
+            policy: format!(r#"path "{name}/*" {{ capabilities = ["read"] }}"#),
+        })
+        .await
+        .unwrap();
+}
+
+async fn create_entity(sdk: &covert_sdk::Client, name: &str, policies: &[&str]) {
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: name.to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_policies(&AttachEntityPolicyParams {
+            name: name.to_string(),
+            policy_names: policies.iter().map(ToString::to_string).collect(),
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn export_and_import() {
+    let sdk = setup_unseal().await;
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Userpass,
+            },
+        )
+        .await
+        .unwrap();
+    create_policy(&sdk, "foo").await;
+    create_policy(&sdk, "bar").await;
+    create_entity(&sdk, "alice", &["foo"]).await;
+    let alias = EntityAlias {
+        name: "alice".to_string(),
+        mount_path: "auth/userpass/".to_string(),
+    };
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: "alice".to_string(),
+            aliases: vec![alias.clone()],
+        })
+        .await
+        .unwrap();
+
+    let snapshot = sdk.identity.export().await.unwrap();
+    let before = sdk.entity.list().await.unwrap().entities;
+
+    // Change the store after the snapshot was taken
+    create_policy(&sdk, "baz").await;
+    create_entity(&sdk, "bob", &["baz"]).await;
+    sdk.entity
+        .attach_policies(&AttachEntityPolicyParams {
+            name: "alice".to_string(),
+            policy_names: vec!["bar".to_string()],
+        })
+        .await
+        .unwrap();
+
+    // Merging keeps everything that is not in the snapshot
+    let resp = sdk
+        .identity
+        .import(&ImportIdentityParams {
+            snapshot: snapshot.clone(),
+            mode: ImportIdentityMode::Merge,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.policies, 2);
+    assert_eq!(resp.entities, 1);
+    assert!(resp.removed_entities.is_empty());
+    assert!(resp.skipped_aliases.is_empty());
+    let entities = sdk.entity.list().await.unwrap().entities;
+    let alice = entities.iter().find(|e| e.name == "alice").unwrap();
+    assert_eq!(alice.policies, vec!["bar".to_string(), "foo".to_string()]);
+    assert_eq!(alice.aliases, vec![alias]);
+    assert!(entities.iter().any(|e| e.name == "bob"));
+
+    // Replacing restores the store as it was exported
+    let resp = sdk
+        .identity
+        .import(&ImportIdentityParams {
+            snapshot,
+            mode: ImportIdentityMode::Replace,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.removed_policies, vec!["baz".to_string()]);
+    assert_eq!(resp.removed_entities, vec!["bob".to_string()]);
+    assert_eq!(sdk.entity.list().await.unwrap().entities, before);
+    let policies = sdk
+        .policy
+        .list()
+        .await
+        .unwrap()
+        .policies
+        .into_iter()
+        .map(|policy| policy.name)
+        .collect::<Vec<_>>();
+    assert!(!policies.contains(&"baz".to_string()));
+    assert!(policies.contains(&"root".to_string()));
+}
+
+#[tokio::test]
+async fn reject_snapshot_of_other_server() {
+    let sdk = setup_unseal().await;
+    create_policy(&sdk, "foo").await;
+    create_entity(&sdk, "alice", &["foo"]).await;
+    let snapshot = sdk.identity.export().await.unwrap();
+
+    // Another server is unsealed with another master key
+    let other = setup_unseal().await;
+    let err = other
+        .identity
+        .import(&ImportIdentityParams {
+            snapshot: snapshot.clone(),
+            mode: ImportIdentityMode::Merge,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    assert!(other
+        .entity
+        .list()
+        .await
+        .unwrap()
+        .entities
+        .iter()
+        .all(|e| e.name != "alice"));
+
+    // A modified snapshot is rejected as well
+    let mut modified = snapshot;
+    modified.created_at += chrono::Duration::seconds(1);
+    let err = sdk
+        .identity
+        .import(&ImportIdentityParams {
+            snapshot: modified,
+            mode: ImportIdentityMode::Replace,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    let entities = sdk.entity.list().await.unwrap().entities;
+    assert!(entities.iter().any(|e| e.name == "alice"));
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use covert_sdk::{
+    lease::LeaseEntry,
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    pagination::PageParams,
+    userpass::LoginParams,
+    Client, ErrorCode,
+};
+use futures::{pin_mut, StreamExt, TryStreamExt};
+
+use common::{login_with_policy, setup_unseal};
+
+const MOUNT_PATH: &str = "auth/userpass/";
+
+/// Log in `count` times, every login issues a token with a lease.
+async fn issue_leases(sdk: &Client, count: usize) {
+    sdk.mount
+        .create(
+            MOUNT_PATH,
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    login_with_policy(sdk, "foo", r#"path "sys/*" { capabilities = ["read"] }"#).await;
+    let login = LoginParams {
+        username: "foo".to_string(),
+        password: "password".to_string(),
+        renewable: true,
+    };
+    for _ in 1..count {
+        sdk.userpass.login(MOUNT_PATH, &login).await.unwrap();
+    }
+}
+
+fn ids(leases: &[LeaseEntry]) -> Vec<String> {
+    leases.iter().map(|lease| lease.id.clone()).collect()
+}
+
+/// Ids of the leases of the mount in the order they are paginated in.
+async fn lease_ids(sdk: &Client) -> Vec<String> {
+    let mut ids = ids(&sdk.lease.list_by_mount(MOUNT_PATH).await.unwrap().leases);
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn list_leases_page_by_page() {
+    let sdk = setup_unseal().await;
+    issue_leases(&sdk, 5).await;
+    let tags = BTreeMap::new();
+
+    let expected = lease_ids(&sdk).await;
+    assert_eq!(expected.len(), 5);
+
+    let page = sdk
+        .lease
+        .list_page_by_mount(
+            MOUNT_PATH,
+            &tags,
+            &PageParams {
+                limit: Some(2),
+                cursor: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids(&page.items), expected[..2]);
+    assert!(page.next_cursor.is_some());
+
+    let leases = sdk
+        .lease
+        .list_all_by_mount(MOUNT_PATH, &tags, 2)
+        .await
+        .unwrap();
+    assert_eq!(ids(&leases), expected);
+    let leases: Vec<_> = sdk
+        .lease
+        .stream_by_mount(MOUNT_PATH, &tags, 2)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(ids(&leases), expected);
+}
+
+#[tokio::test]
+async fn lease_revoked_mid_iteration() {
+    let sdk = setup_unseal().await;
+    issue_leases(&sdk, 5).await;
+    let tags = BTreeMap::new();
+
+    let expected = lease_ids(&sdk).await;
+
+    let leases = sdk.lease.stream_by_mount(MOUNT_PATH, &tags, 2);
+    pin_mut!(leases);
+    let mut seen = vec![];
+    for _ in 0..2 {
+        seen.push(leases.next().await.unwrap().unwrap().id);
+    }
+
+    // The cursor points at a lease that no longer exists, and a lease of a
+    // later page is gone too
+    sdk.lease.revoke(&expected[1]).await.unwrap();
+    sdk.lease.revoke(&expected[3]).await.unwrap();
+    while let Some(lease) = leases.next().await {
+        seen.push(lease.unwrap().id);
+    }
+    assert_eq!(
+        seen,
+        [&expected[0], &expected[1], &expected[2], &expected[4]]
+    );
+}
+
+#[tokio::test]
+async fn invalid_cursor() {
+    let sdk = setup_unseal().await;
+    issue_leases(&sdk, 1).await;
+
+    let err = sdk
+        .lease
+        .list_page_by_mount(
+            MOUNT_PATH,
+            &BTreeMap::new(),
+            &PageParams {
+                limit: Some(2),
+                cursor: Some("not-a-cursor".to_string()),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+}
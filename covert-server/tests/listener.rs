@@ -0,0 +1,108 @@
+use std::{net::TcpListener, time::Duration};
+
+use covert_sdk::{
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    Client,
+};
+use covert_system::Config;
+use covert_types::state::StorageState;
+use tokio::sync::oneshot;
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test]
+async fn serve_on_multiple_tcp_listeners() {
+    let ports = [free_port(), free_port()];
+    let config = Config::from_toml(&format!(
+        r#"
+        storage-path = ":memory:"
+        tls-disable = true
+
+        [[listener]]
+        address = "tcp://127.0.0.1:{}"
+
+        [[listener]]
+        address = "tcp://127.0.0.1:{}"
+        "#,
+        ports[0], ports[1]
+    ))
+    .unwrap();
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(covert_system::start(config, async move {
+        let _ = shutdown_rx.await;
+    }));
+
+    let clients = ports.map(|port| Client::new(format!("http://127.0.0.1:{port}/v1")));
+    for sdk in &clients {
+        let mut attempts = 0;
+        while sdk.status.status().await.is_err() {
+            attempts += 1;
+            assert!(attempts < 100, "listener did not start");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    // Both listeners serve the same server
+    let shares = match clients[0]
+        .operator
+        .initialize(&InitializeParams {
+            shares: 1,
+            threshold: 1,
+            stored_shares: None,
+        })
+        .await
+        .unwrap()
+    {
+        InitializeResponse::NewKeyShares(shares) => shares.shares,
+        _ => panic!("should get new shares"),
+    };
+    let UnsealResponse::Complete { root_token } = clients[1]
+        .operator
+        .unseal(&UnsealParams { shares })
+        .await
+        .unwrap()
+    else {
+        panic!("should be unsealed");
+    };
+    assert_eq!(
+        clients[0].status.status().await.unwrap().state,
+        StorageState::Unsealed
+    );
+
+    clients[0].set_token(Some(root_token.to_string())).await;
+    let config = clients[0].status.config_state().await.unwrap();
+    assert_eq!(config.listener.port, None);
+    assert_eq!(
+        config.listener.tcp_listeners,
+        ports.map(|port| format!("tcp://127.0.0.1:{port}"))
+    );
+    assert!(config.listener.unix_sockets.is_empty());
+
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn tcp_listeners_require_tls_config() {
+    let config = Config::from_toml(&format!(
+        r#"
+        storage-path = ":memory:"
+
+        [[listener]]
+        address = "tcp://127.0.0.1:{}"
+        "#,
+        free_port()
+    ))
+    .unwrap();
+    let err = covert_system::start(config, covert_system::shutdown_signal())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("tls-disable"), "{err}");
+}
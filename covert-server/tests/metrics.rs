@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use covert_sdk::{
+    kv::CreateSecretParams,
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    Client,
+};
+use covert_system::{Config, MetricsConfig};
+use hyper::{header::CONTENT_TYPE, Body, Request, StatusCode};
+use tokio::sync::oneshot;
+
+async fn setup(metrics: MetricsConfig) -> (Client, u16) {
+    let (port_tx, port_rx) = oneshot::channel();
+    let config = Config {
+        port: Some(0),
+        port_tx: Some(port_tx),
+        tls_disable: true,
+        storage_path: ":memory:".into(),
+        metrics,
+        ..Default::default()
+    };
+    tokio::spawn(async move {
+        if let Err(err) = covert_system::start(config, covert_system::shutdown_signal()).await {
+            panic!("server error: {}", err);
+        }
+    });
+    let port = port_rx.await.unwrap();
+    (Client::new(format!("http://localhost:{port}/v1")), port)
+}
+
+async fn unseal(sdk: &Client) -> String {
+    let InitializeResponse::NewKeyShares(shares) = sdk
+        .operator
+        .initialize(&InitializeParams {
+            shares: 1,
+            threshold: 1,
+            stored_shares: None,
+        })
+        .await
+        .unwrap()
+    else {
+        panic!("should get new shares");
+    };
+    let UnsealResponse::Complete { root_token } = sdk
+        .operator
+        .unseal(&UnsealParams {
+            shares: shares.shares,
+        })
+        .await
+        .unwrap()
+    else {
+        panic!("should be unsealed");
+    };
+    sdk.set_token(Some(root_token.to_string())).await;
+    root_token.to_string()
+}
+
+async fn scrape(port: u16, path: &str, token: Option<&str>) -> (StatusCode, String) {
+    let mut req = Request::get(format!("http://localhost:{port}{path}"));
+    if let Some(token) = token {
+        req = req.header("X-Covert-Token", token);
+    }
+    let resp = hyper::Client::new()
+        .request(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = resp.status();
+    if status.is_success() {
+        assert_eq!(
+            resp.headers()[CONTENT_TYPE],
+            "text/plain; version=0.0.4; charset=utf-8"
+        );
+    }
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn unauthenticated_metrics() {
+    let (sdk, port) = setup(MetricsConfig {
+        enabled: true,
+        unauthenticated: true,
+    })
+    .await;
+
+    let (status, text) = scrape(port, "/metrics", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(text.contains("covert_storage_state{state=\"uninitialized\"} 1\n"));
+    assert!(text.contains("covert_storage_state{state=\"unsealed\"} 0\n"));
+    assert!(text.contains("covert_storage_size_bytes{storage=\"seal\"}"));
+    // Unavailable until unsealed
+    assert!(!text.contains("covert_tokens"));
+
+    unseal(&sdk).await;
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+    let data = HashMap::from([("foo".to_string(), "bar".to_string())]);
+    sdk.kv
+        .create("kv/", "foo", &CreateSecretParams { data })
+        .await
+        .unwrap();
+    sdk.kv.read("kv/", "foo", None).await.unwrap();
+    assert!(sdk.kv.read("kv/", "missing", None).await.is_err());
+
+    let (status, text) = scrape(port, "/metrics", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let labels = r#"namespace="root",mount="kv/",operation="read""#;
+    assert!(
+        text.contains(&format!("covert_requests_total{{{labels}}} 2\n")),
+        "{text}"
+    );
+    assert!(text.contains(&format!("covert_request_errors_total{{{labels}}} 1\n")));
+    assert!(text.contains(&format!(
+        "covert_request_duration_seconds_count{{{labels}}} 2\n"
+    )));
+    assert!(
+        text.contains(r#"covert_requests_total{namespace="root",mount="sys/",operation="create"}"#)
+    );
+    assert!(text.contains("covert_storage_state{state=\"unsealed\"} 1\n"));
+    assert!(text.contains("covert_storage_size_bytes{storage=\"encrypted\"}"));
+    assert!(text.contains("covert_tokens 1\n"));
+    assert!(text.contains("covert_leases "));
+    assert!(text.contains("covert_expiration_queue_depth 0\n"));
+    assert!(text.contains("covert_lease_revocations_total "));
+    assert!(text.contains("covert_lease_revocation_failures_total 0\n"));
+}
+
+#[tokio::test]
+async fn metrics_require_policy() {
+    let (sdk, port) = setup(MetricsConfig {
+        enabled: true,
+        unauthenticated: false,
+    })
+    .await;
+    let root_token = unseal(&sdk).await;
+
+    let (status, _) = scrape(port, "/metrics", None).await;
+    assert!(status.is_client_error(), "{status}");
+
+    let (status, text) = scrape(port, "/v1/sys/metrics", Some(&root_token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(text.contains("covert_tokens 1\n"));
+}
+
+#[tokio::test]
+async fn metrics_disabled_by_default() {
+    let (_sdk, port) = setup(MetricsConfig::default()).await;
+    let (status, _) = scrape(port, "/metrics", None).await;
+    assert!(status.is_client_error(), "{status}");
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use chrono::Utc;
+use covert_sdk::{
+    mfa::{GenerateTotpKeyParams, MfaMethod, MfaRequirement, ValidateMfaParams},
+    mounts::{BackendType, CreateMountParams, MountConfig, UpdateMountParams},
+    userpass::LoginParams,
+    Client, Error, ErrorCode,
+};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use common::{login_with_policy, setup_unseal};
+
+fn base32_decode(encoded: &str) -> Vec<u8> {
+    let mut bits = 0u64;
+    let mut len = 0;
+    let mut bytes = vec![];
+    for c in encoded.bytes() {
+        let value = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567"
+            .iter()
+            .position(|a| *a == c)
+            .unwrap();
+        bits = (bits << 5) | value as u64;
+        len += 5;
+        if len >= 8 {
+            len -= 8;
+            bytes.push((bits >> len) as u8);
+        }
+    }
+    bytes
+}
+
+fn totp(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).unwrap();
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = usize::from(hash[19] & 0xf);
+    let code = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    format!("{:06}", code % 1_000_000)
+}
+
+async fn login(sdk: &Client) -> Result<MfaRequirement, Error> {
+    let resp = sdk
+        .userpass
+        .login(
+            "auth/userpass/",
+            &LoginParams {
+                username: "foo".to_string(),
+                password: "password".to_string(),
+                renewable: true,
+            },
+        )
+        .await;
+    match resp {
+        Err(Error::MfaRequired(requirement)) => Ok(requirement),
+        Err(err) => Err(err),
+        Ok(_) => panic!("login did not require MFA"),
+    }
+}
+
+async fn validate(
+    sdk: &Client,
+    requirement: &MfaRequirement,
+    code: String,
+) -> Result<String, Error> {
+    sdk.mfa
+        .validate(&ValidateMfaParams {
+            request_id: requirement.request_id,
+            code,
+        })
+        .await
+        .map(|auth| auth.token.to_string())
+}
+
+#[tokio::test]
+async fn login_with_totp() {
+    let sdk = setup_unseal().await;
+
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    login_with_policy(
+        &sdk,
+        "foo",
+        r#"path "sys/token/*" { capabilities = ["read"] }"#,
+    )
+    .await;
+    let config = MountConfig {
+        require_mfa: true,
+        ..Default::default()
+    };
+    sdk.mount
+        .update("auth/userpass/", &UpdateMountParams { config })
+        .await
+        .unwrap();
+
+    // Only auth methods can require MFA
+    let err = sdk
+        .mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                variant: BackendType::Kv,
+                config: MountConfig {
+                    require_mfa: true,
+                    ..Default::default()
+                },
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    // The login is refused while the entity has no key
+    let err = login(&sdk).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    let key = sdk
+        .mfa
+        .generate_totp_key("foo", &GenerateTotpKeyParams::default())
+        .await
+        .unwrap();
+    assert!(key.url.starts_with("otpauth://totp/Covert:foo?"));
+    assert_eq!(sdk.mfa.read_totp_key("foo").await.unwrap().entity, "foo");
+    let secret = base32_decode(&key.secret);
+    let step = Utc::now().timestamp() / 30;
+
+    // A request is gone after a wrong code
+    let requirement = login(&sdk).await.unwrap();
+    assert_eq!(requirement.methods, vec![MfaMethod::Totp]);
+    let err = validate(&sdk, &requirement, "abcdef".to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let err = validate(&sdk, &requirement, totp(&secret, step))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    let requirement = login(&sdk).await.unwrap();
+    let token = validate(&sdk, &requirement, totp(&secret, step))
+        .await
+        .unwrap();
+
+    // A code cannot be used twice
+    let requirement = login(&sdk).await.unwrap();
+    let err = validate(&sdk, &requirement, totp(&secret, step))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let requirement = login(&sdk).await.unwrap();
+    validate(&sdk, &requirement, totp(&secret, step + 1))
+        .await
+        .unwrap();
+
+    // Logins are refused again once the key is removed
+    sdk.mfa.remove_totp_key("foo").await.unwrap();
+    let err = login(&sdk).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    sdk.set_token(Some(token)).await;
+    assert_eq!(sdk.token.lookup_self().await.unwrap().entity_name, "foo");
+}
@@ -1,13 +1,19 @@
 mod common;
 
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
-use common::{setup, setup_unseal};
+use common::{login_with_policy, setup, setup_unseal, setup_unseal_with, start, unseal};
 use covert_sdk::{
-    mounts::{BackendType, CreateMountParams, MountConfig, UpdateMountParams},
+    kv::CreateSecretParams,
+    mounts::{
+        BackendType, CreateMountParams, ListingVisibility, MountConfig, MountTuning,
+        TuneMountParams, UpdateMountParams,
+    },
     operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    Client, ErrorCode,
 };
-use covert_types::state::StorageState;
+use covert_system::PathDisclosure;
+use covert_types::{request::Operation, state::StorageState};
 
 #[tokio::test]
 async fn mount() {
@@ -77,6 +83,50 @@ async fn mount() {
     assert_eq!(mounts.secret.len(), 0);
 }
 
+#[tokio::test]
+async fn mount_paths_with_and_without_trailing_slash() {
+    let sdk = setup_unseal().await;
+    let params = CreateMountParams {
+        config: MountConfig::default(),
+        variant: BackendType::Kv,
+    };
+
+    // The path is stored with the slash
+    let resp = sdk.mount.create("secret", &params).await.unwrap();
+    assert_eq!(resp.path, "secret/");
+    assert_eq!(secret_mounts(&sdk).await, ["secret/"]);
+
+    // Both paths name the same mount
+    for path in ["secret/", "secret", "secret//"] {
+        let err = sdk.mount.create(path, &params).await.unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Conflict));
+        let resp = sdk.mount.get(path).await.unwrap();
+        assert_eq!(resp.path, "secret/");
+        sdk.mount.migrations(path).await.unwrap();
+    }
+    let data: HashMap<_, _> = [("foo".to_string(), "bar".to_string())]
+        .into_iter()
+        .collect();
+    sdk.kv
+        .create("secret", "foo", &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+    let resp = sdk.kv.read("secret/", "foo", None).await.unwrap();
+    assert_eq!(resp.data, Some(data));
+
+    // A mount path is not a prefix of other mount paths
+    sdk.mount.create("secrets", &params).await.unwrap();
+    assert_eq!(secret_mounts(&sdk).await, ["secret/", "secrets/"]);
+    let resp = sdk.kv.read("secrets", "foo", None).await.unwrap_err();
+    assert_eq!(resp.code(), Some(ErrorCode::NotFound));
+
+    let err = sdk.mount.create("a//b", &params).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    sdk.mount.remove("secret").await.unwrap();
+    assert_eq!(secret_mounts(&sdk).await, ["secrets/"]);
+}
+
 #[tokio::test]
 async fn recover_mounts_after_seal() {
     let tmpdir_storage_path = tempfile::tempdir().unwrap();
@@ -87,6 +137,7 @@ async fn recover_mounts_after_seal() {
         .initialize(&InitializeParams {
             shares: 1,
             threshold: 1,
+            stored_shares: None,
         })
         .await
         .unwrap();
@@ -163,3 +214,478 @@ async fn recover_mounts_after_seal() {
     assert_eq!(mounts.auth.len(), 0);
     assert_eq!(mounts.secret.len(), 3);
 }
+
+#[tokio::test]
+async fn mount_migrations() {
+    let sdk = setup_unseal().await;
+
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Userpass,
+            },
+        )
+        .await
+        .unwrap();
+
+    // All migrations are applied when the backend is mounted
+    let resp = sdk.mount.migrations("auth/userpass/").await.unwrap();
+    assert_eq!(resp.applied.len(), 4);
+    assert_eq!(resp.applied[0].version, 20_221_227);
+    assert_eq!(resp.applied[1].version, 20_230_320);
+    assert_eq!(resp.applied[2].version, 20_230_405);
+    assert_eq!(resp.applied[3].version, 20_230_424);
+    assert!(resp.pending.is_empty());
+
+    // The mount reports the version of the latest applied migration
+    let resp = sdk.mount.get("auth/userpass/").await.unwrap();
+    assert_eq!(resp.path, "auth/userpass/");
+    assert_eq!(resp.variant, BackendType::Userpass);
+    assert_eq!(resp.schema_version, Some(20_230_424));
+
+    // Unknown mount
+    assert!(sdk.mount.migrations("auth/foo/").await.is_err());
+    assert!(sdk.mount.get("auth/foo/").await.is_err());
+}
+
+#[tokio::test]
+async fn mount_paths() {
+    let sdk = setup_unseal().await;
+
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+
+    let resp = sdk.mount.paths("kv/").await.unwrap();
+    let paths = resp
+        .paths
+        .iter()
+        .map(|route| route.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            "/config",
+            "/data/*path",
+            "/delete/*path",
+            "/destroy/*path",
+            "/undelete/*path"
+        ]
+    );
+    let data = &resp.paths[1];
+    assert_eq!(
+        data.operations,
+        vec![Operation::Create, Operation::Read, Operation::Update]
+    );
+    assert!(data.help.is_some());
+
+    // Unknown mount
+    assert!(sdk.mount.paths("foo/").await.is_err());
+}
+
+#[tokio::test]
+async fn mount_paths_named_like_mount_views() {
+    let sdk = setup_unseal().await;
+    let params = CreateMountParams {
+        config: MountConfig::default(),
+        variant: BackendType::Kv,
+    };
+
+    for path in ["apps/tune/", "apps/paths/", "apps/migrations/"] {
+        sdk.mount.create(path, &params).await.unwrap();
+        assert_eq!(sdk.mount.get(path).await.unwrap().path, path);
+        assert_eq!(sdk.mount.tuning(path).await.unwrap().path, path);
+        sdk.mount.paths(path).await.unwrap();
+        sdk.mount.migrations(path).await.unwrap();
+        sdk.mount.remove(path).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn suggest_mounts_for_unknown_path() {
+    let sdk = setup_unseal().await;
+    for (path, variant) in [
+        ("kv/", BackendType::Kv),
+        ("secret/", BackendType::Kv),
+        ("auth/userpass/", BackendType::Userpass),
+    ] {
+        sdk.mount
+            .create(
+                path,
+                &CreateMountParams {
+                    config: MountConfig::default(),
+                    variant,
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    let err = sdk.kv.read("kvv/", "foo", None).await.unwrap_err();
+    assert!(err.message().contains("`kv/`"), "{err}");
+    assert!(!err.message().contains("`secret/`"), "{err}");
+
+    let err = sdk.kv.read("secrets/", "foo", None).await.unwrap_err();
+    assert!(err.message().contains("`secret/`"), "{err}");
+
+    // Mounts the token cannot access are never suggested
+    let token = login_with_policy(
+        &sdk,
+        "reader",
+        r#"path "secret/*" { capabilities = ["read"] }"#,
+    )
+    .await;
+    sdk.set_token(Some(token)).await;
+    let err = sdk.kv.read("kvv/", "foo", None).await.unwrap_err();
+    assert!(!err.message().contains("`kv/`"), "{err}");
+    let err = sdk.kv.read("secrets/", "foo", None).await.unwrap_err();
+    assert!(err.message().contains("`secret/`"), "{err}");
+
+    // Nor without a token
+    sdk.set_token(None).await;
+    let err = sdk.kv.read("secrets/", "foo", None).await.unwrap_err();
+    assert!(!err.message().contains("`secret/`"), "{err}");
+}
+
+async fn secret_mounts(sdk: &Client) -> Vec<String> {
+    let mounts = sdk.mount.list().await.unwrap();
+    assert_eq!(mounts.auth.len(), 1);
+    mounts.secret.into_iter().map(|mount| mount.path).collect()
+}
+
+#[tokio::test]
+async fn hidden_mounts() {
+    let sdk = setup_unseal().await;
+    for (path, variant, listing_visibility) in [
+        ("kv/", BackendType::Kv, ListingVisibility::Default),
+        ("internal/", BackendType::Kv, ListingVisibility::Hidden),
+        (
+            "auth/userpass/",
+            BackendType::Userpass,
+            ListingVisibility::Unauth,
+        ),
+    ] {
+        sdk.mount
+            .create(
+                path,
+                &CreateMountParams {
+                    config: MountConfig {
+                        listing_visibility,
+                        ..Default::default()
+                    },
+                    variant,
+                },
+            )
+            .await
+            .unwrap();
+    }
+    // The root token has access to every mount
+    assert_eq!(secret_mounts(&sdk).await, ["internal/", "kv/"]);
+
+    let lister = login_with_policy(
+        &sdk,
+        "lister",
+        r#"path "sys/mounts" { capabilities = ["read"] }"#,
+    )
+    .await;
+    let reader = login_with_policy(
+        &sdk,
+        "reader",
+        r#"
+        path "sys/mounts" { capabilities = ["read"] }
+        path "internal/*" { capabilities = ["read"] }
+        "#,
+    )
+    .await;
+
+    // Hidden mounts are still accessible, just not listed
+    sdk.set_token(Some(lister)).await;
+    assert_eq!(secret_mounts(&sdk).await, ["kv/"]);
+    sdk.set_token(Some(reader)).await;
+    assert_eq!(secret_mounts(&sdk).await, ["internal/", "kv/"]);
+}
+
+#[tokio::test]
+async fn tune_mount() {
+    let sdk = setup_unseal().await;
+    for (path, variant) in [
+        ("kv/", BackendType::Kv),
+        ("auth/userpass/", BackendType::Userpass),
+    ] {
+        sdk.mount
+            .create(
+                path,
+                &CreateMountParams {
+                    config: MountConfig {
+                        max_concurrent_requests: Some(5),
+                        ..Default::default()
+                    },
+                    variant,
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    let resp = sdk.mount.tuning("kv/").await.unwrap();
+    assert_eq!(resp.path, "kv/");
+    assert_eq!(
+        resp.tuning,
+        MountTuning {
+            default_lease_ttl: Duration::from_secs(30 * 60),
+            max_lease_ttl: Duration::from_secs(4 * 60 * 60),
+            description: None,
+            listing_visibility: ListingVisibility::Default,
+            require_mfa: false,
+            max_request_body_size: None,
+            max_concurrent_requests: Some(5),
+        }
+    );
+    assert_eq!(
+        resp.effective_max_lease_ttl,
+        Duration::from_secs(4 * 60 * 60)
+    );
+    assert_eq!(
+        resp.effective_max_request_body_size,
+        covert_system::DEFAULT_MAX_REQUEST_BODY_SIZE as u64
+    );
+
+    // Only the given settings change
+    let resp = sdk
+        .mount
+        .tune(
+            "kv",
+            &TuneMountParams {
+                default_lease_ttl: Some(Duration::from_secs(60 * 60)),
+                description: Some("Secrets of the apps".to_string()),
+                listing_visibility: Some(ListingVisibility::Hidden),
+                max_request_body_size: Some(Some(4 * 1024 * 1024)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.tuning.default_lease_ttl, Duration::from_secs(60 * 60));
+    assert_eq!(resp.tuning.max_lease_ttl, Duration::from_secs(4 * 60 * 60));
+    assert_eq!(
+        resp.tuning.description.as_deref(),
+        Some("Secrets of the apps")
+    );
+    assert_eq!(resp.tuning.listing_visibility, ListingVisibility::Hidden);
+    assert_eq!(resp.tuning.max_concurrent_requests, Some(5));
+    assert_eq!(resp.effective_max_request_body_size, 4 * 1024 * 1024);
+    let mount = sdk.mount.get("kv/").await.unwrap();
+    assert_eq!(MountTuning::from(&mount.config), resp.tuning);
+
+    // Limits are removed with `null` and the description with an empty one
+    let resp = sdk
+        .mount
+        .tune(
+            "kv/",
+            &TuneMountParams {
+                description: Some(String::new()),
+                max_concurrent_requests: Some(None),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.tuning.description, None);
+    assert_eq!(resp.tuning.max_concurrent_requests, None);
+    assert_eq!(resp.tuning.max_request_body_size, Some(4 * 1024 * 1024));
+
+    let err = sdk
+        .mount
+        .tune(
+            "kv/",
+            &TuneMountParams {
+                default_lease_ttl: Some(Duration::from_secs(5 * 60 * 60)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    let err = sdk
+        .mount
+        .tune("missing/", &TuneMountParams::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    // Tuning can be granted without granting to remount or remove the mount
+    let tuner = login_with_policy(
+        &sdk,
+        "tuner",
+        r#"path "sys/mounts/kv/tune" { capabilities = ["read", "update", "sudo"] }"#,
+    )
+    .await;
+    let config = sdk.mount.get("kv/").await.unwrap().config;
+    sdk.set_token(Some(tuner)).await;
+    sdk.mount
+        .tune(
+            "kv/",
+            &TuneMountParams {
+                max_lease_ttl: Some(Duration::from_secs(8 * 60 * 60)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let err = sdk
+        .mount
+        .update("kv/", &UpdateMountParams { config })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let err = sdk.mount.remove("kv/").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+async fn read_mounts_without_sudo() {
+    let sdk = setup_unseal().await;
+    let params = CreateMountParams {
+        config: MountConfig::default(),
+        variant: BackendType::Kv,
+    };
+    sdk.mount.create("kv/", &params).await.unwrap();
+
+    let reader = login_with_policy(
+        &sdk,
+        "reader",
+        r#"path "sys/mount*" { capabilities = ["read", "create", "update", "delete"] }"#,
+    )
+    .await;
+    sdk.set_token(Some(reader)).await;
+    assert_eq!(sdk.mount.get("kv/").await.unwrap().path, "kv/");
+    sdk.mount.migrations("kv/").await.unwrap();
+    sdk.mount.paths("kv/").await.unwrap();
+    sdk.mount.tuning("kv/").await.unwrap();
+
+    // Changes still require sudo
+    let err = sdk.mount.create("other/", &params).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let err = sdk
+        .mount
+        .tune("kv/", &TuneMountParams::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let err = sdk.mount.remove("kv/").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+async fn conceal_paths_the_caller_cannot_access() {
+    let sdk = setup_unseal_with(|config| config.path_disclosure = PathDisclosure::Conceal).await;
+    for (path, variant) in [
+        ("kv/", BackendType::Kv),
+        ("auth/userpass/", BackendType::Userpass),
+    ] {
+        sdk.mount
+            .create(
+                path,
+                &CreateMountParams {
+                    config: MountConfig::default(),
+                    variant,
+                },
+            )
+            .await
+            .unwrap();
+    }
+    let reader = login_with_policy(
+        &sdk,
+        "reader",
+        r#"path "secret/*" { capabilities = ["read"] }"#,
+    )
+    .await;
+    let lister = login_with_policy(&sdk, "lister", r#"path "*" { capabilities = ["list"] }"#).await;
+
+    // The root token may list everything
+    let err = sdk.kv.read("kvv/", "foo", None).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+    assert!(err.message().contains("`kv/`"), "{err}");
+
+    // A forbidden mount and a missing mount can't be told apart
+    for token in [None, Some(reader)] {
+        sdk.set_token(token).await;
+        let forbidden = sdk.kv.read("kv/", "foo", None).await.unwrap_err();
+        let missing = sdk.kv.read("kvv/", "foo", None).await.unwrap_err();
+        assert_eq!(forbidden.code(), Some(ErrorCode::PermissionDenied));
+        assert_eq!(missing.code(), Some(ErrorCode::PermissionDenied));
+        assert_eq!(forbidden.status(), missing.status());
+        assert_eq!(forbidden.message(), missing.message());
+    }
+
+    // Unless the caller may list the parent path
+    sdk.set_token(Some(lister)).await;
+    let err = sdk.kv.read("kv/", "foo", None).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let err = sdk.kv.read("kvv/", "foo", None).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
+
+#[tokio::test]
+async fn request_body_size_limit() {
+    let port = start(":memory:", covert_system::shutdown_signal(), None).await;
+    let sdk = Client::new(format!("http://localhost:{port}/v1"));
+    let token = unseal(&sdk).await;
+
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+    // Mounts can allow larger bodies than the server
+    let large = MountConfig {
+        max_request_body_size: Some(4 * 1024 * 1024),
+        ..Default::default()
+    };
+    sdk.mount
+        .create(
+            "large/",
+            &CreateMountParams {
+                config: large,
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+
+    let data = CreateSecretParams {
+        data: HashMap::from([("foo".to_string(), "a".repeat(2 * 1024 * 1024))]),
+    };
+    let err = sdk.kv.create("kv/", "foo", &data).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PayloadTooLarge));
+    sdk.kv.create("large/", "foo", &data).await.unwrap();
+
+    // Bodies without a `Content-Length` are limited while they are read
+    let send = |mount: &str| {
+        let chunks = (0..32).map(|_| Ok::<_, std::io::Error>(vec![b' '; 64 * 1024]));
+        let req = hyper::Request::post(format!("http://localhost:{port}/v1/{mount}data/bar"))
+            .header("X-Covert-Token", &token)
+            .body(hyper::Body::wrap_stream(futures::stream::iter(chunks)))
+            .unwrap();
+        hyper::Client::new().request(req)
+    };
+    let resp = send("kv/").await.unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::PAYLOAD_TOO_LARGE);
+    // Only whitespace, the body is read fully and rejected as invalid JSON
+    let resp = send("large/").await.unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::BAD_REQUEST);
+}
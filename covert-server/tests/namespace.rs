@@ -1,12 +1,16 @@
 mod common;
 
+use std::collections::HashMap;
+
 use common::setup_unseal;
 use covert_sdk::{
     entity::{AttachEntityAliasParams, AttachEntityPolicyParams, CreateEntityParams, EntityAlias},
+    kv::CreateSecretParams,
     mounts::{BackendType, CreateMountParams, MountConfig},
     namespace::CreateNamespaceParams,
     policy::CreatePolicyParams,
     userpass::{CreateUserParams, LoginParams},
+    ErrorCode,
 };
 
 #[tokio::test]
@@ -202,7 +206,7 @@ async fn access_to_parent_ns_is_denied() {
         .create(&CreatePolicyParams {
             name: policy_name.clone(),
             policy: r#"path "*" { 
-                capabilities = ["read","update","create","delete] 
+                capabilities = ["read","update","create","delete","sudo"] 
             }"#
             .to_string(),
         })
@@ -271,6 +275,7 @@ async fn access_to_parent_ns_is_denied() {
             &LoginParams {
                 username: alias_name.clone(),
                 password: password.clone(),
+                renewable: true,
             },
         )
         .await
@@ -306,5 +311,74 @@ async fn access_to_parent_ns_is_denied() {
         )
         .await
         .unwrap_err()
+        .message()
         .contains("not authorized"));
 }
+
+#[tokio::test]
+async fn select_namespace_with_path_prefix() {
+    let sdk = setup_unseal().await;
+    let kv = CreateMountParams {
+        config: MountConfig::default(),
+        variant: BackendType::Kv,
+    };
+
+    sdk.namespace
+        .create(&CreateNamespaceParams {
+            name: "team-a".to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.set_namespace(Some("root/team-a".to_string())).await;
+    sdk.namespace
+        .create(&CreateNamespaceParams {
+            name: "dev".to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.mount.create("kv/", &kv).await.unwrap();
+    let data = HashMap::from([("password".to_string(), "secret".to_string())]);
+    sdk.kv
+        .create("kv/", "foo", &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+
+    // The path prefix is relative to the namespace of the header
+    sdk.set_namespace(None).await;
+    let secret = sdk.kv.read("team-a/kv/", "foo", None).await.unwrap();
+    assert_eq!(secret.data, Some(data.clone()));
+    sdk.set_namespace(Some("root/team-a/dev".to_string())).await;
+    sdk.mount.create("kv/", &kv).await.unwrap();
+    sdk.set_namespace(Some("root/team-a".to_string())).await;
+    sdk.kv
+        .create("dev/kv/", "bar", &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+    sdk.set_namespace(None).await;
+    let secret = sdk.kv.read("team-a/dev/kv/", "bar", None).await.unwrap();
+    assert_eq!(secret.data, Some(data.clone()));
+
+    // Mounts and namespaces cannot shadow each other
+    let err = sdk.mount.create("team-a/", &kv).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Conflict));
+    sdk.mount.create("kv/", &kv).await.unwrap();
+    let err = sdk
+        .namespace
+        .create(&CreateNamespaceParams {
+            name: "kv".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Conflict));
+
+    for name in ["sys", "auth", "Team-B", "team/b", ""] {
+        let err = sdk
+            .namespace
+            .create(&CreateNamespaceParams {
+                name: name.to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    }
+}
@@ -1,9 +1,18 @@
 mod common;
 
-use covert_sdk::policy::CreatePolicyParams;
+use std::collections::HashMap;
+
+use covert_sdk::{
+    entity::AttachEntityPolicyParams,
+    kv::CreateSecretParams,
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    policy::{CreatePolicyParams, FormatPolicyParams},
+    token::{PolicySource, TokenPolicy},
+    ErrorCode,
+};
 use covert_types::policy::{PathPolicy, Policy};
 
-use common::setup_unseal;
+use common::{login_with_policy, setup_unseal};
 
 #[tokio::test]
 async fn policy() {
@@ -19,7 +28,7 @@ async fn policy() {
             capabilities = ["delete"] 
         }
     "#;
-    let policies = PathPolicy::parse(policy_raw).unwrap();
+    let policies = PathPolicy::canonicalize(PathPolicy::parse(policy_raw).unwrap());
     let policy = Policy::new(name, policies, "foo".to_string());
 
     let created_policy = sdk
@@ -34,4 +43,197 @@ async fn policy() {
 
     assert_eq!(created_policy.name, policy.name);
     assert_eq!(created_policy.paths, policy.paths);
+
+    let read = sdk.policy.read(&policy.name).await.unwrap();
+    assert_eq!(read.policy.paths, policy.paths);
+    assert_eq!(PathPolicy::parse(&read.document).unwrap(), policy.paths);
+    let err = sdk.policy.read("bar").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    // Parse errors point at the line of the error
+    let err = sdk
+        .policy
+        .create(&CreatePolicyParams {
+            name: "bar".to_string(),
+            policy: "path \"sys/*\" {\n    capabilities = [\"fly\"]\n}".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    assert!(
+        err.to_string()
+            .contains("line 2, column 21: unknown capability `fly`"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn format_policy() {
+    let sdk = setup_unseal().await;
+    let existing = sdk.policy.list().await.unwrap().policies.len();
+
+    let policy_raw = r#"
+        path "sys/*" { capabilities = ["update","read"] }
+        path "auth/userpass/*" {
+            capabilities = ["delete"]
+        }
+    "#;
+    let formatted = sdk
+        .policy
+        .format(&FormatPolicyParams {
+            policy: policy_raw.to_string(),
+        })
+        .await
+        .unwrap()
+        .policy;
+    assert_eq!(
+        formatted,
+        r#"path "auth/userpass/*" {
+    capabilities = ["delete"]
+}
+
+path "sys/*" {
+    capabilities = ["read", "update"]
+}
+"#
+    );
+
+    // Formatting does not store the policy
+    assert_eq!(sdk.policy.list().await.unwrap().policies.len(), existing);
+
+    assert!(sdk
+        .policy
+        .format(&FormatPolicyParams {
+            policy: r#"path "sys/*" { capabilities = ["fly"] }"#.to_string(),
+        })
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn sudo_paths_require_sudo_capability() {
+    let sdk = setup_unseal().await;
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let operator = login_with_policy(
+        &sdk,
+        "operator",
+        r#"path "sys/*" { capabilities = ["create", "read", "update", "delete"] }"#,
+    )
+    .await;
+    let admin = login_with_policy(
+        &sdk,
+        "admin",
+        r#"path "sys/*" { capabilities = ["create", "read", "update", "delete", "sudo"] }"#,
+    )
+    .await;
+
+    let mount = CreateMountParams {
+        variant: BackendType::Kv,
+        config: MountConfig::default(),
+    };
+
+    // A broad policy is not enough for privileged paths
+    sdk.set_token(Some(operator)).await;
+    assert!(sdk.mount.create("kv/", &mount).await.is_err());
+    assert!(sdk.operator.seal().await.is_err());
+    // But works for the other paths
+    assert!(sdk.mount.list().await.is_ok());
+
+    sdk.set_token(Some(admin)).await;
+    sdk.mount.create("kv/", &mount).await.unwrap();
+    sdk.operator.seal().await.unwrap();
+}
+
+#[tokio::test]
+async fn policies_are_a_union_where_deny_wins() {
+    let sdk = setup_unseal().await;
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                variant: BackendType::Kv,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let data = HashMap::from([("password".to_string(), "secret".to_string())]);
+    for key in ["foo", "admin"] {
+        sdk.kv
+            .create("kv/", key, &CreateSecretParams { data: data.clone() })
+            .await
+            .unwrap();
+    }
+    sdk.policy
+        .create(&CreatePolicyParams {
+            name: "default".to_string(),
+            policy: r#"path "kv/data/admin" { capabilities = ["deny"] }"#.to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.policy
+        .create(&CreatePolicyParams {
+            name: "writer".to_string(),
+            policy: r#"path "kv/*" { capabilities = ["create"] }"#.to_string(),
+        })
+        .await
+        .unwrap();
+    let token =
+        login_with_policy(&sdk, "reader", r#"path "kv/*" { capabilities = ["read"] }"#).await;
+    sdk.entity
+        .attach_policies(&AttachEntityPolicyParams {
+            name: "reader".to_string(),
+            policy_names: vec!["writer".to_string()],
+        })
+        .await
+        .unwrap();
+
+    sdk.set_token(Some(token)).await;
+    // The policies of the entity are combined
+    sdk.kv.read("kv/", "foo", None).await.unwrap();
+    sdk.kv
+        .create("kv/", "bar", &CreateSecretParams { data })
+        .await
+        .unwrap();
+    // The deny of the default policy wins over the other policies
+    assert!(sdk.kv.read("kv/", "admin", None).await.is_err());
+
+    let resp = sdk.token.lookup_self().await.unwrap();
+    assert_eq!(
+        resp.policies,
+        vec![
+            TokenPolicy {
+                name: "default".to_string(),
+                sources: vec![PolicySource::Default],
+            },
+            TokenPolicy {
+                name: "reader".to_string(),
+                sources: vec![PolicySource::Entity],
+            },
+            TokenPolicy {
+                name: "writer".to_string(),
+                sources: vec![PolicySource::Entity],
+            },
+        ]
+    );
 }
@@ -0,0 +1,125 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::setup_unseal;
+use covert_sdk::{
+    kv::CreateSecretParams,
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    quota::{QuotaScope, RateLimitQuotaParams},
+    ErrorCode,
+};
+
+#[tokio::test]
+async fn rate_limit_quota() {
+    let sdk = setup_unseal().await;
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+    let data = HashMap::from([("foo".to_string(), "bar".to_string())]);
+    sdk.kv
+        .create("kv/", "foo", &CreateSecretParams { data })
+        .await
+        .unwrap();
+
+    let params = RateLimitQuotaParams {
+        scope: QuotaScope::Mount {
+            path: "kv/".to_string(),
+        },
+        rate: 0.001,
+        burst: Some(2),
+        exempt_root: false,
+    };
+    let quota = sdk.quota.set_rate_limit("kv", &params).await.unwrap();
+    assert_eq!(quota.burst, 2);
+
+    sdk.kv.read("kv/", "foo", None).await.unwrap();
+    sdk.kv.read("kv/", "foo", None).await.unwrap();
+    let err = sdk.kv.read("kv/", "foo", None).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::RateLimited));
+    assert!(err.message().contains("`kv`"), "{err}");
+
+    // Other paths are not limited
+    sdk.mount.list().await.unwrap();
+
+    let resp = sdk.quota.read_rate_limit("kv").await.unwrap();
+    assert_eq!(resp.quota, quota);
+    assert_eq!(resp.usage.allowed_requests, 2);
+    assert_eq!(resp.usage.rejected_requests, 1);
+    assert!(resp.usage.available < 1.0);
+
+    // Root tokens can be exempted, updating the quota resets its usage
+    sdk.quota
+        .set_rate_limit(
+            "kv",
+            &RateLimitQuotaParams {
+                exempt_root: true,
+                ..params
+            },
+        )
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        sdk.kv.read("kv/", "foo", None).await.unwrap();
+    }
+    let resp = sdk.quota.read_rate_limit("kv").await.unwrap();
+    assert_eq!(resp.usage.allowed_requests, 0);
+
+    assert_eq!(sdk.quota.list_rate_limits().await.unwrap().quotas.len(), 1);
+    sdk.quota.delete_rate_limit("kv").await.unwrap();
+    assert!(sdk
+        .quota
+        .list_rate_limits()
+        .await
+        .unwrap()
+        .quotas
+        .is_empty());
+    assert_eq!(
+        sdk.quota.read_rate_limit("kv").await.unwrap_err().code(),
+        Some(ErrorCode::NotFound)
+    );
+}
+
+#[tokio::test]
+async fn rate_limit_quota_must_be_valid() {
+    let sdk = setup_unseal().await;
+    for (rate, burst) in [(0.0, None), (-1.0, None), (1.0, Some(0))] {
+        let err = sdk
+            .quota
+            .set_rate_limit(
+                "global",
+                &RateLimitQuotaParams {
+                    scope: QuotaScope::Global,
+                    rate,
+                    burst,
+                    exempt_root: false,
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    }
+
+    // The burst defaults to the rate
+    let quota = sdk
+        .quota
+        .set_rate_limit(
+            "global",
+            &RateLimitQuotaParams {
+                scope: QuotaScope::Global,
+                rate: 2.5,
+                burst: None,
+                exempt_root: true,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(quota.burst, 3);
+}
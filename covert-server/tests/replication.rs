@@ -1,6 +1,6 @@
 mod common;
 
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use covert_sdk::{
     entity::CreateEntityParams,
@@ -43,7 +43,11 @@ async fn unseal_with_recovered_seal_config_from_local_storage() {
     let threshold = 3;
     let resp = sdk
         .operator
-        .initialize(&InitializeParams { shares, threshold })
+        .initialize(&InitializeParams {
+            shares,
+            threshold,
+            stored_shares: None,
+        })
         .await
         .unwrap();
     let InitializeResponse::NewKeyShares(key_shares) = resp else {
@@ -150,7 +154,11 @@ async fn unseal_with_recovered_seal_config_from_remote_backup() {
     let threshold = 3;
     let resp = sdk
         .operator
-        .initialize(&InitializeParams { shares, threshold })
+        .initialize(&InitializeParams {
+            shares,
+            threshold,
+            stored_shares: None,
+        })
         .await
         .unwrap();
     let InitializeResponse::NewKeyShares(key_shares) = resp else {
@@ -268,7 +276,11 @@ async fn recover_encrypted_db_from_backup() {
     let threshold = 3;
     let resp = sdk
         .operator
-        .initialize(&InitializeParams { shares, threshold })
+        .initialize(&InitializeParams {
+            shares,
+            threshold,
+            stored_shares: None,
+        })
         .await
         .unwrap();
     let InitializeResponse::NewKeyShares(key_shares) = resp else {
@@ -352,16 +364,19 @@ async fn recover_encrypted_db_from_backup() {
         vec![
             EntityWithPolicyAndAlias {
                 name: james.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: john.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: "root".to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec!["root".to_string()]
             },
@@ -413,21 +428,25 @@ async fn recover_encrypted_db_from_backup() {
         vec![
             EntityWithPolicyAndAlias {
                 name: foo_name.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: james.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: john.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: "root".to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec!["root".to_string()]
             },
@@ -472,26 +491,31 @@ async fn recover_encrypted_db_from_backup() {
         vec![
             EntityWithPolicyAndAlias {
                 name: bar_name.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: foo_name.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: james.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: john.to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec![]
             },
             EntityWithPolicyAndAlias {
                 name: "root".to_string(),
+                metadata: HashMap::new(),
                 aliases: vec![],
                 policies: vec!["root".to_string()]
             },
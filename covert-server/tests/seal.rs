@@ -1,9 +1,15 @@
 mod common;
 
-use covert_sdk::operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse};
+use std::time::Duration;
+
+use covert_sdk::{
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    status::TamperConfig,
+    ErrorCode,
+};
 use covert_types::state::StorageState;
 
-use common::setup;
+use common::{setup, unseal};
 use tokio::sync::oneshot;
 
 #[tokio::test]
@@ -19,7 +25,11 @@ async fn seal() {
     let threshold = 3;
     let resp = sdk
         .operator
-        .initialize(&InitializeParams { shares, threshold })
+        .initialize(&InitializeParams {
+            shares,
+            threshold,
+            stored_shares: None,
+        })
         .await
         .unwrap();
     let InitializeResponse::NewKeyShares(key_shares) = resp else {
@@ -32,13 +42,19 @@ async fn seal() {
     assert_eq!(resp, Ok(StorageState::Sealed));
 
     // Init again fails
-    assert!(sdk
+    let err = sdk
         .operator
-        .initialize(&InitializeParams { shares, threshold })
+        .initialize(&InitializeParams {
+            shares,
+            threshold,
+            stored_shares: None,
+        })
         .await
-        .is_err());
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::SealedState));
 
     // Start unseal
+    let mut root = None;
     for i in 0..usize::from(threshold) {
         let resp = sdk
             .operator
@@ -49,7 +65,10 @@ async fn seal() {
             .unwrap();
 
         if u8::try_from(i).unwrap() == threshold - 1 {
-            assert!(matches!(resp, UnsealResponse::Complete { .. }));
+            let UnsealResponse::Complete { root_token } = resp else {
+                panic!("Unexpected unseal response");
+            };
+            root = Some(root_token);
         } else {
             assert!(matches!(
                 resp,
@@ -79,6 +98,10 @@ async fn seal() {
         .await
         .is_err());
 
+    // Sealing requires a token with the sudo capability
+    assert!(sdk.operator.seal().await.is_err());
+    sdk.set_token(root.map(|token| token.to_string())).await;
+
     // Seal again
     assert!(sdk.operator.seal().await.is_ok());
     let resp = sdk.status.status().await.map(|resp| resp.state);
@@ -172,7 +195,11 @@ async fn recover_seal_config_after_shutdown() {
     let threshold = 3;
     let resp = sdk
         .operator
-        .initialize(&InitializeParams { shares, threshold })
+        .initialize(&InitializeParams {
+            shares,
+            threshold,
+            stored_shares: None,
+        })
         .await
         .unwrap();
     let InitializeResponse::NewKeyShares(key_shares) = resp else {
@@ -230,3 +257,100 @@ async fn recover_seal_config_after_shutdown() {
     let resp = sdk.status.status().await.map(|resp| resp.state);
     assert_eq!(resp, Ok(StorageState::Unsealed));
 }
+
+#[tokio::test]
+async fn seal_on_malformed_token_flood() {
+    let sdk = setup(":memory:", covert_system::shutdown_signal(), None).await;
+    let root_token = unseal(&sdk).await;
+
+    // Disabled by default
+    assert_eq!(
+        sdk.status.tamper_config().await.unwrap(),
+        TamperConfig::default()
+    );
+    sdk.set_token(Some("malformed".to_string())).await;
+    for _ in 0..150 {
+        assert!(sdk.status.status().await.is_err());
+    }
+    sdk.set_token(Some(root_token.clone())).await;
+    assert_eq!(
+        sdk.status.status().await.unwrap().state,
+        StorageState::Unsealed
+    );
+
+    // The window has to be set when enabled
+    let err = sdk
+        .status
+        .set_tamper_config(&TamperConfig {
+            enabled: true,
+            window: Duration::ZERO,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    let config = TamperConfig {
+        enabled: true,
+        malformed_tokens: 5,
+        ..Default::default()
+    };
+    assert_eq!(sdk.status.set_tamper_config(&config).await.unwrap(), config);
+    assert_eq!(sdk.status.tamper_config().await.unwrap(), config);
+
+    sdk.set_token(Some("malformed".to_string())).await;
+    for _ in 0..5 {
+        assert!(sdk.status.status().await.is_err());
+    }
+
+    // The server seals itself in the background
+    sdk.set_token(None).await;
+    let mut state = StorageState::Unsealed;
+    for _ in 0..50 {
+        state = sdk.status.status().await.unwrap().state;
+        if state == StorageState::Sealed {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(state, StorageState::Sealed);
+}
+
+#[cfg(feature = "pkcs11")]
+#[tokio::test]
+async fn pkcs11_seal_fails_closed() {
+    let (port_tx, port_rx) = oneshot::channel();
+    let config = covert_system::Config {
+        port: Some(0),
+        port_tx: Some(port_tx),
+        tls_disable: true,
+        storage_path: ":memory:".into(),
+        seal: Some(covert_system::SealConfig::Pkcs11 {
+            lib_path: "/does/not/exist/libpkcs11.so".into(),
+            slot: 0,
+            key_label: "covert".to_string(),
+            pin: "1234".to_string(),
+        }),
+        ..Default::default()
+    };
+    tokio::spawn(covert_system::start(
+        config,
+        covert_system::shutdown_signal(),
+    ));
+    let port = port_rx.await.unwrap();
+    let sdk = covert_sdk::Client::new(format!("http://localhost:{port}/v1"));
+
+    // The storage is not initialized with a key the seal cannot wrap
+    let err = sdk
+        .operator
+        .initialize(&InitializeParams {
+            shares: 1,
+            threshold: 1,
+            stored_shares: None,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::UpstreamError));
+    let resp = sdk.status.status().await.map(|resp| resp.state);
+    assert_eq!(resp, Ok(StorageState::Uninitialized));
+}
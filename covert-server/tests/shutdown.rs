@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use covert_sdk::Client;
+use covert_system::{Config, ShutdownTimedOut};
+use tokio::{io::AsyncWriteExt, net::TcpStream, sync::oneshot, task::JoinHandle};
+
+async fn setup(
+    shutdown_timeout: Duration,
+) -> (u16, oneshot::Sender<()>, JoinHandle<anyhow::Result<()>>) {
+    let (port_tx, port_rx) = oneshot::channel();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let config = Config {
+        port: Some(0),
+        port_tx: Some(port_tx),
+        tls_disable: true,
+        storage_path: ":memory:".into(),
+        shutdown_timeout,
+        ..Default::default()
+    };
+    let server = tokio::spawn(covert_system::start(config, async {
+        let _ = shutdown_rx.await;
+    }));
+    let port = port_rx.await.unwrap();
+    (port, shutdown_tx, server)
+}
+
+#[tokio::test]
+async fn graceful_shutdown() {
+    let (port, shutdown_tx, server) = setup(Duration::from_secs(5)).await;
+    let sdk = Client::new(format!("http://localhost:{port}/v1"));
+    assert!(sdk.status.status().await.is_ok());
+
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+
+    // No new connections are accepted
+    assert!(TcpStream::connect(("127.0.0.1", port)).await.is_err());
+}
+
+#[tokio::test]
+async fn shutdown_times_out_on_unfinished_requests() {
+    let (port, shutdown_tx, server) = setup(Duration::from_millis(200)).await;
+
+    // A request that is still being received keeps the connection busy
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"GET /v1/sys/status HTTP/1.1\r\nhost: localhost\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    shutdown_tx.send(()).unwrap();
+    let err = server.await.unwrap().unwrap_err();
+    assert!(err.is::<ShutdownTimedOut>());
+}
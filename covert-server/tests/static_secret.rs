@@ -0,0 +1,112 @@
+mod common;
+
+use std::time::Duration;
+
+use common::setup_unseal;
+use covert_sdk::{
+    static_secret::{RotationHandler, SetStaticSecretParams},
+    ErrorCode,
+};
+
+#[tokio::test]
+async fn static_secret_rotation() {
+    let sdk = setup_unseal().await;
+    let params = SetStaticSecretParams {
+        handler: RotationHandler::Random { length: 16 },
+        interval: Duration::from_secs(60 * 60),
+        grace: None,
+    };
+    let secret = sdk.static_secret.set("app", &params).await.unwrap();
+    assert_eq!(secret.version, 1);
+    assert_eq!(secret.grace, params.interval);
+
+    let first = sdk.static_secret.read("app").await.unwrap();
+    assert_eq!(first.secret, secret);
+    assert_eq!(first.data["value"].as_str().unwrap().len(), 16);
+    // Nothing was rotated yet
+    assert_eq!(
+        sdk.static_secret
+            .read_previous("app")
+            .await
+            .unwrap_err()
+            .code(),
+        Some(ErrorCode::NotFound)
+    );
+
+    let rotated = sdk.static_secret.rotate("app").await.unwrap();
+    assert_eq!(rotated.version, 2);
+    let second = sdk.static_secret.read("app").await.unwrap();
+    assert_ne!(second.data, first.data);
+
+    // The previous value can be read during the grace period
+    let previous = sdk.static_secret.read_previous("app").await.unwrap();
+    assert_eq!(previous.version, 1);
+    assert_eq!(previous.data, first.data);
+
+    // Changing the schedule keeps the value
+    let secret = sdk
+        .static_secret
+        .set(
+            "app",
+            &SetStaticSecretParams {
+                interval: Duration::from_secs(2 * 60 * 60),
+                ..params
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(secret.version, 2);
+    assert_eq!(
+        sdk.static_secret.read("app").await.unwrap().data,
+        second.data
+    );
+
+    let list = sdk.static_secret.list().await.unwrap();
+    assert_eq!(list.secrets, vec![secret]);
+
+    sdk.static_secret.delete("app").await.unwrap();
+    assert!(sdk.static_secret.list().await.unwrap().secrets.is_empty());
+    for err in [
+        sdk.static_secret.read("app").await.unwrap_err(),
+        sdk.static_secret.rotate("app").await.unwrap_err(),
+    ] {
+        assert_eq!(err.code(), Some(ErrorCode::NotFound));
+    }
+}
+
+#[tokio::test]
+async fn static_secret_must_be_valid() {
+    let sdk = setup_unseal().await;
+    let invalid = [
+        (
+            RotationHandler::Random { length: 32 },
+            Duration::from_secs(1),
+        ),
+        (
+            RotationHandler::Random { length: 4 },
+            Duration::from_secs(60),
+        ),
+        (
+            RotationHandler::Backend {
+                path: "sys/static-secrets/other".into(),
+            },
+            Duration::from_secs(60),
+        ),
+    ];
+    for (handler, interval) in invalid {
+        let err = sdk
+            .static_secret
+            .set(
+                "app",
+                &SetStaticSecretParams {
+                    handler,
+                    interval,
+                    grace: None,
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+    }
+    assert!(sdk.static_secret.list().await.unwrap().secrets.is_empty());
+}
@@ -1,6 +1,12 @@
 mod common;
 
-use common::setup_unseal;
+use std::time::Duration;
+
+use common::{login_with_policy, setup_unseal};
+use covert_sdk::{
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    ErrorCode,
+};
 
 #[tokio::test]
 async fn status() {
@@ -9,3 +15,41 @@ async fn status() {
     let resp = sdk.status.status().await.map(|resp| resp.state);
     assert_eq!(resp, Ok(covert_types::state::StorageState::Unsealed));
 }
+
+#[tokio::test]
+async fn config_state() {
+    let sdk = setup_unseal().await;
+
+    let config = sdk.status.config_state().await.unwrap();
+    assert_eq!(config.listener.port, Some(0));
+    assert!(config.listener.unix_sockets.is_empty());
+    assert!(config.listener.tls.is_none());
+    assert!(config.storage.in_memory);
+    assert_eq!(config.seal.variant, "shamir");
+    assert_eq!(config.seal.shares, Some(1));
+    assert_eq!(config.seal.threshold, Some(1));
+    assert_eq!(config.ttl.default_lease_ttl, Duration::from_mins(30));
+    assert_eq!(config.ttl.system_max_lease_ttl, None);
+    assert!(config.replication.is_none());
+
+    // Requires sudo
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let token = login_with_policy(
+        &sdk,
+        "reader",
+        r#"path "sys/config/*" { capabilities = ["read"] }"#,
+    )
+    .await;
+    sdk.set_token(Some(token)).await;
+    let err = sdk.status.config_state().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
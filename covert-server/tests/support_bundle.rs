@@ -0,0 +1,101 @@
+mod common;
+
+use std::io::Read;
+
+use common::{login_with_policy, setup_unseal};
+use covert_sdk::{
+    audit::{AuditDeviceConfig, AuditFormat, EnableAuditDeviceParams, FileAuditConfig},
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    ErrorCode,
+};
+use flate2::read::GzDecoder;
+use serde_json::Value;
+
+/// Files of the gzip compressed tarball, keyed by their path without the root
+/// directory of the bundle.
+fn untar(bundle: &[u8]) -> Vec<(String, Value)> {
+    let mut tar = vec![];
+    GzDecoder::new(bundle).read_to_end(&mut tar).unwrap();
+
+    let mut files = vec![];
+    let mut offset = 0;
+    while tar[offset] != 0 {
+        let header = &tar[offset..offset + 512];
+        let path = std::str::from_utf8(&header[..100])
+            .unwrap()
+            .trim_end_matches('\0');
+        let size =
+            usize::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap();
+        let data = &tar[offset + 512..offset + 512 + size];
+        let (root, name) = path.split_once('/').unwrap();
+        assert!(root.starts_with("covert-support-bundle-"));
+        files.push((name.to_string(), serde_json::from_slice(data).unwrap()));
+        offset += 512 + size.div_ceil(512) * 512;
+    }
+    files
+}
+
+#[tokio::test]
+async fn support_bundle() {
+    let sdk = setup_unseal().await;
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Userpass,
+            },
+        )
+        .await
+        .unwrap();
+    let tmpdir = tempfile::tempdir().unwrap();
+    let log_path = tmpdir.path().join("audit.log");
+    sdk.audit
+        .enable(
+            "file",
+            &EnableAuditDeviceParams {
+                config: AuditDeviceConfig::File(FileAuditConfig {
+                    file_path: log_path.to_str().unwrap().to_string(),
+                    format: AuditFormat::Jsonl,
+                    mode: None,
+                }),
+                hmac_exempt_response_fields: vec![],
+                filter: vec![],
+            },
+        )
+        .await
+        .unwrap();
+
+    let files = untar(&sdk.operator.support_bundle().await.unwrap());
+    let names = files
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            "host.json",
+            "config.json",
+            "seal-status.json",
+            "mounts.json",
+            "audit.json",
+            "diagnose.json"
+        ]
+    );
+    let file = |name: &str| &files.iter().find(|(n, _)| n == name).unwrap().1;
+    assert_eq!(file("host.json")["os"], std::env::consts::OS);
+    assert_eq!(file("seal-status.json")["state"], "unsealed");
+    assert_eq!(file("mounts.json")["auth"][0]["path"], "auth/userpass/");
+    assert_eq!(file("audit.json")[0]["path"], "file");
+    assert!(file("audit.json")[0]["entries_written"].as_u64().unwrap() > 0);
+    let checks = file("diagnose.json").as_array().unwrap();
+    assert!(checks
+        .iter()
+        .any(|check| check["name"] == "tls" && check["status"] == "warning"));
+
+    // Requires sudo
+    let token = login_with_policy(&sdk, "foo", r#"path "sys/*" { capabilities = ["read"] }"#).await;
+    sdk.set_token(Some(token)).await;
+    let err = sdk.operator.support_bundle().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
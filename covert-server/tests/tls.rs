@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use covert_sdk::{
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    Client,
+};
+use covert_system::{Config, TlsConfig, TlsVersion};
+use covert_types::state::StorageState;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use tokio::sync::oneshot;
+
+struct Certs {
+    ca: String,
+    cert: String,
+    key: String,
+}
+
+fn generate_certs() -> Certs {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(vec![]).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "Covert test CA");
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "localhost");
+    let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+
+    Certs {
+        ca: ca.pem(),
+        cert: cert.pem(),
+        key: key.serialize_pem(),
+    }
+}
+
+fn tls_config(dir: &Path, cert: &str, key: &str) -> TlsConfig {
+    let cert_file = dir.join("cert.pem");
+    let key_file = dir.join("key.pem");
+    std::fs::write(&cert_file, cert).unwrap();
+    std::fs::write(&key_file, key).unwrap();
+    TlsConfig {
+        cert_file,
+        key_file,
+        min_version: TlsVersion::Tls13,
+        request_client_cert: false,
+        require_client_cert: false,
+        client_ca_file: None,
+        cipher_suites: vec![],
+    }
+}
+
+fn config(tls: Option<TlsConfig>, tls_disable: bool) -> Config {
+    Config {
+        port: Some(0),
+        tls,
+        tls_disable,
+        storage_path: ":memory:".into(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn status_over_tls() {
+    let dir = tempfile::tempdir().unwrap();
+    let certs = generate_certs();
+
+    let (port_tx, port_rx) = oneshot::channel();
+    let mut config = config(Some(tls_config(dir.path(), &certs.cert, &certs.key)), false);
+    config.port_tx = Some(port_tx);
+    tokio::spawn(async move {
+        if let Err(err) = covert_system::start(config, covert_system::shutdown_signal()).await {
+            panic!("server error: {err}");
+        }
+    });
+    let port = port_rx.await.unwrap();
+
+    let sdk = Client::builder(format!("https://localhost:{port}/v1"))
+        .root_certificate_pem(certs.ca.as_bytes())
+        .unwrap()
+        .build()
+        .unwrap();
+    let resp = sdk.status.status().await.map(|resp| resp.state);
+    assert_eq!(resp, Ok(StorageState::Uninitialized));
+
+    // The server certificate is not trusted without the CA
+    let sdk = Client::new(format!("https://localhost:{port}/v1"));
+    assert!(sdk.status.status().await.is_err());
+
+    // Plain HTTP is not served on the TLS listener
+    let sdk = Client::new(format!("http://localhost:{port}/v1"));
+    assert!(sdk.status.status().await.is_err());
+}
+
+#[tokio::test]
+async fn key_must_match_certificate() {
+    let dir = tempfile::tempdir().unwrap();
+    let certs = generate_certs();
+    let other_key = KeyPair::generate().unwrap().serialize_pem();
+
+    let config = config(Some(tls_config(dir.path(), &certs.cert, &other_key)), false);
+    let err = covert_system::start(config, covert_system::shutdown_signal())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("does not match"), "{err}");
+}
+
+#[tokio::test]
+async fn plain_http_must_be_enabled_explicitly() {
+    let err = covert_system::start(config(None, false), covert_system::shutdown_signal())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("tls-disable"), "{err}");
+
+    let dir = tempfile::tempdir().unwrap();
+    let certs = generate_certs();
+    let config = config(Some(tls_config(dir.path(), &certs.cert, &certs.key)), true);
+    assert!(
+        covert_system::start(config, covert_system::shutdown_signal())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn reload_certificates() {
+    let dir = tempfile::tempdir().unwrap();
+    let certs = generate_certs();
+    let new_certs = generate_certs();
+
+    let (port_tx, port_rx) = oneshot::channel();
+    let tls = tls_config(dir.path(), &certs.cert, &certs.key);
+    let mut config = config(Some(tls.clone()), false);
+    config.port_tx = Some(port_tx);
+    tokio::spawn(async move {
+        if let Err(err) = covert_system::start(config, covert_system::shutdown_signal()).await {
+            panic!("server error: {err}");
+        }
+    });
+    let port = port_rx.await.unwrap();
+
+    let client = |ca: &str| {
+        Client::builder(format!("https://localhost:{port}/v1"))
+            .root_certificate_pem(ca.as_bytes())
+            .unwrap()
+            .build()
+            .unwrap()
+    };
+    let sdk = client(&certs.ca);
+    let shares = match sdk
+        .operator
+        .initialize(&InitializeParams {
+            shares: 1,
+            threshold: 1,
+            stored_shares: None,
+        })
+        .await
+        .unwrap()
+    {
+        InitializeResponse::NewKeyShares(shares) => shares.shares,
+        _ => panic!("should get new shares"),
+    };
+    let UnsealResponse::Complete { root_token } =
+        sdk.operator.unseal(&UnsealParams { shares }).await.unwrap()
+    else {
+        panic!("should be unsealed");
+    };
+    sdk.set_token(Some(root_token.to_string())).await;
+
+    // A broken certificate is rejected and the current one is kept
+    std::fs::write(&tls.cert_file, "not a certificate").unwrap();
+    let err = sdk.status.reload_config().await.unwrap_err();
+    assert!(err.to_string().contains("Failed to reload config"), "{err}");
+    assert!(client(&certs.ca).status.status().await.is_ok());
+
+    // New connections are served with the new certificate
+    tls_config(dir.path(), &new_certs.cert, &new_certs.key);
+    let resp = sdk.status.reload_config().await.unwrap();
+    assert!(resp.tls);
+    assert!(!resp.logging);
+    let sdk = client(&new_certs.ca);
+    sdk.set_token(Some(root_token.to_string())).await;
+    assert!(sdk.status.status().await.is_ok());
+    assert!(client(&certs.ca).status.status().await.is_err());
+
+    // Only the certificate is reloaded, a broken one is rejected
+    std::fs::write(&tls.key_file, "not a key").unwrap();
+    assert!(sdk.status.reload_certificates().await.is_err());
+    assert!(client(&new_certs.ca).status.status().await.is_ok());
+
+    tls_config(dir.path(), &certs.cert, &certs.key);
+    let resp = sdk.status.reload_certificates().await.unwrap();
+    assert!(resp.tls);
+    assert!(!resp.logging);
+    assert!(client(&certs.ca).status.status().await.is_ok());
+    assert!(client(&new_certs.ca).status.status().await.is_err());
+}
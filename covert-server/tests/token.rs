@@ -0,0 +1,490 @@
+mod common;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use covert_sdk::{
+    entity::{AttachEntityAliasParams, AttachEntityPolicyParams, CreateEntityParams, EntityAlias},
+    mounts::{BackendType, CreateMountParams, ListingVisibility, MountConfig},
+    policy::CreatePolicyParams,
+    token::{
+        CreateTokenParams, RenewTokenSelfParams, RevokeTokensByPolicyParams,
+        TokenRevocationJobState, TokenTargetParams,
+    },
+    userpass::{CreateUserParams, LoginParams},
+    ErrorCode,
+};
+
+use chrono::{DateTime, Utc};
+use common::{login_with_policy, setup_unseal};
+
+#[tokio::test]
+async fn revoke_tokens_by_policy() {
+    let sdk = setup_unseal().await;
+
+    let mount_path = "auth/userpass/";
+    sdk.mount
+        .create(
+            mount_path,
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.policy
+        .create(&CreatePolicyParams {
+            name: "compromised".to_string(),
+            policy: r#"path "sys/*" { capabilities = ["read"] }"#.to_string(),
+        })
+        .await
+        .unwrap();
+
+    // Setup user with an entity that carries the policy
+    sdk.userpass
+        .create(
+            mount_path,
+            &CreateUserParams {
+                username: "foo".to_string(),
+                password: "bar".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: "foo".to_string(),
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_policies(&AttachEntityPolicyParams {
+            name: "foo".to_string(),
+            policy_names: vec!["compromised".to_string()],
+        })
+        .await
+        .unwrap();
+    sdk.entity
+        .attach_alias(&AttachEntityAliasParams {
+            name: "foo".to_string(),
+            aliases: vec![EntityAlias {
+                name: "foo".to_string(),
+                mount_path: mount_path.to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let login = LoginParams {
+        username: "foo".to_string(),
+        password: "bar".to_string(),
+        renewable: true,
+    };
+    sdk.userpass.login(mount_path, &login).await.unwrap();
+    let auth = sdk.userpass.login(mount_path, &login).await.unwrap();
+
+    // Dry run only reports the number of tokens
+    let resp = sdk
+        .token
+        .revoke_by_policy(&RevokeTokensByPolicyParams {
+            policy: "compromised".to_string(),
+            dry_run: true,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.matched, 2);
+    assert!(resp.job_id.is_none());
+
+    let resp = sdk
+        .token
+        .revoke_by_policy(&RevokeTokensByPolicyParams {
+            policy: "compromised".to_string(),
+            dry_run: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.matched, 2);
+    let job_id = resp.job_id.unwrap().to_string();
+
+    let mut status = sdk.token.revoke_by_policy_status(&job_id).await.unwrap();
+    while status.state == TokenRevocationJobState::Running {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        status = sdk.token.revoke_by_policy_status(&job_id).await.unwrap();
+    }
+    assert_eq!(status.total, 2);
+    assert_eq!(status.revoked, 2);
+    assert_eq!(status.failed, 0);
+
+    // Nothing left to revoke
+    let resp = sdk
+        .token
+        .revoke_by_policy(&RevokeTokensByPolicyParams {
+            policy: "compromised".to_string(),
+            dry_run: true,
+        })
+        .await
+        .unwrap();
+    assert_eq!(resp.matched, 0);
+
+    // And the revoked token can no longer be used
+    sdk.set_token(Some(auth.token.to_string())).await;
+    assert!(sdk.token.lookup_self().await.is_err());
+}
+
+fn time_until(expire_time: &str) -> chrono::Duration {
+    DateTime::parse_from_rfc3339(expire_time)
+        .unwrap()
+        .with_timezone(&Utc)
+        - Utc::now()
+}
+
+#[tokio::test]
+async fn renew_tokens() {
+    let sdk = setup_unseal().await;
+    let root_token = sdk.token.lookup_self().await.unwrap();
+    assert!(root_token.renewable);
+
+    let mount_path = "auth/userpass/";
+    sdk.mount
+        .create(
+            mount_path,
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig {
+                    default_lease_ttl: Duration::from_secs(60 * 30),
+                    max_lease_ttl: Duration::from_secs(60 * 60 * 4),
+                    require_mfa: false,
+                    max_request_body_size: None,
+                    max_concurrent_requests: None,
+                    plugin: None,
+                    listing_visibility: ListingVisibility::default(),
+                    description: None,
+                    encrypted_fields: BTreeMap::new(),
+                },
+            },
+        )
+        .await
+        .unwrap();
+    let token = login_with_policy(
+        &sdk,
+        "foo",
+        r#"path "sys/leases/*" { capabilities = ["update"] }"#,
+    )
+    .await;
+    let non_renewable = sdk
+        .userpass
+        .login(
+            mount_path,
+            &LoginParams {
+                username: "foo".to_string(),
+                password: "password".to_string(),
+                renewable: false,
+            },
+        )
+        .await
+        .unwrap();
+
+    // Renew by the requested increment
+    sdk.set_token(Some(token)).await;
+    let lookup = sdk.token.lookup_self().await.unwrap();
+    assert!(lookup.renewable);
+    let resp = sdk
+        .token
+        .renew_self(&RenewTokenSelfParams {
+            increment: Some(Duration::from_secs(60 * 60)),
+        })
+        .await
+        .unwrap();
+    let expires_in = time_until(&resp.lease.expire_time);
+    assert!(expires_in > chrono::Duration::minutes(59));
+    assert!(expires_in <= chrono::Duration::minutes(60));
+
+    // The increment is capped by the max TTL of the mount
+    let resp = sdk
+        .token
+        .renew_self(&RenewTokenSelfParams {
+            increment: Some(Duration::from_secs(60 * 60 * 10)),
+        })
+        .await
+        .unwrap();
+    let expires_in = time_until(&resp.lease.expire_time);
+    assert!(expires_in > chrono::Duration::minutes(239));
+    assert!(expires_in <= chrono::Duration::minutes(240));
+
+    // Non-renewable tokens are rejected both when renewing the token itself
+    // and its lease
+    sdk.set_token(Some(non_renewable.token.to_string())).await;
+    let lookup = sdk.token.lookup_self().await.unwrap();
+    assert!(!lookup.renewable);
+    let err = sdk
+        .token
+        .renew_self(&RenewTokenSelfParams { increment: None })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::LeaseNotRenewable));
+    assert_eq!(err.message(), "Token is not renewable");
+
+    let err = sdk
+        .lease
+        .renew(&non_renewable.lease_id, None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::LeaseNotRenewable));
+}
+
+#[tokio::test]
+async fn revoke_token_with_children() {
+    let sdk = setup_unseal().await;
+
+    let mount_path = "auth/userpass/";
+    sdk.mount
+        .create(
+            mount_path,
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let token = login_with_policy(
+        &sdk,
+        "foo",
+        r#"path "sys/*" { capabilities = ["read", "update", "sudo"] }"#,
+    )
+    .await;
+    let login = LoginParams {
+        username: "foo".to_string(),
+        password: "password".to_string(),
+        renewable: true,
+    };
+
+    // Logging in with a token issues a child of it, and a grandchild
+    sdk.set_token(Some(token.clone())).await;
+    let parent = sdk.userpass.login(mount_path, &login).await.unwrap();
+    sdk.set_token(Some(parent.token.to_string())).await;
+    let child = sdk.userpass.login(mount_path, &login).await.unwrap();
+
+    // Revoking the lease of the parent revokes the child before the parent
+    sdk.set_token(Some(token.clone())).await;
+    sdk.lease.revoke(&parent.lease_id).await.unwrap();
+    for revoked in [&parent, &child] {
+        sdk.set_token(Some(revoked.token.to_string())).await;
+        assert!(sdk.token.lookup_self().await.is_err());
+    }
+
+    // Nothing was left half revoked
+    sdk.set_token(Some(token)).await;
+    assert_eq!(sdk.token.tidy().await.unwrap().revoked, 0);
+    assert_eq!(
+        sdk.lease.lookup(&child.lease_id).await.unwrap_err().code(),
+        Some(ErrorCode::NotFound)
+    );
+}
+
+#[tokio::test]
+async fn revoke_token_self() {
+    let sdk = setup_unseal().await;
+    let mount_path = "auth/userpass/";
+    sdk.mount
+        .create(
+            mount_path,
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let token = login_with_policy(
+        &sdk,
+        "foo",
+        r#"path "sys/leases/*" { capabilities = ["read"] }"#,
+    )
+    .await;
+    sdk.set_token(Some(token.clone())).await;
+    let child = sdk
+        .userpass
+        .login(
+            mount_path,
+            &LoginParams {
+                username: "foo".to_string(),
+                password: "password".to_string(),
+                renewable: true,
+            },
+        )
+        .await
+        .unwrap();
+
+    // Revoking the token revokes its children
+    let resp = sdk.token.revoke_self().await.unwrap();
+    assert_eq!(resp.entity_name, "foo");
+    for revoked in [token, child.token.to_string()] {
+        sdk.set_token(Some(revoked)).await;
+        assert!(sdk.token.lookup_self().await.is_err());
+    }
+
+    sdk.set_token(None).await;
+    let err = sdk.token.revoke_self().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+async fn bootstrap_token() {
+    let sdk = setup_unseal().await;
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: "bootstrap".to_string(),
+        })
+        .await
+        .unwrap();
+    let params = |not_before: Option<DateTime<Utc>>, num_uses: Option<u32>| CreateTokenParams {
+        entity_name: "bootstrap".to_string(),
+        policies: vec![],
+        ttl: Duration::from_secs(10 * 60),
+        not_before,
+        num_uses,
+        renewable: false,
+        metadata: Default::default(),
+        orphan: false,
+    };
+
+    let err = sdk.token.create(&params(None, Some(0))).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    let single_use = sdk.token.create(&params(None, Some(1))).await.unwrap();
+    assert_eq!(single_use.lease_duration, Duration::from_secs(10 * 60));
+
+    // The TTL is clamped to the max lease TTL of the system mount
+    let long_lived = sdk
+        .token
+        .create(&CreateTokenParams {
+            ttl: Duration::from_secs(24 * 60 * 60),
+            ..params(None, None)
+        })
+        .await
+        .unwrap();
+    assert_eq!(long_lived.lease_duration, Duration::from_secs(4 * 60 * 60));
+    let err = sdk
+        .token
+        .create(&params(Some(Utc::now() + chrono::Duration::hours(5)), None))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    let not_before = Utc::now() + chrono::Duration::hours(1);
+    let scheduled = sdk
+        .token
+        .create(&params(Some(not_before), Some(1)))
+        .await
+        .unwrap();
+    // The lease covers the wait for the window
+    assert!(scheduled.lease_duration > Duration::from_secs(60 * 60));
+
+    // The token can be used once
+    sdk.set_token(Some(single_use.token.to_string())).await;
+    let resp = sdk.token.lookup_self().await.unwrap();
+    assert_eq!(resp.entity_name, "bootstrap");
+    assert_eq!(resp.num_uses, Some(0));
+    let err = sdk.token.lookup_self().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    // The token cannot be used before its window
+    sdk.set_token(Some(scheduled.token.to_string())).await;
+    let err = sdk.token.lookup_self().await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+async fn lookup_and_revoke_other_tokens() {
+    let sdk = setup_unseal().await;
+    sdk.entity
+        .create(&CreateEntityParams {
+            name: "app".to_string(),
+        })
+        .await
+        .unwrap();
+    let params = |orphan: bool| CreateTokenParams {
+        entity_name: "app".to_string(),
+        policies: vec![],
+        ttl: Duration::from_secs(10 * 60),
+        not_before: None,
+        num_uses: None,
+        renewable: true,
+        metadata: Default::default(),
+        orphan,
+    };
+    let token = sdk.token.create(&params(false)).await.unwrap();
+    assert!(!token.accessor.is_empty());
+
+    let by_token = sdk
+        .token
+        .lookup(&TokenTargetParams {
+            token: Some(token.token.to_string()),
+            accessor: None,
+        })
+        .await
+        .unwrap();
+    let by_accessor = sdk
+        .token
+        .lookup(&TokenTargetParams {
+            token: None,
+            accessor: Some(token.accessor.clone()),
+        })
+        .await
+        .unwrap();
+    assert_eq!(by_token.entity_name, "app");
+    assert_eq!(by_token.accessor, token.accessor);
+    assert_eq!(by_accessor.issue_time, by_token.issue_time);
+
+    let err = sdk
+        .token
+        .lookup(&TokenTargetParams::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadRequest));
+
+    sdk.token
+        .revoke(&TokenTargetParams {
+            token: None,
+            accessor: Some(token.accessor.clone()),
+        })
+        .await
+        .unwrap();
+    let err = sdk
+        .token
+        .lookup(&TokenTargetParams {
+            token: Some(token.token.to_string()),
+            accessor: None,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    // An orphan outlives the token it was created with
+    sdk.mount
+        .create(
+            "auth/userpass/",
+            &CreateMountParams {
+                variant: BackendType::Userpass,
+                config: MountConfig::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let token = login_with_policy(
+        &sdk,
+        "foo",
+        r#"path "sys/token/*" { capabilities = ["create", "update", "sudo"] }"#,
+    )
+    .await;
+    sdk.set_token(Some(token)).await;
+    let child = sdk.token.create(&params(false)).await.unwrap();
+    let orphan = sdk.token.create(&params(true)).await.unwrap();
+    sdk.token.revoke_self().await.unwrap();
+
+    sdk.set_token(Some(child.token.to_string())).await;
+    assert!(sdk.token.lookup_self().await.is_err());
+    sdk.set_token(Some(orphan.token.to_string())).await;
+    assert_eq!(sdk.token.lookup_self().await.unwrap().entity_name, "app");
+}
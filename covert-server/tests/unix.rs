@@ -0,0 +1,119 @@
+use std::{collections::HashMap, os::unix::fs::PermissionsExt, time::Duration};
+
+use covert_sdk::{
+    kv::CreateSecretParams,
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    operator::{InitializeParams, InitializeResponse, UnsealParams, UnsealResponse},
+    Client,
+};
+use covert_system::{Config, FileMode, ListenerAddress, ListenerConfig};
+use tokio::sync::oneshot;
+
+#[tokio::test]
+async fn serve_over_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("covert.sock");
+
+    let config = Config {
+        listeners: vec![ListenerConfig {
+            address: ListenerAddress::Unix(path.clone()),
+            mode: Some(FileMode(0o600)),
+            owner: None,
+            group: None,
+        }],
+        storage_path: ":memory:".into(),
+        ..Default::default()
+    };
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(covert_system::start(config, async move {
+        let _ = shutdown_rx.await;
+    }));
+
+    while !path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o7777,
+        0o600
+    );
+
+    let sdk = Client::builder("http://localhost/v1")
+        .unix_socket(&path)
+        .build()
+        .unwrap();
+
+    // System routes
+    let shares = match sdk
+        .operator
+        .initialize(&InitializeParams {
+            shares: 1,
+            threshold: 1,
+            stored_shares: None,
+        })
+        .await
+        .unwrap()
+    {
+        InitializeResponse::NewKeyShares(shares) => shares.shares,
+        _ => panic!("should get new shares"),
+    };
+    let UnsealResponse::Complete { root_token } =
+        sdk.operator.unseal(&UnsealParams { shares }).await.unwrap()
+    else {
+        panic!("should be unsealed");
+    };
+    sdk.set_token(Some(root_token.to_string())).await;
+
+    // Backend routes
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+    let data = HashMap::from([("foo".to_string(), "bar".to_string())]);
+    sdk.kv
+        .create("kv/", "foo", &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+    let secret = sdk.kv.read("kv/", "foo", None).await.unwrap();
+    assert_eq!(secret.data, Some(data));
+
+    // No TCP listener is bound
+    let config = sdk.status.config_state().await.unwrap();
+    assert_eq!(config.listener.port, None);
+    assert_eq!(
+        config.listener.unix_sockets,
+        vec![format!("unix://{}", path.display())]
+    );
+
+    // The socket is removed on shutdown
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn listener_address_must_be_supported() {
+    let err = toml_listener("address = \"http://127.0.0.1:8080\"").unwrap_err();
+    assert!(err.contains("Unsupported listener address"), "{err}");
+    let err = toml_listener("address = \"unix:///tmp/covert.sock\"\nmode = \"999\"").unwrap_err();
+    assert!(err.contains("Invalid file mode"), "{err}");
+
+    let listener = toml_listener("address = \"unix:///tmp/covert.sock\"\nmode = \"0660\"").unwrap();
+    assert_eq!(
+        listener.address,
+        ListenerAddress::Unix("/tmp/covert.sock".into())
+    );
+    assert_eq!(listener.mode, Some(FileMode(0o660)));
+}
+
+fn toml_listener(listener: &str) -> Result<ListenerConfig, String> {
+    let config = format!("storage-path = \":memory:\"\n[[listener]]\n{listener}");
+    toml::from_str::<Config>(&config)
+        .map(|mut config| config.listeners.remove(0))
+        .map_err(|err| err.to_string())
+}
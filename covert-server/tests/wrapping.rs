@@ -0,0 +1,136 @@
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use common::setup_unseal;
+use covert_sdk::{
+    kv::{CreateSecretParams, ReadSecretResponse},
+    mounts::{BackendType, CreateMountParams, MountConfig},
+    wrapping::Method,
+    ErrorCode,
+};
+
+#[tokio::test]
+async fn wrap_and_unwrap_response() {
+    let sdk = setup_unseal().await;
+
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+    let data = HashMap::from([("password".to_string(), "secret".to_string())]);
+    sdk.kv
+        .create("kv/", "foo", &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+
+    let info = sdk
+        .wrapping
+        .wrap::<()>(Method::GET, "kv/data/foo", None, Duration::from_secs(300))
+        .await
+        .unwrap();
+    assert_eq!(info.ttl, Duration::from_secs(300));
+    assert_eq!(info.creation_path, "kv/data/foo");
+
+    // Wrap TTL is bounded
+    assert!(sdk
+        .wrapping
+        .wrap::<()>(
+            Method::GET,
+            "kv/data/foo",
+            None,
+            Duration::from_secs(3600 * 48)
+        )
+        .await
+        .is_err());
+
+    // Lookup does not consume the token
+    let lookup = sdk.wrapping.lookup(&info.token).await.unwrap();
+    assert_eq!(lookup.creation_path, "kv/data/foo");
+    assert_eq!(lookup.creation_time, info.creation_time);
+
+    // Rewrap invalidates the old token
+    let rewrapped = sdk.wrapping.rewrap(&info.token).await.unwrap();
+    assert_ne!(rewrapped.token, info.token);
+    assert_eq!(rewrapped.ttl, info.ttl);
+    let err = sdk
+        .wrapping
+        .unwrap::<ReadSecretResponse>(&info.token)
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("already unwrapped"), "{err}");
+
+    let secret: ReadSecretResponse = sdk.wrapping.unwrap(&rewrapped.token).await.unwrap();
+    assert_eq!(secret.data, Some(data));
+
+    // Wrapping tokens can only be unwrapped once
+    let err = sdk
+        .wrapping
+        .unwrap::<ReadSecretResponse>(&rewrapped.token)
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("already unwrapped"), "{err}");
+    assert_eq!(err.code(), Some(ErrorCode::WrappingTokenUnwrapped));
+}
+
+#[tokio::test]
+async fn unwrap_without_client_token() {
+    let sdk = setup_unseal().await;
+
+    sdk.mount
+        .create(
+            "kv/",
+            &CreateMountParams {
+                config: MountConfig::default(),
+                variant: BackendType::Kv,
+            },
+        )
+        .await
+        .unwrap();
+    let data = HashMap::from([("password".to_string(), "secret".to_string())]);
+    sdk.kv
+        .create("kv/", "foo", &CreateSecretParams { data: data.clone() })
+        .await
+        .unwrap();
+    let info = sdk
+        .wrapping
+        .wrap::<()>(Method::GET, "kv/data/foo", None, Duration::from_secs(300))
+        .await
+        .unwrap();
+    let expiring = sdk
+        .wrapping
+        .wrap::<()>(Method::GET, "kv/data/foo", None, Duration::from_secs(1))
+        .await
+        .unwrap();
+
+    // The recipient only has the wrapping token
+    sdk.set_token(None).await;
+    let lookup = sdk.wrapping.lookup(&info.token).await.unwrap();
+    assert_eq!(lookup.creation_path, "kv/data/foo");
+    let secret: ReadSecretResponse = sdk.wrapping.unwrap(&info.token).await.unwrap();
+    assert_eq!(secret.data, Some(data));
+    let err = sdk
+        .wrapping
+        .unwrap::<ReadSecretResponse>(&info.token)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::WrappingTokenUnwrapped));
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let err = sdk
+        .wrapping
+        .unwrap::<ReadSecretResponse>(&expiring.token)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::WrappingTokenExpired));
+
+    // Rewrapping still requires a token
+    let err = sdk.wrapping.rewrap(&info.token).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
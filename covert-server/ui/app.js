@@ -0,0 +1,330 @@
+"use strict";
+
+// The UI is served by the server it manages, so the API is on the same origin.
+const API = "/v1";
+const TOKEN_KEY = "covert-token";
+// Mount of the OIDC login waiting for the provider to send the user back.
+const OIDC_MOUNT_KEY = "covert-oidc-mount";
+
+const main = document.getElementById("main");
+const errorBox = document.getElementById("error");
+
+function token() {
+  return sessionStorage.getItem(TOKEN_KEY);
+}
+
+function setToken(value) {
+  if (value) {
+    sessionStorage.setItem(TOKEN_KEY, value);
+  } else {
+    sessionStorage.removeItem(TOKEN_KEY);
+  }
+}
+
+async function api(method, path, body) {
+  const headers = {};
+  if (token()) {
+    headers["X-Covert-Token"] = token();
+  }
+  if (body !== undefined) {
+    headers["Content-Type"] = "application/json";
+  }
+  const resp = await fetch(API + path, {
+    method,
+    headers,
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  const json = await resp.json();
+  if (json.error) {
+    throw new Error(json.error);
+  }
+  return json.data;
+}
+
+function showError(err) {
+  errorBox.textContent = err ? err.message : "";
+  errorBox.hidden = !err;
+}
+
+function view(name) {
+  const template = document.getElementById(`${name}-view`);
+  main.replaceChildren(template.content.cloneNode(true));
+}
+
+function onSubmit(id, handler) {
+  document.getElementById(id).addEventListener("submit", (event) => {
+    event.preventDefault();
+    showError(null);
+    handler(new FormData(event.target)).catch(showError);
+  });
+}
+
+function onClick(id, handler) {
+  document.getElementById(id).addEventListener("click", () => {
+    showError(null);
+    handler().catch(showError);
+  });
+}
+
+function cell(row, text) {
+  const td = document.createElement("td");
+  td.textContent = text;
+  row.appendChild(td);
+  return td;
+}
+
+function initView() {
+  view("init");
+  onSubmit("init-form", async (form) => {
+    const data = await api("POST", "/sys/init", {
+      shares: Number(form.get("shares")),
+      threshold: Number(form.get("threshold")),
+    });
+    document.getElementById("init-form").hidden = true;
+    document.getElementById("init-shares").textContent = (data.shares || []).join("\n");
+    document.getElementById("init-result").hidden = false;
+  });
+  onClick("init-continue", route);
+}
+
+function unsealView() {
+  view("unseal");
+  onSubmit("unseal-form", async (form) => {
+    const shares = form
+      .get("shares")
+      .split("\n")
+      .map((share) => share.trim())
+      .filter((share) => share.length > 0);
+    const resp = await api("POST", "/sys/unseal", { shares });
+    if (resp.unseal_status === "complete") {
+      document.getElementById("unseal-form").hidden = true;
+      document.getElementById("unseal-progress").hidden = true;
+      document.getElementById("unseal-root-token").textContent = resp.data.root_token;
+      document.getElementById("unseal-result").hidden = false;
+      onClick("unseal-login", async () => {
+        setToken(resp.data.root_token);
+        await route();
+      });
+    } else {
+      const progress = document.getElementById("unseal-progress");
+      progress.textContent =
+        `${resp.data.key_shares_provided} of ${resp.data.threshold} key shares provided.`;
+      progress.hidden = false;
+      document.getElementById("unseal-form").reset();
+    }
+  });
+}
+
+function loginView() {
+  view("login");
+  onSubmit("token-login-form", async (form) => {
+    setToken(form.get("token"));
+    try {
+      await api("GET", "/sys/token/lookup-self");
+    } catch (err) {
+      setToken(null);
+      throw err;
+    }
+    location.hash = "#/mounts";
+    await route();
+  });
+  onSubmit("userpass-login-form", async (form) => {
+    let mount = form.get("mount").replace(/^\/+/, "");
+    if (!mount.endsWith("/")) {
+      mount += "/";
+    }
+    const auth = await api("PUT", `/${mount}login`, {
+      username: form.get("username"),
+      password: form.get("password"),
+    });
+    setToken(auth.token);
+    location.hash = "#/mounts";
+    await route();
+  });
+  onSubmit("oidc-login-form", async (form) => {
+    let mount = form.get("mount").replace(/^\/+/, "");
+    if (!mount.endsWith("/")) {
+      mount += "/";
+    }
+    // The provider sends the user back to the UI with the code and state in
+    // the query, so the UI URL has to be an allowed redirect URI of the mount
+    const data = await api("PUT", `/${mount}auth_url`, {
+      redirect_uri: location.origin + location.pathname,
+    });
+    sessionStorage.setItem(OIDC_MOUNT_KEY, mount);
+    location.assign(data.auth_url);
+  });
+}
+
+// Completes the OIDC login started in the login view once the provider sent
+// the user back to the UI.
+async function oidcCallback() {
+  const params = new URLSearchParams(location.search);
+  const mount = sessionStorage.getItem(OIDC_MOUNT_KEY);
+  if (mount === null || !params.has("state")) {
+    return;
+  }
+  sessionStorage.removeItem(OIDC_MOUNT_KEY);
+  // The code can only be used once, so it should not stay in the history
+  history.replaceState(null, "", `${location.pathname}#/mounts`);
+  if (params.has("error")) {
+    throw new Error(params.get("error_description") || params.get("error"));
+  }
+  const auth = await api("PUT", `/${mount}callback`, {
+    state: params.get("state"),
+    code: params.get("code"),
+  });
+  setToken(auth.token);
+}
+
+async function mountsView() {
+  view("mounts");
+  const data = await api("GET", "/sys/mounts");
+  const tbody = document.getElementById("mounts");
+  for (const mount of [...data.secret, ...data.auth]) {
+    const row = document.createElement("tr");
+    cell(row, mount.path);
+    cell(row, mount.category);
+    cell(row, mount.type);
+    tbody.appendChild(row);
+  }
+}
+
+function kvPath(form) {
+  let mount = form.get("mount").replace(/^\/+/, "");
+  if (!mount.endsWith("/")) {
+    mount += "/";
+  }
+  return `/${mount}data/${form.get("key").replace(/^\/+/, "")}`;
+}
+
+function kvView() {
+  view("kv");
+  const readForm = document.getElementById("kv-read-form");
+  const dataInput = document.querySelector("#kv-write-form textarea");
+  const version = document.getElementById("kv-version");
+  onSubmit("kv-read-form", async (form) => {
+    const secret = await api("GET", kvPath(form));
+    dataInput.value = JSON.stringify(secret.data || {}, null, 2);
+    version.textContent = `Version ${secret.metadata.version}`;
+  });
+  onSubmit("kv-write-form", async (form) => {
+    let data;
+    try {
+      data = JSON.parse(form.get("data"));
+    } catch (err) {
+      throw new Error(`Invalid JSON: ${err.message}`);
+    }
+    const meta = await api("POST", kvPath(new FormData(readForm)), { data });
+    version.textContent = `Wrote version ${meta.version}`;
+  });
+}
+
+async function policiesView() {
+  view("policies");
+  const data = await api("GET", "/sys/policies");
+  const tbody = document.getElementById("policies");
+  for (const policy of data.policies) {
+    const row = document.createElement("tr");
+    cell(row, policy.name);
+    cell(
+      row,
+      policy.paths.map((path) => `${path.path} (${path.operations.join(", ")})`).join("; "),
+    );
+    const remove = document.createElement("button");
+    remove.type = "button";
+    remove.textContent = "Remove";
+    remove.addEventListener("click", () => {
+      showError(null);
+      api("DELETE", `/sys/policies/${encodeURIComponent(policy.name)}`)
+        .then(policiesView)
+        .catch(showError);
+    });
+    cell(row, "").appendChild(remove);
+    tbody.appendChild(row);
+  }
+  onSubmit("policy-form", async (form) => {
+    await api("POST", "/sys/policies", {
+      name: form.get("name"),
+      policy: form.get("policy"),
+    });
+    await policiesView();
+  });
+}
+
+async function tokenView() {
+  view("token");
+  const info = await api("GET", "/sys/token/lookup-self");
+  const dl = document.getElementById("token-info");
+  const fields = [
+    ["Entity", info.entity_name],
+    ["Issued", info.issue_time],
+    ["Expires", info.expire_time || "never"],
+    ["Renewable", info.renewable ? "yes" : "no"],
+  ];
+  for (const [name, value] of fields) {
+    const dt = document.createElement("dt");
+    dt.textContent = name;
+    const dd = document.createElement("dd");
+    dd.textContent = value;
+    dl.append(dt, dd);
+  }
+  const renew = document.getElementById("token-renew");
+  renew.hidden = !info.renewable || !info.expire_time;
+  onClick("token-renew", async () => {
+    await api("PUT", "/sys/token/renew-self", {});
+    await tokenView();
+  });
+}
+
+const views = {
+  "#/mounts": mountsView,
+  "#/kv": kvView,
+  "#/policies": policiesView,
+  "#/token": tokenView,
+};
+
+// Pick the view from the seal state first and the location second, so the
+// operator is always taken through initialization and unsealing.
+async function route() {
+  const status = await api("GET", "/sys/status");
+  const state = document.getElementById("state");
+  state.textContent = status.state;
+  const loggedIn = status.state === "unsealed" && token() !== null;
+  document.getElementById("nav").hidden = !loggedIn;
+  document.getElementById("seal").hidden = !loggedIn;
+  document.getElementById("logout").hidden = !loggedIn;
+
+  if (status.state === "uninitialized") {
+    initView();
+  } else if (status.state === "sealed") {
+    unsealView();
+  } else if (!loggedIn) {
+    loginView();
+  } else {
+    await (views[location.hash] || mountsView)();
+  }
+}
+
+document.getElementById("logout").addEventListener("click", () => {
+  setToken(null);
+  showError(null);
+  route().catch(showError);
+});
+
+document.getElementById("seal").addEventListener("click", () => {
+  showError(null);
+  api("POST", "/sys/seal", {})
+    .then(route)
+    .catch(showError);
+});
+
+window.addEventListener("hashchange", () => {
+  showError(null);
+  route().catch(showError);
+});
+
+oidcCallback()
+  .catch(showError)
+  .then(route)
+  .catch(showError);
@@ -10,7 +10,9 @@ pub mod migrator;
 mod scoped_queries;
 mod states;
 mod storage;
+mod storage_view;
 mod utils;
 
 pub use backend_pool::BackendStoragePool;
 pub use encrypted_pool::{EncryptedPool, EncryptedPoolError, PoolState};
+pub use storage_view::{StorageView, StorageViewError};
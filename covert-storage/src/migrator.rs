@@ -6,8 +6,15 @@ use crate::{BackendStoragePool, EncryptedPool};
 
 const BACKEND_MIGRATIONS_TABLE: &str = "_BACKEND_STORAGE_MIGRATIONS";
 
+/// A versioned migration of the storage of a backend.
+///
+/// Migrations are applied in increasing order of their version and every
+/// applied migration is recorded together with a checksum of its script. The
+/// version is the date prefix of the file name, e.g. `2023-03-20-lockout.sql`
+/// has version `20230320`.
 #[derive(Debug)]
 pub struct MigrationScript {
+    pub version: i64,
     pub script: String,
     pub description: String,
 }
@@ -28,11 +35,6 @@ async fn create_migrate_table(pool: &EncryptedPool) -> Result<(), sqlx::Error> {
     Ok(())
 }
 
-#[derive(Debug, sqlx::FromRow)]
-struct LatestMigration {
-    latest_version: Option<i64>,
-}
-
 #[derive(Debug, thiserror::Error)]
 pub enum MigrationError {
     #[error("sqlx error")]
@@ -46,6 +48,20 @@ pub enum MigrationError {
         filename: String,
         error: sqlx::Error,
     },
+    #[error("migration `{filename}` has version {version} which is not greater than the version of the previous migration")]
+    Version { filename: String, version: i64 },
+    #[error("migrations `{first}` and `{second}` have the same version {version}")]
+    DuplicateVersion {
+        first: String,
+        second: String,
+        version: i64,
+    },
+    #[error(
+        "applied migration `{description}` (version {version}) does not match the migration script"
+    )]
+    ChecksumMismatch { version: i64, description: String },
+    #[error("applied migration `{description}` (version {version}) is unknown to this version of the backend")]
+    UnknownMigration { version: i64, description: String },
 }
 
 impl From<sqlx::Error> for MigrationError {
@@ -56,29 +72,51 @@ impl From<sqlx::Error> for MigrationError {
 
 /// Apply [`MigrationScript`]'s for a backend by applying the mount id and prefix.
 ///
+/// Only the migrations with a version greater than the latest applied version
+/// are run. The migrations that were already applied must match the given
+/// scripts, unless `verify_checksums` is `false`, as the storage of the mount
+/// was otherwise created by a different version of the backend.
+///
 /// # Errors
 ///
-/// Returns error if it fails to apply any of the migration scripts.
+/// Returns error if the applied migrations don't match the scripts or if it
+/// fails to apply any of the migration scripts.
 pub async fn migrate(
     pool: &EncryptedPool,
     migrations: &[MigrationScript],
     mount_id: &str,
     prefix: &str,
+    verify_checksums: bool,
 ) -> Result<(), MigrationError> {
+    for pair in migrations.windows(2) {
+        if pair[1].version <= pair[0].version {
+            return Err(MigrationError::Version {
+                filename: pair[1].description.clone(),
+                version: pair[1].version,
+            });
+        }
+    }
+
     create_migrate_table(pool).await?;
+    upgrade_legacy_versions(pool, migrations, mount_id).await?;
 
-    let latest_migration: Option<LatestMigration> = sqlx::query_as(&format!(
-        "SELECT MAX(version) AS latest_version FROM {BACKEND_MIGRATIONS_TABLE} 
-        WHERE mount_id = ?"
-    ))
-    .bind(mount_id)
-    .fetch_optional(pool)
-    .await?;
-    let last_migration_version = latest_migration.and_then(|m| m.latest_version);
+    let applied = list_migrations(pool, mount_id).await?;
+    for migration in &applied {
+        if let Err(error) = verify_migration(migrations, migration, prefix) {
+            if verify_checksums {
+                return Err(error);
+            }
+            tracing::warn!(?error, mount_id, "Ignoring mismatching migration");
+        }
+    }
+    let last_migration_version = applied.last().map(|migration| migration.version);
 
-    for (version, migration) in migrations.iter().enumerate() {
+    // All pending migrations are applied in a single transaction to never
+    // leave the backend storage half-upgraded.
+    let mut tx = pool.begin().await?;
+    for migration in migrations {
         if let Some(last_migration_version) = last_migration_version {
-            if last_migration_version >= version as i64 {
+            if last_migration_version >= migration.version {
                 continue;
             }
         }
@@ -87,8 +125,6 @@ pub async fn migrate(
             ScopedQuery::new(prefix, &migration.script).map_err(|_| MigrationError::BadQuery)?;
         let checksum = Sha384::digest(sql.sql().as_bytes()).to_vec();
 
-        let mut tx = pool.begin().await?;
-
         // Try to add new migration version for backend
         sqlx::query(&format!(
             "INSERT INTO {BACKEND_MIGRATIONS_TABLE} (
@@ -106,7 +142,7 @@ pub async fn migrate(
     )"
         ))
         .bind(mount_id)
-        .bind(version as i64)
+        .bind(migration.version)
         .bind(&migration.description)
         .bind(checksum)
         .bind(chrono::Utc::now())
@@ -115,14 +151,72 @@ pub async fn migrate(
         .map_err(|_| MigrationError::BadQuery)?;
 
         // Migration script
-        tx.execute(sql.sql()).await?;
-
-        tx.commit().await?;
+        tx.execute(sql.sql())
+            .await
+            .map_err(|error| MigrationError::Execution {
+                filename: migration.description.clone(),
+                error,
+            })?;
     }
+    tx.commit().await?;
 
     Ok(())
 }
 
+/// Migrations used to be versioned by their index in the sorted list of
+/// scripts. Renumber such applied migrations to the version of the script
+/// with the same file name.
+async fn upgrade_legacy_versions(
+    pool: &EncryptedPool,
+    migrations: &[MigrationScript],
+    mount_id: &str,
+) -> Result<(), MigrationError> {
+    for applied in list_migrations(pool, mount_id).await? {
+        let Some(script) = migrations
+            .iter()
+            .find(|migration| migration.description == applied.description)
+        else {
+            continue;
+        };
+        if script.version == applied.version {
+            continue;
+        }
+        sqlx::query(&format!(
+            "UPDATE {BACKEND_MIGRATIONS_TABLE} SET version = ? WHERE mount_id = ? AND version = ?"
+        ))
+        .bind(script.version)
+        .bind(mount_id)
+        .bind(applied.version)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Check that an applied migration matches the script with the same version.
+fn verify_migration(
+    migrations: &[MigrationScript],
+    applied: &Migration,
+    prefix: &str,
+) -> Result<(), MigrationError> {
+    let script = migrations
+        .iter()
+        .find(|migration| migration.version == applied.version)
+        .ok_or_else(|| MigrationError::UnknownMigration {
+            version: applied.version,
+            description: applied.description.clone(),
+        })?;
+    let sql = ScopedQuery::new(prefix, &script.script).map_err(|_| MigrationError::BadQuery)?;
+    if Sha384::digest(sql.sql().as_bytes()).as_slice() == applied.checksum.as_slice() {
+        Ok(())
+    } else {
+        Err(MigrationError::ChecksumMismatch {
+            version: applied.version,
+            description: applied.description.clone(),
+        })
+    }
+}
+
 /// Run migrations for a given backend.
 ///
 /// # Errors
@@ -151,29 +245,82 @@ pub async fn migrate_backend<M: rust_embed::RustEmbed>(
     Ok(())
 }
 
+/// Parse the version of a migration from the `YYYY-MM-DD-` date prefix of
+/// its file name.
+///
+/// # Errors
+///
+/// Returns error if the file name does not start with a date.
+pub fn migration_version(filename: &str) -> Result<i64, MigrationError> {
+    let parts = filename.splitn(4, '-').collect::<Vec<_>>();
+    if let [year, month, day, _name] = parts.as_slice() {
+        let date = format!("{year}{month}{day}");
+        if year.len() == 4
+            && month.len() == 2
+            && day.len() == 2
+            && date.bytes().all(|byte| byte.is_ascii_digit())
+        {
+            if let Ok(version) = date.parse() {
+                return Ok(version);
+            }
+        }
+    }
+    Err(MigrationError::Script {
+        filename: filename.to_string(),
+        error: "Migration file name must start with a `YYYY-MM-DD-` date".to_string(),
+    })
+}
+
+/// Create a [`MigrationScript`] versioned by the date prefix of its file name.
+///
+/// # Errors
+///
+/// Returns error if the file name does not start with a date.
+pub fn migration_script(filename: &str, script: String) -> Result<MigrationScript, MigrationError> {
+    Ok(MigrationScript {
+        version: migration_version(filename)?,
+        description: filename.to_string(),
+        script,
+    })
+}
+
+/// Sort migrations by version and check that no two migrations share a
+/// version.
+///
+/// # Errors
+///
+/// Returns error if two migrations have the same version.
+pub fn sort_migrations(migrations: &mut [MigrationScript]) -> Result<(), MigrationError> {
+    migrations.sort_by_key(|migration| migration.version);
+    for pair in migrations.windows(2) {
+        if pair[0].version == pair[1].version {
+            return Err(MigrationError::DuplicateVersion {
+                first: pair[0].description.clone(),
+                second: pair[1].description.clone(),
+                version: pair[1].version,
+            });
+        }
+    }
+    Ok(())
+}
+
 /// Retrieve [`MigrationScript`]'s from type that implements [`rust_embed::RustEmbed`].
 ///
 /// # Errors
 ///
 /// Returns error if it is unable to parse the contents of any of the migration
-/// script files.
+/// script files or if two of the scripts have the same version.
 pub fn migration_scripts<M: rust_embed::RustEmbed>() -> Result<Vec<MigrationScript>, MigrationError>
 {
-    let mut migrations = M::iter().collect::<Vec<_>>();
-    migrations.sort();
-
     let mut migration_scripts = vec![];
-    for migration_file_name in migrations {
+    for migration_file_name in M::iter() {
         if let Some(migration) = M::get(&migration_file_name) {
             let sql =
                 String::from_utf8(migration.data.to_vec()).map_err(|_| MigrationError::Script {
                     error: "Unable to parse migration script to UTF-8".to_string(),
                     filename: migration_file_name.to_string(),
                 })?;
-            migration_scripts.push(MigrationScript {
-                description: migration_file_name.to_string(),
-                script: sql,
-            });
+            migration_scripts.push(migration_script(&migration_file_name, sql)?);
         } else {
             return Err(MigrationError::Script {
                 filename: migration_file_name.to_string(),
@@ -181,13 +328,13 @@ pub fn migration_scripts<M: rust_embed::RustEmbed>() -> Result<Vec<MigrationScri
             });
         }
     }
+    sort_migrations(&mut migration_scripts)?;
 
     Ok(migration_scripts)
 }
 
 #[derive(Debug, sqlx::FromRow)]
 pub struct Migration {
-    pub mount_id: i64,
     pub version: i64,
     pub description: String,
     pub checksum: Vec<u8>,
@@ -204,7 +351,8 @@ pub async fn list_migrations(
     mount_id: &str,
 ) -> Result<Vec<Migration>, sqlx::Error> {
     sqlx::query_as(&format!(
-        "SELECT * FROM {BACKEND_MIGRATIONS_TABLE} WHERE mount_id = ?"
+        "SELECT version, description, checksum, created_at FROM {BACKEND_MIGRATIONS_TABLE}
+        WHERE mount_id = ? ORDER BY version"
     ))
     .bind(mount_id)
     .fetch_all(pool)
@@ -243,6 +391,7 @@ mod tests {
 
         let mut migrations = vec![
             MigrationScript {
+                version: 0,
                 description: "2022-12-12-init.sql".into(),
                 script: r#"
 CREATE TABLE IF NOT EXISTS SECRETS (
@@ -274,6 +423,7 @@ CREATE TABLE IF NOT EXISTS CONFIG (
                 .to_string(),
             },
             MigrationScript {
+                version: 1,
                 description: "2022-12-14-add-user.sql".into(),
                 script: r#"
 CREATE TABLE IF NOT EXISTS USERS (
@@ -284,7 +434,9 @@ CREATE TABLE IF NOT EXISTS USERS (
                 .to_string(),
             },
         ];
-        migrate(&pool, &migrations, mount_id, prefix).await.unwrap();
+        migrate(&pool, &migrations, mount_id, prefix, true)
+            .await
+            .unwrap();
 
         let res: Vec<Migration> =
             sqlx::query_as(&format!("SELECT * FROM {BACKEND_MIGRATIONS_TABLE}"))
@@ -294,6 +446,7 @@ CREATE TABLE IF NOT EXISTS USERS (
         assert_eq!(res.len(), 2);
 
         migrations.push(MigrationScript {
+            version: 2,
             description: "2022-12-16-add-user-email.sql".into(),
             script: r#"
 ALTER TABLE USERS 
@@ -301,7 +454,9 @@ ALTER TABLE USERS
             "#
             .to_string(),
         });
-        migrate(&pool, &migrations, mount_id, prefix).await.unwrap();
+        migrate(&pool, &migrations, mount_id, prefix, true)
+            .await
+            .unwrap();
 
         let res: Vec<Migration> =
             sqlx::query_as(&format!("SELECT * FROM {BACKEND_MIGRATIONS_TABLE}"))
@@ -311,7 +466,9 @@ ALTER TABLE USERS
         assert_eq!(res.len(), 3);
 
         // Run again and nothing should change
-        migrate(&pool, &migrations, mount_id, prefix).await.unwrap();
+        migrate(&pool, &migrations, mount_id, prefix, true)
+            .await
+            .unwrap();
         let res: Vec<Migration> = list_migrations(&pool, mount_id).await.unwrap();
         assert_eq!(res.len(), 3);
 
@@ -354,8 +511,223 @@ ALTER TABLE USERS
             .execute(&pool)
             .await
             .unwrap();
-        migrate(&pool, &migrations, mount_id, prefix).await.unwrap();
+        migrate(&pool, &migrations, mount_id, prefix, true)
+            .await
+            .unwrap();
         let res: Vec<Migration> = list_migrations(&pool, mount_id).await.unwrap();
         assert_eq!(res.len(), 3);
     }
+
+    #[tokio::test]
+    async fn failed_migration_is_rolled_back() {
+        let pool = EncryptedPool::new(&":memory:".to_string());
+        let master_key = pool.initialize().unwrap().unwrap();
+        pool.unseal(master_key).unwrap();
+
+        let mount_id = "12421412";
+        let prefix = "foo_bar_";
+
+        sqlx::query("CREATE TABLE MOUNTS ( id INTEGER PRIMARY KEY )")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO MOUNTS (id) VALUES (?)")
+            .bind(mount_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let migrations = vec![
+            MigrationScript {
+                version: 0,
+                description: "2022-12-12-init.sql".into(),
+                script: "CREATE TABLE USERS (uid INTEGER PRIMARY KEY)".to_string(),
+            },
+            MigrationScript {
+                version: 1,
+                description: "2022-12-14-add-email.sql".into(),
+                script: "ALTER TABLE NOT_EXISTING ADD email TEXT".to_string(),
+            },
+        ];
+        assert!(matches!(
+            migrate(&pool, &migrations, mount_id, prefix, true).await,
+            Err(MigrationError::Execution { filename, .. }) if filename == "2022-12-14-add-email.sql"
+        ));
+
+        // Neither the first migration or the migration versions are applied
+        assert!(list_migrations(&pool, mount_id).await.unwrap().is_empty());
+        let res: Vec<Tables> = sqlx::query_as(&format!(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name = '{prefix}USERS'"
+        ))
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(res.is_empty());
+    }
+
+    #[tokio::test]
+    async fn applied_migrations_must_match_scripts() {
+        let pool = EncryptedPool::new(&":memory:".to_string());
+        let master_key = pool.initialize().unwrap().unwrap();
+        pool.unseal(master_key).unwrap();
+
+        let mount_id = "12421412";
+        let prefix = "foo_bar_";
+
+        sqlx::query("CREATE TABLE MOUNTS ( id INTEGER PRIMARY KEY )")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO MOUNTS (id) VALUES (?)")
+            .bind(mount_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let migration = |version: i64, description: &str, script: &str| MigrationScript {
+            version,
+            description: description.to_string(),
+            script: script.to_string(),
+        };
+        let init = "CREATE TABLE USERS (uid INTEGER PRIMARY KEY)";
+        migrate(
+            &pool,
+            &[migration(0, "init.sql", init)],
+            mount_id,
+            prefix,
+            true,
+        )
+        .await
+        .unwrap();
+
+        // The applied migration was changed
+        let changed = [
+            migration(0, "init.sql", "CREATE TABLE USERS (id INTEGER PRIMARY KEY)"),
+            migration(1, "email.sql", "ALTER TABLE USERS ADD email TEXT"),
+        ];
+        assert!(matches!(
+            migrate(&pool, &changed, mount_id, prefix, true).await,
+            Err(MigrationError::ChecksumMismatch { version: 0, .. })
+        ));
+        assert_eq!(list_migrations(&pool, mount_id).await.unwrap().len(), 1);
+
+        // The applied migration is not known
+        let unknown = [migration(
+            1,
+            "email.sql",
+            "ALTER TABLE USERS ADD email TEXT",
+        )];
+        assert!(matches!(
+            migrate(&pool, &unknown, mount_id, prefix, true).await,
+            Err(MigrationError::UnknownMigration { version: 0, .. })
+        ));
+
+        // Versions must be increasing
+        let unordered = [
+            migration(1, "email.sql", "ALTER TABLE USERS ADD email TEXT"),
+            migration(0, "init.sql", init),
+        ];
+        assert!(matches!(
+            migrate(&pool, &unordered, mount_id, prefix, true).await,
+            Err(MigrationError::Version { version: 0, .. })
+        ));
+
+        // Pending migrations are applied when the verification is skipped
+        migrate(&pool, &changed, mount_id, prefix, false)
+            .await
+            .unwrap();
+        let applied = list_migrations(&pool, mount_id).await.unwrap();
+        assert_eq!(
+            applied
+                .iter()
+                .map(|migration| migration.version)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn versions_are_parsed_from_file_names() {
+        assert_eq!(
+            migration_version("2023-03-20-auth-lockout.sql").unwrap(),
+            20_230_320
+        );
+        assert!(migration_version("init.sql").is_err());
+        assert!(migration_version("2023-3-200-init.sql").is_err());
+        assert!(migration_version("2023-03-+1-init.sql").is_err());
+
+        let script = |filename: &str| migration_script(filename, String::new()).unwrap();
+        let mut migrations = vec![script("2023-04-05-b.sql"), script("2022-12-27-a.sql")];
+        sort_migrations(&mut migrations).unwrap();
+        assert_eq!(
+            migrations
+                .iter()
+                .map(|migration| migration.version)
+                .collect::<Vec<_>>(),
+            vec![20_221_227, 20_230_405]
+        );
+
+        migrations.push(script("2023-04-05-c.sql"));
+        assert!(matches!(
+            sort_migrations(&mut migrations),
+            Err(MigrationError::DuplicateVersion {
+                version: 20_230_405,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn legacy_versions_are_renumbered() {
+        let pool = EncryptedPool::new(&":memory:".to_string());
+        let master_key = pool.initialize().unwrap().unwrap();
+        pool.unseal(master_key).unwrap();
+
+        let mount_id = "12421412";
+        let prefix = "foo_bar_";
+
+        sqlx::query("CREATE TABLE MOUNTS ( id INTEGER PRIMARY KEY )")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO MOUNTS (id) VALUES (?)")
+            .bind(mount_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Applied when the version was the index of the script
+        let init = MigrationScript {
+            version: 0,
+            description: "2022-12-12-init.sql".into(),
+            script: "CREATE TABLE USERS (uid INTEGER PRIMARY KEY)".to_string(),
+        };
+        migrate(&pool, &[init], mount_id, prefix, true)
+            .await
+            .unwrap();
+
+        let migrations = [
+            migration_script(
+                "2022-12-12-init.sql",
+                "CREATE TABLE USERS (uid INTEGER PRIMARY KEY)".to_string(),
+            )
+            .unwrap(),
+            migration_script(
+                "2022-12-14-add-email.sql",
+                "ALTER TABLE USERS ADD email TEXT".to_string(),
+            )
+            .unwrap(),
+        ];
+        migrate(&pool, &migrations, mount_id, prefix, true)
+            .await
+            .unwrap();
+        let applied = list_migrations(&pool, mount_id).await.unwrap();
+        assert_eq!(
+            applied
+                .iter()
+                .map(|migration| migration.version)
+                .collect::<Vec<_>>(),
+            vec![20_221_212, 20_221_214]
+        );
+    }
 }
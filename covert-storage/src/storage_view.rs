@@ -0,0 +1,269 @@
+//! Key-value storage scoped to a single mount.
+//!
+//! A [`StorageView`] is created from the [`BackendStoragePool`] of a mount and
+//! handed to the backend handlers through an `Extension`. Every key is stored
+//! under the storage prefix of the mount, which already includes the
+//! namespace, so a backend cannot read or write the data of another mount.
+//! All built-in engines must use the view and keep their config under a key
+//! of it. Data with its own schema, like the versions of KV secrets, may stay
+//! in tables of the scoped [`BackendStoragePool`]. No engine goes through the
+//! raw [`crate::EncryptedPool`].
+
+use std::sync::Arc;
+
+use tokio::sync::OnceCell;
+
+use crate::BackendStoragePool;
+
+const STORAGE_VIEW_TABLE: &str = "STORAGE_VIEW";
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageViewError {
+    #[error("Invalid storage key `{key}`: {reason}")]
+    InvalidKey { key: String, reason: &'static str },
+    #[error(transparent)]
+    Storage(#[from] sqlx::Error),
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct Entry {
+    value: Vec<u8>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct Key {
+    key: String,
+}
+
+/// Key-value storage scoped to the storage prefix of a mount.
+#[derive(Debug, Clone)]
+pub struct StorageView {
+    storage: BackendStoragePool,
+    initialized: Arc<OnceCell<()>>,
+}
+
+impl StorageView {
+    #[must_use]
+    pub fn new(storage: BackendStoragePool) -> Self {
+        Self {
+            storage,
+            initialized: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// The storage prefix all keys are scoped to.
+    #[must_use]
+    pub fn prefix(&self) -> &str {
+        self.storage.prefix()
+    }
+
+    async fn init(&self) -> Result<(), StorageViewError> {
+        self.initialized
+            .get_or_try_init(|| async {
+                self.storage
+                    .query(&format!(
+                        "CREATE TABLE IF NOT EXISTS {STORAGE_VIEW_TABLE} (
+                            key TEXT PRIMARY KEY,
+                            value BLOB NOT NULL
+                        )"
+                    ))?
+                    .execute()
+                    .await?;
+                Ok::<_, StorageViewError>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Get the value stored under `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the key is invalid or the storage cannot be read.
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageViewError> {
+        validate_key(key)?;
+        self.init().await?;
+
+        let entry: Option<Entry> = self
+            .storage
+            .query(&format!(
+                "SELECT value FROM {STORAGE_VIEW_TABLE} WHERE key = ?"
+            ))?
+            .bind(key)
+            .fetch_optional()
+            .await?;
+        Ok(entry.map(|entry| entry.value))
+    }
+
+    /// Store `value` under `key`, replacing any existing value.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the key is invalid or the storage cannot be written.
+    pub async fn put(&self, key: &str, value: Vec<u8>) -> Result<(), StorageViewError> {
+        validate_key(key)?;
+        self.init().await?;
+
+        self.storage
+            .query(&format!(
+                "INSERT OR REPLACE INTO {STORAGE_VIEW_TABLE} (key, value) VALUES (?, ?)"
+            ))?
+            .bind(key)
+            .bind(value)
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the value stored under `key`. Returns `true` if a value was
+    /// removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the key is invalid or the storage cannot be written.
+    pub async fn delete(&self, key: &str) -> Result<bool, StorageViewError> {
+        validate_key(key)?;
+        self.init().await?;
+
+        let res = self
+            .storage
+            .query(&format!("DELETE FROM {STORAGE_VIEW_TABLE} WHERE key = ?"))?
+            .bind(key)
+            .execute()
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// List the keys directly under `prefix`. Keys that have children are
+    /// returned once with a trailing `/`. An empty prefix lists the root.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the prefix is invalid or the storage cannot be read.
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageViewError> {
+        let prefix = match prefix.trim_end_matches('/') {
+            "" => String::new(),
+            folder => {
+                validate_key(folder)?;
+                format!("{folder}/")
+            }
+        };
+        self.init().await?;
+
+        let keys: Vec<Key> = self
+            .storage
+            .query(&format!(
+                "SELECT key FROM {STORAGE_VIEW_TABLE} WHERE substr(key, 1, ?) = ? ORDER BY key"
+            ))?
+            .bind(i64::try_from(prefix.chars().count()).unwrap_or(i64::MAX))
+            .bind(prefix.clone())
+            .fetch_all()
+            .await?;
+
+        let mut children: Vec<String> = vec![];
+        for Key { key } in keys {
+            let child = match key[prefix.len()..].split_once('/') {
+                Some((folder, _)) => format!("{folder}/"),
+                None => key[prefix.len()..].to_string(),
+            };
+            if children.last() != Some(&child) {
+                children.push(child);
+            }
+        }
+        Ok(children)
+    }
+}
+
+/// Keys are `/` separated paths relative to the mount. Absolute paths, empty
+/// segments and `.` or `..` segments are rejected so a key can never be
+/// interpreted as pointing outside of the view.
+fn validate_key(key: &str) -> Result<(), StorageViewError> {
+    let invalid = |reason| StorageViewError::InvalidKey {
+        key: key.to_string(),
+        reason,
+    };
+
+    if key.is_empty() {
+        return Err(invalid("key is empty"));
+    }
+    if key.contains('\0') {
+        return Err(invalid("key contains a NUL character"));
+    }
+    for segment in key.split('/') {
+        match segment {
+            "" => return Err(invalid("key contains an empty segment")),
+            "." | ".." => return Err(invalid("key contains a relative segment")),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::EncryptedPool;
+
+    use super::*;
+
+    fn view(pool: &Arc<EncryptedPool>, prefix: &str) -> StorageView {
+        StorageView::new(BackendStoragePool::new(prefix, Arc::clone(pool)))
+    }
+
+    #[tokio::test]
+    async fn crud() {
+        let pool = EncryptedPool::new(&":memory:".to_string());
+        let master_key = pool.initialize().unwrap().unwrap();
+        pool.unseal(master_key).unwrap();
+        let pool = Arc::new(pool);
+
+        let foo = view(&pool, "foo_");
+        let bar = view(&pool, "bar_");
+
+        foo.put("config", b"foo".to_vec()).await.unwrap();
+        foo.put("roles/a", b"a".to_vec()).await.unwrap();
+        foo.put("roles/b/c", b"c".to_vec()).await.unwrap();
+        bar.put("config", b"bar".to_vec()).await.unwrap();
+
+        assert_eq!(foo.get("config").await.unwrap(), Some(b"foo".to_vec()));
+        assert_eq!(bar.get("config").await.unwrap(), Some(b"bar".to_vec()));
+        assert_eq!(bar.get("roles/a").await.unwrap(), None);
+
+        foo.put("config", b"baz".to_vec()).await.unwrap();
+        assert_eq!(foo.get("config").await.unwrap(), Some(b"baz".to_vec()));
+
+        assert_eq!(foo.list("").await.unwrap(), vec!["config", "roles/"]);
+        assert_eq!(foo.list("roles/").await.unwrap(), vec!["a", "b/"]);
+        assert_eq!(foo.list("roles").await.unwrap(), vec!["a", "b/"]);
+        assert!(foo.list("other/").await.unwrap().is_empty());
+
+        assert!(foo.delete("roles/a").await.unwrap());
+        assert!(!foo.delete("roles/a").await.unwrap());
+        assert_eq!(foo.list("roles/").await.unwrap(), vec!["b/"]);
+        assert_eq!(bar.list("").await.unwrap(), vec!["config"]);
+    }
+
+    #[tokio::test]
+    async fn rejects_keys_escaping_the_view() {
+        let pool = EncryptedPool::new(&":memory:".to_string());
+        let master_key = pool.initialize().unwrap().unwrap();
+        pool.unseal(master_key).unwrap();
+        let foo = view(&Arc::new(pool), "foo_");
+
+        for key in [
+            "",
+            "/config",
+            "../bar/config",
+            "roles/../config",
+            "roles//a",
+        ] {
+            assert!(
+                matches!(
+                    foo.put(key, vec![]).await,
+                    Err(StorageViewError::InvalidKey { .. })
+                ),
+                "{key}"
+            );
+        }
+        assert!(foo.list("../").await.is_err());
+    }
+}
@@ -3,6 +3,9 @@ pub enum AuthPolicy {
     /// Authorized to access the requested path with operation as long
     /// as the given `Route` does not require `Root` level privilege.
     Authenticated,
+    /// Authorized to access the requested path with operation and the policy
+    /// also grants the `sudo` capability on the path.
+    Sudo,
     /// Anyone without a token.
     Unauthenticated,
 }
@@ -1,16 +1,28 @@
+use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use strum::{Display, EnumString};
 
+use crate::request::Operation;
+
 #[derive(
     Debug, Copy, Clone, PartialEq, EnumString, Display, SerializeDisplay, DeserializeFromStr, Eq,
 )]
 pub enum BackendType {
     #[strum(ascii_case_insensitive, serialize = "kv")]
     Kv,
+    #[strum(ascii_case_insensitive, serialize = "ldap")]
+    Ldap,
+    #[strum(ascii_case_insensitive, serialize = "oidc")]
+    Oidc,
+    /// Backend served by an external plugin process.
+    #[strum(ascii_case_insensitive, serialize = "plugin")]
+    Plugin,
     #[strum(ascii_case_insensitive, serialize = "psql")]
     Postgres,
     #[strum(ascii_case_insensitive, serialize = "system")]
     System,
+    #[strum(ascii_case_insensitive, serialize = "transit")]
+    Transit,
     #[strum(ascii_case_insensitive, serialize = "userpass")]
     Userpass,
 }
@@ -28,10 +40,25 @@ pub enum BackendCategory {
 impl From<BackendType> for BackendCategory {
     fn from(value: BackendType) -> Self {
         match value {
-            BackendType::Kv | BackendType::Postgres | BackendType::System => {
-                BackendCategory::Logical
+            BackendType::Kv
+            | BackendType::Plugin
+            | BackendType::Postgres
+            | BackendType::System
+            | BackendType::Transit => BackendCategory::Logical,
+            BackendType::Ldap | BackendType::Oidc | BackendType::Userpass => {
+                BackendCategory::Credential
             }
-            BackendType::Userpass => BackendCategory::Credential,
         }
     }
 }
+
+/// Description of a route served by a backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteHelp {
+    /// Route pattern relative to the mount, e.g. `/data/*path`.
+    pub path: String,
+    /// Operations supported by the route.
+    pub operations: Vec<Operation>,
+    /// Help text provided when the route was registered.
+    pub help: Option<String>,
+}
@@ -1,19 +1,111 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
-use http::header::CONTENT_TYPE;
-use serde::Serialize;
+use http::header::{CONTENT_TYPE, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use thiserror::Error;
 
 pub use http::StatusCode;
 use tracing_error::SpanTrace;
 
-use crate::state::StorageState;
+use crate::{request::Operation, state::StorageState};
+
+/// Stable machine-readable error code returned with every error response.
+///
+/// Clients should match on the code instead of the error message, the message
+/// is meant for humans and can change between releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    PermissionDenied,
+    NotFound,
+    MethodNotAllowed,
+    Timeout,
+    Conflict,
+    CasConflict,
+    PreconditionFailed,
+    /// The request body is larger than the limit of the path.
+    PayloadTooLarge,
+    /// The operation is not allowed in the current storage state, e.g. while
+    /// sealed.
+    SealedState,
+    LeaseNotRenewable,
+    /// The wrapping token expired before it was unwrapped.
+    WrappingTokenExpired,
+    /// The wrapping token was already unwrapped, maybe by someone who
+    /// intercepted it.
+    WrappingTokenUnwrapped,
+    /// Too many failed login attempts.
+    LockedOut,
+    RateLimited,
+    /// The server or the mount is handling as many requests as it is allowed
+    /// to, the request can be retried later.
+    ServerBusy,
+    /// A remote system the backend depends on failed.
+    UpstreamError,
+    Internal,
+    /// Code not known to this version of the client.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    /// The HTTP status code usually returned with the error code.
+    #[must_use]
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest
+            | ErrorCode::LeaseNotRenewable
+            | ErrorCode::WrappingTokenExpired
+            | ErrorCode::WrappingTokenUnwrapped => StatusCode::BAD_REQUEST,
+            ErrorCode::PermissionDenied | ErrorCode::SealedState | ErrorCode::LockedOut => {
+                StatusCode::FORBIDDEN
+            }
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::Timeout => StatusCode::REQUEST_TIMEOUT,
+            ErrorCode::Conflict | ErrorCode::CasConflict => StatusCode::CONFLICT,
+            ErrorCode::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::UpstreamError => StatusCode::BAD_GATEWAY,
+            ErrorCode::ServerBusy => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Internal | ErrorCode::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<StatusCode> for ErrorCode {
+    fn from(status_code: StatusCode) -> Self {
+        match status_code {
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => ErrorCode::BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorCode::PermissionDenied,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+            StatusCode::REQUEST_TIMEOUT => ErrorCode::Timeout,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::PRECONDITION_FAILED => ErrorCode::PreconditionFailed,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+            StatusCode::BAD_GATEWAY => ErrorCode::UpstreamError,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Error tied to a single field of the request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
 
 /// A shares errod type used to produce public error and add additional context
 /// for internal diagnostics. A public error will be produced by using the inner
-/// error [`Display`] implementation and `status_code` field. The internal error
-/// report will be created used the [`Debug`] implementation and `span_trace` field.
+/// error [`Display`] implementation, `code`, `details` and `status_code` field.
+/// The internal error report will be created used the [`Debug`] implementation
+/// and `span_trace` field.
 #[serde_as]
 #[derive(Error, Debug, Serialize)]
 pub struct ApiError {
@@ -21,13 +113,39 @@ pub struct ApiError {
     #[serde_as(as = "DisplayFromStr")]
     #[source]
     pub error: anyhow::Error,
+    pub code: ErrorCode,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
     #[serde(skip)]
     pub status_code: StatusCode,
+    /// How long the client should wait before retrying, sent as whole seconds
+    /// both in the body and in the `Retry-After` header.
+    #[serde(
+        rename = "retry_after_secs",
+        serialize_with = "serialize_retry_after",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retry_after: Option<Duration>,
     // TODO: make it non-optional
     #[serde(skip)]
     pub span_trace: Option<SpanTrace>,
 }
 
+#[allow(clippy::ref_option)]
+fn serialize_retry_after<S: serde::Serializer>(
+    retry_after: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(retry_after.map_or(0, retry_after_secs))
+}
+
+/// Whole seconds to wait, rounded up so the client never retries too early.
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after
+        .as_secs()
+        .saturating_add(u64::from(retry_after.subsec_nanos() > 0))
+}
+
 impl Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let report = self.report();
@@ -45,11 +163,37 @@ pub struct Report {
 }
 
 impl ApiError {
+    /// Create an error with the usual status code of the error code.
+    #[must_use]
+    pub fn new(code: ErrorCode, error: anyhow::Error) -> Self {
+        Self {
+            error,
+            code,
+            details: vec![],
+            status_code: code.status_code(),
+            retry_after: None,
+            span_trace: Some(SpanTrace::capture()),
+        }
+    }
+
+    /// Attach an error for a single field of the request.
+    #[must_use]
+    pub fn with_detail(mut self, field: impl Into<String>, message: impl Into<String>) -> Self {
+        self.details.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+        self
+    }
+
     #[must_use]
     pub fn bad_request() -> Self {
         Self {
             error: anyhow::Error::msg("Bad request"),
+            code: ErrorCode::BadRequest,
+            details: vec![],
             status_code: StatusCode::BAD_REQUEST,
+            retry_after: None,
             span_trace: Some(SpanTrace::capture()),
         }
     }
@@ -58,7 +202,10 @@ impl ApiError {
     pub fn internal_error() -> Self {
         Self {
             error: anyhow::Error::msg("Internal error"),
+            code: ErrorCode::Internal,
+            details: vec![],
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            retry_after: None,
             span_trace: Some(SpanTrace::capture()),
         }
     }
@@ -67,7 +214,10 @@ impl ApiError {
     pub fn timeout() -> Self {
         Self {
             error: anyhow::Error::msg("Request timed out"),
+            code: ErrorCode::Timeout,
+            details: vec![],
             status_code: StatusCode::REQUEST_TIMEOUT,
+            retry_after: None,
             span_trace: Some(SpanTrace::capture()),
         }
     }
@@ -78,7 +228,10 @@ impl ApiError {
             error: anyhow::Error::msg(format!(
                 "This operation is not allowed when the current state is `{current_state}`"
             )),
+            code: ErrorCode::SealedState,
+            details: vec![],
             status_code: StatusCode::FORBIDDEN,
+            retry_after: None,
             span_trace: Some(SpanTrace::capture()),
         }
     }
@@ -87,7 +240,10 @@ impl ApiError {
     pub fn unauthorized() -> Self {
         Self {
             error: anyhow::Error::msg("User is not authorized to perform this operation"),
+            code: ErrorCode::PermissionDenied,
+            details: vec![],
             status_code: StatusCode::UNAUTHORIZED,
+            retry_after: None,
             span_trace: Some(SpanTrace::capture()),
         }
     }
@@ -96,11 +252,66 @@ impl ApiError {
     pub fn not_found() -> Self {
         Self {
             error: anyhow::Error::msg("Not found"),
+            code: ErrorCode::NotFound,
+            details: vec![],
             status_code: StatusCode::NOT_FOUND,
+            retry_after: None,
             span_trace: Some(SpanTrace::capture()),
         }
     }
 
+    #[must_use]
+    pub fn method_not_allowed(operation: Operation, supported: &[Operation]) -> Self {
+        let supported = supported
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self {
+            error: anyhow::Error::msg(format!(
+                "Operation `{operation}` is not supported on this path. Supported operations: {supported}"
+            )),
+            code: ErrorCode::MethodNotAllowed,
+            details: vec![],
+            status_code: StatusCode::METHOD_NOT_ALLOWED,
+            retry_after: None,
+            span_trace: Some(SpanTrace::capture()),
+        }
+    }
+
+    /// Too many requests, the client can retry after `retry_after`.
+    #[must_use]
+    pub fn rate_limited(error: anyhow::Error, retry_after: Duration) -> Self {
+        Self {
+            retry_after: Some(retry_after),
+            ..Self::new(ErrorCode::RateLimited, error)
+        }
+    }
+
+    /// Too many requests are in flight, the request is refused instead of
+    /// waiting for one of them to complete.
+    #[must_use]
+    pub fn server_busy() -> Self {
+        Self {
+            retry_after: Some(Duration::from_secs(1)),
+            ..Self::new(
+                ErrorCode::ServerBusy,
+                anyhow::Error::msg("Server busy, too many concurrent requests"),
+            )
+        }
+    }
+
+    /// The request body is larger than `limit` bytes.
+    #[must_use]
+    pub fn payload_too_large(limit: usize) -> Self {
+        Self::new(
+            ErrorCode::PayloadTooLarge,
+            anyhow::Error::msg(format!(
+                "Request body is larger than the limit of {limit} bytes"
+            )),
+        )
+    }
+
     #[must_use]
     pub fn report(&self) -> Report {
         Report {
@@ -113,11 +324,15 @@ impl ApiError {
 impl From<ApiError> for hyper::Response<hyper::Body> {
     fn from(err: ApiError) -> Self {
         match serde_json::to_vec(&err) {
-            Ok(err_body) => hyper::Response::builder()
-                .header(CONTENT_TYPE, "application/json")
-                .status(err.status_code)
-                .body(err_body.into())
-                .expect("a valid response"),
+            Ok(err_body) => {
+                let mut builder = hyper::Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .status(err.status_code);
+                if let Some(retry_after) = err.retry_after {
+                    builder = builder.header(RETRY_AFTER, retry_after_secs(retry_after));
+                }
+                builder.body(err_body.into()).expect("a valid response")
+            }
             Err(_) => hyper::Response::builder()
                 .header(CONTENT_TYPE, "application/json")
                 .status(StatusCode::INTERNAL_SERVER_ERROR)
@@ -153,13 +368,19 @@ mod tests {
         };
         let api_err = ApiError {
             error: err.into(),
+            code: ErrorCode::Internal,
+            details: vec![],
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            retry_after: None,
             span_trace: None,
         };
 
         // Check serialized error response
         let api_err_serialized = serde_json::to_string(&api_err).unwrap();
-        assert_eq!(api_err_serialized, r#"{"error":"display error"}"#);
+        assert_eq!(
+            api_err_serialized,
+            r#"{"error":"display error","code":"internal"}"#
+        );
 
         // The error report should use the Debug impl of the root cause
         let err_report = format!("{:?}", api_err.report());
@@ -168,4 +389,45 @@ mod tests {
             r#"Report { cause: "DummyError { debug_field: \"debug error\", display_field: \"display error\" }", span_trace: None }"#
         );
     }
+
+    #[test]
+    fn rate_limited_error_has_retry_after() {
+        let api_err = ApiError::rate_limited(
+            anyhow::Error::msg("Rate limit exceeded"),
+            Duration::from_millis(1500),
+        );
+        assert_eq!(api_err.status_code, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            serde_json::to_string(&api_err).unwrap(),
+            r#"{"error":"Rate limit exceeded","code":"rate_limited","retry_after_secs":2}"#
+        );
+
+        let resp = hyper::Response::from(api_err);
+        assert_eq!(resp.headers()[RETRY_AFTER], "2");
+    }
+
+    #[test]
+    fn serialize_error_code_and_details() {
+        let api_err = ApiError::new(ErrorCode::BadRequest, anyhow::Error::msg("Invalid TTL"))
+            .with_detail("ttl", "must be positive");
+        assert_eq!(api_err.status_code, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::to_value(&api_err).unwrap(),
+            serde_json::json!({
+                "error": "Invalid TTL",
+                "code": "bad_request",
+                "details": [{ "field": "ttl", "message": "must be positive" }]
+            })
+        );
+
+        // Unknown codes from newer servers do not fail deserialization
+        assert_eq!(
+            serde_json::from_str::<ErrorCode>(r#""lease_not_renewable""#).unwrap(),
+            ErrorCode::LeaseNotRenewable
+        );
+        assert_eq!(
+            serde_json::from_str::<ErrorCode>(r#""something_new""#).unwrap(),
+            ErrorCode::Unknown
+        );
+    }
 }
@@ -6,16 +6,60 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SetConfigParams {
     pub max_versions: u32,
+    /// Secret scanning of writes. Left unchanged if unset.
+    #[serde(default)]
+    pub secret_scan: Option<SecretScanConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SetConfigResponse {
     pub max_versions: u32,
+    pub secret_scan: SecretScanConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ReadConfigResponse {
     pub max_versions: u32,
+    pub secret_scan: SecretScanConfig,
+}
+
+/// Checks run on the values of a secret before a new version is written.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SecretScanConfig {
+    #[serde(default)]
+    pub mode: SecretScanMode,
+    /// Additional patterns that are detected together with the built-in
+    /// detectors.
+    #[serde(default)]
+    pub patterns: Vec<SecretScanPattern>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretScanMode {
+    #[default]
+    Off,
+    /// Write the secret and return the findings as warnings.
+    Warn,
+    /// Reject writes with findings.
+    Block,
+}
+
+/// Detects words starting with `prefix`, e.g. the API keys of a vendor.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SecretScanPattern {
+    pub name: String,
+    pub prefix: String,
+}
+
+/// A value that looks like something that should not be stored in the
+/// secret. The value itself is never included.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SecretScanFinding {
+    /// Key of the secret data the value was found in.
+    pub field: String,
+    /// Name of the detector that matched.
+    pub detector: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
@@ -23,6 +67,13 @@ pub struct CreateSecretParams {
     pub data: HashMap<String, String>,
 }
 
+/// Fields merged into the latest version of the secret to create a new
+/// version. Fields set to `null` are removed.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PatchSecretParams {
+    pub data: HashMap<String, Option<String>>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateSecretResponse {
     pub version: u32,
@@ -31,6 +82,9 @@ pub struct CreateSecretResponse {
     pub created_time: DateTime<Utc>,
     pub deleted: bool,
     pub destroyed: bool,
+    /// Findings of the secret scan when the mount only warns about them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<SecretScanFinding>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -74,3 +128,10 @@ pub struct RecoverSecretParams {
 pub struct RecoverSecretResponse {
     pub not_recovered: Vec<u32>,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListSecretsResponse {
+    /// Keys directly below the prefix, sorted. Keys ending with `/` have
+    /// more keys below them.
+    pub keys: Vec<String>,
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+fn default_user_attr() -> String {
+    "uid".to_string()
+}
+
+fn default_group_filter() -> String {
+    "(|(member={{user_dn}})(uniqueMember={{user_dn}})(memberUid={{username}}))".to_string()
+}
+
+fn default_group_attr() -> String {
+    "cn".to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SetConfigParams {
+    /// LDAP server URL, e.g. `ldaps://ldap.example.com`
+    pub url: String,
+    /// DN used to search for the user DN. If not set the user DN is built
+    /// from `user_attr` and `user_dn`.
+    #[serde(default)]
+    pub bind_dn: Option<String>,
+    #[serde(default)]
+    pub bind_password: Option<String>,
+    /// Base DN under which users are located
+    pub user_dn: String,
+    /// Attribute on the user entry matching the username
+    #[serde(default = "default_user_attr")]
+    pub user_attr: String,
+    /// Base DN under which to search for groups. Group membership is only
+    /// resolved if this is set.
+    #[serde(default)]
+    pub group_dn: Option<String>,
+    /// Filter used to find the groups of the user. `{{username}}` and
+    /// `{{user_dn}}` are replaced with the escaped username and user DN.
+    #[serde(default = "default_group_filter")]
+    pub group_filter: String,
+    /// Attribute on the group entry containing the group name
+    #[serde(default = "default_group_attr")]
+    pub group_attr: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ConfigResponse {
+    pub url: String,
+    pub bind_dn: Option<String>,
+    pub user_dn: String,
+    pub user_attr: String,
+    pub group_dn: Option<String>,
+    pub group_filter: String,
+    pub group_attr: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LoginParams {
+    pub password: String,
+    /// Issue a token that can be renewed. Defaults to true.
+    #[serde(default = "default_as_true")]
+    pub renewable: bool,
+}
+
+fn default_as_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SetGroupParams {
+    pub policies: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct GroupResponse {
+    pub name: String,
+    pub policies: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListGroupsResponse {
+    pub groups: Vec<GroupResponse>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RemoveGroupResponse {
+    pub name: String,
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutConfig {
+    /// Number of failed logins within `window` before the alias is locked.
+    /// Setting this to zero disables the lockout.
+    pub attempts: u32,
+    /// Duration failed logins are counted for
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// Duration the alias is locked for
+    #[serde(with = "humantime_serde")]
+    pub lockout_duration: Duration,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            window: Duration::from_secs(60 * 15),
+            lockout_duration: Duration::from_secs(60 * 15),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct LockedAliasItem {
+    pub alias: String,
+    pub failed_attempts: u32,
+    pub locked_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListLockedAliasesResponse {
+    pub aliases: Vec<LockedAliasItem>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UnlockAliasResponse {
+    pub alias: String,
+}
@@ -1,28 +1,71 @@
 pub mod kv;
+pub mod ldap;
+pub mod lockout;
+pub mod oidc;
 pub mod psql;
 pub mod system;
+pub mod transit;
 pub mod userpass;
 
 use std::time::Duration;
 
 use serde::{self, Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::token::Token;
 
+/// Response of every engine issuing a secret with a lease. The lease fields
+/// are the same as in the [`AuthResponse`] so clients can renew and revoke
+/// any lease without knowing the engine that issued it.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SecretLeaseResponse<T> {
-    pub data: T,
     pub lease_id: String,
-    #[serde(with = "humantime_serde")]
-    pub ttl: std::time::Duration,
+    /// Time left until the lease expires.
+    #[serde(with = "humantime_serde", alias = "ttl")]
+    pub lease_duration: std::time::Duration,
+    /// Whether the lease can be renewed with `sys/leases/renew`.
+    pub renewable: bool,
+    pub data: T,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub token: Token,
+    /// Identifies the token to look it up or revoke it without the token.
+    #[serde(default)]
+    pub accessor: String,
     pub lease_id: String,
+    /// Time left until the lease, and with it the token, expires.
+    #[serde(with = "humantime_serde", alias = "ttl")]
+    pub lease_duration: std::time::Duration,
+    /// Whether the lease of the token can be renewed.
+    pub renewable: bool,
+}
+
+/// Returned instead of an [`AuthResponse`] by a login through an auth mount
+/// that requires MFA. The token is issued once the login is completed with
+/// the second factor at `sys/mfa/validate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MfaRequirementResponse {
+    pub mfa_requirement: MfaRequirement,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MfaRequirement {
+    /// Identifies the login when it is validated. It can only be used once.
+    pub request_id: Uuid,
+    /// The methods any of which completes the login.
+    pub methods: Vec<MfaMethod>,
+    /// Time left to complete the login.
     #[serde(with = "humantime_serde")]
-    pub ttl: std::time::Duration,
+    pub ttl: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MfaMethod {
+    /// A TOTP code generated with the key of the entity.
+    Totp,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+fn default_scopes() -> Vec<String> {
+    vec!["profile".to_string(), "email".to_string()]
+}
+
+fn default_user_claim() -> String {
+    "sub".to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SetConfigParams {
+    /// Issuer URL of the provider, e.g. `https://accounts.example.com`. The
+    /// provider endpoints are discovered from
+    /// `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Redirect URIs the provider may send the user back to after login.
+    pub allowed_redirect_uris: Vec<String>,
+    /// Scopes requested in addition to `openid`
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// Claim of the ID token used as the alias of the entity
+    #[serde(default = "default_user_claim")]
+    pub user_claim: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ConfigResponse {
+    pub issuer: String,
+    pub client_id: String,
+    pub allowed_redirect_uris: Vec<String>,
+    pub scopes: Vec<String>,
+    pub user_claim: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuthUrlParams {
+    /// Where the provider sends the user back to. Must be one of the allowed
+    /// redirect URIs.
+    pub redirect_uri: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuthUrlResponse {
+    /// Provider URL to send the user to for login.
+    pub auth_url: String,
+    /// Returned by the provider with the code and passed on to the callback.
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CallbackParams {
+    pub state: String,
+    /// Authorization code returned by the provider.
+    pub code: String,
+    /// Issue a token that can be renewed. Defaults to true.
+    #[serde(default = "default_as_true")]
+    pub renewable: bool,
+}
+
+fn default_as_true() -> bool {
+    true
+}
@@ -28,9 +28,15 @@ pub struct ReadConnectionResponse {
     pub connection: Option<ConnectionConfig>,
 }
 
+/// The new password of the user the engine connects with.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RotateRootResponse {
+    pub password: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateRoleCredsParams {
-    #[serde(with = "humantime_serde")]
+    #[serde(default, with = "humantime_serde")]
     pub ttl: Option<Duration>,
 }
 
@@ -40,12 +46,18 @@ pub type CreateRoleCredsResponse = SecretLeaseResponse<RoleCredentials>;
 pub struct CreateRoleParams {
     pub sql: String,
     pub revocation_sql: String,
+    /// Max TTL of the credentials generated for the role. The max lease TTL
+    /// of the mount applies if it is lower.
+    #[serde(default, with = "humantime_serde")]
+    pub max_ttl: Option<Duration>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateRoleResponse {
     pub sql: String,
     pub revocation_sql: String,
+    #[serde(with = "humantime_serde")]
+    pub max_ttl: Option<Duration>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
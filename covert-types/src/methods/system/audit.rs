@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::request::Operation;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditDeviceConfig {
+    /// Append the audit entries to a local file.
+    File(FileAuditConfig),
+    /// Send the audit entries to a socket, e.g. of a log collector.
+    Socket(SocketAuditConfig),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileAuditConfig {
+    pub file_path: String,
+    #[serde(default)]
+    pub format: AuditFormat,
+    /// Permissions of the file as an octal string, e.g. `"0600"`. Defaults
+    /// to `"0600"` for new files.
+    #[serde(default)]
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SocketAuditConfig {
+    /// `host:port` of TCP and UDP sockets or the path of a unix datagram
+    /// socket.
+    pub address: String,
+    #[serde(default)]
+    pub socket_type: SocketType,
+    #[serde(default)]
+    pub format: AuditFormat,
+    /// How long writing an entry may take before it fails. Defaults to 2
+    /// seconds.
+    #[serde(default, with = "humantime_serde")]
+    pub write_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SocketType {
+    /// Newline delimited entries over a TCP connection.
+    #[default]
+    Tcp,
+    /// One entry per UDP datagram.
+    Udp,
+    /// One entry per datagram on a unix socket.
+    Unixgram,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditFormat {
+    /// One compact JSON object per line.
+    #[default]
+    Jsonl,
+    /// Pretty printed JSON objects.
+    Json,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnableAuditDeviceParams {
+    #[serde(flatten)]
+    pub config: AuditDeviceConfig,
+    /// Top level response fields that are not sensitive and are written
+    /// without being hashed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hmac_exempt_response_fields: Vec<String>,
+    /// Rules picking the requests written by the device. The first rule
+    /// matching a request applies, requests matching no rule are written.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filter: Vec<AuditFilterRule>,
+}
+
+/// Which of the requests matching the path prefix and operations are written.
+/// Denied requests and requests to paths requiring `sudo` are written
+/// whatever the rules say.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditFilterRule {
+    /// Prefix of the path of the request, without the namespace. Matches
+    /// every path when empty.
+    #[serde(default)]
+    pub path_prefix: String,
+    /// Matches every operation when empty.
+    #[serde(default)]
+    pub operations: Vec<Operation>,
+    /// Write one in every `sample_every` requests, `1` writes all of them and
+    /// `0` none.
+    #[serde(default = "default_sample_every")]
+    pub sample_every: u32,
+}
+
+fn default_sample_every() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditDevice {
+    pub path: String,
+    #[serde(flatten)]
+    pub config: AuditDeviceConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hmac_exempt_response_fields: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filter: Vec<AuditFilterRule>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListAuditDevicesResponse {
+    pub devices: Vec<AuditDevice>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadAuditDeviceResponse {
+    #[serde(flatten)]
+    pub device: AuditDevice,
+    /// Number of entries written by the device since it was enabled or the
+    /// server was unsealed.
+    pub entries_written: u64,
+    /// The last failed write.
+    pub last_error: Option<AuditDeviceError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditDeviceError {
+    pub message: String,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DisableAuditDeviceResponse {
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditHashParams {
+    pub input: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditHashResponse {
+    /// The input hashed the same way as the sensitive values written by the
+    /// device.
+    pub hash: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditTailParams {
+    /// Epoch of the interrupted stream to resume, see [`AuditTailEvent::Start`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<Uuid>,
+    /// Sequence number of the last entry received from the interrupted
+    /// stream. The stream resumes with the entry after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    /// Number of entries written before the request to start with when not
+    /// resuming a stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<u64>,
+}
+
+/// Line of the newline delimited JSON stream of the entries written by an
+/// audit device.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditTailEvent {
+    /// First line of every stream. The sequence numbers of the entries are
+    /// only comparable within an epoch, a device gets a new epoch whenever it
+    /// is opened.
+    Start { epoch: Uuid },
+    /// An entry written by the device.
+    Entry { seq: u64, entry: Value },
+    /// Entries were written but could not be sent, e.g. because the client
+    /// was too slow to keep up. `missed` is unset when the number is unknown,
+    /// e.g. because the device was opened again since the stream was
+    /// interrupted.
+    Gap { missed: Option<u64> },
+}
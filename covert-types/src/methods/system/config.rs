@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Placeholder returned instead of secret config values.
+pub const REDACTED: &str = "<redacted>";
+
+/// Effective configuration of the running server with secrets redacted.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigStateResponse {
+    pub listener: ListenerState,
+    pub storage: StorageConfigState,
+    pub seal: SealState,
+    pub ttl: TtlState,
+    pub replication: Option<ReplicationState>,
+    pub ignore_migration_checksums: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListenerState {
+    /// Unset when no TCP listener is configured.
+    pub port: Option<u16>,
+    /// Unset when the listener serves plain HTTP.
+    pub tls: Option<TlsState>,
+    /// Addresses of the unix socket listeners.
+    pub unix_sockets: Vec<String>,
+    /// Addresses of the TCP listeners in addition to `port`.
+    #[serde(default)]
+    pub tcp_listeners: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TlsState {
+    pub cert_file: String,
+    pub min_version: String,
+    pub request_client_cert: bool,
+    #[serde(default)]
+    pub require_client_cert: bool,
+    /// Allowed cipher suites, empty when every supported suite is allowed.
+    #[serde(default)]
+    pub cipher_suites: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageConfigState {
+    pub path: String,
+    pub in_memory: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SealState {
+    #[serde(rename = "type")]
+    pub variant: String,
+    /// Number of key shares, unset until the server is initialized.
+    pub shares: Option<u8>,
+    pub threshold: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TtlState {
+    /// Default lease TTL of new mounts.
+    #[serde(with = "humantime_serde")]
+    pub default_lease_ttl: Duration,
+    /// Max lease TTL of new mounts.
+    #[serde(with = "humantime_serde")]
+    pub mount_max_lease_ttl: Duration,
+    /// Max TTL of any lease issued by the server.
+    #[serde(with = "humantime_serde")]
+    pub system_max_lease_ttl: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationState {
+    pub bucket_url: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+/// Parts of the config that were reloaded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReloadConfigResponse {
+    /// The certificates and key of the TCP listeners were read again.
+    pub tls: bool,
+    /// The log level and format were applied again.
+    pub logging: bool,
+}
+
+/// Automatic sealing of the server when it looks like it is being tampered
+/// with. The server seals itself when either threshold is reached within
+/// `window` and has to be unsealed manually again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TamperConfig {
+    pub enabled: bool,
+    /// Failed attempts to decrypt the storage with the provided key shares.
+    /// Zero disables the check.
+    pub decryption_failures: u32,
+    /// Requests with a token that is not a token at all. Zero disables the
+    /// check.
+    pub malformed_tokens: u32,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+}
+
+impl Default for TamperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            decryption_failures: 5,
+            malformed_tokens: 100,
+            window: Duration::from_mins(1),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::entity::EntityAlias;
@@ -54,6 +56,34 @@ pub struct RemoveEntityAliasResponse {
     pub entity: EntityWithPolicyAndAlias,
 }
 
+/// What happens to the tokens issued to other entities with a token of a
+/// deleted entity, e.g. by logging in with it. The tokens of the entity are
+/// always revoked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildTokens {
+    /// Revoke them together with the tokens of the entity.
+    #[default]
+    Revoke,
+    /// Keep them as tokens without a parent.
+    Orphan,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DeleteEntityParams {
+    #[serde(default)]
+    pub child_tokens: ChildTokens,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeleteEntityResponse {
+    pub name: String,
+    /// Number of tokens of the entity that were revoked.
+    pub revoked_tokens: usize,
+    /// Number of child tokens that were kept without a parent.
+    pub orphaned_tokens: usize,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ListEntitiesResponse {
     pub entities: Vec<EntityWithPolicyAndAlias>,
@@ -62,6 +92,47 @@ pub struct ListEntitiesResponse {
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct EntityWithPolicyAndAlias {
     pub name: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
     pub policies: Vec<String>,
     pub aliases: Vec<EntityAlias>,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportEntitiesParams {
+    pub entities: Vec<ImportEntity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ImportEntity {
+    pub name: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub policies: Vec<String>,
+    #[serde(default)]
+    pub aliases: Vec<EntityAlias>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportEntitiesResponse {
+    /// Result for each of the imported entities, in the order they were given.
+    pub entities: Vec<ImportEntityResult>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ImportEntityResult {
+    pub name: String,
+    #[serde(flatten)]
+    pub status: ImportEntityStatus,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ImportEntityStatus {
+    /// The entity was created. Aliases that already belong to another entity
+    /// are not attached and are returned in `skipped_aliases`.
+    Created { skipped_aliases: Vec<EntityAlias> },
+    /// The entity was not created.
+    Failed { error: String },
+}
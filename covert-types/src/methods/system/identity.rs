@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::entity::EntityAlias;
+
+/// Version of the snapshot format written by this version of Covert.
+pub const IDENTITY_SNAPSHOT_VERSION: u32 = 1;
+
+/// Snapshot of the policies and entities of a namespace, encrypted with a key
+/// derived from the master key. Only a server unsealed with the same master
+/// key can import it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IdentitySnapshot {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Hex encoded nonce followed by the ciphertext.
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportIdentityParams {
+    pub snapshot: IdentitySnapshot,
+    #[serde(default)]
+    pub mode: ImportIdentityMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportIdentityMode {
+    /// Create the policies and entities of the snapshot and add its metadata,
+    /// policies and aliases to the existing entities. Nothing is removed.
+    #[default]
+    Merge,
+    /// Make the policies and entities of the namespace match the snapshot.
+    /// Policies and entities that are not in the snapshot are removed, together
+    /// with the tokens of the removed entities.
+    Replace,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ImportIdentityResponse {
+    pub mode: ImportIdentityMode,
+    /// Number of policies created or updated.
+    pub policies: usize,
+    /// Number of entities created or updated.
+    pub entities: usize,
+    pub removed_policies: Vec<String>,
+    pub removed_entities: Vec<String>,
+    /// Aliases of the snapshot that were not imported, because the mount does
+    /// not exist or the alias belongs to another entity.
+    pub skipped_aliases: Vec<EntityAlias>,
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Complete a login that requires MFA.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidateMfaParams {
+    pub request_id: Uuid,
+    /// Current TOTP code of the entity.
+    pub code: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GenerateTotpKeyParams {
+    /// Issuer shown by authenticator apps, `Covert` by default.
+    #[serde(default)]
+    pub issuer: Option<String>,
+}
+
+/// A new TOTP key of an entity. The secret is only returned once.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateTotpKeyResponse {
+    pub entity: String,
+    /// Base32 encoded secret.
+    pub secret: String,
+    /// `otpauth://` URI of the key for authenticator apps.
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpKeyResponse {
+    pub entity: String,
+    pub created_at: DateTime<Utc>,
+}
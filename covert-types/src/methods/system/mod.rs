@@ -1,26 +1,47 @@
+mod audit;
+mod config;
 mod entity;
+mod identity;
+mod mfa;
 mod namespace;
 mod policy;
+mod quota;
+mod rotation;
+mod token;
+mod wrapping;
 
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    backend::{BackendCategory, BackendType},
-    mount::MountConfig,
+    backend::{BackendCategory, BackendType, RouteHelp},
+    mount::{ListingVisibility, MountConfig},
     state::StorageState,
     token::Token,
 };
+pub use audit::*;
+pub use config::*;
 pub use entity::*;
+pub use identity::*;
+pub use mfa::*;
 pub use namespace::*;
 pub use policy::*;
+pub use quota::*;
+pub use rotation::*;
+pub use token::*;
+pub use wrapping::*;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InitializeParams {
     pub shares: u8,
     pub threshold: u8,
+    /// Number of key shares kept by the seal for auto-unseal. Only valid
+    /// with a seal, which keeps every share, so it must equal `shares`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_shares: Option<u8>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +49,9 @@ pub struct InitializeParams {
 pub enum InitializeResponse {
     NewKeyShares(InitializedKeyShares),
     ExistingKey(InitializedWithExistingKey),
+    /// The master key is wrapped by the seal of the server, which is already
+    /// unsealed.
+    Unsealed(InitializedWithSeal),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,6 +64,13 @@ pub struct InitializedWithExistingKey {
     pub message: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InitializedWithSeal {
+    /// Type of the seal, e.g. `pkcs11`.
+    pub seal: String,
+    pub root_token: Token,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UnsealParams {
     pub shares: Vec<String>,
@@ -66,6 +97,25 @@ pub struct SealResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StatusResponse {
     pub state: StorageState,
+    /// Version of the server.
+    #[serde(default)]
+    pub version: String,
+    /// Unset when the server does not run in HA mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ha: Option<HaStatus>,
+}
+
+/// Role of the node in an HA cluster.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HaStatus {
+    pub node_id: String,
+    /// Whether this node is the active node.
+    pub active: bool,
+    /// Whether this node serves reads while it is a standby.
+    #[serde(default)]
+    pub performance_standby: bool,
+    /// Address of the active node, unset while no node holds the HA lock.
+    pub active_address: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -122,6 +172,125 @@ pub struct MountsListItemResponse {
     pub config: MountConfig,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MountResponse {
+    pub id: Uuid,
+    pub path: String,
+    pub category: BackendCategory,
+    #[serde(rename = "type")]
+    pub variant: BackendType,
+    pub config: MountConfig,
+    /// Version of the latest applied storage migration.
+    pub schema_version: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MountMigrationsResponse {
+    pub applied: Vec<AppliedMigration>,
+    pub pending: Vec<PendingMigration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MountPathsResponse {
+    pub paths: Vec<RouteHelp>,
+}
+
+/// Settings of a mount that can be changed without touching its data, the
+/// path it is mounted at or the backend serving it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountTuning {
+    #[serde(with = "humantime_serde")]
+    pub default_lease_ttl: Duration,
+    #[serde(with = "humantime_serde")]
+    pub max_lease_ttl: Duration,
+    pub description: Option<String>,
+    pub listing_visibility: ListingVisibility,
+    pub require_mfa: bool,
+    pub max_request_body_size: Option<u64>,
+    pub max_concurrent_requests: Option<u32>,
+}
+
+impl From<&MountConfig> for MountTuning {
+    fn from(config: &MountConfig) -> Self {
+        Self {
+            default_lease_ttl: config.default_lease_ttl,
+            max_lease_ttl: config.max_lease_ttl,
+            description: config.description.clone(),
+            listing_visibility: config.listing_visibility,
+            require_mfa: config.require_mfa,
+            max_request_body_size: config.max_request_body_size,
+            max_concurrent_requests: config.max_concurrent_requests,
+        }
+    }
+}
+
+/// Changes to the tuning of a mount, the settings that are left out are
+/// kept.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TuneMountParams {
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_lease_ttl: Option<Duration>,
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_lease_ttl: Option<Duration>,
+    /// An empty description removes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listing_visibility: Option<ListingVisibility>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_mfa: Option<bool>,
+    /// `null` removes the limit of the mount, the limit of the server
+    /// applies then.
+    #[serde(
+        default,
+        with = "::serde_with::rust::double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_request_body_size: Option<Option<u64>>,
+    /// `null` removes the limit of the mount.
+    #[serde(
+        default,
+        with = "::serde_with::rust::double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_concurrent_requests: Option<Option<u32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MountTuneResponse {
+    pub path: String,
+    #[serde(flatten)]
+    pub tuning: MountTuning,
+    /// Max TTL of the leases of the mount once the max lease TTL of the
+    /// server is applied.
+    #[serde(with = "humantime_serde")]
+    pub effective_max_lease_ttl: Duration,
+    /// Max size in bytes of the request bodies sent to the mount, the limit
+    /// of the server unless the mount has its own.
+    pub effective_max_request_body_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub version: u64,
+    pub description: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PendingMigration {
+    pub version: u64,
+    pub description: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LeaseEntry {
     pub id: String,
@@ -129,11 +298,20 @@ pub struct LeaseEntry {
     pub issue_time: String,
     pub expire_time: String,
     pub last_renewal_time: String,
+    /// Whether the backend that issued the lease can extend it.
+    #[serde(default)]
+    pub renewable: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RevokedLeasesResponse {
     pub leases: Vec<LeaseEntry>,
+    /// Leases that were removed even though the backend failed to revoke
+    /// them. Only set by a forced revocation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backend_failures: Vec<LeaseEntry>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -149,12 +327,23 @@ pub struct LookupLeaseResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListLeasesResponse {
     pub leases: Vec<LeaseEntry>,
+    /// Cursor of the next page when the leases are listed with a `limit`,
+    /// unset on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountLeasesResponse {
+    pub count: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RenewLeaseParams {
-    #[serde(with = "humantime_serde")]
-    pub ttl: Option<Duration>,
+    /// How long the lease should live from now. Defaults to the default lease
+    /// TTL of the mount and is capped by the max TTL.
+    #[serde(default, alias = "ttl", with = "humantime_serde")]
+    pub increment: Option<Duration>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -8,6 +8,16 @@ pub struct CreatePolicyParams {
     pub policy: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FormatPolicyParams {
+    pub policy: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FormatPolicyResponse {
+    pub policy: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreatePolicyResponse {
     pub policy: Policy,
@@ -18,6 +28,13 @@ pub struct ListPolicyResponse {
     pub policies: Vec<Policy>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReadPolicyResponse {
+    pub policy: Policy,
+    /// The policy formatted as a policy document.
+    pub document: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RemovePolicyResponse {
     pub policy: String,
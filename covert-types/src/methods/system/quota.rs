@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The requests a quota applies to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuotaScope {
+    /// Every request.
+    Global,
+    /// Requests to paths under the prefix, e.g. `kv/` or `auth/userpass/`.
+    /// Paths in child namespaces are prefixed with the namespace path, e.g.
+    /// `team/kv/`.
+    Mount { path: String },
+    /// Requests made with the tokens of an entity in the root namespace.
+    Entity { name: String },
+    /// Requests made with the tokens of any entity in the root namespace.
+    /// Every entity is limited on its own, whichever of its tokens it uses.
+    PerEntity,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RateLimitQuotaParams {
+    pub scope: QuotaScope,
+    /// Sustained number of requests allowed per second.
+    pub rate: f64,
+    /// Number of requests allowed in a burst. Defaults to the rate rounded
+    /// up.
+    #[serde(default)]
+    pub burst: Option<u32>,
+    /// Do not limit requests made with a root token.
+    #[serde(default)]
+    pub exempt_root: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RateLimitQuota {
+    pub name: String,
+    pub scope: QuotaScope,
+    pub rate: f64,
+    pub burst: u32,
+    pub exempt_root: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Usage of a rate limit quota since it was configured or the storage was
+/// last unsealed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RateLimitQuotaUsage {
+    /// Requests that can currently be made without waiting.
+    pub available: f64,
+    pub allowed_requests: u64,
+    pub rejected_requests: u64,
+}
+
+/// Usage of a per-entity quota by one entity.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EntityQuotaUsage {
+    pub entity: String,
+    #[serde(flatten)]
+    pub usage: RateLimitQuotaUsage,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReadRateLimitQuotaResponse {
+    #[serde(flatten)]
+    pub quota: RateLimitQuota,
+    /// Usage of the quota, summed over the entities for per-entity quotas.
+    pub usage: RateLimitQuotaUsage,
+    /// Usage of the entities that made requests, for per-entity quotas.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<EntityQuotaUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListRateLimitQuotasResponse {
+    pub quotas: Vec<RateLimitQuota>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteRateLimitQuotaResponse {
+    pub quota: RateLimitQuota,
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How the value of a static secret is replaced when it is rotated.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RotationHandler {
+    /// A random alphanumeric password generated by the server, stored in the
+    /// `value` field.
+    Random {
+        #[serde(default = "default_random_length")]
+        length: usize,
+    },
+    /// An update request to a path of a mount in the same namespace, e.g.
+    /// `psql/rotate-root`. The data of the response is the new value.
+    Backend { path: String },
+}
+
+fn default_random_length() -> usize {
+    32
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SetStaticSecretParams {
+    pub handler: RotationHandler,
+    /// Time between two rotations.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// How long the previous value can still be read after a rotation.
+    /// Defaults to the interval, i.e. until the next rotation.
+    #[serde(default, with = "humantime_serde")]
+    pub grace: Option<Duration>,
+}
+
+/// A secret with a static value that is replaced on a schedule.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StaticSecret {
+    pub name: String,
+    pub handler: RotationHandler,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub grace: Duration,
+    /// Incremented on every rotation, starting at 1.
+    pub version: u64,
+    pub rotated_at: DateTime<Utc>,
+    pub next_rotation_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadStaticSecretResponse {
+    #[serde(flatten)]
+    pub secret: StaticSecret,
+    /// The current value.
+    pub data: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadPreviousStaticSecretResponse {
+    pub version: u64,
+    /// The previous value.
+    pub data: Value,
+    /// The previous value cannot be read anymore after this time.
+    pub grace_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListStaticSecretsResponse {
+    pub secrets: Vec<StaticSecret>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteStaticSecretResponse {
+    pub secret: StaticSecret,
+}
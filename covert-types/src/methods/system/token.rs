@@ -0,0 +1,124 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::policy::PolicySource;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupTokenResponse {
+    pub entity_name: String,
+    #[serde(default)]
+    pub accessor: String,
+    pub issue_time: String,
+    pub expire_time: Option<String>,
+    pub metadata: HashMap<String, String>,
+    pub renewable: bool,
+    /// The policies of the token, the union of the policies of all sources.
+    #[serde(default)]
+    pub policies: Vec<TokenPolicy>,
+    /// The token is not valid before this time.
+    #[serde(default)]
+    pub not_before: Option<String>,
+    /// Number of requests the token can still be used for, unlimited if unset.
+    #[serde(default)]
+    pub num_uses: Option<u32>,
+}
+
+/// Parameters of a token issued ahead of time, e.g. a single use token to
+/// bootstrap a machine during a scheduled window.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTokenParams {
+    /// Entity the token is issued to.
+    pub entity_name: String,
+    /// Policies granted in addition to the policies of the entity.
+    #[serde(default)]
+    pub policies: Vec<String>,
+    /// How long the token is valid, from `not_before` if it is set.
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+    /// The token is rejected before this time.
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
+    /// Number of requests the token can be used for, unlimited if unset.
+    #[serde(default)]
+    pub num_uses: Option<u32>,
+    #[serde(default)]
+    pub renewable: bool,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Issue the token without a parent, so it is not revoked together with
+    /// the token of the request.
+    #[serde(default)]
+    pub orphan: bool,
+}
+
+/// Another token to look up or revoke, given by the token itself or by its
+/// accessor.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TokenTargetParams {
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub accessor: Option<String>,
+}
+
+/// A policy of a token and the sources that granted it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenPolicy {
+    pub name: String,
+    pub sources: Vec<PolicySource>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenewTokenSelfParams {
+    /// How long the token should live from now. Defaults to the default lease
+    /// TTL of the mount that issued the token and is capped by the max TTL.
+    #[serde(default, with = "humantime_serde")]
+    pub increment: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeTokenSelfResponse {
+    /// Entity the revoked token was issued to.
+    pub entity_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeTokensByPolicyParams {
+    pub policy: String,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeTokensByPolicyResponse {
+    /// Id of the revocation job. Not set for dry runs.
+    pub job_id: Option<Uuid>,
+    /// Number of tokens carrying the policy.
+    pub matched: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenRevocationJobState {
+    Running,
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenRevocationJobStatus {
+    pub job_id: Uuid,
+    pub policy: String,
+    pub state: TokenRevocationJobState,
+    pub total: usize,
+    pub revoked: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TidyTokensResponse {
+    /// Number of interrupted token revocations that were completed.
+    pub revoked: usize,
+}
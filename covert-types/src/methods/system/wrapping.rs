@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::token::WrappingToken;
+
+/// Returned instead of the response of a request that was wrapped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WrapInfo {
+    pub token: WrappingToken,
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+    pub creation_time: DateTime<Utc>,
+    pub creation_path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnwrapParams {
+    pub token: WrappingToken,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WrappingLookupParams {
+    pub token: WrappingToken,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WrappingLookupResponse {
+    pub creation_time: DateTime<Utc>,
+    pub creation_path: String,
+    pub expire_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RewrapParams {
+    pub token: WrappingToken,
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the ciphertexts returned by the transit engine, followed by the
+/// base64 encoded nonce and ciphertext.
+pub const CIPHERTEXT_PREFIX: &str = "covert:v1:";
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct UpdateKeyParams {
+    /// A disabled key refuses to encrypt and decrypt until it is enabled
+    /// again.
+    pub disabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct KeyResponse {
+    pub name: String,
+    pub disabled: bool,
+    pub created_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct EncryptParams {
+    /// Base64 encoded plaintext.
+    pub plaintext: String,
+    /// Base64 encoded data the ciphertext is bound to without being
+    /// encrypted. The ciphertext is only decrypted with the same context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct EncryptResponse {
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct DecryptParams {
+    pub ciphertext: String,
+    /// Base64 encoded context the plaintext was encrypted with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct DecryptResponse {
+    /// Base64 encoded plaintext.
+    pub plaintext: String,
+}
@@ -41,4 +41,49 @@ pub struct RemoveUserResponse {
 pub struct LoginParams {
     pub username: String,
     pub password: String,
+    /// Issue a token that can be renewed. Defaults to true.
+    #[serde(default = "default_as_true")]
+    pub renewable: bool,
+}
+
+fn default_as_true() -> bool {
+    true
+}
+
+/// Algorithm and cost parameters used to hash new passwords. Stored hashes
+/// using weaker parameters are rehashed on the next successful login.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+pub enum PasswordHashConfig {
+    /// Argon2id
+    Argon2 {
+        /// Memory cost in KiB.
+        memory_kib: u32,
+        /// Number of passes over the memory.
+        iterations: u32,
+        /// Number of lanes.
+        parallelism: u32,
+    },
+    Scrypt {
+        /// Base 2 logarithm of the CPU/memory cost `N`.
+        log_n: u8,
+        /// Block size.
+        r: u32,
+        /// Parallelization.
+        p: u32,
+    },
+    Bcrypt {
+        cost: u32,
+    },
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        // One of the OWASP recommended Argon2id configurations
+        Self::Argon2 {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
 }
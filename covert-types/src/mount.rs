@@ -1,6 +1,8 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use serde::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use strum::{Display, EnumString};
 use uuid::Uuid;
 
 use crate::backend::BackendType;
@@ -11,6 +13,73 @@ pub struct MountConfig {
     pub default_lease_ttl: Duration,
     #[serde(with = "humantime_serde")]
     pub max_lease_ttl: Duration,
+    /// Logins through the auth mount have to be completed with a second
+    /// factor of the entity before the token is issued.
+    #[serde(default)]
+    pub require_mfa: bool,
+    /// Maximum size in bytes of the request bodies sent to the mount,
+    /// overrides the limit of the server for mounts that need larger bodies.
+    #[serde(default)]
+    pub max_request_body_size: Option<u64>,
+    /// Maximum number of requests the mount handles at the same time, further
+    /// requests are refused until one of them completes. The limit of the
+    /// server applies to every mount too.
+    #[serde(default)]
+    pub max_concurrent_requests: Option<u32>,
+    /// Name of the plugin serving the mount, one of the plugins registered in
+    /// the config of the server. Only set for `plugin` mounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    /// Who sees the mount when listing the mounts. It does not change who
+    /// can access the mount.
+    #[serde(default)]
+    pub listing_visibility: ListingVisibility,
+    /// What the mount is used for, shown to operators.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Fields of the secrets of a KV mount that are encrypted with a key of
+    /// a transit mount, by field name. The fields are encrypted when they are
+    /// written and only decrypted for callers allowed to decrypt with the key.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub encrypted_fields: BTreeMap<String, TransitKey>,
+}
+
+/// Key of a transit mount used to encrypt a field of a KV mount.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransitKey {
+    /// Path of the transit mount in the namespace of the KV mount, e.g.
+    /// `transit/`.
+    pub mount: String,
+    /// Name of the key.
+    pub key: String,
+}
+
+/// Who sees a mount in the listing of the mounts.
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    EnumString,
+    Display,
+    SerializeDisplay,
+    DeserializeFromStr,
+)]
+pub enum ListingVisibility {
+    /// Listed to every caller allowed to list the mounts.
+    #[default]
+    #[strum(ascii_case_insensitive, serialize = "default")]
+    Default,
+    /// Only listed to callers with a policy granting access to the mount
+    /// path. The system mount is always hidden.
+    #[strum(ascii_case_insensitive, serialize = "hidden")]
+    Hidden,
+    /// Listed like [`ListingVisibility::Default`], and meant to be listed to
+    /// unauthenticated callers too, e.g. on the login page of a UI.
+    #[strum(ascii_case_insensitive, serialize = "unauth")]
+    Unauth,
 }
 
 impl Default for MountConfig {
@@ -18,6 +87,13 @@ impl Default for MountConfig {
         Self {
             default_lease_ttl: Duration::from_secs(60 * 30),
             max_lease_ttl: Duration::from_secs(60 * 60 * 4),
+            require_mfa: false,
+            max_request_body_size: None,
+            max_concurrent_requests: None,
+            plugin: None,
+            listing_visibility: ListingVisibility::default(),
+            description: None,
+            encrypted_fields: BTreeMap::new(),
         }
     }
 }
@@ -1,6 +1,5 @@
 use std::str::FromStr;
 
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::{error::ApiError, request::Operation};
@@ -35,6 +34,32 @@ impl Policy {
             .any(|path_policy| path_policy.is_authorized(path, operations))
     }
 
+    /// Returns true if the policy denies the path. A denied path cannot be
+    /// accessed even if another policy grants it.
+    #[must_use]
+    pub fn denies(&self, path: &str) -> bool {
+        self.paths.iter().any(|path_policy| {
+            path_policy.operations.contains(&Operation::Deny) && path_policy.matches(path)
+        })
+    }
+
+    /// Returns true if the policy grants any capability on a path starting
+    /// with `prefix`.
+    #[must_use]
+    pub fn can_access_prefix(&self, prefix: &str) -> bool {
+        self.paths.iter().any(|path_policy| {
+            if path_policy.operations.is_empty()
+                || path_policy.operations.contains(&Operation::Deny)
+            {
+                return false;
+            }
+            match path_policy.path.strip_suffix('*') {
+                Some(glob) => glob.starts_with(prefix) || prefix.starts_with(glob),
+                None => path_policy.path.starts_with(prefix),
+            }
+        })
+    }
+
     #[must_use]
     pub fn batch_is_authorized(policies: &[Policy], derived_policies: &[Policy]) -> bool {
         let mut derived_policies = derived_policies
@@ -53,6 +78,35 @@ impl Policy {
     }
 }
 
+/// Where a policy of a token comes from. The policies of a token are the union
+/// of the policies of all sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicySource {
+    /// Granted by the auth method on login, e.g. by the role that was used.
+    Role,
+    /// Attached to the entity of the token.
+    Entity,
+    /// Mapped from a group the user is a member of by the auth method.
+    Group,
+    /// The `default` policy of the namespace, granted to every token.
+    Default,
+}
+
+impl FromStr for PolicySource {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "role" => Ok(Self::Role),
+            "entity" => Ok(Self::Entity),
+            "group" => Ok(Self::Group),
+            "default" => Ok(Self::Default),
+            _ => Err(ApiError::bad_request()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::FromRow)]
 pub struct PathPolicy {
     pub path: String,
@@ -60,12 +114,6 @@ pub struct PathPolicy {
     pub operations: Vec<Operation>,
 }
 
-lazy_static::lazy_static! {
-    static ref HCL_POLICY_REGEX: Regex = Regex::new(r#"path"(.+)"\{capabilities=\[(.+)\]\}"#).expect("a valid regex");
-
-    static ref HCL_POLICY_RULE_REGEX: Regex = Regex::new(r"(?m)path[^\}]+\}").expect("a valid regex");
-}
-
 impl PathPolicy {
     #[must_use]
     pub fn new(path: String, operations: Vec<Operation>) -> Self {
@@ -82,58 +130,238 @@ impl PathPolicy {
         &self.operations
     }
 
-    /// Parse a raw string into a list of policies.
+    /// Parse a policy document into a list of policies. A document is a list
+    /// of `path "<path>" { capabilities = ["<capability>", ...] }` rules,
+    /// lines starting with `#` are comments.
     ///
     /// # Errors
     ///
-    /// Returns an error if the string is not in a valid policy format.
-    pub fn parse(s: &str) -> Result<Vec<Self>, ApiError> {
-        // Remove comments
-        let mut s = s
-            .lines()
-            .filter(|line| !line.trim_start().starts_with('#'))
-            .collect::<String>();
-        s.retain(|c| !c.is_whitespace());
-
-        let s = s.replace('\n', "");
-
-        let rules = HCL_POLICY_RULE_REGEX.captures_iter(&s);
+    /// Returns an error with the line and column of the first part of the
+    /// document that is not a valid rule.
+    pub fn parse(s: &str) -> Result<Vec<Self>, PolicyParseError> {
+        let mut parser = PolicyParser::new(s);
         let mut policies = vec![];
-        for rule in rules {
-            let ma = rule.get(0).map(|m| m.range()).map(|range| &s[range]);
-            if let Some(m) = ma {
-                let caps = HCL_POLICY_REGEX
-                    .captures(m)
-                    .ok_or_else(ApiError::bad_request)?;
-                let path = caps.get(1).ok_or_else(ApiError::bad_request)?.as_str();
-                let operations = caps
-                    .get(2)
-                    .ok_or_else(ApiError::bad_request)?
-                    .as_str()
-                    .split(',')
-                    .map(|c| c.chars().filter(|c| c.is_alphabetic()).collect::<String>())
-                    .map(|s| Operation::from_str(&s))
-                    .collect::<Result<Vec<_>, _>>()?;
-                policies.push(PathPolicy {
-                    path: path.into(),
-                    operations,
-                });
+        while !parser.at_end() {
+            parser.keyword("path")?;
+            let (position, path) = parser.string()?;
+            if path.is_empty() {
+                return Err(position.error("the path cannot be empty"));
             }
+            parser.expect('{')?;
+            parser.keyword("capabilities")?;
+            parser.expect('=')?;
+            parser.expect('[')?;
+            let mut operations = vec![];
+            while !parser.eat(']') {
+                let (position, capability) = parser.string()?;
+                let operation = Operation::from_str(&capability)
+                    .map_err(|_| position.error(format!("unknown capability `{capability}`")))?;
+                operations.push(operation);
+                if !parser.eat(',') {
+                    parser.expect(']')?;
+                    break;
+                }
+            }
+            parser.expect('}')?;
+            policies.push(PathPolicy { path, operations });
         }
 
         Ok(policies)
     }
 
+    /// Canonicalize a list of policies. The policies are sorted by path,
+    /// policies for the same path are merged and the capabilities are sorted
+    /// and deduplicated.
+    #[must_use]
+    pub fn canonicalize(policies: Vec<Self>) -> Vec<Self> {
+        let mut canonical: Vec<Self> = vec![];
+        for policy in policies {
+            match canonical.iter_mut().find(|p| p.path == policy.path) {
+                Some(existing) => existing.operations.extend(policy.operations),
+                None => canonical.push(policy),
+            }
+        }
+        for policy in &mut canonical {
+            policy.operations.sort();
+            policy.operations.dedup();
+        }
+        canonical.sort_by(|a, b| a.path.cmp(&b.path));
+        canonical
+    }
+
+    /// Format a list of policies as a policy document.
+    #[must_use]
+    pub fn format(policies: &[Self]) -> String {
+        policies
+            .iter()
+            .map(|policy| {
+                let capabilities = policy
+                    .operations
+                    .iter()
+                    .map(|op| format!("\"{op}\""))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "path \"{}\" {{\n    capabilities = [{capabilities}]\n}}\n",
+                    policy.path
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self.path.strip_suffix('*') {
+            Some(glob) => path.starts_with(glob),
+            None => path == self.path,
+        }
+    }
+
     fn is_authorized(&self, path: &str, operations: &[Operation]) -> bool {
-        if self.path.ends_with('*') {
-            if !path.starts_with(&self.path[..self.path.len() - 1]) {
-                return false;
+        self.matches(path)
+            && !self.operations.contains(&Operation::Deny)
+            && operations.iter().all(|op| self.operations.contains(op))
+    }
+}
+
+/// Error of [`PathPolicy::parse`] at a position of the policy document.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}, column {column}: {message}")]
+pub struct PolicyParseError {
+    /// Line of the error, starting at 1.
+    pub line: usize,
+    /// Column of the error in characters, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn error(self, message: impl Into<String>) -> PolicyParseError {
+        PolicyParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads the tokens of a policy document, skipping whitespace and comments.
+struct PolicyParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    position: Position,
+}
+
+impl<'a> PolicyParser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            chars: s.chars().peekable(),
+            position: Position { line: 1, column: 1 },
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == '#' {
+                while self.chars.peek().is_some_and(|c| *c != '\n') {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
             }
-        } else if path != self.path {
-            return false;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_trivia();
+        self.chars.peek().is_none()
+    }
+
+    /// Description of the next character for errors.
+    fn found(&mut self) -> String {
+        self.chars
+            .peek()
+            .map_or_else(|| "the end of the policy".to_string(), |c| format!("`{c}`"))
+    }
+
+    /// Consume the character if it is next.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_trivia();
+        if self.chars.peek() == Some(&expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), PolicyParseError> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        let found = self.found();
+        Err(self
+            .position
+            .error(format!("expected `{expected}`, found {found}")))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), PolicyParseError> {
+        self.skip_trivia();
+        let position = self.position;
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_alphanumeric() && c != '_' && c != '-' {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        if word == keyword {
+            return Ok(());
+        }
+        let found = if word.is_empty() {
+            self.found()
+        } else {
+            format!("`{word}`")
         };
+        Err(position.error(format!("expected `{keyword}`, found {found}")))
+    }
 
-        operations.iter().all(|op| self.operations.contains(op))
+    /// A double quoted string and the position it starts at.
+    fn string(&mut self) -> Result<(Position, String), PolicyParseError> {
+        self.skip_trivia();
+        let position = self.position;
+        if self.chars.peek() != Some(&'"') {
+            let found = self.found();
+            return Err(position.error(format!("expected a quoted string, found {found}")));
+        }
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok((position, value)),
+                Some('\n') | None => return Err(position.error("unterminated string")),
+                Some(c) => value.push(c),
+            }
+        }
     }
 }
 
@@ -298,6 +526,25 @@ path "sys/control-group/request" {
         );
     }
 
+    #[test]
+    fn access_to_prefix() {
+        let policy = Policy::new(
+            "foo".into(),
+            vec![
+                PathPolicy::new("kv/data/*".into(), vec![Read]),
+                PathPolicy::new("sys/mounts".into(), vec![Read]),
+                PathPolicy::new("psql/*".into(), vec![]),
+            ],
+            String::new(),
+        );
+        assert!(policy.can_access_prefix("kv/"));
+        assert!(policy.can_access_prefix("kv/data/foo/"));
+        assert!(policy.can_access_prefix("sys/"));
+        assert!(!policy.can_access_prefix("sys/mounts/foo/"));
+        assert!(!policy.can_access_prefix("kvv/"));
+        assert!(!policy.can_access_prefix("psql/"));
+    }
+
     #[test]
     fn authorize_request_against_policy() {
         use Operation::{Read, Update};
@@ -323,4 +570,159 @@ path "sys/control-group/request" {
         assert!(!policy.is_authorized("secret/", &[Read]));
         assert!(!policy.is_authorized("/", &[Read]));
     }
+
+    #[test]
+    fn deny_capability() {
+        let policy = r#"
+        path "kv/*" {
+            capabilities = ["read", "update"]
+        }
+        path "kv/data/admin" {
+            capabilities = ["deny"]
+        }
+        "#;
+        let policy = Policy::new(
+            "foo".into(),
+            PathPolicy::parse(policy).unwrap(),
+            String::new(),
+        );
+        assert!(policy.denies("kv/data/admin"));
+        assert!(!policy.denies("kv/data/foo"));
+        assert!(policy.is_authorized("kv/data/foo", &[Operation::Read]));
+
+        // A denied path grants nothing, not even together with other
+        // capabilities
+        let deny = PathPolicy::new("kv/*".into(), vec![Operation::Read, Operation::Deny]);
+        assert!(!deny.is_authorized("kv/data/foo", &[Operation::Read]));
+        let policy = Policy::new("bar".into(), vec![deny], String::new());
+        assert!(!policy.can_access_prefix("kv/"));
+    }
+
+    #[test]
+    fn parses_list_capability() {
+        let policy = r#"
+        path "kv/metadata/*" {
+            capabilities = ["read", "list"]
+        }
+        "#;
+        let policies = PathPolicy::parse(policy).unwrap();
+        assert_eq!(
+            policies,
+            vec![PathPolicy {
+                path: "kv/metadata/*".into(),
+                operations: vec![Operation::Read, Operation::List],
+            }]
+        );
+        assert!(policies[0].is_authorized("kv/metadata/foo", &[Operation::List]));
+    }
+
+    #[test]
+    fn patch_requires_patch_capability() {
+        let policy = r#"
+        path "kv/data/*" {
+            capabilities = ["update"]
+        }
+        path "sys/mounts/*" {
+            capabilities = ["patch"]
+        }
+        "#;
+        let policies = PathPolicy::parse(policy).unwrap();
+        assert!(!policies[0].is_authorized("kv/data/foo", &[Operation::Patch]));
+        assert!(policies[1].is_authorized("sys/mounts/foo", &[Operation::Patch]));
+    }
+
+    #[test]
+    fn reports_position_of_parse_errors() {
+        for (policy, line, column, message) in [
+            (
+                "path \"kv/*\" {\n    capabilities = [\"read\", \"reed\"]\n}",
+                2,
+                29,
+                "unknown capability `reed`",
+            ),
+            (
+                "# Secrets\npath \"kv/*\" {\n    capabilites = [\"read\"]\n}",
+                3,
+                5,
+                "expected `capabilities`, found `capabilites`",
+            ),
+            (
+                "path \"kv/*\" { capabilities = [\"read\"] ",
+                1,
+                39,
+                "expected `}`, found the end of the policy",
+            ),
+            (
+                "path \"kv/*\" { capabilities = [\"read\"] }\npath kv",
+                2,
+                6,
+                "expected a quoted string, found `k`",
+            ),
+            ("path \"kv/* {", 1, 6, "unterminated string"),
+            ("path \"\" {}", 1, 6, "the path cannot be empty"),
+        ] {
+            assert_eq!(
+                PathPolicy::parse(policy).unwrap_err(),
+                PolicyParseError {
+                    line,
+                    column,
+                    message: message.to_string(),
+                },
+                "{policy}"
+            );
+        }
+
+        // Trailing commas and comments at the end of a line are fine
+        let policies = PathPolicy::parse(
+            "path \"kv/*\" { # Secrets\n    capabilities = [\"read\", \"list\",]\n}",
+        )
+        .unwrap();
+        assert_eq!(policies[0].operations, vec![Read, Operation::List]);
+    }
+
+    #[test]
+    fn canonicalize_policy() {
+        let policy = r#"
+        # Secrets
+        path "kv/data/*" {
+            capabilities = ["update","read",  "read"]
+        }
+        path "auth/userpass/login" { capabilities = ["update"] }
+        path "kv/data/*" {
+            capabilities = ["create"]
+        }
+        "#;
+        let policies = PathPolicy::canonicalize(PathPolicy::parse(policy).unwrap());
+        assert_eq!(
+            policies,
+            vec![
+                PathPolicy {
+                    path: "auth/userpass/login".into(),
+                    operations: vec![Update],
+                },
+                PathPolicy {
+                    path: "kv/data/*".into(),
+                    operations: vec![Create, Read, Update],
+                },
+            ]
+        );
+
+        let formatted = PathPolicy::format(&policies);
+        assert_eq!(
+            formatted,
+            r#"path "auth/userpass/login" {
+    capabilities = ["update"]
+}
+
+path "kv/data/*" {
+    capabilities = ["create", "read", "update"]
+}
+"#
+        );
+
+        // Canonicalizing a canonical policy does not change it
+        let reparsed = PathPolicy::canonicalize(PathPolicy::parse(&formatted).unwrap());
+        assert_eq!(reparsed, policies);
+        assert_eq!(PathPolicy::format(&reparsed), formatted);
+    }
 }
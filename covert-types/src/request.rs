@@ -1,13 +1,13 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, fmt::Display, net::SocketAddr, str::FromStr, time::Duration};
 
 use bytes::Bytes;
-use http::{Extensions, Method};
-use http_body::Limited;
+use http::{HeaderMap, Method};
+use http_body::{LengthLimitError, Limited};
 use hyper::Body;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::error::ApiError;
+use crate::error::{ApiError, ErrorCode};
 
 #[derive(Debug)]
 pub struct Request {
@@ -23,24 +23,61 @@ pub struct Request {
     pub query_string: String,
     // TODO: don't use this
     pub extensions: http::Extensions,
-    pub params: Vec<String>,
+    /// Captured path parameters as `(name, value)` pairs, in route order.
+    pub params: Vec<(String, String)>,
     pub token: Option<String>,
 
     pub headers: HashMap<String, String>,
 }
 
+/// Header carrying the id of the request. Returned on every response and
+/// honored on requests from trusted proxies.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Id assigned to the request by the outermost server middleware. Attached to
+/// the request extensions and used as the id of the logical [`Request`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestId(pub Uuid);
+
+/// Maximum size in bytes of the body of the request. Attached to the request
+/// extensions by the middleware limiting the body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimit(pub usize);
+
+/// Address of the client on the other end of a TCP connection. Attached to the
+/// request extensions of requests received over TCP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub SocketAddr);
+
+/// Credentials of the process on the other end of a unix socket connection.
+/// Attached to the request extensions of requests received over a unix socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
 /// Operation is an enum that is used to specify the type
 /// of request being made
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Operation {
     // The operations below are called per path
     Create,
     Read,
     Update,
+    Patch,
     Delete,
+    List,
     // The operations below are called globally, the path is less relevant.
     Revoke,
     Renew,
+    // Not a request operation. A policy must grant `sudo` together with the
+    // requested operation to access routes that require sudo.
+    Sudo,
+    // Not a request operation. Denies every operation on the path, whatever
+    // the other policies of the token grant.
+    Deny,
 }
 
 impl FromStr for Operation {
@@ -51,14 +88,160 @@ impl FromStr for Operation {
             "create" => Ok(Self::Create),
             "read" => Ok(Self::Read),
             "update" => Ok(Self::Update),
+            "patch" => Ok(Self::Patch),
             "delete" => Ok(Self::Delete),
+            "list" => Ok(Self::List),
             "revoke" => Ok(Self::Revoke),
             "renew" => Ok(Self::Renew),
+            "sudo" => Ok(Self::Sudo),
+            "deny" => Ok(Self::Deny),
             _ => Err(ApiError::bad_request()),
         }
     }
 }
 
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Self::Create => "create",
+            Self::Read => "read",
+            Self::Update => "update",
+            Self::Patch => "patch",
+            Self::Delete => "delete",
+            Self::List => "list",
+            Self::Revoke => "revoke",
+            Self::Renew => "renew",
+            Self::Sudo => "sudo",
+            Self::Deny => "deny",
+        };
+        write!(f, "{op}")
+    }
+}
+
+/// Header carrying the token of the request. The token can also be sent as
+/// `Authorization: Bearer <token>`, e.g. by clients behind gateways that only
+/// forward the `Authorization` header.
+pub const TOKEN_HEADER: &str = "X-Covert-Token";
+
+/// Header the token of the request was sent in. Attached to the request
+/// extensions of requests carrying a token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenHeader {
+    /// The [`TOKEN_HEADER`].
+    #[default]
+    Covert,
+    /// `Authorization: Bearer <token>`.
+    Authorization,
+}
+
+/// Returns the token of the request and the header it was sent in. Requests
+/// sending different tokens in both headers are rejected, an `Authorization`
+/// header with another scheme than `Bearer` is ignored.
+fn token_from_headers(headers: &HeaderMap) -> Result<Option<(String, TokenHeader)>, ApiError> {
+    let covert = headers
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|token| !token.is_empty());
+    let bearer = headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim())
+        .filter(|token| !token.is_empty());
+
+    match (covert, bearer) {
+        (Some(covert), Some(bearer)) if covert != bearer => Err(ApiError::new(
+            ErrorCode::BadRequest,
+            anyhow::Error::msg(format!(
+                "The `{TOKEN_HEADER}` and `Authorization` headers carry different tokens"
+            )),
+        )),
+        (Some(token), _) => Ok(Some((token.to_string(), TokenHeader::Covert))),
+        (None, Some(token)) => Ok(Some((token.to_string(), TokenHeader::Authorization))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Header used by clients that cannot send arbitrary HTTP methods to tunnel
+/// the method through a `POST` request.
+pub const METHOD_OVERRIDE_HEADER: &str = "X-HTTP-Method-Override";
+
+/// Header used to request that the response is wrapped in a single-use
+/// wrapping token that is valid for the given TTL.
+pub const WRAP_TTL_HEADER: &str = "X-Covert-Wrap-TTL";
+
+/// Header with a client generated key that makes retries of a `create`
+/// request return the result of the first attempt instead of creating a
+/// duplicate.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Max length in bytes of an idempotency key.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Returns the method of the request, taking the [`METHOD_OVERRIDE_HEADER`]
+/// into account for `POST` requests.
+fn effective_method(method: &Method, headers: &HeaderMap) -> Result<Method, ApiError> {
+    if method != Method::POST {
+        return Ok(method.clone());
+    }
+    match headers.get(METHOD_OVERRIDE_HEADER) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| Method::from_bytes(value.trim().to_uppercase().as_bytes()).ok())
+            .ok_or_else(ApiError::bad_request),
+        None => Ok(method.clone()),
+    }
+}
+
+/// Maps the HTTP method to the logical operation.
+fn operation_from_method(method: &Method, query: Option<&str>) -> Result<Operation, ApiError> {
+    match *method {
+        Method::GET if is_list_query(query) => Ok(Operation::List),
+        Method::GET => Ok(Operation::Read),
+        Method::POST => Ok(Operation::Create),
+        Method::PUT => Ok(Operation::Update),
+        Method::PATCH => Ok(Operation::Patch),
+        Method::DELETE => Ok(Operation::Delete),
+        ref method if method.as_str() == "LIST" => Ok(Operation::List),
+        _ => Err(ApiError::bad_request()),
+    }
+}
+
+/// Returns true if the query string contains `list=true`.
+fn is_list_query(query: Option<&str>) -> bool {
+    query.is_some_and(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .any(|(key, val)| key == "list" && val == "true")
+    })
+}
+
+/// Path of the namespace selected with the `X-Covert-Namespace` header, the
+/// root namespace if the header is missing.
+#[must_use]
+pub fn namespace_from_headers(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get("X-Covert-Namespace")
+        .and_then(|namespace| namespace.to_str().ok())
+        .map_or_else(
+            || vec!["root".to_string()],
+            |namespace| {
+                namespace
+                    .trim()
+                    .to_lowercase()
+                    .split('/')
+                    .map(ToString::to_string)
+                    .filter(|ns| !ns.is_empty())
+                    .collect::<Vec<_>>()
+            },
+        )
+}
+
 impl Request {
     /// Create a internal logical request from a http request.
     ///
@@ -66,35 +249,14 @@ impl Request {
     ///
     /// Returns an error if the http request contains unsupported elements that
     /// cannot be converted to the logical request format.
-    pub async fn new(raw: hyper::Request<Limited<Body>>) -> Result<Self, ApiError> {
+    pub async fn new(mut raw: hyper::Request<Limited<Body>>) -> Result<Self, ApiError> {
+        let mut extensions = std::mem::take(raw.extensions_mut());
         let uri = raw.uri().clone();
-        let token = raw
-            .headers()
-            .get("X-Covert-Token")
-            .map(|val| val.to_str().unwrap_or_default())
-            .and_then(|token| {
-                if token.is_empty() {
-                    None
-                } else {
-                    Some(token.to_string())
-                }
-            });
-        let namespace = raw
-            .headers()
-            .get("X-Covert-Namespace")
-            .and_then(|namespace| namespace.to_str().ok())
-            .map_or_else(
-                || vec!["root".to_string()],
-                |namespace| {
-                    namespace
-                        .trim()
-                        .to_lowercase()
-                        .split('/')
-                        .map(ToString::to_string)
-                        .filter(|ns| !ns.is_empty())
-                        .collect::<Vec<_>>()
-                },
-            );
+        let token = token_from_headers(raw.headers())?.map(|(token, header)| {
+            extensions.insert(header);
+            token
+        });
+        let namespace = namespace_from_headers(raw.headers());
         let headers = raw
             .headers()
             .iter()
@@ -106,17 +268,20 @@ impl Request {
             })
             .collect();
 
-        let operation = match *raw.method() {
-            Method::GET => Operation::Read,
-            Method::POST => Operation::Create,
-            Method::PUT => Operation::Update,
-            Method::DELETE => Operation::Delete,
-            _ => return Err(ApiError::bad_request()),
-        };
+        let method = effective_method(raw.method(), raw.headers())?;
+        let operation = operation_from_method(&method, uri.query())?;
 
         let bytes = hyper::body::to_bytes(raw.into_body())
             .await
-            .map_err(|_| ApiError::bad_request())?;
+            .map_err(|err| {
+                match (
+                    err.downcast_ref::<LengthLimitError>(),
+                    extensions.get::<BodyLimit>(),
+                ) {
+                    (Some(_), Some(BodyLimit(limit))) => ApiError::payload_too_large(*limit),
+                    _ => ApiError::bad_request(),
+                }
+            })?;
 
         let mut path = uri.path();
         if path.starts_with("/v1/") {
@@ -124,12 +289,14 @@ impl Request {
         }
 
         Ok(Self {
-            id: Uuid::new_v4(),
+            id: extensions
+                .get::<RequestId>()
+                .map_or_else(Uuid::new_v4, |id| id.0),
             operation,
             namespace,
             query_string: uri.query().unwrap_or_default().to_string(),
             path: path.to_string(),
-            extensions: Extensions::new(),
+            extensions,
             token,
             params: vec![],
             data: bytes,
@@ -141,6 +308,61 @@ impl Request {
         self.operation
     }
 
+    /// The TTL of the wrapping token requested with the [`WRAP_TTL_HEADER`].
+    /// The TTL is either a number of seconds or a duration like `5m`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header is not a valid, non-zero duration.
+    pub fn wrap_ttl(&self) -> Result<Option<Duration>, ApiError> {
+        let Some(value) = self.headers.get(&WRAP_TTL_HEADER.to_lowercase()) else {
+            return Ok(None);
+        };
+        let value = value.trim();
+        let ttl = match value.parse::<u64>() {
+            Ok(secs) => Some(Duration::from_secs(secs)),
+            Err(_) => humantime_serde::re::humantime::parse_duration(value).ok(),
+        };
+        match ttl {
+            Some(ttl) if !ttl.is_zero() => Ok(Some(ttl)),
+            _ => Err(ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg(format!("Invalid wrap TTL `{value}`")),
+            )
+            .with_detail(
+                WRAP_TTL_HEADER,
+                "expected a positive number of seconds or a duration such as `5m`",
+            )),
+        }
+    }
+
+    /// The key set with the [`IDEMPOTENCY_KEY_HEADER`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is empty, too long or contains other than
+    /// visible ASCII characters.
+    pub fn idempotency_key(&self) -> Result<Option<&str>, ApiError> {
+        let Some(key) = self.headers.get(&IDEMPOTENCY_KEY_HEADER.to_lowercase()) else {
+            return Ok(None);
+        };
+        if !key.is_empty()
+            && key.len() <= MAX_IDEMPOTENCY_KEY_LEN
+            && key.bytes().all(|b| b.is_ascii_graphic())
+        {
+            Ok(Some(key))
+        } else {
+            Err(ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg("Invalid idempotency key"),
+            )
+            .with_detail(
+                IDEMPOTENCY_KEY_HEADER,
+                format!("expected 1 to {MAX_IDEMPOTENCY_KEY_LEN} visible ASCII characters"),
+            ))
+        }
+    }
+
     pub fn advance_path(&mut self, prefix: &str) -> bool {
         if !self.path.starts_with(prefix) {
             return false;
@@ -151,3 +373,91 @@ impl Request {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_query() {
+        assert!(is_list_query(Some("list=true")));
+        assert!(is_list_query(Some("version=2&list=true")));
+        assert!(!is_list_query(Some("list=false")));
+        assert!(!is_list_query(Some("list")));
+        assert!(!is_list_query(None));
+    }
+
+    #[test]
+    fn method_override() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            effective_method(&Method::POST, &headers).unwrap(),
+            Method::POST
+        );
+
+        headers.insert(METHOD_OVERRIDE_HEADER, "patch".parse().unwrap());
+        assert_eq!(
+            effective_method(&Method::POST, &headers).unwrap(),
+            Method::PATCH
+        );
+        // Only POST requests can be overridden
+        assert_eq!(
+            effective_method(&Method::GET, &headers).unwrap(),
+            Method::GET
+        );
+
+        headers.insert(METHOD_OVERRIDE_HEADER, "LIST".parse().unwrap());
+        let method = effective_method(&Method::POST, &headers).unwrap();
+        assert_eq!(
+            operation_from_method(&method, None).unwrap(),
+            Operation::List
+        );
+    }
+
+    #[test]
+    fn patch_method() {
+        assert_eq!(
+            operation_from_method(&Method::PATCH, None).unwrap(),
+            Operation::Patch
+        );
+        assert!(operation_from_method(&Method::OPTIONS, None).is_err());
+    }
+
+    #[test]
+    fn token_headers() {
+        let headers = |pairs: &[(&str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(
+                    name.parse::<http::header::HeaderName>().unwrap(),
+                    value.parse().unwrap(),
+                );
+            }
+            headers
+        };
+        let token = |pairs: &[(&str, &str)]| token_from_headers(&headers(pairs));
+
+        assert_eq!(token(&[]).unwrap(), None);
+        assert_eq!(
+            token(&[(TOKEN_HEADER, "s.abc")]).unwrap(),
+            Some(("s.abc".to_string(), TokenHeader::Covert))
+        );
+        assert_eq!(
+            token(&[("Authorization", "bearer s.abc")]).unwrap(),
+            Some(("s.abc".to_string(), TokenHeader::Authorization))
+        );
+        assert_eq!(
+            token(&[(TOKEN_HEADER, "s.abc"), ("Authorization", "Bearer s.abc")]).unwrap(),
+            Some(("s.abc".to_string(), TokenHeader::Covert))
+        );
+        // Other schemes are meant for something else
+        assert_eq!(
+            token(&[("Authorization", "Basic Zm9vOmJhcg==")]).unwrap(),
+            None
+        );
+        assert_eq!(token(&[("Authorization", "Bearer ")]).unwrap(), None);
+
+        let err = token(&[(TOKEN_HEADER, "s.abc"), ("Authorization", "Bearer s.def")]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::BadRequest);
+    }
+}
@@ -1,11 +1,15 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    time::Duration,
+};
 
-use http::StatusCode;
+use bytes::Bytes;
+use futures::stream::BoxStream;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
-use tracing_error::SpanTrace;
 
-use crate::error::ApiError;
+use crate::error::{ApiError, ErrorCode};
 
 /// Response from the backend
 #[derive(Debug, Serialize)]
@@ -19,6 +23,35 @@ pub enum Response {
     /// Register a lease for the payload. Useful for returning dynamic
     /// secrets that can be revoked and renewed.
     Lease(LeaseResponse),
+    /// Binary payload returned as is to the client with its own content type.
+    #[serde(skip)]
+    Bytes(RawResponse),
+    /// Binary payload streamed to the client without being buffered.
+    #[serde(skip)]
+    Stream(StreamResponse),
+}
+
+#[derive(Debug)]
+pub struct RawResponse {
+    pub content_type: String,
+    pub body: Bytes,
+}
+
+pub struct StreamResponse {
+    pub content_type: String,
+    /// Length of the body if known up front. Returned as the content length
+    /// of the response.
+    pub length: Option<u64>,
+    pub body: BoxStream<'static, Result<Bytes, std::io::Error>>,
+}
+
+impl Debug for StreamResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamResponse")
+            .field("content_type", &self.content_type)
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
 }
 
 // TODO: add renew fields as well
@@ -29,6 +62,14 @@ pub struct LeaseResponse {
     pub data: Value,
     #[serde(with = "humantime_serde")]
     pub ttl: Option<Duration>,
+    /// Max TTL of the role the lease is issued for. The lease can not be
+    /// renewed past it.
+    #[serde(with = "humantime_serde")]
+    pub max_ttl: Option<Duration>,
+    /// Tags to attach to the lease, e.g. the role the secret was issued for.
+    /// They are merged with the metadata of the entity requesting the secret
+    /// and take precedence over it.
+    pub tags: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -42,6 +83,22 @@ pub struct AuthResponse {
     pub alias: String,
     #[serde(with = "humantime_serde")]
     pub ttl: Option<Duration>,
+    /// Alias metadata describing the authenticated user, e.g. the username or
+    /// role name. It is stored on the issued token.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Policies granted to the issued token in addition to the policies
+    /// attached to the entity.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policies: Vec<String>,
+    /// Policies mapped from the groups of the user.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub group_policies: Vec<String>,
+    /// Create an entity with the alias attached if no entity has the alias.
+    #[serde(default)]
+    pub create_entity: bool,
+    /// Whether the lease of the issued token can be renewed.
+    pub renewable: bool,
 }
 
 impl Response {
@@ -59,6 +116,27 @@ impl Response {
         serde_json::to_value(data).map(Self::Raw)
     }
 
+    /// Construct a binary response with the given content type.
+    pub fn bytes(content_type: impl Into<String>, body: impl Into<Bytes>) -> Self {
+        Self::Bytes(RawResponse {
+            content_type: content_type.into(),
+            body: body.into(),
+        })
+    }
+
+    /// Construct a response that streams the body to the client.
+    pub fn stream(
+        content_type: impl Into<String>,
+        length: Option<u64>,
+        body: BoxStream<'static, Result<Bytes, std::io::Error>>,
+    ) -> Self {
+        Self::Stream(StreamResponse {
+            content_type: content_type.into(),
+            length,
+            body,
+        })
+    }
+
     /// Try to deserialize the raw data payload from the response.
     ///
     /// # Errors
@@ -67,21 +145,20 @@ impl Response {
     /// response is not a raw payload.
     pub fn data<T: DeserializeOwned>(self) -> Result<T, ApiError> {
         match self {
-            Response::Raw(data) => serde_json::from_value(data).map_err(|err| ApiError {
-                error: err.into(),
-                status_code: StatusCode::BAD_REQUEST,
-                span_trace: Some(SpanTrace::capture()),
-            }),
-            Response::Auth(_data) => Err(ApiError {
-                error: anyhow::Error::msg("expected raw data, found auth data"),
-                status_code: StatusCode::BAD_REQUEST,
-                span_trace: Some(SpanTrace::capture()),
-            }),
-            Response::Lease(_data) => Err(ApiError {
-                error: anyhow::Error::msg("expected raw data, found lease data"),
-                status_code: StatusCode::BAD_REQUEST,
-                span_trace: Some(SpanTrace::capture()),
-            }),
+            Response::Raw(data) => serde_json::from_value(data)
+                .map_err(|err| ApiError::new(ErrorCode::BadRequest, err.into())),
+            Response::Auth(_data) => Err(ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg("expected raw data, found auth data"),
+            )),
+            Response::Lease(_data) => Err(ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg("expected raw data, found lease data"),
+            )),
+            Response::Bytes(_) | Response::Stream(_) => Err(ApiError::new(
+                ErrorCode::BadRequest,
+                anyhow::Error::msg("expected raw data, found binary data"),
+            )),
         }
     }
 }
@@ -9,17 +9,27 @@ const TOKEN_LENGTH: usize = 24;
 
 enum TokenType {
     Service,
+    Wrapping,
 }
 
 impl TokenType {
     pub fn prefix(&self) -> &'static str {
         match self {
             TokenType::Service => "s",
+            TokenType::Wrapping => "w",
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn random_token(token_type: &TokenType) -> String {
+    let mut rng = thread_rng();
+    let chars: String = (0..TOKEN_LENGTH)
+        .map(|_| rng.sample(Alphanumeric) as char)
+        .collect();
+    format!("{}.{chars}", token_type.prefix())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Token(String);
 
 impl FromStr for Token {
@@ -38,12 +48,7 @@ impl FromStr for Token {
 impl Token {
     #[must_use]
     pub fn new() -> Self {
-        let mut rng = thread_rng();
-        let chars: String = (0..TOKEN_LENGTH)
-            .map(|_| rng.sample(Alphanumeric) as char)
-            .collect();
-        let token = format!("{}.{chars}", TokenType::Service.prefix());
-        Self(token)
+        Self(random_token(&TokenType::Service))
     }
 
     // Not using the ToString/Display trait to prevent accidental leaks
@@ -59,3 +64,40 @@ impl Default for Token {
         Self::new()
     }
 }
+
+/// Single-use token that gives access to a wrapped response. It cannot be
+/// used to authenticate requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WrappingToken(String);
+
+impl FromStr for WrappingToken {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(TokenType::Wrapping.prefix()) {
+            Ok(Self(s.to_string()))
+        } else {
+            Err(ApiError::bad_request())
+        }
+    }
+}
+
+impl WrappingToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(random_token(&TokenType::Wrapping))
+    }
+
+    // Not using the ToString/Display trait to prevent accidental leaks
+    #[allow(clippy::inherent_to_string)]
+    #[must_use]
+    pub fn to_string(&self) -> String {
+        self.0.clone()
+    }
+}
+
+impl Default for WrappingToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,24 +2,46 @@ use chrono::{DateTime, Duration, Utc};
 
 use crate::mount::MountConfig;
 
-/// Calculate a new TTL
+/// Upper bounds on the lifetime of a lease besides the max lease TTL of the
+/// mount it is issued from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TtlLimits {
+    /// Max TTL of any lease issued by the server.
+    pub system_max: Option<std::time::Duration>,
+    /// Max TTL of the role the lease is issued for.
+    pub role_max: Option<std::time::Duration>,
+}
+
+/// Compute the TTL of a lease that is issued or renewed at `now`.
+///
+/// The requested TTL defaults to the default lease TTL of the mount. The
+/// lease can never outlive `issued_at` plus the smallest of the role, mount
+/// and system max TTL, so the effective TTL is
+/// `min(requested, role max, mount max, system max)` measured from when the
+/// lease was issued. Every lease issued or renewed must go through this
+/// function.
 ///
 /// # Errors
 ///
 /// Returns error if it fails to covert any time to live parameter to either
 /// [`chrono::Duration`] or [`std::time::Duration`].
-pub fn calculate_ttl(
+pub fn compute_ttl(
     now: DateTime<Utc>,
     issued_at: DateTime<Utc>,
     mount_config: &MountConfig,
-    ttl: Option<std::time::Duration>,
+    requested: Option<std::time::Duration>,
+    limits: &TtlLimits,
 ) -> Result<Duration, String> {
-    let ttl = ttl.unwrap_or(mount_config.default_lease_ttl);
+    let ttl = requested.unwrap_or(mount_config.default_lease_ttl);
 
     let ttl = Duration::from_std(ttl).map_err(|_| "Unable to create TTL from renew response")?;
 
-    let max_lease_ttl = Duration::from_std(mount_config.max_lease_ttl)
-        .map_err(|_| "Unable to create max lease TTL from mount")?;
+    let max_lease_ttl = [limits.role_max, limits.system_max]
+        .into_iter()
+        .flatten()
+        .fold(mount_config.max_lease_ttl, std::cmp::Ord::min);
+    let max_lease_ttl =
+        Duration::from_std(max_lease_ttl).map_err(|_| "Unable to create max lease TTL")?;
 
     let max_expires_at = issued_at + max_lease_ttl;
     let new_expires_at = now + ttl;
@@ -40,15 +62,19 @@ pub fn calculate_ttl(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::mount::ListingVisibility;
+
     use super::*;
 
-    fn calculate_ttl_std(
+    fn compute_ttl_std(
         now: DateTime<Utc>,
         issued_at: DateTime<Utc>,
         mount_config: &MountConfig,
         ttl: Option<std::time::Duration>,
     ) -> std::time::Duration {
-        calculate_ttl(now, issued_at, mount_config, ttl)
+        compute_ttl(now, issued_at, mount_config, ttl, &TtlLimits::default())
             .unwrap()
             .to_std()
             .unwrap()
@@ -59,6 +85,13 @@ mod tests {
         let mount_config = MountConfig {
             default_lease_ttl: std::time::Duration::from_secs(30),
             max_lease_ttl: std::time::Duration::from_secs(3600),
+            require_mfa: false,
+            max_request_body_size: None,
+            max_concurrent_requests: None,
+            plugin: None,
+            listing_visibility: ListingVisibility::default(),
+            description: None,
+            encrypted_fields: BTreeMap::new(),
         };
 
         let mut now = Utc::now();
@@ -66,13 +99,13 @@ mod tests {
 
         // Default to the mount default lease ttl
         assert_eq!(
-            calculate_ttl_std(now, issued_at, &mount_config, None),
+            compute_ttl_std(now, issued_at, &mount_config, None),
             mount_config.default_lease_ttl
         );
 
         // Explicit ttl
         assert_eq!(
-            calculate_ttl_std(
+            compute_ttl_std(
                 now,
                 issued_at,
                 &mount_config,
@@ -83,7 +116,7 @@ mod tests {
 
         // Is capped at mount max lease ttl
         assert_eq!(
-            calculate_ttl_std(
+            compute_ttl_std(
                 now,
                 issued_at,
                 &mount_config,
@@ -95,7 +128,7 @@ mod tests {
         // Is capped at mount max lease ttl, when default to default mount lease ttl
         now += Duration::from_std(mount_config.max_lease_ttl).unwrap();
         assert_eq!(
-            calculate_ttl_std(now, issued_at, &mount_config, None),
+            compute_ttl_std(now, issued_at, &mount_config, None),
             std::time::Duration::ZERO
         );
 
@@ -105,7 +138,7 @@ mod tests {
             issued_at + Duration::from_std(mount_config.max_lease_ttl).unwrap()
         );
         assert_eq!(
-            calculate_ttl_std(
+            compute_ttl_std(
                 now,
                 issued_at,
                 &mount_config,
@@ -114,7 +147,7 @@ mod tests {
             std::time::Duration::ZERO
         );
         assert_eq!(
-            calculate_ttl_std(now, issued_at, &mount_config, None),
+            compute_ttl_std(now, issued_at, &mount_config, None),
             std::time::Duration::ZERO
         );
 
@@ -122,7 +155,7 @@ mod tests {
         now += Duration::minutes(5);
         assert!(now > issued_at + Duration::from_std(mount_config.max_lease_ttl).unwrap());
         assert_eq!(
-            calculate_ttl_std(
+            compute_ttl_std(
                 now,
                 issued_at,
                 &mount_config,
@@ -131,8 +164,80 @@ mod tests {
             std::time::Duration::ZERO
         );
         assert_eq!(
-            calculate_ttl_std(now, issued_at, &mount_config, None),
+            compute_ttl_std(now, issued_at, &mount_config, None),
             std::time::Duration::ZERO
         );
     }
+
+    #[test]
+    fn smallest_max_ttl_wins() {
+        let hours = std::time::Duration::from_hours;
+        let mount_config = MountConfig {
+            default_lease_ttl: hours(10),
+            max_lease_ttl: hours(8),
+            require_mfa: false,
+            max_request_body_size: None,
+            max_concurrent_requests: None,
+            plugin: None,
+            listing_visibility: ListingVisibility::default(),
+            description: None,
+            encrypted_fields: BTreeMap::new(),
+        };
+        let now = Utc::now();
+        let ttl = |requested, system_max, role_max| {
+            compute_ttl(
+                now,
+                now,
+                &mount_config,
+                requested,
+                &TtlLimits {
+                    system_max,
+                    role_max,
+                },
+            )
+            .unwrap()
+            .to_std()
+            .unwrap()
+        };
+
+        // Mount max wins over the requested and default TTL
+        assert_eq!(ttl(None, None, None), hours(8));
+        assert_eq!(ttl(Some(hours(9)), None, None), hours(8));
+        // Role max wins when it is the smallest
+        assert_eq!(
+            ttl(Some(hours(9)), Some(hours(12)), Some(hours(6))),
+            hours(6)
+        );
+        // System max wins when it is the smallest
+        assert_eq!(
+            ttl(Some(hours(9)), Some(hours(4)), Some(hours(6))),
+            hours(4)
+        );
+        // Requested TTL wins when it is the smallest
+        assert_eq!(
+            ttl(Some(hours(2)), Some(hours(4)), Some(hours(6))),
+            hours(2)
+        );
+        // Limits larger than the mount max cannot extend it
+        assert_eq!(
+            ttl(Some(hours(9)), Some(hours(24)), Some(hours(24))),
+            hours(8)
+        );
+
+        // The limits are measured from when the lease was issued
+        let limits = TtlLimits {
+            system_max: Some(hours(4)),
+            role_max: Some(hours(6)),
+        };
+        let renewed_at = now + Duration::hours(3);
+        assert_eq!(
+            compute_ttl(renewed_at, now, &mount_config, Some(hours(2)), &limits).unwrap(),
+            Duration::hours(1)
+        );
+        let renewed_at = now + Duration::hours(5);
+        assert_eq!(
+            compute_ttl(renewed_at, now, &mount_config, Some(hours(2)), &limits).unwrap(),
+            Duration::zero()
+        );
+    }
 }